@@ -0,0 +1,60 @@
+//! Hot paths of the streaming and REST clients : live order book updates, Bittrex hub messages
+//! and Kraken REST responses. Run with `cargo bench`.
+
+#[macro_use]
+extern crate criterion;
+
+mod synthetic;
+
+use criterion::{BatchSize, Criterion, Throughput};
+use serde_json::Value;
+
+use coinnect_rt::bittrex::streaming_api::BittrexStreamingApi;
+use coinnect_rt::kraken::utils;
+use coinnect_rt::types::{LiveAggregatedOrderBook, Pair};
+
+use synthetic::DeltaGenerator;
+
+/// Levels of each side of a delta batch
+const DELTA_LEVELS: usize = 100;
+
+fn live_book_updates(c: &mut Criterion) {
+    let mut group = c.benchmark_group("live_book");
+    group.throughput(Throughput::Elements(2 * DELTA_LEVELS as u64));
+    let mut gen = DeltaGenerator::new(42, 1_000_000, 5_000);
+    group.bench_function("update", |b| {
+        let mut book = LiveAggregatedOrderBook::default(Pair::BTC_USD);
+        let (asks, bids) = gen.deltas(1000);
+        book.reset_asks(asks.into_iter());
+        book.reset_bids(bids.into_iter());
+        b.iter_batched(|| gen.deltas(DELTA_LEVELS), |(asks, bids)| {
+            book.update_asks(asks.into_iter());
+            book.update_bids(bids.into_iter());
+            book.latest_order_book()
+        }, BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+fn bittrex_deflate(c: &mut Criterion) {
+    let mut gen = DeltaGenerator::new(7, 1_000_000, 5_000);
+    let message = gen.bittrex_delta(DELTA_LEVELS);
+    c.bench_function("bittrex_deflate_delta", |b| {
+        b.iter(|| BittrexStreamingApi::deflate::<Value>(&message).unwrap())
+    });
+}
+
+fn kraken_depth(c: &mut Criterion) {
+    let mut gen = DeltaGenerator::new(11, 1_000_000, 50_000);
+    let body = gen.kraken_depth("XXBTZUSD", 1000).to_string();
+    c.bench_function("kraken_parse_depth", |b| {
+        b.iter(|| {
+            let response: serde_json::Map<String, Value> = serde_json::from_str(&body).unwrap();
+            let result = utils::parse_result(&response).unwrap();
+            utils::parse_orderbook(Pair::BTC_USD, &result["XXBTZUSD"]).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, live_book_updates, bittrex_deflate, kraken_depth);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+//! Synthetic market data, so that benchmarks run without network access.
+//! The generator is seeded, two generators with the same seed give the same data.
+
+use std::io::Write;
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use libflate::deflate::Encoder;
+use serde_json::{json, Value};
+
+/// A price level as sent by exchanges
+pub type Level = (BigDecimal, BigDecimal);
+
+/// Order book deltas around a mid price, a tenth of them remove their level
+pub struct DeltaGenerator {
+    state: u64,
+    mid: i64,
+    spread: i64,
+}
+
+impl DeltaGenerator {
+    /// Deltas around `mid` in cents, on `spread` cents on each side
+    pub fn new(seed: u64, mid: i64, spread: i64) -> Self {
+        DeltaGenerator { state: seed.max(1), mid, spread: spread.max(1) }
+    }
+
+    /// xorshift64, good enough for benchmark data
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn level(&mut self, ask: bool) -> Level {
+        let offset = 1 + (self.next_u64() % self.spread as u64) as i64;
+        let cents = if ask { self.mid + offset } else { self.mid - offset };
+        let volume = if self.next_u64() % 10 == 0 { 0 } else { 1 + self.next_u64() % 100_000 };
+        (BigDecimal::new(cents.into(), 2), BigDecimal::new(volume.into(), 4))
+    }
+
+    /// `n` ask and `n` bid deltas
+    pub fn deltas(&mut self, n: usize) -> (Vec<Level>, Vec<Level>) {
+        let asks = (0..n).map(|_| self.level(true)).collect();
+        let bids = (0..n).map(|_| self.level(false)).collect();
+        (asks, bids)
+    }
+
+    /// A Bittrex exchange delta of `n` levels per side, deflated and base64 encoded like the hub
+    /// sends them
+    pub fn bittrex_delta(&mut self, n: usize) -> String {
+        let (asks, bids) = self.deltas(n);
+        let logs = |levels: Vec<Level>| -> Vec<Value> {
+            levels.into_iter()
+                .map(|(rate, quantity)| json!({"TY": if quantity == BigDecimal::from(0) { 1 } else { 2 }, "R": rate.to_f64(), "Q": quantity.to_f64()}))
+                .collect()
+        };
+        let message = json!({"M": "USD-BTC", "N": self.next_u64() % 1_000_000, "Z": logs(bids), "S": logs(asks), "f": []});
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(message.to_string().as_bytes()).unwrap();
+        base64::encode(&encoder.finish().into_result().unwrap())
+    }
+
+    /// A response of the Kraken Depth endpoint for `pair`, with `n` levels per side
+    pub fn kraken_depth(&mut self, pair: &str, n: usize) -> Value {
+        let side = |gen: &mut Self, ask: bool| -> Vec<Value> {
+            (0..n).map(|_| {
+                let (price, volume) = gen.level(ask);
+                json!([price.to_string(), volume.to_string(), 1_580_000_000 + gen.next_u64() % 1000])
+            }).collect()
+        };
+        let asks = side(self, true);
+        let bids = side(self, false);
+        json!({"error": [], "result": {pair: {"asks": asks, "bids": bids}}})
+    }
+}
@@ -0,0 +1,46 @@
+// This example polls the tickers of a pair on several exchanges and prints the best bid and ask
+// of each venue, along with the spread between the best bid and the best ask across venues.
+// Run it with `cargo run --example cross_exchange_monitor --features examples`
+
+use std::time::Duration;
+
+use coinnect_rt::coinnect::Coinnect;
+use coinnect_rt::env_credentials::{EnvCredentials, DEFAULT_PREFIX};
+use coinnect_rt::exchange::{Exchange, ExchangeApi};
+use coinnect_rt::types::{Pair, Price};
+
+const EXCHANGES: [Exchange; 3] = [Exchange::Bitstamp, Exchange::Kraken, Exchange::Gdax];
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let pair = Pair::BTC_EUR;
+    // Keys are optional, tickers are public
+    let mut apis: Vec<(Exchange, Box<dyn ExchangeApi>)> = vec![];
+    for &exchange in EXCHANGES.iter() {
+        apis.push((exchange, Coinnect::new(exchange, EnvCredentials::new(DEFAULT_PREFIX, exchange))?));
+    }
+
+    loop {
+        let mut best_bid: Option<(Exchange, Price)> = None;
+        let mut best_ask: Option<(Exchange, Price)> = None;
+        for (exchange, api) in apis.iter() {
+            match api.ticker(pair).await {
+                Ok(ticker) => {
+                    println!("{:?} {:?} : bid {} ask {}", exchange, pair, ticker.highest_bid, ticker.lowest_ask);
+                    if best_bid.as_ref().map_or(true, |(_, bid)| &ticker.highest_bid > bid) {
+                        best_bid = Some((*exchange, ticker.highest_bid.clone()));
+                    }
+                    if best_ask.as_ref().map_or(true, |(_, ask)| &ticker.lowest_ask < ask) {
+                        best_ask = Some((*exchange, ticker.lowest_ask.clone()));
+                    }
+                }
+                Err(e) => println!("{:?} {:?} : {}", exchange, pair, e),
+            }
+        }
+        if let (Some((bid_exchange, bid)), Some((ask_exchange, ask))) = (best_bid, best_ask) {
+            println!("Best bid on {:?}, best ask on {:?}, cross venue spread {}", bid_exchange, ask_exchange, ask - bid);
+        }
+        tokio::time::delay_for(POLL_INTERVAL).await;
+    }
+}
@@ -25,7 +25,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // You could use a simple unwrap() or use match to recover from an error for example
     let pairs_request = match my_api.get_tradable_asset_pairs("", "") {
         Ok(pairs_request) => pairs_request,
-        Err(err) => panic!("Error : {:?}, description : {}", err, err.description()),
+        Err(err) => panic!("Error : {:?}, description : {}", err, err),
     };
     let list_all_pairs = pairs_request.get("result").unwrap().as_object().unwrap();
 
@@ -119,6 +119,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                       "", // oflags (see doc)
                                       "", // starttm
                                       "", // expiretm
+                                      "", // timeinforce
                                       "", // userref
                                       ""); // validate
     // In a real case example, you should check if any error occurs.
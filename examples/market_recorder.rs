@@ -0,0 +1,42 @@
+// This example records the live order books and trades of Bitstamp into rotated JSON lines files,
+// which can be replayed later with `ReplayBot`.
+// Run it with `cargo run --example market_recorder --features examples -- <directory>`
+
+use std::path::PathBuf;
+
+use actix::Actor;
+use actix_rt::System;
+
+use coinnect_rt::bitstamp::BitstampCreds;
+use coinnect_rt::codec::EventFormat;
+use coinnect_rt::coinnect::Coinnect;
+use coinnect_rt::exchange::{Exchange, ExchangeSettings};
+use coinnect_rt::recorder::{RecorderActor, RecorderSettings, Rotation};
+use coinnect_rt::types::Pair;
+
+fn main() {
+    let dir = std::env::args().nth(1).map(PathBuf::from).unwrap_or_else(|| PathBuf::from("recordings"));
+    let sys = System::new("market-recorder");
+    actix_rt::spawn(async move {
+        let settings = RecorderSettings {
+            dir,
+            prefix: "bitstamp".to_string(),
+            format: EventFormat::JsonLines,
+            rotation: Rotation { max_bytes: Some(100 * 1024 * 1024), max_age_secs: Some(3600) },
+        };
+        let recorder = RecorderActor::new(settings).unwrap().start();
+
+        let exchange_settings = ExchangeSettings::builder()
+            .exchange(Exchange::Bitstamp)
+            .orderbook(vec![Pair::BTC_USD])
+            .trades(vec![Pair::BTC_USD])
+            .build()
+            .unwrap();
+        // Public feeds do not need any key
+        let creds = BitstampCreds::new("recorder", "", "", "");
+        let _bot = Coinnect::new_stream(Exchange::Bitstamp, Box::new(creds), exchange_settings, vec![recorder.recipient()]).await.unwrap();
+        // Keep the bot alive
+        futures::future::pending::<()>().await;
+    });
+    sys.run().unwrap();
+}
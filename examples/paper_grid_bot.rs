@@ -0,0 +1,65 @@
+// This example runs a basic grid bot on the simulated exchange, fed with the live Bitstamp order
+// book. Every time the mid price crosses a grid level downwards the bot buys, every time it
+// crosses one upwards it sells back.
+// Run it with `cargo run --example paper_grid_bot --features examples`
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use actix_rt::System;
+use bigdecimal::BigDecimal;
+
+use coinnect_rt::bitstamp::BitstampCreds;
+use coinnect_rt::coinnect::Coinnect;
+use coinnect_rt::exchange::{Exchange, ExchangeApi, ExchangeSettings};
+use coinnect_rt::simulated::{SimulatedApi, SimulatedCreds};
+use coinnect_rt::spread::bbo;
+use coinnect_rt::types::{OrderType, Pair, Price};
+
+const PAIR: Pair = Pair::BTC_USD;
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Index of the grid level right below `price`
+fn grid_level(price: &Price, origin: &Price, step: &Price) -> i64 {
+    let level = (price.clone() - origin.clone()) / step.clone();
+    level.to_string().parse::<f64>().map(|l| l.floor() as i64).unwrap_or(0)
+}
+
+fn main() {
+    let sys = System::new("paper-grid-bot");
+    actix_rt::spawn(async move {
+        let creds = SimulatedCreds::new("grid", r#"{"USD": "10000", "BTC": "0"}"#, "0.0025", "0");
+        let api = SimulatedApi::new(creds).unwrap();
+
+        let settings = ExchangeSettings::builder().exchange(Exchange::Bitstamp).orderbook(vec![PAIR]).build().unwrap();
+        let _bot = Coinnect::new_stream(Exchange::Bitstamp, Box::new(BitstampCreds::new("grid", "", "", "")), settings, vec![api.book_feed().recipient()]).await.unwrap();
+
+        let step = BigDecimal::from(50);
+        let quantity = BigDecimal::from_str("0.01").unwrap();
+        let mut origin: Option<Price> = None;
+        let mut last_level = 0;
+        loop {
+            tokio::time::delay_for(CHECK_INTERVAL).await;
+            let mid = match api.orderbook(PAIR).await.ok().as_ref().and_then(bbo) {
+                Some((bid, ask)) => (bid + ask) / BigDecimal::from(2),
+                None => continue,
+            };
+            let origin = origin.get_or_insert_with(|| mid.clone());
+            let level = grid_level(&mid, origin, &step);
+            let order_type = if level < last_level {
+                OrderType::BuyMarket
+            } else if level > last_level {
+                OrderType::SellMarket
+            } else {
+                continue;
+            };
+            match api.add_order(order_type, PAIR, quantity.clone(), None).await {
+                Ok(info) => println!("{:?} at mid {} : {:?}", order_type, mid, info.identifier),
+                Err(e) => println!("{:?} at mid {} failed : {}", order_type, mid, e),
+            }
+            last_level = level;
+            println!("Balances : {:?}", api.balances().await);
+        }
+    });
+    sys.run().unwrap();
+}
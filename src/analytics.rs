@@ -0,0 +1,202 @@
+//! Rolling statistics of live prices.
+//! `StatsActor` listens to live trades or closed candles and maintains, for every pair of every
+//! venue, the simple and exponential moving averages, the rolling high and low and the realized
+//! volatility of the prices over the last `window` samples. The statistics are queried with the
+//! `GetStats` and `GetAllStats` messages.
+
+use std::collections::{HashMap, VecDeque};
+
+use actix::{Actor, Context, Handler, MessageResult};
+use bigdecimal::{BigDecimal, ToPrimitive};
+
+use crate::exchange::Exchange;
+use crate::types::*;
+use crate::utils;
+
+/// Digits kept by the exponential moving average, repeated products would grow them forever
+const EMA_SCALE: i64 = 12;
+
+/// Events sampled by a `StatsActor`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum StatsSource {
+    /// Every trade is a sample at its price
+    Trades,
+    /// Every closed candle is a sample at its close, with its high and low
+    Candles,
+}
+
+/// Statistics of a pair over the last samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairStats {
+    pub exchange: Exchange,
+    pub pair: Pair,
+    /// UNIX timestamp in ms of the last sample
+    pub timestamp: i64,
+    pub last: Price,
+    /// Simple moving average over the window
+    pub sma: Price,
+    /// Exponential moving average, with a smoothing of 2 / (window + 1)
+    pub ema: Price,
+    /// Highest price over the window
+    pub high: Price,
+    /// Lowest price over the window
+    pub low: Price,
+    /// Standard deviation of the log returns between samples, `None` before three samples
+    pub volatility: Option<f64>,
+    /// Number of samples in the window
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Sample {
+    price: Price,
+    high: Price,
+    low: Price,
+}
+
+#[derive(Debug, Default)]
+struct RollingWindow {
+    samples: VecDeque<Sample>,
+    ema: Option<Price>,
+    timestamp: i64,
+}
+
+impl RollingWindow {
+    fn push(&mut self, timestamp: i64, sample: Sample, window: usize) {
+        let alpha = BigDecimal::from(2) / BigDecimal::from(window as i64 + 1);
+        let ema = match self.ema.take() {
+            Some(ema) => (&alpha * &sample.price + (BigDecimal::from(1) - &alpha) * ema).with_scale(EMA_SCALE),
+            None => sample.price.clone(),
+        };
+        self.ema = Some(ema);
+        self.timestamp = timestamp;
+        if self.samples.len() == window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    fn volatility(&self) -> Option<f64> {
+        let prices: Vec<f64> = self.samples.iter().filter_map(|s| s.price.to_f64()).filter(|p| *p > 0.0).collect();
+        if prices.len() < 3 {
+            return None;
+        }
+        let returns: Vec<f64> = prices.windows(2).map(|w| (w[1] / w[0]).ln()).collect();
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+        Some(variance.sqrt())
+    }
+
+    fn stats(&self, exchange: Exchange, pair: Pair) -> Option<PairStats> {
+        let last = self.samples.back()?;
+        let sum = self.samples.iter().fold(BigDecimal::from(0), |acc, s| acc + &s.price);
+        Some(PairStats {
+            exchange,
+            pair,
+            timestamp: self.timestamp,
+            last: last.price.clone(),
+            sma: sum / BigDecimal::from(self.samples.len() as i64),
+            ema: self.ema.clone()?,
+            high: self.samples.iter().map(|s| &s.high).max()?.clone(),
+            low: self.samples.iter().map(|s| &s.low).min()?.clone(),
+            volatility: self.volatility(),
+            samples: self.samples.len(),
+        })
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Option<PairStats>")]
+pub struct GetStats {
+    pub exchange: Exchange,
+    pub pair: Pair,
+}
+
+#[derive(Message)]
+#[rtype(result = "Vec<PairStats>")]
+pub struct GetAllStats;
+
+/// Actor keeping the rolling statistics of every pair it receives samples of
+pub struct StatsActor {
+    source: StatsSource,
+    window: usize,
+    windows: HashMap<(Exchange, Pair), RollingWindow>,
+}
+
+impl StatsActor {
+    /// Statistics over the last `window` samples taken from `source`
+    pub fn new(source: StatsSource, window: usize) -> Self {
+        StatsActor { source, window: window.max(1), windows: HashMap::new() }
+    }
+
+    fn sample(&mut self, exchange: Exchange, le: &LiveEvent) {
+        let (pair, timestamp, sample) = match (self.source, le) {
+            (StatsSource::Trades, LiveEvent::LiveTrade(t)) => {
+                (t.pair, t.event_ms, Sample { price: t.price.clone(), high: t.price.clone(), low: t.price.clone() })
+            }
+            (StatsSource::Candles, LiveEvent::LiveCandle(c)) if c.closed => match utils::live_pair(exchange, &c.pair) {
+                Some(pair) => (pair, c.event_ms, Sample { price: c.close.clone(), high: c.high.clone(), low: c.low.clone() }),
+                None => return,
+            },
+            _ => return,
+        };
+        let window = self.window;
+        self.windows.entry((exchange, pair)).or_default().push(timestamp, sample, window);
+    }
+}
+
+impl Actor for StatsActor {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for StatsActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        self.sample(msg.0, &msg.1);
+    }
+}
+
+impl Handler<GetStats> for StatsActor {
+    type Result = MessageResult<GetStats>;
+
+    fn handle(&mut self, msg: GetStats, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.windows.get(&(msg.exchange, msg.pair)).and_then(|w| w.stats(msg.exchange, msg.pair)))
+    }
+}
+
+impl Handler<GetAllStats> for StatsActor {
+    type Result = MessageResult<GetAllStats>;
+
+    fn handle(&mut self, _msg: GetAllStats, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.windows.iter().filter_map(|((e, p), w)| w.stats(*e, *p)).collect())
+    }
+}
+
+#[cfg(test)]
+mod analytics_tests {
+    use super::*;
+
+    fn trade(event_ms: i64, price: i32) -> LiveEvent {
+        LiveEvent::LiveTrade(LiveTrade { event_ms, pair: Pair::BTC_USD, amount: BigDecimal::from(1), price: BigDecimal::from(price), tt: TradeType::Buy, meta: TradeMeta::default() })
+    }
+
+    #[test]
+    fn rolling_stats_over_the_window() {
+        let mut actor = StatsActor::new(StatsSource::Trades, 3);
+        for (i, price) in [100, 110, 90, 120].iter().enumerate() {
+            actor.sample(Exchange::Binance, &trade(i as i64, *price));
+        }
+        let stats = actor.windows[&(Exchange::Binance, Pair::BTC_USD)].stats(Exchange::Binance, Pair::BTC_USD).unwrap();
+        assert_eq!(stats.samples, 3);
+        assert_eq!(stats.sma, BigDecimal::from(320) / BigDecimal::from(3));
+        assert_eq!(stats.high, BigDecimal::from(120));
+        assert_eq!(stats.low, BigDecimal::from(90));
+        // 100, then 105, 97.5 and 108.75 with a smoothing of 0.5
+        assert_eq!(stats.ema, "108.75".parse::<BigDecimal>().unwrap());
+        assert!(stats.volatility.map_or(false, |v| v > 0.0));
+        // Other events are not samples
+        actor.sample(Exchange::Binance, &LiveEvent::Noop);
+        assert_eq!(actor.windows.len(), 1);
+    }
+}
@@ -0,0 +1,369 @@
+//! Helpers to run multi-leg strategies on a single exchange.
+//! A triangular arbitrage is made of three legs that must all be filled for the position to be
+//! flat at the end. Legs are pre-checked and rounded before anything is sent, then submitted one
+//! after the other as immediate or cancel orders. If a leg is rejected or only partly filled, the
+//! quantities already filled are hedged back with market orders so that the account does not keep
+//! a residual exposure. A leg whose filled quantity is unknown stops the arbitrage unhedged, the
+//! exposure is then to be read from the balances.
+//!
+//! Cross-exchange opportunities are estimated with `PnlEstimator`, which accounts for the taker
+//! fees on both legs, the withdrawal fee of the transfer and a haircut for the price risk taken
+//! while the funds are moving.
+
+use std::collections::HashMap;
+
+use bigdecimal::{BigDecimal, One, Zero};
+
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeApi};
+use crate::types::*;
+
+#[derive(Debug, Clone)]
+pub struct Leg {
+    /// The Pair traded by this leg
+    pub pair: Pair,
+    /// Must be a limit order type, the price acts as the worst acceptable price
+    pub order_type: OrderType,
+    /// Quantity in quote currency, see `ExchangeApi::add_order`
+    pub quantity: Volume,
+    /// Limit price of the leg
+    pub price: Price,
+}
+
+impl Leg {
+    pub fn new(pair: Pair, order_type: OrderType, quantity: Volume, price: Price) -> Self {
+        Leg { pair, order_type, quantity, price }
+    }
+
+    /// The market order that cancels out the exposure created by this leg
+    fn hedge_type(&self) -> OrderType {
+        match self.order_type.side() {
+            Side::Buy => OrderType::SellMarket,
+            Side::Sell => OrderType::BuyMarket,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TriangularArbitrage {
+    pub legs: [Leg; 3],
+    /// Number of decimals accepted by the exchange for quantities
+    pub quantity_scale: i64,
+    /// Number of decimals accepted by the exchange for prices
+    pub price_scale: i64,
+}
+
+#[derive(Debug)]
+pub enum ArbitrageOutcome {
+    /// All three legs were accepted by the exchange
+    Filled(Vec<OrderInfo>),
+    /// A leg failed or was partly filled, the filled quantities were hedged with the returned orders
+    Hedged { failed_leg: usize, reason: String, hedges: Vec<OrderInfo> },
+    /// The exchange accepted the order of `leg` but its filled quantity is unknown, nothing was
+    /// hedged. `orders` are the orders of the legs up to `leg` included.
+    Unsettled { leg: usize, orders: Vec<OrderInfo> },
+}
+
+impl TriangularArbitrage {
+    pub fn new(legs: [Leg; 3], quantity_scale: i64, price_scale: i64) -> Self {
+        TriangularArbitrage { legs, quantity_scale, price_scale }
+    }
+
+    /// Round every leg to the exchange precision and make sure it can be submitted.
+    /// Nothing is sent to the exchange if one of the legs is invalid.
+    pub fn checked_legs(&self) -> Result<Vec<Leg>> {
+        let mut legs = Vec::with_capacity(self.legs.len());
+        for leg in self.legs.iter() {
+            match leg.order_type {
+                OrderType::BuyLimit | OrderType::SellLimit => (),
+                _ => return Err(Error::InvalidArguments),
+            }
+            let quantity = leg.quantity.with_scale(self.quantity_scale);
+            let price = leg.price.with_scale(self.price_scale);
+            if quantity <= BigDecimal::zero() {
+                return Err(Error::InsufficientOrderSize);
+            }
+            if price <= BigDecimal::zero() {
+                return Err(Error::MissingPrice);
+            }
+            legs.push(Leg::new(leg.pair, leg.order_type, quantity, price));
+        }
+        Ok(legs)
+    }
+
+    /// Submit the three legs in order as immediate or cancel orders. A leg that is not fully
+    /// filled stops the arbitrage, and the quantities filled so far are hedged in reverse order.
+    /// A leg whose filled quantity is unknown stops it with `ArbitrageOutcome::Unsettled`.
+    pub async fn execute(&self, api: &dyn ExchangeApi) -> Result<ArbitrageOutcome> {
+        let legs = self.checked_legs()?;
+        let mut filled: Vec<(Leg, OrderInfo)> = Vec::with_capacity(legs.len());
+        for (i, leg) in legs.into_iter().enumerate() {
+            let reason = match api.add_ioc_order(leg.order_type, leg.pair, leg.quantity.clone(), leg.price.clone()).await {
+                Ok(Execution { order, filled: None }) => {
+                    warn!("Arbitrage : fill of leg {} on {:?} unknown, stopping without hedging", i, leg.pair);
+                    let mut orders: Vec<OrderInfo> = filled.into_iter().map(|(_, info)| info).collect();
+                    orders.push(order);
+                    return Ok(ArbitrageOutcome::Unsettled { leg: i, orders });
+                }
+                Ok(Execution { order, filled: Some(quantity) }) if quantity >= leg.quantity => {
+                    filled.push((leg, order));
+                    continue;
+                }
+                Ok(Execution { order, filled: Some(quantity) }) => {
+                    let reason = format!("filled {} of {}", quantity, leg.quantity);
+                    if quantity > BigDecimal::zero() {
+                        filled.push((Leg::new(leg.pair, leg.order_type, quantity, leg.price.clone()), order));
+                    }
+                    reason
+                }
+                Err(e) => format!("{}", e),
+            };
+            warn!("Arbitrage : leg {} on {:?} failed, hedging {} filled legs : {}", i, leg.pair, filled.len(), reason);
+            let hedges = Self::hedge(api, &filled).await?;
+            return Ok(ArbitrageOutcome::Hedged { failed_leg: i, reason, hedges });
+        }
+        Ok(ArbitrageOutcome::Filled(filled.into_iter().map(|(_, info)| info).collect()))
+    }
+
+    /// Unwind the quantity filled by each leg, the last one first
+    async fn hedge(api: &dyn ExchangeApi, filled: &[(Leg, OrderInfo)]) -> Result<Vec<OrderInfo>> {
+        let mut hedges = Vec::with_capacity(filled.len());
+        for (leg, _) in filled.iter().rev() {
+            let info = api.add_order(leg.hedge_type(), leg.pair, leg.quantity.clone(), None).await
+                .with_context(|| Error::ExchangeSpecificError(format!("unable to hedge leg on {:?}", leg.pair)))?;
+            hedges.push(info);
+        }
+        Ok(hedges)
+    }
+}
+
+/// Costs of trading on and withdrawing from a venue
+#[derive(Debug, Clone, Deserialize)]
+pub struct VenueCosts {
+    /// Taker fee rate, 0.0026 for 0.26%
+    pub taker_fee: BigDecimal,
+    /// Fee charged when withdrawing the base currency, in base currency
+    pub withdrawal_fee: BigDecimal,
+    /// Fraction of the proceeds discounted for the price risk taken while the base currency is
+    /// transferred out of this venue, e.g. 0.001 for a fast chain and 0.01 for a slow one
+    pub transfer_haircut: BigDecimal,
+}
+
+/// Buy on one exchange, transfer, and sell on another
+#[derive(Debug, Clone)]
+pub struct Opportunity {
+    pub pair: Pair,
+    pub buy_on: Exchange,
+    pub sell_on: Exchange,
+    /// Quantity bought, capped by the top of book sizes when known
+    pub quantity: Volume,
+    pub buy_price: Price,
+    pub sell_price: Price,
+    /// Proceeds minus cost before any fee, in quote currency
+    pub gross_pnl: BigDecimal,
+    /// Taker fees, withdrawal fee and transfer haircut, in quote currency
+    pub costs: BigDecimal,
+    pub net_pnl: BigDecimal,
+    /// Net PnL divided by the cost of the buy leg
+    pub net_edge: BigDecimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct PnlEstimator {
+    venues: HashMap<Exchange, VenueCosts>,
+    /// Opportunities with a lower net edge are discarded
+    min_net_edge: BigDecimal,
+}
+
+impl PnlEstimator {
+    pub fn new(venues: HashMap<Exchange, VenueCosts>, min_net_edge: BigDecimal) -> Self {
+        PnlEstimator { venues, min_net_edge }
+    }
+
+    fn costs(&self, exchange: Exchange) -> Result<&VenueCosts> {
+        self.venues.get(&exchange).ok_or_else(|| Error::MissingField(format!("{:?} costs", exchange)))
+    }
+
+    /// Estimate the PnL of buying `quantity` at the ask of `buy` and selling it at the bid of `sell`
+    pub fn estimate(&self, buy: (Exchange, &Ticker), sell: (Exchange, &Ticker), quantity: Volume) -> Result<Opportunity> {
+        let (buy_on, buy_ticker) = buy;
+        let (sell_on, sell_ticker) = sell;
+        if buy_ticker.pair != sell_ticker.pair {
+            return Err(Error::InvalidArguments);
+        }
+        let buy_costs = self.costs(buy_on)?;
+        let sell_costs = self.costs(sell_on)?;
+
+        let quantity = [buy_ticker.ask_size.as_ref(), sell_ticker.bid_size.as_ref()].iter()
+            .filter_map(|s| s.cloned())
+            .fold(quantity, |q, size| if size < q { size } else { q });
+        if quantity <= BigDecimal::zero() {
+            return Err(Error::InsufficientOrderSize);
+        }
+        let buy_price = buy_ticker.lowest_ask.clone();
+        let sell_price = sell_ticker.highest_bid.clone();
+
+        let notional = buy_price.clone() * quantity.clone();
+        let buy_fee = notional.clone() * buy_costs.taker_fee.clone();
+        let received = quantity.clone() - buy_costs.withdrawal_fee.clone();
+        let proceeds = sell_price.clone() * received;
+        let sell_fee = proceeds.clone() * sell_costs.taker_fee.clone();
+        let haircut = (proceeds.clone() - sell_fee.clone()) * buy_costs.transfer_haircut.clone();
+
+        let gross_pnl = (sell_price.clone() - buy_price.clone()) * quantity.clone();
+        let net_pnl = proceeds - sell_fee - haircut - notional.clone() - buy_fee;
+        let costs = gross_pnl.clone() - net_pnl.clone();
+        let net_edge = net_pnl.clone() / (notional * (BigDecimal::one() + buy_costs.taker_fee.clone()));
+
+        Ok(Opportunity { pair: buy_ticker.pair, buy_on, sell_on, quantity, buy_price, sell_price, gross_pnl, costs, net_pnl, net_edge })
+    }
+
+    /// Every opportunity between the tickers of a pair on several exchanges whose net edge is
+    /// above the threshold, best net PnL first. Exchanges without known costs are ignored.
+    pub fn scan(&self, tickers: &HashMap<Exchange, Ticker>, quantity: Volume) -> Vec<Opportunity> {
+        let mut opportunities = vec![];
+        for (buy_on, buy_ticker) in tickers {
+            for (sell_on, sell_ticker) in tickers {
+                if buy_on == sell_on || sell_ticker.highest_bid <= buy_ticker.lowest_ask {
+                    continue;
+                }
+                match self.estimate((*buy_on, buy_ticker), (*sell_on, sell_ticker), quantity.clone()) {
+                    Ok(o) if o.net_edge >= self.min_net_edge => opportunities.push(o),
+                    Ok(_) => (),
+                    Err(e) => trace!("Arbitrage : skipping {:?} -> {:?} : {}", buy_on, sell_on, e),
+                }
+            }
+        }
+        opportunities.sort_by(|a, b| b.net_pnl.cmp(&a.net_pnl));
+        opportunities
+    }
+}
+
+#[cfg(test)]
+mod arbitrage_tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::str::FromStr;
+    use std::sync::Mutex;
+
+    fn leg(order_type: OrderType, quantity: &str) -> Leg {
+        Leg::new(Pair::ETH_BTC, order_type, BigDecimal::from_str(quantity).unwrap(), BigDecimal::from_str("0.021").unwrap())
+    }
+
+    #[test]
+    fn checked_legs_rounds_quantities() {
+        let arb = TriangularArbitrage::new([leg(OrderType::BuyLimit, "1.123456"), leg(OrderType::SellLimit, "2"), leg(OrderType::SellLimit, "3")], 2, 4);
+        let legs = arb.checked_legs().unwrap();
+        assert_eq!(legs[0].quantity, BigDecimal::from_str("1.12").unwrap());
+    }
+
+    #[test]
+    fn checked_legs_rejects_market_orders() {
+        let arb = TriangularArbitrage::new([leg(OrderType::BuyMarket, "1"), leg(OrderType::SellLimit, "2"), leg(OrderType::SellLimit, "3")], 2, 4);
+        assert!(arb.checked_legs().is_err());
+    }
+
+    #[test]
+    fn checked_legs_rejects_dust() {
+        let arb = TriangularArbitrage::new([leg(OrderType::BuyLimit, "0.001"), leg(OrderType::SellLimit, "2"), leg(OrderType::SellLimit, "3")], 2, 4);
+        assert!(arb.checked_legs().is_err());
+    }
+
+    /// Fills every leg but the second, half filled, and records the orders. Fills on BTC_EUR are unknown.
+    #[derive(Debug, Default)]
+    struct PartialFills(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl ExchangeApi for PartialFills {
+        async fn ticker(&self, _pair: Pair) -> Result<Ticker> { Err(Error::Unsupported) }
+        async fn orderbook(&self, _pair: Pair) -> Result<Orderbook> { Err(Error::Unsupported) }
+        async fn balances(&self) -> Result<Balances> { Err(Error::Unsupported) }
+
+        async fn add_order(&self, order_type: OrderType, _pair: Pair, quantity: Volume, _price: Option<Price>) -> Result<OrderInfo> {
+            self.0.lock().unwrap().push(format!("{:?} {}", order_type, quantity.with_scale(2)));
+            Ok(OrderInfo { timestamp: 0, identifier: vec![], raw: None, request: None })
+        }
+
+        async fn add_ioc_order(&self, order_type: OrderType, pair: Pair, quantity: Volume, price: Price) -> Result<Execution> {
+            let order = self.add_order(order_type, pair, quantity.clone(), Some(price)).await?;
+            let filled = match self.0.lock().unwrap().len() {
+                _ if pair == Pair::BTC_EUR => None,
+                2 => Some(quantity / BigDecimal::from(2)),
+                _ => Some(quantity),
+            };
+            Ok(Execution { order, filled })
+        }
+    }
+
+    #[test]
+    fn partial_fills_are_hedged_for_the_filled_quantity() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let arb = TriangularArbitrage::new([leg(OrderType::BuyLimit, "1"), leg(OrderType::SellLimit, "2"), leg(OrderType::SellLimit, "3")], 2, 4);
+        let api = PartialFills::default();
+        match rt.block_on(arb.execute(&api)).unwrap() {
+            ArbitrageOutcome::Hedged { failed_leg, hedges, .. } => {
+                assert_eq!(failed_leg, 1);
+                assert_eq!(hedges.len(), 2);
+            }
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+        assert_eq!(*api.0.lock().unwrap(), vec!["BuyLimit 1.00", "SellLimit 2.00", "BuyMarket 1.00", "SellMarket 1.00"]);
+    }
+
+    #[test]
+    fn unknown_fills_stop_without_hedging() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let unknown = Leg::new(Pair::BTC_EUR, OrderType::SellLimit, BigDecimal::from(2), BigDecimal::from(7000));
+        let arb = TriangularArbitrage::new([leg(OrderType::BuyLimit, "1"), unknown, leg(OrderType::SellLimit, "3")], 2, 4);
+        let api = PartialFills::default();
+        match rt.block_on(arb.execute(&api)).unwrap() {
+            ArbitrageOutcome::Unsettled { leg, orders } => {
+                assert_eq!(leg, 1);
+                assert_eq!(orders.len(), 2);
+            }
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+        assert_eq!(*api.0.lock().unwrap(), vec!["BuyLimit 1.00", "SellLimit 2.00"]);
+    }
+
+    fn ticker(ask: &str, bid: &str) -> Ticker {
+        Ticker {
+            timestamp: 0,
+            pair: Pair::BTC_EUR,
+            last_trade_price: BigDecimal::from_str(bid).unwrap(),
+            lowest_ask: BigDecimal::from_str(ask).unwrap(),
+            highest_bid: BigDecimal::from_str(bid).unwrap(),
+            volume: None,
+            bid_size: None,
+            ask_size: None,
+            open_24h: None,
+            high_24h: None,
+            low_24h: None,
+            percent_change: None,
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn scan_keeps_opportunities_above_net_edge() {
+        let costs = VenueCosts {
+            taker_fee: BigDecimal::from_str("0.001").unwrap(),
+            withdrawal_fee: BigDecimal::from_str("0.0005").unwrap(),
+            transfer_haircut: BigDecimal::from_str("0.001").unwrap(),
+        };
+        let venues = vec![(Exchange::Kraken, costs.clone()), (Exchange::Bitstamp, costs)].into_iter().collect();
+        let mut tickers = HashMap::new();
+        tickers.insert(Exchange::Kraken, ticker("7000", "6990"));
+        tickers.insert(Exchange::Bitstamp, ticker("7110", "7100"));
+
+        let estimator = PnlEstimator::new(venues, BigDecimal::from_str("0.005").unwrap());
+        let found = estimator.scan(&tickers, BigDecimal::from(1));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].buy_on, Exchange::Kraken);
+        assert_eq!(found[0].gross_pnl, BigDecimal::from(100));
+        assert!(found[0].net_pnl < found[0].gross_pnl);
+
+        let strict = PnlEstimator::new(estimator.venues.clone(), BigDecimal::from_str("0.02").unwrap());
+        assert!(strict.scan(&tickers, BigDecimal::from(1)).is_empty());
+    }
+}
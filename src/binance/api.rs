@@ -2,10 +2,9 @@
 //! Please see examples for more informations.
 
 
-use hyper::{Client, Uri, Request, Body, Method};
+use hyper::{Uri, Request, Body, Method};
 use hyper::header::{CONTENT_TYPE,USER_AGENT};
 
-use hyper_tls::HttpsConnector;
 
 use serde_json::Value;
 use serde_json::value::Map;
@@ -22,7 +21,8 @@ use crate::helpers;
 use crate::types::Pair;
 use crate::binance::utils;
 use crate::types::*;
-use hyper::client::HttpConnector;
+use crate::helpers::proxy::HttpClient;
+use crate::helpers::secret::SecretString;
 use futures::{TryFutureExt};
 use bytes::buf::BufExt as _;
 use crate::helpers::json;
@@ -33,10 +33,10 @@ use binance::account::Account;
 #[derive(Debug, Clone)]
 pub struct BinanceApi {
     last_request: i64, // unix timestamp in ms, to avoid ban
-    pub api_key: Option<String>,
-    pub api_secret: Option<String>,
+    pub api_key: Option<SecretString>,
+    pub api_secret: Option<SecretString>,
     customer_id: String,
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    http_client: HttpClient,
     burst: bool,
 }
 
@@ -45,13 +45,16 @@ impl BinanceApi {
     /// Create a new BinanceApi by providing an API key & API secret
     pub fn new<C: Credentials>(creds: C) -> Result<BinanceApi> {
         if creds.exchange() != Exchange::Binance {
-            return Err(ErrorKind::InvalidConfigType(Exchange::Binance, creds.exchange()).into());
+            return Err(Error::InvalidConfigType(Exchange::Binance, creds.exchange()));
         }
+        creds.environment()?.production_only(Exchange::Binance)?;
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
-        let option = creds.get("api_key");
-        let option1 = creds.get("api_secret");
+        let connection = creds.connection();
+        // Requests go through the binance client, which has its own endpoint and http client
+        connection.unsupported(Exchange::Binance, true, true)?;
+        let ssl = connection.http_client()?;
+        let option = creds.get("api_key").map(SecretString::from);
+        let option1 = creds.get("api_secret").map(SecretString::from);
 
         Ok(BinanceApi {
             last_request: 0,
@@ -64,11 +67,11 @@ impl BinanceApi {
     }
 
     pub fn market(&self) -> Market {
-        Binance::new(self.api_key.clone(), self.api_secret.clone())
+        Binance::new(self.api_key.as_ref().map(|k| k.expose().to_string()), self.api_secret.as_ref().map(|s| s.expose().to_string()))
     }
 
     pub fn account(&self) -> Account {
-        Binance::new(self.api_key.clone(), self.api_secret.clone())
+        Binance::new(self.api_key.as_ref().map(|k| k.expose().to_string()), self.api_secret.as_ref().map(|s| s.expose().to_string()))
     }
 
     /// The number of calls in a given period is limited. In order to avoid a ban we limit
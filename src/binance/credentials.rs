@@ -4,23 +4,36 @@ use serde_json;
 use serde_json::Value;
 
 use crate::coinnect::Credentials;
+use crate::env_credentials::EnvCredentials;
 use crate::exchange::Exchange;
 use crate::helpers;
+use crate::helpers::secret::Redacted;
 use crate::error::*;
 
 use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct BinanceCreds {
     exchange: Exchange,
     name: String,
     data: HashMap<String, String>,
 }
 
+impl fmt::Debug for BinanceCreds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BinanceCreds")
+            .field("exchange", &self.exchange)
+            .field("name", &self.name)
+            .field("data", &Redacted(&self.data))
+            .finish()
+    }
+}
+
 impl BinanceCreds {
     /// Create a new `BinanceCreds` from a json configuration file. This file must follow this
     /// structure:
@@ -43,23 +56,25 @@ impl BinanceCreds {
 
         let data: Value = serde_json::from_str(&buffer)?;
         let json_obj = data.as_object()
-            .ok_or_else(|| ErrorKind::BadParse)?
+            .ok_or_else(|| Error::BadParse)?
             .get(name)
-            .ok_or_else(|| ErrorKind::MissingField(name.to_string()))?;
+            .ok_or_else(|| Error::MissingField(name.to_string()))?;
 
         let api_key = helpers::get_json_string(json_obj, "api_key")?;
         let api_secret = helpers::get_json_string(json_obj, "api_secret")?;
         let exchange = {
             let exchange_str = helpers::get_json_string(json_obj, "exchange")?;
             Exchange::from_str(exchange_str)
-                .chain_err(|| ErrorKind::InvalidFieldValue("exchange".to_string()))?
+                .with_context(|| Error::InvalidFieldValue("exchange".to_string()))?
         };
 
         if exchange != Exchange::Binance {
-            return Err(ErrorKind::InvalidConfigType(Exchange::Binance, exchange).into());
+            return Err(Error::InvalidConfigType(Exchange::Binance, exchange));
         }
 
-        Ok(BinanceCreds::new(name, api_key, api_secret))
+        let mut creds = BinanceCreds::new(name, api_key, api_secret);
+        helpers::copy_client_settings(json_obj, &mut creds.data);
+        Ok(creds)
     }
 
 
@@ -95,6 +110,18 @@ impl BinanceCreds {
         //}
         creds
     }
+
+    /// Create a new `BinanceCreds` from the environment variables `<prefix>_BINANCE_API_KEY`
+    /// and `<prefix>_BINANCE_API_SECRET`, see `EnvCredentials`.
+    /// The optional client settings are read the same way, e.g. `<prefix>_BINANCE_PROXY`.
+    pub fn new_from_env(prefix: &str) -> Result<Self> {
+        let env = EnvCredentials::new(prefix, Exchange::Binance);
+        let api_key = env.require("api_key")?;
+        let api_secret = env.require("api_secret")?;
+        let mut creds = BinanceCreds::new(&env.name(), &api_key, &api_secret);
+        env.copy_client_settings(&mut creds.data);
+        Ok(creds)
+    }
 }
 
 impl Credentials for BinanceCreds {
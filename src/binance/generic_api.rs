@@ -2,7 +2,7 @@
 //! This a more convenient and safe way to deal with the exchange since methods return a Result<>
 //! but this generic API does not provide all the functionnality that Binance offers.
 
-use crate::exchange::{ExchangeApi, Exchange};
+use crate::exchange::{ExchangeApi, Exchange, ExchangeCapabilities};
 use crate::binance::api::BinanceApi;
 use crate::binance::utils;
 
@@ -12,31 +12,41 @@ use crate::helpers;
 use async_trait::async_trait;
 use binance::api::Binance;
 use binance::market::Market;
+use binance::general::General;
+use binance::account::{OrderSide, OrderType as BinanceOrderType, TimeInForce};
 use crate::utils::pair_or;
 use bigdecimal::{BigDecimal, ToPrimitive};
+use std::str::FromStr;
 
 #[async_trait]
 impl ExchangeApi for BinanceApi {
-    async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
         let market = self.market();
 
         let pair_str = pair_or(Exchange::Binance, &pair)?;
-        let result =   market.get_24h_price_stats(*pair_str).await?;
+        let result =   market.get_24h_price_stats(*pair_str).await.map_err(utils::map_error)?;
         Ok(Ticker {
             timestamp: helpers::get_unix_timestamp_ms(),
             pair,
             last_trade_price: BigDecimal::from(result.last_price),
-            lowest_ask: BigDecimal::from(result.low_price),
-            highest_bid: BigDecimal::from(result.high_price),
+            lowest_ask: BigDecimal::from(result.ask_price),
+            highest_bid: BigDecimal::from(result.bid_price),
             volume: Some(BigDecimal::from(result.volume)),
+            bid_size: None,
+            ask_size: None,
+            open_24h: Some(BigDecimal::from(result.open_price)),
+            high_24h: Some(BigDecimal::from(result.high_price)),
+            low_24h: Some(BigDecimal::from(result.low_price)),
+            percent_change: BigDecimal::from_str(&result.price_change_percent).ok(),
+            raw: None,
         })
     }
 
-    async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
         let market = self.market();
         let pair_str = pair_or(Exchange::Binance, &pair)?;
 
-        let book_ticker = market.get_depth(*pair_str).await?;
+        let book_ticker = market.get_depth(*pair_str).await.map_err(utils::map_error)?;
 
         Ok(Orderbook {
             timestamp: helpers::get_unix_timestamp_ms(),
@@ -46,41 +56,87 @@ impl ExchangeApi for BinanceApi {
         })
     }
 
-    async fn add_order(&mut self,
+    async fn add_order(&self,
                        order_type: OrderType,
                        pair: Pair,
                        quantity: Volume,
                        price: Option<Price>)
                        -> Result<OrderInfo> {
+        let request = OrderRequest::new(order_type, pair, &quantity, &price);
         let pair_str = *pair_or(Exchange::Binance, &pair)?;
         let account = self.account();
         let quantity_f64 = quantity.as_f64()?;
         let result = match order_type {
             OrderType::BuyLimit => {
                 if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
+                    return Err(Error::MissingPrice);
                 }
                 account.limit_buy(pair_str, quantity_f64, price.unwrap().as_f64()?).await
             }
             OrderType::BuyMarket => account.market_buy(pair_str, quantity_f64).await,
             OrderType::SellLimit => {
                 if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
+                    return Err(Error::MissingPrice);
                 }
                 account.limit_sell(pair_str, quantity_f64, price.unwrap().as_f64()?).await
             }
             OrderType::SellMarket => account.market_sell(pair_str, quantity_f64).await,
+            _ => return Err(Error::OrderTypeUnsupported),
         };
 
         Ok(OrderInfo {
             timestamp: helpers::get_unix_timestamp_ms(),
-            identifier: vec![result?.client_order_id],
+            identifier: vec![result.map_err(utils::map_error)?.client_order_id],
+            raw: None,
+            request: Some(request),
         })
     }
 
+    /// Limit orders are sent with `timeInForce=IOC`, the filled quantity is the executed quantity
+    /// of the order once placed
+    async fn add_ioc_order(&self,
+                           order_type: OrderType,
+                           pair: Pair,
+                           quantity: Volume,
+                           price: Price)
+                           -> Result<Execution> {
+        let request = OrderRequest::new(order_type, pair, &quantity, &Some(price.clone()));
+        let pair_str = *pair_or(Exchange::Binance, &pair)?;
+        let side = match order_type {
+            OrderType::BuyLimit => OrderSide::Buy,
+            OrderType::SellLimit => OrderSide::Sell,
+            _ => return Err(Error::OrderTypeUnsupported),
+        };
+        let account = self.account();
+        let result = account.custom_order(pair_str, quantity.as_f64()?, price.as_f64()?, None, side, BinanceOrderType::Limit, TimeInForce::IOC, None).await
+            .map_err(utils::map_error)?;
+        let filled = match account.order_status(pair_str, result.order_id).await {
+            Ok(status) => BigDecimal::from_str(&status.executed_qty.to_string()).ok(),
+            Err(e) => {
+                warn!("{:?} : fills of order {} unknown : {}", pair, result.order_id, utils::map_error(e));
+                None
+            }
+        };
+        let order = OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: vec![result.client_order_id],
+            raw: None,
+            request: Some(request),
+        };
+        Ok(Execution { order, filled })
+    }
+
+    /// Account commissions, given in basis points
+    async fn fees(&self, _pair: Pair) -> Result<FeeRates> {
+        let result = self.account().get_account().await.map_err(utils::map_error)?;
+        let bps = BigDecimal::from(10_000);
+        Ok(FeeRates::new(BigDecimal::from(f64::from(result.maker_commission)) / bps.clone(),
+                         BigDecimal::from(f64::from(result.taker_commission)) / bps))
+    }
+
     /// Return the balances for each currency on the account
-    async fn balances(&mut self) -> Result<Balances> {
-        let result = self.account().get_account().await?;
+    async fn balances(&self) -> Result<Balances> {
+        let result = self.account().get_account().await.map_err(utils::map_error)?;
 
         let mut balances = Balances::new();
 
@@ -97,4 +153,32 @@ impl ExchangeApi for BinanceApi {
 
         Ok(balances)
     }
+
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        let result = self.account().get_account().await.map_err(utils::map_error)?;
+
+        let mut balances = DetailedBalances::new();
+
+        for balance in result.balances {
+            if let Some(c) = utils::get_currency_enum(balance.asset.as_str()) {
+                balances.insert(c, Balance::new(balance.free.parse::<BigDecimal>()?, balance.locked.parse::<BigDecimal>()?));
+            }
+        }
+
+        Ok(balances)
+    }
+
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        let general: General = Binance::new(None, None);
+        let info = general.exchange_info().await.map_err(utils::map_error)?;
+        // Halted and delisted symbols stay in the exchange information
+        Ok(info.symbols.iter()
+            .filter(|s| s.status == "TRADING")
+            .filter_map(|s| utils::get_pair_enum(&s.symbol).copied())
+            .collect())
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Binance.capabilities()
+    }
 }
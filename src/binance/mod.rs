@@ -4,6 +4,7 @@ pub mod api;
 pub mod credentials;
 pub mod utils;
 pub mod generic_api;
+#[cfg(feature = "streaming")]
 pub mod streaming_api;
 pub mod models;
 
@@ -1,4 +1,6 @@
-use crate::types::{Channel, LiveEvent, TradeType};
+use crate::error::{Error, Result};
+use crate::exchange::Exchange;
+use crate::types::{Channel, LiveEvent};
 use crate::types;
 use bigdecimal::BigDecimal;
 
@@ -37,13 +39,17 @@ pub enum Event {
 impl Into<LiveEvent> for Event {
     fn into(self) -> LiveEvent {
         match self {
-            Event::Trade(e) => LiveEvent::LiveTrade(types::LiveTrade{
-                amount: e.qty.parse::<f32>().unwrap(),
-                event_ms: e.event_time as i64,
-                price: e.price.parse::<BigDecimal>().unwrap(),
-                tt: TradeType::Sell,
-                pair: e.symbol,
-            }),
+            Event::Trade(e) => match super::utils::get_pair_enum(&e.symbol) {
+                Some(&pair) => LiveEvent::LiveTrade(types::LiveTrade{
+                    amount: e.qty.parse::<BigDecimal>().unwrap(),
+                    event_ms: e.event_time as i64,
+                    price: e.price.parse::<BigDecimal>().unwrap(),
+                    tt: super::utils::get_trade_type(e.is_buyer_maker),
+                    pair,
+                    meta: types::TradeMeta::with_id(e.aggregated_trade_id),
+                }),
+                None => LiveEvent::Noop,
+            },
             Event::DepthOrderBook(e) => {
                 let asks = e.asks;
                 let bids = e.bids;
@@ -58,11 +64,48 @@ impl Into<LiveEvent> for Event {
                     pair: *super::utils::get_pair_enum(e.symbol.as_str()).unwrap(),
                 })
             },
+            Event::Kline(e) => match kline_candle(e.kline) {
+                Ok(candle) => LiveEvent::LiveCandle(candle),
+                Err(e) => {
+                    warn!("Binance : cannot parse a kline : {}", e);
+                    LiveEvent::Noop
+                }
+            },
             _ => LiveEvent::Noop
         }
     }
 }
 
+fn kline_candle(k: Kline) -> Result<types::LiveCandle> {
+    let decimal = |value: &str, field: &str| value.parse::<BigDecimal>().map_err(|_| Error::InvalidFieldFormat(field.to_string()));
+    Ok(types::LiveCandle {
+        event_ms: k.start_time,
+        interval_secs: interval_secs(&k.interval).ok_or_else(|| Error::InvalidFieldValue("interval".to_string()))?,
+        open: decimal(&k.open, "open")?,
+        high: decimal(&k.high, "high")?,
+        low: decimal(&k.low, "low")?,
+        close: decimal(&k.close, "close")?,
+        volume: decimal(&k.volume, "volume")?,
+        closed: k.is_final_bar,
+        pair: k.symbol,
+    })
+}
+
+const KLINE_INTERVALS: [(&str, u64); 14] = [
+    ("1m", 60), ("3m", 180), ("5m", 300), ("15m", 900), ("30m", 1800),
+    ("1h", 3600), ("2h", 7200), ("4h", 14400), ("6h", 21600), ("8h", 28800), ("12h", 43200),
+    ("1d", 86400), ("3d", 259200), ("1w", 604800),
+];
+
+/// Name of the native kline interval of this length, if Binance has one
+pub fn kline_interval(interval_secs: u64) -> Option<&'static str> {
+    KLINE_INTERVALS.iter().find(|(_, secs)| *secs == interval_secs).map(|(name, _)| *name)
+}
+
+fn interval_secs(interval: &str) -> Option<u64> {
+    KLINE_INTERVALS.iter().find(|(name, _)| *name == interval).map(|(_, secs)| *secs)
+}
+
 #[derive(Serialize, Deserialize, Debug, Message)]
 #[rtype(result = "()")]
 pub struct Subscription {
@@ -71,18 +114,40 @@ pub struct Subscription {
     id: i32,
 }
 
-pub fn subscription(c: Channel, currency_pairs: Vec<&str>, id: i32) -> Subscription {
+/// Fails with `Error::ChannelUnsupported` for the channels Binance does not stream
+pub fn subscription(c: Channel, currency_pairs: Vec<&str>, id: i32) -> Result<Subscription> {
     let channel_str = match c {
         Channel::LiveTrades => "trade",
         Channel::LiveOrders => "orders",
         Channel::LiveOrderBook => "depth@100ms",
         Channel::LiveDetailOrderBook => "depth@100ms",
         Channel::LiveFullOrderBook => "depth@100ms",
+        Channel::LiveCandles => "kline_1m",
+        Channel::LiveSummary => return Err(Error::ChannelUnsupported(Exchange::Binance, c)),
     };
-    Subscription {
+    Ok(Subscription {
         method: String::from("SUBSCRIBE"),
         params: currency_pairs.into_iter().map(|cp| format!("{}@{}", cp.to_lowercase(), channel_str)).collect(),
         id,
+    })
+}
+
+/// Reply to a subscription : `{"result": null, "id": 1}`, or
+/// `{"error": {"code": 2, "msg": "Invalid request: ..."}, "id": 1}` when one of its streams is
+/// invalid
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionResponse {
+    pub id: i32,
+    #[serde(default)]
+    pub error: Option<serde_json::Value>,
+}
+
+/// Subscribe to the klines of `interval`, see `kline_interval`
+pub fn kline_subscription(currency_pairs: Vec<&str>, interval: &str, id: i32) -> Subscription {
+    Subscription {
+        method: String::from("SUBSCRIBE"),
+        params: currency_pairs.into_iter().map(|cp| format!("{}@kline_{}", cp.to_lowercase(), interval)).collect(),
+        id,
     }
 }
 
@@ -1,6 +1,7 @@
+use futures::future::LocalBoxFuture;
 use futures::stream::{SplitSink, FuturesUnordered};
-use crate::exchange_bot::{ExchangeBot, WsHandler, DefaultWsActor};
-use crate::types::{Channel, Pair, LiveEventEnveloppe, LiveAggregatedOrderBook, LiveEvent, Orderbook};
+use crate::exchange_bot::{self, ExchangeBot, WsHandler, DefaultWsActor, ObserveRawFrames, RawFrameEvent, Snapshot};
+use crate::types::{Channel, Pair, LiveEventEnveloppe, LiveAggregatedOrderBook, DEFAULT_BOOK_DEPTH, LiveEvent, LiveTrade, Orderbook, SubscriptionResult, TradeTape, WarmUp};
 use std::collections::{HashSet, HashMap};
 use crate::coinnect::Credentials;
 use std::rc::Rc;
@@ -13,7 +14,10 @@ use actix_codec::Framed;
 use awc::BoxedSocket;
 use bytes::Bytes;
 use bytes::Buf;
-use crate::exchange::{Exchange, ExchangeApi};
+use crate::exchange::{Exchange, ExchangeApi, ExchangeCapabilities};
+use crate::helpers::delivery::{Delivery, DeliveryPolicy};
+use crate::helpers::failover::Endpoints;
+use crate::metrics;
 use super::models::*;
 use crate::binance::BinanceApi;
 use futures::Future;
@@ -25,7 +29,6 @@ use tokio::sync::mpsc::{self, Receiver};
 
 use std::{thread, ptr};
 use actix::{Addr, Recipient, Context, Actor, AsyncContext};
-use actix_web::error::Canceled;
 use tokio::runtime::Runtime;
 use async_trait::async_trait;
 use bigdecimal::BigDecimal;
@@ -33,12 +36,37 @@ use bigdecimal::BigDecimal;
 static WEBSOCKET_URL: &'static str = "wss://stream.binance.com:9443/ws";
 
 pub struct BinanceBot {
-    addr: Addr<DefaultWsActor>
+    addr: Addr<DefaultWsActor>,
+    endpoints: Arc<Endpoints>,
 }
 
 impl ExchangeBot for BinanceBot {
     fn is_connected(&self) -> bool {
-        unimplemented!()
+        self.addr.connected()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Binance.capabilities()
+    }
+
+    fn observe_raw_frames(&self, recipient: Recipient<RawFrameEvent>) -> Result<()> {
+        self.addr.do_send(ObserveRawFrames(Exchange::Binance, recipient));
+        Ok(())
+    }
+
+    fn endpoints(&self) -> Option<Arc<Endpoints>> {
+        Some(self.endpoints.clone())
+    }
+
+    fn shutdown(&self) -> LocalBoxFuture<'static, ()> {
+        exchange_bot::shutdown_ws(&self.addr)
+    }
+}
+
+impl BinanceBot {
+    /// The current order book and latest trade of `pair`
+    pub async fn snapshot(&self, pair: Pair) -> Result<Option<Snapshot>> {
+        exchange_bot::query_snapshot(&self.addr, pair).await
     }
 }
 
@@ -46,26 +74,47 @@ impl ExchangeBot for BinanceBot {
 pub struct BinanceStreamingApi {
     books: Arc<RefCell<HashMap<Pair, LiveAggregatedOrderBook>>>,
     pub channels: HashMap<Channel, HashSet<Pair>>,
-    pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    delivery: Rc<RefCell<Delivery<LiveEventEnveloppe>>>,
     api: Arc<BinanceApi>,
+    last_trades: HashMap<Pair, LiveTrade>,
+    tape: Rc<RefCell<TradeTape>>,
+    warm_up: WarmUp,
+    depth: i8,
+    candle_interval: &'static str,
+    /// Channel and pairs of each subscription request, by id
+    subscriptions: HashMap<i32, (Channel, Vec<Pair>)>,
 }
 
 impl BinanceStreamingApi {
-    /// Create a new bittrex exchange bot, unavailable channels and currencies are ignored
-    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BinanceBot> {
+    /// Create a new binance exchange bot, unavailable channels and currencies are ignored.
+    /// Candles use the native kline stream, `candle_interval_secs` must be one of its intervals.
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, warm_up: WarmUp, depth: Option<i8>, candle_interval_secs: u64, delivery: DeliveryPolicy, recipients: Vec<Recipient<LiveEventEnveloppe>>, raw_frames: Option<Recipient<RawFrameEvent>>) -> Result<BinanceBot> {
+        let candle_interval = match kline_interval(candle_interval_secs) {
+            Some(interval) => interval,
+            None if channels.contains_key(&Channel::LiveCandles) => return Err(Error::InvalidFieldValue("interval_secs".to_string())),
+            None => "1m",
+        };
+        let connection = creds.connection();
         let mut map = channels.clone();
         let order_book_pairs: &HashSet<Pair> = map.entry(Channel::LiveFullOrderBook).or_default();
         let trade_pairs: &HashSet<Pair> = map.entry(Channel::LiveTrades).or_default();
         let api = BinanceStreamingApi {
-            recipients,
+            delivery: Rc::new(RefCell::new(Delivery::new(recipients, delivery))),
             books: Arc::new(RefCell::new(HashMap::new())),
             channels,
-            api: Arc::new(BinanceApi::new(*creds).unwrap()),
+            api: Arc::new(BinanceApi::new(*creds)?),
+            last_trades: HashMap::new(),
+            tape: Rc::new(RefCell::new(TradeTape::default())),
+            warm_up,
+            depth: depth.unwrap_or(DEFAULT_BOOK_DEPTH),
+            candle_interval,
+            subscriptions: HashMap::new(),
         };
         api.refresh_order_books().await;
-        let addr = DefaultWsActor::new("BinanceStream", WEBSOCKET_URL, Some(Duration::from_secs(30)), Box::new(api)).await?;
+        let endpoints = Arc::new(connection.ws_endpoints(Exchange::Binance, WEBSOCKET_URL));
+        let addr = DefaultWsActor::new("BinanceStream", endpoints.clone(), connection.proxy()?, Some(Duration::from_secs(30)), raw_frames.map(|r| (Exchange::Binance, r)).into_iter().collect(), Box::new(api)).await?;
 
-        return Ok(BinanceBot { addr });
+        return Ok(BinanceBot { addr, endpoints });
     }
 
     async fn refresh_order_books(&self) {
@@ -90,34 +139,49 @@ impl BinanceStreamingApi {
             for ob in ret2.into_iter().filter(|&ref o| o.as_ref().and_then(|r| r.as_ref().ok()).is_some()) {
                 let ob = ob.unwrap().unwrap();
                 let mut books = self.books.borrow_mut();
-                let default_book = LiveAggregatedOrderBook::default(ob.pair);
+                let default_book = LiveAggregatedOrderBook::with_warm_up(ob.pair, self.warm_up).with_depth(self.depth);
                 let mut agg = books.entry(ob.pair).or_insert(default_book);
                 agg.reset_asks(ob.asks.into_iter());
                 agg.reset_bids(ob.bids.into_iter());
+                if !agg.is_warm() {
+                    continue;
+                }
                 let latest_order_book: Orderbook = agg.order_book();
                 self.broadcast(LiveEvent::LiveOrderbook(latest_order_book.clone()));
             }
     }
 
-    fn broadcast(&self, v: LiveEvent) {
-        let vec = self.recipients.clone();
-        if vec.len() == 0 as usize {
-            println!("{:?}", v);
-        } else {
-            for r in &vec {
-                let le: LiveEvent = v.clone();
-                r.do_send(LiveEventEnveloppe(Exchange::Binance, le));
-            }
+    /// A request is rejected as a whole when one of its streams is invalid
+    fn subscription_result(&self, response: SubscriptionResponse) {
+        let (channel, pairs) = match self.subscriptions.get(&response.id) {
+            Some(subscription) => subscription,
+            None => return,
+        };
+        let reason = response.error.as_ref().map(|e| e["msg"].as_str().unwrap_or_default().to_string());
+        for &pair in pairs {
+            let result = match &reason {
+                Some(reason) => SubscriptionResult::rejected(channel.clone(), pair, reason),
+                None => SubscriptionResult::accepted(channel.clone(), pair),
+            };
+            self.broadcast(LiveEvent::SubscriptionResult(result));
         }
     }
+
+    fn broadcast(&self, v: LiveEvent) {
+        metrics::ws_event(Exchange::Binance, &v);
+        self.delivery.borrow_mut().send(LiveEventEnveloppe(Exchange::Binance, v));
+    }
 }
 
 #[async_trait]
 impl WsHandler for BinanceStreamingApi {
     #[cfg_attr(feature = "flame_it", flame)]
     fn handle_in(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, msg: Bytes) {
-        let v: Result<Event> = serde_json::from_slice(msg.bytes()).map_err(|e| ErrorKind::Json(e).into());
+        let v: Result<Event> = serde_json::from_slice(msg.bytes()).map_err(Error::Json);
         if v.is_err() {
+            if let Ok(response) = serde_json::from_slice::<SubscriptionResponse>(msg.bytes()) {
+                return self.subscription_result(response);
+            }
             return trace!("Binance : error {:?} deserializing {:?}", v.err().unwrap(), msg);
         }
         match v {
@@ -128,7 +192,7 @@ impl WsHandler for BinanceStreamingApi {
                 }
                 let current_pair = *pair.unwrap();
                 let mut books = self.books.borrow_mut();
-                let default_book = LiveAggregatedOrderBook::default(current_pair);
+                let default_book = LiveAggregatedOrderBook::with_warm_up(current_pair, self.warm_up).with_depth(self.depth);
                 let mut agg = books.entry(current_pair).or_insert(default_book);
                 agg.update_asks(ob.asks.into_iter().map(|a| (BigDecimal::from(a.price), BigDecimal::from(a.qty))));
                 agg.update_bids(ob.bids.into_iter().map(|a| (BigDecimal::from(a.price), BigDecimal::from(a.qty))));
@@ -136,12 +200,41 @@ impl WsHandler for BinanceStreamingApi {
             }
             Ok(Event::Trade(t)) => {
                 let le : LiveEvent = Event::Trade(t).into();
+                if let LiveEvent::LiveTrade(trade) = le {
+                    let trade = match self.tape.borrow_mut().accept(trade) {
+                        Some(trade) => trade,
+                        None => return,
+                    };
+                    self.last_trades.insert(trade.pair, trade.clone());
+                    self.broadcast(LiveEvent::LiveTrade(trade))
+                }
+            },
+            Ok(Event::Kline(k)) => {
+                let le : LiveEvent = Event::Kline(k).into();
                 self.broadcast(le)
             },
             _ => return
         }
     }
 
+    fn snapshot(&self, pair: Pair) -> Option<Snapshot> {
+        let orderbook = self.books.borrow().get(&pair).filter(|agg| agg.is_warm()).map(|agg| agg.order_book());
+        let last_trade = self.last_trades.get(&pair).cloned();
+        if orderbook.is_none() && last_trade.is_none() {
+            return None;
+        }
+        Some(Snapshot { orderbook, last_trade })
+    }
+
+    /// Trades missed while disconnected are not sent again
+    fn handle_reconnect(&mut self) {
+        self.tape.borrow_mut().mark_gap();
+    }
+
+    fn flush_deliveries(&mut self) {
+        self.delivery.borrow_mut().flush();
+    }
+
     fn handle_started(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>) {
         let rc = self.books.clone();
 
@@ -149,15 +242,32 @@ impl WsHandler for BinanceStreamingApi {
 
         let x = self.channels.get(&Channel::LiveFullOrderBook).unwrap();
         for &pair in x {
-            books.insert(pair.clone(), LiveAggregatedOrderBook::default(pair.clone()));
+            books.insert(pair.clone(), LiveAggregatedOrderBook::with_warm_up(pair.clone(), self.warm_up).with_depth(self.depth));
         }
 //        ctx.spawn(self.clone().refresh_order_books());
 
-        for (k, v) in &self.channels {
+        self.subscriptions.clear();
+        for (i, (k, v)) in self.channels.iter().enumerate() {
+            let id = i as i32 + 1;
+            self.subscriptions.insert(id, (k.clone(), v.iter().copied().collect()));
             let pairs = v.into_iter().map(|pair| *super::utils::get_pair_string(&pair).unwrap()).collect();
             info!("Binance : connecting to {:?} for {:?}", k, &pairs);
-            let result = serde_json::to_string(&subscription(k.clone(), pairs, 1)).unwrap();
-            w.write(Message::Text(result));
+            let sub = match k {
+                Channel::LiveCandles => Ok(kline_subscription(pairs, self.candle_interval, id)),
+                _ => subscription(k.clone(), pairs, id),
+            };
+            match sub {
+                Ok(sub) => {
+                    let result = serde_json::to_string(&sub).unwrap();
+                    w.write(Message::Text(result));
+                }
+                Err(e) => {
+                    warn!("Binance : {}", e);
+                    for &pair in v {
+                        self.broadcast(LiveEvent::SubscriptionResult(SubscriptionResult::rejected(k.clone(), pair, &e.to_string())));
+                    }
+                }
+            }
         }
     }
 }
@@ -8,8 +8,11 @@ use serde_json::Value;
 use serde_json::value::Map;
 
 use crate::error::*;
+use crate::exchange::Exchange;
 use crate::helpers;
+use binance::errors::ErrorKind as BinanceErrorKind;
 use crate::types::Currency;
+use crate::types::TradeType;
 use crate::types::Pair;
 use crate::types::Pair::*;
 
@@ -35,6 +38,34 @@ lazy_static! {
     };
 }
 
+/// Side of the taker of a trade : when the buyer is the maker, the taker sold
+pub fn get_trade_type(is_buyer_maker: bool) -> TradeType {
+    if is_buyer_maker {
+        TradeType::Sell
+    } else {
+        TradeType::Buy
+    }
+}
+
+/// Map the error codes of Binance to typed errors, see
+/// https://github.com/binance-exchange/binance-official-api-docs/blob/master/errors.md
+pub fn map_error(e: binance::errors::Error) -> Error {
+    let content = match e.kind() {
+        BinanceErrorKind::BinanceError(response) => Some((response.code, response.msg.to_lowercase())),
+        _ => None,
+    };
+    match content {
+        Some((-1003, _)) | Some((-1015, _)) => Error::RateLimited(None),
+        Some((-1021, _)) => Error::InvalidNonce,
+        Some((-1022, _)) | Some((-2014, _)) | Some((-2015, _)) => Error::BadCredentials,
+        Some((-1121, _)) => Error::PairUnsupported,
+        Some((-2011, _)) | Some((-2013, _)) => Error::OrderNotFound,
+        Some((-2010, ref msg)) if msg.contains("insufficient balance") => Error::InsufficientFunds,
+        Some((-1013, ref msg)) if msg.contains("min_notional") || msg.contains("lot_size") => Error::InsufficientOrderSize,
+        _ => e.into(),
+    }
+}
+
 /// Return the name associated to pair used by Bitstamp
 /// If the Pair is not supported, None is returned.
 pub fn get_pair_string(pair: &Pair) -> Option<&&str> {
@@ -47,6 +78,11 @@ pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
     PAIRS_STRING.get_by_second(&pair)
 }
 
+/// Every pair with a Binance name
+pub fn supported_pairs() -> Vec<Pair> {
+    PAIRS_STRING.iter().map(|(pair, _)| *pair).collect()
+}
+
 /// Return the currency enum associated with the
 /// string used by Bitstamp. If no currency is found,
 /// return None
@@ -60,8 +96,7 @@ pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
 /// assert_eq!(Some(Currency::USD), currency);
 /// ```
 pub fn get_currency_enum(currency: &str) -> Option<Currency> {
-    let c : Option<Currency> = serde_json::from_str(currency).ok();
-    c
+    Currency::from_alias(Exchange::Binance, currency)
 }
 
 /// Return the currency string associated with the
@@ -77,5 +112,5 @@ pub fn get_currency_enum(currency: &str) -> Option<Currency> {
 /// assert_eq!(currency, Some("USD".to_string()));
 /// ```
 pub fn get_currency_string(currency: Currency) -> Option<String> {
-    serde_json::to_string(&currency).ok()
+    Some(currency.alias_for(Exchange::Binance))
 }
@@ -2,38 +2,59 @@
 //! Please see examples for more informations.
 
 
-use hyper::{Client, Body, Request, Uri, Method};
-use hyper::header::CONTENT_TYPE;
-use hyper_tls::HttpsConnector;
+use hyper::{Body, Request, Uri, Method};
+use hyper::header::{CONTENT_TYPE, RETRY_AFTER};
 
 use serde_json::Value;
 use serde_json::value::Map;
 
 use std::collections::HashMap;
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::AtomicI64;
+use std::time::{Duration, Instant};
 
 use crate::coinnect::Credentials;
 use crate::exchange::Exchange;
 
 use crate::error::*;
+use crate::helpers::failover::Endpoints;
+use crate::helpers::secret::SecretString;
+use crate::helpers::signing;
 use crate::helpers;
+use crate::metrics;
 use crate::types::Pair;
 use crate::bitstamp::utils;
 use crate::types::*;
-use hyper::client::HttpConnector;
+use crate::helpers::proxy::HttpClient;
+use crate::helpers::rate_limit::RateLimits;
 use futures::{TryFutureExt};
 use bytes::buf::BufExt as _;
-use awc::http::StatusCode;
+use hyper::StatusCode;
+
+/// Execution flags of a limit order, only one of `ioc_order` and `fok_order` can be set
+#[derive(Debug, Clone, Default)]
+pub struct LimitOrderOptions {
+    /// If the order gets executed, a new order in the other direction is placed at this price
+    pub limit_price: Option<Price>,
+    /// Cancel the order at 0:00 UTC unless it has been executed
+    pub daily_order: bool,
+    /// Immediate or cancel, the part not executed at once is canceled
+    pub ioc_order: bool,
+    /// Fill or kill, the order is canceled unless it executes fully at once
+    pub fok_order: bool,
+}
 
 #[derive(Debug)]
 pub struct BitstampApi {
-    last_request: i64, // unix timestamp in ms, to avoid ban
-    api_key: String,
-    api_secret: String,
+    last_request: AtomicI64, // unix timestamp in ms, to avoid ban
+    api_key: SecretString,
+    api_secret: SecretString,
     customer_id: String,
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    http_client: HttpClient,
     burst: bool,
+    pub(crate) keep_raw: bool,
+    debug_signing: bool,
+    endpoints: Endpoints,
+    pub(crate) rate_limits: RateLimits,
 }
 
 
@@ -41,22 +62,33 @@ impl BitstampApi {
     /// Create a new BitstampApi by providing an API key & API secret
     pub fn new<C: Credentials>(creds: C) -> Result<BitstampApi> {
         if creds.exchange() != Exchange::Bitstamp {
-            return Err(ErrorKind::InvalidConfigType(Exchange::Bitstamp, creds.exchange()).into());
+            return Err(Error::InvalidConfigType(Exchange::Bitstamp, creds.exchange()));
         }
+        creds.environment()?.production_only(Exchange::Bitstamp)?;
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
+        let connection = creds.connection();
+        let ssl = connection.http_client()?;
+        let endpoints = connection.rest_endpoints(Exchange::Bitstamp, utils::API_URL);
 
         Ok(BitstampApi {
-               last_request: 0,
-               api_key: creds.get("api_key").unwrap_or_default(),
-               api_secret: creds.get("api_secret").unwrap_or_default(),
+               last_request: AtomicI64::new(0),
+               api_key: creds.get("api_key").unwrap_or_default().into(),
+               api_secret: creds.get("api_secret").unwrap_or_default().into(),
                customer_id: creds.get("customer_id").unwrap_or_default(),
                http_client: ssl,
                burst: false, // No burst by default
+               keep_raw: connection.keep_raw,
+               debug_signing: connection.debug_signing,
+               endpoints,
+               rate_limits: RateLimits::default(),
            })
     }
 
+    /// The REST endpoints, to subscribe to failover events or recover the primary endpoint
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
     /// The number of calls in a given period is limited. In order to avoid a ban we limit
     /// by default the number of api requests.
     /// This function sets or removes the limitation.
@@ -66,29 +98,36 @@ impl BitstampApi {
         self.burst = burst
     }
 
-    fn block_or_continue(&self) {
-        if ! self.burst {
-            let threshold: u64 = 1000; // 600 requests per 10 mins = 1 request per second
-            let offset: u64 = helpers::get_unix_timestamp_ms() as u64 - self.last_request as u64;
-            if offset < threshold {
-                let wait_ms = Duration::from_millis(threshold - offset);
-                thread::sleep(wait_ms);
-            }
+    /// Keep the payload of the exchange in the `raw` field of typed responses
+    pub fn set_keep_raw(&mut self, keep_raw: bool) {
+        self.keep_raw = keep_raw
+    }
+
+    /// Log the strings signed for the private requests and the status of their responses
+    pub fn set_debug_signing(&mut self, debug_signing: bool) {
+        self.debug_signing = debug_signing
+    }
+
+    async fn block_or_continue(&self) {
+        if !self.burst {
+            helpers::rate_limit::wait_for_slot(&self.last_request, 1000).await; // 600 requests per 10 mins = 1 request per second
         }
     }
 
-    async fn public_query(&mut self, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
+    async fn public_query(&self, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
 
         let method: &str = params
             .get("method")
             .ok_or_else(|| "Missing \"method\" field.")?;
         let pair: &str = params.get("pair").ok_or_else(|| "Missing \"pair\" field.")?;
-        let string = utils::build_url(method, pair);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
-
-        self.block_or_continue();
-        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
-        self.last_request = helpers::get_unix_timestamp_ms();
+        let string = utils::build_url(self.endpoints.current(), method, pair);
+        let url: Uri = string.as_str().parse().map_err(|_e| Error::BadParse)?;
+
+        self.block_or_continue().await;
+        let start = Instant::now();
+        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body()).map_err(Error::from)).await;
+        metrics::rest_request(Exchange::Bitstamp, method, start);
+        let buf = self.endpoints.track(buf)?;
         let reader = buf.reader();
         utils::deserialize_json_r(reader)
     }
@@ -104,26 +143,30 @@ impl BitstampApi {
     /// let  result = api.private_query("balance", "btcusd");
     /// assert_eq!(true, true);
     /// ```
-    async fn private_query(&mut self, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
+    async fn private_query(&self, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
 
         let method: &str = params
             .get("method")
             .ok_or_else(|| "Missing \"method\" field.")?;
         let pair: &str = params.get("pair").ok_or_else(|| "Missing \"pair\" field.")?;
-        let url: String = utils::build_url(method, pair);
+        let url: String = utils::build_url(self.endpoints.current(), method, pair);
 
         let nonce = utils::generate_nonce(None);
+        if self.debug_signing {
+            let canonical = format!("{}{}{}", nonce, self.customer_id, self.api_key.expose());
+            signing::log_signed(Exchange::Bitstamp, method, &nonce, &canonical, &[self.api_key.expose()]);
+        }
         let signature =
-            utils::build_signature(&nonce, &self.customer_id, &self.api_key, &self.api_secret)?;
+            utils::build_signature(&nonce, &self.customer_id, self.api_key.expose(), self.api_secret.expose())?;
 
-        let copy_api_key = self.api_key.clone();
+        let copy_api_key = self.api_key.expose().to_string();
         let mut post_params: &mut HashMap<&str, &str> = &mut HashMap::new();
         post_params.insert("key", &copy_api_key);
         post_params.insert("signature", &signature);
         post_params.insert("nonce", &nonce);
 
-        // copy params into post_params .... bit of a hack but will do for now
-        params.iter().for_each(|(k,v)| {
+        // the method and pair are part of the url
+        params.iter().filter(|(k, _)| **k != "method" && **k != "pair").for_each(|(k,v)| {
             post_params.insert(k,v);
         });
 
@@ -135,12 +178,27 @@ impl BitstampApi {
             .uri(url)
             .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
             .body(post_data.into())
-            .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
+            .map_err(|e| Error::ServiceUnavailable(e.to_string()));
         let req2 = req.unwrap();
-        let resp = self.http_client.request(req2).await?;
+        let start = Instant::now();
+        let resp = self.http_client.request(req2).await.map_err(Error::from);
+        metrics::rest_request(Exchange::Bitstamp, method, start);
+        let resp = self.endpoints.track(resp)?;
+        self.rate_limits.observe(resp.headers(), helpers::get_unix_timestamp_ms());
         let code = resp.status().clone();
+        if self.debug_signing {
+            signing::log_response(Exchange::Bitstamp, method, code);
+        }
         if code.is_client_error() && code == StatusCode::FORBIDDEN {
-            return Err(ErrorKind::BadCredentials.into());
+            return Err(Error::BadCredentials);
+        }
+        if code == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = resp.headers().get(RETRY_AFTER)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|s| s.parse::<u64>().ok())
+                .map(Duration::from_secs);
+            self.rate_limits.exhausted(retry_after);
+            return Err(Error::RateLimited(retry_after));
         }
         let buf = hyper::body::aggregate(resp.into_body()).await?;
         let reader = buf.reader();
@@ -160,10 +218,10 @@ impl BitstampApi {
     /// "percentChange":"0.16701570","baseVolume":"0.45347489","quoteVolume":"9094"},
     /// ... }
     /// ```
-    pub async fn return_ticker(&mut self, pair: Pair) -> Result<Map<String, Value>> {
+    pub async fn return_ticker(&self, pair: Pair) -> Result<Map<String, Value>> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
 
         let mut params: HashMap<&str, &str> = HashMap::new();
@@ -172,16 +230,44 @@ impl BitstampApi {
         self.public_query(&params).await
     }
 
+    /// Markets of the exchange, the list is returned under `result`.
+    ///
+    /// Sample output :
+    ///
+    /// ```json
+    /// {"result": [{"name": "BTC/USD", "url_symbol": "btcusd", "trading": "Enabled", ...}, ...]}
+    /// ```
+    pub async fn return_trading_pairs_info(&self) -> Result<Map<String, Value>> {
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        params.insert("method", "trading-pairs-info");
+        params.insert("pair", "");
+        self.public_query(&params).await
+    }
+
+    /// Summary of the status page, `indicator` is one of none, minor, major, critical or
+    /// maintenance
+    ///
+    /// ```json
+    /// {"page":{"id":"..","name":"Bitstamp"},"status":{"indicator":"none","description":"All Systems Operational"}}
+    /// ```
+    pub async fn return_status(&self) -> Result<Map<String, Value>> {
+        let url: Uri = utils::STATUS_URL.parse().map_err(|_e| Error::BadParse)?;
+        let start = Instant::now();
+        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body()).map_err(Error::from)).await?;
+        metrics::rest_request(Exchange::Bitstamp, "status", start);
+        utils::deserialize_json_r(buf.reader())
+    }
+
     /// Sample output :
     ///
     /// ```json
     /// {"asks":[[0.00007600,1164],[0.00007620,1300], ... ], "bids":[[0.00006901,200],
     /// [0.00006900,408], ... ], "timestamp": "1234567890"}
     /// ```
-    pub async fn return_order_book(&mut self, pair: Pair) -> Result<Map<String, Value>> {
+    pub async fn return_order_book(&self, pair: Pair) -> Result<Map<String, Value>> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
 
         };
 
@@ -199,10 +285,10 @@ impl BitstampApi {
     /// {"date":"2014-02-10 01:19:37","type":"buy","rate":"0.00007600","amount":"655",
     /// "total":"0.04978"}, ... ]
     /// ```
-    pub async fn return_trade_history(&mut self, pair: Pair) -> Result<Map<String, Value>> {
+    pub async fn return_trade_history(&self, pair: Pair) -> Result<Map<String, Value>> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
 
         let mut params: HashMap<&str, &str> = HashMap::new();
@@ -219,68 +305,76 @@ impl BitstampApi {
     /// ```json
     /// {"BTC":"0.59098578","LTC":"3.31117268", ... }
     /// ```
-    pub async fn return_balances(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_balances(&self) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("method", "balance");
         params.insert("pair", "");
         self.private_query(&params).await
     }
 
-    /// Add a buy limit order to the exchange
-    /// limit_price	: If the order gets executed, a new sell order will be placed,
-    /// with "limit_price" as its price.
-    /// daily_order (Optional) : Opens buy limit order which will be canceled
-    /// at 0:00 UTC unless it already has been executed. Possible value: True
-    pub async fn buy_limit(&mut self,
+    /// Returns the transactions of the account on `pair`, most recent first. Trades have the
+    /// type 2, amounts are signed from the point of view of the account and the price is keyed by
+    /// the pair, the list is returned under `result`.
+    ///
+    /// Sample output :
+    ///
+    /// ```json
+    /// {"result": [{"id": 1357, "order_id": 24680, "type": "2", "datetime": "2018-11-01 10:17:35.215000",
+    /// "fee": "0.25000", "btc": "0.01000000", "usd": "-100.00", "btc_usd": 10000.0, "eur": 0.0}, ...]}
+    /// ```
+    pub async fn return_user_transactions(&self, pair: Pair) -> Result<Map<String, Value>> {
+        let pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let mut params = HashMap::new();
+        params.insert("method", "user_transactions");
+        params.insert("pair", pair_name);
+        self.private_query(&params).await
+    }
+
+    /// Add a buy limit order to the exchange, see `LimitOrderOptions` for the execution flags
+    pub async fn buy_limit(&self,
                      pair: Pair,
                      amount: Volume,
                      price: Price,
-                     price_limit: Option<Price>,
-                     daily_order: Option<bool>)
+                     options: LimitOrderOptions)
                      -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
-
-        let amount_string = amount.to_string();
-        let price_string = price.to_string();
-        let price_limit_string = match price_limit {
-            Some(limit) => limit.to_string(),
-            None => "".to_string(),
-        };
-
-        let mut params = HashMap::new();
-        params.insert("method", "buy");
-        params.insert("pair", pair_name);
-
-        params.insert("amount", &amount_string);
-        params.insert("price", &price_string);
-        params.insert("limit_price", &price_limit_string);
-        if let Some(order) = daily_order {
-            let daily_order_str = if order { "True" } else { "" }; // False is not a possible value
-            params.insert("daily_order", daily_order_str);
-        }
-
-        self.private_query(&params).await
+        self.limit_order("buy", pair, amount, price, None, options).await
     }
 
-    /// Add a sell limit order to the exchange
-    /// limit_price	: If the order gets executed, a new sell order will be placed,
-    /// with "limit_price" as its price.
-    /// daily_order (Optional) : Opens sell limit order which will be canceled
-    /// at 0:00 UTC unless it already has been executed. Possible value: True
-    pub async fn sell_limit(&mut self,
+    /// Add a sell limit order to the exchange, see `LimitOrderOptions` for the execution flags
+    pub async fn sell_limit(&self,
                       pair: Pair,
                       amount: Volume,
                       price: Price,
-                      price_limit: Option<Price>,
-                      daily_order: Option<bool>)
+                      options: LimitOrderOptions)
                       -> Result<Map<String, Value>> {
+        self.limit_order("sell", pair, amount, price, None, options).await
+    }
+
+    /// Add a buy stop order, placed as a limit order at `price` once the market reaches `stop_price`
+    pub async fn buy_stop(&self, pair: Pair, amount: Volume, stop_price: Price, price: Price) -> Result<Map<String, Value>> {
+        self.limit_order("buy", pair, amount, price, Some(stop_price), LimitOrderOptions::default()).await
+    }
+
+    /// Add a sell stop order, placed as a limit order at `price` once the market reaches `stop_price`
+    pub async fn sell_stop(&self, pair: Pair, amount: Volume, stop_price: Price, price: Price) -> Result<Map<String, Value>> {
+        self.limit_order("sell", pair, amount, price, Some(stop_price), LimitOrderOptions::default()).await
+    }
+
+    async fn limit_order(&self,
+                         side: &str,
+                         pair: Pair,
+                         amount: Volume,
+                         price: Price,
+                         stop_price: Option<Price>,
+                         options: LimitOrderOptions)
+                         -> Result<Map<String, Value>> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
+        if options.ioc_order && options.fok_order {
+            return Err(Error::InvalidArguments);
+        }
 
         let amount_string = format!("{:.8}", amount);
         let price_string = if pair == Pair::BTC_USD {
@@ -288,21 +382,26 @@ impl BitstampApi {
         } else {
             format!("{}", price)
         };
-        let price_limit_string = match price_limit {
-            Some(limit) => limit.to_string(),
-            None => "".to_string(),
-        };
+        let limit_price_string = options.limit_price.map(|p| p.to_string()).unwrap_or_default();
+        let stop_price_string = stop_price.map(|p| p.to_string()).unwrap_or_default();
 
         let mut params = HashMap::new();
-        params.insert("method", "sell");
+        params.insert("method", side);
         params.insert("pair", pair_name);
 
         params.insert("amount", &amount_string);
         params.insert("price", &price_string);
-        params.insert("limit_price", &price_limit_string);
-        if let Some(order) = daily_order {
-            let daily_order_str = if order { "True" } else { "" }; // False is not a possible value
-            params.insert("daily_order", daily_order_str);
+        params.insert("limit_price", &limit_price_string);
+        params.insert("stop_price", &stop_price_string);
+        // False is not a possible value of the flags
+        if options.daily_order {
+            params.insert("daily_order", "True");
+        }
+        if options.ioc_order {
+            params.insert("ioc_order", "True");
+        }
+        if options.fok_order {
+            params.insert("fok_order", "True");
         }
 
         self.private_query(&params).await
@@ -312,37 +411,59 @@ impl BitstampApi {
     /// By placing a market order you acknowledge that the execution of your order depends
     /// on the market conditions and that these conditions may be subject to sudden changes
     /// that cannot be foreseen.
-    pub async fn buy_market(&mut self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+    pub async fn buy_market(&self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
+        self.amount_order("buy/market", pair, amount).await
+    }
 
-        let amount_string = amount.to_string();
+    /// Add a market sell order to the exchange
+    /// By placing a market order you acknowledge that the execution of your order depends
+    /// on the market conditions and that these conditions may be subject to sudden changes
+    /// that cannot be foreseen.
+    pub async fn sell_market(&self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
+        self.amount_order("sell/market", pair, amount).await
+    }
 
-        let mut params = HashMap::new();
-        params.insert("method", "buy/market");
-        params.insert("pair", pair_name);
+    /// Add an instant buy order, `amount` is the quote currency amount to spend
+    pub async fn buy_instant(&self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
+        self.amount_order("buy/instant", pair, amount).await
+    }
 
-        params.insert("amount", &amount_string);
+    /// Add an instant sell order, `amount` is in base currency
+    pub async fn sell_instant(&self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
+        self.amount_order("sell/instant", pair, amount).await
+    }
+
+    /// Cancel the order `id`, returns the order
+    ///
+    /// ```json
+    /// {"id": 1453282316578816, "amount": "0.02035278", "price": "2100.45", "type": 0}
+    /// ```
+    pub async fn cancel_order(&self, id: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("method", "cancel_order");
+        params.insert("pair", "");
+        params.insert("id", id);
+        self.private_query(&params).await
+    }
 
+    /// Cancel every open order, `{"success": true}` when done
+    pub async fn cancel_all_orders(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("method", "cancel_all_orders");
+        params.insert("pair", "");
         self.private_query(&params).await
     }
 
-    /// Add a market sell order to the exchange
-    /// By placing a market order you acknowledge that the execution of your order depends
-    /// on the market conditions and that these conditions may be subject to sudden changes
-    /// that cannot be foreseen.
-    pub async fn sell_market(&mut self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
+    async fn amount_order(&self, method: &str, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
 
         let amount_string = amount.to_string();
 
         let mut params = HashMap::new();
-        params.insert("method", "sell/market");
+        params.insert("method", method);
         params.insert("pair", pair_name);
 
         params.insert("amount", &amount_string);
@@ -4,23 +4,36 @@ use serde_json;
 use serde_json::Value;
 
 use crate::coinnect::Credentials;
+use crate::env_credentials::EnvCredentials;
 use crate::exchange::Exchange;
 use crate::helpers;
+use crate::helpers::secret::Redacted;
 use crate::error::*;
 
 use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct BitstampCreds {
     exchange: Exchange,
     name: String,
     data: HashMap<String, String>,
 }
 
+impl fmt::Debug for BitstampCreds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BitstampCreds")
+            .field("exchange", &self.exchange)
+            .field("name", &self.name)
+            .field("data", &Redacted(&self.data))
+            .finish()
+    }
+}
+
 impl BitstampCreds {
     /// Create a new `BitstampCreds` from arguments.
     pub fn new(name: &str, api_key: &str, api_secret: &str, customer_id: &str) -> Self {
@@ -87,9 +100,9 @@ impl BitstampCreds {
 
         let data: Value = serde_json::from_str(&buffer)?;
         let json_obj = data.as_object()
-            .ok_or_else(|| ErrorKind::BadParse)?
+            .ok_or_else(|| Error::BadParse)?
             .get(name)
-            .ok_or_else(|| ErrorKind::MissingField(name.to_string()))?;
+            .ok_or_else(|| Error::MissingField(name.to_string()))?;
 
         let api_key = helpers::get_json_string(json_obj, "api_key")?;
         let api_secret = helpers::get_json_string(json_obj, "api_secret")?;
@@ -97,14 +110,29 @@ impl BitstampCreds {
         let exchange = {
             let exchange_str = helpers::get_json_string(json_obj, "exchange")?;
             Exchange::from_str(exchange_str)
-                .chain_err(|| ErrorKind::InvalidFieldValue("exchange".to_string()))?
+                .with_context(|| Error::InvalidFieldValue("exchange".to_string()))?
         };
 
         if exchange != Exchange::Bitstamp {
-            return Err(ErrorKind::InvalidConfigType(Exchange::Bitstamp, exchange).into());
+            return Err(Error::InvalidConfigType(Exchange::Bitstamp, exchange));
         }
 
-        Ok(BitstampCreds::new(name, api_key, api_secret, customer_id))
+        let mut creds = BitstampCreds::new(name, api_key, api_secret, customer_id);
+        helpers::copy_client_settings(json_obj, &mut creds.data);
+        Ok(creds)
+    }
+
+    /// Create a new `BitstampCreds` from the environment variables `<prefix>_BITSTAMP_API_KEY`, `<prefix>_BITSTAMP_API_SECRET`
+    /// and `<prefix>_BITSTAMP_CUSTOMER_ID`, see `EnvCredentials`.
+    /// The optional client settings are read the same way, e.g. `<prefix>_BITSTAMP_PROXY`.
+    pub fn new_from_env(prefix: &str) -> Result<Self> {
+        let env = EnvCredentials::new(prefix, Exchange::Bitstamp);
+        let api_key = env.require("api_key")?;
+        let api_secret = env.require("api_secret")?;
+        let customer_id = env.require("customer_id")?;
+        let mut creds = BitstampCreds::new(&env.name(), &api_key, &api_secret, &customer_id);
+        env.copy_client_settings(&mut creds.data);
+        Ok(creds)
     }
 }
 
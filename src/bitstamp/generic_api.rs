@@ -2,117 +2,162 @@
 //! This a more convenient and safe way to deal with the exchange since methods return a Result<>
 //! but this generic API does not provide all the functionnality that Bitstamp offers.
 
-use crate::exchange::{ExchangeApi};
-use crate::bitstamp::api::BitstampApi;
+use crate::exchange::{settled_fill, Exchange, ExchangeApi, ExchangeCapabilities};
+use crate::bitstamp::api::{BitstampApi, LimitOrderOptions};
 use crate::bitstamp::utils;
 
 use crate::error::*;
 use crate::types::*;
 use crate::helpers;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 #[async_trait]
 impl ExchangeApi for BitstampApi {
-    async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
         let result = self.return_ticker(pair).await?;
-        let price = helpers::from_json_bigdecimal(&result["last"], "last")?;
-        let ask = helpers::from_json_bigdecimal(&result["ask"], "ask")?;
-        let bid = helpers::from_json_bigdecimal(&result["bid"], "bid")?;
-        let vol = helpers::from_json_bigdecimal(&result["volume"], "volume")?;
-        Ok(Ticker {
-            timestamp: helpers::get_unix_timestamp_ms(),
-            pair: pair,
-            last_trade_price: price,
-            lowest_ask: ask,
-            highest_bid: bid,
-            volume: Some(vol),
-        })
+        utils::parse_ticker(pair, &result, self.keep_raw)
     }
 
-    async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
 
         let raw_response = self.return_order_book(pair).await?;
 
         let result = utils::parse_result(&raw_response)?;
 
-        let mut ask_offers = Vec::new();
-        let mut bid_offers = Vec::new();
-
-        let ask_array =
-            result["asks"]
-                .as_array()
-                .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["asks"])))?;
-        let bid_array =
-            result["bids"]
-                .as_array()
-                .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["asks"])))?;
-
-        for ask in ask_array {
-            let price = helpers::from_json_bigdecimal(&ask[0], "ask price")?;
-            let volume = helpers::from_json_bigdecimal(&ask[1], "ask volume")?;
-
-            ask_offers.push((price, volume));
-        }
-
-        for bid in bid_array {
-            let price = helpers::from_json_bigdecimal(&bid[0], "bid price")?;
-            let volume = helpers::from_json_bigdecimal(&bid[1], "bid volume")?;
-
-            bid_offers.push((price, volume));
-        }
-
-        Ok(Orderbook {
-            timestamp: helpers::get_unix_timestamp_ms(),
-            pair: pair,
-            asks: ask_offers,
-            bids: bid_offers,
-        })
+        utils::parse_orderbook(pair, &result)
     }
 
-    async fn add_order(&mut self,
+    async fn add_order(&self,
                  order_type: OrderType,
                  pair: Pair,
                  quantity: Volume,
                  price: Option<Price>)
                  -> Result<OrderInfo> {
+        let request = OrderRequest::new(order_type, pair, &quantity, &price);
         //let pair_name = match utils::get_pair_string(&pair) {
         //Some(name) => name,
-        //None => return Err(ErrorKind::PairUnsupported.into()),
+        //None => return Err(Error::PairUnsupported),
         //};
 
         let result = match order_type {
             OrderType::BuyLimit => {
                 if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
+                    return Err(Error::MissingPrice);
                 }
 
                 // Unwrap safe here with the check above.
-                self.buy_limit(pair, quantity, price.unwrap(), None, None).await
+                self.buy_limit(pair, quantity, price.unwrap(), LimitOrderOptions::default()).await
             }
             OrderType::BuyMarket => self.buy_market(pair, quantity).await,
             OrderType::SellLimit => {
                 if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
+                    return Err(Error::MissingPrice);
                 }
 
                 // Unwrap safe here with the check above.
-                self.sell_limit(pair, quantity, price.unwrap(), None, None).await
+                self.sell_limit(pair, quantity, price.unwrap(), LimitOrderOptions::default()).await
             }
             OrderType::SellMarket => self.sell_market(pair, quantity).await,
+            _ => return Err(Error::OrderTypeUnsupported),
         }?;
         Ok(OrderInfo {
                timestamp: helpers::get_unix_timestamp_ms(),
                identifier: vec![result["id"]
                                     .as_str()
                                     .ok_or_else(|| {
-                                                    ErrorKind::MissingField("id".to_string())
+                                                    Error::MissingField("id".to_string())
                                                 })?
                                     .to_string()],
+               raw: helpers::raw_payload(self.keep_raw, &result),
+               request: Some(request),
            })
     }
 
+    /// Limit orders are sent with the `ioc_order` flag
+    async fn add_ioc_order(&self,
+                           order_type: OrderType,
+                           pair: Pair,
+                           quantity: Volume,
+                           price: Price)
+                           -> Result<Execution> {
+        let request = OrderRequest::new(order_type, pair, &quantity, &Some(price.clone()));
+        let options = LimitOrderOptions { ioc_order: true, ..LimitOrderOptions::default() };
+        let result = match order_type {
+            OrderType::BuyLimit => self.buy_limit(pair, quantity, price, options).await,
+            OrderType::SellLimit => self.sell_limit(pair, quantity, price, options).await,
+            _ => return Err(Error::OrderTypeUnsupported),
+        }?;
+        let id = result["id"].as_str().ok_or_else(|| Error::MissingField("id".to_string()))?;
+        let order = OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: vec![id.to_string()],
+            raw: helpers::raw_payload(self.keep_raw, &result),
+            request: Some(request),
+        };
+        let filled = settled_fill(self, pair, &order).await;
+        Ok(Execution { order, filled })
+    }
+
+    /// Only stop limit orders are native
+    async fn add_conditional_order(&self,
+                             order_type: OrderType,
+                             pair: Pair,
+                             quantity: Volume,
+                             trigger_price: Price,
+                             price: Option<Price>)
+                             -> Result<OrderInfo> {
+        let request = OrderRequest::conditional(order_type, pair, &quantity, &trigger_price, &price);
+        let result = match order_type {
+            OrderType::StopLimit(side) => {
+                let price = price.ok_or(Error::MissingPrice)?;
+                match side {
+                    Side::Buy => self.buy_stop(pair, quantity, trigger_price, price).await,
+                    Side::Sell => self.sell_stop(pair, quantity, trigger_price, price).await,
+                }
+            }
+            _ => return Err(Error::OrderTypeUnsupported),
+        }?;
+        Ok(OrderInfo {
+               timestamp: helpers::get_unix_timestamp_ms(),
+               identifier: vec![result["id"]
+                                    .as_str()
+                                    .ok_or_else(|| {
+                                                    Error::MissingField("id".to_string())
+                                                })?
+                                    .to_string()],
+               raw: helpers::raw_payload(self.keep_raw, &result),
+               request: Some(request),
+           })
+    }
+
+    async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+        for id in &order.identifier {
+            let raw_response = self.cancel_order(id).await?;
+            utils::parse_result(&raw_response)?;
+        }
+        Ok(())
+    }
+
+    async fn cancel_all_orders(&self) -> Result<()> {
+        let raw_response = self.cancel_all_orders().await?;
+        utils::parse_result(&raw_response)?;
+        Ok(())
+    }
+
+    /// The balance lists the fee of every pair in percent, e.g. `"btcusd_fee": "0.500"`
+    async fn fees(&self, pair: Pair) -> Result<FeeRates> {
+        let pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let raw_response = self.return_balances().await?;
+        let result = utils::parse_result(&raw_response)?;
+        match helpers::from_json_bigdecimal_opt(&result.get(&format!("{}_fee", pair_name)).cloned().unwrap_or_default()) {
+            Some(fee) => Ok(FeeRates::from_percent(fee.clone(), fee)),
+            None => Ok(Exchange::Bitstamp.default_fees()),
+        }
+    }
+
     /// Return the balances for each currency on the account
-    async fn balances(&mut self) -> Result<Balances> {
+    async fn balances(&self) -> Result<Balances> {
         let raw_response = self.return_balances().await?;
         let result = utils::parse_result(&raw_response)?;
 
@@ -133,4 +178,95 @@ impl ExchangeApi for BitstampApi {
 
         Ok(balances)
     }
+
+    /// The balance gives `{currency}_available` and `{currency}_reserved` for every currency
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        let raw_response = self.return_balances().await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let mut balances = DetailedBalances::new();
+
+        for key in result.keys() {
+            let currency = utils::get_currency_enum(key);
+            if let (Some(c), Some(name)) = (currency, key.split('_').next()) {
+                let field = |suffix: &str| result.get(&format!("{}_{}", name, suffix)).cloned().unwrap_or_default();
+                let available = helpers::from_json_bigdecimal(&field("available"), "available")?;
+                let reserved = helpers::from_json_bigdecimal(&field("reserved"), "reserved")?;
+                balances.insert(c, Balance::new(available, reserved));
+            }
+        }
+
+        Ok(balances)
+    }
+
+    /// Trades among the transactions of the account, the fee is charged in the quote currency
+    async fn fills(&self, pair: Pair) -> Result<Vec<FillEvent>> {
+        let raw_response = self.return_user_transactions(pair).await?;
+        let result = utils::parse_result(&raw_response)?;
+        let transactions = result.get("result").and_then(|r| r.as_array()).ok_or_else(|| Error::InvalidFieldFormat("result".to_string()))?;
+        let mut fills = transactions.iter()
+            .filter(|t| utils::json_text(&t["type"]) == "2")
+            .map(|t| utils::parse_fill(pair, t))
+            .collect::<Result<Vec<FillEvent>>>()?;
+        fills.sort_by_key(|f| f.event_ms);
+        Ok(fills)
+    }
+
+    /// The transactions of the last hour
+    async fn public_trades(&self, pair: Pair, since: Option<DateTime<Utc>>) -> Result<Vec<PublicTrade>> {
+        let raw_response = self.return_trade_history(pair).await?;
+        let result = utils::parse_result(&raw_response)?;
+        let transactions = result.get("result").and_then(|r| r.as_array()).ok_or_else(|| Error::InvalidFieldFormat("result".to_string()))?;
+        let since_ms = since.map_or(i64::MIN, |s| s.timestamp_millis());
+        let mut trades = vec![];
+        for t in transactions {
+            let date = utils::json_text(&t["date"]);
+            let event_ms = date.parse::<i64>().map_err(|_| Error::InvalidFieldFormat(date.clone()))? * 1000;
+            if event_ms <= since_ms {
+                continue;
+            }
+            trades.push(PublicTrade {
+                id: Some(utils::json_text(&t["tid"])),
+                event_ms,
+                pair,
+                price: helpers::from_json_bigdecimal(&t["price"], "price")?,
+                amount: helpers::from_json_bigdecimal(&t["amount"], "amount")?,
+                side: utils::get_trade_type(utils::json_text(&t["type"]).parse::<i64>().unwrap_or(-1)),
+            });
+        }
+        trades.sort_by_key(|t| t.event_ms);
+        Ok(trades)
+    }
+
+    /// From the status page, incidents are reported as degraded
+    async fn status(&self) -> Result<ExchangeStatus> {
+        let sent_ms = helpers::get_unix_timestamp_ms();
+        let result = self.return_status().await?;
+        let latency_ms = (helpers::get_unix_timestamp_ms() - sent_ms).max(0) as u64;
+        let status = result.get("status").ok_or_else(|| Error::MissingField("status".to_string()))?;
+        let indicator = status["indicator"].as_str().ok_or_else(|| Error::MissingField("indicator".to_string()))?;
+        Ok(ExchangeStatus {
+            state: utils::get_exchange_state(indicator),
+            latency_ms,
+            message: status["description"].as_str().map(|d| d.to_string()),
+        })
+    }
+
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        let raw_response = self.return_trading_pairs_info().await?;
+        let markets = raw_response["result"].as_array().ok_or(Error::BadParse)?;
+        // Disabled markets are listed but cannot be traded
+        Ok(markets.iter()
+            .filter(|m| m["trading"] == "Enabled")
+            .filter_map(|m| m["url_symbol"].as_str().and_then(utils::get_pair_enum).copied())
+            .collect())
+    }
+
+    fn limits(&self) -> RateLimitStatus {
+        self.rate_limits.status()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Bitstamp.capabilities()
+    }
 }
@@ -1,10 +1,16 @@
 //! Use this module to interact with Bitstamp exchange.
 
+#[cfg(feature = "native")]
 pub mod api;
+#[cfg(feature = "native")]
 pub mod generic_api;
+#[cfg(feature = "native")]
 pub mod credentials;
 pub mod utils;
 pub mod models;
+#[cfg(feature = "streaming")]
 pub mod streaming_api;
+#[cfg(feature = "native")]
 pub use self::credentials::BitstampCreds;
-pub use self::api::BitstampApi;
+#[cfg(feature = "native")]
+pub use self::api::{BitstampApi, LimitOrderOptions};
@@ -1,6 +1,8 @@
 use serde::{Serialize, Deserialize};
 use serde_json;
 use std::collections::HashSet;
+use crate::error::*;
+use crate::exchange::Exchange;
 use crate::types::{LiveEvent, Pair, Channel};
 use crate::types;
 use bigdecimal::BigDecimal;
@@ -25,8 +27,24 @@ pub struct LiveTrade {
 pub struct LiveOrderBook {
     microtimestamp: String,
     timestamp: String,
-    bids: Vec<(String, String)>,
-    asks: Vec<(String, String)>,
+    /// `[price, amount]`, followed by the order id on the `detail_order_book` channel
+    bids: Vec<Vec<String>>,
+    asks: Vec<Vec<String>>,
+}
+
+/// The `microtimestamp` of an event in ms
+fn event_ms(microtimestamp: &str) -> Option<i64> {
+    microtimestamp.parse::<i64>().ok().map(|us| us / 1000)
+}
+
+/// `None` for a level too short or whose price or amount is not a number
+fn level(l: &[String]) -> Option<(BigDecimal, BigDecimal)> {
+    Some((l.get(0)?.parse::<BigDecimal>().ok()?, l.get(1)?.parse::<BigDecimal>().ok()?))
+}
+
+fn book_order(l: &[String]) -> Option<types::BookOrder> {
+    let (price, amount) = level(l)?;
+    Some(types::BookOrder { id: l.get(2).cloned().unwrap_or_default(), price, amount })
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -58,46 +76,128 @@ pub enum Event {
     SubSucceeded(PlainEvent),
     #[serde(alias = "bts:request_reconnect")]
     ReconnectRequest(PlainEvent),
+    #[serde(alias = "bts:error")]
+    Error(PlainEvent),
     #[serde(alias = "trade")]
     LiveTrade(Enveloppe<LiveTrade>),
     #[serde(alias = "data")]
     LiveFullOrderBook(Enveloppe<LiveOrderBook>),
-    #[serde(alias = "order_created", alias = "order_deleted")]
-    LiveOrder(Enveloppe<LiveOrder>),
+    #[serde(alias = "order_created")]
+    OrderCreated(Enveloppe<LiveOrder>),
+    #[serde(alias = "order_changed")]
+    OrderChanged(Enveloppe<LiveOrder>),
+    #[serde(alias = "order_deleted")]
+    OrderDeleted(Enveloppe<LiveOrder>),
+}
+
+impl<T> Enveloppe<T> {
+    /// The pair of the channel, named like `live_trades_btcusd`
+    fn pair(&self) -> Option<Pair> {
+        self.channel.rsplit('_').next().and_then(utils::get_pair_enum).copied()
+    }
+}
+
+impl PlainEvent {
+    /// The channel of a subscription, named like `live_trades_btcusd`
+    fn live_channel(&self) -> Option<Channel> {
+        match &self.channel[..self.channel.rfind('_')?] {
+            "live_trades" => Some(Channel::LiveTrades),
+            "live_orders" => Some(Channel::LiveOrders),
+            "order_book" => Some(Channel::LiveOrderBook),
+            "detail_order_book" => Some(Channel::LiveDetailOrderBook),
+            "diff_order_book" => Some(Channel::LiveFullOrderBook),
+            _ => None,
+        }
+    }
+}
+
+impl Enveloppe<LiveOrder> {
+    fn into_live_order(self, kind: types::OrderEventKind) -> LiveEvent {
+        match (self.pair(), event_ms(&self.data.microtimestamp)) {
+            (Some(pair), Some(event_ms)) => LiveEvent::LiveOrder(types::LiveOrder {
+                event_ms,
+                id: Some(self.data.id.to_string()),
+                kind,
+                amount: self.data.amount_str.parse::<BigDecimal>().unwrap_or_else(|_| self.data.amount.into()),
+                price: self.data.price_str.parse::<BigDecimal>().unwrap_or_else(|_| self.data.price.into()),
+                tt: utils::get_trade_type(self.data.order_type),
+                pair,
+            }),
+            _ => LiveEvent::Noop,
+        }
+    }
+}
+
+impl Enveloppe<LiveOrderBook> {
+    /// Whether the levels are changes of the `diff_order_book` channel rather than a whole book
+    pub fn is_diff(&self) -> bool {
+        self.channel.starts_with("diff_order_book")
+    }
+
+    /// Whether the levels are orders of the `detail_order_book` channel
+    pub fn is_detail(&self) -> bool {
+        self.channel.starts_with("detail_order_book")
+    }
 }
 
 impl Into<LiveEvent> for Event {
     fn into(self) -> LiveEvent {
         match self {
-            Event::LiveOrder(e) => LiveEvent::LiveOrder(types::LiveOrder{
-                amount: e.data.amount,
-                event_ms: e.data.microtimestamp.parse::<i64>().unwrap(),
-                price: e.data.price.into(),
-                tt: e.data.order_type.into(),
-                pair: utils::get_pair_string(&Pair::BTC_USD).unwrap().to_string(),
-            }),
-            Event::LiveTrade(e) => LiveEvent::LiveTrade(types::LiveTrade{
-                amount: e.data.amount,
-                event_ms: e.data.microtimestamp.parse::<i64>().unwrap(),
-                price: e.data.price.into(),
-                tt: e.data.ty.into(),
-                pair: utils::get_pair_string(&Pair::BTC_USD).unwrap().to_string(),
-            }),
+            Event::SubSucceeded(e) => match (e.live_channel(), e.pair()) {
+                (Some(channel), Some(pair)) => LiveEvent::SubscriptionResult(types::SubscriptionResult::accepted(channel, pair)),
+                _ => LiveEvent::Noop,
+            },
+            // Errors of malformed requests are not bound to a channel
+            Event::Error(e) => {
+                let reason = e.data["message"].as_str().unwrap_or_default();
+                match (e.live_channel(), e.pair()) {
+                    (Some(channel), Some(pair)) => LiveEvent::SubscriptionResult(types::SubscriptionResult::rejected(channel, pair, reason)),
+                    _ => {
+                        warn!("Bitstamp : {}", reason);
+                        LiveEvent::Noop
+                    }
+                }
+            }
+            Event::OrderCreated(e) => e.into_live_order(types::OrderEventKind::Created),
+            Event::OrderChanged(e) => e.into_live_order(types::OrderEventKind::Changed),
+            Event::OrderDeleted(e) => e.into_live_order(types::OrderEventKind::Deleted),
+            Event::LiveTrade(e) => match (e.pair(), event_ms(&e.data.microtimestamp)) {
+                (Some(pair), Some(event_ms)) => LiveEvent::LiveTrade(types::LiveTrade{
+                    amount: e.data.amount_str.parse::<BigDecimal>().unwrap_or_else(|_| e.data.amount.into()),
+                    event_ms,
+                    price: e.data.price.into(),
+                    tt: utils::get_trade_type(e.data.ty),
+                    pair,
+                    meta: types::TradeMeta::with_id(e.data.id),
+                }),
+                _ => LiveEvent::Noop,
+            },
             Event::LiveFullOrderBook(e) => {
-                let asks = e.data.asks;
-                let bids = e.data.bids;
-                LiveEvent::LiveOrderbook(types::Orderbook {
-                    asks: asks.into_iter().map(|(p, v)| {
-                        (p.parse::<BigDecimal>().unwrap(),
-                         v.parse::<BigDecimal>().unwrap())
-                    }).collect(),
-                    bids: bids.into_iter().map(|(p, v)| {
-                        (p.parse::<BigDecimal>().unwrap(),
-                         v.parse::<BigDecimal>().unwrap())
-                    }).collect(),
-                    timestamp: e.data.microtimestamp.parse::<i64>().unwrap(),
-                    pair: Pair::BTC_USD,
-                })
+                let (pair, timestamp) = match (e.pair(), event_ms(&e.data.microtimestamp)) {
+                    (Some(pair), Some(timestamp)) => (pair, timestamp),
+                    _ => return LiveEvent::Noop,
+                };
+                // A book missing a malformed level would be wrong, it is dropped whole
+                if e.is_detail() {
+                    let asks = e.data.asks.iter().map(|l| book_order(l)).collect::<Option<Vec<_>>>();
+                    let bids = e.data.bids.iter().map(|l| book_order(l)).collect::<Option<Vec<_>>>();
+                    return match (asks, bids) {
+                        (Some(asks), Some(bids)) => LiveEvent::LiveDetailOrderbook(types::DetailOrderbook { asks, bids, timestamp, pair }),
+                        _ => {
+                            warn!("Bitstamp : malformed level in {}", e.channel);
+                            LiveEvent::Noop
+                        }
+                    };
+                }
+                let asks = e.data.asks.iter().map(|l| level(l)).collect::<Option<Vec<_>>>();
+                let bids = e.data.bids.iter().map(|l| level(l)).collect::<Option<Vec<_>>>();
+                match (asks, bids) {
+                    (Some(asks), Some(bids)) => LiveEvent::LiveOrderbook(types::Orderbook { asks, bids, timestamp, pair }),
+                    _ => {
+                        warn!("Bitstamp : malformed level in {}", e.channel);
+                        LiveEvent::Noop
+                    }
+                }
             },
             _ => LiveEvent::Noop
         }
@@ -116,20 +216,24 @@ pub struct Subscription {
     data: Data,
 }
 
-pub fn subscription(c: Channel, currency_pair: &str) -> Subscription {
+/// Fails with `Error::ChannelUnsupported` for the channels Bitstamp does not stream
+pub fn subscription(c: Channel, currency_pair: &str) -> Result<Subscription> {
     let channel_str = match c {
         Channel::LiveTrades => "live_trades",
         Channel::LiveOrders => "live_orders",
         Channel::LiveOrderBook => "order_book",
         Channel::LiveDetailOrderBook => "detail_order_book",
         Channel::LiveFullOrderBook => "diff_order_book",
+        // No native candles, aggregated from live trades
+        Channel::LiveCandles => "live_trades",
+        Channel::LiveSummary => return Err(Error::ChannelUnsupported(Exchange::Bitstamp, c)),
     };
-    Subscription {
+    Ok(Subscription {
         event: String::from("bts:subscribe"),
         data: Data {
             channel: format!("{}_{}", channel_str, currency_pair)
         },
-    }
+    })
 }
 
 lazy_static! {
@@ -149,4 +253,62 @@ mod model_tests {
     fn deserialize_sub_succeeded() {
         let _v: Event = serde_json::from_slice(b"{\"data\": {\"microtimestamp\": \"1577146143220559\", \"amount\": 0.00434678, \"buy_order_id\": 4481152330, \"sell_order_id\": 4481152280, \"amount_str\": \"0.00434678\", \"price_str\": \"7312.91\", \"timestamp\": \"1577146143\", \"price\": 7312.91, \"type\": 0, \"id\": 102177815}, \"event\": \"trade\", \"channel\": \"live_trades_btcusd\"}").unwrap();
     }
+
+    #[test]
+    fn summaries_are_not_subscribed() {
+        assert!(subscription(Channel::LiveSummary, "btcusd").is_err());
+        assert!(subscription(Channel::LiveTrades, "btcusd").is_ok());
+    }
+
+    #[test]
+    fn subscriptions_are_reported() {
+        let v: Event = serde_json::from_slice(b"{\"event\": \"bts:subscription_succeeded\", \"channel\": \"diff_order_book_btcusd\", \"data\": {}}").unwrap();
+        let le: LiveEvent = v.into();
+        match le {
+            LiveEvent::SubscriptionResult(r) => {
+                assert_eq!((r.channel, r.pair, r.success), (Channel::LiveFullOrderBook, Pair::BTC_USD, true));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        let v: Event = serde_json::from_slice(b"{\"event\": \"bts:error\", \"channel\": \"\", \"data\": {\"code\": null, \"message\": \"Incorrect JSON format.\"}}").unwrap();
+        let le: LiveEvent = v.into();
+        assert_eq!(crate::metrics::channel_label(&le), "noop");
+    }
+
+    #[test]
+    fn detail_books_keep_the_order_ids() {
+        let v: Event = serde_json::from_slice(b"{\"data\": {\"timestamp\": \"1617013236\", \"microtimestamp\": \"1617013236146000\", \"bids\": [[\"57806.49\", \"0.5\", \"1353482387406848\"], [\"57806.49\", \"0.25\", \"1353482387406849\"]], \"asks\": [[\"57810\", \"1\", \"1353482387406850\"]]}, \"channel\": \"detail_order_book_btcusd\", \"event\": \"data\"}").unwrap();
+        match v.into() {
+            LiveEvent::LiveDetailOrderbook(ob) => {
+                assert_eq!(ob.orders()["1353482387406849"].amount, "0.25".parse::<BigDecimal>().unwrap());
+                assert_eq!(ob.aggregated().bids, vec![("57806.49".parse::<BigDecimal>().unwrap(), "0.75".parse::<BigDecimal>().unwrap())]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn malformed_levels_drop_the_book() {
+        let v: Event = serde_json::from_slice(b"{\"data\": {\"timestamp\": \"1617013236\", \"microtimestamp\": \"1617013236146000\", \"bids\": [[\"57806.49\"]], \"asks\": [[\"57810\", \"1\"]]}, \"channel\": \"order_book_btcusd\", \"event\": \"data\"}").unwrap();
+        let le: LiveEvent = v.into();
+        assert_eq!(crate::metrics::channel_label(&le), "noop");
+        let v: Event = serde_json::from_slice(b"{\"data\": {\"timestamp\": \"1617013236\", \"microtimestamp\": \"1617013236146000\", \"bids\": [[\"57806.49\", \"0.5\", \"1\"]], \"asks\": [[\"abc\", \"1\", \"2\"]]}, \"channel\": \"detail_order_book_btcusd\", \"event\": \"data\"}").unwrap();
+        let le: LiveEvent = v.into();
+        assert_eq!(crate::metrics::channel_label(&le), "noop");
+    }
+
+    #[test]
+    fn live_orders_carry_their_kind() {
+        let v: Event = serde_json::from_slice(b"{\"data\": {\"id\": 1353482387406848, \"id_str\": \"1353482387406848\", \"order_type\": 1, \"datetime\": \"1617013236\", \"microtimestamp\": \"1617013236146000\", \"amount\": 0.5, \"amount_str\": \"0.50000000\", \"price\": 57806.49, \"price_str\": \"57806.49\"}, \"channel\": \"live_orders_btcusd\", \"event\": \"order_changed\"}").unwrap();
+        match v.into() {
+            LiveEvent::LiveOrder(o) => {
+                assert_eq!(o.kind, types::OrderEventKind::Changed);
+                assert_eq!(o.id.as_deref(), Some("1353482387406848"));
+                assert_eq!(o.price, "57806.49".parse::<BigDecimal>().unwrap());
+                assert_eq!(o.pair, Pair::BTC_USD);
+                assert_eq!(o.event_ms, 1617013236146);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
 }
@@ -1,51 +1,215 @@
+//! Stream the live channels of Bitstamp.
+//! The `diff_order_book` channel only sends the levels that changed, its books are seeded with
+//! a REST snapshot fetched after every subscription. Changes received while the snapshot is
+//! pending are buffered, and those not older than the snapshot are replayed on top of it.
+
+use crate::bitstamp::api::BitstampApi;
 use crate::coinnect::Credentials;
-use crate::exchange_bot::{DefaultWsActor, WsHandler, ExchangeBot};
+use crate::exchange_bot::{self, DefaultWsActor, ObserveRawFrames, RawFrameEvent, WsHandler, ExchangeBot, Snapshot};
+use crate::exchange::ExchangeApi;
 use crate::error::*;
 use super::models::*;
 use bytes::Bytes;
 use bytes::Buf;
+use futures::future::LocalBoxFuture;
 use futures::stream::{SplitSink};
 use actix::{io::SinkWrite, Addr, Recipient, Context, Actor};
 use awc::{
     ws::{Codec, Message},  BoxedSocket
 };
 use actix_codec::{Framed};
-use crate::types::{LiveEvent, Channel, LiveEventEnveloppe, Pair};
-use crate::exchange::Exchange;
-use std::collections::{HashMap, HashSet};
+use crate::types::{LiveEvent, Channel, LiveAggregatedOrderBook, LiveEventEnveloppe, LiveTrade, Orderbook, Pair, SubscriptionResult, TradeTape, WarmUp};
+use crate::exchange::{Exchange, ExchangeCapabilities};
+use crate::helpers::delivery::{Delivery, DeliveryPolicy};
+use crate::helpers::failover::Endpoints;
+use crate::helpers::secret::SecretString;
+use crate::metrics;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use std::sync::Arc;
 use std::time::Duration;
 use async_trait::async_trait;
 
+static WEBSOCKET_URL: &'static str = "wss://ws.bitstamp.net";
+
+/// Delay before fetching again an order book snapshot that failed
+const SNAPSHOT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Changes kept per book while its snapshot is pending, the oldest are dropped beyond
+const MAX_PENDING_DIFFS: usize = 10_000;
+
 pub struct BitstampBot {
-    addr: Addr<DefaultWsActor>
+    addr: Addr<DefaultWsActor>,
+    endpoints: Arc<Endpoints>,
 }
 
 impl ExchangeBot for BitstampBot {
     fn is_connected(&self) -> bool {
-        unimplemented!()
+        self.addr.connected()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Bitstamp.capabilities()
+    }
+
+    fn observe_raw_frames(&self, recipient: Recipient<RawFrameEvent>) -> Result<()> {
+        self.addr.do_send(ObserveRawFrames(Exchange::Bitstamp, recipient));
+        Ok(())
+    }
+
+    fn endpoints(&self) -> Option<Arc<Endpoints>> {
+        Some(self.endpoints.clone())
+    }
+
+    fn shutdown(&self) -> LocalBoxFuture<'static, ()> {
+        exchange_bot::shutdown_ws(&self.addr)
+    }
+}
+
+impl BitstampBot {
+    /// The current order book and latest trade of `pair`
+    pub async fn snapshot(&self, pair: Pair) -> Result<Option<Snapshot>> {
+        exchange_bot::query_snapshot(&self.addr, pair).await
+    }
+}
+
+/// A book of the `diff_order_book` channel and the time of the snapshot it was seeded with
+#[derive(Debug)]
+struct DiffBook {
+    agg: LiveAggregatedOrderBook,
+    snapshot_ms: i64,
+    /// Changes received since the subscription, until the snapshot is applied
+    pending: Option<VecDeque<Orderbook>>,
+}
+
+impl DiffBook {
+    fn new(pair: Pair, depth: Option<i8>) -> Self {
+        DiffBook {
+            agg: LiveAggregatedOrderBook::with_warm_up(pair, WarmUp::Disabled).with_depth(depth.unwrap_or(i8::MAX)),
+            snapshot_ms: 0,
+            pending: Some(VecDeque::new()),
+        }
+    }
+
+    /// Apply a change, or buffer it while the snapshot is pending. Returns the book if its top changed.
+    fn apply(&mut self, diff: Orderbook) -> Option<Orderbook> {
+        if let Some(pending) = self.pending.as_mut() {
+            if pending.len() == MAX_PENDING_DIFFS {
+                warn!("Bitstamp : too many changes of {:?} before its snapshot, dropping the oldest", diff.pair);
+                pending.pop_front();
+            }
+            pending.push_back(diff);
+            return None;
+        }
+        if diff.timestamp < self.snapshot_ms {
+            return None;
+        }
+        self.agg.update_asks(diff.asks.into_iter());
+        self.agg.update_bids(diff.bids.into_iter());
+        self.agg.latest_order_book()
+    }
+
+    /// Clear the book until the next snapshot
+    fn resync(&mut self) {
+        self.agg.resync();
+        self.pending = Some(VecDeque::new());
+    }
+
+    /// Seed the book with a REST snapshot and replay the buffered changes that are not older.
+    /// Changes carry the amounts of their levels, those of the same ms as the snapshot can be
+    /// applied again.
+    fn seed(&mut self, snapshot: Orderbook) {
+        self.snapshot_ms = snapshot.timestamp;
+        self.agg.reset_asks(snapshot.asks.into_iter());
+        self.agg.reset_bids(snapshot.bids.into_iter());
+        for diff in self.pending.take().unwrap_or_default() {
+            self.apply(diff);
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct BitstampStreamingApi {
-    api_key: String,
-    api_secret: String,
+    api_key: SecretString,
+    api_secret: SecretString,
     customer_id: String,
-    pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    delivery: Delivery<LiveEventEnveloppe>,
     channels: HashMap<Channel, HashSet<Pair>>,
+    warm_up: WarmUp,
+    /// Levels kept on each side of the order books, all the streamed levels when not set
+    depth: Option<i8>,
+    warm_pairs: HashSet<Pair>,
+    books: HashMap<Pair, Orderbook>,
+    diff_books: Rc<RefCell<HashMap<Pair, DiffBook>>>,
+    rest: Arc<BitstampApi>,
+    last_trades: HashMap<Pair, LiveTrade>,
+    tape: TradeTape,
 }
 
 impl BitstampStreamingApi {
-    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BitstampBot> {
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, warm_up: WarmUp, depth: Option<i8>, delivery: DeliveryPolicy, recipients: Vec<Recipient<LiveEventEnveloppe>>, raw_frames: Option<Recipient<RawFrameEvent>>) -> Result<BitstampBot> {
+        creds.environment()?.production_only(Exchange::Bitstamp)?;
+        let connection = creds.connection();
+        let diff_books = channels.get(&Channel::LiveFullOrderBook).into_iter().flatten()
+            .map(|&pair| (pair, DiffBook::new(pair, depth)))
+            .collect();
         let api = BitstampStreamingApi {
-            api_key: creds.get("api_key").unwrap_or_default(),
-            api_secret: creds.get("api_secret").unwrap_or_default(),
+            api_key: creds.get("api_key").unwrap_or_default().into(),
+            api_secret: creds.get("api_secret").unwrap_or_default().into(),
             customer_id: creds.get("customer_id").unwrap_or_default(),
-            recipients,
+            delivery: Delivery::new(recipients, delivery),
             channels,
+            warm_up,
+            depth,
+            warm_pairs: HashSet::new(),
+            books: HashMap::new(),
+            diff_books: Rc::new(RefCell::new(diff_books)),
+            rest: Arc::new(BitstampApi::new(*creds)?),
+            last_trades: HashMap::new(),
+            tape: TradeTape::default(),
         };
-        let addr = DefaultWsActor::new("BitstampStream", "wss://ws.bitstamp.net", Some(Duration::from_secs(5)), Box::new(api)).await?;
-        Ok(BitstampBot { addr })
+        let endpoints = Arc::new(connection.ws_endpoints(Exchange::Bitstamp, WEBSOCKET_URL));
+        let addr = DefaultWsActor::new("BitstampStream", endpoints.clone(), connection.proxy()?, Some(Duration::from_secs(5)), raw_frames.map(|r| (Exchange::Bitstamp, r)).into_iter().collect(), Box::new(api)).await?;
+        Ok(BitstampBot { addr, endpoints })
+    }
+
+    /// Apply the changes of the `diff_order_book` channel, returns the book if its top changed
+    fn apply_diff(&mut self, diff: Orderbook) -> Option<Orderbook> {
+        self.diff_books.borrow_mut().get_mut(&diff.pair)?.apply(diff)
+    }
+
+    /// Clear the books of the `diff_order_book` channel and seed them with REST snapshots,
+    /// changes are buffered until the snapshot of their book is received
+    fn seed_diff_books(&self) {
+        for book in self.diff_books.borrow_mut().values_mut() {
+            book.resync();
+        }
+        let pairs: Vec<Pair> = self.diff_books.borrow().keys().copied().collect();
+        let books = Rc::downgrade(&self.diff_books);
+        let rest = self.rest.clone();
+        actix_rt::spawn(async move {
+            for pair in pairs {
+                let ob = loop {
+                    match rest.orderbook(pair).await {
+                        Ok(ob) => break ob,
+                        Err(e) => {
+                            warn!("Bitstamp : cannot fetch the order book snapshot of {:?} : {}", pair, e);
+                            tokio::time::delay_for(SNAPSHOT_RETRY_DELAY).await;
+                        }
+                    }
+                };
+                // The bot stopped in the meantime
+                let books = match books.upgrade() {
+                    Some(books) => books,
+                    None => return,
+                };
+                let mut books = books.borrow_mut();
+                if let Some(book) = books.get_mut(&pair) {
+                    book.seed(ob);
+                }
+            }
+        });
     }
 }
 
@@ -56,32 +220,121 @@ impl WsHandler for BitstampStreamingApi {
         let v : Event = serde_json::from_slice(msg.bytes()).unwrap();
         match v {
             Event::ReconnectRequest(_) =>  {
+                self.handle_reconnect();
                 self.handle_started(w);
             },
-            Event::SubSucceeded(_) => (),
             o => {
-                let vec = self.recipients.clone();
-                if vec.len() == 0 as usize {
-                    debug!("{:?}", o);
-                } else {
-                    let le : LiveEvent = o.into();
-                    for r in &vec {
-                        let le : LiveEvent = le.clone();
-                        r.do_send(LiveEventEnveloppe(Exchange::Bitstamp, le));
+                let diff = match &o {
+                    Event::LiveFullOrderBook(e) => e.is_diff(),
+                    _ => false,
+                };
+                let mut le : LiveEvent = o.into();
+                if diff {
+                    le = match le {
+                        LiveEvent::LiveOrderbook(ob) => match self.apply_diff(ob) {
+                            Some(ob) => LiveEvent::LiveOrderbook(ob),
+                            None => return,
+                        },
+                        le => le,
+                    };
+                }
+                if let LiveEvent::LiveTrade(t) = le {
+                    le = match self.tape.accept(t) {
+                        Some(t) => LiveEvent::LiveTrade(t),
+                        None => return,
+                    };
+                }
+                if let (LiveEvent::LiveOrderbook(ob), Some(depth)) = (&mut le, self.depth) {
+                    ob.asks.truncate(depth as usize);
+                    ob.bids.truncate(depth as usize);
+                }
+                match &le {
+                    LiveEvent::LiveOrderbook(ob) => {
+                        if !self.warm_pairs.contains(&ob.pair) {
+                            if !self.warm_up.accepts(ob) {
+                                return;
+                            }
+                            self.warm_pairs.insert(ob.pair);
+                        }
+                        self.books.insert(ob.pair, ob.clone());
                     }
+                    LiveEvent::LiveTrade(t) => {
+                        self.last_trades.insert(t.pair, t.clone());
+                    }
+                    _ => (),
+                }
+                metrics::ws_event(Exchange::Bitstamp, &le);
+                if self.delivery.is_empty() {
+                    debug!("{:?}", le);
+                } else {
+                    self.delivery.send(LiveEventEnveloppe(Exchange::Bitstamp, le));
                 }
             },
         };
     }
 
+    fn snapshot(&self, pair: Pair) -> Option<Snapshot> {
+        let orderbook = self.books.get(&pair).cloned();
+        let last_trade = self.last_trades.get(&pair).cloned();
+        if orderbook.is_none() && last_trade.is_none() {
+            return None;
+        }
+        Some(Snapshot { orderbook, last_trade })
+    }
+
+    /// Order books are sent whole on the order book channel, the next one after subscribing is
+    /// emitted as a fresh snapshot once it passes the warm-up again
+    fn handle_reconnect(&mut self) {
+        self.warm_pairs.clear();
+        self.books.clear();
+        self.tape.mark_gap();
+    }
+
+    fn flush_deliveries(&mut self) {
+        self.delivery.flush();
+    }
+
     #[cfg_attr(feature = "flame_it", flame)]
     fn handle_started(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>) {
         for (k, v) in self.channels.clone() {
             for pair in v {
-                let result = serde_json::to_string(&subscription(k.clone(), *super::utils::get_pair_string(&pair).unwrap())).unwrap();
-                w.write(Message::Binary(result.into()));
+                match subscription(k.clone(), *super::utils::get_pair_string(&pair).unwrap()) {
+                    Ok(sub) => {
+                        let result = serde_json::to_string(&sub).unwrap();
+                        w.write(Message::Binary(result.into()));
+                    }
+                    Err(e) => {
+                        warn!("Bitstamp : {}", e);
+                        let result = SubscriptionResult::rejected(k.clone(), pair, &e.to_string());
+                        self.delivery.send(LiveEventEnveloppe(Exchange::Bitstamp, LiveEvent::SubscriptionResult(result)));
+                    }
+                }
             }
 
         }
+        if !self.diff_books.borrow().is_empty() {
+            self.seed_diff_books();
+        }
+    }
+}
+
+#[cfg(test)]
+mod bitstamp_streaming_tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+
+    fn book(timestamp: i64, ask: (i64, i64)) -> Orderbook {
+        Orderbook { timestamp, pair: Pair::BTC_USD, asks: vec![(BigDecimal::from(ask.0), BigDecimal::from(ask.1))], bids: vec![(BigDecimal::from(90), BigDecimal::from(1))] }
+    }
+
+    #[test]
+    fn changes_before_the_snapshot_are_replayed() {
+        let mut diff_book = DiffBook::new(Pair::BTC_USD, None);
+        assert!(diff_book.apply(book(999, (101, 5))).is_none());
+        assert!(diff_book.apply(book(1_001, (100, 3))).is_none());
+        diff_book.seed(book(1_000, (100, 1)));
+        let asks = diff_book.agg.order_book().asks;
+        assert_eq!(asks, vec![(BigDecimal::from(100), BigDecimal::from(3))]);
+        assert!(diff_book.apply(book(1_002, (100, 2))).is_some());
     }
 }
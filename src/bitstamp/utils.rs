@@ -1,4 +1,6 @@
 use bidir_map::BidirMap;
+use bigdecimal::Signed;
+use chrono::NaiveDateTime;
 
 use hmac::{Hmac, Mac};
 use sha2::{Sha256};
@@ -9,7 +11,8 @@ use serde_json::value::Map;
 
 use crate::error::*;
 use crate::helpers;
-use crate::types::Currency;
+use crate::types::{Currency, ExchangeState, FillEvent, Liquidity, Orderbook, Ticker};
+use crate::types::TradeType;
 use crate::types::Pair;
 use crate::types::Pair::*;
 use bytes::buf::ext::Reader;
@@ -49,6 +52,11 @@ pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
     PAIRS_STRING.get_by_second(&pair)
 }
 
+/// Every pair with a Bitstamp name
+pub fn supported_pairs() -> Vec<Pair> {
+    PAIRS_STRING.iter().map(|(pair, _)| *pair).collect()
+}
+
 pub fn build_signature(nonce: &str,
                        customer_id: &str,
                        api_key: &str,
@@ -69,35 +77,132 @@ pub fn build_signature(nonce: &str,
         signature.push(C[(byte >> 4) as usize]);
         signature.push(C[(byte & 0xf) as usize]);
     }
-    // TODO: Handle correctly the from_utf8 errors with error_chain.
+    // TODO: Handle correctly the from_utf8 errors.
     Ok(String::from_utf8(signature)?)
 }
 
-pub fn build_url(method: &str, pair: &str) -> String {
-    "https://www.bitstamp.net/api/v2/".to_string() + method + "/" + pair + "/"
+pub const API_URL: &str = "https://www.bitstamp.net/api/v2";
+/// Status page of the exchange, announcing incidents and maintenance windows
+pub const STATUS_URL: &str = "https://status.bitstamp.net/api/v2/status.json";
+
+/// State of the exchange from the status page indicator
+pub fn get_exchange_state(indicator: &str) -> ExchangeState {
+    match indicator {
+        "none" => ExchangeState::Online,
+        "maintenance" => ExchangeState::Maintenance,
+        _ => ExchangeState::Degraded,
+    }
+}
+
+/// Side of a live trade or order, 0 for buy and 1 for sell
+pub fn get_trade_type(order_type: i64) -> TradeType {
+    match order_type {
+        0 => TradeType::Buy,
+        1 => TradeType::Sell,
+        _ => TradeType::unknown(order_type),
+    }
+}
+
+/// Url of a v2 endpoint, account wide endpoints such as `balance` take an empty pair
+pub fn build_url(base_url: &str, method: &str, pair: &str) -> String {
+    if pair.is_empty() {
+        format!("{}/{}/", base_url, method)
+    } else {
+        format!("{}/{}/{}/", base_url, method, pair)
+    }
+}
+
+/// Parse the ticker of `pair` from the response of the ticker endpoint
+pub fn parse_ticker(pair: Pair, result: &Map<String, Value>, keep_raw: bool) -> Result<Ticker> {
+    let price = helpers::from_json_bigdecimal(&result["last"], "last")?;
+    let ask = helpers::from_json_bigdecimal(&result["ask"], "ask")?;
+    let bid = helpers::from_json_bigdecimal(&result["bid"], "bid")?;
+    let vol = helpers::from_json_bigdecimal(&result["volume"], "volume")?;
+    let open = helpers::from_json_bigdecimal_opt(&result["open"]);
+    let percent_change = open.as_ref().and_then(|o| helpers::percent_change(o, &price));
+    Ok(Ticker {
+        timestamp: helpers::get_unix_timestamp_ms(),
+        pair,
+        last_trade_price: price,
+        lowest_ask: ask,
+        highest_bid: bid,
+        volume: Some(vol),
+        bid_size: None,
+        ask_size: None,
+        open_24h: open,
+        high_24h: helpers::from_json_bigdecimal_opt(&result["high"]),
+        low_24h: helpers::from_json_bigdecimal_opt(&result["low"]),
+        percent_change,
+        raw: helpers::raw_payload(keep_raw, result),
+    })
+}
+
+/// Parse the order book of `pair` from the response of the order_book endpoint
+pub fn parse_orderbook(pair: Pair, result: &Map<String, Value>) -> Result<Orderbook> {
+    let ask_array = result["asks"]
+        .as_array()
+        .ok_or_else(|| Error::InvalidFieldFormat(format!("{}", result["asks"])))?;
+    let bid_array = result["bids"]
+        .as_array()
+        .ok_or_else(|| Error::InvalidFieldFormat(format!("{}", result["bids"])))?;
+
+    let mut ask_offers = Vec::with_capacity(ask_array.len());
+    let mut bid_offers = Vec::with_capacity(bid_array.len());
+
+    for ask in ask_array {
+        let price = helpers::from_json_bigdecimal(&ask[0], "ask price")?;
+        let volume = helpers::from_json_bigdecimal(&ask[1], "ask volume")?;
+
+        ask_offers.push((price, volume));
+    }
+
+    for bid in bid_array {
+        let price = helpers::from_json_bigdecimal(&bid[0], "bid price")?;
+        let volume = helpers::from_json_bigdecimal(&bid[1], "bid volume")?;
+
+        bid_offers.push((price, volume));
+    }
+
+    let timestamp = result["microtimestamp"].as_str()
+        .and_then(|t| t.parse::<i64>().ok())
+        .map(|us| us / 1000)
+        .unwrap_or_else(helpers::get_unix_timestamp_ms);
+
+    Ok(Orderbook {
+        timestamp,
+        pair,
+        asks: ask_offers,
+        bids: bid_offers,
+    })
 }
 
 pub fn deserialize_json(json_string: &str) -> Result<Map<String, Value>> {
     let data: Value = match serde_json::from_str(json_string) {
         Ok(data) => data,
-        Err(_) => return Err(ErrorKind::BadParse.into()),
+        Err(_) => return Err(Error::BadParse),
     };
 
     match data.as_object() {
         Some(value) => Ok(value.clone()),
-        None => Err(ErrorKind::BadParse.into()),
+        None => Err(Error::BadParse),
     }
 }
 
+/// Arrays, returned by the transaction lists, are wrapped in an object under `result`
 pub fn deserialize_json_r<B>(reader: Reader<B>) -> Result<Map<String, Value>> where B: Buf {
     let data: Value = match serde_json::from_reader(reader) {
         Ok(data) => data,
-        Err(_) => return Err(ErrorKind::BadParse.into()),
+        Err(_) => return Err(Error::BadParse),
     };
 
-    match data.as_object() {
-        Some(value) => Ok(value.clone()),
-        None => Err(ErrorKind::BadParse.into()),
+    match data {
+        Value::Object(value) => Ok(value),
+        Value::Array(_) => {
+            let mut value = Map::new();
+            value.insert("result".to_string(), data);
+            Ok(value)
+        }
+        _ => Err(Error::BadParse),
     }
 }
 
@@ -111,20 +216,45 @@ pub fn generate_nonce(fixed_nonce: Option<String>) -> String {
 /// If error array is null, return the result (encoded in a json object)
 /// else return the error string found in array
 pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>> {
-    let error_msg = match response.get("error") {
-        Some(error) => {
-            error
-                .as_str()
-                .ok_or_else(|| ErrorKind::InvalidFieldFormat("error".to_string()))?
+    let error = match response.get("error") {
+        Some(error) => error,
+        None if response.get("status").and_then(|s| s.as_str()) == Some("error") => {
+            response.get("reason").ok_or_else(|| Error::MissingField("reason".to_string()))?
         }
         None => return Ok(response.clone()),
     };
+    let error_msg = error_message(error).ok_or_else(|| Error::InvalidFieldFormat("error".to_string()))?;
 
+    let lowercase = error_msg.to_lowercase();
     match error_msg.as_ref() {
-        "Invalid command." => Err(ErrorKind::InvalidArguments.into()),
-        "Invalid API key/secret pair." => Err(ErrorKind::BadCredentials.into()),
-        "Total must be at least 0.0001." => Err(ErrorKind::InsufficientOrderSize.into()),
-        other => Err(ErrorKind::ExchangeSpecificError(other.to_string()).into()),
+        "Invalid command." => Err(Error::InvalidArguments),
+        "Invalid API key/secret pair." => Err(Error::BadCredentials),
+        _ if lowercase.contains("nonce") => Err(Error::InvalidNonce),
+        _ if lowercase.contains("api key not found") || lowercase.contains("invalid signature") => Err(Error::BadCredentials),
+        _ if lowercase.contains("order not found") => Err(Error::OrderNotFound),
+        _ if lowercase.contains("check your account balance") => Err(Error::InsufficientFunds),
+        _ if lowercase.contains("minimum order size") || lowercase.starts_with("total must be at least") => Err(Error::InsufficientOrderSize),
+        _ if lowercase.contains("too many requests") => Err(Error::RateLimited(None)),
+        other => Err(Error::ExchangeSpecificError(other.to_string())),
+    }
+}
+
+/// Bitstamp reasons are either a string or an object of field name to messages, e.g.
+/// `{"__all__": ["You have only 0.00 USD available. Check your account balance for details."]}`
+fn error_message(error: &Value) -> Option<String> {
+    match error {
+        Value::String(s) => Some(s.clone()),
+        Value::Object(fields) => {
+            let messages: Vec<String> = fields.values()
+                .flat_map(|v| match v {
+                    Value::Array(a) => a.iter().filter_map(|m| m.as_str()).map(|m| m.to_string()).collect(),
+                    Value::String(s) => vec![s.clone()],
+                    _ => vec![],
+                })
+                .collect();
+            if messages.is_empty() { None } else { Some(messages.join(" ")) }
+        }
+        _ => None,
     }
 }
 
@@ -171,3 +301,58 @@ pub fn get_currency_string(currency: Currency) -> Option<String> {
         _ => None,
     }
 }
+
+/// Identifiers and types of the transactions are either numbers or strings
+pub fn json_text(v: &Value) -> String {
+    v.as_str().map(|s| s.to_string()).unwrap_or_else(|| v.to_string())
+}
+
+/// A trade of the user transactions of `pair`, the amount of the base currency is negative for the
+/// sells. The fee is charged in the quote currency.
+pub fn parse_fill(pair: Pair, transaction: &Value) -> Result<FillEvent> {
+    let (base_currency, quote_currency) = crate::utils::pair_currencies(&pair).ok_or(Error::PairUnsupported)?;
+    let (base, quote) = (format!("{:?}", base_currency).to_lowercase(), format!("{:?}", quote_currency).to_lowercase());
+    let decimal = |key: &str| helpers::from_json_bigdecimal_opt(&transaction[key]).ok_or_else(|| Error::MissingField(key.to_string()));
+    let datetime = transaction["datetime"].as_str().ok_or_else(|| Error::MissingField("datetime".to_string()))?;
+    let event_ms = NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S%.f")
+        .map_err(|_| Error::InvalidFieldFormat(datetime.to_string()))?
+        .timestamp_millis();
+    let amount = decimal(&base)?;
+    Ok(FillEvent {
+        event_ms,
+        order_id: json_text(&transaction["order_id"]),
+        trade_id: Some(json_text(&transaction["id"])),
+        pair,
+        side: if amount.is_negative() { TradeType::Sell } else { TradeType::Buy },
+        price: decimal(&format!("{}_{}", base, quote))?,
+        amount: amount.abs(),
+        fee: decimal("fee")?,
+        fee_currency: Some(quote_currency),
+        liquidity: Liquidity::Unknown,
+    })
+}
+
+#[cfg(test)]
+mod bitstamp_utils_tests {
+    use super::*;
+
+    #[test]
+    fn status_indicators_map_to_states() {
+        assert_eq!(get_exchange_state("none"), ExchangeState::Online);
+        assert_eq!(get_exchange_state("maintenance"), ExchangeState::Maintenance);
+        assert_eq!(get_exchange_state("major"), ExchangeState::Degraded);
+    }
+
+    #[test]
+    fn sells_are_parsed_from_negative_amounts() {
+        let transaction = serde_json::json!({"id": 1133, "order_id": "12", "type": "2", "datetime": "2020-01-02 03:04:05.250",
+            "btc": "-0.5", "usd": "3600", "btc_usd": 7200.5, "fee": "9"});
+        let fill = parse_fill(Pair::BTC_USD, &transaction).unwrap();
+        assert_eq!(fill.event_ms, 1577934245250);
+        assert_eq!((fill.order_id.as_str(), fill.trade_id.as_deref()), ("12", Some("1133")));
+        assert_eq!(fill.side, TradeType::Sell);
+        assert_eq!(fill.amount, "0.5".parse().unwrap());
+        assert_eq!(fill.price, "7200.5".parse().unwrap());
+        assert_eq!(fill.fee_currency, Some(Currency::USD));
+    }
+}
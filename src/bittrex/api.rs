@@ -1,13 +1,13 @@
-//! Use this module to interact with the raw-original API provided by Bittrex.
+//! Use this module to interact with the raw-original v3 REST API provided by Bittrex.
 //! WARNING: Special attention should be paid to error management: parsing number, etc.
 
 #![allow(too_many_arguments)]
 
 use hmac::{Hmac, Mac};
-use sha2::{Sha512};
+use sha2::{Digest, Sha512};
 
-use hyper::{Client, Uri, Method, Request, Body};
-use hyper_tls::HttpsConnector;
+use hyper::{Method, Request, Body};
+use hyper::header::CONTENT_TYPE;
 
 use data_encoding::HEXLOWER;
 
@@ -15,29 +15,44 @@ use serde_json::Value;
 use serde_json::value::Map;
 
 use std::collections::HashMap;
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::AtomicI64;
+use std::time::Instant;
 use std::str;
 
 use crate::error::*;
+use crate::helpers::clock::ServerClock;
+use crate::helpers::rate_limit::RateLimits;
+use crate::helpers::failover::Endpoints;
+use crate::helpers::secret::SecretString;
+use crate::helpers::signing;
 use crate::helpers;
+use crate::metrics;
 
 use crate::exchange::Exchange;
 use crate::coinnect::Credentials;
-use hyper::client::HttpConnector;
-use futures::{TryFutureExt};
+use crate::helpers::proxy::HttpClient;
+use crate::bittrex::utils;
 use bytes::buf::BufExt as _;
-use crate::helpers::json;
 
-const API_SIGN: &str = "apisign";
+const API_KEY: &str = "Api-Key";
+const API_TIMESTAMP: &str = "Api-Timestamp";
+const API_CONTENT_HASH: &str = "Api-Content-Hash";
+const API_SIGNATURE: &str = "Api-Signature";
+
+const API_URL: &str = "https://api.bittrex.com/v3";
 
 #[derive(Debug)]
 pub struct BittrexApi {
-    last_request: i64, // unix timestamp in ms, to avoid ban
-    api_key: String,
-    api_secret: String,
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    last_request: AtomicI64, // unix timestamp in ms, to avoid ban
+    api_key: SecretString,
+    api_secret: SecretString,
+    http_client: HttpClient,
     burst: bool,
+    pub(crate) keep_raw: bool,
+    debug_signing: bool,
+    endpoints: Endpoints,
+    pub(crate) clock: ServerClock,
+    pub(crate) rate_limits: RateLimits,
 }
 
 
@@ -45,21 +60,33 @@ impl BittrexApi {
     /// Create a new BittrexApi by providing an API key & API secret
     pub fn new<C: Credentials>(creds: C) -> Result<BittrexApi> {
         if creds.exchange() != Exchange::Bittrex {
-            return Err(ErrorKind::InvalidConfigType(Exchange::Bittrex, creds.exchange()).into());
+            return Err(Error::InvalidConfigType(Exchange::Bittrex, creds.exchange()));
         }
+        creds.environment()?.production_only(Exchange::Bittrex)?;
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
+        let connection = creds.connection();
+        let ssl = connection.http_client()?;
+        let endpoints = connection.rest_endpoints(Exchange::Bittrex, API_URL);
 
         Ok(BittrexApi {
-               last_request: 0,
-               api_key: creds.get("api_key").unwrap_or_default(),
-               api_secret: creds.get("api_secret").unwrap_or_default(),
+               last_request: AtomicI64::new(0),
+               api_key: creds.get("api_key").unwrap_or_default().into(),
+               api_secret: creds.get("api_secret").unwrap_or_default().into(),
                http_client: ssl,
                burst: false,
+               keep_raw: connection.keep_raw,
+               debug_signing: connection.debug_signing,
+               endpoints,
+               clock: ServerClock::default(),
+               rate_limits: RateLimits::default(),
            })
     }
 
+    /// The REST endpoints, to subscribe to failover events or recover the primary endpoint
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
     /// The number of calls in a given period is limited. In order to avoid a ban we limit
     /// by default the number of api requests.
     /// This function sets or removes the limitation.
@@ -69,705 +96,333 @@ impl BittrexApi {
         self.burst = burst
     }
 
-    pub async fn block_or_continue(&self) {
-        if ! self.burst {
-            let threshold: u64 = 500; // 1 request/500ms
-            let offset: u64 = helpers::get_unix_timestamp_ms() as u64 - self.last_request as u64;
-            if offset < threshold {
-                let wait_ms = Duration::from_millis(threshold - offset);
-                thread::sleep(wait_ms);
-            }
-        }
+    /// Keep the payload of the exchange in the `raw` field of typed responses
+    pub fn set_keep_raw(&mut self, keep_raw: bool) {
+        self.keep_raw = keep_raw
     }
 
-    async fn public_query(&mut self,
-                    method: &str,
-                    params: &mut HashMap<&str, &str>)
-                    -> Result<Map<String, Value>> {
-
-        helpers::strip_empties(params);
-        let string = "https://bittrex.com/api/v1.1".to_string() + method + "?" +
-            &helpers::url_encode_hashmap(params);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
-
-        self.block_or_continue().await;
-        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
-        self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
-        json::deserialize_json_r(reader)
+    /// Log the strings signed for the private requests and the status of their responses
+    pub fn set_debug_signing(&mut self, debug_signing: bool) {
+        self.debug_signing = debug_signing
     }
 
-    async fn private_query(&mut self,
-                     method: &str,
-                     mut params: &mut HashMap<&str, &str>)
-                     -> Result<Map<String, Value>> {
-        let nonce = helpers::get_unix_timestamp_ms().to_string();
-        let mut initial_params: HashMap<&str, &str> = HashMap::new();
+    pub async fn block_or_continue(&self) {
+        if !self.burst {
+            helpers::rate_limit::wait_for_slot(&self.last_request, 500).await; // 1 request/500ms
+        }
+    }
 
-        initial_params.insert("nonce", &nonce);
-        initial_params.insert("apikey", &self.api_key);
+    /// Send a request to `path`, signed if `private`.
+    /// Successful responses are returned as is, errors are mapped from their `code`.
+    async fn query(&self,
+                   method: Method,
+                   path: &str,
+                   params: &mut HashMap<&str, &str>,
+                   body: Option<Value>,
+                   private: bool)
+                   -> Result<Value> {
+        helpers::strip_empties(params);
+        let mut url = self.endpoints.current().to_string() + path;
+        if !params.is_empty() {
+            url = url + "?" + &helpers::url_encode_hashmap(params);
+        }
+        let content = body.map(|b| b.to_string()).unwrap_or_default();
 
-        let base_url = "https://bittrex.com/api/v1.1".to_string() + method + "?apikey=" +
-        &self.api_key + "&nonce=" + &nonce;
+        let mut builder = Request::builder().method(method.clone()).uri(&url);
+        if !content.is_empty() {
+            builder = builder.header(CONTENT_TYPE, "application/json");
+        }
+        if private {
+            let timestamp = self.clock.now_ms().to_string();
+            let mut sha512 = Sha512::default();
+            sha512.input(content.as_bytes());
+            let content_hash = HEXLOWER.encode(&sha512.result());
+            let pre_sign = format!("{}{}{}{}", timestamp, url, method.as_str(), content_hash);
+            if self.debug_signing {
+                signing::log_signed(Exchange::Bittrex, path, &timestamp, &pre_sign, &[self.api_key.expose()]);
+            }
+            let mut mac = Hmac::<Sha512>::new(self.api_secret.expose().as_bytes());
+            mac.input(pre_sign.as_bytes());
+            let signature = HEXLOWER.encode(mac.result().code());
+            builder = builder
+                .header(API_KEY, self.api_key.expose())
+                .header(API_TIMESTAMP, timestamp)
+                .header(API_CONTENT_HASH, content_hash)
+                .header(API_SIGNATURE, signature);
+        }
+        let req = builder.body(Body::from(content)).map_err(|e| Error::ServiceUnavailable(e.to_string()))?;
 
-        let url = if params.is_empty() {
-            base_url
-        } else {
-            base_url + "&" + &helpers::url_encode_hashmap(&mut params)
+        self.block_or_continue().await;
+        let start = Instant::now();
+        let resp = self.http_client.request(req).await.map_err(Error::from);
+        metrics::rest_request(Exchange::Bittrex, path, start);
+        let resp = self.endpoints.track(resp)?;
+        self.rate_limits.observe(resp.headers(), helpers::get_unix_timestamp_ms());
+        let status = resp.status();
+        if private && self.debug_signing {
+            signing::log_response(Exchange::Bittrex, path, status);
+        }
+        let buf = hyper::body::aggregate(resp.into_body()).await?;
+        let value: Value = match serde_json::from_reader(buf.reader()) {
+            Ok(value) => value,
+            // e.g. the html page served during maintenance
+            Err(_) if !status.is_success() => Value::Null,
+            Err(_) => return Err(Error::BadParse),
         };
+        if !status.is_success() {
+            return match utils::parse_error(status.as_u16(), &value) {
+                Error::RateLimited(_) => {
+                    self.rate_limits.exhausted(None);
+                    Err(Error::RateLimited(self.rate_limits.retry_after(helpers::get_unix_timestamp_ms())))
+                }
+                e => Err(e),
+            };
+        }
+        Ok(value)
+    }
 
-        let hmac_key = self.api_secret.as_bytes();
-        let mut mac = Hmac::<Sha512>::new(&hmac_key[..]);
-        mac.input(url.as_bytes());
-
-        let signature = HEXLOWER.encode(mac.result().code());
+    async fn public_query(&self, path: &str, params: &mut HashMap<&str, &str>) -> Result<Value> {
+        self.query(Method::GET, path, params, None, false).await
+    }
 
-        let req: Result<Request<Body>> = Request::builder()
-            .method(Method::POST)
-            .uri(&url)
-            .header(API_SIGN, signature)
-            .body(Body::empty())
-            .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
-        let req2 = req.unwrap();
-        let buf = self.http_client.request(req2).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
-        self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
-        json::deserialize_json_r(reader)
+    async fn private_query(&self, method: Method, path: &str, params: &mut HashMap<&str, &str>, body: Option<Value>) -> Result<Value> {
+        self.query(method, path, params, body, true).await
     }
 
     /// Used to get the open and available trading markets at Bittrex along with other meta data.
     ///
     /// ```json
-    ///    {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : [{
-    /// 			"MarketCurrency" : "LTC",
-    /// 			"BaseCurrency" : "BTC",
-    /// 			"MarketCurrencyLong" : "Litecoin",
-    /// 			"BaseCurrencyLong" : "Bitcoin",
-    /// 			"MinTradeSize" : 0.01000000,
-    /// 			"MarketName" : "BTC-LTC",
-    /// 			"IsActive" : true,
-    /// 			"Created" : "2014-02-13T00:00:00"
-    /// 		}, {
-    /// 			"MarketCurrency" : "DOGE",
-    /// 			"BaseCurrency" : "BTC",
-    /// 			"MarketCurrencyLong" : "Dogecoin",
-    /// 			"BaseCurrencyLong" : "Bitcoin",
-    /// 			"MinTradeSize" : 100.00000000,
-    /// 			"MarketName" : "BTC-DOGE",
-    /// 			"IsActive" : true,
-    /// 			"Created" : "2014-02-13T00:00:00"
-    /// 		}
-    ///     ]
-    /// }
+    /// [{"symbol": "LTC-BTC", "baseCurrencySymbol": "LTC", "quoteCurrencySymbol": "BTC",
+    /// "minTradeSize": "0.01", "precision": 8, "status": "ONLINE", ...}, ...]
     /// ```
-    pub async fn get_markets(&mut self) -> Result<Map<String, Value>> {
+    pub async fn get_markets(&self) -> Result<Value> {
         let mut params = HashMap::new();
-        self.public_query("/public/getmarkets", &mut params).await
+        self.public_query("/markets", &mut params).await
     }
 
-    /// Used to get all supported currencies at Bittrex along with other meta data.
+    /// Time of the server, in ms
     ///
     /// ```json
-    /// {
-    ///     "success" : true,
-    ///     "message" : "",
-    ///     "result" : [{
-    ///             "Currency" : "BTC",
-    ///             "CurrencyLong" : "Bitcoin",
-    ///             "MinConfirmation" : 2,
-    ///             "TxFee" : 0.00020000,
-    ///             "IsActive" : true,
-    ///             "CoinType" : "BITCOIN",
-    ///             "BaseAddress" : null
-    ///         }, {
-    ///             "Currency" : "LTC",
-    ///             "CurrencyLong" : "Litecoin",
-    ///             "MinConfirmation" : 5,
-    ///             "TxFee" : 0.00200000,
-    ///             "IsActive" : true,
-    ///             "CoinType" : "BITCOIN",
-    ///             "BaseAddress" : null
-    ///         }
-    ///     ]
-    /// }
+    /// {"serverTime": 1594596023162}
     /// ```
-    pub async fn get_currencies(&mut self) -> Result<Map<String, Value>> {
+    pub async fn ping(&self) -> Result<Value> {
         let mut params = HashMap::new();
-        self.public_query("/public/getcurrencies", &mut params).await
+        self.public_query("/ping", &mut params).await
     }
 
-    /// Used to get the current tick values for a market.
-    /// "market" required a string literal for the market (ex: BTC-LTC)
+    /// Used to get all supported currencies at Bittrex along with other meta data.
     ///
-    /// ````json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : {
-    /// 		"Bid" : 2.05670368,
-    /// 		"Ask" : 3.35579531,
-    /// 		"Last" : 3.35579531
-    /// 	}
-    /// }
+    /// ```json
+    /// [{"symbol": "BTC", "name": "Bitcoin", "coinType": "BITCOIN", "status": "ONLINE",
+    /// "minConfirmations": 2, "txFee": "0.0005", ...}, ...]
     /// ```
-    pub async fn get_ticker(&mut self, market: &str) -> Result<Map<String, Value>> {
+    pub async fn get_currencies(&self) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("market", market);
-        self.public_query("/public/getticker", &mut params).await
+        self.public_query("/currencies", &mut params).await
     }
 
-    /// Used to get the last 24 hour summary of all active exchanges
+    /// Used to get the current tick values for a market.
     ///
-    /// ````json
-    /// {
-    ///     "success" : true,
-    ///     "message" : "",
-    ///     "result" : [{
-    ///             "MarketName" : "BTC-888",
-    ///             "High" : 0.00000919,
-    ///             "Low" : 0.00000820,
-    ///             "Volume" : 74339.61396015,
-    ///             "Last" : 0.00000820,
-    ///             "BaseVolume" : 0.64966963,
-    ///             "TimeStamp" : "2014-07-09T07:19:30.15",
-    ///             "Bid" : 0.00000820,
-    ///             "Ask" : 0.00000831,
-    ///             "OpenBuyOrders" : 15,
-    ///             "OpenSellOrders" : 15,
-    ///             "PrevDay" : 0.00000821,
-    ///             "Created" : "2014-03-20T06:00:00",
-    ///             "DisplayMarketName" : null
-    ///         }, {
-    ///             "MarketName" : "BTC-A3C",
-    ///             "High" : 0.00000072,
-    ///             "Low" : 0.00000001,
-    ///             "Volume" : 166340678.42280999,
-    ///             "Last" : 0.00000005,
-    ///             "BaseVolume" : 17.59720424,
-    ///             "TimeStamp" : "2014-07-09T07:21:40.51",
-    ///             "Bid" : 0.00000004,
-    ///             "Ask" : 0.00000005,
-    ///             "OpenBuyOrders" : 18,
-    ///             "OpenSellOrders" : 18,
-    ///             "PrevDay" : 0.00000002,
-    ///             "Created" : "2014-05-30T07:57:49.637",
-    ///             "DisplayMarketName" : null
-    ///         }
-    ///     ]
-    /// }
+    /// ```json
+    /// {"symbol": "LTC-BTC", "lastTradeRate": "0.00435", "bidRate": "0.00434", "askRate": "0.00436"}
     /// ```
-    pub async fn get_market_summaries(&mut self) -> Result<Map<String, Value>> {
+    pub async fn get_ticker(&self, market: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        self.public_query("/public/getmarketsummaries", &mut params).await
+        self.public_query(&format!("/markets/{}/ticker", market), &mut params).await
     }
 
-    /// Used to get the last 24 hour summary of all active exchanges
-    /// "market" required a string literal for the market (ex: BTC-LTC)
+    /// Used to get the current tick values of every market.
+    pub async fn get_tickers(&self) -> Result<Value> {
+        let mut params = HashMap::new();
+        self.public_query("/markets/tickers", &mut params).await
+    }
+
+    /// Used to get the last 24 hour summary of all active markets.
     ///
     /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : [{
-    /// 			"MarketName" : "BTC-LTC",
-    /// 			"High" : 0.01350000,
-    /// 			"Low" : 0.01200000,
-    /// 			"Volume" : 3833.97619253,
-    /// 			"Last" : 0.01349998,
-    /// 			"BaseVolume" : 47.03987026,
-    /// 			"TimeStamp" : "2014-07-09T07:22:16.72",
-    /// 			"Bid" : 0.01271001,
-    /// 			"Ask" : 0.01291100,
-    /// 			"OpenBuyOrders" : 45,
-    /// 			"OpenSellOrders" : 45,
-    /// 			"PrevDay" : 0.01229501,
-    /// 			"Created" : "2014-02-13T00:00:00",
-    /// 			"DisplayMarketName" : null
-    /// 		}
-    ///     ]
-    /// }
+    /// [{"symbol": "LTC-BTC", "high": "0.0045", "low": "0.0043", "volume": "12345.6",
+    /// "quoteVolume": "54.2", "percentChange": "1.2", "updatedAt": "2020-03-01T10:00:00Z"}, ...]
     /// ```
-    pub async fn get_market_summary(&mut self, market: &str) -> Result<Map<String, Value>> {
+    pub async fn get_market_summaries(&self) -> Result<Value> {
+        let mut params = HashMap::new();
+        self.public_query("/markets/summaries", &mut params).await
+    }
+
+    /// Used to get the last 24 hour summary of a market, see `get_market_summaries`.
+    pub async fn get_market_summary(&self, market: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("market", market);
-        self.public_query("/public/getmarketsummary", &mut params).await
+        self.public_query(&format!("/markets/{}/summary", market), &mut params).await
     }
 
-    /// Used to get retrieve the orderbook for a given market
-    /// "market" required a string literal for the market (ex: BTC-LTC)
-    /// "order_type" required "buy", "sell" or "both" to identify the type of orderbook to return.
+    /// Used to get the order book of a market, `depth` is one of 1, 25 and 500.
     ///
     /// ```json
-    /// {
-    ///     "success" : true,
-    ///     "message" : "",
-    ///     "result" : {
-    ///         "buy" : [{
-    ///                 "Quantity" : 12.37000000,
-    ///                 "Rate" : 0.02525000
-    ///             }
-    ///         ],
-    ///         "sell" : [{
-    ///                 "Quantity" : 32.55412402,
-    ///                 "Rate" : 0.02540000
-    ///             }, {
-    ///                 "Quantity" : 60.00000000,
-    ///                 "Rate" : 0.02550000
-    ///             }, {
-    ///                 "Quantity" : 60.00000000,
-    ///                 "Rate" : 0.02575000
-    ///             }, {
-    ///                 "Quantity" : 84.00000000,
-    ///                 "Rate" : 0.02600000
-    ///             }
-    ///         ]
-    ///     }
-    /// }
+    /// {"bid": [{"quantity": "12.5", "rate": "0.00434"}, ...],
+    /// "ask": [{"quantity": "3.2", "rate": "0.00436"}, ...]}
     /// ```
-    pub async fn get_order_book(&mut self, market: &str, order_type: &str) -> Result<Map<String, Value>> {
+    pub async fn get_order_book(&self, market: &str, depth: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("market", market);
-        params.insert("type", order_type);
-        self.public_query("/public/getorderbook", &mut params).await
+        params.insert("depth", depth);
+        self.public_query(&format!("/markets/{}/orderbook", market), &mut params).await
     }
 
     /// Used to retrieve the latest trades that have occured for a specific market.
-    /// "market" required a string literal for the market (ex: BTC-LTC)
     ///
     /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : [{
-    /// 			"Id" : 319435,
-    /// 			"TimeStamp" : "2014-07-09T03:21:20.08",
-    /// 			"Quantity" : 0.30802438,
-    /// 			"Price" : 0.01263400,
-    /// 			"Total" : 0.00389158,
-    /// 			"FillType" : "FILL",
-    /// 			"OrderType" : "BUY"
-    /// 		}, {
-    /// 			"Id" : 319433,
-    /// 			"TimeStamp" : "2014-07-09T03:21:20.08",
-    /// 			"Quantity" : 0.31820814,
-    /// 			"Price" : 0.01262800,
-    /// 			"Total" : 0.00401833,
-    /// 			"FillType" : "PARTIAL_FILL",
-    /// 			"OrderType" : "BUY"
-    /// 		}, {
-    /// 			"Id" : 319379,
-    /// 			"TimeStamp" : "2014-07-09T02:58:48.127",
-    /// 			"Quantity" : 49.64643541,
-    /// 			"Price" : 0.01263200,
-    /// 			"Total" : 0.62713377,
-    /// 			"FillType" : "FILL",
-    /// 			"OrderType" : "SELL"
-    /// 		}, {
-    /// 			"Id" : 319378,
-    /// 			"TimeStamp" : "2014-07-09T02:58:46.27",
-    /// 			"Quantity" : 0.35356459,
-    /// 			"Price" : 0.01263200,
-    /// 			"Total" : 0.00446622,
-    /// 			"FillType" : "PARTIAL_FILL",
-    /// 			"OrderType" : "BUY"
-    /// 		}
-    /// 	]
-    /// }
+    /// [{"id": "...", "executedAt": "2020-03-01T10:00:00Z", "quantity": "0.5", "rate": "0.00435",
+    /// "takerSide": "BUY"}, ...]
     /// ```
-    pub async fn get_market_history(&mut self, market: &str) -> Result<Map<String, Value>> {
+    pub async fn get_market_history(&self, market: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("market", market);
-        self.public_query("/public/getmarkethistory", &mut params).await
+        self.public_query(&format!("/markets/{}/trades", market), &mut params).await
     }
 
-    /// Used to place a buy order in a specific market. Use buylimit to place limit orders.
-    /// Make sure you have the proper permissions set on your API keys for this call to work.
-    /// "market" required a string literal for the market (ex: BTC-LTC)
-    /// "quantity" required the amount to purchase
-    /// "rate" required the rate at which to place the order.
+    /// Used to place an order. `direction` is `BUY` or `SELL`, `order_type` is `LIMIT` or
+    /// `MARKET` and `time_in_force` one of `GOOD_TIL_CANCELLED`, `IMMEDIATE_OR_CANCEL` and
+    /// `FILL_OR_KILL`. `limit` is only used by limit orders.
     ///
     /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : {
-    /// 			"uuid" : "e606d53c-8d70-11e3-94b5-425861b86ab6"
-    /// 	}
-    /// }
+    /// {"id": "3e4e9e3b-...", "marketSymbol": "LTC-BTC", "direction": "BUY", "type": "LIMIT",
+    /// "quantity": "1.0", "limit": "0.004", "status": "OPEN", ...}
     /// ```
-    pub async fn buy_limit(&mut self, market: &str, quantity: &str, rate: &str) -> Result<Map<String, Value>> {
+    pub async fn new_order(&self,
+                           market: &str,
+                           direction: &str,
+                           order_type: &str,
+                           quantity: &str,
+                           limit: Option<&str>,
+                           time_in_force: &str)
+                           -> Result<Value> {
+        let mut order = Map::new();
+        order.insert("marketSymbol".to_string(), Value::from(market));
+        order.insert("direction".to_string(), Value::from(direction));
+        order.insert("type".to_string(), Value::from(order_type));
+        order.insert("quantity".to_string(), Value::from(quantity));
+        if let Some(limit) = limit {
+            order.insert("limit".to_string(), Value::from(limit));
+        }
+        order.insert("timeInForce".to_string(), Value::from(time_in_force));
         let mut params = HashMap::new();
-        params.insert("market", market);
-        params.insert("quantity", quantity);
-        params.insert("rate", rate);
-        self.private_query("/market/buylimit", &mut params).await
+        self.private_query(Method::POST, "/orders", &mut params, Some(Value::Object(order))).await
     }
 
-    /// Used to place a sell order in a specific market. Use selllimit to place limit orders.
-    /// Make sure you have the proper permissions set on your API keys for this call to work.
-    /// "market" required a string literal for the market (ex: BTC-LTC)
-    /// "quantity" required the amount to purchase
-    /// "rate" required the rate at which to place the order.
-    ///
-    /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : {
-    /// 			"uuid" : "614c34e4-8d71-11e3-94b5-425861b86ab6"
-    /// 	}
-    /// }
-    /// ```
-    pub async fn sell_limit(&mut self, market: &str, quantity: &str, rate: &str) -> Result<Map<String, Value>> {
+    /// Used to place a good til cancelled buy order in a specific market.
+    pub async fn buy_limit(&self, market: &str, quantity: &str, rate: &str) -> Result<Value> {
+        self.new_order(market, "BUY", "LIMIT", quantity, Some(rate), "GOOD_TIL_CANCELLED").await
+    }
+
+    /// Used to place a good til cancelled sell order in a specific market.
+    pub async fn sell_limit(&self, market: &str, quantity: &str, rate: &str) -> Result<Value> {
+        self.new_order(market, "SELL", "LIMIT", quantity, Some(rate), "GOOD_TIL_CANCELLED").await
+    }
+
+    /// Used to cancel an open order, returns the order.
+    pub async fn cancel(&self, uuid: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("market", market);
-        params.insert("quantity", quantity);
-        params.insert("rate", rate);
-        self.private_query("/market/selllimit", &mut params).await
+        self.private_query(Method::DELETE, &format!("/orders/{}", uuid), &mut params, None).await
     }
 
-    /// Used to cancel a buy or sell order.
-    /// "uuid" required uuid of buy or sell order
-    ///
-    /// ```json
-    /// {
-    /// "success" : true,
-    /// "message" : "",
-    /// "result" : null
-    /// }
-    /// ```
-    pub async fn cancel(&mut self, uuid: &str) -> Result<Map<String, Value>> {
+    /// Used to cancel every open order, of a specific market if `market` is not empty.
+    pub async fn cancel_open(&self, market: &str) -> Result<Value> {
+        let mut params = HashMap::new();
+        if !market.is_empty() {
+            params.insert("marketSymbol", market);
+        }
+        self.private_query(Method::DELETE, "/orders/open", &mut params, None).await
+    }
+
+    /// Get all orders that you currently have opened, of a specific market if `market` is not
+    /// empty.
+    pub async fn get_open_orders(&self, market: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("uuid", uuid);
-        self.private_query("/market/cancel", &mut params).await
+        params.insert("marketSymbol", market);
+        self.private_query(Method::GET, "/orders/open", &mut params, None).await
     }
 
-    /// Get all orders that you currently have opened. A specific market can be requested
-    /// "market" optional a string literal for the market (ie. BTC-LTC)
+    /// Used to retrieve the trading fees of the account for every market.
     ///
     /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : [{
-    /// 			"Uuid" : null,
-    /// 			"OrderUuid" : "09aa5bb6-8232-41aa-9b78-a5a1093e0211",
-    /// 			"Exchange" : "BTC-LTC",
-    /// 			"OrderType" : "LIMIT_SELL",
-    /// 			"Quantity" : 5.00000000,
-    /// 			"QuantityRemaining" : 5.00000000,
-    /// 			"Limit" : 2.00000000,
-    /// 			"CommissionPaid" : 0.00000000,
-    /// 			"Price" : 0.00000000,
-    /// 			"PricePerUnit" : null,
-    /// 			"Opened" : "2014-07-09T03:55:48.77",
-    /// 			"Closed" : null,
-    /// 			"CancelInitiated" : false,
-    /// 			"ImmediateOrCancel" : false,
-    /// 			"IsConditional" : false,
-    /// 			"Condition" : null,
-    /// 			"ConditionTarget" : null
-    /// 		}, {
-    /// 			"Uuid" : null,
-    /// 			"OrderUuid" : "8925d746-bc9f-4684-b1aa-e507467aaa99",
-    /// 			"Exchange" : "BTC-LTC",
-    /// 			"OrderType" : "LIMIT_BUY",
-    /// 			"Quantity" : 100000.00000000,
-    /// 			"QuantityRemaining" : 100000.00000000,
-    /// 			"Limit" : 0.00000001,
-    /// 			"CommissionPaid" : 0.00000000,
-    /// 			"Price" : 0.00000000,
-    /// 			"PricePerUnit" : null,
-    /// 			"Opened" : "2014-07-09T03:55:48.583",
-    /// 			"Closed" : null,
-    /// 			"CancelInitiated" : false,
-    /// 			"ImmediateOrCancel" : false,
-    /// 			"IsConditional" : false,
-    /// 			"Condition" : null,
-    /// 			"ConditionTarget" : null
-    /// 		}
-    /// 	]
-    /// }
+    /// [{"marketSymbol": "LTC-BTC", "makerRate": "0.0035", "takerRate": "0.0035"}, ...]
     /// ```
-    pub async fn get_open_orders(&mut self, market: &str) -> Result<Map<String, Value>> {
+    pub async fn get_trading_fees(&self) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("market", market);
-        self.private_query("/market/getopenorders", &mut params).await
+        self.private_query(Method::GET, "/account/fees/trading", &mut params, None).await
     }
 
-    /// Used to retrieve all balances from your account
+    /// Used to retrieve all balances from your account.
     ///
     /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : [{
-    /// 			"Currency" : "DOGE",
-    /// 			"Balance" : 0.00000000,
-    /// 			"Available" : 0.00000000,
-    /// 			"Pending" : 0.00000000,
-    /// 			"CryptoAddress" : "DLxcEt3AatMyr2NTatzjsfHNoB9NT62HiF",
-    /// 			"Requested" : false,
-    /// 			"Uuid" : null
-    ///
-    /// 		}, {
-    /// 			"Currency" : "BTC",
-    /// 			"Balance" : 14.21549076,
-    /// 			"Available" : 14.21549076,
-    /// 			"Pending" : 0.00000000,
-    /// 			"CryptoAddress" : "1Mrcdr6715hjda34pdXuLqXcju6qgwHA31",
-    /// 			"Requested" : false,
-    /// 			"Uuid" : null
-    /// 		}
-    /// 	]
-    /// }
+    /// [{"currencySymbol": "BTC", "total": "4.21549076", "available": "4.21549076",
+    /// "updatedAt": "2020-03-01T10:00:00Z"}, ...]
     /// ```
-    pub async fn get_balances(&mut self) -> Result<Map<String, Value>> {
+    pub async fn get_balances(&self) -> Result<Value> {
         let mut params = HashMap::new();
-        self.private_query("/account/getbalances", &mut params).await
+        self.private_query(Method::GET, "/balances", &mut params, None).await
     }
 
     /// Used to retrieve the balance from your account for a specific currency.
-    /// "currency" required a string literal for the currency (ex: LTC)
-    ///
-    /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : {
-    /// 		"Currency" : "BTC",
-    /// 		"Balance" : 4.21549076,
-    /// 		"Available" : 4.21549076,
-    /// 		"Pending" : 0.00000000,
-    /// 		"CryptoAddress" : "1MacMr6715hjds342dXuLqXcju6fgwHA31",
-    /// 		"Requested" : false,
-    /// 		"Uuid" : null
-    /// 	}
-    /// }
-    /// ```
-    pub async fn get_balance(&mut self, currency: &str) -> Result<Map<String, Value>> {
+    pub async fn get_balance(&self, currency: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("currency", currency);
-        self.private_query("/account/getbalance", &mut params).await
+        self.private_query(Method::GET, &format!("/balances/{}", currency), &mut params, None).await
     }
 
-    /// Used to retrieve or generate an address for a specific currency.
-    /// If one does not exist, the call will fail and return ADDRESS_GENERATING until one is available.
-    /// "currency" required a string literal for the currency (ex: LTC)
+    /// Used to retrieve the address of a specific currency.
     ///
     /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : {
-    /// 		"Currency" : "VTC",
-    /// 		"Address" : "Vy5SKeKGXUHKS2WVpJ76HYuKAu3URastUo"
-    /// 	}
-    /// }
+    /// {"status": "PROVISIONED", "currencySymbol": "BTC", "cryptoAddress": "1Q...", "cryptoAddressTag": null}
     /// ```
-    pub async fn get_deposit_address(&mut self, currency: &str) -> Result<Map<String, Value>> {
+    pub async fn get_deposit_address(&self, currency: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("currency", currency);
-        self.private_query("/account/getdepositaddress", &mut params).await
+        self.private_query(Method::GET, &format!("/addresses/{}", currency), &mut params, None).await
     }
 
-    /// Used to withdraw funds from your account. note: please account for txfee.
-    /// "currency" required a string literal for the currency (ie. BTC)
-    /// "quantity" required the quantity of coins to withdraw
-    /// "address" required the address where to send the funds.
-    /// "paymentid" optional used for CryptoNotes/BitShareX/Nxt optional field (memo/paymentid)
+    /// Used to withdraw funds from your account, `paymentid` is the optional address tag.
     ///
     /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : {
-    /// 			"uuid" : "68b5a16c-92de-11e3-ba3b-425861b86ab6"
-    /// 	}
-    /// }
+    /// {"id": "68b5a16c-...", "currencySymbol": "BTC", "quantity": "0.5", "cryptoAddress": "1Q...",
+    /// "status": "REQUESTED", ...}
     /// ```
-    pub async fn withdraw(&mut self, currency: &str, quantity: &str, address: &str, paymentid: &str) -> Result<Map<String, Value>> {
+    pub async fn withdraw(&self, currency: &str, quantity: &str, address: &str, paymentid: &str) -> Result<Value> {
+        let mut withdrawal = Map::new();
+        withdrawal.insert("currencySymbol".to_string(), Value::from(currency));
+        withdrawal.insert("quantity".to_string(), Value::from(quantity));
+        withdrawal.insert("cryptoAddress".to_string(), Value::from(address));
+        if !paymentid.is_empty() {
+            withdrawal.insert("cryptoAddressTag".to_string(), Value::from(paymentid));
+        }
         let mut params = HashMap::new();
-        params.insert("currency", currency);
-        params.insert("quantity", quantity);
-        params.insert("address", address);
-        params.insert("paymentid", paymentid);
-        self.private_query("/account/withdraw", &mut params).await
+        self.private_query(Method::POST, "/withdrawals", &mut params, Some(Value::Object(withdrawal))).await
     }
 
     /// Used to retrieve a single order by uuid.
-    /// "uuid" required the uuid of the buy or sell order
-    ///
-    /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : {
-    /// 		"AccountId" : null,
-    /// 		"OrderUuid" : "0cb4c4e4-bdc7-4e13-8c13-430e587d2cc1",
-    /// 		"Exchange" : "BTC-SHLD",
-    /// 		"Type" : "LIMIT_BUY",
-    /// 		"Quantity" : 1000.00000000,
-    /// 		"QuantityRemaining" : 1000.00000000,
-    /// 		"Limit" : 0.00000001,
-    /// 		"Reserved" : 0.00001000,
-    /// 		"ReserveRemaining" : 0.00001000,
-    /// 		"CommissionReserved" : 0.00000002,
-    /// 		"CommissionReserveRemaining" : 0.00000002,
-    /// 		"CommissionPaid" : 0.00000000,
-    /// 		"Price" : 0.00000000,
-    /// 		"PricePerUnit" : null,
-    /// 		"Opened" : "2014-07-13T07:45:46.27",
-    /// 		"Closed" : null,
-    /// 		"IsOpen" : true,
-    /// 		"Sentinel" : "6c454604-22e2-4fb4-892e-179eede20972",
-    /// 		"CancelInitiated" : false,
-    /// 		"ImmediateOrCancel" : false,
-    /// 		"IsConditional" : false,
-    /// 		"Condition" : "NONE",
-    /// 		"ConditionTarget" : null
-    /// 	}
-    /// }
-    /// ```
-    pub async fn get_order(&mut self, uuid: &str) -> Result<Map<String, Value>> {
+    pub async fn get_order(&self, uuid: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("uuid", uuid);
-        self.private_query("/account/getorder", &mut params).await
+        self.private_query(Method::GET, &format!("/orders/{}", uuid), &mut params, None).await
     }
 
-    /// Used to retrieve your order history.
-    /// "market" optional a string literal for the market (ie. BTC-LTC).
-    /// If ommited, will return for all markets
-    ///
-    /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : [{
-    /// 			"OrderUuid" : "fd97d393-e9b9-4dd1-9dbf-f288fc72a185",
-    /// 			"Exchange" : "BTC-LTC",
-    /// 			"TimeStamp" : "2014-07-09T04:01:00.667",
-    /// 			"OrderType" : "LIMIT_BUY",
-    /// 			"Limit" : 0.00000001,
-    /// 			"Quantity" : 100000.00000000,
-    /// 			"QuantityRemaining" : 100000.00000000,
-    /// 			"Commission" : 0.00000000,
-    /// 			"Price" : 0.00000000,
-    /// 			"PricePerUnit" : null,
-    /// 			"IsConditional" : false,
-    /// 			"Condition" : null,
-    /// 			"ConditionTarget" : null,
-    /// 			"ImmediateOrCancel" : false
-    /// 		}, {
-    /// 			"OrderUuid" : "17fd64d1-f4bd-4fb6-adb9-42ec68b8697d",
-    /// 			"Exchange" : "BTC-ZS",
-    /// 			"TimeStamp" : "2014-07-08T20:38:58.317",
-    /// 			"OrderType" : "LIMIT_SELL",
-    /// 			"Limit" : 0.00002950,
-    /// 			"Quantity" : 667.03644955,
-    /// 			"QuantityRemaining" : 0.00000000,
-    /// 			"Commission" : 0.00004921,
-    /// 			"Price" : 0.01968424,
-    /// 			"PricePerUnit" : 0.00002950,
-    /// 			"IsConditional" : false,
-    /// 			"Condition" : null,
-    /// 			"ConditionTarget" : null,
-    /// 			"ImmediateOrCancel" : false
-    /// 		}
-    /// 	]
-    /// }
-    /// ```
-    pub async fn get_order_history(&mut self, market: &str) -> Result<Map<String, Value>> {
+    /// Used to retrieve your closed orders, of a specific market if `market` is not empty, by pages
+    /// of `page_size` orders following the order id `page_token` if it is not empty.
+    pub async fn get_order_history(&self, market: &str, page_size: &str, page_token: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("market", market);
-        self.private_query("/account/getorderhistory", &mut params).await
+        params.insert("marketSymbol", market);
+        params.insert("pageSize", page_size);
+        params.insert("nextPageToken", page_token);
+        self.private_query(Method::GET, "/orders/closed", &mut params, None).await
     }
 
-    /// Used to retrieve your withdrawal history.
-    /// "currency" optional	a string literal for the currecy (ie. BTC).
-    /// If omitted, will return for all currencies
-    ///
-    /// ```json
-    /// {
-	/// "success" : true,
-	/// "message" : "",
-	/// "result" : [{
-	/// 		"PaymentUuid" : "b52c7a5c-90c6-4c6e-835c-e16df12708b1",
-	/// 		"Currency" : "BTC",
-	/// 		"Amount" : 17.00000000,
-	/// 		"Address" : "1DeaaFBdbB5nrHj87x3NHS4onvw1GPNyAu",
-	/// 		"Opened" : "2014-07-09T04:24:47.217",
-	/// 		"Authorized" : true,
-	/// 		"PendingPayment" : false,
-	/// 		"TxCost" : 0.00020000,
-	/// 		"TxId" : null,
-	/// 		"Canceled" : true,
-	/// 		"InvalidAddress" : false
-	/// 	}, {
-	/// 		"PaymentUuid" : "f293da98-788c-4188-a8f9-8ec2c33fdfcf",
-	/// 		"Currency" : "XC",
-	/// 		"Amount" : 7513.75121715,
-	/// 		"Address" : "XVnSMgAd7EonF2Dgc4c9K14L12RBaW5S5J",
-	/// 		"Opened" : "2014-07-08T23:13:31.83",
-	/// 		"Authorized" : true,
-	/// 		"PendingPayment" : false,
-	/// 		"TxCost" : 0.00002000,
-	/// 		"TxId" : "b4a575c2a71c7e56d02ab8e26bb1ef0a2f6cf2094f6ca2116476a569c1e84f6e",
-	/// 		"Canceled" : false,
-	/// 		"InvalidAddress" : false
-	/// 	}
-	///  ]
-    /// }
-    /// ```
-    pub async fn get_withdrawal_history(&mut self, currency: &str) -> Result<Map<String, Value>> {
+    /// Used to retrieve your completed withdrawals, of a specific currency if `currency` is not
+    /// empty.
+    pub async fn get_withdrawal_history(&self, currency: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("currency", currency);
-        self.private_query("/account/getwithdrawalhistory", &mut params).await
+        params.insert("currencySymbol", currency);
+        self.private_query(Method::GET, "/withdrawals/closed", &mut params, None).await
     }
 
-    /// Used to retrieve your deposit history.
-    /// "currency" optional a string literal for the currecy (ie. BTC).
-    /// If omitted, will return for all currencies
-    ///
-    /// ```json
-    /// {
-    /// 	"success" : true,
-    /// 	"message" : "",
-    /// 	"result" : [{
-    /// 			"PaymentUuid" : "554ec664-8842-4fe9-b491-06225becbd59",
-    /// 			"Currency" : "BTC",
-    /// 			"Amount" : 0.00156121,
-    /// 			"Address" : "1K37yQZaGrPKNTZ5KNP792xw8f7XbXxetE",
-    /// 			"Opened" : "2014-07-11T03:41:25.323",
-    /// 			"Authorized" : true,
-    /// 			"PendingPayment" : false,
-    /// 			"TxCost" : 0.00020000,
-    /// 			"TxId" : "70cf6fdccb9bd38e1a930e13e4ae6299d678ed6902da710fa3cc8d164f9be126",
-    /// 			"Canceled" : false,
-    /// 			"InvalidAddress" : false
-    /// 		}, {
-    /// 			"PaymentUuid" : "d3fdf168-3d8e-40b6-8fe4-f46e2a7035ea",
-    /// 			"Currency" : "BTC",
-    /// 			"Amount" : 0.11800000,
-    /// 			"Address" : "1Mrcar6715hjds34pdXuLqXcju6QgwHA31",
-    /// 			"O
-    /// 			pened" : "2014-07-03T20:27:07.163",
-    /// 			"Authorized" : true,
-    /// 			"PendingPayment" : false,
-    /// 			"TxCost" : 0.00020000,
-    /// 			"TxId" : "3efd41b3a051433a888eed3ecc174c1d025a5e2b486eb418eaaec5efddda22de",
-    /// 			"Canceled" : false,
-    /// 			"InvalidAddress" : false
-    /// 		}
-    ///     ]
-    /// }
-    /// ```
-    pub async fn get_deposit_history(&mut self, currency: &str) -> Result<Map<String, Value>> {
+    /// Used to retrieve your completed deposits, of a specific currency if `currency` is not
+    /// empty.
+    pub async fn get_deposit_history(&self, currency: &str) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("currency", currency);
-        self.private_query("/account/getdeposithistory", &mut params).await
+        params.insert("currencySymbol", currency);
+        self.private_query(Method::GET, "/deposits/closed", &mut params, None).await
     }
 }
@@ -2,10 +2,9 @@
 //! This a more convenient and safe way to deal with the exchange since methods return a Result<>
 //! but this generic API does not provide all the functionnality that Bittrex offers.
 
-use bigdecimal::BigDecimal;
-use std::str::FromStr;
+use chrono::{DateTime, Utc};
 
-use crate::exchange::{ExchangeApi};
+use crate::exchange::{Exchange, ExchangeApi, ExchangeCapabilities};
 use crate::bittrex::api::BittrexApi;
 
 use crate::error::*;
@@ -13,165 +12,314 @@ use crate::types::*;
 use crate::bittrex::utils;
 use crate::helpers;
 use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Closed orders per page of the order history, the maximum allowed
+const HISTORY_PAGE_SIZE: usize = 200;
+
+/// Depths accepted by the order book endpoint
+const BOOK_DEPTHS: [usize; 3] = [1, 25, 500];
+
+impl BittrexApi {
+    /// The `depth` best levels of each side of the order book of `pair`, taken in one request so
+    /// that both sides are consistent. The exchange only serves 1, 25 or 500 levels, the smallest
+    /// of these covering `depth` is requested.
+    pub async fn order_book_snapshot(&self, pair: Pair, depth: usize) -> Result<Orderbook> {
+        let symbol = utils::get_market_symbol(&pair).ok_or(Error::PairUnsupported)?;
+        let result = self.get_order_book(&symbol, &requested_depth(depth).to_string()).await?;
+
+        let mut asks = parse_levels(&result["ask"], "ask")?;
+        asks.truncate(depth);
+        let mut bids = parse_levels(&result["bid"], "bid")?;
+        bids.truncate(depth);
+        Ok(Orderbook {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            pair,
+            asks,
+            bids,
+        })
+    }
+}
+
+/// The smallest depth served covering `depth`, the largest one past it
+fn requested_depth(depth: usize) -> usize {
+    *BOOK_DEPTHS.iter().find(|&&d| d >= depth).unwrap_or(&BOOK_DEPTHS[2])
+}
 
 #[async_trait]
 impl ExchangeApi for BittrexApi {
-    async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
-
-        let raw_response = self.get_market_summary(pair_name).await?;
-
-        let result = utils::parse_result(&raw_response)?;
-        let result_array = result.as_array();
-        let result_obj = result_array.unwrap()[0].as_object().unwrap();
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
+        let symbol = utils::get_market_symbol(&pair).ok_or(Error::PairUnsupported)?;
 
-        let price_str = result_obj.get("Last").unwrap().as_f64().unwrap().to_string();
-        let price = BigDecimal::from_str(&price_str).unwrap();
+        let tick = self.get_ticker(&symbol).await?;
+        let summary = self.get_market_summary(&symbol).await?;
 
-        let ask_str = result_obj.get("Ask").unwrap().as_f64().unwrap().to_string();
-        let ask = BigDecimal::from_str(&ask_str).unwrap();
-
-        let bid_str = result_obj.get("Bid").unwrap().as_f64().unwrap().to_string();
-        let bid = BigDecimal::from_str(&bid_str).unwrap();
+        parse_ticker(pair, &tick, &summary, self.keep_raw)
+    }
 
-        let volume_str = result_obj.get("Volume").unwrap().as_f64().unwrap().to_string();
-        let vol = BigDecimal::from_str(&volume_str).unwrap();
+    async fn tickers(&self, pairs: &[Pair]) -> Result<HashMap<Pair, Ticker>> {
+        for pair in pairs {
+            if utils::get_market_symbol(pair).is_none() {
+                return Err(Error::PairUnsupported);
+            }
+        }
+        let ticks = by_market(self.get_tickers().await?, pairs)?;
+        let summaries = by_market(self.get_market_summaries().await?, pairs)?;
 
-        Ok(Ticker {
-            timestamp: helpers::get_unix_timestamp_ms(),
-            pair: pair,
-            last_trade_price: price,
-            lowest_ask: ask,
-            highest_bid: bid,
-            volume: Some(vol),
-        })
+        let mut tickers = HashMap::new();
+        for (pair, tick) in ticks {
+            if let Some(summary) = summaries.get(&pair) {
+                tickers.insert(pair, parse_ticker(pair, &tick, summary, self.keep_raw)?);
+            }
+        }
+        if let Some(missing) = pairs.iter().find(|p| !tickers.contains_key(p)) {
+            return Err(Error::MissingField(format!("{:?}", missing)));
+        }
+        Ok(tickers)
+    }
 
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
+        self.order_book_snapshot(pair, 500).await
     }
 
-    async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+    async fn add_order(&self,
+                 order_type: OrderType,
+                 pair: Pair,
+                 quantity: Volume,
+                 price: Option<Price>)
+                 -> Result<OrderInfo> {
+        let request = OrderRequest::new(order_type, pair, &quantity, &price);
 
-        let raw_response = self.get_order_book(pair_name, "both").await?;
+        let symbol = utils::get_market_symbol(&pair).ok_or(Error::PairUnsupported)?;
+        let quantity = quantity.to_string();
 
-        let result = utils::parse_result(&raw_response)?;
+        let result = match order_type {
+            OrderType::BuyLimit | OrderType::SellLimit => {
+                let price = price.ok_or(Error::MissingPrice)?.to_string();
+                let direction = if order_type == OrderType::BuyLimit { "BUY" } else { "SELL" };
+                self.new_order(&symbol, direction, "LIMIT", &quantity, Some(&price), "GOOD_TIL_CANCELLED").await
+            }
+            OrderType::BuyMarket => self.new_order(&symbol, "BUY", "MARKET", &quantity, None, "IMMEDIATE_OR_CANCEL").await,
+            OrderType::SellMarket => self.new_order(&symbol, "SELL", "MARKET", &quantity, None, "IMMEDIATE_OR_CANCEL").await,
+            _ => return Err(Error::OrderTypeUnsupported),
+        }?;
 
-        let mut ask_offers = Vec::new();    // buy orders
-        let mut bid_offers = Vec::new();    // sell orders
+        let id = result["id"].as_str().ok_or_else(|| Error::MissingField("id".to_string()))?;
+        Ok(OrderInfo {
+               timestamp: helpers::get_unix_timestamp_ms(),
+               identifier: vec![id.to_string()],
+               raw: helpers::raw_payload(self.keep_raw, &result),
+               request: Some(request),
+        })
+    }
 
-        let buy_orders = result["buy"].as_array()
-        .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["buy"])))?;
+    async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+        for id in &order.identifier {
+            self.cancel(id).await?;
+        }
+        Ok(())
+    }
 
-        let sell_orders = result["sell"].as_array()
-        .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["sell"])))?;
+    async fn cancel_all_orders(&self) -> Result<()> {
+        self.cancel_open("").await?;
+        Ok(())
+    }
 
-        for ask in buy_orders {
-            let ask_obj = ask.as_object().unwrap();
+    async fn fees(&self, pair: Pair) -> Result<FeeRates> {
+        let symbol = utils::get_market_symbol(&pair).ok_or(Error::PairUnsupported)?;
+        let result = self.get_trading_fees().await?;
+        let fees = result.as_array().ok_or_else(|| Error::InvalidFieldFormat("fees".to_string()))?;
+        match fees.iter().find(|f| f["marketSymbol"].as_str() == Some(symbol.as_str())) {
+            Some(f) => Ok(FeeRates::new(helpers::from_json_bigdecimal(&f["makerRate"], "makerRate")?, helpers::from_json_bigdecimal(&f["takerRate"], "takerRate")?)),
+            None => Ok(Exchange::Bittrex.default_fees()),
+        }
+    }
 
-            let price_str = ask_obj.get("Rate").unwrap().as_f64().unwrap().to_string();
-            let price = BigDecimal::from_str(&price_str).unwrap();
+    async fn balances(&self) -> Result<Balances> {
+        let result = self.get_balances().await?;
 
+        let result_array = result.as_array().ok_or_else(|| Error::InvalidFieldFormat("balances".to_string()))?;
 
-            let volume_str = ask_obj.get("Quantity").unwrap().as_f64().unwrap().to_string();
-            let volume = BigDecimal::from_str(&volume_str).unwrap();
+        let mut balances = Balances::new();
 
-            ask_offers.push((price, volume));
+        for balance in result_array {
+            let currency = balance["currencySymbol"].as_str().and_then(utils::get_currency_enum);
+            if let Some(c) = currency {
+                balances.insert(c, helpers::from_json_bigdecimal(&balance["available"], "available")?);
+            }
         }
+        Ok(balances)
+    }
 
-        for bid in sell_orders {
-            let bid_obj = bid.as_object().unwrap();
-
-            let price_str = bid_obj.get("Rate").unwrap().as_f64().unwrap().to_string();
-            let price = BigDecimal::from_str(&price_str).unwrap();
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        let result = self.get_balances().await?;
 
+        let result_array = result.as_array().ok_or_else(|| Error::InvalidFieldFormat("balances".to_string()))?;
 
-            let volume_str = bid_obj.get("Quantity").unwrap().as_f64().unwrap().to_string();
-            let volume = BigDecimal::from_str(&volume_str).unwrap();
+        let mut balances = DetailedBalances::new();
 
-            bid_offers.push((price, volume));
+        for balance in result_array {
+            let currency = balance["currencySymbol"].as_str().and_then(utils::get_currency_enum);
+            if let Some(c) = currency {
+                let total = helpers::from_json_bigdecimal(&balance["total"], "total")?;
+                let available = helpers::from_json_bigdecimal(&balance["available"], "available")?;
+                balances.insert(c, Balance::new(available.clone(), total - available));
+            }
         }
+        Ok(balances)
+    }
 
-        Ok(Orderbook {
+    async fn withdraw(&self, currency: Currency, amount: Amount, destination: &str) -> Result<WithdrawalInfo> {
+        let currency_name = utils::get_currency_string(currency).ok_or_else(|| Error::InvalidArguments)?;
+        let result = self.withdraw(&currency_name, &amount.to_string(), destination, "").await?;
+        Ok(WithdrawalInfo {
             timestamp: helpers::get_unix_timestamp_ms(),
-            pair: pair,
-            asks: ask_offers,
-            bids: bid_offers,
+            identifier: result["id"].as_str().map(|id| id.to_string()),
         })
     }
 
-    async fn add_order(&mut self,
-                 order_type: OrderType,
-                 pair: Pair,
-                 quantity: Volume,
-                 price: Option<Price>)
-                 -> Result<OrderInfo> {
+    /// Bittrex reports the executions of an order as a whole : every closed order that was at
+    /// least partly filled is one fill at its average price, without trade id nor liquidity.
+    /// The commission is paid in the quote currency.
+    async fn fills_page(&self, pair: Pair, cursor: Option<String>) -> Result<FillsPage> {
+        let symbol = utils::get_market_symbol(&pair).ok_or(Error::PairUnsupported)?;
+        let result = self.get_order_history(&symbol, &HISTORY_PAGE_SIZE.to_string(), cursor.as_deref().unwrap_or_default()).await?;
+        let orders = result.as_array().ok_or_else(|| Error::InvalidFieldFormat("orders".to_string()))?;
+        let mut fills = vec![];
+        for order in orders {
+            fills.extend(utils::parse_fill(pair, order)?);
+        }
+        // Orders come newest first, the next page follows the last one
+        let next = match orders.last() {
+            Some(order) if orders.len() == HISTORY_PAGE_SIZE => order["id"].as_str().map(|id| id.to_string()),
+            _ => None,
+        };
+        Ok(FillsPage { fills, next })
+    }
 
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(pair_str) => pair_str,
-            None => return Err(ErrorKind::PairUnsupported.into())
+    async fn time(&self) -> Result<i64> {
+        let sent_ms = helpers::get_unix_timestamp_ms();
+        let result = self.ping().await?;
+        let server_ms = result["serverTime"].as_i64().ok_or_else(|| Error::MissingField("serverTime".to_string()))?;
+        self.clock.observe(server_ms, sent_ms, helpers::get_unix_timestamp_ms());
+        Ok(server_ms)
+    }
+
+    /// From `/ping`, which fails with a 503 during maintenance
+    async fn status(&self) -> Result<ExchangeStatus> {
+        let sent_ms = helpers::get_unix_timestamp_ms();
+        let (state, message) = match self.ping().await {
+            Ok(_) => (ExchangeState::Online, None),
+            Err(Error::ServiceUnavailable(msg)) => (ExchangeState::Maintenance, Some(msg)),
+            Err(e) => return Err(e),
         };
+        let latency_ms = (helpers::get_unix_timestamp_ms() - sent_ms).max(0) as u64;
+        Ok(ExchangeStatus { state, latency_ms, message })
+    }
 
-        let raw_response = match order_type {
-            OrderType::BuyLimit => {
-                if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
-                }
-                self.buy_limit(pair_name, &quantity.to_string(), &price.unwrap().to_string()).await
-            }
-            OrderType::BuyMarket => {
-                let min_price = "0.000000001";
-                self.buy_limit(pair_name, &quantity.to_string(), min_price).await
-            }
-            OrderType::SellLimit => {
-                if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
-                }
-                self.sell_limit(pair_name, &quantity.to_string(), &price.unwrap().to_string()).await
-            }
-            OrderType::SellMarket => {
-                let max_price = "999999999.99";
-                self.buy_limit(pair_name, &quantity.to_string(), max_price).await
-            }
-        }?;
+    fn clock_skew(&self) -> Option<i64> {
+        self.clock.skew()
+    }
 
-        let result = utils::parse_result(&raw_response)?;
+    fn limits(&self) -> RateLimitStatus {
+        self.rate_limits.status()
+    }
 
-        let result_obj = result.as_object().unwrap();
+    /// The 100 most recent trades
+    async fn public_trades(&self, pair: Pair, since: Option<DateTime<Utc>>) -> Result<Vec<PublicTrade>> {
+        let symbol = utils::get_market_symbol(&pair).ok_or(Error::PairUnsupported)?;
+        let result = self.get_market_history(&symbol).await?;
+        let trades = result.as_array().ok_or_else(|| Error::InvalidFieldFormat("trades".to_string()))?;
+        let since_ms = since.map_or(i64::MIN, |s| s.timestamp_millis());
+        let mut trades = trades.iter().map(|t| {
+            let executed_at = t["executedAt"].as_str().ok_or_else(|| Error::MissingField("executedAt".to_string()))?;
+            let event_ms = DateTime::parse_from_rfc3339(executed_at)
+                .map_err(|_| Error::InvalidFieldFormat(executed_at.to_string()))?
+                .timestamp_millis();
+            Ok(PublicTrade {
+                id: t["id"].as_str().map(|id| id.to_string()),
+                event_ms,
+                pair,
+                price: helpers::from_json_bigdecimal(&t["rate"], "rate")?,
+                amount: helpers::from_json_bigdecimal(&t["quantity"], "quantity")?,
+                side: utils::get_trade_type(t["takerSide"].as_str().unwrap_or_default()),
+            })
+        }).collect::<Result<Vec<PublicTrade>>>()?;
+        trades.retain(|t| t.event_ms > since_ms);
+        trades.sort_by_key(|t| t.event_ms);
+        Ok(trades)
+    }
 
-        Ok(OrderInfo {
-               timestamp: helpers::get_unix_timestamp_ms(),
-               identifier: vec![result_obj.get("uuid").unwrap().as_str().unwrap().to_string()],
-        })
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        let markets = self.get_markets().await?;
+        let markets = markets.as_array().ok_or(Error::BadParse)?;
+        Ok(markets.iter()
+            .filter(|m| m["status"] == "ONLINE")
+            .filter_map(|m| m["symbol"].as_str().and_then(utils::get_market_pair))
+            .collect())
     }
 
-    async fn balances(&mut self) -> Result<Balances> {
-        let raw_response = self.get_balances().await?;
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Bittrex.capabilities()
+    }
+}
 
-        let result = utils::parse_result(&raw_response)?;
+/// Index the entries of an array of markets by pair, keeping only `pairs`
+fn by_market(result: Value, pairs: &[Pair]) -> Result<HashMap<Pair, Value>> {
+    let entries = match result {
+        Value::Array(entries) => entries,
+        _ => return Err(Error::InvalidFieldFormat("result".to_string())),
+    };
+    Ok(entries.into_iter()
+        .filter_map(|e| {
+            let pair = e["symbol"].as_str().and_then(utils::get_market_pair)?;
+            if pairs.contains(&pair) { Some((pair, e)) } else { None }
+        })
+        .collect())
+}
 
-        let result_array = result.as_array().unwrap();
+fn parse_levels(levels: &Value, side: &str) -> Result<Vec<(Price, Volume)>> {
+    let levels = levels.as_array().ok_or_else(|| Error::InvalidFieldFormat(side.to_string()))?;
+    levels.iter()
+        .map(|l| Ok((helpers::from_json_bigdecimal(&l["rate"], "rate")?, helpers::from_json_bigdecimal(&l["quantity"], "quantity")?)))
+        .collect()
+}
 
-        let mut balances = Balances::new();
+fn parse_ticker(pair: Pair, tick: &Value, summary: &Value, keep_raw: bool) -> Result<Ticker> {
+    let price = helpers::from_json_bigdecimal(&tick["lastTradeRate"], "lastTradeRate")?;
+    let ask = helpers::from_json_bigdecimal(&tick["askRate"], "askRate")?;
+    let bid = helpers::from_json_bigdecimal(&tick["bidRate"], "bidRate")?;
+    let vol = helpers::from_json_bigdecimal(&summary["volume"], "volume")?;
+
+    Ok(Ticker {
+        timestamp: helpers::get_unix_timestamp_ms(),
+        pair: pair,
+        last_trade_price: price,
+        lowest_ask: ask,
+        highest_bid: bid,
+        volume: Some(vol),
+        bid_size: None,
+        ask_size: None,
+        open_24h: None,
+        high_24h: helpers::from_json_bigdecimal_opt(&summary["high"]),
+        low_24h: helpers::from_json_bigdecimal_opt(&summary["low"]),
+        percent_change: helpers::from_json_bigdecimal_opt(&summary["percentChange"]),
+        raw: if keep_raw { Some(serde_json::json!({ "ticker": tick, "summary": summary })) } else { None },
+    })
+}
 
-        for currency in result_array {
-            let currency_obj = currency.as_object().unwrap();
-            let currency_str = currency_obj.get("Currency").unwrap().as_str().unwrap();
-            let currency = utils::get_currency_enum(&currency_str);
-
-            match currency {
-                Some(c) => {
-                    let amount_str = currency_obj.get("Available").unwrap().as_f64().unwrap().to_string();
-                    let amount = BigDecimal::from_str(&amount_str).unwrap();
-                    balances.insert(c, amount);
-                },
-                _ => ()
-            }
-        }
-        Ok(balances)
+#[cfg(test)]
+mod bittrex_generic_api_tests {
+    use super::*;
+
+    #[test]
+    fn the_smallest_depth_served_is_requested() {
+        assert_eq!(requested_depth(0), 1);
+        assert_eq!(requested_depth(1), 1);
+        assert_eq!(requested_depth(10), 25);
+        assert_eq!(requested_depth(25), 25);
+        assert_eq!(requested_depth(26), 500);
+        assert_eq!(requested_depth(1000), 500);
     }
 }
@@ -5,6 +5,7 @@ pub mod api;
 pub mod generic_api;
 pub mod credentials;
 pub mod utils;
+#[cfg(feature = "streaming")]
 pub mod streaming_api;
 pub mod models;
 
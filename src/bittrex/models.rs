@@ -1,7 +1,9 @@
 #[allow(non_snake_case)]
 
 use serde::{Serialize, Deserialize};
-use crate::types::LiveTrade;
+use bigdecimal::BigDecimal;
+use crate::types::{LiveSummary, LiveTrade};
+use super::utils::get_pair_enum;
 
 #[allow(non_snake_case)]
 #[derive(Debug, Serialize, Deserialize)]
@@ -129,7 +131,7 @@ pub(crate) struct OrderLog {
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct Fill {
     #[serde(alias = "FI")]
-    FillId: i32,
+    pub FillId: i32,
     #[serde(alias = "OT")]
     pub OrderType: String,
     #[serde(alias = "R")]
@@ -159,23 +161,23 @@ pub(crate) struct MarketDelta {
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct SummaryDelta {
     #[serde(alias = "M")]
-    MarketName: String,
+    pub MarketName: String,
     #[serde(alias = "H")]
-    High: f32,
+    pub High: f32,
     #[serde(alias = "L")]
-    Low: f32,
+    pub Low: f32,
     #[serde(alias = "V")]
-    Volume: f32,
+    pub Volume: f32,
     #[serde(alias = "l")]
-    Last: f32,
+    pub Last: f32,
     #[serde(alias = "m")]
     BaseVolume: f32,
     #[serde(alias = "T")]
-    TimeStamp: i64,
+    pub TimeStamp: i64,
     #[serde(alias = "B")]
-    Bid: f32,
+    pub Bid: f32,
     #[serde(alias = "A")]
-    Ask: f32,
+    pub Ask: f32,
     #[serde(alias = "G")]
     OpenBuyOrders: i32,
     #[serde(alias = "g")]
@@ -186,12 +188,28 @@ pub(crate) struct SummaryDelta {
     Created: i64,
 }
 
+impl Into<LiveSummary> for SummaryDelta {
+    fn into(self) -> LiveSummary {
+        let pair = get_pair_enum(self.MarketName.as_str()).map(|p| format!("{:?}", p)).unwrap_or(self.MarketName);
+        LiveSummary {
+            event_ms: self.TimeStamp,
+            pair,
+            high: BigDecimal::from(self.High),
+            low: BigDecimal::from(self.Low),
+            volume: BigDecimal::from(self.Volume),
+            last: BigDecimal::from(self.Last),
+            bid: BigDecimal::from(self.Bid),
+            ask: BigDecimal::from(self.Ask),
+        }
+    }
+}
+
 #[allow(non_snake_case)]
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct SummaryDeltaResponse {
     #[serde(alias = "N")]
     Nonce: i32,
     #[serde(alias = "D")]
-    Deltas: Vec<SummaryDelta>,
+    pub Deltas: Vec<SummaryDelta>,
 }
 
@@ -1,102 +1,211 @@
 use crate::coinnect::Credentials;
-use crate::exchange_bot::{ExchangeBot};
+use crate::exchange_bot::{ExchangeBot, RawFrameEvent, RawFrameObserver, Snapshot};
 use crate::error::*;
 use super::models::*;
 use serde_json::Value;
 use std::io::Read;
 use actix::{Addr, Recipient};
-use crate::types::{LiveEvent, Channel, Orderbook, Pair, LiveAggregatedOrderBook, LiveEventEnveloppe, LiveTrade};
+use crate::types::{LiveEvent, Channel, Orderbook, Pair, LiveAggregatedOrderBook, DEFAULT_BOOK_DEPTH, LiveEventEnveloppe, LiveSummary, LiveTrade, SubscriptionResult, TradeMeta, TradeTape, WarmUp};
 use signalr_rs::hub::client::{HubClientError, HubClientHandler, HubClient, HubQuery, RestartPolicy, PendingQuery};
 use serde::de::DeserializeOwned;
 use libflate::deflate::Decoder;
 use bigdecimal::BigDecimal;
 use std::collections::{BTreeMap, HashMap, HashSet};
-use crate::exchange::Exchange;
+use crate::exchange::{Exchange, ExchangeCapabilities, ExchangeSettings};
+use crate::helpers::delivery::{Delivery, DeliveryPolicy};
+use crate::helpers::secret::SecretString;
+use crate::helpers;
+use crate::metrics;
+use crate::event_stream::CallbackForwarder;
 use std::rc::Rc;
+use bytes::Bytes;
 use std::cell::RefCell;
 
 #[derive(Debug)]
 pub struct BittrexStreamingApi {
-    api_key: String,
-    api_secret: String,
+    api_key: SecretString,
+    api_secret: SecretString,
     customer_id: String,
-    pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    delivery: RefCell<Delivery<LiveEventEnveloppe>>,
     books: Rc<RefCell<HashMap<Pair, LiveAggregatedOrderBook>>>,
     order_book_pairs: HashSet<Pair>,
-    trade_pairs: HashSet<Pair>
+    trade_pairs: HashSet<Pair>,
+    summary_pairs: HashSet<Pair>,
+    warm_up: WarmUp,
+    depth: i8,
+    tape: RefCell<TradeTape>,
+    raw_observers: Rc<RefCell<Vec<RawFrameObserver>>>,
 }
 
 pub struct BittrexBot {
-    addr: Addr<HubClient>
+    addr: Addr<HubClient>,
+    books: Rc<RefCell<HashMap<Pair, LiveAggregatedOrderBook>>>,
+    raw_observers: Rc<RefCell<Vec<RawFrameObserver>>>,
 }
 
 impl ExchangeBot for BittrexBot {
     fn is_connected(&self) -> bool {
-        unimplemented!()
+        self.addr.connected()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Bittrex.capabilities()
+    }
+
+    /// The hub client parses the frames, the hub messages are sent as `{"M": method, "A": message}`
+    fn observe_raw_frames(&self, recipient: Recipient<RawFrameEvent>) -> Result<()> {
+        self.raw_observers.borrow_mut().push(RawFrameObserver(recipient));
+        Ok(())
+    }
+}
+
+impl BittrexBot {
+    /// The current order book of `pair`, trades are not tracked.
+    /// The hub client does not take custom messages so the books are read directly.
+    pub fn snapshot(&self, pair: Pair) -> Option<Snapshot> {
+        let books = self.books.borrow();
+        let agg = books.get(&pair).filter(|agg| agg.is_warm())?;
+        Some(Snapshot { orderbook: Some(agg.order_book()), last_trade: None })
     }
 }
 
 const BITTREX_HUB: &'static str = "c2";
+const SIGNALR_URL: &'static str = "https://socket.bittrex.com/signalr/";
 
 impl BittrexStreamingApi {
     /// Create a new bittrex exchange bot, unavailable channels and currencies are ignored
-    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<BittrexBot> {
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, warm_up: WarmUp, depth: Option<i8>, delivery: DeliveryPolicy, recipients: Vec<Recipient<LiveEventEnveloppe>>, raw_frames: Option<Recipient<RawFrameEvent>>) -> Result<BittrexBot> {
+        let depth = depth.unwrap_or(DEFAULT_BOOK_DEPTH);
+        creds.environment()?.production_only(Exchange::Bittrex)?;
+        let connection = creds.connection();
+        // The signalr client opens its own connections
+        connection.unsupported(Exchange::Bittrex, false, true)?;
         // Live order book pairs
         let mut map = channels.clone();
         let order_book_pairs: &HashSet<Pair> = map.entry(Channel::LiveFullOrderBook).or_default();
         // Live trade pairs
         let mut map = channels.clone();
         let trade_pairs : &HashSet<Pair> = map.entry(Channel::LiveTrades).or_default();
+        // Live summary pairs
+        let summary_pairs = channels.get(&Channel::LiveSummary).cloned().unwrap_or_default();
 
         let api = Box::new(BittrexStreamingApi {
-            api_key: creds.get("api_key").unwrap_or_default(),
-            api_secret: creds.get("api_secret").unwrap_or_default(),
+            api_key: creds.get("api_key").unwrap_or_default().into(),
+            api_secret: creds.get("api_secret").unwrap_or_default().into(),
             customer_id: creds.get("customer_id").unwrap_or_default(),
-            recipients,
+            delivery: RefCell::new(Delivery::new(recipients, delivery)),
             books: Rc::new(RefCell::new(HashMap::new())),
             order_book_pairs: order_book_pairs.clone(),
             trade_pairs: trade_pairs.clone(),
+            summary_pairs,
+            warm_up,
+            depth,
+            tape: RefCell::new(TradeTape::default()),
+            raw_observers: Rc::new(RefCell::new(raw_frames.into_iter().map(RawFrameObserver).collect())),
         });
         let rc = api.books.clone();
+        let raw_observers = api.raw_observers.clone();
 
-        let mut books = rc.borrow_mut();
-        for &pair in order_book_pairs {
-            books.insert(pair, LiveAggregatedOrderBook::default(pair));
+        {
+            let mut books = rc.borrow_mut();
+            for &pair in order_book_pairs {
+                books.insert(pair, LiveAggregatedOrderBook::with_warm_up(pair, warm_up).with_depth(depth));
+            }
         }
 
-        // SignalR Client
-        let client = HubClient::new(BITTREX_HUB, "https://socket.bittrex.com/signalr/", 20, RestartPolicy::Always, api).await;
+        // SignalR Client, it reconnects by itself so only the primary endpoint is used
+        let client = HubClient::new(BITTREX_HUB, &connection.ws_url(SIGNALR_URL), 20, RestartPolicy::Always, api).await;
         match client {
             Ok(addr) => {
-                if !order_book_pairs.is_empty() {
-                    for &pair in order_book_pairs {
-                        let currency = *super::utils::get_pair_string(&pair).unwrap();
-                        addr.do_send(HubQuery::new(BITTREX_HUB.to_string(), "QueryExchangeState".to_string(), vec![currency.to_string()], "QE2".to_string()));
-                    }
-                }
-                return Ok(BittrexBot { addr });
+                return Ok(BittrexBot { addr, books: rc.clone(), raw_observers });
             }
             Err(e) => {
-                return Err(ErrorKind::Hub(e).into());
+                return Err(Error::Hub(e));
             }
         }
     }
 
-    fn deflate<T>(binary: &String) -> Result<T> where T: DeserializeOwned {
-        let decoded = base64::decode(binary).map_err(|e| ErrorKind::Hub(HubClientError::Base64DecodeError(e)))?;
+    /// Create a new bittrex exchange bot which invokes `callback` for every live event
+    pub async fn subscribe_with<C, F>(creds: Box<C>, settings: ExchangeSettings, callback: F) -> Result<BittrexBot>
+        where C: Credentials, F: FnMut(LiveEventEnveloppe) + 'static {
+        let addr = CallbackForwarder::start_new(callback);
+        BittrexStreamingApi::new_bot(creds, settings.channels(Exchange::Bittrex), settings.warm_up(), settings.depth(), settings.delivery, vec![addr.recipient()], settings.raw_frames.map(|o| o.0)).await
+    }
+
+    /// Decode a base64 encoded, deflated JSON message of the hub
+    pub fn deflate<T>(binary: &String) -> Result<T> where T: DeserializeOwned {
+        let decoded = base64::decode(binary).map_err(|e| Error::Hub(HubClientError::Base64DecodeError(e)))?;
         let mut decoder = Decoder::new(&decoded[..]);
         let mut decoded_data: Vec<u8> = Vec::new();
-        decoder.read_to_end(&mut decoded_data).map_err(|_| ErrorKind::Hub(HubClientError::InvalidData { data: vec!["cannot deflate".to_string()]}))?;
+        decoder.read_to_end(&mut decoded_data).map_err(|_| Error::Hub(HubClientError::InvalidData { data: vec!["cannot deflate".to_string()]}))?;
         let v: &[u8] = &decoded_data;
-        serde_json::from_slice::<T>(v).map_err(|e| ErrorKind::Hub(HubClientError::ParseError(e)).into())
+        serde_json::from_slice::<T>(v).map_err(|e| Error::Hub(HubClientError::ParseError(e)))
+    }
+
+    /// The order book of the encoded result of a `QueryExchangeState` query, limited to `depth`
+    /// levels per side
+    pub fn exchange_state_snapshot(binary: &String, depth: usize) -> Result<Orderbook> {
+        BittrexStreamingApi::state_snapshot(BittrexStreamingApi::deflate::<ExchangeState>(binary)?, depth)
+    }
+
+    /// The `depth` best levels of each side of an exchange state, the best first
+    fn state_snapshot(state: ExchangeState, depth: usize) -> Result<Orderbook> {
+        let pair = *super::utils::get_pair_enum(state.MarketName.as_str()).ok_or(Error::PairUnsupported)?;
+        let levels = |orders: Vec<OrderPair>| orders.into_iter().map(|op| (BigDecimal::from(op.R), BigDecimal::from(op.Q)));
+        let mut asks: Vec<(BigDecimal, BigDecimal)> = levels(state.Sells).collect();
+        asks.sort_by(|a, b| a.0.cmp(&b.0));
+        asks.truncate(depth);
+        let mut bids: Vec<(BigDecimal, BigDecimal)> = levels(state.Buys).collect();
+        bids.sort_by(|a, b| b.0.cmp(&a.0));
+        bids.truncate(depth);
+        Ok(Orderbook { timestamp: helpers::get_unix_timestamp_ms(), pair, asks, bids })
     }
 
     fn deflate_array<T>(a: &Value) -> Result<T> where T: DeserializeOwned {
         let data: Vec<String> = serde_json::from_value(a.clone())?;
-        let binary = data.first().ok_or(ErrorKind::Hub(HubClientError::MissingData))?;
+        let binary = data.first().ok_or(Error::Hub(HubClientError::MissingData))?;
         BittrexStreamingApi::deflate::<T>(binary)
     }
 
+    /// Results of the subscriptions of the query `id`, `SD_<market>` for the deltas of the book and
+    /// the trades of a market, `QE_<market>` for the state of its book
+    fn subscription_results(&self, id: &str, reason: Option<&str>) -> Vec<LiveEvent> {
+        let pair = match id.get(3..).and_then(super::utils::get_pair_enum) {
+            Some(pair) => *pair,
+            None => return vec![],
+        };
+        let mut channels = vec![];
+        if self.order_book_pairs.contains(&pair) {
+            channels.push(Channel::LiveFullOrderBook);
+        }
+        if id.starts_with("SD_") && self.trade_pairs.contains(&pair) {
+            channels.push(Channel::LiveTrades);
+        }
+        channels.into_iter().map(|channel| LiveEvent::SubscriptionResult(match reason {
+            Some(reason) => SubscriptionResult::rejected(channel, pair, reason),
+            None => SubscriptionResult::accepted(channel, pair),
+        })).collect()
+    }
+
+    fn send(&self, le: LiveEvent) {
+        metrics::ws_event(Exchange::Bittrex, &le);
+        self.delivery.borrow_mut().send(LiveEventEnveloppe(Exchange::Bittrex, le));
+    }
+
+    fn notify_raw(&self, method: &str, message: &Value) {
+        let observers = self.raw_observers.borrow();
+        if observers.is_empty() {
+            return;
+        }
+        let payload = Bytes::from(serde_json::json!({"M": method, "A": message}).to_string());
+        for r in observers.iter() {
+            let event = RawFrameEvent { exchange: Exchange::Bittrex, channel: BITTREX_HUB.to_string(), payload: payload.clone() };
+            if let Err(e) = r.0.do_send(event) {
+                trace!("Bittrex : unable to send a raw frame : {}", e);
+            }
+        }
+    }
+
     fn deflate_string<T>(a: &Value) -> Result<T> where T: DeserializeOwned {
         let binary: String = serde_json::from_value(a.clone())?;
         BittrexStreamingApi::deflate::<T>(&binary)
@@ -111,15 +220,40 @@ impl HubClientHandler for BittrexStreamingApi {
             let currencies : Vec<String> = all_pairs.iter().map(|p| (*super::utils::get_pair_string(p).unwrap()).to_string()).collect();
             info!("Bittrex : connecting to ExchangeDeltas for {:?}", &currencies);
             for currency in currencies {
-                conn_queries.push(Box::new(HubQuery::new(BITTREX_HUB.to_string(), "SubscribeToExchangeDeltas".to_string(), vec![currency], "1".to_string())));
+                conn_queries.push(Box::new(HubQuery::new(BITTREX_HUB.to_string(), "SubscribeToExchangeDeltas".to_string(), vec![currency.clone()], format!("SD_{}", currency))));
             }
         }
+        self.tape.borrow_mut().mark_gap();
+        // On every (re)connection, levels from a previous connection are stale : the books are cleared
+        // and ignore deltas until the exchange state queried after the subscriptions is received
+        if !self.order_book_pairs.is_empty() {
+            let mut books = self.books.borrow_mut();
+            for &pair in &self.order_book_pairs {
+                books.entry(pair)
+                    .or_insert_with(|| LiveAggregatedOrderBook::with_warm_up(pair, self.warm_up).with_depth(self.depth))
+                    .resync();
+                let currency = *super::utils::get_pair_string(&pair).unwrap();
+                conn_queries.push(Box::new(HubQuery::new(BITTREX_HUB.to_string(), "QueryExchangeState".to_string(), vec![currency.to_string()], format!("QE_{}", currency))));
+            }
+        }
+        if !self.summary_pairs.is_empty() {
+            info!("Bittrex : connecting to SummaryDeltas");
+            conn_queries.push(Box::new(HubQuery::new(BITTREX_HUB.to_string(), "SubscribeToSummaryDeltas".to_string(), Vec::<String>::new(), "2".to_string())));
+        }
         conn_queries
     }
 
-    fn error(&self, _: Option<&str>, _: &Value) {}
+    fn error(&self, id: Option<&str>, error: &Value) {
+        warn!("Bittrex : query {:?} failed : {}", id, error);
+        if let Some(id) = id {
+            for le in self.subscription_results(id, Some(&error.to_string())) {
+                self.send(le);
+            }
+        }
+    }
 
     fn handle(&mut self, method: &str, message: &Value) {
+        self.notify_raw(method, message);
         let live_events = match method {
             "uE" => {
                 let delta = BittrexStreamingApi::deflate_array::<MarketDelta>(message).unwrap();
@@ -131,7 +265,7 @@ impl HubClientHandler for BittrexStreamingApi {
                 let current_pair = *pair.unwrap();
                 if self.order_book_pairs.contains(&current_pair) {
                     let mut books = self.books.borrow_mut();
-                    let default_book = LiveAggregatedOrderBook::default(current_pair);
+                    let default_book = LiveAggregatedOrderBook::with_warm_up(current_pair, self.warm_up).with_depth(self.depth);
                     let mut agg = books.entry(current_pair).or_insert(default_book);
                     let asks = delta.Sells.into_iter().map(|op| (BigDecimal::from(op.Rate), BigDecimal::from(op.Quantity)));
                     agg.update_asks(asks);
@@ -140,16 +274,20 @@ impl HubClientHandler for BittrexStreamingApi {
                     agg.latest_order_book().map(|ob| events.push(LiveEvent::LiveOrderbook(ob)));
                 }
                 if self.trade_pairs.contains(&current_pair) {
+                    let mut trades = vec![];
                     for fill in delta.Fills {
                         let lt = LiveTrade {
                             event_ms: fill.TimeStamp as i64,
-                            pair: format!("{:?}", current_pair),
-                            amount: fill.Quantity,
+                            pair: current_pair,
+                            amount: BigDecimal::from(fill.Quantity),
                             price: BigDecimal::from(fill.Rate),
-                            tt: fill.OrderType.into(),
+                            tt: super::utils::get_trade_type(&fill.OrderType),
+                            meta: TradeMeta::with_id(fill.FillId),
                         };
-                        events.push(LiveEvent::LiveTrade(lt));
+                        trades.push(lt);
                     }
+                    let trades = self.tape.borrow_mut().accept_all(trades);
+                    events.extend(trades.into_iter().map(LiveEvent::LiveTrade));
                 }
                 if events.is_empty() {
                     Err(())
@@ -158,23 +296,45 @@ impl HubClientHandler for BittrexStreamingApi {
                 }
             }
             "uS" => {
-                BittrexStreamingApi::deflate_array::<SummaryDeltaResponse>(message);
-                Err(())
+                let response = BittrexStreamingApi::deflate_array::<SummaryDeltaResponse>(message).unwrap();
+                let events: Vec<LiveEvent> = response.Deltas.into_iter()
+                    .filter(|delta| super::utils::get_pair_enum(delta.MarketName.as_str()).map_or(false, |p| self.summary_pairs.contains(p)))
+                    .map(|delta| LiveEvent::LiveSummary(delta.into()))
+                    .collect();
+                if events.is_empty() {
+                    Err(())
+                } else {
+                    Ok(events)
+                }
             }
-            s if s.starts_with("QE") => {
-                let state = BittrexStreamingApi::deflate_string::<ExchangeState>(message).unwrap();
-                let pair = super::utils::get_pair_enum(state.MarketName.as_str());
-                if pair.is_none() {
+            // Unknown markets, e.g. delisted ones, are refused
+            s if s.starts_with("SD_") => {
+                let refused = message.as_bool() == Some(false);
+                Ok(self.subscription_results(s, if refused { Some("market not found") } else { None }))
+            }
+            s if s.starts_with("QE_") => {
+                let state = match BittrexStreamingApi::deflate_string::<ExchangeState>(message) {
+                    Ok(state) => state,
+                    Err(_) => {
+                        for le in self.subscription_results(s, Some("no state for the market")) {
+                            self.send(le);
+                        }
+                        return;
+                    }
+                };
+                // Every level is kept, the book is limited to its own depth
+                let snapshot = match BittrexStreamingApi::state_snapshot(state, usize::MAX) {
+                    Ok(snapshot) => snapshot,
+                    Err(_) => return,
+                };
+                let mut books = self.books.borrow_mut();
+                let default_book = LiveAggregatedOrderBook::with_warm_up(snapshot.pair, self.warm_up).with_depth(self.depth);
+                let agg = books.entry(snapshot.pair).or_insert(default_book);
+                agg.reset_asks(snapshot.asks.into_iter());
+                agg.reset_bids(snapshot.bids.into_iter());
+                if !agg.is_warm() {
                     return;
                 }
-                let mut books = self.books.borrow_mut();
-                let current_pair = *pair.unwrap();
-                let default_book = LiveAggregatedOrderBook::default(current_pair);
-                let mut agg = books.entry(current_pair).or_insert(default_book);
-                let asks = state.Sells.into_iter().map(|op| (BigDecimal::from(op.R), BigDecimal::from(op.Q)));
-                agg.reset_asks(asks);
-                let bids = state.Buys.into_iter().map(|op| (BigDecimal::from(op.R), BigDecimal::from(op.Q)));
-                agg.reset_bids(bids);
                 let latest_order_book: Orderbook = agg.order_book();
                 Ok(vec![LiveEvent::LiveOrderbook(latest_order_book.clone())])
             }
@@ -184,14 +344,31 @@ impl HubClientHandler for BittrexStreamingApi {
             }
         };
         if let Ok(les) = live_events {
-            let recipients = self.recipients.clone();
             for le in les {
-                for r in &recipients {
-                    let le: LiveEvent = le.clone();
-                    r.do_send(LiveEventEnveloppe(Exchange::Bittrex, le)).unwrap();
-                }
+                self.send(le);
             }
 
         }
     }
 }
+
+#[cfg(test)]
+mod bittrex_streaming_tests {
+    use super::*;
+    use libflate::deflate::Encoder;
+    use std::io::Write;
+
+    #[test]
+    fn exchange_states_are_limited_to_the_best_levels() {
+        let state = serde_json::json!({"M": "BTC-ETH", "N": 1, "f": [],
+            "Z": [{"Q": 1.0, "R": 0.25}, {"Q": 2.0, "R": 0.5}, {"Q": 3.0, "R": 0.125}],
+            "S": [{"Q": 1.0, "R": 1.5}, {"Q": 2.0, "R": 0.75}, {"Q": 3.0, "R": 1.0}]});
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(state.to_string().as_bytes()).unwrap();
+        let binary = base64::encode(&encoder.finish().into_result().unwrap());
+        let ob = BittrexStreamingApi::exchange_state_snapshot(&binary, 2).unwrap();
+        assert_eq!(ob.pair, Pair::ETH_BTC);
+        assert_eq!(ob.asks, vec![(BigDecimal::from(0.75), BigDecimal::from(2.0)), (BigDecimal::from(1.0), BigDecimal::from(3.0))]);
+        assert_eq!(ob.bids, vec![(BigDecimal::from(0.5), BigDecimal::from(2.0)), (BigDecimal::from(0.25), BigDecimal::from(1.0))]);
+    }
+}
@@ -0,0 +1,115 @@
+//! Order book deltas instead of full snapshots.
+//! `DeltaEncoder` turns the live order books of a bot into `LiveOrderbookDelta` events carrying
+//! only the levels that changed since the previous book of the pair, numbered by a sequence
+//! starting at 1. The full book is still available on demand with the `QueryBook` message, e.g.
+//! after a gap in the sequence.
+
+use std::collections::HashMap;
+
+use actix::{Actor, Context, Handler, MessageResult, Recipient};
+
+use crate::exchange::Exchange;
+use crate::types::*;
+
+/// Latest full order book of a pair, `None` until a book of the pair was received
+#[derive(Message)]
+#[rtype(result = "Option<Orderbook>")]
+pub struct QueryBook {
+    pub exchange: Exchange,
+    pub pair: Pair,
+}
+
+/// Actor sending order book deltas to its recipients in place of full order books.
+/// Every other event is forwarded untouched.
+pub struct DeltaEncoder {
+    recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    /// Last order book of each pair, with the sequence of its delta
+    books: HashMap<(Exchange, Pair), (Orderbook, u64)>,
+}
+
+impl DeltaEncoder {
+    pub fn new(recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Self {
+        DeltaEncoder { recipients, books: HashMap::new() }
+    }
+
+    /// The delta from the last book of the pair to `ob`, `None` if no level changed.
+    /// The first book of a pair gives all its levels.
+    fn encode(&mut self, exchange: Exchange, ob: Orderbook) -> Option<OrderbookDelta> {
+        let (changes, sequence) = match self.books.get(&(exchange, ob.pair)) {
+            Some((previous, sequence)) => (previous.changes_to(&ob), sequence + 1),
+            None => {
+                let empty = Orderbook { timestamp: ob.timestamp, pair: ob.pair, asks: vec![], bids: vec![] };
+                (empty.changes_to(&ob), 1)
+            }
+        };
+        if changes.is_empty() && sequence > 1 {
+            return None;
+        }
+        let delta = OrderbookDelta { timestamp: ob.timestamp, pair: ob.pair, sequence, changes };
+        self.books.insert((exchange, ob.pair), (ob, sequence));
+        Some(delta)
+    }
+
+    fn send(&self, le: LiveEventEnveloppe) {
+        for r in &self.recipients {
+            if let Err(e) = r.do_send(le.clone()) {
+                trace!("Deltas : unable to send to recipient : {}", e);
+            }
+        }
+    }
+}
+
+impl Actor for DeltaEncoder {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for DeltaEncoder {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        match msg.1 {
+            LiveEvent::LiveOrderbook(ob) => {
+                if let Some(delta) = self.encode(msg.0, ob) {
+                    self.send(LiveEventEnveloppe(msg.0, LiveEvent::LiveOrderbookDelta(delta)));
+                }
+            }
+            _ => self.send(msg),
+        }
+    }
+}
+
+impl Handler<QueryBook> for DeltaEncoder {
+    type Result = MessageResult<QueryBook>;
+
+    fn handle(&mut self, msg: QueryBook, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.books.get(&(msg.exchange, msg.pair)).map(|(ob, _)| ob.clone()))
+    }
+}
+
+#[cfg(test)]
+mod book_deltas_tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+
+    fn book(timestamp: i64, ask: i32) -> Orderbook {
+        Orderbook {
+            timestamp,
+            pair: Pair::BTC_USD,
+            asks: vec![(BigDecimal::from(ask), BigDecimal::from(1))],
+            bids: vec![(BigDecimal::from(99), BigDecimal::from(1))],
+        }
+    }
+
+    #[test]
+    fn books_are_encoded_as_sequenced_deltas() {
+        let mut encoder = DeltaEncoder::new(vec![]);
+        let first = encoder.encode(Exchange::Bitstamp, book(1, 101)).unwrap();
+        assert_eq!((first.sequence, first.changes.len()), (1, 2));
+        assert!(encoder.encode(Exchange::Bitstamp, book(2, 101)).is_none());
+        let second = encoder.encode(Exchange::Bitstamp, book(3, 102)).unwrap();
+        assert_eq!((second.sequence, second.changes.len()), (2, 2));
+        let mut rebuilt = book(0, 101);
+        rebuilt.apply(&second);
+        assert_eq!(rebuilt.asks, book(3, 102).asks);
+    }
+}
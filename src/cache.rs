@@ -0,0 +1,341 @@
+//! Cache of the public REST responses of an `ExchangeApi`, for dashboards polling many pairs.
+//! Tickers, order books and markets are kept for the TTL of their endpoint, private endpoints
+//! always reach the exchange. `tickers` only fetches the pairs missing from the cache, in one
+//! batch.
+//!
+//! ```ignore
+//! let api = CachedApi::new(Coinnect::new(Exchange::Kraken, creds)?, CacheSettings::default());
+//! let ticker = api.ticker(Pair::BTC_EUR).await?; // reaches Kraken
+//! let ticker = api.ticker(Pair::BTC_EUR).await?; // served from the cache
+//! ```
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::error::*;
+use crate::exchange::{ExchangeApi, ExchangeCapabilities};
+use crate::types::*;
+
+/// TTL of each cached endpoint in ms, zero disables the cache of the endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct CacheSettings {
+    #[serde(default = "default_ticker_ttl")]
+    pub ticker_ttl_ms: u64,
+    #[serde(default = "default_orderbook_ttl")]
+    pub orderbook_ttl_ms: u64,
+    #[serde(default = "default_markets_ttl")]
+    pub markets_ttl_ms: u64,
+}
+
+fn default_ticker_ttl() -> u64 {
+    1000
+}
+
+fn default_orderbook_ttl() -> u64 {
+    500
+}
+
+fn default_markets_ttl() -> u64 {
+    3_600_000
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        CacheSettings { ticker_ttl_ms: default_ticker_ttl(), orderbook_ttl_ms: default_orderbook_ttl(), markets_ttl_ms: default_markets_ttl() }
+    }
+}
+
+/// Responses by key, with the time they were received
+#[derive(Debug)]
+struct Entries<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> Entries<K, V> {
+    fn new(ttl_ms: u64) -> Self {
+        Entries { ttl: Duration::from_millis(ttl_ms), entries: Mutex::new(HashMap::new()) }
+    }
+
+    fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key).filter(|(at, _)| at.elapsed() < self.ttl).map(|(_, v)| v.clone())
+    }
+
+    fn insert(&self, key: K, value: V) {
+        if self.ttl > Duration::from_millis(0) {
+            self.entries.lock().unwrap().insert(key, (Instant::now(), value));
+        }
+    }
+
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// An `ExchangeApi` answering the public endpoints from its cache while their responses are
+/// fresh, see `CacheSettings`
+#[derive(Debug)]
+pub struct CachedApi {
+    inner: Box<dyn ExchangeApi>,
+    tickers: Entries<Pair, Ticker>,
+    books: Entries<Pair, Orderbook>,
+    markets: Entries<(), Vec<Pair>>,
+    bypass: AtomicBool,
+}
+
+impl CachedApi {
+    pub fn new(inner: Box<dyn ExchangeApi>, settings: CacheSettings) -> Self {
+        CachedApi {
+            inner,
+            tickers: Entries::new(settings.ticker_ttl_ms),
+            books: Entries::new(settings.orderbook_ttl_ms),
+            markets: Entries::new(settings.markets_ttl_ms),
+            bypass: AtomicBool::new(false),
+        }
+    }
+
+    /// While set, every request reaches the exchange, its response still refreshes the cache
+    pub fn set_bypass(&self, bypass: bool) {
+        self.bypass.store(bypass, Ordering::SeqCst);
+    }
+
+    fn bypassed(&self) -> bool {
+        self.bypass.load(Ordering::SeqCst)
+    }
+
+    /// Drop every cached response
+    pub fn invalidate(&self) {
+        self.tickers.clear();
+        self.books.clear();
+        self.markets.clear();
+    }
+}
+
+#[async_trait]
+impl ExchangeApi for CachedApi {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
+        if let Some(ticker) = self.tickers.get(&pair).filter(|_| !self.bypassed()) {
+            return Ok(ticker);
+        }
+        let ticker = self.inner.ticker(pair).await?;
+        self.tickers.insert(pair, ticker.clone());
+        Ok(ticker)
+    }
+
+    async fn tickers(&self, pairs: &[Pair]) -> Result<HashMap<Pair, Ticker>> {
+        let mut tickers = HashMap::new();
+        let mut missing = vec![];
+        for &pair in pairs {
+            match self.tickers.get(&pair).filter(|_| !self.bypassed()) {
+                Some(ticker) => {
+                    tickers.insert(pair, ticker);
+                }
+                None => missing.push(pair),
+            }
+        }
+        if !missing.is_empty() {
+            for (pair, ticker) in self.inner.tickers(&missing).await? {
+                self.tickers.insert(pair, ticker.clone());
+                tickers.insert(pair, ticker);
+            }
+        }
+        Ok(tickers)
+    }
+
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
+        if let Some(book) = self.books.get(&pair).filter(|_| !self.bypassed()) {
+            return Ok(book);
+        }
+        let book = self.inner.orderbook(pair).await?;
+        self.books.insert(pair, book.clone());
+        Ok(book)
+    }
+
+    async fn add_order(&self,
+                 order_type: OrderType,
+                 pair: Pair,
+                 quantity: Volume,
+                 price: Option<Price>)
+                 -> Result<OrderInfo> {
+        self.inner.add_order(order_type, pair, quantity, price).await
+    }
+
+    async fn add_conditional_order(&self,
+                             order_type: OrderType,
+                             pair: Pair,
+                             quantity: Volume,
+                             trigger_price: Price,
+                             price: Option<Price>)
+                             -> Result<OrderInfo> {
+        self.inner.add_conditional_order(order_type, pair, quantity, trigger_price, price).await
+    }
+
+    async fn add_ioc_order(&self,
+                           order_type: OrderType,
+                           pair: Pair,
+                           quantity: Volume,
+                           price: Price)
+                           -> Result<Execution> {
+        self.inner.add_ioc_order(order_type, pair, quantity, price).await
+    }
+
+    async fn add_order_with_id(&self,
+                               order_type: OrderType,
+                               pair: Pair,
+                               quantity: Volume,
+                               price: Option<Price>,
+                               client_id: &str)
+                               -> Result<OrderInfo> {
+        self.inner.add_order_with_id(order_type, pair, quantity, price, client_id).await
+    }
+
+    async fn order_by_client_id(&self, pair: Pair, client_id: &str) -> Result<Option<OrderInfo>> {
+        self.inner.order_by_client_id(pair, client_id).await
+    }
+
+    async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+        self.inner.cancel_order(order).await
+    }
+
+    async fn cancel_all_orders(&self) -> Result<()> {
+        self.inner.cancel_all_orders().await
+    }
+
+    async fn set_cancel_after(&self, timeout: Duration) -> Result<()> {
+        self.inner.set_cancel_after(timeout).await
+    }
+
+    async fn amend_order(&self, order: &OrderInfo, new_price: Option<Price>, new_amount: Option<Volume>) -> Result<OrderInfo> {
+        self.inner.amend_order(order, new_price, new_amount).await
+    }
+
+    async fn balances(&self) -> Result<Balances> {
+        self.inner.balances().await
+    }
+
+    async fn verify_credentials(&self) -> Result<()> {
+        self.inner.verify_credentials().await
+    }
+
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        self.inner.detailed_balances().await
+    }
+
+    async fn fees(&self, pair: Pair) -> Result<FeeRates> {
+        self.inner.fees(pair).await
+    }
+
+    async fn withdraw(&self, currency: Currency, amount: Amount, destination: &str) -> Result<WithdrawalInfo> {
+        self.inner.withdraw(currency, amount, destination).await
+    }
+
+    async fn fills(&self, pair: Pair) -> Result<Vec<FillEvent>> {
+        self.inner.fills(pair).await
+    }
+
+    async fn fills_page(&self, pair: Pair, cursor: Option<String>) -> Result<FillsPage> {
+        self.inner.fills_page(pair, cursor).await
+    }
+
+    async fn public_trades(&self, pair: Pair, since: Option<DateTime<Utc>>) -> Result<Vec<PublicTrade>> {
+        self.inner.public_trades(pair, since).await
+    }
+
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        if let Some(markets) = self.markets.get(&()).filter(|_| !self.bypassed()) {
+            return Ok(markets);
+        }
+        let markets = self.inner.markets().await?;
+        self.markets.insert((), markets.clone());
+        Ok(markets)
+    }
+
+    async fn time(&self) -> Result<i64> {
+        self.inner.time().await
+    }
+
+    async fn status(&self) -> Result<ExchangeStatus> {
+        self.inner.status().await
+    }
+
+    fn clock_skew(&self) -> Option<i64> {
+        self.inner.clock_skew()
+    }
+
+    fn limits(&self) -> RateLimitStatus {
+        self.inner.limits()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        self.inner.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use bigdecimal::BigDecimal;
+
+    #[derive(Debug, Default)]
+    struct Counting(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl ExchangeApi for Counting {
+        async fn ticker(&self, pair: Pair) -> Result<Ticker> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(Ticker {
+                timestamp: 0,
+                pair,
+                last_trade_price: BigDecimal::from(100),
+                lowest_ask: BigDecimal::from(101),
+                highest_bid: BigDecimal::from(99),
+                volume: None,
+                bid_size: None,
+                ask_size: None,
+                open_24h: None,
+                high_24h: None,
+                low_24h: None,
+                percent_change: None,
+                raw: None,
+            })
+        }
+
+        async fn orderbook(&self, _pair: Pair) -> Result<Orderbook> {
+            Err(Error::ServiceUnavailable("no book".to_string()))
+        }
+
+        async fn add_order(&self, _order_type: OrderType, _pair: Pair, _quantity: Volume, _price: Option<Price>) -> Result<OrderInfo> {
+            Err(Error::PermissionDenied)
+        }
+
+        async fn balances(&self) -> Result<Balances> {
+            Ok(Balances::new())
+        }
+    }
+
+    #[test]
+    fn fresh_tickers_are_served_from_the_cache() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let api = CachedApi::new(Box::new(Counting(calls.clone())), CacheSettings::default());
+        rt.block_on(api.ticker(Pair::BTC_USD)).unwrap();
+        rt.block_on(api.ticker(Pair::BTC_USD)).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        // Only the missing pair is fetched
+        rt.block_on(api.tickers(&[Pair::BTC_USD, Pair::ETH_USD])).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        api.set_bypass(true);
+        rt.block_on(api.ticker(Pair::BTC_USD)).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(rt.block_on(api.orderbook(Pair::BTC_USD)).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Local aggregation of live trades into candles, for exchanges without a native OHLC channel.
+//! Candles are aligned on multiples of the interval since the UNIX epoch and are sent once
+//! closed, i.e. when the first trade of a later candle is received, or `CLOSE_DELAY_MS` after the
+//! end of their interval when the market is quiet. Intervals without any trade give no candle.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix::{Actor, AsyncContext, Context, Handler, Recipient};
+
+use crate::exchange::Exchange;
+use crate::helpers;
+use crate::types::*;
+
+/// Delay after the end of an interval before its candle is closed without a later trade, so that
+/// trades received late still count
+pub const CLOSE_DELAY_MS: i64 = 1000;
+
+/// Start of the candle of `interval_secs` containing `event_ms`
+pub fn candle_start(event_ms: i64, interval_secs: u64) -> i64 {
+    let interval_ms = (interval_secs * 1000) as i64;
+    event_ms - event_ms.rem_euclid(interval_ms)
+}
+
+/// Builds candles from the trades of every pair
+#[derive(Debug, Default)]
+pub struct CandleBuilder {
+    interval_secs: u64,
+    open: HashMap<Pair, LiveCandle>,
+    /// End of the last closed candle of each pair, earlier trades are ignored
+    closed_until: HashMap<Pair, i64>,
+}
+
+impl CandleBuilder {
+    pub fn new(interval_secs: u64) -> Self {
+        CandleBuilder { interval_secs, open: HashMap::new(), closed_until: HashMap::new() }
+    }
+
+    fn interval_ms(&self) -> i64 {
+        (self.interval_secs * 1000) as i64
+    }
+
+    /// Add a trade to the candle of its pair, returns the previous candle if this trade closes it.
+    /// Late trades belonging to an already closed candle are ignored.
+    pub fn add_trade(&mut self, trade: &LiveTrade) -> Option<LiveCandle> {
+        let interval_secs = self.interval_secs;
+        let start = candle_start(trade.event_ms, interval_secs);
+        if self.closed_until.get(&trade.pair).map_or(false, |&end| start < end) {
+            return None;
+        }
+        let volume = trade.amount.clone();
+        let new_candle = || LiveCandle {
+            event_ms: start,
+            pair: format!("{:?}", trade.pair),
+            interval_secs,
+            open: trade.price.clone(),
+            high: trade.price.clone(),
+            low: trade.price.clone(),
+            close: trade.price.clone(),
+            volume: volume.clone(),
+            closed: false,
+        };
+        match self.open.get_mut(&trade.pair) {
+            Some(candle) if candle.event_ms == start => {
+                if trade.price > candle.high {
+                    candle.high = trade.price.clone();
+                }
+                if trade.price < candle.low {
+                    candle.low = trade.price.clone();
+                }
+                candle.close = trade.price.clone();
+                candle.volume = candle.volume.clone() + volume.clone();
+                None
+            }
+            Some(candle) if candle.event_ms > start => None,
+            _ => {
+                let previous = self.open.insert(trade.pair, new_candle());
+                previous.map(|c| self.close(trade.pair, c))
+            }
+        }
+    }
+
+    /// Close the candles whose interval ended before `now_ms`, minus `CLOSE_DELAY_MS`
+    pub fn close_expired(&mut self, now_ms: i64) -> Vec<(Pair, LiveCandle)> {
+        let interval_ms = self.interval_ms();
+        let expired: Vec<Pair> = self.open.iter()
+            .filter(|(_, c)| c.event_ms + interval_ms + CLOSE_DELAY_MS <= now_ms)
+            .map(|(pair, _)| *pair)
+            .collect();
+        expired.into_iter()
+            .filter_map(|pair| self.open.remove(&pair).map(|c| (pair, self.close(pair, c))))
+            .collect()
+    }
+
+    fn close(&mut self, pair: Pair, mut candle: LiveCandle) -> LiveCandle {
+        self.closed_until.insert(pair, candle.event_ms + self.interval_ms());
+        candle.closed = true;
+        candle
+    }
+}
+
+/// Actor turning the live trades of a bot into closed candles for its recipients.
+/// Every other event is forwarded untouched, trades are only forwarded if they were asked for.
+pub struct CandleAggregator {
+    builder: CandleBuilder,
+    forward_trades: bool,
+    recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    /// Exchange of the last trade of each pair, to send the candles closed on expiry
+    exchanges: HashMap<Pair, Exchange>,
+}
+
+/// Name of `CandleAggregator` for users looking for OHLC candles
+pub type OhlcAggregator = CandleAggregator;
+
+impl CandleAggregator {
+    pub fn new(interval_secs: u64, forward_trades: bool, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Self {
+        CandleAggregator { builder: CandleBuilder::new(interval_secs), forward_trades, recipients, exchanges: HashMap::new() }
+    }
+
+    fn close_expired(&mut self) {
+        for (pair, candle) in self.builder.close_expired(helpers::get_unix_timestamp_ms()) {
+            if let Some(exchange) = self.exchanges.get(&pair).copied() {
+                self.send(LiveEventEnveloppe(exchange, LiveEvent::LiveCandle(candle)));
+            }
+        }
+    }
+
+    fn send(&self, le: LiveEventEnveloppe) {
+        for r in &self.recipients {
+            if let Err(e) = r.do_send(le.clone()) {
+                trace!("Candles : unable to send to recipient : {}", e);
+            }
+        }
+    }
+}
+
+impl Actor for CandleAggregator {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(Duration::from_secs(1), |act, _ctx| act.close_expired());
+    }
+}
+
+impl Handler<LiveEventEnveloppe> for CandleAggregator {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        if let LiveEvent::LiveTrade(trade) = &msg.1 {
+            self.exchanges.insert(trade.pair, msg.0);
+            if let Some(candle) = self.builder.add_trade(trade) {
+                self.send(LiveEventEnveloppe(msg.0, LiveEvent::LiveCandle(candle)));
+            }
+            if !self.forward_trades {
+                return;
+            }
+        }
+        self.send(msg);
+    }
+}
+
+#[cfg(test)]
+mod candles_tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+
+    fn trade(event_ms: i64, price: i32) -> LiveTrade {
+        LiveTrade { event_ms, pair: Pair::BTC_USD, amount: BigDecimal::from(1), price: BigDecimal::from(price), tt: TradeType::Buy, meta: TradeMeta::default() }
+    }
+
+    #[test]
+    fn trades_are_aggregated_per_interval() {
+        let mut builder = CandleBuilder::new(60);
+        assert!(builder.add_trade(&trade(60_000, 10)).is_none());
+        assert!(builder.add_trade(&trade(70_000, 12)).is_none());
+        assert!(builder.add_trade(&trade(80_000, 9)).is_none());
+        let candle = builder.add_trade(&trade(125_000, 11)).unwrap();
+        assert_eq!(candle.event_ms, 60_000);
+        assert_eq!(candle.open, BigDecimal::from(10));
+        assert_eq!(candle.high, BigDecimal::from(12));
+        assert_eq!(candle.low, BigDecimal::from(9));
+        assert_eq!(candle.close, BigDecimal::from(9));
+        assert_eq!(candle.volume, BigDecimal::from(3));
+        assert!(candle.closed);
+    }
+
+    #[test]
+    fn quiet_candles_close_on_expiry() {
+        let mut builder = CandleBuilder::new(60);
+        assert!(builder.add_trade(&trade(60_000, 10)).is_none());
+        assert!(builder.close_expired(120_000).is_empty());
+        let closed = builder.close_expired(120_000 + CLOSE_DELAY_MS);
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].0, Pair::BTC_USD);
+        assert!(closed[0].1.closed);
+        // A late trade of the closed candle does not reopen it
+        assert!(builder.add_trade(&trade(110_000, 12)).is_none());
+        assert!(builder.close_expired(200_000).is_empty());
+    }
+}
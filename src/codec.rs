@@ -0,0 +1,219 @@
+//! Text encodings of live events, used to record and replay market data.
+//! JSON Lines keeps the full `LiveEventEnveloppe`, CSV uses one row per event with the columns
+//! `exchange,event,timestamp,pair,price,amount,side,asks,bids` where order book sides are
+//! encoded as `price:volume` levels separated by `|`.
+
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::types::*;
+
+pub const CSV_HEADER: &str = "exchange,event,timestamp,pair,price,amount,side,asks,bids";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum EventFormat {
+    JsonLines,
+    Csv,
+}
+
+impl EventFormat {
+    /// Encode an event as a single line, without the line terminator.
+    /// `Noop` and subscription results have no representation and return `None`, as do candles,
+    /// summaries, fills, book deltas and detail books in CSV.
+    pub fn encode(&self, e: &LiveEventEnveloppe) -> Result<Option<String>> {
+        match (self, &e.1) {
+            (_, LiveEvent::Noop) | (_, LiveEvent::SubscriptionResult(_)) | (EventFormat::Csv, LiveEvent::LiveCandle(_)) | (EventFormat::Csv, LiveEvent::LiveSummary(_)) | (EventFormat::Csv, LiveEvent::Fill(_)) | (EventFormat::Csv, LiveEvent::LiveOrderbookDelta(_)) | (EventFormat::Csv, LiveEvent::LiveDetailOrderbook(_)) => Ok(None),
+            (EventFormat::JsonLines, _) => Ok(Some(serde_json::to_string(e)?)),
+            (EventFormat::Csv, _) => Ok(Some(encode_csv(e))),
+        }
+    }
+
+    /// Decode a line produced by `encode`
+    pub fn decode(&self, line: &str) -> Result<LiveEventEnveloppe> {
+        match self {
+            EventFormat::JsonLines => Ok(serde_json::from_str(line)?),
+            EventFormat::Csv => decode_csv(line),
+        }
+    }
+
+    /// Header line written at the top of new files, if any
+    pub fn header(&self) -> Option<&'static str> {
+        match self {
+            EventFormat::JsonLines => None,
+            EventFormat::Csv => Some(CSV_HEADER),
+        }
+    }
+}
+
+/// Binary or text encodings of live events sent to clients, negotiated per client since JSON
+/// encoding of full-depth books is expensive for high-throughput consumers
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum WireFormat {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Default for WireFormat {
+    fn default() -> Self {
+        WireFormat::Json
+    }
+}
+
+impl WireFormat {
+    pub fn encode(&self, e: &LiveEventEnveloppe) -> Result<Vec<u8>> {
+        match self {
+            WireFormat::Json => Ok(serde_json::to_vec(e)?),
+            WireFormat::MessagePack => Ok(rmp_serde::to_vec_named(e)?),
+            WireFormat::Cbor => Ok(serde_cbor::to_vec(e)?),
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<LiveEventEnveloppe> {
+        match self {
+            WireFormat::Json => Ok(serde_json::from_slice(bytes)?),
+            WireFormat::MessagePack => Ok(rmp_serde::from_read_ref(bytes)?),
+            WireFormat::Cbor => Ok(serde_cbor::from_slice(bytes)?),
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            WireFormat::Json => "application/json",
+            WireFormat::MessagePack => "application/msgpack",
+            WireFormat::Cbor => "application/cbor",
+        }
+    }
+
+    /// Pick the first supported format of a client preference list, e.g. an `Accept` header or a
+    /// websocket subprotocol list. Mime types and the short names `json`, `msgpack` and `cbor`
+    /// are accepted, quality values are ignored.
+    pub fn negotiate(preferences: &str) -> Option<WireFormat> {
+        preferences.split(',').filter_map(|p| {
+            match p.split(';').next().unwrap_or_default().trim().to_lowercase().as_str() {
+                "json" | "application/json" | "*/*" => Some(WireFormat::Json),
+                "msgpack" | "application/msgpack" | "application/x-msgpack" => Some(WireFormat::MessagePack),
+                "cbor" | "application/cbor" => Some(WireFormat::Cbor),
+                _ => None,
+            }
+        }).next()
+    }
+}
+
+fn encode_levels(levels: &[(Price, Volume)]) -> String {
+    levels.iter().map(|(p, v)| format!("{}:{}", p, v)).collect::<Vec<String>>().join("|")
+}
+
+fn decode_levels(s: &str) -> Result<Vec<(Price, Volume)>> {
+    if s.is_empty() {
+        return Ok(vec![]);
+    }
+    s.split('|').map(|level| {
+        let mut parts = level.splitn(2, ':');
+        let price = BigDecimal::from_str(parts.next().unwrap_or_default())?;
+        let volume = BigDecimal::from_str(parts.next().ok_or_else(|| Error::InvalidFieldFormat("level".to_string()))?)?;
+        Ok((price, volume))
+    }).collect()
+}
+
+fn encode_csv(e: &LiveEventEnveloppe) -> String {
+    let exchange: String = e.0.into();
+    match &e.1 {
+        LiveEvent::LiveTrade(t) => format!("{},trade,{},{:?},{},{},{},,", exchange, t.event_ms, t.pair, t.price, t.amount, t.tt),
+        LiveEvent::LiveOrder(o) => format!("{},order,{},{:?},{},{},{},{:?},{}", exchange, o.event_ms, o.pair, o.price, o.amount, o.tt, o.kind, o.id.as_deref().unwrap_or_default()),
+        LiveEvent::LiveOrderbook(ob) => format!("{},orderbook,{},{:?},,,,{},{}", exchange, ob.timestamp, ob.pair, encode_levels(&ob.asks), encode_levels(&ob.bids)),
+        LiveEvent::LiveOrderbookDelta(_) | LiveEvent::LiveDetailOrderbook(_) | LiveEvent::LiveCandle(_) | LiveEvent::LiveSummary(_) | LiveEvent::Fill(_) | LiveEvent::SubscriptionResult(_) | LiveEvent::Noop => format!("{},noop,,,,,,,", exchange),
+    }
+}
+
+fn decode_csv(line: &str) -> Result<LiveEventEnveloppe> {
+    let cols: Vec<&str> = line.trim_end().split(',').collect();
+    if cols.len() != 9 {
+        return Err(Error::BadParse);
+    }
+    let exchange = Exchange::from_str(cols[0])?;
+    let timestamp = cols[2].parse::<i64>().with_context(|| Error::InvalidFieldFormat("timestamp".to_string()))?;
+    let event = match cols[1] {
+        "trade" | "order" => {
+            let price = BigDecimal::from_str(cols[4])?;
+            // Older recordings name the pair as the exchange does
+            let pair = crate::utils::live_pair(exchange, cols[3]).ok_or(Error::PairUnsupported)?;
+            let amount = BigDecimal::from_str(cols[5])?;
+            let tt = TradeType::parse(cols[6]);
+            if cols[1] == "trade" {
+                LiveEvent::LiveTrade(LiveTrade { event_ms: timestamp, pair, amount, price, tt, meta: TradeMeta::default() })
+            } else {
+                // Kind and id of the order, missing in older recordings
+                let kind = OrderEventKind::deserialize(Value::String(cols[7].to_string())).unwrap_or_default();
+                let id = Some(cols[8].to_string()).filter(|id| !id.is_empty());
+                LiveEvent::LiveOrder(LiveOrder { event_ms: timestamp, id, kind, pair, amount, price, tt })
+            }
+        }
+        "orderbook" => {
+            let pair = Pair::deserialize(Value::String(cols[3].to_string()))?;
+            LiveEvent::LiveOrderbook(Orderbook { timestamp, pair, asks: decode_levels(cols[7])?, bids: decode_levels(cols[8])? })
+        }
+        other => return Err(Error::InvalidFieldValue(other.to_string())),
+    };
+    Ok(LiveEventEnveloppe(exchange, event))
+}
+
+#[cfg(test)]
+mod codec_tests {
+    use super::*;
+
+    fn book() -> LiveEventEnveloppe {
+        LiveEventEnveloppe(Exchange::Bitstamp, LiveEvent::LiveOrderbook(Orderbook {
+            timestamp: 1577146143220,
+            pair: Pair::BTC_USD,
+            asks: vec![(BigDecimal::from_str("7312.91").unwrap(), BigDecimal::from_str("0.5").unwrap())],
+            bids: vec![(BigDecimal::from_str("7310.2").unwrap(), BigDecimal::from(2))],
+        }))
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let line = EventFormat::Csv.encode(&book()).unwrap().unwrap();
+        assert_eq!(line, "Bitstamp,orderbook,1577146143220,BTC_USD,,,,7312.91:0.5,7310.2:2");
+        match EventFormat::Csv.decode(&line).unwrap() {
+            LiveEventEnveloppe(Exchange::Bitstamp, LiveEvent::LiveOrderbook(ob)) => assert_eq!(ob.asks, vec![(BigDecimal::from_str("7312.91").unwrap(), BigDecimal::from_str("0.5").unwrap())]),
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+
+    #[test]
+    fn csv_trades_keep_precise_amounts() {
+        let trade = "Bitstamp,trade,1577146143220,BTC_USD,7312.91,0.00000001,Buy,,";
+        match EventFormat::Csv.decode(trade).unwrap() {
+            LiveEventEnveloppe(_, LiveEvent::LiveTrade(t)) => {
+                assert_eq!(t.amount, BigDecimal::from_str("0.00000001").unwrap());
+                assert_eq!(EventFormat::Csv.encode(&LiveEventEnveloppe(Exchange::Bitstamp, LiveEvent::LiveTrade(t))).unwrap().unwrap(), trade);
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+        // Pairs named as the exchange does
+        assert!(EventFormat::Csv.decode("Bitstamp,trade,1577146143220,btcusd,7312.91,1,Sell,,").is_ok());
+    }
+
+    #[test]
+    fn binary_round_trips() {
+        for format in &[WireFormat::MessagePack, WireFormat::Cbor] {
+            let bytes = format.encode(&book()).unwrap();
+            let decoded = format.decode(&bytes).unwrap();
+            assert_eq!(serde_json::to_value(decoded).unwrap(), serde_json::to_value(book()).unwrap(), "{:?}", format);
+        }
+        assert_eq!(WireFormat::negotiate("application/cbor;q=0.9, application/json"), Some(WireFormat::Cbor));
+        assert_eq!(WireFormat::negotiate("text/html"), None);
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let line = EventFormat::JsonLines.encode(&book()).unwrap().unwrap();
+        assert!(EventFormat::JsonLines.decode(&line).is_ok());
+    }
+}
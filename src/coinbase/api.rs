@@ -0,0 +1,305 @@
+//! Use this module to interact with the Advanced Trade API of Coinbase, which replaced the
+//! Coinbase Pro (Gdax) API.
+//! Requests are authenticated with a JWT for the keys of the developer platform, with the
+//! CB-ACCESS-* headers for the legacy keys.
+
+use hyper::{Body, Method, Request, Uri};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
+
+use serde_json::Value;
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicI64;
+use std::time::Instant;
+
+use crate::coinbase::utils;
+use crate::coinnect::Credentials;
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::helpers;
+use crate::helpers::clock::ServerClock;
+use crate::helpers::rate_limit::RateLimits;
+use crate::helpers::failover::Endpoints;
+use crate::helpers::proxy::HttpClient;
+use crate::helpers::secret::SecretString;
+use crate::helpers::signing;
+use crate::metrics;
+use bytes::buf::BufExt as _;
+
+const CB_ACCESS_KEY: &str = "CB-ACCESS-KEY";
+const CB_ACCESS_SIGN: &str = "CB-ACCESS-SIGN";
+const CB_ACCESS_TIMESTAMP: &str = "CB-ACCESS-TIMESTAMP";
+
+#[derive(Debug)]
+pub struct CoinbaseApi {
+    /// `Coinbase`, or `Gdax` for the clients built from Gdax credentials
+    exchange: Exchange,
+    last_request: AtomicI64, // unix timestamp in ms, to avoid ban
+    api_key: SecretString,
+    api_secret: SecretString,
+    http_client: HttpClient,
+    burst: bool,
+    pub(crate) keep_raw: bool,
+    debug_signing: bool,
+    endpoints: Endpoints,
+    pub(crate) clock: ServerClock,
+    pub(crate) rate_limits: RateLimits,
+}
+
+impl CoinbaseApi {
+    /// Create a new CoinbaseApi by providing an API key & API secret, the sandbox is targeted
+    /// when the credentials ask for it.
+    /// Gdax credentials are accepted too, their passphrase is not used.
+    pub fn new<C: Credentials>(creds: C) -> Result<CoinbaseApi> {
+        let exchange = creds.exchange();
+        if exchange != Exchange::Coinbase && exchange != Exchange::Gdax {
+            return Err(Error::InvalidConfigType(Exchange::Coinbase, exchange));
+        }
+
+        let connection = creds.connection();
+        let endpoints = connection.rest_endpoints(exchange, utils::base_url(creds.environment()?));
+
+        Ok(CoinbaseApi {
+            exchange,
+            last_request: AtomicI64::new(0),
+            api_key: creds.get("api_key").unwrap_or_default().into(),
+            api_secret: creds.get("api_secret").unwrap_or_default().into(),
+            http_client: connection.http_client()?,
+            burst: false,
+            keep_raw: connection.keep_raw,
+            debug_signing: connection.debug_signing,
+            endpoints,
+            clock: ServerClock::default(),
+            rate_limits: RateLimits::default(),
+        })
+    }
+
+    /// The REST endpoints, to subscribe to failover events or recover the primary endpoint
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
+    /// The number of calls in a given period is limited. In order to avoid a ban we limit
+    /// by default the number of api requests.
+    /// This function sets or removes the limitation.
+    /// Burst false implies no block.
+    /// Burst true implies there is a control over the number of calls allowed to the exchange
+    pub fn set_burst(&mut self, burst: bool) {
+        self.burst = burst
+    }
+
+    /// Keep the payload of the exchange in the `raw` field of typed responses
+    pub fn set_keep_raw(&mut self, keep_raw: bool) {
+        self.keep_raw = keep_raw
+    }
+
+    /// Log the strings signed for the private requests and the status of their responses
+    pub fn set_debug_signing(&mut self, debug_signing: bool) {
+        self.debug_signing = debug_signing
+    }
+
+    async fn block_or_continue(&self) {
+        if !self.burst {
+            helpers::rate_limit::wait_for_slot(&self.last_request, 100).await; // 10 requests/sec
+        }
+    }
+
+    /// Send a request to `endpoint`, relative to the Advanced Trade root, with the query string
+    /// of `params`. Signed requests carry a JWT or the CB-ACCESS-* headers, `body` is sent as JSON.
+    /// Error responses are mapped with `utils::parse_result`.
+    async fn query(&self, method: Method, endpoint: &str, params: &mut HashMap<&str, &str>, body: Option<Value>, signed: bool) -> Result<Value> {
+        helpers::strip_empties(params);
+        let path = format!("{}{}", utils::API_PREFIX, endpoint);
+        let query = helpers::url_encode_hashmap(params);
+        let url = if query.is_empty() {
+            format!("{}{}", self.endpoints.current(), path)
+        } else {
+            format!("{}{}?{}", self.endpoints.current(), path, query)
+        };
+        let uri: Uri = url.parse().map_err(|_e| Error::BadParse)?;
+        let content = body.map(|b| b.to_string()).unwrap_or_default();
+
+        let mut builder = Request::builder()
+            .method(method.clone())
+            .uri(&uri)
+            .header(USER_AGENT, "coinnect")
+            .header(CONTENT_TYPE, "application/json");
+        if signed {
+            let now_secs = self.clock.now_ms() / 1000;
+            if utils::is_pem_key(self.api_secret.expose()) {
+                // The JWT is bound to the method, host and path of the request
+                let request_uri = format!("{} {}{}", method.as_str(), uri.host().unwrap_or_default(), path);
+                let jwt = utils::build_jwt(self.api_key.expose(), self.api_secret.expose(), Some(&request_uri), now_secs)?;
+                if self.debug_signing {
+                    signing::log_signed(self.exchange, endpoint, &now_secs.to_string(), &request_uri, &[self.api_key.expose()]);
+                }
+                builder = builder.header(AUTHORIZATION, format!("Bearer {}", jwt));
+            } else {
+                let timestamp = now_secs.to_string();
+                let signature = utils::hmac_signature(&timestamp, method.as_str(), &path, &content, self.api_secret.expose());
+                if self.debug_signing {
+                    let canonical = format!("{}{}{}{}", timestamp, method.as_str(), path, content);
+                    signing::log_signed(self.exchange, endpoint, &timestamp, &canonical, &[self.api_key.expose()]);
+                }
+                builder = builder
+                    .header(CB_ACCESS_KEY, self.api_key.expose())
+                    .header(CB_ACCESS_SIGN, signature)
+                    .header(CB_ACCESS_TIMESTAMP, timestamp);
+            }
+        }
+        let req = builder.body(Body::from(content)).map_err(|e| Error::ServiceUnavailable(e.to_string()))?;
+
+        self.block_or_continue().await;
+        let start = Instant::now();
+        let resp = self.http_client.request(req).await.map_err(Error::from);
+        metrics::rest_request(self.exchange, endpoint, start);
+        let resp = self.endpoints.track(resp)?;
+        self.rate_limits.observe(resp.headers(), helpers::get_unix_timestamp_ms());
+        let status = resp.status();
+        if signed && self.debug_signing {
+            signing::log_response(self.exchange, endpoint, status);
+        }
+        let buf = hyper::body::aggregate(resp.into_body()).await?;
+        let value: Value = serde_json::from_reader(buf.reader()).map_err(|_| Error::BadParse)?;
+        if !status.is_success() {
+            utils::parse_result(&value)?;
+            return Err(Error::ExchangeSpecificError(status.to_string()));
+        }
+        Ok(value)
+    }
+
+    /// Time of the server
+    ///
+    /// ```json
+    /// {"iso": "2023-05-02T13:18:35.421Z", "epochSeconds": "1683033515", "epochMillis": "1683033515421"}
+    /// ```
+    pub async fn get_time(&self) -> Result<Value> {
+        let mut params = HashMap::new();
+        self.query(Method::GET, "/time", &mut params, None, false).await
+    }
+
+    /// Products of a type, `SPOT` or `FUTURE`, with their status
+    pub async fn get_products(&self, product_type: &str) -> Result<Value> {
+        let mut params = HashMap::new();
+        params.insert("product_type", product_type);
+        self.query(Method::GET, "/market/products", &mut params, None, false).await
+    }
+
+    /// ```json
+    /// {"pricebook": {"product_id": "BTC-USD", "bids": [{"price": "27000.01", "size": "0.5"}],
+    /// "asks": [{"price": "27000.02", "size": "0.1"}], "time": "2023-05-02T13:18:35.421Z"}}
+    /// ```
+    pub async fn get_product_book(&self, product_id: &str, limit: &str) -> Result<Value> {
+        let mut params = HashMap::new();
+        params.insert("product_id", product_id);
+        params.insert("limit", limit);
+        self.query(Method::GET, "/market/product_book", &mut params, None, false).await
+    }
+
+    /// Latest trades of a product along with its best bid and ask
+    ///
+    /// ```json
+    /// {"trades": [{"trade_id": "34b080bf", "product_id": "BTC-USD", "price": "27000.01",
+    /// "size": "0.01", "time": "2023-05-02T13:18:35.421Z", "side": "BUY"}],
+    /// "best_bid": "27000.01", "best_ask": "27000.02"}
+    /// ```
+    pub async fn get_market_trades(&self, product_id: &str, limit: &str) -> Result<Value> {
+        let mut params = HashMap::new();
+        params.insert("limit", limit);
+        self.query(Method::GET, &format!("/market/products/{}/ticker", product_id), &mut params, None, false).await
+    }
+
+    /// Price, 24h volume and 24h change of a product
+    pub async fn get_product(&self, product_id: &str) -> Result<Value> {
+        let mut params = HashMap::new();
+        self.query(Method::GET, &format!("/market/products/{}", product_id), &mut params, None, false).await
+    }
+
+    /// One page of the accounts of the portfolio, one per currency
+    ///
+    /// ```json
+    /// {"accounts": [{"uuid": "8bfc20d7", "currency": "BTC",
+    /// "available_balance": {"value": "1.23", "currency": "BTC"},
+    /// "hold": {"value": "0.1", "currency": "BTC"}, ...}], "has_next": false, "cursor": ""}
+    /// ```
+    pub async fn get_accounts(&self, cursor: &str) -> Result<Value> {
+        let mut params = HashMap::new();
+        params.insert("limit", "250");
+        params.insert("cursor", cursor);
+        self.query(Method::GET, "/accounts", &mut params, None, true).await
+    }
+
+    /// Fee tier of the account and its 30 days volume
+    ///
+    /// ```json
+    /// {"total_volume": 1000, "fee_tier": {"pricing_tier": "<$10k", "maker_fee_rate": "0.006",
+    /// "taker_fee_rate": "0.008"}, ...}
+    /// ```
+    pub async fn get_transaction_summary(&self) -> Result<Value> {
+        let mut params = HashMap::new();
+        self.query(Method::GET, "/transaction_summary", &mut params, None, true).await
+    }
+
+    /// Input:
+    ///
+    /// ```json
+    /// client_order_id = unique id of the order, orders sent twice with the same id are deduplicated
+    /// product_id = product id, e.g. BTC-USD
+    /// side = BUY or SELL
+    /// order_configuration = e.g. {"limit_limit_gtc": {"base_size": "0.1", "limit_price": "27000"}}
+    /// or {"market_market_ioc": {"base_size": "0.1"}}
+    /// ```
+    /// Result: `success`, with the `order_id` in `success_response` or the failure reason in
+    /// `error_response`
+    pub async fn create_order(&self, client_order_id: &str, product_id: &str, side: &str, order_configuration: Value) -> Result<Value> {
+        let mut params = HashMap::new();
+        let body = serde_json::json!({
+            "client_order_id": client_order_id,
+            "product_id": product_id,
+            "side": side,
+            "order_configuration": order_configuration,
+        });
+        self.query(Method::POST, "/orders", &mut params, Some(body), true).await
+    }
+
+    /// Cancel orders by id, `results` tells which ones were cancelled
+    pub async fn cancel_orders(&self, order_ids: &[&str]) -> Result<Value> {
+        let mut params = HashMap::new();
+        let body = serde_json::json!({"order_ids": order_ids});
+        self.query(Method::POST, "/orders/batch_cancel", &mut params, Some(body), true).await
+    }
+
+    /// Open orders of a product
+    pub async fn get_open_orders(&self, product_id: &str) -> Result<Value> {
+        let mut params = HashMap::new();
+        params.insert("order_status", "OPEN");
+        params.insert("product_ids", product_id);
+        self.query(Method::GET, "/orders/historical/batch", &mut params, None, true).await
+    }
+
+    /// Latest orders of a product, whatever their status
+    ///
+    /// ```json
+    /// {"orders": [{"order_id": "0000", "client_order_id": "1111", "product_id": "BTC-USD",
+    /// "side": "BUY", "status": "FILLED", ...}], "has_next": false}
+    /// ```
+    pub async fn get_orders(&self, product_id: &str) -> Result<Value> {
+        let mut params = HashMap::new();
+        params.insert("product_ids", product_id);
+        self.query(Method::GET, "/orders/historical/batch", &mut params, None, true).await
+    }
+
+    /// One page of the fills of a product, the next one starts at `cursor`
+    ///
+    /// ```json
+    /// {"fills": [{"entry_id": "22222", "trade_id": "1111", "order_id": "0000",
+    /// "trade_time": "2021-05-31T09:59:59Z", "price": "27000", "size": "0.1", "commission": "1.25",
+    /// "product_id": "BTC-USD", "liquidity_indicator": "MAKER", "side": "BUY"}], "cursor": "789100"}
+    /// ```
+    pub async fn get_fills(&self, product_id: &str, cursor: &str) -> Result<Value> {
+        let mut params = HashMap::new();
+        params.insert("product_id", product_id);
+        params.insert("cursor", cursor);
+        self.query(Method::GET, "/orders/historical/fills", &mut params, None, true).await
+    }
+}
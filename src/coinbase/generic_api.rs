@@ -0,0 +1,231 @@
+//! Use this module to interact with Coinbase through a Generic API.
+//! This a more convenient and safe way to deal with the exchange since methods return a Result<>
+//! but this generic API does not provide all the functionnality that Coinbase offers.
+
+use crate::exchange::{ExchangeApi, Exchange, ExchangeCapabilities};
+use crate::coinbase::api::CoinbaseApi;
+use crate::coinbase::utils;
+
+use crate::error::*;
+use crate::types::*;
+use crate::helpers;
+use async_trait::async_trait;
+use chrono::DateTime;
+use serde_json::Value;
+
+/// Currencies are native symbols, which match the `Currency` names
+fn parse_currency(ccy: &Value) -> Option<Currency> {
+    serde_json::from_value(ccy.clone()).ok()
+}
+
+#[async_trait]
+impl ExchangeApi for CoinbaseApi {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
+        let product_id = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let result = self.get_market_trades(product_id, "1").await?;
+        let last_trade = &result["trades"][0];
+
+        Ok(Ticker {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            pair,
+            last_trade_price: helpers::from_json_bigdecimal(&last_trade["price"], "price")?,
+            lowest_ask: helpers::from_json_bigdecimal(&result["best_ask"], "best_ask")?,
+            highest_bid: helpers::from_json_bigdecimal(&result["best_bid"], "best_bid")?,
+            volume: None,
+            bid_size: None,
+            ask_size: None,
+            open_24h: None,
+            high_24h: None,
+            low_24h: None,
+            percent_change: None,
+            raw: helpers::raw_payload(self.keep_raw, &result),
+        })
+    }
+
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
+        let product_id = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let result = self.get_product_book(product_id, "").await?;
+        utils::parse_pricebook(pair, &result["pricebook"])
+    }
+
+    /// Limit orders are good till cancelled
+    async fn add_order(&self,
+                 order_type: OrderType,
+                 pair: Pair,
+                 quantity: Volume,
+                 price: Option<Price>)
+                 -> Result<OrderInfo> {
+        self.simple_order(OrderRequest::new(order_type, pair, &quantity, &price)).await
+    }
+
+    /// Client ids are sent as the `client_order_id` of the order, orders sent again with the same
+    /// id are not placed twice
+    async fn add_order_with_id(&self,
+                               order_type: OrderType,
+                               pair: Pair,
+                               quantity: Volume,
+                               price: Option<Price>,
+                               client_id: &str)
+                               -> Result<OrderInfo> {
+        self.simple_order(OrderRequest::new(order_type, pair, &quantity, &price).with_client_id(client_id)).await
+    }
+
+    /// Looked up in the latest orders of the product
+    async fn order_by_client_id(&self, pair: Pair, client_id: &str) -> Result<Option<OrderInfo>> {
+        let product_id = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let result = self.get_orders(product_id).await?;
+        let order = match utils::find_order(&result, client_id)? {
+            Some(order) => order,
+            None => return Ok(None),
+        };
+        Ok(Some(OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: vec![order["order_id"].as_str().ok_or_else(|| Error::MissingField("order_id".to_string()))?.to_string()],
+            raw: helpers::raw_payload(self.keep_raw, order),
+            request: None,
+        }))
+    }
+
+    async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+        let ids: Vec<&str> = order.identifier.iter().map(|id| id.as_str()).collect();
+        let result = self.cancel_orders(&ids).await?;
+        utils::parse_result(&result)?;
+        let results = result["results"].as_array().ok_or_else(|| Error::MissingField("results".to_string()))?;
+        match results.iter().find(|r| r["success"].as_bool() != Some(true)) {
+            Some(failed) => Err(utils::parse_error(failed["failure_reason"].as_str().unwrap_or_default(), "")),
+            None => Ok(()),
+        }
+    }
+
+    /// Fee rates depend on the 30 days volume of the account, not on the pair
+    async fn fees(&self, _pair: Pair) -> Result<FeeRates> {
+        let result = self.get_transaction_summary().await?;
+        let tier = &result["fee_tier"];
+        Ok(FeeRates::new(helpers::from_json_bigdecimal(&tier["maker_fee_rate"], "maker_fee_rate")?,
+                         helpers::from_json_bigdecimal(&tier["taker_fee_rate"], "taker_fee_rate")?))
+    }
+
+    /// Return the available balance of each currency, one account per currency
+    async fn balances(&self) -> Result<Balances> {
+        Ok(self.detailed_balances().await?.into_iter().map(|(c, b)| (c, b.available)).collect())
+    }
+
+    /// Accounts are walked page by page
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        let mut balances = DetailedBalances::new();
+        let mut cursor = String::new();
+        loop {
+            let result = self.get_accounts(&cursor).await?;
+            let accounts = result["accounts"].as_array().ok_or_else(|| Error::InvalidFieldFormat("accounts".to_string()))?;
+            for account in accounts {
+                if let Some(c) = parse_currency(&account["currency"]) {
+                    let available = utils::parse_amount(&account["available_balance"], "available_balance")?;
+                    let hold = utils::parse_amount(&account["hold"], "hold")?;
+                    balances.insert(c, Balance::new(available, hold));
+                }
+            }
+            match result["cursor"].as_str() {
+                Some(next) if result["has_next"] == true && !next.is_empty() => cursor = next.to_string(),
+                _ => return Ok(balances),
+            }
+        }
+    }
+
+    /// Coinbase charges the fee in the quote currency
+    async fn fills_page(&self, pair: Pair, cursor: Option<String>) -> Result<FillsPage> {
+        let product_id = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let result = self.get_fills(product_id, cursor.as_deref().unwrap_or_default()).await?;
+        let fills = result["fills"].as_array().ok_or_else(|| Error::InvalidFieldFormat("fills".to_string()))?;
+        let fee_currency = crate::utils::pair_currencies(&pair).map(|(_, quote)| quote);
+        let fills = fills.iter().map(|f| {
+            let trade_time = f["trade_time"].as_str().ok_or_else(|| Error::MissingField("trade_time".to_string()))?;
+            let event_ms = DateTime::parse_from_rfc3339(trade_time)
+                .map_err(|_| Error::InvalidFieldFormat(trade_time.to_string()))?
+                .timestamp_millis();
+            Ok(FillEvent {
+                event_ms,
+                order_id: f["order_id"].as_str().ok_or_else(|| Error::MissingField("order_id".to_string()))?.to_string(),
+                trade_id: f["trade_id"].as_str().map(|id| id.to_string()),
+                pair,
+                side: TradeType::parse(&f["side"].as_str().unwrap_or_default().to_lowercase()),
+                price: helpers::from_json_bigdecimal(&f["price"], "price")?,
+                amount: helpers::from_json_bigdecimal(&f["size"], "size")?,
+                fee: helpers::from_json_bigdecimal(&f["commission"], "commission")?,
+                fee_currency,
+                liquidity: match f["liquidity_indicator"].as_str() {
+                    Some("MAKER") => Liquidity::Maker,
+                    Some("TAKER") => Liquidity::Taker,
+                    _ => Liquidity::Unknown,
+                },
+            })
+        }).collect::<Result<Vec<FillEvent>>>()?;
+        let next = result["cursor"].as_str().filter(|c| !c.is_empty()).map(|c| c.to_string());
+        Ok(FillsPage { fills, next })
+    }
+
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        let result = self.get_products("SPOT").await?;
+        let products = result["products"].as_array().ok_or_else(|| Error::InvalidFieldFormat("products".to_string()))?;
+        Ok(products.iter()
+            .filter(|p| p["status"] == "online" && p["trading_disabled"] != true)
+            .filter_map(|p| p["product_id"].as_str().and_then(utils::get_pair_enum).copied())
+            .collect())
+    }
+
+    async fn time(&self) -> Result<i64> {
+        let sent_ms = helpers::get_unix_timestamp_ms();
+        let result = self.get_time().await?;
+        let server_ms = result["epochMillis"].as_str().and_then(|t| t.parse().ok()).ok_or_else(|| Error::MissingField("epochMillis".to_string()))?;
+        self.clock.observe(server_ms, sent_ms, helpers::get_unix_timestamp_ms());
+        Ok(server_ms)
+    }
+
+    fn clock_skew(&self) -> Option<i64> {
+        self.clock.skew()
+    }
+
+    fn limits(&self) -> RateLimitStatus {
+        self.rate_limits.status()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Coinbase.capabilities()
+    }
+}
+
+impl CoinbaseApi {
+    /// Place a limit or market order, with a random client id unless the request has one
+    async fn simple_order(&self, request: OrderRequest) -> Result<OrderInfo> {
+        let product_id = utils::get_pair_string(&request.pair).ok_or(Error::PairUnsupported)?;
+        let quantity = &request.quantity;
+        let configuration = match request.order_type {
+            OrderType::BuyLimit | OrderType::SellLimit => {
+                let price = request.price.as_ref().ok_or(Error::MissingPrice)?;
+                serde_json::json!({"limit_limit_gtc": {"base_size": quantity.to_string(), "limit_price": price.to_string()}})
+            }
+            OrderType::BuyMarket | OrderType::SellMarket => serde_json::json!({"market_market_ioc": {"base_size": quantity.to_string()}}),
+            _ => return Err(Error::OrderTypeUnsupported),
+        };
+        let side = match request.order_type.side() {
+            Side::Buy => "BUY",
+            Side::Sell => "SELL",
+        };
+        let client_order_id = request.client_id.clone().unwrap_or_else(utils::client_order_id);
+        let result = self.create_order(&client_order_id, product_id, side, configuration).await?;
+        if result["success"] != true {
+            let failure = &result["error_response"];
+            let reason = failure["preview_failure_reason"].as_str()
+                .filter(|r| *r != "UNKNOWN_PREVIEW_FAILURE_REASON")
+                .or_else(|| failure["error"].as_str())
+                .unwrap_or_default();
+            return Err(utils::parse_error(reason, failure["message"].as_str().unwrap_or_default()));
+        }
+
+        Ok(OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: vec![result["success_response"]["order_id"].as_str().ok_or_else(|| Error::MissingField("order_id".to_string()))?.to_string()],
+            raw: helpers::raw_payload(self.keep_raw, &result),
+            request: Some(request),
+        })
+    }
+}
@@ -0,0 +1,13 @@
+//! Use this module to interact with Coinbase through its Advanced Trade API.
+//! `Exchange::Gdax` clients and bots are built from this module too, the Coinbase Pro API of the
+//! `gdax` module having been retired.
+
+pub mod api;
+pub mod generic_api;
+pub mod credentials;
+pub mod utils;
+#[cfg(feature = "streaming")]
+pub mod streaming_api;
+
+pub use self::credentials::CoinbaseCreds;
+pub use self::api::CoinbaseApi;
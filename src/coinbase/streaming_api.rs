@@ -0,0 +1,333 @@
+//! Stream the public channels of the Advanced Trade websocket of Coinbase.
+//! The `level2` channel sends a snapshot of each book then the levels that changed. Messages are
+//! numbered over the connection : after a gap every book is resynced and `level2` subscribed
+//! again for fresh snapshots.
+
+use crate::coinbase::utils;
+use crate::coinnect::Credentials;
+use crate::exchange_bot::{self, DefaultWsActor, ObserveRawFrames, RawFrameEvent, WsHandler, ExchangeBot, Snapshot};
+use crate::error::*;
+use bytes::Bytes;
+use bytes::Buf;
+use futures::future::LocalBoxFuture;
+use futures::stream::SplitSink;
+use actix::{io::SinkWrite, Addr, Recipient};
+use awc::{
+    ws::{Codec, Message},  BoxedSocket
+};
+use actix_codec::Framed;
+use crate::types::{LiveEvent, Channel, LiveAggregatedOrderBook, DEFAULT_BOOK_DEPTH, LiveEventEnveloppe, LiveTrade, Orderbook, Pair, Price, SubscriptionResult, TradeMeta, TradeTape, TradeType, Volume, WarmUp};
+use crate::exchange::{Exchange, ExchangeCapabilities};
+use crate::helpers;
+use crate::helpers::delivery::{Delivery, DeliveryPolicy};
+use crate::helpers::failover::Endpoints;
+use crate::helpers::secret::SecretString;
+use crate::metrics;
+use chrono::DateTime;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+
+pub struct CoinbaseBot {
+    exchange: Exchange,
+    addr: Addr<DefaultWsActor>,
+    endpoints: Arc<Endpoints>,
+}
+
+impl ExchangeBot for CoinbaseBot {
+    fn is_connected(&self) -> bool {
+        self.addr.connected()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        self.exchange.capabilities()
+    }
+
+    fn observe_raw_frames(&self, recipient: Recipient<RawFrameEvent>) -> Result<()> {
+        self.addr.do_send(ObserveRawFrames(self.exchange, recipient));
+        Ok(())
+    }
+
+    fn endpoints(&self) -> Option<Arc<Endpoints>> {
+        Some(self.endpoints.clone())
+    }
+
+    fn shutdown(&self) -> LocalBoxFuture<'static, ()> {
+        exchange_bot::shutdown_ws(&self.addr)
+    }
+}
+
+impl CoinbaseBot {
+    /// The current order book and latest trade of `pair`
+    pub async fn snapshot(&self, pair: Pair) -> Result<Option<Snapshot>> {
+        exchange_bot::query_snapshot(&self.addr, pair).await
+    }
+}
+
+#[derive(Debug)]
+pub struct CoinbaseStreamingApi {
+    /// `Coinbase`, or `Gdax` for the bots built from Gdax credentials
+    exchange: Exchange,
+    api_key: SecretString,
+    api_secret: SecretString,
+    delivery: Delivery<LiveEventEnveloppe>,
+    channels: HashMap<Channel, HashSet<Pair>>,
+    warm_up: WarmUp,
+    depth: i8,
+    agg: HashMap<Pair, LiveAggregatedOrderBook>,
+    books: HashMap<Pair, Orderbook>,
+    last_trades: HashMap<Pair, LiveTrade>,
+    tape: TradeTape,
+    /// Subscriptions already reported, every acknowledgement lists all the subscriptions
+    acknowledged: HashSet<(Channel, Pair)>,
+    /// Number of the last message of the connection
+    sequence: Option<u64>,
+}
+
+impl CoinbaseStreamingApi {
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, warm_up: WarmUp, depth: Option<i8>, delivery: DeliveryPolicy, recipients: Vec<Recipient<LiveEventEnveloppe>>, raw_frames: Option<Recipient<RawFrameEvent>>) -> Result<CoinbaseBot> {
+        creds.environment()?.production_only(Exchange::Coinbase)?;
+        let exchange = creds.exchange();
+        let connection = creds.connection();
+        let api = CoinbaseStreamingApi {
+            exchange,
+            api_key: creds.get("api_key").unwrap_or_default().into(),
+            api_secret: creds.get("api_secret").unwrap_or_default().into(),
+            delivery: Delivery::new(recipients, delivery),
+            channels,
+            warm_up,
+            depth: depth.unwrap_or(DEFAULT_BOOK_DEPTH),
+            agg: HashMap::new(),
+            books: HashMap::new(),
+            last_trades: HashMap::new(),
+            tape: TradeTape::default(),
+            acknowledged: HashSet::new(),
+            sequence: None,
+        };
+        let endpoints = Arc::new(connection.ws_endpoints(exchange, utils::WEBSOCKET_URL));
+        let addr = DefaultWsActor::new("CoinbaseStream", endpoints.clone(), connection.proxy()?, Some(Duration::from_secs(5)), raw_frames.map(|r| (exchange, r)).into_iter().collect(), Box::new(api)).await?;
+        Ok(CoinbaseBot { exchange, addr, endpoints })
+    }
+
+    /// Every order book channel is served by the `level2` channel
+    fn book_pairs(&self) -> Vec<Pair> {
+        let pairs: HashSet<Pair> = [Channel::LiveOrderBook, Channel::LiveDetailOrderBook, Channel::LiveFullOrderBook].iter()
+            .filter_map(|c| self.channels.get(c))
+            .flatten()
+            .copied()
+            .collect();
+        pairs.into_iter().collect()
+    }
+
+    /// Subscriptions are authenticated with a JWT for the keys of the developer platform, public
+    /// ones otherwise
+    fn subscription(&self, op: &str, channel: &str, pairs: &[Pair]) -> Message {
+        let product_ids: Vec<&str> = pairs.iter().filter_map(|p| utils::get_pair_string(p)).copied().collect();
+        let mut sub = serde_json::json!({"type": op, "product_ids": product_ids, "channel": channel});
+        if utils::is_pem_key(self.api_secret.expose()) {
+            match utils::build_jwt(self.api_key.expose(), self.api_secret.expose(), None, helpers::get_unix_timestamp_ms() / 1000) {
+                Ok(jwt) => sub["jwt"] = Value::String(jwt),
+                Err(e) => warn!("Coinbase : cannot sign the {} subscription : {}", channel, e),
+            }
+        }
+        Message::Text(sub.to_string())
+    }
+
+    /// Resync every book and subscribe to `level2` again, its snapshots seed the books
+    fn resync_books(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>) {
+        for book in self.agg.values_mut() {
+            book.resync();
+        }
+        self.books.clear();
+        let pairs = self.book_pairs();
+        if !pairs.is_empty() {
+            w.write(self.subscription("unsubscribe", "level2", &pairs));
+            w.write(self.subscription("subscribe", "level2", &pairs));
+        }
+    }
+
+    /// Apply a `l2_data` event, returns the book if its top changed
+    fn apply_book(&mut self, pair: Pair, snapshot: bool, updates: &Value) -> Result<Option<Orderbook>> {
+        let updates = updates.as_array().ok_or_else(|| Error::InvalidFieldFormat("updates".to_string()))?;
+        let mut asks: Vec<(Price, Volume)> = Vec::new();
+        let mut bids: Vec<(Price, Volume)> = Vec::new();
+        for update in updates {
+            let level = (helpers::from_json_bigdecimal(&update["price_level"], "price_level")?,
+                         helpers::from_json_bigdecimal(&update["new_quantity"], "new_quantity")?);
+            match update["side"].as_str() {
+                Some("bid") => bids.push(level),
+                _ => asks.push(level),
+            }
+        }
+        let warm_up = self.warm_up;
+        let depth = self.depth;
+        let book = self.agg.entry(pair).or_insert_with(|| LiveAggregatedOrderBook::with_warm_up(pair, warm_up).with_depth(depth));
+        if snapshot {
+            book.resync();
+            book.reset_asks(asks.into_iter());
+            book.reset_bids(bids.into_iter());
+        } else {
+            book.update_asks(asks.into_iter());
+            book.update_bids(bids.into_iter());
+        }
+        Ok(book.latest_order_book())
+    }
+
+    /// Report the subscriptions listed for the first time by a `subscriptions` event
+    fn acknowledge(&mut self, subscriptions: &Value) {
+        let mut results = vec![];
+        for (name, channels) in &[("level2", vec![Channel::LiveOrderBook, Channel::LiveDetailOrderBook, Channel::LiveFullOrderBook]), ("market_trades", vec![Channel::LiveTrades])] {
+            let pairs = subscriptions[*name].as_array().into_iter().flatten()
+                .filter_map(|p| p.as_str().and_then(utils::get_pair_enum));
+            for &pair in pairs {
+                for channel in channels.iter().filter(|c| self.channels.get(c).map_or(false, |pairs| pairs.contains(&pair))) {
+                    if self.acknowledged.insert((channel.clone(), pair)) {
+                        results.push(SubscriptionResult::accepted(channel.clone(), pair));
+                    }
+                }
+            }
+        }
+        for result in results {
+            self.send(LiveEvent::SubscriptionResult(result));
+        }
+    }
+
+    fn send(&mut self, le: LiveEvent) {
+        metrics::ws_event(self.exchange, &le);
+        if self.delivery.is_empty() {
+            debug!("{:?}", le);
+        } else {
+            self.delivery.send(LiveEventEnveloppe(self.exchange, le));
+        }
+    }
+}
+
+fn parse_trade(pair: Pair, trade: &Value) -> Result<LiveTrade> {
+    Ok(LiveTrade {
+        event_ms: trade["time"].as_str()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map_or_else(helpers::get_unix_timestamp_ms, |t| t.timestamp_millis()),
+        pair,
+        amount: helpers::from_json_bigdecimal(&trade["size"], "size")?,
+        price: helpers::from_json_bigdecimal(&trade["price"], "price")?,
+        tt: TradeType::parse(&trade["side"].as_str().unwrap_or_default().to_lowercase()),
+        meta: trade["trade_id"].as_str().map(TradeMeta::with_id).unwrap_or_default(),
+    })
+}
+
+#[async_trait]
+impl WsHandler for CoinbaseStreamingApi {
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_in(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, msg: Bytes) {
+        let v: Value = match serde_json::from_slice(msg.bytes()) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Coinbase : unreadable message : {}", e);
+                return;
+            }
+        };
+        if v["type"] == "error" {
+            warn!("Coinbase : {}", v["message"]);
+            return;
+        }
+        if let Some(sequence) = v["sequence_num"].as_u64() {
+            let gap = self.sequence.map_or(false, |last| sequence > last + 1);
+            self.sequence = Some(sequence);
+            if gap {
+                warn!("Coinbase : messages lost before {}, resyncing the order books", sequence);
+                self.tape.mark_gap();
+                self.resync_books(w);
+                return;
+            }
+        }
+        let events = match v["events"].as_array() {
+            Some(events) => events,
+            None => return,
+        };
+        match v["channel"].as_str() {
+            Some("l2_data") => {
+                for event in events {
+                    let pair = match event["product_id"].as_str().and_then(utils::get_pair_enum) {
+                        Some(pair) => *pair,
+                        None => continue,
+                    };
+                    match self.apply_book(pair, event["type"] == "snapshot", &event["updates"]) {
+                        Ok(Some(ob)) => {
+                            self.books.insert(pair, ob.clone());
+                            self.send(LiveEvent::LiveOrderbook(ob));
+                        }
+                        Ok(None) => (),
+                        Err(e) => warn!("Coinbase : cannot parse the order book of {:?} : {}", pair, e),
+                    }
+                }
+            }
+            Some("market_trades") => {
+                // Snapshots replay the latest trades
+                let mut trades = vec![];
+                for event in events.iter().filter(|e| e["type"] == "update") {
+                    for trade in event["trades"].as_array().into_iter().flatten() {
+                        let pair = match trade["product_id"].as_str().and_then(utils::get_pair_enum) {
+                            Some(pair) => *pair,
+                            None => continue,
+                        };
+                        match parse_trade(pair, trade) {
+                            Ok(t) => trades.push(t),
+                            Err(e) => warn!("Coinbase : cannot parse a trade of {:?} : {}", pair, e),
+                        }
+                    }
+                }
+                for t in self.tape.accept_all(trades) {
+                    self.last_trades.insert(t.pair, t.clone());
+                    self.send(LiveEvent::LiveTrade(t));
+                }
+            }
+            Some("subscriptions") => {
+                for event in events {
+                    self.acknowledge(&event["subscriptions"]);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn snapshot(&self, pair: Pair) -> Option<Snapshot> {
+        let orderbook = self.books.get(&pair).cloned();
+        let last_trade = self.last_trades.get(&pair).cloned();
+        if orderbook.is_none() && last_trade.is_none() {
+            return None;
+        }
+        Some(Snapshot { orderbook, last_trade })
+    }
+
+    /// Books are seeded again by the snapshots sent after subscribing, messages are numbered
+    /// from zero on the new connection
+    fn handle_reconnect(&mut self) {
+        for book in self.agg.values_mut() {
+            book.resync();
+        }
+        self.books.clear();
+        self.sequence = None;
+        self.tape.mark_gap();
+        self.acknowledged.clear();
+    }
+
+    fn flush_deliveries(&mut self) {
+        self.delivery.flush();
+    }
+
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_started(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>) {
+        let book_pairs = self.book_pairs();
+        if !book_pairs.is_empty() {
+            w.write(self.subscription("subscribe", "level2", &book_pairs));
+        }
+        let trade_pairs: Vec<Pair> = self.channels.get(&Channel::LiveTrades).into_iter().flatten().copied().collect();
+        if !trade_pairs.is_empty() {
+            w.write(self.subscription("subscribe", "market_trades", &trade_pairs));
+        }
+        // Keeps the connection open when the subscribed products are quiet
+        w.write(self.subscription("subscribe", "heartbeats", &[]));
+    }
+}
@@ -0,0 +1,223 @@
+use std::str::FromStr;
+
+use bidir_map::BidirMap;
+use bigdecimal::BigDecimal;
+use data_encoding::{BASE64URL_NOPAD, HEXLOWER};
+use hmac::{Hmac, Mac};
+use openssl::bn::BigNumRef;
+use openssl::ec::EcKey;
+use openssl::ecdsa::EcdsaSig;
+use sha2::Sha256;
+
+use serde_json::Value;
+
+use crate::error::*;
+use crate::exchange::Environment;
+use crate::helpers;
+use crate::types::{Orderbook, Pair, Price, Volume};
+use crate::types::Pair::*;
+
+pub const PRODUCTION_URL: &str = "https://api.coinbase.com";
+pub const SANDBOX_URL: &str = "https://api-sandbox.coinbase.com";
+pub const WEBSOCKET_URL: &str = "wss://advanced-trade-ws.coinbase.com";
+
+/// Root of the Advanced Trade endpoints
+pub const API_PREFIX: &str = "/api/v3/brokerage";
+
+/// Lifetime of the JWTs, in seconds
+const JWT_TTL_SECS: i64 = 120;
+
+lazy_static! {
+    static ref PAIRS_STRING: BidirMap<Pair, &'static str> = {
+        let mut m = BidirMap::new();
+        m.insert(BCH_USD, "BCH-USD");
+        m.insert(LTC_EUR, "LTC-EUR");
+        m.insert(LTC_USD, "LTC-USD");
+        m.insert(LTC_BTC, "LTC-BTC");
+        m.insert(ETH_EUR, "ETH-EUR");
+        m.insert(ETH_USD, "ETH-USD");
+        m.insert(ETH_BTC, "ETH-BTC");
+        m.insert(ETH_USDT, "ETH-USDT");
+        m.insert(BTC_GBP, "BTC-GBP");
+        m.insert(BTC_EUR, "BTC-EUR");
+        m.insert(BTC_USD, "BTC-USD");
+        m.insert(BTC_USDT, "BTC-USDT");
+        m.insert(XRP_USD, "XRP-USD");
+        m
+    };
+}
+
+/// Return the product id of the pair, e.g. `BTC-USD`
+/// If the Pair is not supported, None is returned.
+pub fn get_pair_string(pair: &Pair) -> Option<&&str> {
+    PAIRS_STRING.get_by_first(pair)
+}
+
+/// Return the Pair enum associated to a product id
+/// If the Pair is not supported, None is returned.
+pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
+    PAIRS_STRING.get_by_second(&pair)
+}
+
+/// Every pair with a Coinbase name
+pub fn supported_pairs() -> Vec<Pair> {
+    PAIRS_STRING.iter().map(|(pair, _)| *pair).collect()
+}
+
+/// REST root of an environment, the sandbox answers with static responses
+pub fn base_url(environment: Environment) -> &'static str {
+    match environment {
+        Environment::Production => PRODUCTION_URL,
+        Environment::Sandbox => SANDBOX_URL,
+    }
+}
+
+/// Keys of the developer platform have an EC private key as secret, legacy keys a plain string
+pub fn is_pem_key(api_secret: &str) -> bool {
+    api_secret.trim_start().starts_with("-----BEGIN")
+}
+
+/// CB-ACCESS-SIGN header of the legacy keys : the hex encoded HMAC-SHA256 of the timestamp in
+/// seconds, the HTTP method, the request path (without its query string) and the body
+pub fn hmac_signature(timestamp: &str, method: &str, request_path: &str, body: &str, api_secret: &str) -> String {
+    let message = format!("{}{}{}{}", timestamp, method, request_path, body);
+    let mut mac = Hmac::<Sha256>::new(api_secret.as_bytes());
+    mac.input(message.as_bytes());
+    HEXLOWER.encode(mac.result().code())
+}
+
+/// Big endian bytes of `n`, left padded to `len`
+fn padded(n: &BigNumRef, len: usize) -> Vec<u8> {
+    let bytes = n.to_vec();
+    let mut out = vec![0; len.saturating_sub(bytes.len())];
+    out.extend(bytes);
+    out
+}
+
+/// ES256 JWT of the developer platform keys, valid for two minutes from `now_secs`.
+/// REST requests are bound to `uri`, e.g. `GET api.coinbase.com/api/v3/brokerage/accounts`,
+/// websocket subscriptions have none.
+pub fn build_jwt(key_name: &str, pem: &str, uri: Option<&str>, now_secs: i64) -> Result<String> {
+    // Keys copied from a json file often keep their escaped line breaks
+    let pem = pem.replace("\\n", "\n");
+    let key = EcKey::private_key_from_pem(pem.as_bytes()).map_err(|_| Error::BadCredentials)?;
+    let mut nonce = [0u8; 16];
+    openssl::rand::rand_bytes(&mut nonce).map_err(|e| Error::ExchangeSpecificError(e.to_string()))?;
+
+    let header = serde_json::json!({"alg": "ES256", "typ": "JWT", "kid": key_name, "nonce": HEXLOWER.encode(&nonce)});
+    let mut claims = serde_json::json!({"sub": key_name, "iss": "cdp", "nbf": now_secs, "exp": now_secs + JWT_TTL_SECS});
+    if let Some(uri) = uri {
+        claims["uri"] = Value::String(uri.to_string());
+    }
+    let signing_input = format!("{}.{}", BASE64URL_NOPAD.encode(header.to_string().as_bytes()), BASE64URL_NOPAD.encode(claims.to_string().as_bytes()));
+    let digest = openssl::sha::sha256(signing_input.as_bytes());
+    let signature = EcdsaSig::sign(&digest, &key).map_err(|_| Error::BadCredentials)?;
+    let mut raw = padded(signature.r(), 32);
+    raw.extend(padded(signature.s(), 32));
+    Ok(format!("{}.{}", signing_input, BASE64URL_NOPAD.encode(&raw)))
+}
+
+/// A random `client_order_id`, Coinbase deduplicates orders sent twice with the same one
+pub fn client_order_id() -> String {
+    let mut id = [0u8; 16];
+    match openssl::rand::rand_bytes(&mut id) {
+        Ok(()) => HEXLOWER.encode(&id),
+        Err(_) => helpers::get_unix_timestamp_ms().to_string(),
+    }
+}
+
+/// Map the error codes of Coinbase, including the failure reasons of refused orders, to typed errors
+pub fn parse_error(error: &str, message: &str) -> Error {
+    match error {
+        "UNAUTHORIZED" | "UNAUTHENTICATED" | "INVALID_API_KEY" => Error::BadCredentials,
+        "PERMISSION_DENIED" => Error::PermissionDenied,
+        "NOT_FOUND" | "UNKNOWN_CANCEL_ORDER" => Error::OrderNotFound,
+        "INVALID_PRODUCT_ID" => Error::PairUnsupported,
+        "INSUFFICIENT_FUND" | "INSUFFICIENT_FUNDS" => Error::InsufficientFunds,
+        "INVALID_SIZE_PRECISION" | "INVALID_LIMIT_PRICE_PRECISION" => Error::InvalidArguments,
+        "ORDER_SIZE_TOO_SMALL" | "PREVIEW_INVALID_BASE_SIZE_TOO_SMALL" | "PREVIEW_INVALID_QUOTE_SIZE_TOO_SMALL" => Error::InsufficientOrderSize,
+        "RESOURCE_EXHAUSTED" | "TOO_MANY_REQUESTS" => Error::RateLimited(None),
+        _ => Error::ExchangeSpecificError(format!("{} : {}", error, message)),
+    }
+}
+
+/// Errors are `{"error": "NOT_FOUND", "message": "..."}`
+pub fn parse_result(response: &Value) -> Result<()> {
+    match response["error"].as_str() {
+        Some(error) => Err(parse_error(error, response["message"].as_str().unwrap_or_default())),
+        None => Ok(()),
+    }
+}
+
+/// The order of the orders list `result` tagged with `client_id`
+pub fn find_order<'a>(result: &'a Value, client_id: &str) -> Result<Option<&'a Value>> {
+    parse_result(result)?;
+    let orders = result["orders"].as_array().ok_or_else(|| Error::MissingField("orders".to_string()))?;
+    Ok(orders.iter().find(|o| o["client_order_id"].as_str() == Some(client_id)))
+}
+
+/// Levels are `{"price": "...", "size": "..."}`
+fn parse_levels(levels: &Value) -> Result<Vec<(Price, Volume)>> {
+    let levels = levels.as_array().ok_or_else(|| Error::InvalidFieldFormat(levels.to_string()))?;
+    levels.iter().map(|level| {
+        Ok((helpers::from_json_bigdecimal(&level["price"], "price")?, helpers::from_json_bigdecimal(&level["size"], "size")?))
+    }).collect()
+}
+
+/// Order book of a `pricebook`
+pub fn parse_pricebook(pair: Pair, book: &Value) -> Result<Orderbook> {
+    Ok(Orderbook {
+        timestamp: book["time"].as_str()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .map_or_else(helpers::get_unix_timestamp_ms, |t| t.timestamp_millis()),
+        pair,
+        asks: parse_levels(&book["asks"])?,
+        bids: parse_levels(&book["bids"])?,
+    })
+}
+
+/// Amounts are `{"value": "...", "currency": "..."}`
+pub fn parse_amount(amount: &Value, key: &str) -> Result<BigDecimal> {
+    match &amount["value"] {
+        Value::String(v) => Ok(BigDecimal::from_str(v)?),
+        _ => Err(Error::MissingField(key.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod coinbase_utils_tests {
+    use super::*;
+    use openssl::ec::EcGroup;
+    use openssl::nid::Nid;
+
+    #[test]
+    fn jwts_are_signed_with_the_key() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = EcKey::generate(&group).unwrap();
+        let pem = String::from_utf8(key.private_key_to_pem().unwrap()).unwrap();
+        let jwt = build_jwt("organizations/org/apiKeys/key", &pem, Some("GET api.coinbase.com/api/v3/brokerage/accounts"), 1_700_000_000).unwrap();
+        let parts: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        let claims: Value = serde_json::from_slice(&BASE64URL_NOPAD.decode(parts[1].as_bytes()).unwrap()).unwrap();
+        assert_eq!(claims["exp"], 1_700_000_120);
+        let raw = BASE64URL_NOPAD.decode(parts[2].as_bytes()).unwrap();
+        let r = openssl::bn::BigNum::from_slice(&raw[..32]).unwrap();
+        let s = openssl::bn::BigNum::from_slice(&raw[32..]).unwrap();
+        let signature = EcdsaSig::from_private_components(r, s).unwrap();
+        let digest = openssl::sha::sha256(format!("{}.{}", parts[0], parts[1]).as_bytes());
+        assert!(signature.verify(&digest, &key).unwrap());
+        assert!(build_jwt("key", "not a key", None, 0).is_err());
+        assert!(parse_result(&serde_json::json!({"error": "INSUFFICIENT_FUND", "message": ""})).is_err());
+    }
+
+    #[test]
+    fn orders_are_found_by_client_order_id() {
+        let result = serde_json::json!({"orders": [
+            {"order_id": "a", "client_order_id": "11"},
+            {"order_id": "b", "client_order_id": "42"}
+        ]});
+        assert_eq!(find_order(&result, "42").unwrap().unwrap()["order_id"], "b");
+        assert!(find_order(&result, "7").unwrap().is_none());
+        assert!(find_order(&serde_json::json!({"error": "UNKNOWN", "message": ""}), "42").is_err());
+    }
+}
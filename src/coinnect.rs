@@ -4,21 +4,50 @@
 #![allow(new_ret_no_self)]
 
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
 
+#[cfg(feature = "kraken")]
 use crate::kraken::{KrakenApi, KrakenCreds};
+#[cfg(all(feature = "kraken", feature = "streaming"))]
+use crate::kraken::{streaming_api::KrakenStreamingApi, ws_trading::KrakenWsTrading};
+#[cfg(feature = "poloniex")]
 use crate::poloniex::{PoloniexApi, PoloniexCreds};
+#[cfg(feature = "bittrex")]
 use crate::bittrex::{BittrexApi, BittrexCreds};
+#[cfg(all(feature = "bittrex", feature = "streaming"))]
 use crate::bittrex::streaming_api::BittrexStreamingApi;
+#[cfg(all(feature = "gdax", not(feature = "coinbase")))]
 use crate::gdax::{GdaxApi, GdaxCreds};
-use crate::binance::{BinanceApi, BinanceCreds, streaming_api::BinanceStreamingApi};
-use crate::error::{Result};
-use crate::exchange::{Exchange, ExchangeApi, ExchangeSettings};
+#[cfg(feature = "coinbase")]
+use crate::coinbase::{CoinbaseApi, CoinbaseCreds};
+#[cfg(all(feature = "coinbase", feature = "streaming"))]
+use crate::coinbase::streaming_api::CoinbaseStreamingApi;
+#[cfg(feature = "binance")]
+use crate::binance::{BinanceApi, BinanceCreds};
+#[cfg(all(feature = "binance", feature = "streaming"))]
+use crate::binance::streaming_api::BinanceStreamingApi;
+#[cfg(feature = "okx")]
+use crate::okx::{OkxApi, OkxCreds};
+#[cfg(all(feature = "okx", feature = "streaming"))]
+use crate::okx::streaming_api::OkxStreamingApi;
+use crate::error::{Error, Result};
+use crate::exchange::{ConnectionConfig, Environment, Exchange, ExchangeApi, ExchangeSettings};
+#[cfg(feature = "bitstamp")]
 use crate::bitstamp::{BitstampApi, BitstampCreds};
+#[cfg(all(feature = "bitstamp", feature = "streaming"))]
 use crate::bitstamp::streaming_api::BitstampStreamingApi;
-use crate::exchange_bot::{ExchangeBot};
-use actix::{Recipient};
-use crate::types::{Channel, Pair, LiveEventEnveloppe};
-use std::collections::{HashMap, HashSet};
+use crate::simulated::{SimulatedApi, SimulatedCreds};
+use crate::config::{Deployment, DeploymentConfig};
+use crate::exchange_bot::{ExchangeBot, OrderGateway};
+use crate::registry::{self, StreamParams};
+use actix::{Actor, Addr, Recipient};
+use crate::types::{Channel, LiveEventEnveloppe};
+use crate::candles::CandleAggregator;
+use crate::snapshots::SnapshotScheduler;
+use crate::precision::PrecisionNormalizer;
+use crate::event_stream::{ActorThread, ChannelForwarder, CallbackForwarder};
+use tokio::sync::mpsc::UnboundedReceiver;
 
 pub trait Credentials {
     /// Get an element from the credentials.
@@ -27,6 +56,23 @@ pub trait Credentials {
     fn exchange(&self) -> Exchange;
     /// Return the client name.
     fn name(&self) -> String;
+    /// Return the targeted environment, production when not set.
+    fn environment(&self) -> Result<Environment> {
+        self.get("environment").map_or(Ok(Environment::Production), |e| Environment::from_str(&e))
+    }
+    /// Return the endpoint overrides and proxy, none when not set.
+    fn connection(&self) -> ConnectionConfig {
+        ConnectionConfig {
+            rest_url: self.get("rest_url"),
+            ws_url: self.get("ws_url"),
+            proxy: self.get("proxy"),
+            failover_errors: self.get("failover_errors").and_then(|v| v.parse().ok()),
+            keep_raw: self.get("keep_raw").map_or(false, |v| v == "true"),
+            cassette: self.get("cassette"),
+            record_cassette: self.get("record_cassette").map_or(false, |v| v == "true"),
+            debug_signing: self.get("debug_signing").map_or(false, |v| v == "true"),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -36,40 +82,153 @@ impl Coinnect {
     /// Create a new CoinnectApi by providing an API key & API secret
     pub fn new<C: Credentials>(exchange: Exchange, creds: C) -> Result<Box<dyn ExchangeApi>> {
         match exchange {
+            #[cfg(feature = "bitstamp")]
             Exchange::Bitstamp => Ok(Box::new(BitstampApi::new(creds)?)),
+            #[cfg(feature = "kraken")]
             Exchange::Kraken => Ok(Box::new(KrakenApi::new(creds)?)),
+            #[cfg(feature = "poloniex")]
             Exchange::Poloniex => Ok(Box::new(PoloniexApi::new(creds)?)),
+            #[cfg(feature = "bittrex")]
             Exchange::Bittrex => Ok(Box::new(BittrexApi::new(creds)?)),
+            #[cfg(feature = "coinbase")]
+            Exchange::Gdax | Exchange::Coinbase => Ok(Box::new(CoinbaseApi::new(creds)?)),
+            #[cfg(all(feature = "gdax", not(feature = "coinbase")))]
             Exchange::Gdax => Ok(Box::new(GdaxApi::new(creds)?)),
-            Exchange::Binance => unimplemented!(),
+            #[cfg(feature = "binance")]
+            Exchange::Binance => Ok(Box::new(BinanceApi::new(creds)?)),
+            #[cfg(feature = "okx")]
+            Exchange::Okx => Ok(Box::new(OkxApi::new(creds)?)),
+            Exchange::Simulated => Ok(Box::new(SimulatedApi::new(creds)?)),
+            Exchange::Custom(name) => registry::new_api(name, &creds),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::ExchangeDisabled(exchange)),
         }
     }
 
+    /// Create a new async REST client, shared between actors and tasks without any lock
+    pub fn new_async<C: Credentials>(exchange: Exchange, creds: C) -> Result<Arc<dyn ExchangeApi>> {
+        Coinnect::new(exchange, creds).map(Arc::from)
+    }
+
+    /// Create a new streaming bot sending live events to the recipients.
+    /// Candles come from the native OHLC channel of the exchange when it has one for the
+    /// requested interval, otherwise they are aggregated locally from live trades.
+    /// Order books are sent on clock aligned ticks when the settings have a snapshot interval.
+    /// Prices and volumes are rounded to the precision of their pair, when the settings have one.
+    /// The bot is an actor : this must be called from a running actix system, `event_stream` runs
+    /// one for applications that only have a tokio runtime.
     pub async fn new_stream<C: Credentials>(exchange: Exchange, creds: Box<C>, s: ExchangeSettings, r: Vec<Recipient<LiveEventEnveloppe>>) -> Result<Box<dyn ExchangeBot>> {
-        let mut channels : HashMap<Channel, HashSet<Pair>> = HashMap::new();
-        let pair_fn = crate::utils::pair_fn(exchange);
-        if let Some(fs) = s.orderbook {
-            // Live order book pairs
-            let order_book_pairs: HashSet<Pair> = fs.symbols
-                .iter().filter(|&currency_pair|
-                pair_fn(currency_pair).is_some()).map(|&p| p).collect();
-            channels.insert(Channel::LiveFullOrderBook, order_book_pairs);
+        let creds = Box::new(StreamCreds { inner: *creds, ws_url: s.ws_url.clone(), failover_errors: s.failover_errors });
+        let mut channels = s.channels(exchange);
+        let warm_up = s.warm_up();
+        let depth = s.depth();
+        let candle_interval = s.candle_interval();
+        let delivery = s.delivery;
+        let raw_frames = s.raw_frames.clone().map(|o| o.0);
+        let native_candles = native_candles(exchange, candle_interval);
+        let mut r = r;
+        if !s.precision.is_empty() {
+            let normalizer = PrecisionNormalizer::new(s.precision.clone(), r).start();
+            r = vec![normalizer.recipient()];
+        }
+        if let Some(interval_ms) = s.snapshot_interval() {
+            let scheduler = SnapshotScheduler::new(interval_ms, r).start();
+            r = vec![scheduler.recipient()];
         }
-        if let Some(fs) = s.trades {
-            // Live trade pairs
-            let trade_pairs : HashSet<Pair> = fs.symbols
-                .iter().filter(|&currency_pair| pair_fn(&currency_pair).is_some()).map(|&p| p).collect();
-            channels.insert(Channel::LiveTrades, trade_pairs);
+        if !native_candles {
+            if let Some(candle_pairs) = channels.remove(&Channel::LiveCandles) {
+                let forward_trades = channels.contains_key(&Channel::LiveTrades);
+                channels.entry(Channel::LiveTrades).or_default().extend(candle_pairs);
+                let aggregator = CandleAggregator::new(candle_interval, forward_trades, r).start();
+                r = vec![aggregator.recipient()];
+            }
+        }
+        if !exchange.capabilities().detail_book && channels.contains_key(&Channel::LiveDetailOrderBook) {
+            return Err(Error::ChannelUnsupported(exchange, Channel::LiveDetailOrderBook));
+        }
+        if exchange != Exchange::Bittrex && channels.remove(&Channel::LiveSummary).is_some() {
+            warn!("{:?} : live summaries are not supported, ignored", exchange);
         }
         debug!("{:?}", channels);
         match exchange {
-            Exchange::Bitstamp => Ok(Box::new(BitstampStreamingApi::new_bot(creds, channels, r).await?)),
-            Exchange::Bittrex => Ok(Box::new(BittrexStreamingApi::new_bot(creds, channels, r).await?)),
-            Exchange::Binance => Ok(Box::new(BinanceStreamingApi::new_bot(creds, channels, r).await?)),
-            _ => unimplemented!()
+            #[cfg(all(feature = "bitstamp", feature = "streaming"))]
+            Exchange::Bitstamp => Ok(Box::new(BitstampStreamingApi::new_bot(creds, channels, warm_up, depth, delivery, r, raw_frames).await?)),
+            #[cfg(all(feature = "bittrex", feature = "streaming"))]
+            Exchange::Bittrex => Ok(Box::new(BittrexStreamingApi::new_bot(creds, channels, warm_up, depth, delivery, r, raw_frames).await?)),
+            #[cfg(all(feature = "binance", feature = "streaming"))]
+            Exchange::Binance => Ok(Box::new(BinanceStreamingApi::new_bot(creds, channels, warm_up, depth, candle_interval, delivery, r, raw_frames).await?)),
+            #[cfg(all(feature = "coinbase", feature = "streaming"))]
+            Exchange::Gdax | Exchange::Coinbase => Ok(Box::new(CoinbaseStreamingApi::new_bot(creds, channels, warm_up, depth, delivery, r, raw_frames).await?)),
+            #[cfg(all(feature = "okx", feature = "streaming"))]
+            Exchange::Okx => Ok(Box::new(OkxStreamingApi::new_bot(creds, channels, warm_up, depth, delivery, r, raw_frames).await?)),
+            #[cfg(all(feature = "kraken", feature = "streaming"))]
+            Exchange::Kraken => Ok(Box::new(KrakenStreamingApi::new_bot(creds, channels, candle_interval, delivery, r, raw_frames).await?)),
+            Exchange::Custom(name) => {
+                let params = StreamParams { channels, warm_up, depth, delivery, recipients: r, raw_frames };
+                registry::new_stream(name, &*creds, params).await
+            }
+            #[allow(unreachable_patterns)]
+            Exchange::Bitstamp | Exchange::Bittrex | Exchange::Binance | Exchange::Okx | Exchange::Gdax | Exchange::Coinbase | Exchange::Kraken => {
+                let _ = (creds, warm_up, depth, delivery, r, candle_interval, raw_frames);
+                Err(Error::ExchangeDisabled(exchange))
+            }
+            _ => Err(Error::StreamingUnsupported(exchange)),
         }
     }
 
+    /// Create a new streaming bot whose events are delivered through a tokio channel instead of
+    /// actix recipients. The receiver implements `futures::Stream` and can be consumed from any
+    /// tokio runtime : the bot runs on an actix system of its own, on a dedicated thread, until the
+    /// returned `ActorThread` is dropped.
+    pub async fn event_stream<C: Credentials + Send + 'static>(exchange: Exchange, creds: Box<C>, s: ExchangeSettings) -> Result<(ActorThread, UnboundedReceiver<LiveEventEnveloppe>)> {
+        let actors = ActorThread::spawn("coinnect-event-stream")?;
+        let rx = actors.run(move || async move {
+            let (addr, rx) = ChannelForwarder::start_new();
+            let bot = Coinnect::new_stream(exchange, creds, s, vec![addr.recipient()]).await?;
+            // The bot is dropped with the system
+            actix_rt::spawn(async move {
+                futures::future::pending::<()>().await;
+                drop(bot);
+            });
+            Ok::<_, Error>(rx)
+        }).await??;
+        Ok((actors, rx))
+    }
+
+    /// Create a new streaming bot that invokes `callback` for every live event. Like `new_stream`,
+    /// it must be called from a running actix system.
+    pub async fn subscribe_with<C, F>(exchange: Exchange, creds: Box<C>, s: ExchangeSettings, callback: F) -> Result<Box<dyn ExchangeBot>>
+        where C: Credentials, F: FnMut(LiveEventEnveloppe) + 'static {
+        let addr = CallbackForwarder::start_new(callback);
+        Coinnect::new_stream(exchange, creds, s, vec![addr.recipient()]).await
+    }
+
+    /// Create an `OrderGateway` answering `AddOrder` and `CancelOrder` messages, through the
+    /// websocket trading channel of the exchange when it has one (Kraken), through its REST API
+    /// otherwise. The websocket trading channel also sends the fills of the account to `recipients`.
+    pub async fn order_gateway<C: Credentials>(exchange: Exchange, creds: C, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<Addr<OrderGateway>> {
+        match exchange {
+            #[cfg(all(feature = "kraken", feature = "streaming"))]
+            Exchange::Kraken => KrakenWsTrading::new_gateway(creds, recipients).await,
+            _ => {
+                let _ = recipients;
+                Ok(OrderGateway::rest_only(Arc::from(Coinnect::new(exchange, creds)?)).start())
+            }
+        }
+    }
+
+    /// Build every account, stream and sink described in a deployment configuration file,
+    /// see `config` for its structure.
+    pub async fn from_config(path: PathBuf, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<Deployment> {
+        DeploymentConfig::from_file(path)?.deploy(recipients).await
+    }
+
+    /// Start the streaming bots and sinks described in a json or toml configuration file, without
+    /// the REST clients of the accounts. Subscriptions are changed by editing the file.
+    pub async fn streams_from_file(path: PathBuf, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<Deployment> {
+        DeploymentConfig::from_file(path)?.deploy_streams(recipients).await
+    }
+
     /// Create a new CoinnectApi from a json configuration file. This file must follow this
     /// structure:
     ///
@@ -80,24 +239,107 @@ impl Coinnect {
                          path: PathBuf)
                          -> Result<Box<dyn ExchangeApi>> {
         match exchange {
+            #[cfg(feature = "bitstamp")]
             Exchange::Bitstamp => {
                 Ok(Box::new(BitstampApi::new(BitstampCreds::new_from_file(name, path)?)?))
             }
+            #[cfg(feature = "kraken")]
             Exchange::Kraken => {
                 Ok(Box::new(KrakenApi::new(KrakenCreds::new_from_file(name, path)?)?))
             }
+            #[cfg(feature = "poloniex")]
             Exchange::Poloniex => {
                 Ok(Box::new(PoloniexApi::new(PoloniexCreds::new_from_file(name, path)?)?))
             }
+            #[cfg(feature = "bittrex")]
             Exchange::Bittrex => {
                 Ok(Box::new(BittrexApi::new(BittrexCreds::new_from_file(name, path)?)?))
             }
+            #[cfg(feature = "coinbase")]
+            Exchange::Gdax | Exchange::Coinbase => {
+                Ok(Box::new(CoinbaseApi::new(CoinbaseCreds::new_from_file(name, path)?)?))
+            }
+            #[cfg(all(feature = "gdax", not(feature = "coinbase")))]
             Exchange::Gdax => {
                 Ok(Box::new(GdaxApi::new(GdaxCreds::new_from_file(name, path)?)?))
             },
+            #[cfg(feature = "binance")]
             Exchange::Binance => {
                 Ok(Box::new(BinanceApi::new(BinanceCreds::new_from_file(name, path)?)?))
             }
+            #[cfg(feature = "okx")]
+            Exchange::Okx => {
+                Ok(Box::new(OkxApi::new(OkxCreds::new_from_file(name, path)?)?))
+            }
+            Exchange::Simulated => {
+                Ok(Box::new(SimulatedApi::new(SimulatedCreds::new_from_file(name, path)?)?))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::ExchangeDisabled(exchange)),
+        }
+    }
+}
+
+/// Credentials whose websocket endpoints are overridden by the stream settings
+struct StreamCreds<C> {
+    inner: C,
+    ws_url: Option<String>,
+    failover_errors: Option<u32>,
+}
+
+impl<C: Credentials> Credentials for StreamCreds<C> {
+    fn get(&self, cred: &str) -> Option<String> {
+        match cred {
+            "ws_url" if self.ws_url.is_some() => self.ws_url.clone(),
+            "failover_errors" if self.failover_errors.is_some() => self.failover_errors.map(|n| n.to_string()),
+            _ => self.inner.get(cred),
         }
     }
+
+    fn exchange(&self) -> Exchange {
+        self.inner.exchange()
+    }
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+}
+
+/// Whether candles of `interval_secs` come from a native OHLC channel of the exchange
+#[allow(unused_variables)]
+fn native_candles(exchange: Exchange, interval_secs: u64) -> bool {
+    match exchange {
+        #[cfg(feature = "binance")]
+        Exchange::Binance => crate::binance::models::kline_interval(interval_secs).is_some(),
+        #[cfg(all(feature = "kraken", feature = "streaming"))]
+        Exchange::Kraken => crate::kraken::streaming_api::ohlc_interval(interval_secs).is_some(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod coinnect_tests {
+    use super::*;
+
+    #[test]
+    fn stream_settings_override_the_ws_endpoints() {
+        let creds = SimulatedCreds::new("sim", "{}", "0", "0");
+        let overridden = StreamCreds { inner: creds.clone(), ws_url: Some("wss://a,wss://b".to_string()), failover_errors: Some(2) };
+        let connection = overridden.connection();
+        assert_eq!(connection.ws_url("wss://default"), "wss://a");
+        assert_eq!(connection.failover_errors, Some(2));
+        let kept = StreamCreds { inner: creds, ws_url: None, failover_errors: None };
+        assert_eq!(kept.connection().ws_url("wss://default"), "wss://default");
+    }
+
+    #[test]
+    fn async_clients_are_shared_between_tasks() {
+        let creds = SimulatedCreds::new("sim", r#"{"BTC": "1.5"}"#, "0", "0");
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let api = Coinnect::new_async(Exchange::Simulated, creds).unwrap();
+        let shared = api.clone();
+        let balances = rt.block_on(rt.spawn(async move { shared.balances().await })).unwrap().unwrap();
+        assert!(balances.contains_key(&crate::types::Currency::BTC));
+        assert!(rt.block_on(api.balances()).is_ok());
+    }
 }
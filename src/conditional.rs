@@ -0,0 +1,541 @@
+//! Client-side conditional orders, for exchanges without native stop orders.
+//! The `ConditionalOrderManager` watches live order books and trades, and sends a market or limit
+//! order through the REST API of the exchange once the trigger price of a pending order is
+//! crossed. Pending triggers can be saved to a JSON file after every change, and are loaded back
+//! when the manager starts so that a restart does not lose them.
+//!
+//! Orders are sent with `submission::submit_once` under the client id of their trigger, or once
+//! with `add_order` on exchanges without client ids. A trigger whose order may have reached the
+//! exchange is never fired again blindly : after a restart its order is looked up with
+//! `order_by_client_id`, and only definite rejections watch it again. With a `FeeModel` set for an
+//! exchange, the fees of the fired orders are estimated at their trigger price and reported by
+//! `GetTriggerFees`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::{Actor, ActorFuture, Context, ContextFutureSpawner, Handler, MessageResult, WrapFuture};
+use bigdecimal::Zero;
+
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeApi};
+use crate::helpers;
+use crate::simulated::fees::FeeModel;
+use crate::spread::bbo;
+use crate::submission::submit_once;
+use crate::types::*;
+
+/// Submissions of a fired order whose outcome is unknown, see `submit_once`
+const SUBMIT_ATTEMPTS: u32 = 3;
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A conditional order waiting for its trigger price
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trigger {
+    pub id: u64,
+    pub exchange: Exchange,
+    pub pair: Pair,
+    /// `StopLoss`, `StopLimit`, `TakeProfit` or `TrailingStop`
+    pub order_type: OrderType,
+    pub quantity: Volume,
+    /// Stop or take profit price, distance to the best price seen for a `TrailingStop`
+    pub trigger_price: Price,
+    /// Limit price of a `StopLimit`
+    pub price: Option<Price>,
+    /// Best price seen since the creation of a `TrailingStop`, the highest for a sell
+    pub extreme: Option<Price>,
+    /// UNIX timestamp in ms
+    pub created_ms: i64,
+    /// Id the order is placed with, a 32 bits integer like those of `OrderGateway`
+    #[serde(default)]
+    pub client_id: String,
+    /// Crossed, its order is being sent or may have been. Saved, so that after a restart the order
+    /// is looked up before the trigger is watched again.
+    #[serde(default)]
+    pub firing: bool,
+}
+
+impl Trigger {
+    /// Update the trigger with the market price, returns whether it is crossed
+    fn crossed(&mut self, market: &Price) -> bool {
+        let side = self.order_type.side();
+        match self.order_type {
+            OrderType::StopLoss(_) | OrderType::StopLimit(_) => match side {
+                Side::Sell => market <= &self.trigger_price,
+                Side::Buy => market >= &self.trigger_price,
+            },
+            OrderType::TakeProfit(_) => match side {
+                Side::Sell => market >= &self.trigger_price,
+                Side::Buy => market <= &self.trigger_price,
+            },
+            OrderType::TrailingStop(_) => {
+                let extreme = match (&self.extreme, side) {
+                    (Some(e), Side::Sell) if e >= market => e.clone(),
+                    (Some(e), Side::Buy) if e <= market => e.clone(),
+                    _ => market.clone(),
+                };
+                let crossed = match side {
+                    Side::Sell => market.clone() <= extreme.clone() - self.trigger_price.clone(),
+                    Side::Buy => market.clone() >= extreme.clone() + self.trigger_price.clone(),
+                };
+                self.extreme = Some(extreme);
+                crossed
+            }
+            _ => false,
+        }
+    }
+
+    /// The order sent once the trigger is crossed
+    fn order(&self) -> (OrderType, Option<Price>) {
+        match (self.order_type, self.order_type.side()) {
+            (OrderType::StopLimit(_), Side::Buy) => (OrderType::BuyLimit, self.price.clone()),
+            (OrderType::StopLimit(_), Side::Sell) => (OrderType::SellLimit, self.price.clone()),
+            (_, Side::Buy) => (OrderType::BuyMarket, None),
+            (_, Side::Sell) => (OrderType::SellMarket, None),
+        }
+    }
+
+    fn request(&self) -> OrderRequest {
+        let (order_type, price) = self.order();
+        OrderRequest::new(order_type, self.pair, &self.quantity, &price).with_client_id(&self.client_id)
+    }
+}
+
+/// Whether the exchange refused the order, so that it was certainly not placed
+fn is_rejection(e: &Error) -> bool {
+    match e.kind() {
+        Error::InsufficientFunds | Error::InsufficientOrderSize | Error::InvalidArguments | Error::InvalidFieldValue(_)
+        | Error::MissingPrice | Error::PairUnsupported | Error::OrderTypeUnsupported | Error::BadCredentials
+        | Error::PermissionDenied | Error::RateLimited(_) => true,
+        _ => false,
+    }
+}
+
+/// Place the order of a fired trigger with `submit_once`, or with a single `add_order` when the
+/// exchange does not take client ids
+async fn send(api: &dyn ExchangeApi, request: &OrderRequest, client_id: &str) -> Result<OrderInfo> {
+    match submit_once(api, request, client_id, SUBMIT_ATTEMPTS, SUBMIT_TIMEOUT).await {
+        Err(Error::Unsupported) => api.add_order(request.order_type, request.pair, request.quantity.clone(), request.price.clone()).await,
+        result => result,
+    }
+}
+
+/// The pending triggers, optionally saved to `store`
+#[derive(Debug, Default)]
+pub struct TriggerBook {
+    triggers: Vec<Trigger>,
+    next_id: u64,
+    next_client_id: i32,
+    store: Option<PathBuf>,
+}
+
+impl TriggerBook {
+    /// Load the triggers saved in `store` if the file exists
+    pub fn new(store: Option<PathBuf>) -> Result<Self> {
+        let mut triggers: Vec<Trigger> = match &store {
+            Some(path) if path.exists() => serde_json::from_str(&fs::read_to_string(path)?)?,
+            _ => vec![],
+        };
+        let next_id = triggers.iter().map(|t| t.id + 1).max().unwrap_or(1);
+        // Seconds since the epoch, so that the ids of successive books seldom overlap
+        let first_client_id = (helpers::get_unix_timestamp_ms() / 1000 % i64::from(i32::MAX)) as i32;
+        let next_client_id = triggers.iter()
+            .filter_map(|t| t.client_id.parse::<i32>().ok())
+            .map(|id| id.checked_add(1).unwrap_or(1))
+            .fold(first_client_id, i32::max);
+        let mut book = TriggerBook { triggers: vec![], next_id, next_client_id, store };
+        // Triggers saved before they had a client id
+        for t in triggers.iter_mut().filter(|t| t.client_id.is_empty()) {
+            t.client_id = book.client_id();
+        }
+        book.triggers = triggers;
+        Ok(book)
+    }
+
+    fn client_id(&mut self) -> String {
+        let id = self.next_client_id;
+        self.next_client_id = id.checked_add(1).unwrap_or(1);
+        id.to_string()
+    }
+
+    pub fn pending(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    /// Add a conditional order, returns its id
+    pub fn add(&mut self, exchange: Exchange, pair: Pair, order_type: OrderType, quantity: Volume, trigger_price: Price, price: Option<Price>) -> Result<u64> {
+        match order_type {
+            OrderType::StopLimit(_) if price.is_none() => return Err(Error::MissingPrice),
+            t if !t.is_conditional() => return Err(Error::OrderTypeUnsupported),
+            _ => (),
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let client_id = self.client_id();
+        self.triggers.push(Trigger { id, exchange, pair, order_type, quantity, trigger_price, price, extreme: None, created_ms: helpers::get_unix_timestamp_ms(), client_id, firing: false });
+        self.save()?;
+        Ok(id)
+    }
+
+    /// Remove a pending trigger, once cancelled or once its order is accepted, returns whether it
+    /// was found
+    pub fn cancel(&mut self, id: u64) -> Result<bool> {
+        let before = self.triggers.len();
+        self.triggers.retain(|t| t.id != id);
+        let found = self.triggers.len() != before;
+        if found {
+            self.save()?;
+        }
+        Ok(found)
+    }
+
+    /// Update the triggers of `pair` on `exchange` with the best bid and ask or the last trade
+    /// price, and return the crossed ones. Sells watch the bid, buys the ask. Crossed triggers stay
+    /// in the book until their order is accepted, see `cancel` and `rearm`, and are saved as firing.
+    pub fn on_prices(&mut self, exchange: Exchange, pair: Pair, bid: &Price, ask: &Price) -> Result<Vec<Trigger>> {
+        let mut fired = vec![];
+        let mut changed = false;
+        for t in self.triggers.iter_mut().filter(|t| t.exchange == exchange && t.pair == pair && !t.firing) {
+            let market = if t.order_type.side() == Side::Sell { bid } else { ask };
+            let extreme = t.extreme.clone();
+            if t.crossed(market) {
+                t.firing = true;
+                changed = true;
+                fired.push(t.clone());
+            }
+            changed |= t.extreme != extreme;
+        }
+        if changed {
+            self.save()?;
+        }
+        Ok(fired)
+    }
+
+    /// Watch a fired trigger again, once its order is known to be rejected or never received
+    pub fn rearm(&mut self, id: u64) -> Result<()> {
+        if let Some(t) = self.triggers.iter_mut().find(|t| t.id == id) {
+            t.firing = false;
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Triggers whose order may have been placed, e.g. before a restart
+    pub fn firing(&self) -> Vec<Trigger> {
+        self.triggers.iter().filter(|t| t.firing).cloned().collect()
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(path) = &self.store {
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, serde_json::to_string(&self.triggers)?)?;
+            fs::rename(tmp, path)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<u64>")]
+pub struct AddTrigger {
+    pub exchange: Exchange,
+    pub pair: Pair,
+    pub order_type: OrderType,
+    pub quantity: Volume,
+    pub trigger_price: Price,
+    pub price: Option<Price>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<bool>")]
+pub struct CancelTrigger(pub u64);
+
+#[derive(Message)]
+#[rtype(result = "Vec<Trigger>")]
+pub struct GetTriggers;
+
+/// Estimated fees of the filled triggers, by exchange and pair, in quote currency
+#[derive(Message)]
+#[rtype(result = "HashMap<(Exchange, Pair), Amount>")]
+pub struct GetTriggerFees;
+
+/// Actor firing the pending triggers with the REST API of their exchange
+pub struct ConditionalOrderManager {
+    book: TriggerBook,
+    apis: HashMap<Exchange, Arc<dyn ExchangeApi>>,
+    fee_models: HashMap<Exchange, Box<dyn FeeModel>>,
+    fees: HashMap<(Exchange, Pair), Amount>,
+}
+
+impl ConditionalOrderManager {
+    /// Manager sending orders through `apis`, pending triggers are saved to `store` if given
+    pub fn new(apis: HashMap<Exchange, Box<dyn ExchangeApi>>, store: Option<PathBuf>) -> Result<Self> {
+        let book = TriggerBook::new(store)?;
+        if !book.pending().is_empty() {
+            info!("Conditional orders : {} pending triggers loaded", book.pending().len());
+        }
+        let apis = apis.into_iter().map(|(e, api)| (e, Arc::from(api))).collect();
+        Ok(ConditionalOrderManager { book, apis, fee_models: HashMap::new(), fees: HashMap::new() })
+    }
+
+    /// Estimate the fees of the orders fired on `exchange` with `fee_model`
+    pub fn set_fee_model<F: FeeModel + 'static>(&mut self, exchange: Exchange, fee_model: F) {
+        self.fee_models.insert(exchange, Box::new(fee_model));
+    }
+
+    /// Fee of the order of `trigger` filled at `market`, or at its limit price, market orders pay
+    /// the taker rate and limit orders the maker one
+    fn record_fee(&mut self, trigger: &Trigger, market: &Price) {
+        let model = match self.fee_models.get(&trigger.exchange) {
+            Some(model) => model,
+            None => return,
+        };
+        let (order_type, price) = trigger.order();
+        let liquidity = if price.is_some() { Liquidity::Maker } else { Liquidity::Taker };
+        let price = price.unwrap_or_else(|| market.clone());
+        let fee = model.order_fee(trigger.pair, order_type.side(), &[(price, trigger.quantity.clone())], liquidity);
+        *self.fees.entry((trigger.exchange, trigger.pair)).or_insert_with(Amount::zero) += fee;
+    }
+
+    /// Send the order of a crossed trigger. The trigger is removed once the order is accepted and
+    /// watched again if it is rejected, otherwise it stays firing until its order is looked up at
+    /// the next start.
+    fn fire(&mut self, trigger: Trigger, market: Price, ctx: &mut Context<Self>) {
+        let api = match self.api(&trigger) {
+            Some(api) => api,
+            None => return,
+        };
+        let request = trigger.request();
+        let (id, client_id) = (trigger.id, trigger.client_id.clone());
+        info!("Conditional orders : trigger {} crossed, sending {:?} {} {:?} as {}", id, request.order_type, request.quantity, request.pair, client_id);
+        async move { send(api.as_ref(), &request, &client_id).await }
+            .into_actor(self)
+            .map(move |res, act, _ctx| match res {
+                Ok(info) => {
+                    info!("Conditional orders : trigger {} filled as {:?}", id, info.identifier);
+                    act.record_fee(&trigger, &market);
+                    act.remove(id);
+                }
+                Err(e) if is_rejection(&e) => {
+                    error!("Conditional orders : trigger {} order rejected, watching it again : {}", id, e);
+                    act.rearm(id);
+                }
+                Err(e) => error!("Conditional orders : trigger {} order {} may have been placed, it is looked up at the next start : {}", id, trigger.client_id, e),
+            })
+            .spawn(ctx);
+    }
+
+    /// Look up the order of a trigger that was firing when the manager stopped : the trigger is
+    /// removed if the exchange knows the order and watched again if it never received it. Fees of
+    /// the orders found are not estimated, their price is unknown.
+    fn resume(&mut self, trigger: Trigger, ctx: &mut Context<Self>) {
+        let api = match self.api(&trigger) {
+            Some(api) => api,
+            None => return,
+        };
+        let (id, pair, client_id) = (trigger.id, trigger.pair, trigger.client_id);
+        async move { api.order_by_client_id(pair, &client_id).await }
+            .into_actor(self)
+            .map(move |res, act, _ctx| match res {
+                Ok(Some(info)) => {
+                    info!("Conditional orders : trigger {} was placed as {:?}", id, info.identifier);
+                    act.remove(id);
+                }
+                Ok(None) => {
+                    info!("Conditional orders : trigger {} was never placed, watching it again", id);
+                    act.rearm(id);
+                }
+                Err(e) => error!("Conditional orders : trigger {} may have been placed and cannot be looked up, cancel it once checked : {}", id, e),
+            })
+            .spawn(ctx);
+    }
+
+    /// The api of the exchange of `trigger`, triggers of exchanges without one are dropped
+    fn api(&mut self, trigger: &Trigger) -> Option<Arc<dyn ExchangeApi>> {
+        let api = self.apis.get(&trigger.exchange).cloned();
+        if api.is_none() {
+            error!("Conditional orders : no api for {:?}, trigger {} dropped", trigger.exchange, trigger.id);
+            self.remove(trigger.id);
+        }
+        api
+    }
+
+    fn remove(&mut self, id: u64) {
+        if let Err(e) = self.book.cancel(id) {
+            error!("Conditional orders : cannot save the pending triggers : {}", e);
+        }
+    }
+
+    fn rearm(&mut self, id: u64) {
+        if let Err(e) = self.book.rearm(id) {
+            error!("Conditional orders : cannot save the pending triggers : {}", e);
+        }
+    }
+}
+
+impl Actor for ConditionalOrderManager {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        for trigger in self.book.firing() {
+            self.resume(trigger, ctx);
+        }
+    }
+}
+
+impl Handler<LiveEventEnveloppe> for ConditionalOrderManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, ctx: &mut Context<Self>) {
+        let LiveEventEnveloppe(exchange, le) = msg;
+        let (pair, bid, ask) = match le {
+            LiveEvent::LiveOrderbook(ob) => match bbo(&ob) {
+                Some((bid, ask)) => (ob.pair, bid, ask),
+                None => return,
+            },
+            LiveEvent::LiveTrade(t) => (t.pair, t.price.clone(), t.price),
+            _ => return,
+        };
+        match self.book.on_prices(exchange, pair, &bid, &ask) {
+            Ok(fired) => fired.into_iter().for_each(|t| {
+                let market = if t.order_type.side() == Side::Sell { bid.clone() } else { ask.clone() };
+                self.fire(t, market, ctx)
+            }),
+            Err(e) => error!("Conditional orders : cannot save the pending triggers : {}", e),
+        }
+    }
+}
+
+impl Handler<AddTrigger> for ConditionalOrderManager {
+    type Result = Result<u64>;
+
+    fn handle(&mut self, msg: AddTrigger, _ctx: &mut Context<Self>) -> Result<u64> {
+        if !self.apis.contains_key(&msg.exchange) {
+            return Err(Error::InvalidArguments);
+        }
+        self.book.add(msg.exchange, msg.pair, msg.order_type, msg.quantity, msg.trigger_price, msg.price)
+    }
+}
+
+impl Handler<CancelTrigger> for ConditionalOrderManager {
+    type Result = Result<bool>;
+
+    fn handle(&mut self, msg: CancelTrigger, _ctx: &mut Context<Self>) -> Result<bool> {
+        self.book.cancel(msg.0)
+    }
+}
+
+impl Handler<GetTriggers> for ConditionalOrderManager {
+    type Result = Vec<Trigger>;
+
+    fn handle(&mut self, _msg: GetTriggers, _ctx: &mut Context<Self>) -> Vec<Trigger> {
+        self.book.pending().to_vec()
+    }
+}
+
+impl Handler<GetTriggerFees> for ConditionalOrderManager {
+    type Result = MessageResult<GetTriggerFees>;
+
+    fn handle(&mut self, _msg: GetTriggerFees, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.fees.clone())
+    }
+}
+
+#[cfg(test)]
+mod conditional_tests {
+    use super::*;
+    use actix::System;
+    use async_trait::async_trait;
+    use bigdecimal::BigDecimal;
+    use crate::simulated::fees::PercentageFee;
+
+    #[test]
+    fn fire_and_persist_triggers() {
+        let path = std::env::temp_dir().join(format!("coinnect-triggers-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut book = TriggerBook::new(Some(path.clone())).unwrap();
+        let stop = book.add(Exchange::Poloniex, Pair::BTC_USD, OrderType::StopLoss(Side::Sell), BigDecimal::from(1), BigDecimal::from(95), None).unwrap();
+        let trailing = book.add(Exchange::Poloniex, Pair::BTC_USD, OrderType::TrailingStop(Side::Sell), BigDecimal::from(1), BigDecimal::from(10), None).unwrap();
+        assert!(book.add(Exchange::Poloniex, Pair::BTC_USD, OrderType::BuyMarket, BigDecimal::from(1), BigDecimal::from(1), None).is_err());
+
+        assert!(book.on_prices(Exchange::Poloniex, Pair::BTC_USD, &BigDecimal::from(110), &BigDecimal::from(111)).unwrap().is_empty());
+        // Restarting keeps the pending triggers and the best price of the trailing stop
+        let mut book = TriggerBook::new(Some(path.clone())).unwrap();
+        assert_eq!(book.pending().len(), 2);
+        let fired = book.on_prices(Exchange::Poloniex, Pair::BTC_USD, &BigDecimal::from(99), &BigDecimal::from(100)).unwrap();
+        assert_eq!(fired.iter().map(|t| t.id).collect::<Vec<_>>(), vec![trailing]);
+        assert_eq!(fired[0].order(), (OrderType::SellMarket, None));
+        // Triggers are kept until their order is accepted, and fire again after a rejection
+        let reloaded = TriggerBook::new(Some(path.clone())).unwrap();
+        assert_eq!(reloaded.pending().len(), 2);
+        assert_eq!(reloaded.firing(), fired);
+        book.cancel(trailing).unwrap();
+        let fired = book.on_prices(Exchange::Poloniex, Pair::BTC_USD, &BigDecimal::from(95), &BigDecimal::from(96)).unwrap();
+        assert_eq!(fired[0].id, stop);
+        assert!(book.on_prices(Exchange::Poloniex, Pair::BTC_USD, &BigDecimal::from(94), &BigDecimal::from(95)).unwrap().is_empty());
+        book.rearm(stop).unwrap();
+        assert_eq!(book.on_prices(Exchange::Poloniex, Pair::BTC_USD, &BigDecimal::from(94), &BigDecimal::from(95)).unwrap()[0].id, stop);
+        book.cancel(stop).unwrap();
+        assert!(TriggerBook::new(Some(path.clone())).unwrap().pending().is_empty());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn fees_of_the_fired_orders_are_estimated() {
+        let mut manager = ConditionalOrderManager::new(HashMap::new(), None).unwrap();
+        manager.set_fee_model(Exchange::Poloniex, PercentageFee::new(BigDecimal::from(0), "0.002".parse().unwrap()));
+        let mut book = TriggerBook::new(None).unwrap();
+        book.add(Exchange::Poloniex, Pair::BTC_USD, OrderType::StopLoss(Side::Sell), BigDecimal::from(2), BigDecimal::from(95), None).unwrap();
+        book.add(Exchange::Poloniex, Pair::BTC_USD, OrderType::StopLimit(Side::Sell), BigDecimal::from(1), BigDecimal::from(95), Some(BigDecimal::from(94))).unwrap();
+        for trigger in book.on_prices(Exchange::Poloniex, Pair::BTC_USD, &BigDecimal::from(95), &BigDecimal::from(96)).unwrap() {
+            manager.record_fee(&trigger, &BigDecimal::from(95));
+        }
+        // The stop limit rests in the book and pays the maker rate
+        assert_eq!(manager.fees[&(Exchange::Poloniex, Pair::BTC_USD)], "0.38".parse().unwrap());
+    }
+
+    /// Knows the orders placed with the given client ids
+    #[derive(Debug)]
+    struct Placed(Vec<String>);
+
+    #[async_trait]
+    impl ExchangeApi for Placed {
+        async fn ticker(&self, _pair: Pair) -> Result<Ticker> { Err(Error::Unsupported) }
+        async fn orderbook(&self, _pair: Pair) -> Result<Orderbook> { Err(Error::Unsupported) }
+        async fn add_order(&self, _order_type: OrderType, _pair: Pair, _quantity: Volume, _price: Option<Price>) -> Result<OrderInfo> { Err(Error::Unsupported) }
+        async fn balances(&self) -> Result<Balances> { Err(Error::Unsupported) }
+
+        async fn order_by_client_id(&self, _pair: Pair, client_id: &str) -> Result<Option<OrderInfo>> {
+            Ok(self.0.iter().find(|id| *id == client_id)
+                .map(|id| OrderInfo { timestamp: 0, identifier: vec![id.clone()], raw: None, request: None }))
+        }
+    }
+
+    #[test]
+    fn firing_triggers_are_looked_up_after_a_restart() {
+        let path = std::env::temp_dir().join(format!("coinnect-firing-triggers-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut book = TriggerBook::new(Some(path.clone())).unwrap();
+        let placed = book.add(Exchange::Poloniex, Pair::BTC_USD, OrderType::StopLoss(Side::Sell), BigDecimal::from(1), BigDecimal::from(95), None).unwrap();
+        let lost = book.add(Exchange::Poloniex, Pair::BTC_USD, OrderType::StopLoss(Side::Sell), BigDecimal::from(2), BigDecimal::from(95), None).unwrap();
+        let fired = book.on_prices(Exchange::Poloniex, Pair::BTC_USD, &BigDecimal::from(94), &BigDecimal::from(95)).unwrap();
+        assert_eq!(fired.len(), 2);
+        assert_ne!(fired[0].client_id, fired[1].client_id);
+        let placed_id = fired.iter().find(|t| t.id == placed).unwrap().client_id.clone();
+
+        let mut apis: HashMap<Exchange, Box<dyn ExchangeApi>> = HashMap::new();
+        apis.insert(Exchange::Poloniex, Box::new(Placed(vec![placed_id])));
+        let store = path.clone();
+        System::new("conditional_tests").block_on(async move {
+            let manager = ConditionalOrderManager::new(apis, Some(store)).unwrap().start();
+            tokio::time::delay_for(Duration::from_millis(20)).await;
+            // The placed order is not sent again, the lost one is watched again
+            let pending = manager.send(GetTriggers).await.unwrap();
+            assert_eq!(pending.iter().map(|t| (t.id, t.firing)).collect::<Vec<_>>(), vec![(lost, false)]);
+        });
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,703 @@
+//! Declarative deployment of accounts, streams and sinks from a single json or toml configuration
+//! file.
+//!
+//! ```json
+//! {
+//!     "accounts": {
+//!         "account_kraken": {
+//!             "exchange"   : "kraken",
+//!             "credentials": {"api_key": "123456789ABCDEF", "api_secret": "ABC&EF?abcdef"},
+//!             "limits"     : {"max_order_volume": "0.5", "allowed_pairs": ["BTC_EUR"], "close_only": false},
+//!             "rate_limit" : {"min_interval_ms": 2000},
+//!             "cache"      : {"ticker_ttl_ms": 1000, "orderbook_ttl_ms": 500},
+//!             "precision"  : {"BTC_EUR": {"tick_size": "0.1", "lot_size": "0.00000001"}}
+//!         },
+//!         "account_bitstamp": {
+//!             "exchange"   : "bitstamp",
+//!             "credentials": {"api_key": "", "api_secret": "", "customer_id": ""}
+//!         }
+//!     },
+//!     "streams": [
+//!         {
+//!             "account" : "account_bitstamp",
+//!             "settings": {"orderbook": {"symbols": ["BTC_USD"]}, "trades": {"symbols": ["BTC_USD"]}},
+//!             "sinks"   : ["Log", {"Recorder": {"dir": "data", "prefix": "bitstamp", "format": "JsonLines"}}]
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! The same deployment in toml, read from a file with the `.toml` extension :
+//!
+//! ```toml
+//! [accounts.account_bitstamp]
+//! exchange = "bitstamp"
+//! credentials = { api_key = "", api_secret = "", customer_id = "" }
+//!
+//! [[streams]]
+//! account = "account_bitstamp"
+//! settings = { orderbook = { symbols = ["BTC_USD"] }, trades = { symbols = ["BTC_USD"] } }
+//! sinks = ["Log"]
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use actix::{Actor, ActorContext, Addr, Context, Handler, Recipient};
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use futures::future;
+
+use crate::cache::{CacheSettings, CachedApi};
+use crate::coinnect::{Coinnect, Credentials};
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeApi, ExchangeCapabilities, ExchangeSettings};
+use crate::exchange_bot::{ExchangeBot, Shutdown};
+use crate::helpers::secret::Redacted;
+use crate::precision::{PreciseApi, Precisions};
+use crate::recorder::{RecorderActor, RecorderSettings};
+use crate::types::*;
+
+/// How often `Deployment::shutdown` checks whether a sink stopped
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeploymentConfig {
+    pub accounts: HashMap<String, AccountConfig>,
+    #[serde(default)]
+    pub streams: Vec<StreamConfig>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct AccountConfig {
+    #[serde(deserialize_with = "crate::exchange::DeserializeWith::deserialize_with")]
+    pub exchange: Exchange,
+    #[serde(default)]
+    pub credentials: HashMap<String, String>,
+    #[serde(default)]
+    pub limits: SafetyLimits,
+    #[serde(default)]
+    pub rate_limit: RateLimit,
+    /// Cache of the public endpoints, see `CachedApi`, no cache if missing
+    #[serde(default)]
+    pub cache: Option<CacheSettings>,
+    /// Tick and lot sizes the REST responses are rounded to, see `PreciseApi`
+    #[serde(default)]
+    pub precision: Precisions,
+}
+
+impl fmt::Debug for AccountConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AccountConfig")
+            .field("exchange", &self.exchange)
+            .field("credentials", &Redacted(&self.credentials))
+            .field("limits", &self.limits)
+            .field("rate_limit", &self.rate_limit)
+            .field("cache", &self.cache)
+            .field("precision", &self.precision)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StreamConfig {
+    /// Name of the account in `accounts`
+    pub account: String,
+    /// Name of the bot in the deployment, the account name if missing. Several streams of the
+    /// same account need distinct names.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub settings: ExchangeSettings,
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum SinkConfig {
+    /// Log every event at info level
+    Log,
+    /// Record every event to disk
+    Recorder(RecorderSettings),
+}
+
+/// Orders not satisfying the limits are rejected before reaching the exchange
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SafetyLimits {
+    /// Maximum quantity of a single order
+    pub max_order_volume: Option<BigDecimal>,
+    /// Only these pairs can be traded, all pairs if missing
+    pub allowed_pairs: Option<Vec<Pair>>,
+    /// Reject every order
+    #[serde(default)]
+    pub read_only: bool,
+    /// Start in close-only mode, see `LimitedApi::close_only_switch`
+    #[serde(default)]
+    pub close_only: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RateLimit {
+    /// Minimum delay between two requests to the exchange
+    #[serde(default)]
+    pub min_interval_ms: u64,
+}
+
+/// Credentials read from the `credentials` object of an account
+#[derive(Clone)]
+pub struct AccountCreds {
+    exchange: Exchange,
+    name: String,
+    data: HashMap<String, String>,
+}
+
+impl fmt::Debug for AccountCreds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AccountCreds")
+            .field("exchange", &self.exchange)
+            .field("name", &self.name)
+            .field("data", &Redacted(&self.data))
+            .finish()
+    }
+}
+
+impl AccountCreds {
+    pub fn new(name: &str, exchange: Exchange, data: HashMap<String, String>) -> Self {
+        AccountCreds { exchange, name: name.to_string(), data }
+    }
+}
+
+impl Credentials for AccountCreds {
+    fn get(&self, key: &str) -> Option<String> {
+        self.data.get(key).cloned()
+    }
+
+    fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+/// Runtime toggle of the close-only mode of an account, can be cloned and flipped from anywhere
+/// while the account keeps trading
+#[derive(Debug, Clone, Default)]
+pub struct CloseOnlySwitch(Arc<AtomicBool>);
+
+impl CloseOnlySwitch {
+    pub fn set(&self, close_only: bool) {
+        info!("Close-only mode {}", if close_only { "enabled" } else { "disabled" });
+        self.0.store(close_only, Ordering::SeqCst);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// An `ExchangeApi` enforcing the safety and rate limits of its account
+#[derive(Debug)]
+pub struct LimitedApi {
+    inner: Box<dyn ExchangeApi>,
+    limits: SafetyLimits,
+    rate_limit: RateLimit,
+    /// Time slot of the latest request, later than now when requests are queued
+    last_request: Mutex<Option<Instant>>,
+    close_only: CloseOnlySwitch,
+}
+
+impl LimitedApi {
+    pub fn new(inner: Box<dyn ExchangeApi>, limits: SafetyLimits, rate_limit: RateLimit) -> Self {
+        let close_only = CloseOnlySwitch::default();
+        close_only.set(limits.close_only);
+        LimitedApi { inner, limits, rate_limit, last_request: Mutex::new(None), close_only }
+    }
+
+    /// In close-only mode, only orders reducing the exposure of the account are sent : sells of
+    /// the base currency of a pair, up to the balance held. Buys are rejected.
+    pub fn close_only_switch(&self) -> CloseOnlySwitch {
+        self.close_only.clone()
+    }
+
+    async fn check_reduces_exposure(&self, order_type: OrderType, pair: Pair, quantity: &Volume) -> Result<()> {
+        if order_type.side() == Side::Buy {
+            return Err(Error::PermissionDenied);
+        }
+        let (base, _) = crate::utils::pair_currencies(&pair).ok_or(Error::PairUnsupported)?;
+        let balances = self.inner.balances().await?;
+        let held = balances.get(&base).cloned().unwrap_or_else(BigDecimal::zero);
+        if quantity > &held {
+            return Err(Error::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    fn check_order(&self, pair: Pair, quantity: &Volume) -> Result<()> {
+        if self.limits.read_only {
+            return Err(Error::PermissionDenied);
+        }
+        if let Some(pairs) = &self.limits.allowed_pairs {
+            if !pairs.contains(&pair) {
+                return Err(Error::PairUnsupported);
+            }
+        }
+        if let Some(max) = &self.limits.max_order_volume {
+            if quantity > max {
+                return Err(Error::InvalidArguments);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reserve the next request slot, then wait for it. Concurrent callers get consecutive slots.
+    async fn throttle(&self) {
+        let interval = Duration::from_millis(self.rate_limit.min_interval_ms);
+        let wait = {
+            let mut last = self.last_request.lock().unwrap();
+            let now = Instant::now();
+            let slot = match *last {
+                Some(l) if l + interval > now => l + interval,
+                _ => now,
+            };
+            *last = Some(slot);
+            slot - now
+        };
+        if wait > Duration::from_millis(0) {
+            tokio::time::delay_for(wait).await;
+        }
+    }
+}
+
+#[async_trait]
+impl ExchangeApi for LimitedApi {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
+        self.throttle().await;
+        self.inner.ticker(pair).await
+    }
+
+    async fn tickers(&self, pairs: &[Pair]) -> Result<HashMap<Pair, Ticker>> {
+        self.throttle().await;
+        self.inner.tickers(pairs).await
+    }
+
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
+        self.throttle().await;
+        self.inner.orderbook(pair).await
+    }
+
+    async fn add_order(&self,
+                 order_type: OrderType,
+                 pair: Pair,
+                 quantity: Volume,
+                 price: Option<Price>)
+                 -> Result<OrderInfo> {
+        self.check_order(pair, &quantity)?;
+        if self.close_only.is_set() {
+            self.throttle().await;
+            self.check_reduces_exposure(order_type, pair, &quantity).await?;
+        }
+        self.throttle().await;
+        self.inner.add_order(order_type, pair, quantity, price).await
+    }
+
+    async fn add_conditional_order(&self,
+                             order_type: OrderType,
+                             pair: Pair,
+                             quantity: Volume,
+                             trigger_price: Price,
+                             price: Option<Price>)
+                             -> Result<OrderInfo> {
+        self.check_order(pair, &quantity)?;
+        if self.close_only.is_set() {
+            self.throttle().await;
+            self.check_reduces_exposure(order_type, pair, &quantity).await?;
+        }
+        self.throttle().await;
+        self.inner.add_conditional_order(order_type, pair, quantity, trigger_price, price).await
+    }
+
+    async fn add_ioc_order(&self,
+                           order_type: OrderType,
+                           pair: Pair,
+                           quantity: Volume,
+                           price: Price)
+                           -> Result<Execution> {
+        self.check_order(pair, &quantity)?;
+        if self.close_only.is_set() {
+            self.throttle().await;
+            self.check_reduces_exposure(order_type, pair, &quantity).await?;
+        }
+        self.throttle().await;
+        self.inner.add_ioc_order(order_type, pair, quantity, price).await
+    }
+
+    async fn add_order_with_id(&self,
+                               order_type: OrderType,
+                               pair: Pair,
+                               quantity: Volume,
+                               price: Option<Price>,
+                               client_id: &str)
+                               -> Result<OrderInfo> {
+        self.check_order(pair, &quantity)?;
+        if self.close_only.is_set() {
+            self.throttle().await;
+            self.check_reduces_exposure(order_type, pair, &quantity).await?;
+        }
+        self.throttle().await;
+        self.inner.add_order_with_id(order_type, pair, quantity, price, client_id).await
+    }
+
+    async fn order_by_client_id(&self, pair: Pair, client_id: &str) -> Result<Option<OrderInfo>> {
+        self.throttle().await;
+        self.inner.order_by_client_id(pair, client_id).await
+    }
+
+    async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+        self.throttle().await;
+        self.inner.cancel_order(order).await
+    }
+
+    async fn cancel_all_orders(&self) -> Result<()> {
+        self.throttle().await;
+        self.inner.cancel_all_orders().await
+    }
+
+    async fn set_cancel_after(&self, timeout: Duration) -> Result<()> {
+        self.throttle().await;
+        self.inner.set_cancel_after(timeout).await
+    }
+
+    /// A new quantity is checked like the quantity of a new order
+    async fn amend_order(&self, order: &OrderInfo, new_price: Option<Price>, new_amount: Option<Volume>) -> Result<OrderInfo> {
+        let request = order.request.as_ref().ok_or_else(|| Error::MissingField("request".to_string()))?;
+        if let Some(quantity) = &new_amount {
+            self.check_order(request.pair, quantity)?;
+            if self.close_only.is_set() {
+                self.throttle().await;
+                self.check_reduces_exposure(request.order_type, request.pair, quantity).await?;
+            }
+        }
+        self.throttle().await;
+        self.inner.amend_order(order, new_price, new_amount).await
+    }
+
+    async fn fees(&self, pair: Pair) -> Result<FeeRates> {
+        self.throttle().await;
+        self.inner.fees(pair).await
+    }
+
+    async fn balances(&self) -> Result<Balances> {
+        self.throttle().await;
+        self.inner.balances().await
+    }
+
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        self.throttle().await;
+        self.inner.detailed_balances().await
+    }
+
+    async fn withdraw(&self, currency: Currency, amount: Amount, destination: &str) -> Result<WithdrawalInfo> {
+        self.throttle().await;
+        self.inner.withdraw(currency, amount, destination).await
+    }
+
+    async fn fills(&self, pair: Pair) -> Result<Vec<FillEvent>> {
+        self.throttle().await;
+        self.inner.fills(pair).await
+    }
+
+    async fn fills_page(&self, pair: Pair, cursor: Option<String>) -> Result<FillsPage> {
+        self.throttle().await;
+        self.inner.fills_page(pair, cursor).await
+    }
+
+    async fn public_trades(&self, pair: Pair, since: Option<DateTime<Utc>>) -> Result<Vec<PublicTrade>> {
+        self.throttle().await;
+        self.inner.public_trades(pair, since).await
+    }
+
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        self.throttle().await;
+        self.inner.markets().await
+    }
+
+    async fn time(&self) -> Result<i64> {
+        self.throttle().await;
+        self.inner.time().await
+    }
+
+    async fn status(&self) -> Result<ExchangeStatus> {
+        self.throttle().await;
+        self.inner.status().await
+    }
+
+    fn clock_skew(&self) -> Option<i64> {
+        self.inner.clock_skew()
+    }
+
+    fn limits(&self) -> RateLimitStatus {
+        self.inner.limits()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        self.inner.capabilities()
+    }
+}
+
+/// Sink logging every event
+pub struct LogSink;
+
+impl Actor for LogSink {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for LogSink {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        info!("{:?} : {:?}", msg.0, msg.1);
+    }
+}
+
+impl Handler<Shutdown> for LogSink {
+    type Result = ();
+
+    fn handle(&mut self, _msg: Shutdown, ctx: &mut Context<Self>) {
+        ctx.stop();
+    }
+}
+
+/// Every client, bot and sink built from a configuration file.
+/// Dropping the deployment drops every REST client and streaming bot.
+pub struct Deployment {
+    pub apis: HashMap<String, LimitedApi>,
+    pub bots: HashMap<String, Box<dyn ExchangeBot>>,
+    log_sinks: Vec<Addr<LogSink>>,
+    recorders: Vec<Addr<RecorderActor>>,
+}
+
+impl Deployment {
+    /// The REST client of an account
+    pub fn api(&self, account: &str) -> Option<&LimitedApi> {
+        self.apis.get(account)
+    }
+
+    /// The streaming bot of a stream, named after its account unless it has a `name`
+    pub fn bot(&self, account: &str) -> Option<&dyn ExchangeBot> {
+        self.bots.get(account).map(|b| b.as_ref())
+    }
+
+    /// Put an account in or out of close-only mode
+    pub fn set_close_only(&self, account: &str, close_only: bool) -> Result<()> {
+        let api = self.apis.get(account).ok_or_else(|| Error::MissingField(account.to_string()))?;
+        api.close_only_switch().set(close_only);
+        Ok(())
+    }
+
+    /// Whether every streaming bot is connected
+    pub fn is_connected(&self) -> bool {
+        self.bots.values().all(|b| b.is_connected())
+    }
+
+    /// Close the bots, then stop the sinks once the recorders flushed their files, and drop every
+    /// client and bot. Resolves when every sink is stopped.
+    pub async fn shutdown(self) {
+        info!("Shutting down {} apis, {} bots and {} sinks", self.apis.len(), self.bots.len(), self.log_sinks.len() + self.recorders.len());
+        future::join_all(self.bots.values().map(|b| b.shutdown())).await;
+        future::join_all(self.log_sinks.iter().map(stop)).await;
+        future::join_all(self.recorders.iter().map(stop)).await;
+    }
+}
+
+/// Send `Shutdown` to an actor and wait for it to be stopped
+async fn stop<A>(addr: &Addr<A>)
+    where A: Actor<Context = Context<A>> + Handler<Shutdown> {
+    if let Err(e) = addr.send(Shutdown).await {
+        trace!("Shutdown : actor already stopped : {}", e);
+    }
+    while addr.connected() {
+        tokio::time::delay_for(STOP_POLL_INTERVAL).await;
+    }
+}
+
+impl DeploymentConfig {
+    /// Names of the bots of the streams, in order, checked before anything is started so that a
+    /// bot does not replace another one
+    pub(crate) fn stream_names(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = vec![];
+        for stream in &self.streams {
+            let name = stream.name.clone().unwrap_or_else(|| stream.account.clone());
+            if names.contains(&name) {
+                return Err(Error::InvalidFieldValue(format!("streams : duplicate name {}", name)));
+            }
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    /// Read a configuration file, in toml if its extension is `.toml`, in json otherwise
+    pub fn from_file(path: PathBuf) -> Result<Self> {
+        let mut f = File::open(&path)?;
+        let mut buffer = String::new();
+        f.read_to_string(&mut buffer)?;
+        if path.extension().map_or(false, |ext| ext == "toml") {
+            Ok(toml::from_str(&buffer)?)
+        } else {
+            Ok(serde_json::from_str(&buffer)?)
+        }
+    }
+
+    /// Build every REST client, streaming bot and sink, `recipients` receive the events of every
+    /// stream in addition to the configured sinks
+    pub async fn deploy(self, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<Deployment> {
+        let mut apis = HashMap::new();
+        for (name, account) in &self.accounts {
+            let creds = AccountCreds::new(name, account.exchange, account.credentials.clone());
+            let mut api = Coinnect::new(account.exchange, creds)?;
+            if let Some(settings) = &account.cache {
+                api = Box::new(CachedApi::new(api, settings.clone()));
+            }
+            if !account.precision.is_empty() {
+                api = Box::new(PreciseApi::new(api, account.precision.clone()));
+            }
+            apis.insert(name.clone(), LimitedApi::new(api, account.limits.clone(), account.rate_limit.clone()));
+        }
+        let deployment = self.deploy_streams(recipients).await?;
+        Ok(Deployment { apis, ..deployment })
+    }
+
+    /// Build the streaming bots and sinks only, the deployment has no REST client
+    pub async fn deploy_streams(self, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<Deployment> {
+        let names = self.stream_names()?;
+        let mut bots = HashMap::new();
+        let mut log_sinks = vec![];
+        let mut recorders = vec![];
+        for (name, stream) in names.into_iter().zip(self.streams) {
+            let account = self.accounts.get(&stream.account).ok_or_else(|| Error::MissingField(stream.account.clone()))?;
+            let mut stream_recipients = recipients.clone();
+            for sink in &stream.sinks {
+                match sink {
+                    SinkConfig::Log => {
+                        let addr = LogSink.start();
+                        stream_recipients.push(addr.clone().recipient());
+                        log_sinks.push(addr);
+                    }
+                    SinkConfig::Recorder(settings) => {
+                        let addr = RecorderActor::new(settings.clone())?.start();
+                        stream_recipients.push(addr.clone().recipient());
+                        recorders.push(addr);
+                    }
+                }
+            }
+            let creds = Box::new(AccountCreds::new(&stream.account, account.exchange, account.credentials.clone()));
+            let bot = Coinnect::new_stream(account.exchange, creds, stream.settings, stream_recipients).await?;
+            bots.insert(name, bot);
+        }
+
+        Ok(Deployment { apis: HashMap::new(), bots, log_sinks, recorders })
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn parse_deployment_config() {
+        let config: DeploymentConfig = serde_json::from_str(r#"{
+            "accounts": {
+                "account_kraken": {
+                    "exchange": "kraken",
+                    "credentials": {"api_key": "key", "api_secret": "secret"},
+                    "limits": {"max_order_volume": "0.5", "allowed_pairs": ["BTC_EUR"]},
+                    "rate_limit": {"min_interval_ms": 2000}
+                }
+            },
+            "streams": [{"account": "account_kraken", "settings": {"orderbook": {"symbols": ["BTC_EUR"]}}, "sinks": ["Log"]}]
+        }"#).unwrap();
+        let account = &config.accounts["account_kraken"];
+        assert_eq!(account.exchange, Exchange::Kraken);
+        assert_eq!(account.rate_limit.min_interval_ms, 2000);
+        assert_eq!(config.streams.len(), 1);
+    }
+
+    #[test]
+    fn streams_of_an_account_need_distinct_names() {
+        let stream = |name: Option<&str>| StreamConfig { account: "account_kraken".to_string(), name: name.map(|n| n.to_string()), settings: ExchangeSettings::default(), sinks: vec![] };
+        let mut config = DeploymentConfig { accounts: HashMap::new(), streams: vec![stream(None), stream(None)] };
+        assert!(config.stream_names().is_err());
+        config.streams[1] = stream(Some("kraken_trades"));
+        assert_eq!(config.stream_names().unwrap(), vec!["account_kraken".to_string(), "kraken_trades".to_string()]);
+    }
+
+    #[test]
+    fn parse_toml_deployment_config() {
+        let config: DeploymentConfig = toml::from_str(r#"
+            [accounts.account_bitstamp]
+            exchange = "bitstamp"
+            credentials = { api_key = "key", api_secret = "secret", customer_id = "1" }
+
+            [[streams]]
+            account = "account_bitstamp"
+            settings = { orderbook = { symbols = ["BTC_USD"], depth = 10 } }
+            sinks = ["Log"]
+        "#).unwrap();
+        assert_eq!(config.accounts["account_bitstamp"].exchange, Exchange::Bitstamp);
+        assert_eq!(config.streams[0].settings.depth(), Some(10));
+    }
+
+    #[test]
+    fn close_only_rejects_buys_and_oversized_sells() {
+        let creds = crate::simulated::SimulatedCreds::new("test", r#"{"BTC": "1", "USD": "1000"}"#, "0", "0");
+        let sim = crate::simulated::SimulatedApi::new(creds).unwrap();
+        sim.set_orderbook(Orderbook {
+            timestamp: 0,
+            pair: Pair::BTC_USD,
+            asks: vec![(BigDecimal::from(101), BigDecimal::from(5))],
+            bids: vec![(BigDecimal::from(99), BigDecimal::from(5))],
+        });
+        let api = LimitedApi::new(Box::new(sim), SafetyLimits::default(), RateLimit::default());
+        api.close_only_switch().set(true);
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            assert!(api.add_order(OrderType::BuyMarket, Pair::BTC_USD, BigDecimal::from(1), None).await.is_err());
+            assert!(api.add_order(OrderType::SellMarket, Pair::BTC_USD, BigDecimal::from(2), None).await.is_err());
+            assert!(api.add_order(OrderType::SellMarket, Pair::BTC_USD, BigDecimal::from(1), None).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn shutdown_stops_the_sinks_once_recorded() {
+        let dir = std::env::temp_dir().join(format!("coinnect-shutdown-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let settings = RecorderSettings {
+            dir: dir.clone(),
+            prefix: "test".to_string(),
+            format: crate::codec::EventFormat::Csv,
+            rotation: Default::default(),
+        };
+        let line = "Bitstamp,trade,1577146143220,BTC_USD,7312.91,0.5,Buy,,";
+        actix::System::new("config_tests").block_on(async {
+            let recorder = RecorderActor::new(settings).unwrap().start();
+            let sink = LogSink.start();
+            recorder.do_send(crate::codec::EventFormat::Csv.decode(line).unwrap());
+            let deployment = Deployment { apis: HashMap::new(), bots: HashMap::new(), log_sinks: vec![sink.clone()], recorders: vec![recorder.clone()] };
+            deployment.shutdown().await;
+            assert!(!recorder.connected());
+            assert!(!sink.connected());
+        });
+        let files: Vec<PathBuf> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+        let recorded = std::fs::read_to_string(&files[0]).unwrap();
+        assert_eq!(recorded.lines().skip(1).collect::<Vec<_>>(), vec![line]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+//! Convert an amount between two currencies through the markets of an exchange.
+//! Every ticker gives two conversions : selling the base currency at the best bid, and buying it
+//! with the quote currency at the best ask. The path giving the largest amount is taken, going
+//! through at most `MAX_HOPS` markets, e.g. DOGE -> BTC -> EUR.
+
+use std::collections::HashMap;
+
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::error::*;
+use crate::types::*;
+use crate::utils;
+
+/// Markets crossed at most by a conversion
+pub const MAX_HOPS: usize = 3;
+
+/// One market crossed by a conversion
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hop {
+    pub pair: Pair,
+    pub from: Currency,
+    pub to: Currency,
+    /// Amount of `to` received for one `from`
+    pub rate: BigDecimal,
+}
+
+/// The hops of a conversion and the amount it gives
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionPath {
+    pub hops: Vec<Hop>,
+    pub amount: Amount,
+}
+
+/// Convert `amount` of `from` into `to` along the best path, fails with `PairUnsupported` if
+/// no path joins both currencies
+pub fn convert(amount: &Amount, from: Currency, to: Currency, tickers: &HashMap<Pair, Ticker>) -> Result<Amount> {
+    best_path(amount, from, to, tickers).map(|path| path.amount).ok_or(Error::PairUnsupported)
+}
+
+/// The path giving the largest amount of `to` for `amount` of `from`, `None` if there is none.
+/// Converting a currency into itself takes no hop.
+pub fn best_path(amount: &Amount, from: Currency, to: Currency, tickers: &HashMap<Pair, Ticker>) -> Option<ConversionPath> {
+    let hops = hops(tickers);
+    let mut best: Option<ConversionPath> = None;
+    let mut path = vec![];
+    search(&hops, from, to, amount.clone(), &mut path, &mut best);
+    best
+}
+
+/// Both conversions of every ticker, by currency sold
+fn hops(tickers: &HashMap<Pair, Ticker>) -> HashMap<Currency, Vec<Hop>> {
+    let mut hops: HashMap<Currency, Vec<Hop>> = HashMap::new();
+    for (pair, ticker) in tickers {
+        let (base, quote) = match utils::pair_currencies(pair) {
+            Some(currencies) => currencies,
+            None => continue,
+        };
+        if !ticker.highest_bid.is_zero() {
+            hops.entry(base).or_default().push(Hop { pair: *pair, from: base, to: quote, rate: ticker.highest_bid.clone() });
+        }
+        if !ticker.lowest_ask.is_zero() {
+            hops.entry(quote).or_default().push(Hop { pair: *pair, from: quote, to: base, rate: BigDecimal::from(1) / ticker.lowest_ask.clone() });
+        }
+    }
+    hops
+}
+
+fn search(hops: &HashMap<Currency, Vec<Hop>>, current: Currency, to: Currency, amount: Amount, path: &mut Vec<Hop>, best: &mut Option<ConversionPath>) {
+    if current == to {
+        if best.as_ref().map_or(true, |b| amount > b.amount) {
+            *best = Some(ConversionPath { hops: path.clone(), amount });
+        }
+        return;
+    }
+    if path.len() == MAX_HOPS {
+        return;
+    }
+    for hop in hops.get(&current).map(|h| h.as_slice()).unwrap_or_default() {
+        // Going back through a currency already held is never better
+        if path.iter().any(|h| h.from == hop.to) {
+            continue;
+        }
+        path.push(hop.clone());
+        search(hops, hop.to, to, amount.clone() * hop.rate.clone(), path, best);
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod conversion_tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn ticker(pair: Pair, bid: &str, ask: &str) -> Ticker {
+        Ticker {
+            timestamp: 0,
+            pair,
+            last_trade_price: BigDecimal::from_str(bid).unwrap(),
+            lowest_ask: BigDecimal::from_str(ask).unwrap(),
+            highest_bid: BigDecimal::from_str(bid).unwrap(),
+            volume: None,
+            bid_size: None,
+            ask_size: None,
+            open_24h: None,
+            high_24h: None,
+            low_24h: None,
+            percent_change: None,
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn convert_through_btc() {
+        let mut tickers = HashMap::new();
+        tickers.insert(Pair::DOGE_BTC, ticker(Pair::DOGE_BTC, "0.0000002", "0.0000003"));
+        tickers.insert(Pair::BTC_EUR, ticker(Pair::BTC_EUR, "9990", "10000"));
+        let path = best_path(&BigDecimal::from(1000), Currency::DOGE, Currency::EUR, &tickers).unwrap();
+        assert_eq!(path.hops.len(), 2);
+        assert_eq!(path.amount, BigDecimal::from_str("1.998").unwrap());
+        assert_eq!(convert(&BigDecimal::from(20000), Currency::EUR, Currency::BTC, &tickers).unwrap(), BigDecimal::from(2));
+        assert!(convert(&BigDecimal::from(1), Currency::EUR, Currency::ETH, &tickers).is_err());
+    }
+}
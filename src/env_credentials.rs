@@ -0,0 +1,77 @@
+//! Credentials read from environment variables, to keep secrets out of files in containerized
+//! deployments. The `api_key` of a Kraken account with the `COINNECT` prefix is read from
+//! `COINNECT_KRAKEN_API_KEY`.
+
+use std::collections::HashMap;
+use std::env;
+
+use crate::coinnect::Credentials;
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::helpers;
+
+/// The usual prefix of the variables
+pub const DEFAULT_PREFIX: &str = "COINNECT";
+
+#[derive(Debug, Clone)]
+pub struct EnvCredentials {
+    exchange: Exchange,
+    prefix: String,
+}
+
+impl EnvCredentials {
+    pub fn new(prefix: &str, exchange: Exchange) -> Self {
+        EnvCredentials { exchange, prefix: prefix.to_uppercase() }
+    }
+
+    /// Name of the variable holding `key`
+    pub fn var_name(&self, key: &str) -> String {
+        let exchange: String = self.exchange.into();
+        format!("{}_{}_{}", self.prefix, exchange.to_uppercase(), key.to_uppercase())
+    }
+
+    /// The value of `key`, fails if its variable is not set
+    pub fn require(&self, key: &str) -> Result<String> {
+        self.get(key).ok_or_else(|| Error::MissingField(self.var_name(key)))
+    }
+
+    /// Copy the client settings that are set, see `helpers::CLIENT_SETTINGS`
+    pub(crate) fn copy_client_settings(&self, data: &mut HashMap<String, String>) {
+        for key in helpers::CLIENT_SETTINGS.iter() {
+            if let Some(value) = self.get(key) {
+                data.insert(key.to_string(), value);
+            }
+        }
+    }
+}
+
+impl Credentials for EnvCredentials {
+    fn get(&self, key: &str) -> Option<String> {
+        env::var(self.var_name(key)).ok()
+    }
+
+    fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+
+    fn name(&self) -> String {
+        self.var_name("").trim_end_matches('_').to_lowercase()
+    }
+}
+
+#[cfg(all(test, feature = "kraken"))]
+mod env_credentials_tests {
+    use super::*;
+    use crate::kraken::KrakenCreds;
+
+    #[test]
+    fn read_from_prefixed_variables() {
+        env::set_var("CNTEST_KRAKEN_API_KEY", "key");
+        env::set_var("CNTEST_KRAKEN_API_SECRET", "secret");
+        env::set_var("CNTEST_KRAKEN_PROXY", "socks5://localhost:1080");
+        let creds = KrakenCreds::new_from_env("cntest").unwrap();
+        assert_eq!(creds.get("api_key"), Some("key".to_string()));
+        assert_eq!(creds.get("proxy"), Some("socks5://localhost:1080".to_string()));
+        assert!(KrakenCreds::new_from_env("cntest_missing").is_err());
+    }
+}
@@ -2,142 +2,198 @@
 //! Error type represents all possible errors that can occur when dealing
 //! with the generic or any dedicated-exchange API
 
+use std::error::Error as StdError;
+use std::time::Duration;
 
-use serde_json;
-use hyper;
-use data_encoding;
 use crate::exchange::Exchange;
-use signalr_rs;
+use crate::types::Channel;
 
-error_chain!{
-    types {
-        Error, ErrorKind, ResultExt, Result;
-    }
+pub type Result<T> = ::std::result::Result<T, Error>;
 
-    foreign_links {
-        Json(serde_json::Error);
-        ParseFloat(::std::num::ParseFloatError);
-        ParseString(::std::string::FromUtf8Error);
-        Hyper(hyper::Error);
-        DataDecoding(data_encoding::DecodeError);
-        Io(::std::io::Error);
-        Binance(binance::errors::Error);
-        ParseBigDecimal(bigdecimal::ParseBigDecimalError);
-    }
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
 
-    errors {
-        BigDecimalTooLarge(bd: bigdecimal::BigDecimal) {
-            description("Big decinal was too large")
-                display("{}", bd)
-        }
-        Hub(e: signalr_rs::hub::client::HubClientError) {
-            description("Hub client error")
-                display("{}", e)
-        }
+    #[error(transparent)]
+    ParseFloat(#[from] ::std::num::ParseFloatError),
 
-        BadParse {
-            description("ParsingError")
-                display("The response could not be parsed.")
-        }
+    #[error(transparent)]
+    ParseString(#[from] ::std::string::FromUtf8Error),
 
-        ServiceUnavailable(reason: String) {
-            description("ServiceUnavailable")
-                display("Host could not be reached: {}.", reason)
-        }
+    #[cfg(feature = "native")]
+    #[error(transparent)]
+    Hyper(#[from] hyper::Error),
 
-        BadCredentials {
-            description("BadCredentials")
-                display("The informations provided do not allow authentication.")
-        }
+    /// Error of the browser fetch api, see `public::FetchTransport`
+    #[cfg(target_arch = "wasm32")]
+    #[error(transparent)]
+    Fetch(#[from] reqwest::Error),
 
-        RateLimitExceeded {
-            description("RateLimitExceeded")
-                display("API call rate limit exceeded.")
-        }
+    #[error(transparent)]
+    DataDecoding(#[from] data_encoding::DecodeError),
 
-        PairUnsupported {
-            description("PairUnsupported")
-                display("This pair is not supported.")
-        }
+    #[error(transparent)]
+    Io(#[from] ::std::io::Error),
 
-        InvalidArguments {
-            description("InvalidArguments")
-                display("Arguments passed do not conform to the protocol.")
-        }
+    #[cfg(feature = "binance")]
+    #[error("{0}")]
+    Binance(#[from] binance::errors::Error),
 
-        ExchangeSpecificError(reason: String) {
-            description("ExchangeSpecificError")
-                display("Exchange error: {}", reason)
-        }
+    #[error(transparent)]
+    ParseBigDecimal(#[from] bigdecimal::ParseBigDecimalError),
 
-        TlsError {
-            description("TlsError")
-                display("Fail to initialize TLS client.")
-        }
+    #[error(transparent)]
+    MessagePackEncode(#[from] rmp_serde::encode::Error),
 
-        InvalidFieldFormat(field: String) {
-            description("InvalidFieldFormat")
-                display("Fail to parse field \"{}\".", field)
-        }
+    #[error(transparent)]
+    MessagePackDecode(#[from] rmp_serde::decode::Error),
 
-        InvalidFieldValue(field: String) {
-            description("InvalidFieldValue")
-                display("Invalid value for field \"{}\".", field)
-        }
+    #[error(transparent)]
+    Cbor(#[from] serde_cbor::Error),
 
-        MissingField(field: String) {
-            description("MissingFiled")
-                display("Missing field \"{}\".", field)
-        }
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
 
-        InsufficientFunds {
-            description("InsufficientFunds")
-                display("You haven't enough founds.")
-        }
+    /// Free form error message
+    #[error("{0}")]
+    Msg(String),
 
-        InsufficientOrderSize {
-            description("InsufficientOrderSize")
-                display("Your order is not big enough.")
-        }
+    /// An error of a given kind caused by a lower level error
+    #[error("{kind}")]
+    Chained {
+        kind: Box<Error>,
+        #[source]
+        source: Box<dyn StdError + Send + 'static>,
+    },
 
-        MissingPrice{
-            description("MissingPrice")
-                display("No price specified.")
-        }
+    #[error("{0}")]
+    BigDecimalTooLarge(bigdecimal::BigDecimal),
 
-        InvalidConfigType(expected: Exchange, find: Exchange){
-            description("InvalidConfigType")
-                display("Invalid config: \nExpected: {:?}\nFind: {:?}", expected, find)
-        }
+    #[cfg(feature = "bittrex")]
+    #[error("{0}")]
+    Hub(signalr_rs::hub::client::HubClientError),
 
-        InvalidExchange(value: String) {
-            description("InvalidExchange")
-                display("Invalid exchange: \"{}\"", value)
-        }
+    #[error("The response could not be parsed.")]
+    BadParse,
 
-        InvalidNonce {
-            description("InvalidNonce")
-                display("Invalid nonce")
-        }
+    #[error("Host could not be reached: {0}.")]
+    ServiceUnavailable(String),
 
-        PermissionDenied {
-            description("PermissionDenied")
-                display("The operation cannot be done with the provided credentials")
-        }
+    #[error("The informations provided do not allow authentication.")]
+    BadCredentials,
 
-        WsError(reason: String) {
-            description("WsError")
-                display("Unable to connect to websocket {}", reason)
-        }
+    #[error("API call rate limit exceeded{}.", .0.map(|d| format!(", retry after {:?}", d)).unwrap_or_default())]
+    RateLimited(Option<Duration>),
 
-        BackoffConnectionTimeout(e: String) {
-            description("Connection timeout despite retries and backoff")
-                display("Unable to connect, last error : {}", e)
-        }
+    #[error("The order does not exist or is already closed.")]
+    OrderNotFound,
+
+    #[error("This pair is not supported.")]
+    PairUnsupported,
+
+    #[error("Arguments passed do not conform to the protocol.")]
+    InvalidArguments,
+
+    #[error("Exchange error: {0}")]
+    ExchangeSpecificError(String),
+
+    #[error("Fail to initialize TLS client.")]
+    TlsError,
+
+    #[error("Fail to parse field \"{0}\".")]
+    InvalidFieldFormat(String),
+
+    #[error("Invalid value for field \"{0}\".")]
+    InvalidFieldValue(String),
+
+    #[error("Missing field \"{0}\".")]
+    MissingField(String),
 
-        ChannelCanceled(e: String) {
-            description("Channel was dropped before sender")
-                display("Unable to send into channel : {}", e)
+    #[error("You haven't enough founds.")]
+    InsufficientFunds,
+
+    #[error("Your order is not big enough.")]
+    InsufficientOrderSize,
+
+    #[error("No price specified.")]
+    MissingPrice,
+
+    #[error("Invalid config: \nExpected: {0:?}\nFind: {1:?}")]
+    InvalidConfigType(Exchange, Exchange),
+
+    #[error("Invalid exchange: \"{0}\"")]
+    InvalidExchange(String),
+
+    #[error("{0:?} is not enabled, see the cargo features of coinnect")]
+    ExchangeDisabled(Exchange),
+
+    #[error("Invalid nonce")]
+    InvalidNonce,
+
+    #[error("This operation is not supported by the exchange.")]
+    Unsupported,
+
+    #[error("This order type is not supported by the exchange.")]
+    OrderTypeUnsupported,
+
+    #[error("{0:?} has no streaming API.")]
+    StreamingUnsupported(Exchange),
+
+    #[error("{0:?} does not stream the {1:?} channel.")]
+    ChannelUnsupported(Exchange, Channel),
+
+    #[error("The operation cannot be done with the provided credentials")]
+    PermissionDenied,
+
+    #[error("Unable to connect to websocket {0}")]
+    WsError(String),
+
+    #[error("Unable to connect, last error : {0}")]
+    BackoffConnectionTimeout(String),
+
+    #[error("Unable to send into channel : {0}")]
+    ChannelCanceled(String),
+
+    #[error("No recorded response for \"{0}\".")]
+    FixtureNotFound(String),
+
+    #[error("Message queue error : {0}")]
+    Queue(String),
+
+    #[error("Database error : {0}")]
+    Database(String),
+}
+
+impl Error {
+    /// The kind of a chained error, or the error itself
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::Chained { kind, .. } => kind.kind(),
+            e => e,
         }
     }
 }
+
+impl From<&str> for Error {
+    fn from(s: &str) -> Self {
+        Error::Msg(s.to_string())
+    }
+}
+
+impl From<String> for Error {
+    fn from(s: String) -> Self {
+        Error::Msg(s)
+    }
+}
+
+/// Attach an error kind to the errors of a lower level
+pub trait ResultExt<T> {
+    fn with_context<F>(self, kind: F) -> Result<T> where F: FnOnce() -> Error;
+}
+
+impl<T, E: StdError + Send + 'static> ResultExt<T> for ::std::result::Result<T, E> {
+    fn with_context<F>(self, kind: F) -> Result<T> where F: FnOnce() -> Error {
+        self.map_err(|e| Error::Chained { kind: Box::new(kind()), source: Box::new(e) })
+    }
+}
@@ -0,0 +1,187 @@
+//! Forward live events into a tokio channel so that they can be consumed as a `futures::Stream`,
+//! or into a closure, without having to define an actix actor. `EncodedForwarder` sends the
+//! events already encoded in a `WireFormat`.
+//!
+//! The forwarders are actors : they must be started on a running actix `System`, like the bots
+//! sending them events. `ActorThread` runs a system on a dedicated thread for applications that
+//! only have a tokio runtime, see `Coinnect::event_stream`.
+
+use std::future::Future;
+use std::sync::mpsc as std_mpsc;
+use std::thread::{self, JoinHandle};
+
+use actix::{Actor, Addr, Context, Handler, System};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+
+use crate::codec::WireFormat;
+use crate::error::*;
+use crate::types::LiveEventEnveloppe;
+
+/// An actix system running on a dedicated thread, so that actors can be started from any thread
+/// or tokio runtime. The system and its actors are stopped when it is dropped.
+pub struct ActorThread {
+    system: System,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ActorThread {
+    pub fn spawn(name: &str) -> Result<ActorThread> {
+        let name = name.to_string();
+        let (tx, rx) = std_mpsc::channel();
+        let thread = thread::Builder::new().name(name.clone()).spawn(move || {
+            let runner = System::new(name);
+            let _ = tx.send(System::current());
+            if let Err(e) = runner.run() {
+                error!("Actor thread stopped : {}", e);
+            }
+        })?;
+        let system = rx.recv().map_err(|e| Error::ChannelCanceled(e.to_string()))?;
+        Ok(ActorThread { system, thread: Some(thread) })
+    }
+
+    /// Run the future returned by `f` on the system, e.g. to start actors, and return its output.
+    /// The future itself stays on the thread of the system, so it does not need to be `Send`.
+    pub async fn run<F, Fut, T>(&self, f: F) -> Result<T>
+        where F: FnOnce() -> Fut + Send + 'static, Fut: Future<Output = T> + 'static, T: Send + 'static {
+        let (tx, rx) = oneshot::channel();
+        self.system.arbiter().exec_fn(move || {
+            actix_rt::spawn(async move {
+                let _ = tx.send(f().await);
+            })
+        });
+        rx.await.map_err(|e| Error::ChannelCanceled(e.to_string()))
+    }
+}
+
+impl Drop for ActorThread {
+    fn drop(&mut self) {
+        self.system.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Actor that pushes every `LiveEventEnveloppe` it receives into an unbounded channel.
+pub struct ChannelForwarder {
+    tx: UnboundedSender<LiveEventEnveloppe>,
+}
+
+impl ChannelForwarder {
+    /// Start a forwarder on the current actix system, returns its address and the receiving end of
+    /// the channel
+    pub fn start_new() -> (Addr<ChannelForwarder>, UnboundedReceiver<LiveEventEnveloppe>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (ChannelForwarder { tx }.start(), rx)
+    }
+}
+
+impl Actor for ChannelForwarder {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for ChannelForwarder {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        if let Err(e) = self.tx.send(msg) {
+            trace!("Event stream receiver dropped : {}", e);
+        }
+    }
+}
+
+/// Actor that invokes a callback for every `LiveEventEnveloppe` it receives.
+pub struct CallbackForwarder<F> where F: FnMut(LiveEventEnveloppe) + 'static {
+    callback: F,
+}
+
+impl<F> CallbackForwarder<F> where F: FnMut(LiveEventEnveloppe) + 'static {
+    pub fn start_new(callback: F) -> Addr<CallbackForwarder<F>> {
+        CallbackForwarder { callback }.start()
+    }
+}
+
+impl<F> Actor for CallbackForwarder<F> where F: FnMut(LiveEventEnveloppe) + 'static {
+    type Context = Context<Self>;
+}
+
+impl<F> Handler<LiveEventEnveloppe> for CallbackForwarder<F> where F: FnMut(LiveEventEnveloppe) + 'static {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        (self.callback)(msg)
+    }
+}
+
+/// Actor that encodes every `LiveEventEnveloppe` it receives in the format negotiated with a
+/// client and pushes the bytes into an unbounded channel.
+pub struct EncodedForwarder {
+    format: WireFormat,
+    tx: UnboundedSender<Vec<u8>>,
+}
+
+impl EncodedForwarder {
+    /// Start a forwarder, returns its address and the receiving end of the channel
+    pub fn start_new(format: WireFormat) -> (Addr<EncodedForwarder>, UnboundedReceiver<Vec<u8>>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (EncodedForwarder { format, tx }.start(), rx)
+    }
+}
+
+impl Actor for EncodedForwarder {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for EncodedForwarder {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        match self.format.encode(&msg) {
+            Ok(bytes) => {
+                if let Err(e) = self.tx.send(bytes) {
+                    trace!("Event stream receiver dropped : {}", e);
+                }
+            }
+            Err(e) => error!("Unable to encode {:?} as {:?} : {}", msg, self.format, e),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing", feature = "bitstamp"))]
+mod event_stream_tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use serde_json::json;
+
+    use super::*;
+    use crate::coinnect::Coinnect;
+    use crate::exchange::{Exchange, ExchangeSettings};
+    use crate::testing::MockExchange;
+    use crate::types::{LiveEvent, Pair};
+
+    #[tokio::test]
+    async fn events_are_consumed_from_a_tokio_runtime() {
+        let server = ActorThread::spawn("event_stream_tests").unwrap();
+        let mock = server.run(|| async { MockExchange::start(Exchange::Bitstamp) }).await.unwrap().unwrap();
+        let settings = ExchangeSettings::builder().trades(vec![Pair::BTC_USD]).build().unwrap();
+        let (_bot, mut events) = Coinnect::event_stream(Exchange::Bitstamp, Box::new(mock.credentials()), settings).await.unwrap();
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+        mock.push(json!({
+            "event": "trade", "channel": "live_trades_btcusd",
+            "data": {"microtimestamp": "1577146143220559", "amount": 0.5, "buy_order_id": 2, "sell_order_id": 1, "amount_str": "0.5",
+                     "price_str": "7312.91", "timestamp": "1577146143", "price": 7312.91, "type": 0, "id": 102177815}
+        }));
+        let trade = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(event) = events.next().await {
+                if let LiveEvent::LiveTrade(trade) = event.1 {
+                    return Some(trade);
+                }
+            }
+            None
+        }).await.unwrap().unwrap();
+        assert_eq!(trade.pair, Pair::BTC_USD);
+        mock.stop().await;
+    }
+}
@@ -1,25 +1,59 @@
 //! This module contains Exchange enum.
 
-use std::fmt::Debug;
-use std::convert::Into;
+use std::convert::{Into, TryFrom};
 use std::str::FromStr;
+use std::sync::RwLock;
 
 use crate::error::*;
 use crate::types::*;
 use futures::{Future};
-use async_trait::async_trait;
+use bigdecimal::BigDecimal;
 use serde::{Deserializer, Deserialize};
 use serde::de;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "native")]
+use std::fmt::Debug;
+#[cfg(feature = "native")]
+use std::sync::Arc;
+#[cfg(feature = "native")]
+use std::time::Duration;
+#[cfg(feature = "native")]
+use crate::helpers::delivery::DeliveryPolicy;
+#[cfg(feature = "native")]
+use crate::helpers::failover::Endpoints;
+#[cfg(feature = "native")]
+use crate::helpers::proxy::{self, HttpClient, Proxy};
+#[cfg(feature = "native")]
+use crate::helpers::vcr::{Cassette, CassetteMode};
+#[cfg(feature = "native")]
+use crate::precision::{PairPrecision, Precisions};
+#[cfg(feature = "native")]
+use async_trait::async_trait;
+#[cfg(feature = "native")]
+use bigdecimal::Zero;
+#[cfg(feature = "native")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "native")]
+use actix::Recipient;
+#[cfg(feature = "native")]
+use crate::exchange_bot::{RawFrameEvent, RawFrameObserver};
 
 #[derive(Debug, PartialEq, Clone, Copy, Eq, Hash, Deserialize, Serialize)]
-#[serde(from = "String")]
+#[serde(try_from = "String", into = "String")]
 pub enum Exchange {
     Bitstamp,
     Kraken,
     Poloniex,
     Bittrex,
+    /// Coinbase Pro, whose API was retired. With the `coinbase` feature its clients and bots are
+    /// those of `Coinbase`.
     Gdax,
+    Coinbase,
     Binance,
+    Okx,
+    Simulated,
+    /// An exchange implemented outside of this crate, see `registry`
+    Custom(&'static str),
 }
 
 pub trait DeserializeWith: Sized {
@@ -44,14 +78,100 @@ impl Into<String> for Exchange {
             Exchange::Poloniex => "Poloniex".to_string(),
             Exchange::Bittrex => "Bittrex".to_string(),
             Exchange::Gdax => "Gdax".to_string(),
+            Exchange::Coinbase => "Coinbase".to_string(),
             Exchange::Binance => "Binance".to_string(),
+            Exchange::Okx => "Okx".to_string(),
+            Exchange::Simulated => "Simulated".to_string(),
+            Exchange::Custom(name) => name.to_string(),
         }
     }
 }
 
-impl From<String> for Exchange {
-    fn from(s: String) -> Self {
-        Self::from_str(s.as_ref()).unwrap()
+impl Exchange {
+    /// Fee rates of the lowest volume tier, Gdax ones are Coinbase ones like its `capabilities`
+    pub fn default_fees(self) -> FeeRates {
+        let (maker, taker) = match self {
+            Exchange::Bitstamp => ("0.005", "0.005"),
+            Exchange::Kraken => ("0.0016", "0.0026"),
+            Exchange::Poloniex => ("0.0009", "0.0009"),
+            Exchange::Bittrex => ("0.0035", "0.0035"),
+            Exchange::Gdax if cfg!(feature = "coinbase") => return Exchange::Coinbase.default_fees(),
+            Exchange::Gdax => ("0.005", "0.005"),
+            Exchange::Coinbase => ("0.006", "0.008"),
+            Exchange::Binance => ("0.001", "0.001"),
+            Exchange::Okx => ("0.0008", "0.001"),
+            Exchange::Simulated => ("0", "0"),
+            Exchange::Custom(name) => return crate::registry::fees(name),
+        };
+        FeeRates::new(BigDecimal::from_str(maker).unwrap(), BigDecimal::from_str(taker).unwrap())
+    }
+
+    /// Whether `ExchangeApi::withdraw` takes the withdrawal fee from the withdrawn amount rather
+    /// than on top of it
+    pub fn withdrawal_fee_included(self) -> bool {
+        self == Exchange::Kraken
+    }
+
+    /// What the clients of this crate support on the exchange, streaming requires the `streaming`
+    /// feature
+    pub fn capabilities(self) -> ExchangeCapabilities {
+        let streaming = cfg!(feature = "streaming");
+        let (margin, stop_orders, websocket_book, ohlc, withdrawal) = match self {
+            Exchange::Bitstamp => (false, true, streaming, false, false),
+            Exchange::Kraken => (true, true, false, true, true),
+            Exchange::Poloniex => (true, false, false, true, true),
+            Exchange::Bittrex => (false, false, streaming, false, true),
+            Exchange::Gdax if cfg!(feature = "coinbase") => return Exchange::Coinbase.capabilities(),
+            Exchange::Gdax => (false, true, false, false, true),
+            Exchange::Coinbase => (false, false, streaming, false, false),
+            Exchange::Binance => (false, false, streaming, streaming, false),
+            Exchange::Okx => (false, false, streaming, false, false),
+            Exchange::Simulated => (false, false, false, false, true),
+            Exchange::Custom(name) => return crate::registry::capabilities(name),
+        };
+        let detail_book = self == Exchange::Bitstamp && streaming;
+        ExchangeCapabilities { margin, stop_orders, websocket_book, detail_book, ohlc, withdrawal }
+    }
+
+    /// Pairs that can be traded and streamed on the exchange : those listed at the last
+    /// `refresh_pairs`, every pair the crate has a name for until then. The simulated and custom
+    /// exchanges accept pairs without naming them, and list none.
+    pub fn supported_pairs(self) -> Vec<Pair> {
+        match LISTED_PAIRS.read().unwrap().get(&self) {
+            Some(listed) => listed.iter().copied().collect(),
+            None => crate::utils::supported_pairs(self),
+        }
+    }
+
+    /// Whether `pair` can be traded and streamed on the exchange, see `supported_pairs`
+    pub fn supports(self, pair: Pair) -> bool {
+        match LISTED_PAIRS.read().unwrap().get(&self) {
+            Some(listed) => listed.contains(&pair),
+            None => crate::utils::pair_fn(self)(&pair).is_some(),
+        }
+    }
+
+    /// Restrict the supported pairs to the markets currently listed by the exchange, as returned
+    /// by `api`, so that delisted markets are rejected at startup
+    #[cfg(feature = "native")]
+    pub async fn refresh_pairs(self, api: &dyn ExchangeApi) -> Result<Vec<Pair>> {
+        let markets = api.markets().await?;
+        LISTED_PAIRS.write().unwrap().insert(self, markets.iter().copied().collect());
+        Ok(markets)
+    }
+}
+
+lazy_static! {
+    /// Markets listed by the exchanges at their last `refresh_pairs`
+    static ref LISTED_PAIRS: RwLock<HashMap<Exchange, HashSet<Pair>>> = RwLock::new(HashMap::new());
+}
+
+/// Unknown names are an `InvalidExchange` error, e.g. when deserializing
+impl TryFrom<String> for Exchange {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        Self::from_str(s.as_ref())
     }
 }
 
@@ -65,21 +185,187 @@ impl FromStr for Exchange {
             "poloniex" => Ok(Exchange::Poloniex),
             "bittrex" => Ok(Exchange::Bittrex),
             "gdax" => Ok(Exchange::Gdax),
+            "coinbase" => Ok(Exchange::Coinbase),
             "binance" => Ok(Exchange::Binance),
-            _ => Err(ErrorKind::InvalidExchange(input.to_string()).into()),
+            "okx" => Ok(Exchange::Okx),
+            "simulated" => Ok(Exchange::Simulated),
+            _ => crate::registry::custom_exchange(input).ok_or_else(|| Error::InvalidExchange(input.to_string())),
+        }
+    }
+}
+
+/// The environment an exchange client targets, production unless stated otherwise in the
+/// `environment` credential
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash, Deserialize)]
+pub enum Environment {
+    Production,
+    /// The exchange test environment, with its own accounts and fake funds
+    Sandbox,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::Production
+    }
+}
+
+impl FromStr for Environment {
+    type Err = Error;
+
+    fn from_str(input: &str) -> ::std::result::Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "production" => Ok(Environment::Production),
+            "sandbox" => Ok(Environment::Sandbox),
+            _ => Err(Error::InvalidFieldValue("environment".to_string())),
         }
     }
 }
 
+impl Environment {
+    /// Fail when the sandbox is requested for an exchange that has none
+    pub fn production_only(self, exchange: Exchange) -> Result<()> {
+        match self {
+            Environment::Production => Ok(()),
+            Environment::Sandbox => Err(Error::ExchangeSpecificError(format!("no sandbox environment for {:?}", exchange))),
+        }
+    }
+}
+
+/// Endpoint overrides and proxy of an exchange client, for mocks, regional clusters or
+/// corporate networks. Read from the `rest_url`, `ws_url`, `proxy`, `failover_errors`, `cassette`,
+/// `record_cassette` and `debug_signing` credentials.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ConnectionConfig {
+    /// Root of the REST api, replaces the default one of the environment.
+    /// Backup mirrors can follow the primary root, separated by commas.
+    pub rest_url: Option<String>,
+    /// Websocket endpoint of the live feeds, with backups like `rest_url`
+    pub ws_url: Option<String>,
+    /// `http://host:port` or `socks5://host:port`
+    pub proxy: Option<String>,
+    /// Consecutive connection errors before switching to a backup endpoint
+    pub failover_errors: Option<u32>,
+    /// Keep the payload of the exchange in the `raw` field of typed responses
+    pub keep_raw: bool,
+    /// Fixture file of the REST responses, replayed instead of reaching the exchange
+    pub cassette: Option<String>,
+    /// Record the REST responses to `cassette` instead of replaying them
+    pub record_cassette: bool,
+    /// Log the strings signed by the private clients and the status of their responses, see
+    /// `helpers::signing`
+    pub debug_signing: bool,
+}
+
+#[cfg(feature = "native")]
+impl ConnectionConfig {
+    pub fn proxy(&self) -> Result<Option<Proxy>> {
+        self.proxy.as_ref().map(|p| Proxy::from_str(p)).transpose()
+    }
+
+    /// The primary REST root, `default` unless overridden
+    pub fn rest_url(&self, default: &str) -> String {
+        split_urls(&self.rest_url, default).remove(0)
+    }
+
+    /// The primary websocket endpoint, `default` unless overridden
+    pub fn ws_url(&self, default: &str) -> String {
+        split_urls(&self.ws_url, default).remove(0)
+    }
+
+    /// The REST roots with their failover policy
+    pub fn rest_endpoints(&self, exchange: Exchange, default: &str) -> Endpoints {
+        self.endpoints(exchange, split_urls(&self.rest_url, default))
+    }
+
+    /// The websocket endpoints with their failover policy
+    pub fn ws_endpoints(&self, exchange: Exchange, default: &str) -> Endpoints {
+        self.endpoints(exchange, split_urls(&self.ws_url, default))
+    }
+
+    fn endpoints(&self, exchange: Exchange, urls: Vec<String>) -> Endpoints {
+        let endpoints = Endpoints::new(exchange, urls);
+        match self.failover_errors {
+            Some(max_errors) => endpoints.with_max_errors(max_errors),
+            None => endpoints,
+        }
+    }
+
+    /// A REST client going through the proxy if any, recording or replaying its responses when
+    /// a cassette is set
+    pub fn http_client(&self) -> Result<HttpClient> {
+        let client = proxy::new_http_client(self.proxy()?)?;
+        match &self.cassette {
+            Some(path) => {
+                let mode = if self.record_cassette { CassetteMode::Record } else { CassetteMode::Replay };
+                Ok(client.with_cassette(Arc::new(Cassette::open(path, mode)?)))
+            }
+            None => Ok(client),
+        }
+    }
+
+    /// Fail for clients that cannot use a custom REST root or a proxy
+    pub(crate) fn unsupported(&self, exchange: Exchange, rest_url: bool, proxy: bool) -> Result<()> {
+        if (rest_url && self.rest_url.is_some()) || (proxy && self.proxy.is_some()) {
+            return Err(Error::ExchangeSpecificError(format!("endpoint overrides are not supported for {:?}", exchange)));
+        }
+        Ok(())
+    }
+}
+
+/// The comma separated urls of a setting, `default` if it is not set
+#[cfg(feature = "native")]
+fn split_urls(setting: &Option<String>, default: &str) -> Vec<String> {
+    let urls: Vec<String> = setting.iter()
+        .flat_map(|s| s.split(','))
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty())
+        .collect();
+    if urls.is_empty() { vec![default.to_string()] } else { urls }
+}
+
 pub type FResult<T> = dyn Future<Output = Result<T>>;
 
+/// What an exchange supports, so that generic code can check before calling instead of handling
+/// `Error::Unsupported` or `Error::OrderTypeUnsupported`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct ExchangeCapabilities {
+    /// Leveraged orders, through the exchange specific API
+    pub margin: bool,
+    /// `add_conditional_order`
+    pub stop_orders: bool,
+    /// Live order books streamed by `Coinnect::new_stream`
+    pub websocket_book: bool,
+    /// Order level books streamed for `Channel::LiveDetailOrderBook`
+    pub detail_book: bool,
+    /// Native candles, from a REST endpoint or a websocket channel, rather than aggregated trades
+    pub ohlc: bool,
+    /// `withdraw`
+    pub withdrawal: bool,
+}
+
+/// REST API of an exchange, as returned by `Coinnect::new` and `Coinnect::new_async`. Every call is
+/// async and can be awaited from an actix actor or any tokio task, without spawning blocking
+/// threads. Calls take `&self`, so a client can be shared between tasks and threads behind an `Arc`
+/// without any lock.
+#[cfg(feature = "native")]
 #[async_trait]
-pub trait ExchangeApi: Debug {
+pub trait ExchangeApi: Debug + Send + Sync {
     /// Return a Ticker for the Pair specified.
-    async fn ticker(&mut self, pair: Pair) -> Result<Ticker>;
+    async fn ticker(&self, pair: Pair) -> Result<Ticker>;
+
+    /// Return the Tickers of several pairs, in one request when the exchange allows it.
+    /// The default implementation queries each pair sequentially.
+    async fn tickers(&self, pairs: &[Pair]) -> Result<HashMap<Pair, Ticker>> {
+        let mut tickers = HashMap::new();
+        for pair in pairs {
+            tickers.insert(*pair, self.ticker(*pair).await?);
+        }
+        Ok(tickers)
+    }
 
     /// Return an Orderbook for the specified Pair.
-    async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook>;
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook>;
 
     /// Place an order directly to the exchange.
     /// Quantity is in quote currency. So if you want to buy 1 Bitcoin for X€ (pair BTC_EUR),
@@ -89,25 +375,548 @@ pub trait ExchangeApi: Debug {
     ///
     /// A good practice is to store the return type (OrderInfo) somewhere since it can later be used
     /// to modify or cancel the order.
-    async fn add_order(&mut self,
+    ///
+    /// Conditional order types are rejected with `Error::OrderTypeUnsupported`, they are placed
+    /// with `add_conditional_order`.
+    async fn add_order(&self,
                  order_type: OrderType,
                  pair: Pair,
                  quantity: Volume,
                  price: Option<Price>)
                  -> Result<OrderInfo>;
 
+    /// Add a `StopLoss`, `StopLimit`, `TakeProfit` or `TrailingStop` order.
+    /// `trigger_price` is the stop or take profit price, or the distance to the market for a
+    /// trailing stop. `price` is the limit price of a `StopLimit`.
+    /// Exchanges without native support for `order_type` return `Error::OrderTypeUnsupported`.
+    async fn add_conditional_order(&self,
+                             _order_type: OrderType,
+                             _pair: Pair,
+                             _quantity: Volume,
+                             _trigger_price: Price,
+                             _price: Option<Price>)
+                             -> Result<OrderInfo> {
+        Err(Error::OrderTypeUnsupported)
+    }
+
+    /// Place a limit order that is filled at once for what the book offers at `price` or better,
+    /// the rest is canceled, and return the quantity filled so that the next leg of a multi-leg
+    /// order is sized on it. Once the order is accepted no error is returned, the filled quantity
+    /// is `None` when it could not be known.
+    /// Exchanges without immediate or cancel orders place a limit order then cancel it, the order
+    /// may rest on the book meanwhile, and the filled quantity is read from `fills` once settled.
+    /// Those without `fills` return `Error::Unsupported` before placing anything, all the exchanges
+    /// with `fills` can cancel orders.
+    async fn add_ioc_order(&self,
+                           order_type: OrderType,
+                           pair: Pair,
+                           quantity: Volume,
+                           price: Price)
+                           -> Result<Execution> {
+        self.fills(pair).await?;
+        let order = self.add_order(order_type, pair, quantity.clone(), Some(price)).await?;
+        let canceled = match self.cancel_order(&order).await {
+            Ok(()) => true,
+            Err(e) => {
+                // Fully filled orders cannot be canceled anymore
+                debug!("{:?} : could not cancel order {:?} : {}", pair, order.identifier, e);
+                false
+            }
+        };
+        let filled = settled_fill(self, pair, &order).await
+            // An order that could not be canceled may still be on the book unless fully filled
+            .filter(|filled| canceled || filled >= &quantity);
+        Ok(Execution { order, filled })
+    }
+
+    /// Place a limit or market order tagged with `client_id`, an id chosen by the caller before
+    /// sending it, so that an order whose submission timed out can be found back with
+    /// `order_by_client_id`, see `submission::submit_once`. Exchanges restricting the format of the
+    /// ids reject the others with `Error::InvalidFieldValue`, e.g. Kraken only takes 32 bits integers.
+    async fn add_order_with_id(&self,
+                               _order_type: OrderType,
+                               _pair: Pair,
+                               _quantity: Volume,
+                               _price: Option<Price>,
+                               _client_id: &str)
+                               -> Result<OrderInfo> {
+        Err(Error::Unsupported)
+    }
+
+    /// The order of `pair` placed with `client_id`, open or recently closed, `None` if the exchange
+    /// never received it
+    async fn order_by_client_id(&self, _pair: Pair, _client_id: &str) -> Result<Option<OrderInfo>> {
+        Err(Error::Unsupported)
+    }
+
+    /// Cancel an order placed by `add_order` or `add_conditional_order`
+    async fn cancel_order(&self, _order: &OrderInfo) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    /// Cancel every open order of the account
+    async fn cancel_all_orders(&self) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    /// Arm the dead man's switch of the exchange : every open order is cancelled unless the timer
+    /// is set again within `timeout`, a zero timeout disarms it. Exchanges without a timer return
+    /// `Error::Unsupported`, `watchdog::CancelWatchdog` keeps one on the client side.
+    async fn set_cancel_after(&self, _timeout: Duration) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+
+    /// Change the price and/or the quantity of a live order, the others are kept.
+    /// Exchanges without native amendment cancel the order then place it again with the new
+    /// values, the order loses its priority and stays canceled if the new one is rejected.
+    async fn amend_order(&self, order: &OrderInfo, new_price: Option<Price>, new_amount: Option<Volume>) -> Result<OrderInfo> {
+        let request = order.request.as_ref().ok_or_else(|| Error::MissingField("request".to_string()))?;
+        self.cancel_order(order).await?;
+        let amended = request.amended(new_price, new_amount);
+        match amended.trigger_price {
+            Some(trigger_price) => self.add_conditional_order(amended.order_type, amended.pair, amended.quantity, trigger_price, amended.price).await,
+            None => self.add_order(amended.order_type, amended.pair, amended.quantity, amended.price).await,
+        }
+    }
+
     /// Retrieve the current amounts of all the currencies that the account holds
     /// The amounts returned are available (not used to open an order)
-    async fn balances(&mut self) -> Result<Balances>;
+    async fn balances(&self) -> Result<Balances>;
+
+    /// Check the credentials with a cheap authenticated call, fails with the error of the exchange
+    /// e.g. `BadCredentials` for an invalid signature. The default implementation fetches the
+    /// balances.
+    async fn verify_credentials(&self) -> Result<()> {
+        self.balances().await.map(|_| ())
+    }
+
+    /// Retrieve the total, available and reserved amounts of all the currencies that the account
+    /// holds. Exchanges reporting a single amount give it as available, with nothing reserved.
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        let balances = self.balances().await?;
+        Ok(balances.into_iter().map(|(c, amount)| (c, Balance::new(amount, BigDecimal::zero()))).collect())
+    }
+
+    /// Maker and taker fee rates of the account for `pair`, from the fee endpoint of the exchange
+    /// when it has one, from its base fee schedule otherwise
+    async fn fees(&self, _pair: Pair) -> Result<FeeRates> {
+        Err(Error::Unsupported)
+    }
+
+    /// Withdraw `amount` of `currency` to `destination`, an address or, on Kraken, the name of a
+    /// withdrawal key set up on the account. Most exchanges charge the withdrawal fee on top of
+    /// `amount`, Kraken takes it from `amount`.
+    async fn withdraw(&self, _currency: Currency, _amount: Amount, _destination: &str) -> Result<WithdrawalInfo> {
+        Err(Error::Unsupported)
+    }
+
+    /// Recent executions of our orders on `pair`, as returned by the trade history of the exchange
+    async fn fills(&self, pair: Pair) -> Result<Vec<FillEvent>> {
+        let mut fills = self.fills_page(pair, None).await?.fills;
+        fills.sort_by_key(|f| f.event_ms);
+        Ok(fills)
+    }
+
+    /// The page of the trade history of `pair` at `cursor`, the most recent one if `None`.
+    /// Pages can be empty while older ones are not, `history::TradeHistoryPager` walks them all.
+    async fn fills_page(&self, _pair: Pair, _cursor: Option<String>) -> Result<FillsPage> {
+        Err(Error::Unsupported)
+    }
+
+    /// Recent trades of the market of `pair`, oldest first, only those executed after `since` if
+    /// given. Exchanges always returning their latest trades are filtered on `since` afterwards.
+    async fn public_trades(&self, _pair: Pair, _since: Option<DateTime<Utc>>) -> Result<Vec<PublicTrade>> {
+        Err(Error::Unsupported)
+    }
+
+    /// Pairs currently listed by the exchange, from its markets endpoint. Listed markets without a
+    /// `Pair` are left out.
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        Err(Error::Unsupported)
+    }
+
+    /// Current time of the exchange as a UNIX timestamp in ms. The skew of the local clock is
+    /// estimated along the way, and corrects the timestamps of signed requests when it is too large.
+    async fn time(&self) -> Result<i64> {
+        Err(Error::Unsupported)
+    }
+
+    /// Whether the exchange is trading or in maintenance, with the round trip of the request, so
+    /// that trading can be paused during maintenance windows
+    async fn status(&self) -> Result<ExchangeStatus> {
+        Err(Error::Unsupported)
+    }
+
+    /// Difference in ms between the clock of the exchange and the local clock estimated by the last
+    /// call to `time`, positive when the exchange is ahead
+    fn clock_skew(&self) -> Option<i64> {
+        None
+    }
+
+    /// Request budget last reported by the exchange in its response headers, e.g. to slow down
+    /// polling before being rate limited. Empty for exchanges that report none.
+    fn limits(&self) -> RateLimitStatus {
+        RateLimitStatus::default()
+    }
+
+    /// What the client supports, nothing unless it says otherwise
+    fn capabilities(&self) -> ExchangeCapabilities {
+        ExchangeCapabilities::default()
+    }
+}
+
+/// Time given to an exchange to report the fills of an order that was just executed or canceled
+#[cfg(feature = "native")]
+pub const SETTLEMENT_DELAY: Duration = Duration::from_millis(500);
+
+/// `filled_quantity` once the fills of the order are settled, `None` if they could not be read
+#[cfg(feature = "native")]
+pub async fn settled_fill<A: ExchangeApi + ?Sized>(api: &A, pair: Pair, order: &OrderInfo) -> Option<Volume> {
+    tokio::time::delay_for(SETTLEMENT_DELAY).await;
+    match filled_quantity(api, pair, order).await {
+        Ok(filled) => Some(filled),
+        Err(e) => {
+            warn!("{:?} : fills of order {:?} unknown : {}", pair, order.identifier, e);
+            None
+        }
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+/// Quantity executed for `order`, from the recent fills of `pair`
+#[cfg(feature = "native")]
+pub async fn filled_quantity<A: ExchangeApi + ?Sized>(api: &A, pair: Pair, order: &OrderInfo) -> Result<Volume> {
+    let fills = api.fills(pair).await?;
+    Ok(fills.into_iter()
+        .filter(|f| order.identifier.contains(&f.order_id))
+        .fold(BigDecimal::zero(), |filled, f| filled + f.amount))
+}
+
+#[cfg(feature = "native")]
+#[derive(Clone, Debug, Default, Deserialize)]
 pub struct FeedSettings {
-    pub symbols: Vec<Pair>
+    pub symbols: Vec<Pair>,
+    /// Only used for order books, see `WarmUp`
+    #[serde(default)]
+    pub warm_up: WarmUp,
+    /// Only used for candles, length of a candle in seconds, one minute if missing
+    pub interval_secs: Option<u64>,
+    /// Only used for order books, levels kept on each side of the book
+    #[serde(default)]
+    pub depth: Option<i8>,
+    /// Only used for order books, send the books every `snapshot_interval_ms` on clock aligned
+    /// ticks instead of on every change, see `SnapshotScheduler`
+    #[serde(default)]
+    pub snapshot_interval_ms: Option<u64>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+/// Channels and pairs to stream, built with `ExchangeSettings::builder()` :
+///
+/// ```
+/// use coinnect_rt::exchange::{Exchange, ExchangeSettings};
+/// use coinnect_rt::types::Pair;
+///
+/// let settings = ExchangeSettings::builder()
+///     .exchange(Exchange::Bitstamp)
+///     .orderbook(vec![Pair::BTC_USD])
+///     .trades(vec![Pair::ETH_USD])
+///     .depth(10)
+///     .build()
+///     .unwrap();
+/// assert_eq!(settings.depth(), Some(10));
+/// ```
+#[cfg(feature = "native")]
+#[derive(Clone, Debug, Default, Deserialize)]
 pub struct ExchangeSettings {
     pub orderbook: Option<FeedSettings>,
     pub trades: Option<FeedSettings>,
+    pub candles: Option<FeedSettings>,
+    /// 24 hours market summaries, only streamed by Bittrex
+    pub summaries: Option<FeedSettings>,
+    /// What to do with the events recipients cannot take yet
+    #[serde(default)]
+    pub delivery: DeliveryPolicy,
+    /// Tick and lot sizes the prices and volumes of the events are rounded to, see `precision`
+    #[serde(default)]
+    pub precision: Precisions,
+    /// Websocket endpoint overriding the `ws_url` of the credentials, backups separated by commas
+    #[serde(default)]
+    pub ws_url: Option<String>,
+    /// Consecutive errors before failing over to the next endpoint, overrides the credentials
+    #[serde(default)]
+    pub failover_errors: Option<u32>,
+    /// Recipient of the websocket frames as received, from the first one
+    #[serde(skip)]
+    pub raw_frames: Option<RawFrameObserver>,
+}
+
+#[cfg(feature = "native")]
+impl ExchangeSettings {
+    pub fn builder() -> ExchangeSettingsBuilder {
+        ExchangeSettingsBuilder::default()
+    }
+
+    /// Warm-up condition applied to every live order book
+    pub fn warm_up(&self) -> WarmUp {
+        self.orderbook.as_ref().map(|fs| fs.warm_up).unwrap_or_default()
+    }
+
+    /// Length of the live candles in seconds
+    pub fn candle_interval(&self) -> u64 {
+        self.candles.as_ref().and_then(|fs| fs.interval_secs).unwrap_or(60)
+    }
+
+    /// Levels kept on each side of the live order books, the exchange default if not set
+    pub fn depth(&self) -> Option<i8> {
+        self.orderbook.as_ref().and_then(|fs| fs.depth)
+    }
+
+    /// Interval in ms of the order book snapshots, books are sent on every change if not set
+    pub fn snapshot_interval(&self) -> Option<u64> {
+        self.orderbook.as_ref().and_then(|fs| fs.snapshot_interval_ms)
+    }
+
+    /// Pairs to subscribe to for each channel, pairs unsupported by the exchange are ignored
+    pub fn channels(&self, exchange: Exchange) -> HashMap<Channel, HashSet<Pair>> {
+        let mut channels : HashMap<Channel, HashSet<Pair>> = HashMap::new();
+        if let Some(fs) = &self.orderbook {
+            // Live order book pairs
+            let order_book_pairs: HashSet<Pair> = fs.symbols
+                .iter().filter(|&&currency_pair| exchange.supports(currency_pair)).map(|&p| p).collect();
+            channels.insert(Channel::LiveFullOrderBook, order_book_pairs);
+        }
+        if let Some(fs) = &self.trades {
+            // Live trade pairs
+            let trade_pairs : HashSet<Pair> = fs.symbols
+                .iter().filter(|&&currency_pair| exchange.supports(currency_pair)).map(|&p| p).collect();
+            channels.insert(Channel::LiveTrades, trade_pairs);
+        }
+        if let Some(fs) = &self.candles {
+            let candle_pairs : HashSet<Pair> = fs.symbols
+                .iter().filter(|&&currency_pair| exchange.supports(currency_pair)).map(|&p| p).collect();
+            channels.insert(Channel::LiveCandles, candle_pairs);
+        }
+        if let Some(fs) = &self.summaries {
+            let summary_pairs : HashSet<Pair> = fs.symbols
+                .iter().filter(|&&currency_pair| exchange.supports(currency_pair)).map(|&p| p).collect();
+            channels.insert(Channel::LiveSummary, summary_pairs);
+        }
+        channels
+    }
+}
+
+/// Fluent construction of `ExchangeSettings`, see `ExchangeSettings::builder`
+#[cfg(feature = "native")]
+#[derive(Clone, Debug, Default)]
+pub struct ExchangeSettingsBuilder {
+    exchange: Option<Exchange>,
+    settings: ExchangeSettings,
+}
+
+#[cfg(feature = "native")]
+impl ExchangeSettingsBuilder {
+    /// Check on `build` that every pair is listed by `exchange`
+    pub fn exchange(mut self, exchange: Exchange) -> Self {
+        self.exchange = Some(exchange);
+        self
+    }
+
+    /// Stream the order books of `pairs`
+    pub fn orderbook<I: IntoIterator<Item = Pair>>(mut self, pairs: I) -> Self {
+        Self::feed(&mut self.settings.orderbook).symbols.extend(pairs);
+        self
+    }
+
+    /// Stream the trades of `pairs`
+    pub fn trades<I: IntoIterator<Item = Pair>>(mut self, pairs: I) -> Self {
+        Self::feed(&mut self.settings.trades).symbols.extend(pairs);
+        self
+    }
+
+    /// Stream the candles of `pairs`
+    pub fn candles<I: IntoIterator<Item = Pair>>(mut self, pairs: I) -> Self {
+        Self::feed(&mut self.settings.candles).symbols.extend(pairs);
+        self
+    }
+
+    /// Stream the 24 hours market summaries of `pairs`, only streamed by Bittrex
+    pub fn summaries<I: IntoIterator<Item = Pair>>(mut self, pairs: I) -> Self {
+        Self::feed(&mut self.settings.summaries).symbols.extend(pairs);
+        self
+    }
+
+    /// Levels kept on each side of the order books
+    pub fn depth(mut self, depth: i8) -> Self {
+        Self::feed(&mut self.settings.orderbook).depth = Some(depth);
+        self
+    }
+
+    /// Warm-up condition of the order books, see `WarmUp`
+    pub fn warm_up(mut self, warm_up: WarmUp) -> Self {
+        Self::feed(&mut self.settings.orderbook).warm_up = warm_up;
+        self
+    }
+
+    /// Send the order books every `interval_ms` instead of on every change
+    pub fn snapshot_interval(mut self, interval_ms: u64) -> Self {
+        Self::feed(&mut self.settings.orderbook).snapshot_interval_ms = Some(interval_ms);
+        self
+    }
+
+    /// Length of the candles in seconds
+    pub fn candle_interval(mut self, interval_secs: u64) -> Self {
+        Self::feed(&mut self.settings.candles).interval_secs = Some(interval_secs);
+        self
+    }
+
+    /// Delivery of the events to slow recipients, see `DeliveryPolicy`
+    pub fn delivery(mut self, policy: DeliveryPolicy) -> Self {
+        self.settings.delivery = policy;
+        self
+    }
+
+    /// Round the prices and volumes of the events of `pair`, see `PairPrecision`
+    pub fn precision(mut self, pair: Pair, precision: PairPrecision) -> Self {
+        self.settings.precision.insert(pair, precision);
+        self
+    }
+
+    /// Connect to `url`, backups separated by commas, see `ConnectionConfig`
+    pub fn ws_url(mut self, url: &str) -> Self {
+        self.settings.ws_url = Some(url.to_string());
+        self
+    }
+
+    /// Fail over to the next endpoint after `errors` consecutive errors
+    pub fn failover_errors(mut self, errors: u32) -> Self {
+        self.settings.failover_errors = Some(errors);
+        self
+    }
+
+    /// Send the websocket frames to `recipient` as they are received, see `RawFrameObserver`
+    pub fn raw_frames(mut self, recipient: Recipient<RawFrameEvent>) -> Self {
+        self.settings.raw_frames = Some(RawFrameObserver(recipient));
+        self
+    }
+
+    fn feed(feed: &mut Option<FeedSettings>) -> &mut FeedSettings {
+        feed.get_or_insert_with(FeedSettings::default)
+    }
+
+    /// The settings, fails if the depth is not positive or if a pair is not listed by the exchange
+    pub fn build(self) -> Result<ExchangeSettings> {
+        if self.settings.depth().map_or(false, |depth| depth <= 0) {
+            return Err(Error::InvalidFieldValue("depth".to_string()));
+        }
+        if self.settings.snapshot_interval() == Some(0) {
+            return Err(Error::InvalidFieldValue("snapshot_interval_ms".to_string()));
+        }
+        let zero = BigDecimal::zero();
+        for p in self.settings.precision.values() {
+            if p.tick_size.as_ref().map_or(false, |tick| tick <= &zero) {
+                return Err(Error::InvalidFieldValue("tick_size".to_string()));
+            }
+            if p.lot_size.as_ref().map_or(false, |lot| lot <= &zero) {
+                return Err(Error::InvalidFieldValue("lot_size".to_string()));
+            }
+        }
+        if let Some(exchange) = self.exchange {
+            let feeds = vec![&self.settings.orderbook, &self.settings.trades, &self.settings.candles, &self.settings.summaries];
+            let unsupported = feeds.into_iter().flatten()
+                .flat_map(|fs| fs.symbols.iter())
+                .find(|pair| !exchange.supports(**pair));
+            if let Some(pair) = unsupported {
+                warn!("{:?} : pair {:?} is not listed", exchange, pair);
+                return Err(Error::PairUnsupported);
+            }
+        }
+        Ok(self.settings)
+    }
+}
+
+#[cfg(all(test, feature = "native"))]
+mod exchange_tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records the orders placed and cancelled
+    #[derive(Debug, Default)]
+    struct Orders(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl ExchangeApi for Orders {
+        async fn ticker(&self, _pair: Pair) -> Result<Ticker> { Err(Error::Unsupported) }
+        async fn orderbook(&self, _pair: Pair) -> Result<Orderbook> { Err(Error::Unsupported) }
+        async fn balances(&self) -> Result<Balances> { Err(Error::Unsupported) }
+
+        async fn add_order(&self, order_type: OrderType, pair: Pair, quantity: Volume, price: Option<Price>) -> Result<OrderInfo> {
+            let mut orders = self.0.lock().unwrap();
+            orders.push(format!("add {} at {:?}", quantity, price.as_ref().map(|p| p.to_string())));
+            Ok(OrderInfo {
+                timestamp: 0,
+                identifier: vec![orders.len().to_string()],
+                raw: None,
+                request: Some(OrderRequest::new(order_type, pair, &quantity, &price)),
+            })
+        }
+
+        async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+            self.0.lock().unwrap().push(format!("cancel {}", order.identifier[0]));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn unknown_exchange_names_are_deserialization_errors() {
+        assert_eq!(serde_json::from_str::<Exchange>("\"kraken\"").unwrap(), Exchange::Kraken);
+        assert!(serde_json::from_str::<Exchange>("\"nosuchvenue\"").is_err());
+        assert!(Exchange::try_from("nosuchvenue".to_string()).is_err());
+    }
+
+    #[cfg(feature = "coinbase")]
+    #[test]
+    fn gdax_is_described_as_coinbase() {
+        assert_eq!(Exchange::Gdax.default_fees(), Exchange::Coinbase.default_fees());
+        assert_eq!(Exchange::Gdax.capabilities(), Exchange::Coinbase.capabilities());
+    }
+
+    #[test]
+    fn amending_without_native_support_cancels_and_replaces() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let api = Orders::default();
+        let order = rt.block_on(api.add_order(OrderType::BuyLimit, Pair::BTC_USD, BigDecimal::from(2), Some(BigDecimal::from(100)))).unwrap();
+        let amended = rt.block_on(api.amend_order(&order, Some(BigDecimal::from(101)), None)).unwrap();
+        assert_eq!(*api.0.lock().unwrap(), vec!["add 2 at Some(\"100\")", "cancel 1", "add 2 at Some(\"101\")"]);
+        assert_eq!(amended.identifier, vec!["3".to_string()]);
+    }
+
+    /// Lists the given markets
+    #[derive(Debug)]
+    struct Markets(Vec<Pair>);
+
+    #[async_trait]
+    impl ExchangeApi for Markets {
+        async fn ticker(&self, _pair: Pair) -> Result<Ticker> { Err(Error::Unsupported) }
+        async fn orderbook(&self, _pair: Pair) -> Result<Orderbook> { Err(Error::Unsupported) }
+        async fn add_order(&self, _order_type: OrderType, _pair: Pair, _quantity: Volume, _price: Option<Price>) -> Result<OrderInfo> { Err(Error::Unsupported) }
+        async fn balances(&self) -> Result<Balances> { Err(Error::Unsupported) }
+        async fn markets(&self) -> Result<Vec<Pair>> { Ok(self.0.clone()) }
+    }
+
+    #[test]
+    fn refreshed_pairs_restrict_the_settings() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        // An unregistered exchange accepts any pair until it is refreshed
+        let exchange = Exchange::Custom("listed_markets");
+        assert!(exchange.supports(Pair::ETH_BTC));
+        assert!(exchange.supported_pairs().is_empty());
+
+        let listed = rt.block_on(exchange.refresh_pairs(&Markets(vec![Pair::BTC_USD]))).unwrap();
+        assert_eq!(listed, vec![Pair::BTC_USD]);
+        assert_eq!(exchange.supported_pairs(), vec![Pair::BTC_USD]);
+        assert!(exchange.supports(Pair::BTC_USD));
+        assert!(!exchange.supports(Pair::ETH_BTC));
+
+        assert!(ExchangeSettings::builder().exchange(exchange).orderbook(vec![Pair::BTC_USD]).build().is_ok());
+        assert!(ExchangeSettings::builder().exchange(exchange).orderbook(vec![Pair::BTC_USD]).trades(vec![Pair::ETH_BTC]).build().is_err());
+    }
 }
@@ -1,47 +1,162 @@
-use actix::{Context, io::SinkWrite, Actor, Handler, StreamHandler, AsyncContext, ActorContext, Addr, ActorFuture, WrapFuture, ContextFutureSpawner, Supervisor};
+use actix::{Context, Actor, Handler, Recipient, ResponseFuture};
+#[cfg(feature = "streaming")]
+use actix::{io::SinkWrite, Addr, StreamHandler, AsyncContext, ActorContext, ActorFuture, WrapFuture, ContextFutureSpawner, Supervisor};
+#[cfg(feature = "streaming")]
 use awc::{error::WsProtocolError, ws::{Codec, Frame, Message}, BoxedSocket};
+#[cfg(feature = "streaming")]
 use actix_codec::{Framed};
+#[cfg(feature = "streaming")]
 use std::time::{Duration, Instant};
 use bytes::Bytes;
+#[cfg(feature = "streaming")]
+use futures::channel::oneshot;
+#[cfg(feature = "streaming")]
 use futures::stream::{SplitSink, StreamExt};
+#[cfg(feature = "streaming")]
 use crate::helpers;
+#[cfg(feature = "streaming")]
+use crate::helpers::delivery::FLUSH_INTERVAL;
+use crate::helpers::failover::Endpoints;
+#[cfg(feature = "streaming")]
+use crate::helpers::proxy::Proxy;
 use crate::error::*;
+#[cfg(feature = "streaming")]
+use crate::metrics;
+#[cfg(feature = "streaming")]
 use backoff::backoff::Backoff;
+#[cfg(feature = "streaming")]
 use backoff::ExponentialBackoff;
+#[cfg(feature = "streaming")]
 use async_std::task;
+use futures::future::{self, LocalBoxFuture};
+use std::fmt;
 use std::marker::PhantomData;
 use std::pin::Pin;
 use futures::task::Poll;
+#[cfg(feature = "streaming")]
 use async_trait::async_trait;
+use crate::exchange::{Exchange, ExchangeApi, ExchangeCapabilities};
+use crate::types::{Pair, Orderbook, LiveTrade, OrderInfo, OrderType, Price, Volume};
+use std::sync::Arc;
 
+#[cfg(feature = "streaming")]
 pub struct DefaultWsActor {
     inner: SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>,
     handler: Box<dyn WsHandler>,
     hb: Instant,
     conn_backoff: ExponentialBackoff,
-    pub url: String,
-    pub name: String
+    endpoints: Arc<Endpoints>,
+    pub name: String,
+    proxy: Option<Proxy>,
+    raw_observers: Vec<(Exchange, Recipient<RawFrameEvent>)>,
+    /// Stopped by `Shutdown`, the supervisor must not reconnect it
+    shut_down: bool,
 }
 
+#[cfg(feature = "streaming")]
 #[async_trait]
 pub trait WsHandler {
     /// Handle incoming messages
     fn handle_in(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, msg: Bytes);
     fn handle_started(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>);
     async fn handle_async(&mut self) {}
+    /// Drop the state built from the previous connection, called before reconnecting.
+    /// `handle_started` then subscribes again and the state is rebuilt from fresh snapshots.
+    fn handle_reconnect(&mut self) {}
+    /// Send the events waiting for a full mailbox, see `Delivery::flush`
+    fn flush_deliveries(&mut self) {}
+    /// The current state of `pair`, `None` if the handler does not track it
+    fn snapshot(&self, _pair: Pair) -> Option<Snapshot> { None }
+    /// Send an order over the websocket, `reply` gets the outcome once the exchange answers.
+    /// Handlers without a trading channel reply `Error::Unsupported`.
+    fn add_order(&mut self, _w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, _order: AddOrder, reply: oneshot::Sender<Result<OrderInfo>>) {
+        let _ = reply.send(Err(Error::Unsupported));
+    }
+    /// Cancel an order over the websocket, `reply` gets the outcome once the exchange answers
+    fn cancel_order(&mut self, _w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, _order: CancelOrder, reply: oneshot::Sender<Result<()>>) {
+        let _ = reply.send(Err(Error::Unsupported));
+    }
+}
+
+/// Current state of a pair as tracked by a streaming bot
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// The aggregated order book, `None` until it is warm
+    pub orderbook: Option<Orderbook>,
+    /// The latest trade, if trades are tracked
+    pub last_trade: Option<LiveTrade>,
 }
 
+/// Query the current `Snapshot` of a pair, answered immediately from the bot state
+#[derive(Message)]
+#[rtype(result = "Option<Snapshot>")]
+pub struct GetSnapshot(pub Pair);
+
+/// Place an order, through the websocket trading channel when the exchange has one
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Result<OrderInfo>")]
+pub struct AddOrder {
+    pub order_type: OrderType,
+    pub pair: Pair,
+    pub quantity: Volume,
+    pub price: Option<Price>,
+    /// See `ExchangeApi::add_order_with_id`, set by `OrderGateway` when missing
+    pub client_id: Option<String>,
+}
+
+/// Cancel an order by its exchange identifier
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Result<()>")]
+pub struct CancelOrder(pub String);
+
+/// A websocket frame as received from the exchange, before any parsing
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct RawFrameEvent {
+    pub exchange: Exchange,
+    /// Name of the websocket connection the frame was read from, e.g. `BitstampStream`
+    pub channel: String,
+    pub payload: Bytes,
+}
+
+/// Recipient of the raw frames of a bot, set in its `ExchangeSettings` so that the frames received
+/// while it connects, e.g. subscription acks and first snapshots, are observed too
+#[derive(Clone)]
+pub struct RawFrameObserver(pub Recipient<RawFrameEvent>);
+
+impl fmt::Debug for RawFrameObserver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RawFrameObserver")
+    }
+}
+
+/// Send every frame received by a websocket actor to the recipient, see `ExchangeBot::observe_raw_frames`
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ObserveRawFrames(pub Exchange, pub Recipient<RawFrameEvent>);
+
+/// Stop an actor for good, after it flushed what it buffers, see `ExchangeBot::shutdown`
+#[derive(Message, Debug, Clone, Copy)]
+#[rtype(result = "()")]
+pub struct Shutdown;
+
+#[cfg(feature = "streaming")]
 #[derive(Message)]
 #[rtype(result = "()")]
 struct ClientCommand(String);
 
+#[cfg(feature = "streaming")]
 impl Actor for DefaultWsActor
 {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Context<Self>) {
+        if self.shut_down {
+            return;
+        }
         // start heartbeats otherwise server will disconnect after 10 seconds
-        self.hb(ctx)
+        self.hb(ctx);
+        ctx.run_interval(FLUSH_INTERVAL, |act, _ctx| act.handler.flush_deliveries());
     }
 
     fn stopped(&mut self, _: &mut Context<Self>) {
@@ -49,10 +164,16 @@ impl Actor for DefaultWsActor
     }
 }
 
+#[cfg(feature = "streaming")]
 impl actix::Supervised for DefaultWsActor {
     fn restarting(&mut self, ctx: &mut <Self as Actor>::Context) {
-        let url = self.url.clone();
-        let client1 = helpers::new_ws_client(url.clone());
+        if self.shut_down {
+            return;
+        }
+        metrics::ws_reconnect(&self.name);
+        self.handler.handle_reconnect();
+        let url = self.endpoints.current().to_string();
+        let client1 = helpers::new_ws_client(url.clone(), self.proxy.clone());
         client1
             .into_actor(self)
             .map(move |res, act, ctx| match res {
@@ -60,10 +181,12 @@ impl actix::Supervised for DefaultWsActor {
                     let (sink, stream) = client.split();
                     DefaultWsActor::add_stream(stream, ctx);
                     act.conn_backoff.reset();
+                    act.endpoints.report_success();
                     act.inner = SinkWrite::new(sink, ctx);
                 }
                 Err(err) => {
                     error!("Can not connect to websocket {} : {}", url, err);
+                    act.endpoints.report_error();
                     // re-connect with backoff time.
                     // we stop current context, supervisor will restart it.
                     if let Some(timeout) = act.conn_backoff.next_backoff() {
@@ -74,27 +197,30 @@ impl actix::Supervised for DefaultWsActor {
     }
 }
 
+#[cfg(feature = "streaming")]
 impl DefaultWsActor
 {
-    pub async fn new(name: &'static str, wss_url: &str, conn_timeout: Option<Duration>, handler: Box<dyn WsHandler>) -> Result<Addr<DefaultWsActor>> {
-        let url = wss_url.to_string();
+    /// Connect to the active endpoint, failing over to the backup ones on sustained errors.
+    /// `raw_observers` get every frame from the first one, see `RawFrameObserver`.
+    pub async fn new(name: &'static str, endpoints: Arc<Endpoints>, proxy: Option<Proxy>, conn_timeout: Option<Duration>, raw_observers: Vec<(Exchange, Recipient<RawFrameEvent>)>, handler: Box<dyn WsHandler>) -> Result<Addr<DefaultWsActor>> {
         let name = name.to_string();
         let mut conn_backoff = ExponentialBackoff::default();
         conn_backoff.max_elapsed_time = conn_timeout;
 
         let mut c = None;
         loop {
-            match helpers::new_ws_client(url.clone()).await {
+            match helpers::new_ws_client(endpoints.current().to_string(), proxy.clone()).await {
                 Ok(frames) => {
                     c = Some(frames);
                     break
                 }
                 Err(e) => {
+                    endpoints.report_error();
                     if let Some(timeout) = conn_backoff.next_backoff() {
                         task::sleep(Duration::from_secs(1)).await;
                         continue
                     } else {
-                        return Err(ErrorKind::BackoffConnectionTimeout(format!("{}", e)).into())
+                        return Err(Error::BackoffConnectionTimeout(format!("{}", e)))
                     }
                 }
             }
@@ -104,19 +230,32 @@ impl DefaultWsActor
         let (sink, stream) = c.unwrap().split();
         Ok(Supervisor::start(move |ctx| {
             DefaultWsActor::add_stream(stream, ctx);
-            DefaultWsActor { inner: SinkWrite::new(sink, ctx), handler, hb: Instant::now(), url: url.clone(), conn_backoff, name: name.clone() }
+            DefaultWsActor { inner: SinkWrite::new(sink, ctx), handler, hb: Instant::now(), endpoints: endpoints.clone(), conn_backoff, name: name.clone(), proxy: proxy.clone(), raw_observers, shut_down: false }
         }))
     }
     fn hb(&self, ctx: &mut Context<Self>) {
         ctx.run_later(Duration::new(30, 0), |act, ctx| {
+            if act.shut_down {
+                return;
+            }
             act.inner.write(Message::Ping(Bytes::from_static(b""))).unwrap();
             act.hb(ctx);
             // client should also check for a timeout here, similar to the
             // server code
         });
     }
+
+    fn notify_raw(&self, payload: &Bytes) {
+        for (exchange, r) in &self.raw_observers {
+            let event = RawFrameEvent { exchange: *exchange, channel: self.name.clone(), payload: payload.clone() };
+            if let Err(e) = r.do_send(event) {
+                trace!("DefaultWsActor {} : unable to send a raw frame : {}", self.name, e);
+            }
+        }
+    }
 }
 
+#[cfg(feature = "streaming")]
 /// Handle stdin commands
 impl Handler<ClientCommand> for DefaultWsActor
 {
@@ -127,6 +266,66 @@ impl Handler<ClientCommand> for DefaultWsActor
     }
 }
 
+#[cfg(feature = "streaming")]
+impl Handler<ObserveRawFrames> for DefaultWsActor
+{
+    type Result = ();
+
+    fn handle(&mut self, msg: ObserveRawFrames, _ctx: &mut Context<Self>) {
+        self.raw_observers.push((msg.0, msg.1));
+    }
+}
+
+#[cfg(feature = "streaming")]
+/// Close the websocket, the actor stays idle until its addresses are dropped
+impl Handler<Shutdown> for DefaultWsActor
+{
+    type Result = ();
+
+    fn handle(&mut self, _msg: Shutdown, ctx: &mut Context<Self>) {
+        info!("DefaultWsActor {} : shutting down", self.name);
+        self.shut_down = true;
+        self.handler.flush_deliveries();
+        self.inner.close();
+        ctx.stop();
+    }
+}
+
+#[cfg(feature = "streaming")]
+impl Handler<GetSnapshot> for DefaultWsActor
+{
+    type Result = Option<Snapshot>;
+
+    fn handle(&mut self, msg: GetSnapshot, _ctx: &mut Context<Self>) -> Self::Result {
+        self.handler.snapshot(msg.0)
+    }
+}
+
+#[cfg(feature = "streaming")]
+impl Handler<AddOrder> for DefaultWsActor
+{
+    type Result = ResponseFuture<Result<OrderInfo>>;
+
+    fn handle(&mut self, msg: AddOrder, _ctx: &mut Context<Self>) -> Self::Result {
+        let (tx, rx) = oneshot::channel();
+        self.handler.add_order(&mut self.inner, msg, tx);
+        Box::pin(async move { rx.await.unwrap_or_else(|e| Err(Error::ChannelCanceled(format!("{}", e)))) })
+    }
+}
+
+#[cfg(feature = "streaming")]
+impl Handler<CancelOrder> for DefaultWsActor
+{
+    type Result = ResponseFuture<Result<()>>;
+
+    fn handle(&mut self, msg: CancelOrder, _ctx: &mut Context<Self>) -> Self::Result {
+        let (tx, rx) = oneshot::channel();
+        self.handler.cancel_order(&mut self.inner, msg, tx);
+        Box::pin(async move { rx.await.unwrap_or_else(|e| Err(Error::ChannelCanceled(format!("{}", e)))) })
+    }
+}
+
+#[cfg(feature = "streaming")]
 /// Handle server websocket messages
 impl StreamHandler<std::result::Result<Frame, WsProtocolError>> for DefaultWsActor
 {
@@ -137,8 +336,10 @@ impl StreamHandler<std::result::Result<Frame, WsProtocolError>> for DefaultWsAct
                 self.inner.write(Message::Pong(Bytes::copy_from_slice(&msg)));
             }
             Ok(Frame::Text(txt)) => {
+                self.notify_raw(&txt);
                 self.handler.handle_in(&mut self.inner, txt);
             }
+            Ok(Frame::Binary(bin)) => self.notify_raw(&bin),
             _ => {
                 ();
             }
@@ -156,11 +357,141 @@ impl StreamHandler<std::result::Result<Frame, WsProtocolError>> for DefaultWsAct
     }
 }
 
+#[cfg(feature = "streaming")]
 impl actix::io::WriteHandler<WsProtocolError> for DefaultWsActor
 {}
 
 pub trait ExchangeBot {
     /// Returns the address of the exchange actor
     fn is_connected(&self) -> bool;
+    /// What the bot streams, nothing unless it says otherwise
+    fn capabilities(&self) -> ExchangeCapabilities {
+        ExchangeCapabilities::default()
+    }
+    /// Send the frames received by the bot to `recipient` as they are, before any parsing,
+    /// e.g. to archive them or read fields the live events do not have
+    fn observe_raw_frames(&self, _recipient: Recipient<RawFrameEvent>) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+    /// The websocket endpoints of the bot, e.g. to subscribe to their `FailoverEvent`s.
+    /// `None` for bots whose client picks its endpoint by itself.
+    fn endpoints(&self) -> Option<Arc<Endpoints>> {
+        None
+    }
+    /// Close the connections of the bot for good, the future resolves once they are closed. Bots
+    /// without connections of their own are stopped when dropped.
+    fn shutdown(&self) -> LocalBoxFuture<'static, ()> {
+        Box::pin(future::ready(()))
+    }
+}
+
+#[cfg(feature = "streaming")]
+/// Shut a websocket actor down, see `ExchangeBot::shutdown`
+pub fn shutdown_ws(addr: &Addr<DefaultWsActor>) -> LocalBoxFuture<'static, ()> {
+    let request = addr.send(Shutdown);
+    Box::pin(async move {
+        if let Err(e) = request.await {
+            trace!("Websocket actor already stopped : {}", e);
+        }
+    })
+}
+
+#[cfg(feature = "streaming")]
+/// Ask a websocket actor for the current snapshot of `pair`
+pub async fn query_snapshot(addr: &Addr<DefaultWsActor>, pair: Pair) -> Result<Option<Snapshot>> {
+    addr.send(GetSnapshot(pair)).await.map_err(|e| Error::ChannelCanceled(format!("{}", e)))
 }
 
+
+/// Send orders through the websocket trading channel of an exchange, falling back to its REST API
+/// when there is no websocket or when the websocket did not send the request. Orders are tagged with
+/// a client id : one whose websocket answer was lost is looked up over REST before being sent
+/// again, and is not sent again if the exchange cannot look it up.
+pub struct OrderGateway {
+    #[cfg(feature = "streaming")]
+    ws: Option<Addr<DefaultWsActor>>,
+    rest: Arc<dyn ExchangeApi>,
+    /// Client ids are 32 bits integers, the only ones Kraken takes
+    next_client_id: i32,
+}
+
+impl OrderGateway {
+    #[cfg(feature = "streaming")]
+    pub fn new(ws: Option<Addr<DefaultWsActor>>, rest: Arc<dyn ExchangeApi>) -> Self {
+        OrderGateway { ws, rest, next_client_id: OrderGateway::first_client_id() }
+    }
+
+    /// A gateway sending every order over REST
+    pub fn rest_only(rest: Arc<dyn ExchangeApi>) -> Self {
+        OrderGateway {
+            #[cfg(feature = "streaming")]
+            ws: None,
+            rest,
+            next_client_id: OrderGateway::first_client_id(),
+        }
+    }
+
+    /// Seconds since the epoch, so that the ids of successive gateways seldom overlap
+    fn first_client_id() -> i32 {
+        (helpers::get_unix_timestamp_ms() / 1000 % i64::from(i32::MAX)) as i32
+    }
+}
+
+impl Actor for OrderGateway {
+    type Context = Context<Self>;
+}
+
+impl Handler<AddOrder> for OrderGateway {
+    type Result = ResponseFuture<Result<OrderInfo>>;
+
+    fn handle(&mut self, msg: AddOrder, _ctx: &mut Context<Self>) -> Self::Result {
+        #[cfg(feature = "streaming")]
+        let ws = self.ws.clone();
+        let rest = self.rest.clone();
+        let client_id = msg.client_id.clone().unwrap_or_else(|| {
+            self.next_client_id = self.next_client_id.checked_add(1).unwrap_or(1);
+            self.next_client_id.to_string()
+        });
+        let msg = AddOrder { client_id: Some(client_id.clone()), ..msg };
+        Box::pin(async move {
+            #[cfg(feature = "streaming")]
+            if let Some(addr) = ws.filter(|addr| addr.connected()) {
+                // The request may have gone out when the answer is lost
+                let lost = match addr.send(msg.clone()).await {
+                    Ok(Err(Error::Unsupported)) => None,
+                    Ok(Err(Error::ChannelCanceled(e))) => Some(e),
+                    Ok(result) => return result,
+                    Err(e) => Some(e.to_string()),
+                };
+                if let Some(e) = lost {
+                    warn!("Order gateway : answer to order {} lost, looking it up : {}", client_id, e);
+                    if let Some(order) = rest.order_by_client_id(msg.pair, &client_id).await? {
+                        return Ok(order);
+                    }
+                }
+            }
+            match rest.add_order_with_id(msg.order_type, msg.pair, msg.quantity.clone(), msg.price.clone(), &client_id).await {
+                Err(Error::Unsupported) => rest.add_order(msg.order_type, msg.pair, msg.quantity, msg.price).await,
+                result => result,
+            }
+        })
+    }
+}
+
+impl Handler<CancelOrder> for OrderGateway {
+    type Result = ResponseFuture<Result<()>>;
+
+    fn handle(&mut self, msg: CancelOrder, _ctx: &mut Context<Self>) -> Self::Result {
+        #[cfg(feature = "streaming")]
+        let ws = self.ws.clone();
+        Box::pin(async move {
+            #[cfg(feature = "streaming")]
+            if let Some(addr) = ws {
+                return addr.send(msg).await.map_err(|e| Error::ChannelCanceled(format!("{}", e)))?;
+            }
+            let _ = msg;
+            // ExchangeApi has no cancellation
+            Err(Error::Unsupported)
+        })
+    }
+}
@@ -0,0 +1,200 @@
+//! Fiat cross rates, to compare and value amounts quoted in different fiat currencies.
+//! Rates are fetched from a provider over an `HttpTransport`, by default the ECB reference rates
+//! served by frankfurter.app. Stablecoins are valued at the fiat they are pegged to.
+//!
+//! ```ignore
+//! let rates = HttpFxProvider::new(http_client).fetch(Currency::USD, &[Currency::EUR, Currency::GBP]).await?;
+//! let portfolio = Portfolio::fetch(&apis, Currency::USD).await?.with_fx(rates);
+//! ```
+//!
+//! `FxRates::tickers` gives the rates as tickers, to be merged with the exchange tickers given
+//! to `conversion::convert`.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use serde_json::Value;
+
+use crate::error::*;
+use crate::helpers;
+use crate::public::HttpTransport;
+use crate::types::*;
+use crate::utils;
+
+/// Rates of the ECB, `{base}` and `{symbols}` are replaced by the requested currencies
+pub const DEFAULT_PROVIDER_URL: &str = "https://api.frankfurter.app/latest?from={base}&to={symbols}";
+
+/// Amounts of each currency worth one unit of `base`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FxRates {
+    pub base: Currency,
+    pub rates: HashMap<Currency, BigDecimal>,
+    /// Stablecoins and the fiat currency they are worth one unit of
+    pub pegs: HashMap<Currency, Currency>,
+    /// UNIX timestamp in ms of the rates
+    pub timestamp: i64,
+}
+
+impl Default for FxRates {
+    fn default() -> Self {
+        FxRates::new(Currency::USD)
+    }
+}
+
+impl FxRates {
+    /// No rate but the pegs of USDT and TUSD to USD
+    pub fn new(base: Currency) -> Self {
+        let pegs = vec![(Currency::USDT, Currency::USD), (Currency::TUSD, Currency::USD)].into_iter().collect();
+        FxRates { base, rates: HashMap::new(), pegs, timestamp: helpers::get_unix_timestamp_ms() }
+    }
+
+    /// Set the amount of `currency` worth one unit of the base currency
+    pub fn with_rate(mut self, currency: Currency, per_base: BigDecimal) -> Self {
+        self.rates.insert(currency, per_base);
+        self
+    }
+
+    /// Value `stablecoin` at one unit of `fiat`
+    pub fn with_peg(mut self, stablecoin: Currency, fiat: Currency) -> Self {
+        self.pegs.insert(stablecoin, fiat);
+        self
+    }
+
+    fn fiat(&self, currency: Currency) -> Currency {
+        self.pegs.get(&currency).copied().unwrap_or(currency)
+    }
+
+    /// Amount of `currency` worth one unit of the base currency
+    fn per_base(&self, currency: Currency) -> Option<BigDecimal> {
+        if currency == self.base {
+            return Some(BigDecimal::from(1));
+        }
+        self.rates.get(&currency).filter(|rate| !rate.is_zero()).cloned()
+    }
+
+    /// Amount of `to` worth one `from`, crossed through the base currency
+    pub fn rate(&self, from: Currency, to: Currency) -> Option<BigDecimal> {
+        let (from, to) = (self.fiat(from), self.fiat(to));
+        if from == to {
+            return Some(BigDecimal::from(1));
+        }
+        Some(self.per_base(to)? / self.per_base(from)?)
+    }
+
+    /// Convert `amount` of `from` into `to`, fails with `PairUnsupported` without a rate for both
+    pub fn convert(&self, amount: &Amount, from: Currency, to: Currency) -> Result<Amount> {
+        self.rate(from, to).map(|rate| amount.clone() * rate).ok_or(Error::PairUnsupported)
+    }
+
+    /// Every currency with a rate, and the stablecoins pegged to them
+    pub fn currencies(&self) -> Vec<Currency> {
+        let mut currencies: Vec<Currency> = self.rates.keys().copied().collect();
+        currencies.push(self.base);
+        currencies.extend(self.pegs.iter().filter(|(_, fiat)| self.per_base(**fiat).is_some()).map(|(stablecoin, _)| *stablecoin));
+        currencies
+    }
+
+    /// The rates between the known currencies as tickers, with the rate as bid and ask, for the
+    /// pairs that exist
+    pub fn tickers(&self) -> HashMap<Pair, Ticker> {
+        let currencies = self.currencies();
+        let mut tickers = HashMap::new();
+        for base in &currencies {
+            for quote in &currencies {
+                let pair = match utils::currencies_pair(*base, *quote) {
+                    Some(pair) => pair,
+                    None => continue,
+                };
+                if let Some(rate) = self.rate(*base, *quote) {
+                    tickers.insert(pair, Ticker {
+                        timestamp: self.timestamp,
+                        pair,
+                        last_trade_price: rate.clone(),
+                        lowest_ask: rate.clone(),
+                        highest_bid: rate,
+                        volume: None,
+                        bid_size: None,
+                        ask_size: None,
+                        open_24h: None,
+                        high_24h: None,
+                        low_24h: None,
+                        percent_change: None,
+                        raw: None,
+                    });
+                }
+            }
+        }
+        tickers
+    }
+}
+
+/// A source of fiat rates
+#[async_trait(?Send)]
+pub trait FxProvider {
+    /// Rates of `symbols` against one unit of `base`
+    async fn fetch(&self, base: Currency, symbols: &[Currency]) -> Result<FxRates>;
+}
+
+/// Rates from an HTTP api answering `{"base": "USD", "rates": {"EUR": 0.92, "GBP": 0.79}}`,
+/// as frankfurter.app and exchangerate.host do
+pub struct HttpFxProvider<T> {
+    transport: T,
+    url: String,
+}
+
+impl<T: HttpTransport> HttpFxProvider<T> {
+    pub fn new(transport: T) -> Self {
+        HttpFxProvider { transport, url: DEFAULT_PROVIDER_URL.to_string() }
+    }
+
+    /// Query another provider, `{base}` and `{symbols}` (comma separated) are replaced in `url`
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+}
+
+#[async_trait(?Send)]
+impl<T: HttpTransport> FxProvider for HttpFxProvider<T> {
+    async fn fetch(&self, base: Currency, symbols: &[Currency]) -> Result<FxRates> {
+        let symbols: Vec<String> = symbols.iter().map(|c| format!("{:?}", c)).collect();
+        let url = self.url.replace("{base}", &format!("{:?}", base)).replace("{symbols}", &symbols.join(","));
+        let response: Value = serde_json::from_slice(&self.transport.get(&url).await?)?;
+        let rates = response["rates"].as_object().ok_or_else(|| Error::MissingField("rates".to_string()))?;
+        let mut fx = FxRates::new(base);
+        for (symbol, rate) in rates {
+            let currency: Option<Currency> = serde_json::from_value(Value::String(symbol.clone())).ok();
+            if let (Some(currency), Some(rate)) = (currency, helpers::from_json_bigdecimal_opt(rate)) {
+                fx.rates.insert(currency, rate);
+            }
+        }
+        Ok(fx)
+    }
+}
+
+#[cfg(test)]
+mod fx_tests {
+    use super::*;
+    use std::str::FromStr;
+
+    struct Canned(Value);
+
+    #[async_trait(?Send)]
+    impl HttpTransport for Canned {
+        async fn get(&self, _url: &str) -> Result<Vec<u8>> {
+            Ok(self.0.to_string().into_bytes())
+        }
+    }
+
+    #[test]
+    fn cross_rates_through_the_base() {
+        let response = serde_json::json!({"amount": 1.0, "base": "USD", "date": "2020-03-02", "rates": {"EUR": 0.8, "GBP": 0.5}});
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let rates = rt.block_on(HttpFxProvider::new(Canned(response)).fetch(Currency::USD, &[Currency::EUR, Currency::GBP])).unwrap();
+        assert_eq!(rates.rate(Currency::EUR, Currency::GBP), Some(BigDecimal::from_str("0.625").unwrap()));
+        assert_eq!(rates.convert(&BigDecimal::from(10), Currency::USDT, Currency::EUR).unwrap(), BigDecimal::from(8));
+        assert_eq!(rates.tickers()[&Pair::EUR_USD].highest_bid, BigDecimal::from_str("1.25").unwrap());
+        assert!(rates.convert(&BigDecimal::from(1), Currency::EUR, Currency::JPY).is_err());
+    }
+}
@@ -2,62 +2,99 @@
 //! Please see examples for more informations.
 
 
-use hyper::{Client, Uri, Request, Body, Method};
+use hyper::{Uri, Request, Body, Method};
 use hyper::header::{CONTENT_TYPE,USER_AGENT};
 
-use hyper_tls::HttpsConnector;
 
 use serde_json::Value;
 use serde_json::value::Map;
 
 use std::collections::HashMap;
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::AtomicI64;
+use std::time::Instant;
 
 use crate::coinnect::Credentials;
 use crate::exchange::Exchange;
 
 use crate::error::*;
+use crate::helpers::clock::ServerClock;
+use crate::helpers::rate_limit::RateLimits;
+use crate::helpers::failover::Endpoints;
+use crate::helpers::secret::SecretString;
+use crate::helpers::signing;
 use crate::helpers;
+use crate::metrics;
 use crate::types::Pair;
 use crate::gdax::utils;
 use crate::types::*;
-use hyper::client::HttpConnector;
-use futures::{TryFutureExt};
+use crate::helpers::proxy::HttpClient;
 use bytes::buf::BufExt as _;
 use crate::helpers::json;
 
+const CB_ACCESS_KEY: &str = "CB-ACCESS-KEY";
+const CB_ACCESS_SIGN: &str = "CB-ACCESS-SIGN";
+const CB_ACCESS_TIMESTAMP: &str = "CB-ACCESS-TIMESTAMP";
+const CB_ACCESS_PASSPHRASE: &str = "CB-ACCESS-PASSPHRASE";
+
+/// Execution flags of a limit order
+#[derive(Debug, Clone, Default)]
+pub struct LimitOrderOptions {
+    /// `GTC` (default), `GTT`, `IOC` or `FOK`
+    pub time_in_force: Option<String>,
+    /// Reject the order instead of taking liquidity
+    pub post_only: bool,
+    /// Id chosen by the client, a UUID, to find the order back with `get_order_by_client_oid`
+    pub client_oid: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct GdaxApi {
-    last_request: i64, // unix timestamp in ms, to avoid ban
-    api_key: String,
-    api_secret: String,
-    customer_id: String,
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    last_request: AtomicI64, // unix timestamp in ms, to avoid ban
+    api_key: SecretString,
+    api_secret: SecretString,
+    passphrase: SecretString,
+    http_client: HttpClient,
     burst: bool,
+    pub(crate) keep_raw: bool,
+    debug_signing: bool,
+    endpoints: Endpoints,
+    pub(crate) clock: ServerClock,
+    pub(crate) rate_limits: RateLimits,
 }
 
 
 impl GdaxApi {
-    /// Create a new GdaxApi by providing an API key & API secret
+    /// Create a new GdaxApi by providing an API key & API secret, the sandbox is targeted when
+    /// the credentials ask for it
     pub fn new<C: Credentials>(creds: C) -> Result<GdaxApi> {
         if creds.exchange() != Exchange::Gdax {
-            return Err(ErrorKind::InvalidConfigType(Exchange::Gdax, creds.exchange()).into());
+            return Err(Error::InvalidConfigType(Exchange::Gdax, creds.exchange()));
         }
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
+        let connection = creds.connection();
+        let endpoints = connection.rest_endpoints(Exchange::Gdax, utils::base_url(creds.environment()?));
+        let ssl = connection.http_client()?;
 
         Ok(GdaxApi {
-               last_request: 0,
-               api_key: creds.get("api_key").unwrap_or_default(),
-               api_secret: creds.get("api_secret").unwrap_or_default(),
-               customer_id: creds.get("customer_id").unwrap_or_default(),
+               last_request: AtomicI64::new(0),
+               api_key: creds.get("api_key").unwrap_or_default().into(),
+               api_secret: creds.get("api_secret").unwrap_or_default().into(),
+               passphrase: creds.get("passphrase").unwrap_or_default().into(),
                http_client: ssl,
                burst: false, // No burst by default
+               keep_raw: connection.keep_raw,
+               debug_signing: connection.debug_signing,
+               endpoints,
+               clock: ServerClock::default(),
+               rate_limits: RateLimits::default(),
            })
     }
 
+    /// The REST endpoints, to subscribe to failover events or recover the primary endpoint
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
     /// The number of calls in a given period is limited. In order to avoid a ban we limit
     /// by default the number of api requests.
     /// This function sets or removes the limitation.
@@ -67,91 +104,98 @@ impl GdaxApi {
         self.burst = burst
     }
 
-    fn block_or_continue(&self) {
-        if ! self.burst {
-            let threshold: u64 = 334; // 3 requests/sec = 1/3*1000
-            let offset: u64 = helpers::get_unix_timestamp_ms() as u64 - self.last_request as u64;
-            if offset < threshold {
-                let wait_ms = Duration::from_millis(threshold - offset);
-                thread::sleep(wait_ms);
-            }
+    /// Keep the payload of the exchange in the `raw` field of typed responses
+    pub fn set_keep_raw(&mut self, keep_raw: bool) {
+        self.keep_raw = keep_raw
+    }
+
+    /// Log the strings signed for the private requests and the status of their responses
+    pub fn set_debug_signing(&mut self, debug_signing: bool) {
+        self.debug_signing = debug_signing
+    }
+
+    async fn block_or_continue(&self) {
+        if !self.burst {
+            helpers::rate_limit::wait_for_slot(&self.last_request, 334).await; // 3 requests/sec = 1/3*1000
         }
     }
 
-    async fn public_query(&mut self, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
+    async fn public_query(&self, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
 
         let method: &str = params
             .get("method")
             .ok_or_else(|| "Missing \"method\" field.")?;
         let pair: &str = params.get("pair").ok_or_else(|| "Missing \"pair\" field.")?;
-        let string = utils::build_url(method, pair);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
+        let string = utils::build_url(self.endpoints.current(), method, pair);
+        let url: Uri = string.as_str().parse().map_err(|_e| Error::BadParse)?;
 
-        self.block_or_continue();
+        self.block_or_continue().await;
         let req: Result<Request<Body>> = Request::builder()
             .method(Method::GET)
             .uri(url)
             .header(USER_AGENT, "coinnect")
             .body(Body::empty())
-            .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
+            .map_err(|e| Error::ServiceUnavailable(e.to_string()));
 
         let req2 = req.unwrap();
-        let resp = self.http_client.request(req2).await?;
+        let start = Instant::now();
+        let resp = self.http_client.request(req2).await.map_err(Error::from);
+        metrics::rest_request(Exchange::Gdax, method, start);
+        let resp = self.endpoints.track(resp)?;
+        self.rate_limits.observe(resp.headers(), helpers::get_unix_timestamp_ms());
         let buf = hyper::body::aggregate(resp.into_body()).await?;
 
-        self.last_request = helpers::get_unix_timestamp_ms();
         let reader = buf.reader();
-        json::deserialize_json_r(reader)
+        match method {
+            // Lists are returned under `data`
+            "products" => json::deserialize_json_array_r(reader),
+            _ => json::deserialize_json_r(reader),
+        }
     }
 
-    ///
-    ///
-    /// #Examples
-    ///
-    /// ```json
-    /// extern crate coinnect;
-    /// use crate::coinnect::gdax::GdaxApi;
-    /// let mut api = GdaxApi::new("", "");
-    /// let  result = api.private_query("balance", "btcusd");
-    /// assert_eq!(true, true);
-    /// ```
-    async fn private_query(&mut self, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
-
-        let method: &str = params
-            .get("method")
-            .ok_or_else(|| "Missing \"method\" field.")?;
-        let pair: &str = params.get("pair").ok_or_else(|| "Missing \"pair\" field.")?;
-        let string = utils::build_url(method, pair);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
-
-        let nonce = utils::generate_nonce(None);
-        let signature =
-            utils::build_signature(&nonce, &self.customer_id, &self.api_key, &self.api_secret)?;
-
-        let copy_api_key = self.api_key.clone();
-        let mut post_params: &mut HashMap<&str, &str> = &mut HashMap::new();
-        post_params.insert("key", &copy_api_key);
-        post_params.insert("signature", &signature);
-        post_params.insert("nonce", &nonce);
-
-        // copy params into post_params .... bit of a hack but will do for now
-        params.iter().for_each(|(k,v)| {
-            post_params.insert(k,v);
-        });
+    /// Send a request signed with the CB-ACCESS-* headers to `path`, which includes the query
+    /// string. `body` is sent as JSON. Error responses are mapped with `utils::parse_result`.
+    async fn private_query(&self, method: Method, path: &str, body: Option<Value>) -> Result<Value> {
+        let url: Uri = format!("{}{}", self.endpoints.current(), path).parse().map_err(|_e| Error::BadParse)?;
+        let content = body.map(|b| b.to_string()).unwrap_or_default();
+
+        let timestamp = (self.clock.now_ms() / 1000).to_string();
+        let signature = utils::build_signature(&timestamp, method.as_str(), path, &content, self.api_secret.expose())?;
+        if self.debug_signing {
+            let canonical = format!("{}{}{}{}", timestamp, method.as_str(), path, content);
+            signing::log_signed(Exchange::Gdax, path, &timestamp, &canonical, &[self.api_key.expose(), self.passphrase.expose()]);
+        }
 
-        helpers::strip_empties(&mut post_params);
-        let post_data = helpers::url_encode_hashmap(post_params);
-        let req: Result<Request<Body>> = Request::builder()
-            .method("POST")
+        self.block_or_continue().await;
+        let req = Request::builder()
+            .method(method)
             .uri(url)
-            .header(CONTENT_TYPE, "application/x-www-form-urlencoded".to_owned())
-            .body(Body::from(post_data))
-            .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
-        let req2 = req.unwrap();
-        let buf = self.http_client.request(req2).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
-        self.last_request = helpers::get_unix_timestamp_ms();
-        let reader = buf.reader();
-        json::deserialize_json_r(reader)
+            .header(USER_AGENT, "coinnect")
+            .header(CONTENT_TYPE, "application/json")
+            .header(CB_ACCESS_KEY, self.api_key.expose())
+            .header(CB_ACCESS_SIGN, signature)
+            .header(CB_ACCESS_TIMESTAMP, timestamp)
+            .header(CB_ACCESS_PASSPHRASE, self.passphrase.expose())
+            .body(Body::from(content))
+            .map_err(|e| Error::ServiceUnavailable(e.to_string()))?;
+        let start = Instant::now();
+        let resp = self.http_client.request(req).await.map_err(Error::from);
+        metrics::rest_request(Exchange::Gdax, path, start);
+        let resp = self.endpoints.track(resp)?;
+        self.rate_limits.observe(resp.headers(), helpers::get_unix_timestamp_ms());
+        let status = resp.status();
+        if self.debug_signing {
+            signing::log_response(Exchange::Gdax, path, status);
+        }
+        let buf = hyper::body::aggregate(resp.into_body()).await?;
+        let value: Value = serde_json::from_reader(buf.reader()).map_err(|_| Error::BadParse)?;
+        if !status.is_success() {
+            if let Some(error) = value.as_object() {
+                utils::parse_result(error)?;
+            }
+            return Err(Error::ExchangeSpecificError(status.to_string()));
+        }
+        Ok(value)
     }
 
     /// Sample output :
@@ -166,10 +210,10 @@ impl GdaxApi {
     /// "percentChange":"0.16701570","baseVolume":"0.45347489","quoteVolume":"9094"},
     /// ... }
     /// ```
-    pub async fn return_ticker(&mut self, pair: Pair) -> Result<Map<String, Value>> {
+    pub async fn return_ticker(&self, pair: Pair) -> Result<Map<String, Value>> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
 
         let mut params: HashMap<&str, &str> = HashMap::new();
@@ -178,16 +222,42 @@ impl GdaxApi {
         self.public_query(&params).await
     }
 
+    /// Markets of the exchange, the list is returned under `data`.
+    ///
+    /// Sample output :
+    ///
+    /// ```json
+    /// {"data": [{"id": "BTC-USD", "status": "online", "trading_disabled": false, ...}, ...]}
+    /// ```
+    pub async fn return_products(&self) -> Result<Map<String, Value>> {
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        params.insert("method", "products");
+        params.insert("pair", "");
+        self.public_query(&params).await
+    }
+
+    /// Time of the server
+    ///
+    /// ```json
+    /// {"iso": "2015-01-07T23:47:25.201Z", "epoch": 1420674445.201}
+    /// ```
+    pub async fn get_time(&self) -> Result<Map<String, Value>> {
+        let mut params: HashMap<&str, &str> = HashMap::new();
+        params.insert("method", "time");
+        params.insert("pair", "");
+        self.public_query(&params).await
+    }
+
     /// Sample output :
     ///
     /// ```json
     /// {"asks":[[0.00007600,1164],[0.00007620,1300], ... ], "bids":[[0.00006901,200],
     /// [0.00006900,408], ... ], "timestamp": "1234567890"}
     /// ```
-    pub async fn return_order_book(&mut self, pair: Pair) -> Result<Map<String, Value>> {
+    pub async fn return_order_book(&self, pair: Pair) -> Result<Map<String, Value>> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
 
         };
 
@@ -205,10 +275,10 @@ impl GdaxApi {
     /// {"date":"2014-02-10 01:19:37","type":"buy","rate":"0.00007600","amount":"655",
     /// "total":"0.04978"}, ... ]
     /// ```
-    pub async fn return_trade_history(&mut self, pair: Pair) -> Result<Map<String, Value>> {
+    pub async fn return_trade_history(&self, pair: Pair) -> Result<Map<String, Value>> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
 
         let mut params: HashMap<&str, &str> = HashMap::new();
@@ -218,141 +288,224 @@ impl GdaxApi {
     }
 
 
-    /// Returns all of your available balances.
+    /// Returns the trading accounts of the profile, one per currency.
     ///
     /// Sample output:
     ///
     /// ```json
-    /// {"BTC":"0.59098578","LTC":"3.31117268", ... }
+    /// [{"id": "71452118-efc7-4cc4-8780-a5e22d4baa53", "currency": "BTC", "balance": "0.0100000000",
+    /// "available": "0.0100000000", "hold": "0.0000000000", "profile_id": "..."}, ...]
     /// ```
-    pub async fn return_balances(&mut self) -> Result<Map<String, Value>> {
-        let mut params = HashMap::new();
-        params.insert("method", "balance");
-        params.insert("pair", "");
-        self.private_query(&params).await
+    pub async fn get_accounts(&self) -> Result<Value> {
+        self.private_query(Method::GET, "/accounts", None).await
+    }
+
+    /// Returns the fee rates of the profile and its 30 days volume.
+    ///
+    /// ```json
+    /// {"maker_fee_rate": "0.0015", "taker_fee_rate": "0.0025", "usd_volume": "25000.00"}
+    /// ```
+    pub async fn get_fees(&self) -> Result<Value> {
+        self.private_query(Method::GET, "/fees", None).await
     }
 
-    /// Add a buy limit order to the exchange
-    /// limit_price	: If the order gets executed, a new sell order will be placed,
-    /// with "limit_price" as its price.
-    /// daily_order (Optional) : Opens buy limit order which will be canceled
-    /// at 0:00 UTC unless it already has been executed. Possible value: True
-    pub async fn buy_limit(&mut self,
+    /// Returns a single account
+    pub async fn get_account(&self, account_id: &str) -> Result<Value> {
+        self.private_query(Method::GET, &format!("/accounts/{}", account_id), None).await
+    }
+
+    /// Add a buy limit order to the exchange, see `LimitOrderOptions` for the execution flags
+    pub async fn buy_limit(&self,
                      pair: Pair,
                      amount: Volume,
                      price: Price,
-                     price_limit: Option<Price>,
-                     daily_order: Option<bool>)
-                     -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+                     options: LimitOrderOptions)
+                     -> Result<Value> {
+        self.limit_order("buy", pair, amount, price, options).await
+    }
 
-        let amount_string = amount.to_string();
-        let price_string = price.to_string();
-        let price_limit_string = match price_limit {
-            Some(limit) => limit.to_string(),
-            None => "".to_string(),
-        };
+    /// Add a sell limit order to the exchange, see `LimitOrderOptions` for the execution flags
+    pub async fn sell_limit(&self,
+                      pair: Pair,
+                      amount: Volume,
+                      price: Price,
+                      options: LimitOrderOptions)
+                      -> Result<Value> {
+        self.limit_order("sell", pair, amount, price, options).await
+    }
 
-        let mut params = HashMap::new();
-        params.insert("method", "buy");
-        params.insert("pair", pair_name);
+    /// Add a market buy order of `amount` in base currency to the exchange
+    pub async fn buy_market(&self, pair: Pair, amount: Volume) -> Result<Value> {
+        self.market_order("buy", pair, amount, None).await
+    }
+
+    /// Add a market sell order of `amount` in base currency to the exchange
+    pub async fn sell_market(&self, pair: Pair, amount: Volume) -> Result<Value> {
+        self.market_order("sell", pair, amount, None).await
+    }
 
-        params.insert("amount", &amount_string);
-        params.insert("price", &price_string);
-        params.insert("limit_price", &price_limit_string);
-        if let Some(order) = daily_order {
-            let daily_order_str = if order { "True" } else { "" }; // False is not a possible value
-            params.insert("daily_order", daily_order_str);
+    async fn limit_order(&self, side: &str, pair: Pair, amount: Volume, price: Price, options: LimitOrderOptions) -> Result<Value> {
+        let pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let mut order = serde_json::json!({
+            "type": "limit",
+            "side": side,
+            "product_id": pair_name.to_uppercase(),
+            "size": amount.to_string(),
+            "price": price.to_string(),
+            "post_only": options.post_only,
+        });
+        if let Some(tif) = options.time_in_force {
+            order["time_in_force"] = Value::String(tif);
+        }
+        if let Some(client_oid) = options.client_oid {
+            order["client_oid"] = Value::String(client_oid);
         }
+        self.private_query(Method::POST, "/orders", Some(order)).await
+    }
 
-        self.private_query(&params).await
+    /// Add a market order of `amount` in base currency, tagged with `client_oid` if given
+    pub async fn market_order(&self, side: &str, pair: Pair, amount: Volume, client_oid: Option<&str>) -> Result<Value> {
+        let pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let mut order = serde_json::json!({
+            "type": "market",
+            "side": side,
+            "product_id": pair_name.to_uppercase(),
+            "size": amount.to_string(),
+        });
+        if let Some(client_oid) = client_oid {
+            order["client_oid"] = Value::String(client_oid.to_string());
+        }
+        self.private_query(Method::POST, "/orders", Some(order)).await
     }
 
-    /// Add a sell limit order to the exchange
-    /// limit_price	: If the order gets executed, a new sell order will be placed,
-    /// with "limit_price" as its price.
-    /// daily_order (Optional) : Opens sell limit order which will be canceled
-    /// at 0:00 UTC unless it already has been executed. Possible value: True
-    pub async fn sell_limit(&mut self,
+    /// Add a stop order, sent once the price goes down to `stop_price` for a `loss` stop or up
+    /// to it for an `entry` stop. It is a limit order at `price` if given, a market order otherwise.
+    pub async fn stop_order(&self,
+                      side: &str,
                       pair: Pair,
                       amount: Volume,
-                      price: Price,
-                      price_limit: Option<Price>,
-                      daily_order: Option<bool>)
-                      -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
-
-        let amount_string = amount.to_string();
-        let price_string = price.to_string();
-        let price_limit_string = match price_limit {
-            Some(limit) => limit.to_string(),
-            None => "".to_string(),
-        };
-
-        let mut params = HashMap::new();
-        params.insert("method", "sell");
-        params.insert("pair", pair_name);
-
-        params.insert("amount", &amount_string);
-        params.insert("price", &price_string);
-        params.insert("limit_price", &price_limit_string);
-        if let Some(order) = daily_order {
-            let daily_order_str = if order { "True" } else { "" }; // False is not a possible value
-            params.insert("daily_order", daily_order_str);
+                      stop: &str,
+                      stop_price: Price,
+                      price: Option<Price>)
+                      -> Result<Value> {
+        let pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let mut order = serde_json::json!({
+            "type": if price.is_some() { "limit" } else { "market" },
+            "side": side,
+            "product_id": pair_name.to_uppercase(),
+            "size": amount.to_string(),
+            "stop": stop,
+            "stop_price": stop_price.to_string(),
+        });
+        if let Some(price) = price {
+            order["price"] = Value::String(price.to_string());
         }
+        self.private_query(Method::POST, "/orders", Some(order)).await
+    }
 
-        self.private_query(&params).await
+    /// Cancel an order, returns its id
+    pub async fn cancel_order(&self, order_id: &str) -> Result<Value> {
+        self.private_query(Method::DELETE, &format!("/orders/{}", order_id), None).await
     }
 
-    /// Add a market buy order to the exchange
-    /// By placing a market order you acknowledge that the execution of your order depends
-    /// on the market conditions and that these conditions may be subject to sudden changes
-    /// that cannot be foreseen.
-    pub async fn buy_market(&mut self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+    /// Cancel all the open orders, of `pair` only if given. Returns the ids of the canceled orders.
+    pub async fn cancel_all(&self, pair: Option<Pair>) -> Result<Value> {
+        let path = match pair {
+            Some(pair) => format!("/orders?product_id={}", utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?.to_uppercase()),
+            None => "/orders".to_string(),
         };
+        self.private_query(Method::DELETE, &path, None).await
+    }
 
-        let amount_string = amount.to_string();
-
+    /// List the orders, open ones by default. `status` can be `open`, `pending`, `active`, `done`
+    /// or `all`.
+    ///
+    /// ```json
+    /// [{"id": "d0c5340b-6d6c-49d9-b567-48c4bfca13d2", "price": "0.10000000", "size": "0.01000000",
+    /// "product_id": "BTC-USD", "side": "buy", "type": "limit", "status": "open",
+    /// "filled_size": "0.00000000", "created_at": "2016-12-08T20:02:28.53864Z", ...}, ...]
+    /// ```
+    pub async fn list_orders(&self, status: Option<&str>, pair: Option<Pair>) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("method", "buy/market");
-        params.insert("pair", pair_name);
-
-        params.insert("amount", &amount_string);
+        params.insert("status", status.unwrap_or_default());
+        let pair_name;
+        if let Some(pair) = pair {
+            pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?.to_uppercase();
+            params.insert("product_id", pair_name.as_str());
+        }
+        helpers::strip_empties(&mut params);
+        self.private_query(Method::GET, &with_query("/orders", &params), None).await
+    }
 
-        self.private_query(&params).await
+    /// Returns a single order
+    pub async fn get_order(&self, order_id: &str) -> Result<Value> {
+        self.private_query(Method::GET, &format!("/orders/{}", order_id), None).await
     }
 
-    /// Add a market sell order to the exchange
-    /// By placing a market order you acknowledge that the execution of your order depends
-    /// on the market conditions and that these conditions may be subject to sudden changes
-    /// that cannot be foreseen.
-    pub async fn sell_market(&mut self, pair: Pair, amount: Volume) -> Result<Map<String, Value>> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
-        };
+    /// Returns the order placed with `client_oid`, fails with `OrderNotFound` if there is none
+    pub async fn get_order_by_client_oid(&self, client_oid: &str) -> Result<Value> {
+        self.private_query(Method::GET, &format!("/orders/client:{}", client_oid), None).await
+    }
 
-        let amount_string = amount.to_string();
+    /// Recent fills of an order or of a pair, one of both is required. Fills older than the
+    /// trade id `after` are returned if it is not empty.
+    ///
+    /// ```json
+    /// [{"trade_id": 74, "product_id": "BTC-USD", "price": "10.00", "size": "0.01",
+    /// "order_id": "d50ec984-77a8-460a-b958-66f114b0de9b", "created_at": "2014-11-07T22:19:28.578544Z",
+    /// "liquidity": "T", "fee": "0.00025", "settled": true, "side": "buy"}, ...]
+    /// ```
+    pub async fn get_fills(&self, order_id: Option<&str>, pair: Option<Pair>, after: &str) -> Result<Value> {
+        if order_id.is_none() && pair.is_none() {
+            return Err(Error::InvalidArguments);
+        }
+        let mut params = HashMap::new();
+        params.insert("order_id", order_id.unwrap_or_default());
+        params.insert("after", after);
+        let pair_name;
+        if let Some(pair) = pair {
+            pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?.to_uppercase();
+            params.insert("product_id", pair_name.as_str());
+        }
+        helpers::strip_empties(&mut params);
+        self.private_query(Method::GET, &with_query("/fills", &params), None).await
+    }
 
+    /// Deposits and withdrawals of the profile, `transfer_type` can be `deposit`, `withdraw`,
+    /// `internal_deposit` or `internal_withdraw`.
+    pub async fn get_transfers(&self, transfer_type: Option<&str>) -> Result<Value> {
         let mut params = HashMap::new();
-        params.insert("method", "sell/market");
-        params.insert("pair", pair_name);
+        params.insert("type", transfer_type.unwrap_or_default());
+        helpers::strip_empties(&mut params);
+        self.private_query(Method::GET, &with_query("/transfers", &params), None).await
+    }
 
-        params.insert("amount", &amount_string);
+    /// Withdraw `amount` of `currency` to a crypto address, returns `{"id": ..., "amount": ..., "currency": ...}`
+    pub async fn withdraw_crypto(&self, currency: &str, amount: &str, address: &str) -> Result<Value> {
+        let body = serde_json::json!({"amount": amount, "currency": currency, "crypto_address": address});
+        self.private_query(Method::POST, "/withdrawals/crypto", Some(body)).await
+    }
 
-        self.private_query(&params).await
+    /// Move `amount` of `currency` from a Coinbase account
+    pub async fn deposit_from_coinbase(&self, currency: &str, amount: &str, coinbase_account_id: &str) -> Result<Value> {
+        let body = serde_json::json!({"amount": amount, "currency": currency, "coinbase_account_id": coinbase_account_id});
+        self.private_query(Method::POST, "/deposits/coinbase-account", Some(body)).await
+    }
+
+    /// Move `amount` of `currency` to a Coinbase account
+    pub async fn withdraw_to_coinbase(&self, currency: &str, amount: &str, coinbase_account_id: &str) -> Result<Value> {
+        let body = serde_json::json!({"amount": amount, "currency": currency, "coinbase_account_id": coinbase_account_id});
+        self.private_query(Method::POST, "/withdrawals/coinbase-account", Some(body)).await
     }
 }
 
+fn with_query(path: &str, params: &HashMap<&str, &str>) -> String {
+    if params.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}?{}", path, helpers::url_encode_hashmap(params))
+    }
+}
 
 #[cfg(test)]
 mod gdax_api_tests {
@@ -364,7 +517,7 @@ mod gdax_api_tests {
 //            last_request: helpers::get_unix_timestamp_ms(),
 //            api_key: "".to_string(),
 //            api_secret: "".to_string(),
-//            customer_id: "".to_string(),
+//            passphrase: "".to_string(),
 //            http_client: Client::new(),
 //            burst: false,
 //        };
@@ -4,23 +4,36 @@ use serde_json;
 use serde_json::Value;
 
 use crate::coinnect::Credentials;
-use crate::exchange::Exchange;
+use crate::env_credentials::EnvCredentials;
+use crate::exchange::{Environment, Exchange};
 use crate::helpers;
+use crate::helpers::secret::Redacted;
 use crate::error::*;
 
 use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct GdaxCreds {
     exchange: Exchange,
     name: String,
     data: HashMap<String, String>,
 }
 
+impl fmt::Debug for GdaxCreds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GdaxCreds")
+            .field("exchange", &self.exchange)
+            .field("name", &self.name)
+            .field("data", &Redacted(&self.data))
+            .finish()
+    }
+}
+
 impl GdaxCreds {
     /// Create a new `GdaxCreds` from a json configuration file. This file must follow this
     /// structure:
@@ -31,7 +44,8 @@ impl GdaxCreds {
     ///         "exchange"  : "gdax",
     ///         "api_key"   : "123456789ABCDEF",
     ///         "api_secret": "ABC&EF?abcdef",
-    ///         "passphrase": "123456"
+    ///         "passphrase": "123456",
+    ///         "environment": "sandbox"
     ///     },
     ///     "account_bitstamp": {
     ///         "exchange"   : "bitstamp",
@@ -41,6 +55,7 @@ impl GdaxCreds {
     ///     }
     /// }
     /// ```
+    /// `environment` (defaults to `production`), `rest_url`, `ws_url` and `proxy` are optional.
     /// For this example, you could use load your Gdax account with
     /// `GdaxAPI::new(GdaxCreds::new_from_file("account_gdax", Path::new("/keys.json")))`
     pub fn new_from_file(name: &str, path: PathBuf) -> Result<Self> {
@@ -50,9 +65,9 @@ impl GdaxCreds {
 
         let data: Value = serde_json::from_str(&buffer)?;
         let json_obj = data.as_object()
-            .ok_or_else(|| ErrorKind::BadParse)?
+            .ok_or_else(|| Error::BadParse)?
             .get(name)
-            .ok_or_else(|| ErrorKind::MissingField(name.to_string()))?;
+            .ok_or_else(|| Error::MissingField(name.to_string()))?;
 
         let api_key = helpers::get_json_string(json_obj, "api_key")?;
         let api_secret = helpers::get_json_string(json_obj, "api_secret")?;
@@ -60,14 +75,26 @@ impl GdaxCreds {
         let exchange = {
             let exchange_str = helpers::get_json_string(json_obj, "exchange")?;
             Exchange::from_str(exchange_str)
-                .chain_err(|| ErrorKind::InvalidFieldValue("exchange".to_string()))?
+                .with_context(|| Error::InvalidFieldValue("exchange".to_string()))?
         };
 
         if exchange != Exchange::Gdax {
-            return Err(ErrorKind::InvalidConfigType(Exchange::Gdax, exchange).into());
+            return Err(Error::InvalidConfigType(Exchange::Gdax, exchange));
         }
 
-        Ok(GdaxCreds::new(name, api_key, api_secret, passphrase))
+        let mut creds = GdaxCreds::new(name, api_key, api_secret, passphrase);
+        helpers::copy_client_settings(json_obj, &mut creds.data);
+        Ok(creds)
+    }
+
+    /// Target the given environment instead of production.
+    pub fn with_environment(mut self, environment: Environment) -> Self {
+        let value = match environment {
+            Environment::Production => "production",
+            Environment::Sandbox => "sandbox",
+        };
+        self.data.insert("environment".to_string(), value.to_string());
+        self
     }
 
 
@@ -107,6 +134,19 @@ impl GdaxCreds {
 
         creds
     }
+
+    /// Create a new `GdaxCreds` from the environment variables `<prefix>_GDAX_API_KEY`, `<prefix>_GDAX_API_SECRET`
+    /// and `<prefix>_GDAX_PASSPHRASE`, see `EnvCredentials`.
+    /// The optional client settings are read the same way, e.g. `<prefix>_GDAX_PROXY`.
+    pub fn new_from_env(prefix: &str) -> Result<Self> {
+        let env = EnvCredentials::new(prefix, Exchange::Gdax);
+        let api_key = env.require("api_key")?;
+        let api_secret = env.require("api_secret")?;
+        let passphrase = env.require("passphrase")?;
+        let mut creds = GdaxCreds::new(&env.name(), &api_key, &api_secret, &passphrase);
+        env.copy_client_settings(&mut creds.data);
+        Ok(creds)
+    }
 }
 
 impl Credentials for GdaxCreds {
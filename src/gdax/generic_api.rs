@@ -2,18 +2,19 @@
 //! This a more convenient and safe way to deal with the exchange since methods return a Result<>
 //! but this generic API does not provide all the functionnality that Gdax offers.
 
-use crate::exchange::{ExchangeApi};
-use crate::gdax::api::GdaxApi;
+use crate::exchange::{ExchangeApi, Exchange, ExchangeCapabilities};
+use crate::gdax::api::{GdaxApi, LimitOrderOptions};
 use crate::gdax::utils;
 
 use crate::error::*;
 use crate::types::*;
 use crate::helpers;
 use async_trait::async_trait;
+use serde_json::Value;
 
 #[async_trait]
 impl ExchangeApi for GdaxApi {
-    async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
 
         let result = self.return_ticker(pair).await?;
 
@@ -29,10 +30,17 @@ impl ExchangeApi for GdaxApi {
                lowest_ask: ask,
                highest_bid: bid,
                volume: Some(vol),
+               bid_size: None,
+               ask_size: None,
+               open_24h: None,
+               high_24h: None,
+               low_24h: None,
+               percent_change: None,
+               raw: helpers::raw_payload(self.keep_raw, &result),
            })
     }
 
-    async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
 
         let raw_response = self.return_order_book(pair).await?;
 
@@ -44,11 +52,11 @@ impl ExchangeApi for GdaxApi {
         let ask_array =
             result["asks"]
                 .as_array()
-                .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["asks"])))?;
+                .ok_or_else(|| Error::InvalidFieldFormat(format!("{}", result["asks"])))?;
         let bid_array =
             result["bids"]
                 .as_array()
-                .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["asks"])))?;
+                .ok_or_else(|| Error::InvalidFieldFormat(format!("{}", result["asks"])))?;
 
         for ask in ask_array {
             let price = helpers::from_json_bigdecimal(&ask[0], "ask price")?;
@@ -72,69 +80,236 @@ impl ExchangeApi for GdaxApi {
         })
     }
 
-    async fn add_order(&mut self,
+    async fn add_order(&self,
                  order_type: OrderType,
                  pair: Pair,
                  quantity: Volume,
                  price: Option<Price>)
                  -> Result<OrderInfo> {
-        //let pair_name = match utils::get_pair_string(&pair) {
-        //Some(name) => name,
-        //None => return Err(ErrorKind::PairUnsupported.into()),
-        //};
+        self.simple_order(OrderRequest::new(order_type, pair, &quantity, &price)).await
+    }
 
-        let result = match order_type {
-            OrderType::BuyLimit => {
-                if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
-                }
+    /// Client ids are sent as the `client_oid` of the order, a UUID
+    async fn add_order_with_id(&self,
+                               order_type: OrderType,
+                               pair: Pair,
+                               quantity: Volume,
+                               price: Option<Price>,
+                               client_id: &str)
+                               -> Result<OrderInfo> {
+        self.simple_order(OrderRequest::new(order_type, pair, &quantity, &price).with_client_id(client_id)).await
+    }
 
-                // Unwrap safe here with the check above.
-                self.buy_limit(pair, quantity, price.unwrap(), None, None).await
-            }
-            OrderType::BuyMarket => self.buy_market(pair, quantity).await,
-            OrderType::SellLimit => {
-                if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
-                }
+    async fn order_by_client_id(&self, pair: Pair, client_id: &str) -> Result<Option<OrderInfo>> {
+        client_order(pair, self.get_order_by_client_oid(client_id).await, self.keep_raw)
+    }
 
-                // Unwrap safe here with the check above.
-                self.sell_limit(pair, quantity, price.unwrap(), None, None).await
-            }
-            OrderType::SellMarket => self.sell_market(pair, quantity).await,
+    /// Stop losses and take profits are stop orders, triggered when the price goes against the
+    /// position or in its favor. Trailing stops are not supported.
+    async fn add_conditional_order(&self,
+                             order_type: OrderType,
+                             pair: Pair,
+                             quantity: Volume,
+                             trigger_price: Price,
+                             price: Option<Price>)
+                             -> Result<OrderInfo> {
+        let request = OrderRequest::conditional(order_type, pair, &quantity, &trigger_price, &price);
+        // A buy stop loss triggers when the price goes up, a buy take profit when it goes down
+        let (side, against, in_favor) = match order_type.side() {
+            Side::Buy => ("buy", "entry", "loss"),
+            Side::Sell => ("sell", "loss", "entry"),
         };
+        let result = match order_type {
+            OrderType::StopLoss(_) => self.stop_order(side, pair, quantity, against, trigger_price, None).await,
+            OrderType::StopLimit(_) => {
+                let price = price.ok_or(Error::MissingPrice)?;
+                self.stop_order(side, pair, quantity, against, trigger_price, Some(price)).await
+            }
+            OrderType::TakeProfit(_) => self.stop_order(side, pair, quantity, in_favor, trigger_price, None).await,
+            _ => return Err(Error::OrderTypeUnsupported),
+        }?;
 
         Ok(OrderInfo {
                timestamp: helpers::get_unix_timestamp_ms(),
-               identifier: vec![result?["id"]
-                                    .as_str()
-                                    .ok_or_else(|| {
-                                                    ErrorKind::MissingField("id".to_string())
-                                                })?
-                                    .to_string()],
+               identifier: vec![result["id"].as_str().ok_or_else(|| Error::MissingField("id".to_string()))?.to_string()],
+               raw: helpers::raw_payload(self.keep_raw, &result),
+               request: Some(request),
            })
     }
 
-    /// Return the balances for each currency on the account
-    async fn balances(&mut self) -> Result<Balances> {
-        let raw_response = self.return_balances().await?;
-        let result = utils::parse_result(&raw_response)?;
+    async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+        for id in &order.identifier {
+            self.cancel_order(id).await?;
+        }
+        Ok(())
+    }
+
+    async fn cancel_all_orders(&self) -> Result<()> {
+        self.cancel_all(None).await?;
+        Ok(())
+    }
+
+    /// Fee rates depend on the 30 days volume of the account, not on the pair
+    async fn fees(&self, _pair: Pair) -> Result<FeeRates> {
+        let result = self.get_fees().await?;
+        Ok(FeeRates::new(helpers::from_json_bigdecimal(&result["maker_fee_rate"], "maker_fee_rate")?,
+                         helpers::from_json_bigdecimal(&result["taker_fee_rate"], "taker_fee_rate")?))
+    }
+
+    /// Return the available balance of each currency, one account per currency
+    async fn balances(&self) -> Result<Balances> {
+        let result = self.get_accounts().await?;
+        let accounts = result.as_array().ok_or_else(|| Error::InvalidFieldFormat("accounts".to_string()))?;
 
         let mut balances = Balances::new();
 
-        for (key, val) in result.iter() {
-            let currency = utils::get_currency_enum(key);
+        for account in accounts {
+            let currency = account["currency"].as_str().and_then(utils::get_currency_from_symbol);
+            if let Some(c) = currency {
+                balances.insert(c, helpers::from_json_bigdecimal(&account["available"], "available")?);
+            }
+        }
+
+        Ok(balances)
+    }
 
-            match currency {
-                Some(c) => {
-                    let amount = helpers::from_json_bigdecimal(&val, "amount")?;
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        let result = self.get_accounts().await?;
+        let accounts = result.as_array().ok_or_else(|| Error::InvalidFieldFormat("accounts".to_string()))?;
 
-                    balances.insert(c, amount);
-                },
-                _ => ()
+        let mut balances = DetailedBalances::new();
+
+        for account in accounts {
+            let currency = account["currency"].as_str().and_then(utils::get_currency_from_symbol);
+            if let Some(c) = currency {
+                let available = helpers::from_json_bigdecimal(&account["available"], "available")?;
+                let hold = helpers::from_json_bigdecimal(&account["hold"], "hold")?;
+                balances.insert(c, Balance::new(available, hold));
             }
         }
 
         Ok(balances)
     }
+
+    async fn withdraw(&self, currency: Currency, amount: Amount, destination: &str) -> Result<WithdrawalInfo> {
+        let currency_name = utils::get_currency_string(currency).ok_or(Error::InvalidArguments)?;
+        let result = self.withdraw_crypto(&currency_name, &amount.to_string(), destination).await?;
+        Ok(WithdrawalInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: result["id"].as_str().map(|id| id.to_string()),
+        })
+    }
+
+    /// Gdax charges the fee in the quote currency, pages are walked with the trade id of their
+    /// oldest fill as cursor
+    async fn fills_page(&self, pair: Pair, cursor: Option<String>) -> Result<FillsPage> {
+        let result = self.get_fills(None, Some(pair), cursor.as_deref().unwrap_or_default()).await?;
+        let fills = result.as_array().ok_or_else(|| Error::InvalidFieldFormat("fills".to_string()))?;
+        let fills = fills.iter().map(|f| utils::parse_fill(pair, f)).collect::<Result<Vec<FillEvent>>>()?;
+        let next = fills.iter().filter_map(|f| f.trade_id.as_ref()?.parse::<u64>().ok()).min().map(|id| id.to_string());
+        Ok(FillsPage { fills, next })
+    }
+
+    async fn time(&self) -> Result<i64> {
+        let sent_ms = helpers::get_unix_timestamp_ms();
+        let result = self.get_time().await?;
+        let epoch = result.get("epoch").and_then(|t| t.as_f64()).ok_or_else(|| Error::MissingField("epoch".to_string()))?;
+        let server_ms = (epoch * 1000.0) as i64;
+        self.clock.observe(server_ms, sent_ms, helpers::get_unix_timestamp_ms());
+        Ok(server_ms)
+    }
+
+    fn clock_skew(&self) -> Option<i64> {
+        self.clock.skew()
+    }
+
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        let raw_response = self.return_products().await?;
+        let products = raw_response["data"].as_array().ok_or(Error::BadParse)?;
+        // Delisted and halted products are listed but cannot be traded
+        Ok(products.iter()
+            .filter(|p| p["status"] == "online" && p["trading_disabled"] != true)
+            .filter_map(|p| p["id"].as_str().and_then(utils::get_pair_enum).copied())
+            .collect())
+    }
+
+    fn limits(&self) -> RateLimitStatus {
+        self.rate_limits.status()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Gdax.capabilities()
+    }
+}
+
+impl GdaxApi {
+    /// Place a limit or market order
+    async fn simple_order(&self, request: OrderRequest) -> Result<OrderInfo> {
+        let pair = request.pair;
+        let quantity = request.quantity.clone();
+        let options = LimitOrderOptions { client_oid: request.client_id.clone(), ..LimitOrderOptions::default() };
+        let result = match request.order_type {
+            OrderType::BuyLimit => {
+                let price = request.price.clone().ok_or(Error::MissingPrice)?;
+                self.buy_limit(pair, quantity, price, options).await
+            }
+            OrderType::BuyMarket => self.market_order("buy", pair, quantity, request.client_id.as_deref()).await,
+            OrderType::SellLimit => {
+                let price = request.price.clone().ok_or(Error::MissingPrice)?;
+                self.sell_limit(pair, quantity, price, options).await
+            }
+            OrderType::SellMarket => self.market_order("sell", pair, quantity, request.client_id.as_deref()).await,
+            _ => return Err(Error::OrderTypeUnsupported),
+        }?;
+
+        Ok(OrderInfo {
+               timestamp: helpers::get_unix_timestamp_ms(),
+               identifier: vec![result["id"]
+                                    .as_str()
+                                    .ok_or_else(|| {
+                                                    Error::MissingField("id".to_string())
+                                                })?
+                                    .to_string()],
+               raw: helpers::raw_payload(self.keep_raw, &result),
+               request: Some(request),
+           })
+    }
+}
+
+/// The order answered by a lookup of client oid, unknown ids and orders of other products are
+/// not found
+fn client_order(pair: Pair, lookup: Result<Value>, keep_raw: bool) -> Result<Option<OrderInfo>> {
+    let result = match lookup {
+        Ok(result) => result,
+        Err(e) => return match e.kind() {
+            Error::OrderNotFound => Ok(None),
+            _ => Err(e),
+        },
+    };
+    let product_id = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+    if !result["product_id"].as_str().map_or(false, |p| p.eq_ignore_ascii_case(product_id)) {
+        return Ok(None);
+    }
+    Ok(Some(OrderInfo {
+        timestamp: helpers::get_unix_timestamp_ms(),
+        identifier: vec![result["id"].as_str().ok_or_else(|| Error::MissingField("id".to_string()))?.to_string()],
+        raw: helpers::raw_payload(keep_raw, &result),
+        request: None,
+    }))
+}
+
+#[cfg(test)]
+mod gdax_generic_api_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn client_oids_are_looked_up_on_the_pair() {
+        let order = json!({"id": "d50ec984-77a8-460a-b958-66f114b0de9b", "product_id": "BTC-USD", "size": "0.01"});
+        let found = client_order(Pair::BTC_USD, Ok(order.clone()), false).unwrap().unwrap();
+        assert_eq!(found.identifier, vec!["d50ec984-77a8-460a-b958-66f114b0de9b".to_string()]);
+        assert!(client_order(Pair::ETH_USD, Ok(order), false).unwrap().is_none());
+        assert!(client_order(Pair::BTC_USD, Err(Error::OrderNotFound), false).unwrap().is_none());
+        assert!(client_order(Pair::BTC_USD, Err(Error::BadCredentials), false).is_err());
+    }
 }
@@ -1,4 +1,6 @@
 //! Use this module to interact with Bitstamp exchange.
+//! Deprecated : the Coinbase Pro API is retired, `Coinnect` builds the clients of `Exchange::Gdax`
+//! with the `coinbase` module when its feature is enabled.
 
 pub mod api;
 pub mod generic_api;
@@ -6,4 +8,4 @@ pub mod credentials;
 pub mod utils;
 
 pub use self::credentials::GdaxCreds;
-pub use self::api::GdaxApi;
+pub use self::api::{GdaxApi, LimitOrderOptions};
@@ -1,5 +1,7 @@
 use bidir_map::BidirMap;
+use chrono::DateTime;
 
+use data_encoding::BASE64;
 use hmac::{Hmac, Mac};
 use sha2::{Sha256};
 
@@ -8,8 +10,9 @@ use serde_json::Value;
 use serde_json::value::Map;
 
 use crate::error::*;
+use crate::exchange::{Environment, Exchange};
 use crate::helpers;
-use crate::types::Currency;
+use crate::types::{Currency, FillEvent, Liquidity, TradeType};
 use crate::types::Pair;
 use crate::types::Pair::*;
 
@@ -42,35 +45,45 @@ pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
     PAIRS_STRING.get_by_second(&pair)
 }
 
-pub fn build_signature(nonce: &str,
-                       passphrase: &str,
-                       api_key: &str,
+/// Every pair with a Gdax name
+pub fn supported_pairs() -> Vec<Pair> {
+    PAIRS_STRING.iter().map(|(pair, _)| *pair).collect()
+}
+
+/// CB-ACCESS-SIGN header : the base64 encoded HMAC-SHA256, keyed with the base64 decoded secret,
+/// of the timestamp, the HTTP method, the request path (with its query string) and the body
+pub fn build_signature(timestamp: &str,
+                       method: &str,
+                       request_path: &str,
+                       body: &str,
                        api_secret: &str)
                        -> Result<String> {
-    const C: &'static [u8] = b"0123456789ABCDEF";
-
-    let message = nonce.to_owned() + passphrase + api_key;
-
-    let mut mac = Hmac::<Sha256>::new(api_secret.as_bytes());
+    let message = format!("{}{}{}{}", timestamp, method, request_path, body);
 
+    let hmac_key = BASE64.decode(api_secret.as_bytes())?;
+    let mut mac = Hmac::<Sha256>::new(&hmac_key[..]);
     mac.input(message.as_bytes());
-    let result = mac.result();
+    Ok(BASE64.encode(mac.result().code()))
+}
+
+pub const PRODUCTION_URL: &str = "https://api.gdax.com";
+pub const SANDBOX_URL: &str = "https://api-public.sandbox.pro.coinbase.com";
 
-    let raw_signature = result.code();
-    let mut signature = Vec::with_capacity(raw_signature.len() * 2);
-    for &byte in raw_signature {
-        signature.push(C[(byte >> 4) as usize]);
-        signature.push(C[(byte & 0xf) as usize]);
+/// REST root of an environment
+pub fn base_url(environment: Environment) -> &'static str {
+    match environment {
+        Environment::Production => PRODUCTION_URL,
+        Environment::Sandbox => SANDBOX_URL,
     }
-    // TODO: Handle correctly the from_utf8 errors with error_chain.
-    Ok(String::from_utf8(signature)?)
 }
 
-pub fn build_url(method: &str, pair: &str) -> String {
+pub fn build_url(base_url: &str, method: &str, pair: &str) -> String {
     match method {
-        "ticker" => "https://api.gdax.com/products/".to_string() + pair + "/ticker",
-        "order_book" => "https://api.gdax.com/products/".to_string() + pair + "/book",
-        "transactions" => "https://api.gdax.com/accounts/".to_string() + pair + "/ledger",
+        "ticker" => format!("{}/products/{}/ticker", base_url, pair),
+        "order_book" => format!("{}/products/{}/book", base_url, pair),
+        "transactions" => format!("{}/accounts/{}/ledger", base_url, pair),
+        "time" => format!("{}/time", base_url),
+        "products" => format!("{}/products", base_url),
         _ => "not implemented yet".to_string(),
     }
 }
@@ -78,12 +91,12 @@ pub fn build_url(method: &str, pair: &str) -> String {
 pub fn deserialize_json(json_string: &str) -> Result<Map<String, Value>> {
     let data: Value = match serde_json::from_str(json_string) {
         Ok(data) => data,
-        Err(_) => return Err(ErrorKind::BadParse.into()),
+        Err(_) => return Err(Error::BadParse),
     };
 
     match data.as_object() {
         Some(value) => Ok(value.clone()),
-        None => Err(ErrorKind::BadParse.into()),
+        None => Err(Error::BadParse),
     }
 }
 
@@ -97,20 +110,30 @@ pub fn generate_nonce(fixed_nonce: Option<String>) -> String {
 /// If error array is null, return the result (encoded in a json object)
 /// else return the error string found in array
 pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>> {
-    let error_msg = match response.get("error") {
-        Some(error) => {
-            error
-                .as_str()
-                .ok_or_else(|| ErrorKind::InvalidFieldFormat("error".to_string()))?
-        }
+    // Errors come as {"message": "..."}, possibly with extra fields
+    let error = match response.get("error") {
+        Some(error) => error,
+        None if response.len() <= 2 && response.contains_key("message") => &response["message"],
         None => return Ok(response.clone()),
     };
-
-    match error_msg.as_ref() {
-        "Invalid command." => Err(ErrorKind::InvalidArguments.into()),
-        "Invalid API key/secret pair." => Err(ErrorKind::BadCredentials.into()),
-        "Total must be at least 0.0001." => Err(ErrorKind::InsufficientOrderSize.into()),
-        other => Err(ErrorKind::ExchangeSpecificError(other.to_string()).into()),
+    let error_msg = error
+        .as_str()
+        .ok_or_else(|| Error::InvalidFieldFormat("error".to_string()))?;
+
+    let lowercase = error_msg.to_lowercase();
+    match error_msg {
+        "Invalid command." => Err(Error::InvalidArguments),
+        "Invalid API key/secret pair." | "Invalid API Key" | "invalid signature" => Err(Error::BadCredentials),
+        "Total must be at least 0.0001." => Err(Error::InsufficientOrderSize),
+        "NotFound" => Err(Error::OrderNotFound),
+        _ if lowercase.contains("insufficient funds") => Err(Error::InsufficientFunds),
+        _ if lowercase.contains("order not found") => Err(Error::OrderNotFound),
+        _ if lowercase.contains("rate limit exceeded") => Err(Error::RateLimited(None)),
+        _ if lowercase.contains("request timestamp expired") => Err(Error::InvalidNonce),
+        _ if lowercase.contains("is too small") || lowercase.contains("size is too") => Err(Error::InsufficientOrderSize),
+        _ if lowercase.contains("invalid passphrase") => Err(Error::BadCredentials),
+        _ if lowercase.contains("forbidden") => Err(Error::PermissionDenied),
+        other => Err(Error::ExchangeSpecificError(other.to_string())),
     }
 }
 
@@ -163,3 +186,52 @@ pub fn get_currency_string(currency: Currency) -> Option<String> {
         _ => None,
     }
 }
+
+/// Return the currency enum associated with the symbol used by Gdax, e.g. "BTC".
+/// If no currency is found, return None
+pub fn get_currency_from_symbol(symbol: &str) -> Option<Currency> {
+    Currency::from_alias(Exchange::Gdax, symbol)
+}
+
+/// A fill of the fills list, Gdax charges the fee in the quote currency
+pub fn parse_fill(pair: Pair, fill: &Value) -> Result<FillEvent> {
+    let created_at = fill["created_at"].as_str().ok_or_else(|| Error::MissingField("created_at".to_string()))?;
+    let event_ms = DateTime::parse_from_rfc3339(created_at)
+        .map_err(|_| Error::InvalidFieldFormat(created_at.to_string()))?
+        .timestamp_millis();
+    Ok(FillEvent {
+        event_ms,
+        order_id: fill["order_id"].as_str().ok_or_else(|| Error::MissingField("order_id".to_string()))?.to_string(),
+        trade_id: Some(fill["trade_id"].to_string()),
+        pair,
+        side: TradeType::parse(fill["side"].as_str().unwrap_or_default()),
+        price: helpers::from_json_bigdecimal(&fill["price"], "price")?,
+        amount: helpers::from_json_bigdecimal(&fill["size"], "size")?,
+        fee: helpers::from_json_bigdecimal(&fill["fee"], "fee")?,
+        fee_currency: crate::utils::pair_currencies(&pair).map(|(_, quote)| quote),
+        liquidity: match fill["liquidity"].as_str() {
+            Some("M") => Liquidity::Maker,
+            Some("T") => Liquidity::Taker,
+            _ => Liquidity::Unknown,
+        },
+    })
+}
+
+#[cfg(test)]
+mod gdax_utils_tests {
+    use super::*;
+
+    #[test]
+    fn fills_are_parsed() {
+        let fill = serde_json::json!({"trade_id": 74, "product_id": "BTC-USD", "price": "10.00", "size": "0.01",
+            "order_id": "d50ec984-77a8-460a-b958-66f114b0de9b", "created_at": "2014-11-07T22:19:28.578544Z",
+            "liquidity": "M", "fee": "0.00025", "settled": true, "side": "sell"});
+        let fill = parse_fill(Pair::BTC_USD, &fill).unwrap();
+        assert_eq!(fill.event_ms, 1415398768578);
+        assert_eq!(fill.trade_id, Some("74".to_string()));
+        assert_eq!(fill.side, TradeType::Sell);
+        assert_eq!(fill.amount, "0.01".parse().unwrap());
+        assert_eq!(fill.fee_currency, Some(Currency::USD));
+        assert_eq!(fill.liquidity, Liquidity::Maker);
+    }
+}
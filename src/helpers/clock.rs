@@ -0,0 +1,74 @@
+//! Offset between the local clock and the clock of an exchange.
+//! Signed requests carry a timestamp that exchanges reject when it is too far from their own clock.
+//! `ServerClock` estimates the skew from the server time of the exchange, and corrects the
+//! timestamps of the requests once the skew exceeds a threshold.
+
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+
+use crate::helpers;
+
+/// Skew under which timestamps are left untouched, in ms
+pub const DEFAULT_SKEW_THRESHOLD_MS: i64 = 500;
+
+#[derive(Debug)]
+pub struct ServerClock {
+    skew_ms: AtomicI64,
+    synced: AtomicBool,
+    threshold_ms: i64,
+}
+
+impl Default for ServerClock {
+    fn default() -> Self {
+        ServerClock::with_threshold(DEFAULT_SKEW_THRESHOLD_MS)
+    }
+}
+
+impl ServerClock {
+    pub fn with_threshold(threshold_ms: i64) -> Self {
+        ServerClock { skew_ms: AtomicI64::new(0), synced: AtomicBool::new(false), threshold_ms }
+    }
+
+    /// Record the time `server_ms` answered by the exchange to a request sent at `sent_ms` and
+    /// answered at `received_ms`, both local. Returns the estimated skew, positive when the
+    /// exchange is ahead.
+    pub fn observe(&self, server_ms: i64, sent_ms: i64, received_ms: i64) -> i64 {
+        let skew = server_ms - (sent_ms + received_ms) / 2;
+        self.skew_ms.store(skew, Ordering::SeqCst);
+        self.synced.store(true, Ordering::SeqCst);
+        if skew.abs() > self.threshold_ms {
+            warn!("Local clock is {} ms away from the exchange, correcting request timestamps", skew);
+        }
+        skew
+    }
+
+    /// The last estimated skew in ms, `None` if the clock was never observed
+    pub fn skew(&self) -> Option<i64> {
+        if self.synced.load(Ordering::SeqCst) {
+            Some(self.skew_ms.load(Ordering::SeqCst))
+        } else {
+            None
+        }
+    }
+
+    /// The local time in ms, corrected with the skew when it exceeds the threshold
+    pub fn now_ms(&self) -> i64 {
+        let skew = self.skew().unwrap_or(0);
+        let now = helpers::get_unix_timestamp_ms();
+        if skew.abs() > self.threshold_ms { now + skew } else { now }
+    }
+}
+
+#[cfg(test)]
+mod clock_tests {
+    use super::*;
+
+    #[test]
+    fn corrects_skews_over_the_threshold() {
+        let clock = ServerClock::with_threshold(1000);
+        assert_eq!(clock.skew(), None);
+        assert_eq!(clock.observe(10_500, 10_000, 10_200), 400);
+        assert!((clock.now_ms() - helpers::get_unix_timestamp_ms()).abs() < 100);
+        assert_eq!(clock.observe(5_000, 10_000, 10_000), -5000);
+        assert!((clock.now_ms() + 5000 - helpers::get_unix_timestamp_ms()).abs() < 100);
+    }
+}
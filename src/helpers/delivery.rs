@@ -0,0 +1,234 @@
+//! Delivery of events to the recipients of a stream.
+//! `Recipient::do_send` queues events past the mailbox capacity of a recipient and fails once its
+//! actor stopped. `Delivery` applies a `DeliveryPolicy` to the events a full mailbox cannot take
+//! yet, counts the events it drops, and unregisters the recipients whose actor stopped. The
+//! events waiting for a full mailbox go out with the next event or the next `Delivery::flush`,
+//! which the websocket actors run every `FLUSH_INTERVAL`.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use actix::prelude::SendError;
+use actix::{Message, Recipient};
+
+use crate::metrics;
+
+/// How often the websocket actors send the events waiting for a full mailbox
+pub const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to do with the events a recipient cannot take yet, defaults to `Block`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum DeliveryPolicy {
+    /// Never drop events, they are queued in the mailbox of the recipient past its capacity.
+    /// Streaming handlers run on the event loop and cannot wait for a slow recipient.
+    Block,
+    /// Keep at most this many events waiting for a full mailbox, dropping the oldest ones
+    DropOldest(usize),
+    /// Keep at most this many events waiting for a full mailbox, dropping the newest ones
+    Buffer(usize),
+}
+
+impl DeliveryPolicy {
+    fn label(&self) -> &'static str {
+        match self {
+            DeliveryPolicy::Block => "block",
+            DeliveryPolicy::DropOldest(_) => "drop_oldest",
+            DeliveryPolicy::Buffer(_) => "buffer",
+        }
+    }
+}
+
+impl Default for DeliveryPolicy {
+    fn default() -> Self {
+        DeliveryPolicy::Block
+    }
+}
+
+struct Outbox<M> where M: Message + Send, M::Result: Send {
+    recipient: Recipient<M>,
+    pending: VecDeque<M>,
+}
+
+impl<M> Outbox<M> where M: Message + Send, M::Result: Send {
+    /// Deliver `msg` after the events still pending, returns the number of events dropped,
+    /// or `None` if the recipient stopped
+    fn deliver(&mut self, msg: M, policy: DeliveryPolicy) -> Option<usize> {
+        let max = match policy {
+            DeliveryPolicy::Block => return self.recipient.do_send(msg).ok().map(|_| 0),
+            DeliveryPolicy::DropOldest(max) | DeliveryPolicy::Buffer(max) => max,
+        };
+        self.pending.push_back(msg);
+        if !self.flush() {
+            return None;
+        }
+        let dropped = self.pending.len().saturating_sub(max);
+        match policy {
+            DeliveryPolicy::DropOldest(_) => { self.pending.drain(..dropped); }
+            _ => self.pending.truncate(max),
+        }
+        Some(dropped)
+    }
+
+    /// Send the pending events until the mailbox is full, returns false if the recipient stopped
+    fn flush(&mut self) -> bool {
+        while let Some(msg) = self.pending.pop_front() {
+            match self.recipient.try_send(msg) {
+                Ok(()) => (),
+                Err(SendError::Full(msg)) => {
+                    self.pending.push_front(msg);
+                    break;
+                }
+                Err(SendError::Closed(_)) => return false,
+            }
+        }
+        true
+    }
+}
+
+/// The recipients of a stream and the events waiting for them
+pub struct Delivery<M> where M: Message + Send, M::Result: Send {
+    policy: DeliveryPolicy,
+    outboxes: Vec<Outbox<M>>,
+    dropped: u64,
+}
+
+impl<M> Delivery<M> where M: Message + Send + Clone, M::Result: Send {
+    pub fn new(recipients: Vec<Recipient<M>>, policy: DeliveryPolicy) -> Self {
+        let mut delivery = Delivery { policy, outboxes: vec![], dropped: 0 };
+        for r in recipients {
+            delivery.add(r);
+        }
+        delivery
+    }
+
+    pub fn add(&mut self, recipient: Recipient<M>) {
+        self.outboxes.push(Outbox { recipient, pending: VecDeque::new() });
+    }
+
+    /// Number of registered recipients
+    pub fn len(&self) -> usize {
+        self.outboxes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outboxes.is_empty()
+    }
+
+    /// Number of events dropped since the creation of the delivery
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Number of events waiting for a full mailbox, over every recipient
+    pub fn pending(&self) -> usize {
+        self.outboxes.iter().map(|o| o.pending.len()).sum()
+    }
+
+    /// Send `msg` to every recipient, recipients that stopped are unregistered
+    pub fn send(&mut self, msg: M) {
+        let policy = self.policy;
+        let mut i = 0;
+        while i < self.outboxes.len() {
+            match self.outboxes[i].deliver(msg.clone(), policy) {
+                Some(dropped) => {
+                    if dropped > 0 {
+                        self.dropped += dropped as u64;
+                        metrics::dropped_events(policy.label(), dropped);
+                    }
+                    i += 1;
+                }
+                None => {
+                    debug!("Recipient stopped, unregistering it");
+                    self.outboxes.remove(i);
+                }
+            }
+        }
+    }
+
+    /// Send the events waiting for a full mailbox without a new event, recipients that stopped
+    /// are unregistered
+    pub fn flush(&mut self) {
+        let mut i = 0;
+        while i < self.outboxes.len() {
+            if self.outboxes[i].flush() {
+                i += 1;
+            } else {
+                debug!("Recipient stopped, unregistering it");
+                self.outboxes.remove(i);
+            }
+        }
+    }
+}
+
+impl<M> fmt::Debug for Delivery<M> where M: Message + Send, M::Result: Send {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Delivery")
+            .field("policy", &self.policy)
+            .field("recipients", &self.outboxes.len())
+            .field("dropped", &self.dropped)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod delivery_tests {
+    use actix::{Actor, Context, Handler, System};
+
+    use super::*;
+
+    #[derive(Message, Clone)]
+    #[rtype(result = "()")]
+    struct Event(usize);
+
+    struct Slow;
+
+    impl Actor for Slow {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<Event> for Slow {
+        type Result = ();
+
+        fn handle(&mut self, _msg: Event, _ctx: &mut Context<Self>) {}
+    }
+
+    #[test]
+    fn full_mailboxes_drop_events() {
+        System::new("delivery_tests").block_on(async {
+            // The actor does not run before the future yields, its mailbox fills up
+            let mut delivery = Delivery::new(vec![Slow.start().recipient()], DeliveryPolicy::DropOldest(4));
+            for i in 0..100 {
+                delivery.send(Event(i));
+            }
+            assert_eq!(delivery.pending(), 4);
+            assert!(delivery.dropped() > 0);
+            assert_eq!(delivery.outboxes[0].pending.back().map(|e| e.0), Some(99));
+
+            let mut delivery = Delivery::new(vec![Slow.start().recipient()], DeliveryPolicy::Buffer(4));
+            for i in 0..100 {
+                delivery.send(Event(i));
+            }
+            assert_eq!(delivery.pending(), 4);
+            assert!(delivery.outboxes[0].pending.back().map_or(false, |e| e.0 < 99));
+        });
+    }
+
+    #[test]
+    fn pending_events_are_flushed_without_new_events() {
+        System::new("delivery_tests").block_on(async {
+            let mut delivery = Delivery::new(vec![Slow.start().recipient()], DeliveryPolicy::Buffer(1000));
+            for i in 0..100 {
+                delivery.send(Event(i));
+            }
+            assert!(delivery.pending() > 0);
+            // The mailbox takes a few events at each flush once the actor drained it
+            for _ in 0..50 {
+                tokio::time::delay_for(Duration::from_millis(10)).await;
+                delivery.flush();
+            }
+            assert_eq!(delivery.pending(), 0);
+            assert_eq!(delivery.dropped(), 0);
+        });
+    }
+}
@@ -0,0 +1,191 @@
+//! Failover between the endpoints of an exchange.
+//! The first endpoint is the primary one, the next ones are regional mirrors used as backups.
+//! After `max_errors` consecutive connection errors the next endpoint becomes active and a
+//! `FailoverEvent` is sent to the subscribers. `recover_primary` switches back once the primary
+//! endpoint answers again.
+
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use actix::Recipient;
+use chrono::Utc;
+use hyper::Uri;
+
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::helpers::proxy::HttpClient;
+
+/// Consecutive connection errors before switching endpoint
+pub const DEFAULT_MAX_ERRORS: u32 = 3;
+
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct FailoverEvent {
+    pub exchange: Exchange,
+    /// UNIX timestamp in ms of the switch
+    pub timestamp: i64,
+    pub from: String,
+    pub to: String,
+}
+
+/// The active endpoint and the error count can be updated through a shared reference, so that
+/// REST clients can be shared between threads
+pub struct Endpoints {
+    exchange: Exchange,
+    urls: Vec<String>,
+    active: AtomicUsize,
+    errors: AtomicU32,
+    max_errors: u32,
+    recipients: Mutex<Vec<Recipient<FailoverEvent>>>,
+}
+
+impl Clone for Endpoints {
+    fn clone(&self) -> Self {
+        Endpoints {
+            exchange: self.exchange,
+            urls: self.urls.clone(),
+            active: AtomicUsize::new(self.active.load(Ordering::SeqCst)),
+            errors: AtomicU32::new(self.errors.load(Ordering::SeqCst)),
+            max_errors: self.max_errors,
+            recipients: Mutex::new(self.recipients.lock().unwrap().clone()),
+        }
+    }
+}
+
+impl fmt::Debug for Endpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Endpoints")
+            .field("exchange", &self.exchange)
+            .field("urls", &self.urls)
+            .field("active", &self.active)
+            .field("errors", &self.errors)
+            .finish()
+    }
+}
+
+impl Endpoints {
+    /// Endpoints in order of preference, `urls` must not be empty
+    pub fn new(exchange: Exchange, urls: Vec<String>) -> Self {
+        assert!(!urls.is_empty(), "no endpoint for {:?}", exchange);
+        Endpoints { exchange, urls, active: AtomicUsize::new(0), errors: AtomicU32::new(0), max_errors: DEFAULT_MAX_ERRORS, recipients: Mutex::new(vec![]) }
+    }
+
+    pub fn with_max_errors(mut self, max_errors: u32) -> Self {
+        self.max_errors = max_errors.max(1);
+        self
+    }
+
+    /// Send a `FailoverEvent` to `recipient` on every switch
+    pub fn subscribe(&self, recipient: Recipient<FailoverEvent>) {
+        self.recipients.lock().unwrap().push(recipient);
+    }
+
+    /// The active endpoint
+    pub fn current(&self) -> &str {
+        &self.urls[self.active.load(Ordering::SeqCst)]
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.active.load(Ordering::SeqCst) == 0
+    }
+
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Record the outcome of a request, switching endpoint on sustained connection errors
+    pub fn track<T>(&self, result: Result<T>) -> Result<T> {
+        match &result {
+            Ok(_) => self.report_success(),
+            Err(e) if is_connection_error(e) => self.report_error(),
+            Err(_) => (),
+        }
+        result
+    }
+
+    pub fn report_success(&self) {
+        self.errors.store(0, Ordering::SeqCst);
+    }
+
+    /// Count a connection error, returns whether the active endpoint changed
+    pub fn report_error(&self) -> bool {
+        let errors = self.errors.fetch_add(1, Ordering::SeqCst) + 1;
+        if errors < self.max_errors || self.urls.len() == 1 {
+            return false;
+        }
+        let active = self.active.load(Ordering::SeqCst);
+        self.switch(active, (active + 1) % self.urls.len())
+    }
+
+    /// Switch back to the primary endpoint if it answers, returns whether it is active
+    pub async fn recover_primary(&self, client: &HttpClient) -> bool {
+        if self.is_primary() {
+            return true;
+        }
+        let uri: Uri = match self.urls[0].parse() {
+            Ok(uri) => uri,
+            Err(_) => return false,
+        };
+        match client.get(uri).await {
+            Ok(_) => {
+                self.switch(self.active.load(Ordering::SeqCst), 0);
+                true
+            }
+            Err(e) => {
+                debug!("{:?} : primary endpoint {} still down : {}", self.exchange, self.urls[0], e);
+                false
+            }
+        }
+    }
+
+    /// Switch from `from` to `to`, returns false if another request switched first
+    fn switch(&self, from: usize, to: usize) -> bool {
+        if self.active.compare_exchange(from, to, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+            return false;
+        }
+        self.errors.store(0, Ordering::SeqCst);
+        let event = FailoverEvent {
+            exchange: self.exchange,
+            timestamp: Utc::now().timestamp_millis(),
+            from: self.urls[from].clone(),
+            to: self.urls[to].clone(),
+        };
+        warn!("{:?} : switching endpoint from {} to {}", event.exchange, event.from, event.to);
+        for r in self.recipients.lock().unwrap().iter() {
+            if let Err(e) = r.do_send(event.clone()) {
+                trace!("Failover event not delivered : {}", e);
+            }
+        }
+        true
+    }
+}
+
+/// Errors hinting that the endpoint itself is unreachable, as opposed to a rejected request
+fn is_connection_error(e: &Error) -> bool {
+    match e {
+        Error::Hyper(_) | Error::Io(_) | Error::ServiceUnavailable(_) | Error::BackoffConnectionTimeout(_) => true,
+        Error::Chained { kind, .. } => is_connection_error(kind),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod failover_tests {
+    use super::*;
+
+    #[test]
+    fn switch_on_sustained_errors() {
+        let urls = vec!["https://api.example.com".to_string(), "https://eu.api.example.com".to_string()];
+        let endpoints = Endpoints::new(Exchange::Kraken, urls).with_max_errors(2);
+        let unavailable = || Err::<(), Error>(Error::ServiceUnavailable("down".to_string()));
+        assert!(endpoints.track(unavailable()).is_err());
+        assert!(endpoints.track(Ok(())).is_ok());
+        assert!(endpoints.track(unavailable()).is_err());
+        assert!(endpoints.is_primary());
+        assert!(endpoints.track(Err::<(), Error>(Error::BadParse)).is_err());
+        assert!(endpoints.is_primary());
+        assert!(endpoints.track(unavailable()).is_err());
+        assert_eq!(endpoints.current(), "https://eu.api.example.com");
+    }
+}
@@ -1,36 +1,39 @@
 use serde_json::{Value, Map};
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
 use bytes::Buf;
 use bytes::buf::ext::Reader;
+use std::collections::HashSet;
+use std::fmt;
 use crate::error::*;
 
 pub fn deserialize_json(json_string: &str) -> Result<Map<String, Value>> {
     let data: Value = match serde_json::from_str(json_string) {
         Ok(data) => data,
-        Err(_) => return Err(ErrorKind::BadParse.into()),
+        Err(_) => return Err(Error::BadParse),
     };
 
     match data.as_object() {
         Some(value) => Ok(value.clone()),
-        None => Err(ErrorKind::BadParse.into()),
+        None => Err(Error::BadParse),
     }
 }
 
 pub fn deserialize_json_r<B>(reader: Reader<B>) -> Result<Map<String, Value>> where B: Buf {
     let data: Value = match serde_json::from_reader(reader) {
         Ok(data) => data,
-        Err(_) => return Err(ErrorKind::BadParse.into()),
+        Err(_) => return Err(Error::BadParse),
     };
 
     match data.as_object() {
         Some(value) => Ok(value.clone()),
-        None => Err(ErrorKind::BadParse.into()),
+        None => Err(Error::BadParse),
     }
 }
 
 pub fn deserialize_json_array(json_string: &str) -> Result<Map<String, Value>> {
     let data: Value = match serde_json::from_str(json_string) {
         Ok(data) => data,
-        Err(_) => return Err(ErrorKind::BadParse.into()),
+        Err(_) => return Err(Error::BadParse),
     };
 
     if data.is_array() {
@@ -40,7 +43,7 @@ pub fn deserialize_json_array(json_string: &str) -> Result<Map<String, Value>> {
     }
 
     else {
-        Err(ErrorKind::BadParse.into())
+        Err(Error::BadParse)
     }
 }
 
@@ -48,7 +51,7 @@ pub fn deserialize_json_array(json_string: &str) -> Result<Map<String, Value>> {
 pub fn deserialize_json_array_r<B>(reader: Reader<B>) -> Result<Map<String, Value>> where B: Buf {
     let data: Value = match serde_json::from_reader(reader) {
         Ok(data) => data,
-        Err(_) => return Err(ErrorKind::BadParse.into()),
+        Err(_) => return Err(Error::BadParse),
     };
 
     if data.is_array() {
@@ -58,6 +61,140 @@ pub fn deserialize_json_array_r<B>(reader: Reader<B>) -> Result<Map<String, Valu
     }
 
     else {
-        Err(ErrorKind::BadParse.into())
+        Err(Error::BadParse)
+    }
+}
+
+/// Keys to keep when parsing a large object such as the tickers of every market.
+/// Skipped entries are streamed through without being allocated.
+#[derive(Debug, Clone, Default)]
+pub struct Projection {
+    /// Top level keys to keep, all of them if `None`
+    pub keys: Option<HashSet<String>>,
+    /// Fields to keep in the kept values that are objects, all of them if `None`
+    pub fields: Option<HashSet<String>>,
+}
+
+impl Projection {
+    /// Keep only `keys` at the top level
+    pub fn keys<I, S>(keys: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+        Projection { keys: Some(keys.into_iter().map(Into::into).collect()), fields: None }
+    }
+
+    /// Keep only `fields` in every kept value
+    pub fn with_fields<I, S>(mut self, fields: I) -> Self where I: IntoIterator<Item = S>, S: Into<String> {
+        self.fields = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+}
+
+struct ProjectedMap<'a> {
+    keys: Option<&'a HashSet<String>>,
+    fields: Option<&'a HashSet<String>>,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for ProjectedMap<'a> {
+    type Value = Map<String, Value>;
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<Self::Value, D::Error> where D: Deserializer<'de> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for ProjectedMap<'a> {
+    type Value = Map<String, Value>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON object")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> std::result::Result<Self::Value, A::Error> where A: MapAccess<'de> {
+        let mut map = Map::new();
+        while let Some(key) = access.next_key::<String>()? {
+            if self.keys.map_or(false, |keys| !keys.contains(&key)) {
+                access.next_value::<IgnoredAny>()?;
+                continue;
+            }
+            let value = match self.fields {
+                Some(fields) => access.next_value_seed(ProjectedValue(fields))?,
+                None => access.next_value::<Value>()?,
+            };
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+/// A value whose fields are projected if it is an object, kept as is otherwise
+struct ProjectedValue<'a>(&'a HashSet<String>);
+
+impl<'de, 'a> DeserializeSeed<'de> for ProjectedValue<'a> {
+    type Value = Value;
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<Self::Value, D::Error> where D: Deserializer<'de> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for ProjectedValue<'a> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any JSON value")
+    }
+
+    fn visit_map<A>(self, access: A) -> std::result::Result<Self::Value, A::Error> where A: MapAccess<'de> {
+        ProjectedMap { keys: Some(self.0), fields: None }.visit_map(access).map(Value::Object)
+    }
+
+    fn visit_seq<A>(self, mut access: A) -> std::result::Result<Self::Value, A::Error> where A: SeqAccess<'de> {
+        let mut values = vec![];
+        while let Some(value) = access.next_element::<Value>()? {
+            values.push(value);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn visit_bool<E>(self, v: bool) -> std::result::Result<Self::Value, E> { Ok(Value::from(v)) }
+    fn visit_i64<E>(self, v: i64) -> std::result::Result<Self::Value, E> { Ok(Value::from(v)) }
+    fn visit_u64<E>(self, v: u64) -> std::result::Result<Self::Value, E> { Ok(Value::from(v)) }
+    fn visit_f64<E>(self, v: f64) -> std::result::Result<Self::Value, E> { Ok(Value::from(v)) }
+    fn visit_str<E>(self, v: &str) -> std::result::Result<Self::Value, E> { Ok(Value::from(v)) }
+    fn visit_string<E>(self, v: String) -> std::result::Result<Self::Value, E> { Ok(Value::from(v)) }
+    fn visit_unit<E>(self) -> std::result::Result<Self::Value, E> { Ok(Value::Null) }
+}
+
+/// Parse a JSON object keeping only what `projection` asks for
+pub fn deserialize_json_projected(json_string: &str, projection: &Projection) -> Result<Map<String, Value>> {
+    let mut deserializer = serde_json::Deserializer::from_str(json_string);
+    project(&mut deserializer, projection)
+}
+
+/// Parse a JSON object from a reader keeping only what `projection` asks for
+pub fn deserialize_json_projected_r<B>(reader: Reader<B>, projection: &Projection) -> Result<Map<String, Value>> where B: Buf {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    project(&mut deserializer, projection)
+}
+
+fn project<'de, D>(deserializer: D, projection: &Projection) -> Result<Map<String, Value>> where D: Deserializer<'de> {
+    ProjectedMap { keys: projection.keys.as_ref(), fields: projection.fields.as_ref() }
+        .deserialize(deserializer)
+        .map_err(|_| Error::BadParse)
+}
+
+#[cfg(test)]
+mod json_tests {
+    use super::*;
+
+    #[test]
+    fn project_keys_and_fields() {
+        let tickers = r#"{"error": null, "BTC_LTC": {"last": "0.0251", "lowestAsk": "0.0258", "isFrozen": "0"},
+                          "BTC_NXT": {"last": "0.0000573", "lowestAsk": "0.0000571"},
+                          "BTC_ETH": [1, 2, 3]}"#;
+        let projection = Projection::keys(vec!["error", "BTC_LTC"]).with_fields(vec!["last"]);
+        let map = deserialize_json_projected(tickers, &projection).unwrap();
+        assert_eq!(Value::Object(map), serde_json::json!({"error": null, "BTC_LTC": {"last": "0.0251"}}));
+        let all = deserialize_json_projected(tickers, &Projection::default()).unwrap();
+        assert_eq!(all.len(), 4);
     }
 }
@@ -1,18 +1,37 @@
 
 #![warn(clone_double_ref)]
 
+pub mod clock;
+#[cfg(feature = "native")]
+pub mod delivery;
+#[cfg(feature = "native")]
+pub mod failover;
 pub mod json;
+#[cfg(feature = "native")]
+pub mod proxy;
+#[cfg(feature = "native")]
+pub mod rate_limit;
+pub mod secret;
+#[cfg(feature = "native")]
+pub mod signing;
+#[cfg(feature = "native")]
+pub mod vcr;
 
 use serde_json::Value;
-use bigdecimal::BigDecimal;
+use bigdecimal::{BigDecimal, Zero};
 use std::str::FromStr;
 
 use std::collections::HashMap;
 use chrono::prelude::*;
 use crate::error::*;
+#[cfg(feature = "streaming")]
 use actix_codec::Framed;
+#[cfg(feature = "streaming")]
 use awc::{ws::{Codec}, Client, BoxedSocket};
 use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "streaming")]
+use self::proxy::{Proxy, ProxyConnector};
 
 // Helper functions
 
@@ -42,6 +61,20 @@ pub fn get_unix_timestamp_us() -> i64 {
     (seconds * 1000 * 1000) + (nanoseconds / 1000)
 }
 
+/// A nonce greater than every nonce taken from `last`, the current time in ms unless requests
+/// were signed faster than that or the clock went backwards
+pub fn next_nonce(last: &AtomicU64) -> u64 {
+    let now = get_unix_timestamp_ms() as u64;
+    let mut previous = last.load(Ordering::SeqCst);
+    loop {
+        let nonce = now.max(previous + 1);
+        match last.compare_exchange(previous, nonce, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => return nonce,
+            Err(current) => previous = current,
+        }
+    }
+}
+
 pub fn strip_empties(x: &mut HashMap<&str, &str>) {
     let empties: Vec<_> = x.iter()
         .filter(|&(_, &v)| v.is_empty())
@@ -55,26 +88,66 @@ pub fn strip_empties(x: &mut HashMap<&str, &str>) {
 pub fn get_json_string<'a>(json_obj: &'a Value, key: &str) -> Result<&'a str> {
     Ok(json_obj
            .get(key)
-           .ok_or_else(|| ErrorKind::MissingField(key.to_string()))?
+           .ok_or_else(|| Error::MissingField(key.to_string()))?
            .as_str()
-           .ok_or_else(|| ErrorKind::InvalidFieldFormat(key.to_string()))?)
+           .ok_or_else(|| Error::InvalidFieldFormat(key.to_string()))?)
 }
 
 pub fn from_json_bigdecimal(json_obj: &Value, key: &str) -> Result<BigDecimal> {
     let num = json_obj
         .as_str()
-        .ok_or_else(|| ErrorKind::MissingField(key.to_string()))?;
+        .ok_or_else(|| Error::MissingField(key.to_string()))?;
+
+    Ok(BigDecimal::from_str(num).with_context(|| Error::InvalidFieldFormat(key.to_string()))?)
+}
+
+/// Parse an optional decimal field given either as a json string or number
+pub fn from_json_bigdecimal_opt(json_obj: &Value) -> Option<BigDecimal> {
+    match json_obj {
+        Value::String(s) => BigDecimal::from_str(s).ok(),
+        Value::Number(n) => BigDecimal::from_str(&n.to_string()).ok(),
+        _ => None,
+    }
+}
 
-    Ok(BigDecimal::from_str(num).chain_err(|| ErrorKind::InvalidFieldFormat(key.to_string()))?)
+/// A copy of `payload` for the `raw` field of typed responses, when `keep` is set
+pub fn raw_payload<T: Clone + Into<Value>>(keep: bool, payload: &T) -> Option<Value> {
+    if keep { Some(payload.clone().into()) } else { None }
 }
 
-pub async fn new_ws_client(url: String) -> Result<Framed<BoxedSocket, Codec>> {
+/// Change from `open` to `last` in percent
+pub fn percent_change(open: &BigDecimal, last: &BigDecimal) -> Option<BigDecimal> {
+    if open.is_zero() {
+        return None;
+    }
+    Some((last.clone() - open.clone()) * BigDecimal::from(100) / open.clone())
+}
+
+/// Optional settings of a client that can be given along with its credentials
+pub const CLIENT_SETTINGS: [&str; 8] = ["environment", "rest_url", "ws_url", "proxy", "failover_errors", "cassette", "record_cassette", "debug_signing"];
+
+/// Copy the client settings found in a json credentials object
+pub fn copy_client_settings(json_obj: &Value, data: &mut HashMap<String, String>) {
+    for key in CLIENT_SETTINGS.iter() {
+        if let Some(value) = json_obj.get(*key).and_then(|v| v.as_str()) {
+            data.insert(key.to_string(), value.to_string());
+        }
+    }
+}
+
+/// Connect to a websocket, through `proxy` if any
+#[cfg(feature = "streaming")]
+pub async fn new_ws_client(url: String, proxy: Option<Proxy>) -> Result<Framed<BoxedSocket, Codec>> {
     let ssl = {
         let mut ssl = openssl::ssl::SslConnector::builder(openssl::ssl::SslMethod::tls()).unwrap();
         let _ = ssl.set_alpn_protos(b"\x08http/1.1");
         ssl.build()
     };
-    let connector = awc::Connector::new().ssl(ssl).timeout(Duration::from_secs(1)).finish();
+    let connector = awc::Connector::new()
+        .connector(ProxyConnector::new(proxy))
+        .ssl(ssl)
+        .timeout(Duration::from_secs(1))
+        .finish();
     let client = Client::build()
         .connector(connector)
         .finish();
@@ -85,8 +158,7 @@ pub async fn new_ws_client(url: String) -> Result<Framed<BoxedSocket, Codec>> {
         .await
         .map_err(|e| {
             debug!("Error: {}", e);
-            let e: Error = ErrorKind::WsError(format!("{}", e)).into();
-            e
+            Error::WsError(format!("{}", e))
         })?;
 
     debug!("WS Client Response {:?}", response);
@@ -0,0 +1,218 @@
+//! Outgoing connections through an HTTP or SOCKS5 proxy, shared by the REST clients (hyper) and
+//! the websocket clients (awc). TLS is negotiated with the exchange inside the tunnel.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use actix_connect::{Connect as TcpConnect, ConnectError, Connection as TcpConnection};
+use futures::future::LocalBoxFuture;
+use hyper::client::Client;
+use hyper::{Body, Request, Response, Uri};
+use hyper_tls::HttpsConnector;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::error::*;
+use crate::helpers::vcr::Cassette;
+
+/// The client of every REST api, which records or replays its responses when a cassette is set
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client<HttpsConnector<ProxyConnector>>,
+    cassette: Option<Arc<Cassette>>,
+}
+
+impl HttpClient {
+    /// Record or replay the responses with `cassette`
+    pub fn with_cassette(mut self, cassette: Arc<Cassette>) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    pub async fn get(&self, uri: Uri) -> Result<Response<Body>> {
+        let mut req = Request::new(Body::empty());
+        *req.uri_mut() = uri;
+        self.request(req).await
+    }
+
+    pub async fn request(&self, req: Request<Body>) -> Result<Response<Body>> {
+        match &self.cassette {
+            Some(cassette) => cassette.request(&self.client, req).await,
+            None => Ok(self.client.request(req).await?),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Proxy {
+    /// `http://host:port`, tunnels with the CONNECT method
+    Http(String),
+    /// `socks5://host:port`, without authentication
+    Socks5(String),
+}
+
+impl FromStr for Proxy {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let url = url::Url::parse(input).with_context(|| Error::InvalidFieldFormat("proxy".to_string()))?;
+        let host = url.host_str().ok_or_else(|| Error::InvalidFieldFormat("proxy".to_string()))?;
+        match (url.scheme(), url.port_or_known_default()) {
+            ("http", Some(port)) => Ok(Proxy::Http(format!("{}:{}", host, port))),
+            ("socks5", Some(port)) => Ok(Proxy::Socks5(format!("{}:{}", host, port))),
+            ("socks5", None) => Ok(Proxy::Socks5(format!("{}:1080", host))),
+            _ => Err(Error::InvalidFieldValue("proxy".to_string())),
+        }
+    }
+}
+
+fn proxy_error(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, msg)
+}
+
+async fn http_tunnel(proxy: &str, host: &str, port: u16) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(proxy).await?;
+    let request = format!("CONNECT {0}:{1} HTTP/1.1\r\nHost: {0}:{1}\r\n\r\n", host, port);
+    stream.write_all(request.as_bytes()).await?;
+    // Read the response byte by byte so that nothing past the headers is consumed
+    let mut response = Vec::new();
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte).await? == 0 || response.len() > 8192 {
+            return Err(proxy_error(format!("invalid response from proxy {}", proxy)));
+        }
+        response.push(byte[0]);
+    }
+    let response = String::from_utf8_lossy(&response);
+    match response.split_whitespace().nth(1) {
+        Some("200") => Ok(stream),
+        _ => Err(proxy_error(format!("proxy {} refused the tunnel : {}", proxy, response.lines().next().unwrap_or_default()))),
+    }
+}
+
+/// The connect request of a domain name, which socks5 limits to 255 bytes
+fn socks5_request(host: &str, port: u16) -> io::Result<Vec<u8>> {
+    if host.len() > 255 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("host name too long for socks5 : {}", host)));
+    }
+    let mut request = vec![5, 1, 0, 3, host.len() as u8];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    Ok(request)
+}
+
+async fn socks5_tunnel(proxy: &str, host: &str, port: u16) -> io::Result<TcpStream> {
+    let request = socks5_request(host, port)?;
+    let mut stream = TcpStream::connect(proxy).await?;
+    stream.write_all(&[5, 1, 0]).await?;
+    let mut method = [0u8; 2];
+    stream.read_exact(&mut method).await?;
+    if method != [5, 0] {
+        return Err(proxy_error(format!("socks proxy {} requires authentication", proxy)));
+    }
+    stream.write_all(&request).await?;
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(proxy_error(format!("socks proxy {} failed with code {}", proxy, reply[1])));
+    }
+    // Skip the bound address and port
+    let addr_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        t => return Err(proxy_error(format!("socks proxy {} sent an unknown address type {}", proxy, t))),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(stream)
+}
+
+/// Open a tcp stream to `host:port`, through the proxy if any
+pub async fn connect(proxy: Option<&Proxy>, host: &str, port: u16) -> io::Result<TcpStream> {
+    match proxy {
+        None => TcpStream::connect((host, port)).await,
+        Some(Proxy::Http(addr)) => http_tunnel(addr, host, port).await,
+        Some(Proxy::Socks5(addr)) => socks5_tunnel(addr, host, port).await,
+    }
+}
+
+/// Tcp connector for hyper and awc, connects directly when there is no proxy
+#[derive(Debug, Clone, Default)]
+pub struct ProxyConnector {
+    proxy: Option<Proxy>,
+}
+
+impl ProxyConnector {
+    pub fn new(proxy: Option<Proxy>) -> Self {
+        ProxyConnector { proxy }
+    }
+}
+
+impl tower_service::Service<Uri> for ProxyConnector {
+    type Response = TcpStream;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let proxy = self.proxy.clone();
+        Box::pin(async move {
+            let host = dst.host().ok_or_else(|| proxy_error(format!("no host in {}", dst)))?.to_string();
+            let port = dst.port_u16().unwrap_or(if dst.scheme_str() == Some("https") { 443 } else { 80 });
+            connect(proxy.as_ref(), &host, port).await
+        })
+    }
+}
+
+impl actix_service::Service for ProxyConnector {
+    type Request = TcpConnect<Uri>;
+    type Response = TcpConnection<Uri, TcpStream>;
+    type Error = ConnectError;
+    type Future = LocalBoxFuture<'static, ::std::result::Result<Self::Response, ConnectError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<::std::result::Result<(), ConnectError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: TcpConnect<Uri>) -> Self::Future {
+        let proxy = self.proxy.clone();
+        Box::pin(async move {
+            let stream = connect(proxy.as_ref(), req.host(), req.port()).await.map_err(ConnectError::Io)?;
+            Ok(TcpConnection::new(stream, req.get_ref().clone()))
+        })
+    }
+}
+
+/// A REST client going through `proxy` if any
+pub fn new_http_client(proxy: Option<Proxy>) -> Result<HttpClient> {
+    let tls = native_tls::TlsConnector::new().map_err(|_| Error::TlsError)?;
+    let connector = HttpsConnector::from((ProxyConnector::new(proxy), tokio_tls::TlsConnector::from(tls)));
+    Ok(HttpClient { client: Client::builder().build::<_, hyper::Body>(connector), cassette: None })
+}
+
+#[cfg(test)]
+mod proxy_tests {
+    use super::*;
+
+    #[test]
+    fn parse_proxies() {
+        assert_eq!(Proxy::from_str("http://10.0.0.1:3128").unwrap(), Proxy::Http("10.0.0.1:3128".to_string()));
+        assert_eq!(Proxy::from_str("socks5://localhost").unwrap(), Proxy::Socks5("localhost:1080".to_string()));
+        assert!(Proxy::from_str("ftp://localhost:21").is_err());
+    }
+
+    #[test]
+    fn socks5_host_names_are_limited_to_255_bytes() {
+        assert_eq!(socks5_request("ws.kraken.com", 443).unwrap(), [&[5, 1, 0, 3, 13][..], b"ws.kraken.com", &[1, 187]].concat());
+        assert!(socks5_request(&"a".repeat(255), 443).is_ok());
+        assert_eq!(socks5_request(&"a".repeat(256), 443).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,144 @@
+//! Remaining request budget of a REST client.
+//! `RateLimits` reads the rate limit headers of every response, `X-RateLimit-*` or `RateLimit-*`,
+//! and the `Retry-After` of rate limited responses, so that callers can adapt their polling
+//! frequency with `ExchangeApi::limits`.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+use hyper::HeaderMap;
+use hyper::header::RETRY_AFTER;
+
+use crate::helpers;
+use crate::types::RateLimitStatus;
+
+/// Reset values above this are UNIX timestamps in seconds, below it delays in seconds
+const EPOCH_SECONDS_THRESHOLD: i64 = 1_000_000_000;
+
+#[derive(Debug, Default)]
+pub struct RateLimits {
+    status: Mutex<RateLimitStatus>,
+}
+
+fn header_i64(headers: &HeaderMap, names: &[&str]) -> Option<i64> {
+    names.iter()
+        .filter_map(|name| headers.get(*name))
+        .filter_map(|h| h.to_str().ok())
+        .filter_map(|s| s.trim().parse::<f64>().ok())
+        .map(|v| v as i64)
+        .next()
+}
+
+impl RateLimits {
+    /// Record the headers of a response received at `now_ms`, a status without rate limit headers
+    /// leaves the last known budget untouched
+    pub fn observe(&self, headers: &HeaderMap, now_ms: i64) {
+        let limit = header_i64(headers, &["x-ratelimit-limit", "ratelimit-limit"]);
+        let remaining = header_i64(headers, &["x-ratelimit-remaining", "ratelimit-remaining"]);
+        let reset = header_i64(headers, &["x-ratelimit-reset", "ratelimit-reset"]).map(|reset| {
+            if reset > EPOCH_SECONDS_THRESHOLD { reset * 1000 } else { now_ms + reset * 1000 }
+        });
+        let retry_after = header_i64(headers, &[RETRY_AFTER.as_str()]);
+        if limit.is_none() && remaining.is_none() && reset.is_none() && retry_after.is_none() {
+            return;
+        }
+        let mut status = self.status.lock().unwrap();
+        if limit.is_some() {
+            status.limit = limit.map(|l| l.max(0) as u64);
+        }
+        if remaining.is_some() {
+            status.remaining = remaining.map(|r| r.max(0) as u64);
+        }
+        status.reset_ms = reset.or(status.reset_ms);
+        if let Some(secs) = retry_after {
+            status.remaining = Some(0);
+            status.reset_ms = Some(now_ms + secs.max(0) * 1000);
+        }
+        status.updated_ms = Some(now_ms);
+    }
+
+    /// Record a rate limited response without headers, the budget is exhausted until `retry_after`
+    pub fn exhausted(&self, retry_after: Option<Duration>) {
+        let now_ms = helpers::get_unix_timestamp_ms();
+        let mut status = self.status.lock().unwrap();
+        status.remaining = Some(0);
+        if let Some(delay) = retry_after {
+            status.reset_ms = Some(now_ms + delay.as_millis() as i64);
+        }
+        status.updated_ms = Some(now_ms);
+    }
+
+    /// Delay before the budget resets, if it is exhausted
+    pub fn retry_after(&self, now_ms: i64) -> Option<Duration> {
+        let status = self.status.lock().unwrap();
+        if status.is_exhausted(now_ms) {
+            status.reset_ms.map(|reset| Duration::from_millis((reset - now_ms) as u64))
+        } else {
+            None
+        }
+    }
+
+    pub fn status(&self) -> RateLimitStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
+
+/// Reserve the first request slot at least `interval_ms` after the last one, `last_request` holds
+/// the UNIX timestamp in ms of the last reserved slot. Returns the delay until the slot, so that
+/// concurrent requests are spaced out instead of all waiting for the same one.
+pub fn reserve_slot(last_request: &AtomicI64, interval_ms: i64, now_ms: i64) -> Duration {
+    let mut last = last_request.load(Ordering::SeqCst);
+    loop {
+        let slot = now_ms.max(last + interval_ms);
+        match last_request.compare_exchange(last, slot, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => return Duration::from_millis((slot - now_ms) as u64),
+            Err(current) => last = current,
+        }
+    }
+}
+
+/// Wait for the next request slot, see `reserve_slot`
+pub async fn wait_for_slot(last_request: &AtomicI64, interval_ms: i64) {
+    let wait = reserve_slot(last_request, interval_ms, helpers::get_unix_timestamp_ms());
+    if wait > Duration::from_millis(0) {
+        tokio::time::delay_for(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod rate_limit_tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    #[test]
+    fn headers_update_the_remaining_budget() {
+        let limits = RateLimits::default();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", HeaderValue::from_static("600"));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("598"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("30"));
+        limits.observe(&headers, 10_000);
+        assert_eq!(limits.status(), RateLimitStatus { limit: Some(600), remaining: Some(598), reset_ms: Some(40_000), updated_ms: Some(10_000) });
+        assert_eq!(limits.retry_after(10_000), None);
+
+        // A 429 keeps the limit and empties the budget until the retry
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("5"));
+        limits.observe(&headers, 20_000);
+        assert_eq!(limits.status().limit, Some(600));
+        assert_eq!(limits.retry_after(20_000), Some(Duration::from_secs(5)));
+        limits.observe(&HeaderMap::new(), 30_000);
+        assert_eq!(limits.status().updated_ms, Some(20_000));
+    }
+
+    #[test]
+    fn concurrent_requests_get_distinct_slots() {
+        let last_request = AtomicI64::new(0);
+        assert_eq!(reserve_slot(&last_request, 1000, 10_000), Duration::from_millis(0));
+        assert_eq!(reserve_slot(&last_request, 1000, 10_200), Duration::from_millis(800));
+        assert_eq!(reserve_slot(&last_request, 1000, 10_200), Duration::from_millis(1800));
+        // A clock going backwards waits instead of underflowing
+        assert_eq!(reserve_slot(&last_request, 1000, 9_000), Duration::from_millis(4000));
+    }
+}
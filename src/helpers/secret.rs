@@ -0,0 +1,81 @@
+//! Secrets kept out of `Debug` and `Display` output, so clients and credentials can be logged.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+const MASK: &str = "***";
+
+/// Credential keys whose values are masked when printed
+pub const SECRET_KEYS: [&str; 5] = ["api_key", "api_secret", "passphrase", "otp", "token"];
+
+/// A string printed as `***`, read with `expose`
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new<S: Into<String>>(secret: S) -> Self {
+        SecretString(secret.into())
+    }
+
+    /// The secret itself, to sign or authenticate a request
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        SecretString(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        SecretString(secret.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretString({})", MASK)
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+/// Credential data printed with the values of `SECRET_KEYS` masked
+pub struct Redacted<'a>(pub &'a HashMap<String, String>);
+
+impl<'a> fmt::Debug for Redacted<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let data: BTreeMap<&str, &str> = self.0.iter()
+            .map(|(k, v)| (k.as_str(), if SECRET_KEYS.contains(&k.as_str()) { MASK } else { v.as_str() }))
+            .collect();
+        data.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod secret_tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_masked() {
+        let secret = SecretString::from("ABC&EF?abcdef");
+        assert_eq!(secret.expose(), "ABC&EF?abcdef");
+        assert_eq!(format!("{:?} {}", secret, secret), "SecretString(***) ***");
+
+        let mut data = HashMap::new();
+        data.insert("api_secret".to_string(), "ABC&EF?abcdef".to_string());
+        data.insert("environment".to_string(), "sandbox".to_string());
+        assert_eq!(format!("{:?}", Redacted(&data)), r#"{"api_secret": "***", "environment": "sandbox"}"#);
+    }
+}
@@ -0,0 +1,36 @@
+//! Logs of the signed requests, to diagnose the signatures refused by an exchange.
+//! Enabled per client with the `debug_signing` credential or the `set_debug_signing` setter of
+//! the private clients. Secrets found in the logged strings are masked.
+
+use hyper::StatusCode;
+
+use crate::exchange::Exchange;
+
+const MASK: &str = "***";
+
+/// `text` with every occurrence of the non empty `secrets` masked
+pub fn redact(text: &str, secrets: &[&str]) -> String {
+    secrets.iter()
+        .filter(|s| !s.is_empty())
+        .fold(text.to_string(), |text, secret| text.replace(secret, MASK))
+}
+
+/// Log the string signed for a request to `endpoint`, with its nonce or timestamp
+pub fn log_signed(exchange: Exchange, endpoint: &str, nonce: &str, canonical: &str, secrets: &[&str]) {
+    info!("{:?} : signing {} with nonce {} : {:?}", exchange, endpoint, nonce, redact(canonical, secrets));
+}
+
+/// Log the status of the response to a signed request
+pub fn log_response(exchange: Exchange, endpoint: &str, status: StatusCode) {
+    info!("{:?} : {} answered {}", exchange, endpoint, status);
+}
+
+#[cfg(test)]
+mod signing_tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_masked() {
+        assert_eq!(redact("1594596023162key=abc&otp=123456", &["abc", "123456", ""]), "1594596023162key=***&otp=***");
+    }
+}
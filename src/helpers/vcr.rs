@@ -0,0 +1,169 @@
+//! Record and replay of REST responses, to pin the behavior of an exchange in tests.
+//! A `Cassette` installed on a REST client either records every response to a fixture file, or
+//! answers every request from the file without reaching the exchange. Requests are keyed by their
+//! method, path and parameters, without the keys, signatures and nonces, so fixtures recorded
+//! with real credentials can be committed and replayed with fake ones.
+//! Set the `cassette` credential to the path of the fixture file, and `record_cassette` to `true`
+//! to record it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use hyper::client::Client;
+use hyper::{Body, Request, Response, StatusCode};
+use hyper_tls::HttpsConnector;
+use serde_json::Value;
+
+use crate::error::*;
+use crate::helpers::proxy::ProxyConnector;
+
+/// Parameters left out of the request keys, they hold secrets or change with every request
+const SECRET_PARAMS: [&str; 9] = ["key", "apikey", "api_key", "signature", "sign", "nonce", "timestamp", "token", "otp"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CassetteMode {
+    /// Send requests to the exchange and write their responses to the fixture file
+    Record,
+    /// Answer requests from the fixture file
+    Replay,
+}
+
+/// A recorded response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub key: String,
+    pub status: u16,
+    pub body: String,
+}
+
+#[derive(Debug, Default)]
+struct Tape {
+    interactions: Vec<Interaction>,
+    /// Responses already replayed for each key
+    played: HashMap<String, usize>,
+}
+
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    tape: Mutex<Tape>,
+}
+
+impl Cassette {
+    /// A cassette recording to `path`, previous recordings are replaced
+    pub fn record<P: AsRef<Path>>(path: P) -> Cassette {
+        Cassette { path: path.as_ref().to_path_buf(), mode: CassetteMode::Record, tape: Mutex::new(Tape::default()) }
+    }
+
+    /// A cassette replaying the recordings of `path`
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Cassette> {
+        let interactions: Vec<Interaction> = serde_json::from_str(&fs::read_to_string(path.as_ref())?)?;
+        let tape = Tape { interactions, played: HashMap::new() };
+        Ok(Cassette { path: path.as_ref().to_path_buf(), mode: CassetteMode::Replay, tape: Mutex::new(tape) })
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P, mode: CassetteMode) -> Result<Cassette> {
+        match mode {
+            CassetteMode::Record => Ok(Cassette::record(path)),
+            CassetteMode::Replay => Cassette::replay(path),
+        }
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// The recordings of a key are replayed in order, the last one is repeated once all were played
+    fn next(&self, key: &str) -> Option<Interaction> {
+        let mut tape = self.tape.lock().unwrap();
+        let recorded: Vec<&Interaction> = tape.interactions.iter().filter(|i| i.key == key).collect();
+        let last = recorded.len().checked_sub(1)?;
+        let played = tape.played.get(key).copied().unwrap_or(0);
+        let interaction = recorded[played.min(last)].clone();
+        tape.played.insert(key.to_string(), played + 1);
+        Some(interaction)
+    }
+
+    fn push(&self, interaction: Interaction) -> Result<()> {
+        let mut tape = self.tape.lock().unwrap();
+        tape.interactions.push(interaction);
+        fs::write(&self.path, serde_json::to_string_pretty(&tape.interactions)?)?;
+        Ok(())
+    }
+
+    /// Send `req` with `client` and record its response, or answer it from the recordings
+    pub(crate) async fn request(&self, client: &Client<HttpsConnector<ProxyConnector>>, req: Request<Body>) -> Result<Response<Body>> {
+        let (parts, body) = req.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        let key = request_key(parts.method.as_str(), parts.uri.path(), parts.uri.query().unwrap_or(""), &body);
+        match self.mode {
+            CassetteMode::Replay => {
+                let interaction = self.next(&key).ok_or_else(|| Error::FixtureNotFound(key))?;
+                let mut response = Response::new(Body::from(interaction.body));
+                *response.status_mut() = StatusCode::from_u16(interaction.status).map_err(|_| Error::InvalidFieldValue("status".to_string()))?;
+                Ok(response)
+            }
+            CassetteMode::Record => {
+                let response = client.request(Request::from_parts(parts, Body::from(body))).await?;
+                let (parts, body) = response.into_parts();
+                let body = hyper::body::to_bytes(body).await?;
+                self.push(Interaction { key, status: parts.status.as_u16(), body: String::from_utf8_lossy(&body).into_owned() })?;
+                Ok(Response::from_parts(parts, Body::from(body)))
+            }
+        }
+    }
+}
+
+fn is_secret(name: &str) -> bool {
+    SECRET_PARAMS.contains(&name.to_lowercase().as_str())
+}
+
+/// Sorted form parameters without the secret ones
+fn form_params(encoded: &[u8]) -> String {
+    let params: BTreeMap<String, String> = url::form_urlencoded::parse(encoded)
+        .filter(|(k, _)| !is_secret(k))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    params.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&")
+}
+
+/// The key of a request : its method, path and parameters, without the secret ones
+pub fn request_key(method: &str, path: &str, query: &str, body: &[u8]) -> String {
+    let body = match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Object(mut fields)) => {
+            fields.retain(|k, _| !is_secret(k));
+            Value::Object(fields).to_string()
+        }
+        Ok(v) => v.to_string(),
+        Err(_) => form_params(body),
+    };
+    format!("{} {}?{} {}", method, path, form_params(query.as_bytes()), body)
+}
+
+#[cfg(test)]
+mod vcr_tests {
+    use super::*;
+
+    #[test]
+    fn keys_ignore_secrets() {
+        let a = request_key("POST", "/0/private/Balance", "", b"nonce=1&asset=XBT");
+        let b = request_key("POST", "/0/private/Balance", "", b"asset=XBT&nonce=2");
+        assert_eq!(a, b);
+        assert!(!request_key("GET", "/ticker", "signature=abc&pair=btcusd", b"").contains("abc"));
+        assert_eq!(request_key("POST", "/orders", "", br#"{"timestamp": 1, "size": "1"}"#), r#"POST /orders? {"size":"1"}"#);
+    }
+
+    #[test]
+    fn recordings_are_replayed_in_order() {
+        let cassette = Cassette::record(std::env::temp_dir().join("coinnect_vcr_tests.json"));
+        cassette.push(Interaction { key: "k".to_string(), status: 200, body: "1".to_string() }).unwrap();
+        cassette.push(Interaction { key: "k".to_string(), status: 200, body: "2".to_string() }).unwrap();
+        let cassette = Cassette::replay(&cassette.path).unwrap();
+        let bodies: Vec<String> = (0..3).filter_map(|_| cassette.next("k")).map(|i| i.body).collect();
+        assert_eq!(bodies, vec!["1", "2", "2"]);
+        assert!(cassette.next("other").is_none());
+    }
+}
@@ -0,0 +1,128 @@
+//! Walk the paginated trade history of an account.
+//! `TradeHistoryPager` requests the pages of `ExchangeApi::fills_page` one after the other and
+//! yields their fills from the most recent to the oldest, until the start of the history or a
+//! time horizon is reached :
+//!
+//! ```ignore
+//! let mut pager = TradeHistoryPager::new(api.as_ref(), Pair::BTC_USD, since_ms);
+//! while let Some(fill) = pager.next().await? {
+//!     println!("{:?}", fill);
+//! }
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+
+use futures::stream::{self, Stream};
+
+use crate::error::*;
+use crate::exchange::ExchangeApi;
+use crate::types::*;
+
+pub struct TradeHistoryPager<'a> {
+    api: &'a dyn ExchangeApi,
+    pair: Pair,
+    horizon_ms: i64,
+    cursor: Option<String>,
+    fills: VecDeque<FillEvent>,
+    last_page: bool,
+}
+
+impl<'a> TradeHistoryPager<'a> {
+    /// Fills of `pair` executed after `horizon_ms`, a UNIX timestamp in ms
+    pub fn new(api: &'a dyn ExchangeApi, pair: Pair, horizon_ms: i64) -> Self {
+        TradeHistoryPager { api, pair, horizon_ms, cursor: None, fills: VecDeque::new(), last_page: false }
+    }
+
+    /// The next older fill, `None` once the horizon or the start of the history is reached
+    pub async fn next(&mut self) -> Result<Option<FillEvent>> {
+        loop {
+            if let Some(fill) = self.fills.pop_front() {
+                if fill.event_ms < self.horizon_ms {
+                    self.fills.clear();
+                    self.last_page = true;
+                    return Ok(None);
+                }
+                return Ok(Some(fill));
+            }
+            if self.last_page {
+                return Ok(None);
+            }
+            let page = self.api.fills_page(self.pair, self.cursor.take()).await?;
+            let mut fills = page.fills;
+            fills.sort_by_key(|f| Reverse(f.event_ms));
+            self.fills.extend(fills);
+            self.last_page = page.next.is_none();
+            self.cursor = page.next;
+        }
+    }
+
+    /// The fills as a stream, which ends after the first error
+    pub fn into_stream(self) -> impl Stream<Item = Result<FillEvent>> + 'a {
+        stream::unfold(Some(self), |pager| async move {
+            let mut pager = pager?;
+            match pager.next().await {
+                Ok(Some(fill)) => Some((Ok(fill), Some(pager))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bigdecimal::BigDecimal;
+    use futures::StreamExt;
+
+    /// Three pages of two fills, one every second from the most recent
+    #[derive(Debug)]
+    struct Pages;
+
+    fn fill(event_ms: i64) -> FillEvent {
+        FillEvent {
+            event_ms,
+            order_id: event_ms.to_string(),
+            trade_id: None,
+            pair: Pair::BTC_USD,
+            side: TradeType::Buy,
+            price: BigDecimal::from(100),
+            amount: BigDecimal::from(1),
+            fee: BigDecimal::from(0),
+            fee_currency: None,
+            liquidity: Liquidity::Unknown,
+        }
+    }
+
+    #[async_trait]
+    impl ExchangeApi for Pages {
+        async fn ticker(&self, _pair: Pair) -> Result<Ticker> { Err(Error::Unsupported) }
+        async fn orderbook(&self, _pair: Pair) -> Result<Orderbook> { Err(Error::Unsupported) }
+        async fn add_order(&self, _order_type: OrderType, _pair: Pair, _quantity: Volume, _price: Option<Price>) -> Result<OrderInfo> { Err(Error::Unsupported) }
+        async fn balances(&self) -> Result<Balances> { Err(Error::Unsupported) }
+
+        async fn fills_page(&self, _pair: Pair, cursor: Option<String>) -> Result<FillsPage> {
+            let page = cursor.map_or(0, |c| c.parse::<i64>().unwrap());
+            let fills = vec![fill(6000 - page * 2000 - 1000), fill(6000 - page * 2000)];
+            Ok(FillsPage { fills, next: if page < 2 { Some((page + 1).to_string()) } else { None } })
+        }
+    }
+
+    #[test]
+    fn walks_pages_until_the_horizon() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut pager = TradeHistoryPager::new(&Pages, Pair::BTC_USD, 2500);
+            let mut times = vec![];
+            while let Some(fill) = pager.next().await.unwrap() {
+                times.push(fill.event_ms);
+            }
+            assert_eq!(times, vec![6000, 5000, 4000, 3000]);
+
+            let all: Vec<Result<FillEvent>> = TradeHistoryPager::new(&Pages, Pair::BTC_USD, 0).into_stream().collect().await;
+            assert_eq!(all.len(), 6);
+        });
+    }
+}
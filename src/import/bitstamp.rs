@@ -0,0 +1,99 @@
+//! Bitstamp transaction exports, with the columns
+//! `Type,Datetime,Account,Amount,Value,Rate,Fee,Sub Type`.
+//! Amounts are followed by their currency, e.g. `0.50000000 BTC`, and dates are in UTC in the
+//! `Jan. 02, 2020, 11:00 AM` format. The export has no transaction identifiers.
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::types::*;
+use super::{CsvRows, ImportedHistory, Row, parse_currency, trade_legs};
+
+fn parse_time(time: &str) -> Result<i64> {
+    // Short month names end with a dot, except May
+    NaiveDateTime::parse_from_str(&time.replacen('.', "", 1), "%b %d, %Y, %I:%M %p")
+        .map(|t| t.timestamp_millis())
+        .map_err(|_| Error::InvalidFieldFormat("datetime".to_string()))
+}
+
+/// Parse an amount followed by its currency, `None` for an empty field
+fn parse_amount(row: &Row, column: &str) -> Result<Option<(BigDecimal, Currency)>> {
+    let field = row.get(column);
+    if field.is_empty() {
+        return Ok(None);
+    }
+    let mut parts = field.split_whitespace();
+    let amount = parts.next().unwrap_or_default().parse::<BigDecimal>()
+        .with_context(|| Error::InvalidFieldFormat(column.to_string()))?;
+    let currency = parse_currency(parts.next().ok_or_else(|| Error::InvalidFieldFormat(column.to_string()))?)?;
+    Ok(Some((amount, currency)))
+}
+
+fn required_amount(row: &Row, column: &str) -> Result<(BigDecimal, Currency)> {
+    parse_amount(row, column)?.ok_or_else(|| Error::MissingField(column.to_string()))
+}
+
+/// Import a transaction export, sub account transfers and other entries are ignored
+pub fn import_transactions(content: &str) -> Result<ImportedHistory> {
+    let csv = CsvRows::parse(content)?;
+    csv.require(&["type", "datetime", "amount", "value", "fee", "sub type"])?;
+    let mut history = ImportedHistory::default();
+    for row in csv.rows() {
+        let timestamp = parse_time(row.get("datetime"))?;
+        let fee = parse_amount(&row, "fee")?;
+        match row.get("type") {
+            "Market" | "Limit" => {
+                let (amount, base) = required_amount(&row, "amount")?;
+                let (value, quote) = required_amount(&row, "value")?;
+                let legs = match TradeType::from(row.get("sub type").to_string()) {
+                    TradeType::Buy => trade_legs((quote, value), (base, amount))?,
+                    TradeType::Sell => trade_legs((base, amount), (quote, value))?,
+                    TradeType::Unknown(_) => return Err(Error::InvalidFieldValue("sub type".to_string())),
+                };
+                let (pair, side, price, volume) = legs;
+                let (fee, fee_currency) = fee.unwrap_or_else(|| (BigDecimal::from(0), quote));
+                history.fills.push(TradeFill { exchange: Exchange::Bitstamp, timestamp, pair, side, price, volume, fee, fee_currency, identifier: None, raw: None });
+            }
+            kind @ "Deposit" | kind @ "Withdrawal" => {
+                let (amount, currency) = required_amount(&row, "amount")?;
+                history.transfers.push(Transfer {
+                    exchange: Exchange::Bitstamp,
+                    timestamp,
+                    kind: if kind == "Deposit" { TransferKind::Deposit } else { TransferKind::Withdrawal },
+                    currency,
+                    amount,
+                    fee: fee.map_or_else(|| BigDecimal::from(0), |(f, _)| f),
+                    identifier: None,
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod bitstamp_import_tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn import_trades_and_transfers() {
+        let transactions = r#"Type,Datetime,Account,Amount,Value,Rate,Fee,Sub Type
+Deposit,"Jan. 01, 2020, 10:00 AM",Main Account,1000.00 USD,,,,
+Market,"May 02, 2020, 11:30 PM",Main Account,0.50000000 BTC,3500.00 USD,7000.00 USD,8.75 USD,Buy
+Sub Account Transfer,"May 03, 2020, 09:00 AM",Main Account,0.10000000 BTC,,,,
+"#;
+        let history = import_transactions(transactions).unwrap();
+        assert_eq!(history.transfers.len(), 1);
+        assert_eq!(history.transfers[0].currency, Currency::USD);
+        let fill = &history.fills[0];
+        assert_eq!(fill.pair, Pair::BTC_USD);
+        assert_eq!(fill.side, TradeType::Buy);
+        assert_eq!(fill.price, BigDecimal::from(7000));
+        assert_eq!(fill.volume, BigDecimal::from_str("0.5").unwrap());
+        assert_eq!(fill.fee, BigDecimal::from_str("8.75").unwrap());
+    }
+}
@@ -0,0 +1,116 @@
+//! Kraken ledger exports (`ledgers.csv`), with the columns
+//! `txid,refid,time,type,subtype,aclass,asset,amount,fee,balance`.
+//! A trade is recorded as two ledger entries sharing its `refid`, one per asset.
+//! Entries without a `txid` are pending duplicates and are skipped.
+
+use std::collections::BTreeMap;
+
+use bigdecimal::{BigDecimal, Signed, Zero};
+use chrono::NaiveDateTime;
+
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::kraken::utils;
+use crate::types::*;
+use super::{CsvRows, ImportedHistory, trade_legs};
+
+struct Entry {
+    timestamp: i64,
+    currency: Currency,
+    amount: BigDecimal,
+    fee: BigDecimal,
+}
+
+fn parse_time(time: &str) -> Result<i64> {
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S"))
+        .map(|t| t.timestamp_millis())
+        .map_err(|_| Error::InvalidFieldFormat("time".to_string()))
+}
+
+fn trade_fill(refid: &str, entries: &[Entry]) -> Result<TradeFill> {
+    let spent = entries.iter().find(|e| e.amount < BigDecimal::zero());
+    let received = entries.iter().find(|e| e.amount > BigDecimal::zero());
+    let (spent, received) = match (spent, received) {
+        (Some(s), Some(r)) if entries.len() == 2 => (s, r),
+        _ => return Err(Error::InvalidFieldValue(format!("trade {}", refid))),
+    };
+    let (pair, side, price, volume) = trade_legs((spent.currency, spent.amount.abs()), (received.currency, received.amount.clone()))?;
+    let fee_entry = entries.iter().find(|e| !e.fee.is_zero()).unwrap_or(spent);
+    Ok(TradeFill {
+        exchange: Exchange::Kraken,
+        timestamp: spent.timestamp,
+        pair,
+        side,
+        price,
+        volume,
+        fee: fee_entry.fee.clone(),
+        fee_currency: fee_entry.currency,
+        identifier: Some(refid.to_string()),
+        raw: None,
+    })
+}
+
+/// Import a ledger export, entries other than trades, deposits and withdrawals are ignored
+pub fn import_ledgers(content: &str) -> Result<ImportedHistory> {
+    let csv = CsvRows::parse(content)?;
+    csv.require(&["txid", "refid", "time", "type", "asset", "amount", "fee"])?;
+    let mut history = ImportedHistory::default();
+    let mut trades: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+    for row in csv.rows() {
+        if row.get("txid").is_empty() {
+            continue;
+        }
+        let asset = row.get("asset");
+        let entry = Entry {
+            timestamp: parse_time(row.get("time"))?,
+            currency: utils::parse_asset(asset).ok_or_else(|| Error::InvalidFieldValue(asset.to_string()))?,
+            amount: row.decimal("amount")?,
+            fee: row.decimal("fee")?,
+        };
+        match row.get("type") {
+            "trade" => trades.entry(row.get("refid").to_string()).or_default().push(entry),
+            kind @ "deposit" | kind @ "withdrawal" => history.transfers.push(Transfer {
+                exchange: Exchange::Kraken,
+                timestamp: entry.timestamp,
+                kind: if kind == "deposit" { TransferKind::Deposit } else { TransferKind::Withdrawal },
+                currency: entry.currency,
+                amount: entry.amount.abs(),
+                fee: entry.fee,
+                identifier: Some(row.get("refid").to_string()),
+            }),
+            _ => {}
+        }
+    }
+    for (refid, entries) in trades {
+        history.fills.push(trade_fill(&refid, &entries)?);
+    }
+    history.fills.sort_by_key(|f| f.timestamp);
+    Ok(history)
+}
+
+#[cfg(test)]
+mod kraken_import_tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn import_trades_and_transfers() {
+        let ledgers = r#""txid","refid","time","type","subtype","aclass","asset","amount","fee","balance"
+"L1","D1","2020-01-02 10:00:00","deposit","","currency","ZEUR",1000.0000,0.0000,1000.0000
+"L2","T1","2020-01-03 11:00:00","trade","","currency","ZEUR",-700.0000,1.1200,298.8800
+"L3","T1","2020-01-03 11:00:00","trade","","currency","XXBT",0.1000000000,0.0000000000,0.1000000000
+"","W1","2020-01-04 12:00:00","withdrawal","","currency","XXBT",-0.0500000000,0.0005000000,0.0495000000
+"L4","W1","2020-01-04 12:00:00","withdrawal","","currency","XXBT",-0.0500000000,0.0005000000,0.0495000000
+"#;
+        let history = import_ledgers(ledgers).unwrap();
+        assert_eq!(history.transfers.len(), 2);
+        assert_eq!(history.transfers[1].kind, TransferKind::Withdrawal);
+        assert_eq!(history.transfers[1].amount, BigDecimal::from_str("0.05").unwrap());
+        let fill = &history.fills[0];
+        assert_eq!(fill.pair, Pair::BTC_EUR);
+        assert_eq!(fill.side, TradeType::Buy);
+        assert_eq!(fill.price, BigDecimal::from(7000));
+        assert_eq!(fill.fee_currency, Currency::EUR);
+    }
+}
@@ -0,0 +1,117 @@
+//! Importers of the CSV exports of exchanges, for the history that cannot be queried from their
+//! api anymore. Every importer turns an export into `TradeFill`s and `Transfer`s.
+
+pub mod bitstamp;
+#[cfg(feature = "kraken")]
+pub mod kraken;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use serde_json::Value;
+
+use crate::error::*;
+use crate::types::*;
+use crate::utils;
+
+/// The normalized content of an export
+#[derive(Debug, Clone, Default)]
+pub struct ImportedHistory {
+    pub fills: Vec<TradeFill>,
+    pub transfers: Vec<Transfer>,
+}
+
+/// Split a CSV line, handling quoted fields and `""` escapes
+pub fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches(|c| c == '\r' || c == '\n').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::replace(&mut field, String::new())),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Rows of a CSV export indexed by the column names of its header
+pub(crate) struct CsvRows {
+    columns: HashMap<String, usize>,
+    rows: Vec<Vec<String>>,
+}
+
+impl CsvRows {
+    pub(crate) fn parse(content: &str) -> Result<Self> {
+        let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+        let header = lines.next().ok_or_else(|| Error::MissingField("header".to_string()))?;
+        let columns = split_csv_line(header).into_iter().enumerate()
+            .map(|(i, name)| (name.trim().to_lowercase(), i))
+            .collect();
+        let rows = lines.map(split_csv_line).collect();
+        Ok(CsvRows { columns, rows })
+    }
+
+    /// Fail if one of the columns is missing from the header
+    pub(crate) fn require(&self, columns: &[&str]) -> Result<()> {
+        match columns.iter().find(|c| !self.columns.contains_key(**c)) {
+            Some(c) => Err(Error::MissingField(c.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn rows(&self) -> impl Iterator<Item = Row<'_>> + '_ {
+        self.rows.iter().map(move |fields| Row { columns: &self.columns, fields })
+    }
+}
+
+pub(crate) struct Row<'a> {
+    columns: &'a HashMap<String, usize>,
+    fields: &'a Vec<String>,
+}
+
+impl<'a> Row<'a> {
+    pub(crate) fn get(&self, column: &str) -> &'a str {
+        self.columns.get(column).and_then(|&i| self.fields.get(i)).map_or("", |f| f.trim())
+    }
+
+    pub(crate) fn decimal(&self, column: &str) -> Result<BigDecimal> {
+        match self.get(column) {
+            "" => Ok(BigDecimal::from(0)),
+            v => BigDecimal::from_str(v).with_context(|| Error::InvalidFieldFormat(column.to_string())),
+        }
+    }
+}
+
+/// Parse a currency symbol, accepting the `XBT` alias of bitcoin
+pub(crate) fn parse_currency(symbol: &str) -> Result<Currency> {
+    let symbol = match symbol.to_uppercase().as_str() {
+        "XBT" => "BTC".to_string(),
+        s => s.to_string(),
+    };
+    serde_json::from_value(Value::String(symbol.clone())).with_context(|| Error::InvalidFieldValue(symbol))
+}
+
+/// The pair, side, price and base volume of a trade exchanging `spent` for `received`
+pub(crate) fn trade_legs(spent: (Currency, Amount), received: (Currency, Amount)) -> Result<(Pair, TradeType, Price, Volume)> {
+    let (spent_currency, spent_amount) = spent;
+    let (received_currency, received_amount) = received;
+    if spent_amount.is_zero() || received_amount.is_zero() {
+        return Err(Error::InvalidFieldValue("amount".to_string()));
+    }
+    if let Some(pair) = utils::currencies_pair(received_currency, spent_currency) {
+        Ok((pair, TradeType::Buy, spent_amount / received_amount.clone(), received_amount))
+    } else if let Some(pair) = utils::currencies_pair(spent_currency, received_currency) {
+        Ok((pair, TradeType::Sell, received_amount / spent_amount.clone(), spent_amount))
+    } else {
+        Err(Error::PairUnsupported)
+    }
+}
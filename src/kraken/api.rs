@@ -6,8 +6,7 @@
 use hmac::{Hmac, Mac};
 use sha2::{Sha256, Sha512, Digest};
 
-use hyper::{Client, Uri, Method, Request, Body};
-use hyper_tls::HttpsConnector;
+use hyper::{Uri, Method, Request, Body};
 
 use data_encoding::BASE64;
 
@@ -15,31 +14,51 @@ use serde_json::Value;
 use serde_json::value::Map;
 
 use std::collections::HashMap;
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::{AtomicI64, AtomicU64};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use std::str;
 
 use crate::error::*;
+use crate::helpers::clock::ServerClock;
+use crate::helpers::failover::Endpoints;
+use crate::helpers::secret::SecretString;
+use crate::helpers::signing;
 use crate::helpers;
+use crate::metrics;
 
 use crate::exchange::Exchange;
 use crate::coinnect::Credentials;
-use hyper::client::HttpConnector;
+use crate::helpers::proxy::HttpClient;
 use futures::{TryFutureExt};
 use bytes::buf::BufExt as _;
 use crate::helpers::json;
+use crate::kraken::models::*;
+use crate::kraken::utils;
+use crate::types::Pair;
 
 const KEY_HEADER: &str = "API-Key";
 const SIGN_HEADER: &str = "API-Sign";
 
+/// Time to live of a websocket token when Kraken does not give it
+const DEFAULT_TOKEN_TTL_SECS: u64 = 900;
+/// Websocket tokens are renewed this long before they expire
+pub const TOKEN_RENEWAL_MARGIN: Duration = Duration::from_secs(60);
+const TOKEN_RETRY_DELAY: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
 pub struct KrakenApi {
-    last_request: i64, // unix timestamp in ms, to avoid ban
-    api_key: String,
-    api_secret: String,
-    otp: Option<String>, // two-factor password (if two-factor enabled, otherwise not required)
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    last_request: AtomicI64, // unix timestamp in ms, to avoid ban
+    last_nonce: AtomicU64, // private requests are rejected unless their nonce increases
+    api_key: SecretString,
+    api_secret: SecretString,
+    otp: Option<SecretString>, // two-factor password (if two-factor enabled, otherwise not required)
+    http_client: HttpClient,
     burst: bool,
+    pub(crate) keep_raw: bool,
+    debug_signing: bool,
+    endpoints: Endpoints,
+    pub(crate) clock: ServerClock,
 }
 
 
@@ -47,26 +66,38 @@ impl KrakenApi {
     /// Create a new KrakenApi by providing an API key & API secret
     pub fn new<C: Credentials>(creds: C) -> Result<KrakenApi> {
         if creds.exchange() != Exchange::Kraken {
-            return Err(ErrorKind::InvalidConfigType(Exchange::Kraken, creds.exchange()).into());
+            return Err(Error::InvalidConfigType(Exchange::Kraken, creds.exchange()));
         }
+        creds.environment()?.production_only(Exchange::Kraken)?;
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
+        let connection = creds.connection();
+        let ssl = connection.http_client()?;
+        let endpoints = connection.rest_endpoints(Exchange::Kraken, utils::API_URL);
 
         Ok(KrakenApi {
-               last_request: 0,
-               api_key: creds.get("api_key").unwrap_or_default(),
-               api_secret: creds.get("api_secret").unwrap_or_default(),
+               last_request: AtomicI64::new(0),
+               last_nonce: AtomicU64::new(0),
+               api_key: creds.get("api_key").unwrap_or_default().into(),
+               api_secret: creds.get("api_secret").unwrap_or_default().into(),
                otp: None,
                http_client: ssl,
                burst: false,
+               keep_raw: connection.keep_raw,
+               debug_signing: connection.debug_signing,
+               endpoints,
+               clock: ServerClock::default(),
            })
     }
 
 
     /// Use to provide your two-factor password (if two-factor enabled, otherwise not required)
     pub async fn set_two_pass_auth(&mut self, otp: String) {
-        self.otp = Some(otp);
+        self.otp = Some(otp.into());
+    }
+
+    /// The REST endpoints, to subscribe to failover events or recover the primary endpoint
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
     }
 
     /// The number of calls in a given period is limited. In order to avoid a ban we limit
@@ -78,65 +109,84 @@ impl KrakenApi {
         self.burst = burst
     }
 
+    /// Keep the payload of the exchange in the `raw` field of typed responses
+    pub fn set_keep_raw(&mut self, keep_raw: bool) {
+        self.keep_raw = keep_raw
+    }
+
+    /// Log the strings signed for the private requests and the status of their responses
+    pub fn set_debug_signing(&mut self, debug_signing: bool) {
+        self.debug_signing = debug_signing
+    }
+
     pub async fn block_or_continue(&self) {
-        if ! self.burst {
-            let threshold: u64 = 2000; // 1 request/2sec
-            let offset: u64 = helpers::get_unix_timestamp_ms() as u64 - self.last_request as u64;
-            if offset < threshold {
-                let wait_ms = Duration::from_millis(threshold - offset);
-                thread::sleep(wait_ms);
-            }
+        if !self.burst {
+            helpers::rate_limit::wait_for_slot(&self.last_request, 2000).await; // 1 request/2sec
         }
     }
 
-    async fn public_query(&mut self,
+    async fn public_query(&self,
                     method: &str,
                     params: &mut HashMap<&str, &str>)
                     -> Result<Map<String, Value>> {
         helpers::strip_empties(params);
-        let string = "https://api.kraken.com/0/public/".to_string() + method + "?" +
+        let string = self.endpoints.current().to_string() + "/0/public/" + method + "?" +
             &helpers::url_encode_hashmap(params);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
+        let url: Uri = string.as_str().parse().map_err(|_e| Error::BadParse)?;
 
         self.block_or_continue().await;
-        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
-        self.last_request = helpers::get_unix_timestamp_ms();
+        let start = Instant::now();
+        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body()).map_err(Error::from)).await;
+        metrics::rest_request(Exchange::Kraken, method, start);
+        let buf = self.endpoints.track(buf)?;
         let reader = buf.reader();
         json::deserialize_json_r(reader)
     }
 
-    async fn private_query(&mut self,
+    async fn private_query(&self,
                      method: &str,
                      mut params: &mut HashMap<&str, &str>)
                      -> Result<Map<String, Value>> {
-        let url = "https://api.kraken.com/0/private/".to_string() + method;
+        let url = self.endpoints.current().to_string() + "/0/private/" + method;
 
         let urlpath = "/0/private/".to_string() + method;
 
-        let nonce = helpers::get_unix_timestamp_ms().to_string();
+        let nonce = helpers::next_nonce(&self.last_nonce).to_string();
         helpers::strip_empties(&mut params);
 
         let mut params = params.clone(); // TODO: Remove .clone()
         params.insert("nonce", &nonce);
 
         if let Some(ref password) = self.otp {
-            params.insert("otp", password);
+            params.insert("otp", password.expose());
         }
 
         let post_data = helpers::url_encode_hashmap(&params);
 
+        if self.debug_signing {
+            let otp = self.otp.as_ref().map_or("", |otp| otp.expose());
+            signing::log_signed(Exchange::Kraken, &urlpath, &nonce, &format!("{} + sha256({}{})", urlpath, nonce, post_data), &[self.api_key.expose(), otp]);
+        }
         let signature = self.create_signature(urlpath, &post_data, &nonce)?;
 
         let req: Result<Request<Body>> = Request::builder()
             .method(Method::POST)
             .uri(&url)
-            .header(KEY_HEADER, self.api_key.as_str())
+            .header(KEY_HEADER, self.api_key.expose())
             .header(SIGN_HEADER, signature)
             .body(post_data.into())
-            .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
+            .map_err(|e| Error::ServiceUnavailable(e.to_string()));
         let req2 = req.unwrap();
-        let buf = self.http_client.request(req2).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
-        self.last_request = helpers::get_unix_timestamp_ms();
+        let start = Instant::now();
+        let debug_signing = self.debug_signing;
+        let buf = self.http_client.request(req2).and_then(|resp| {
+            if debug_signing {
+                signing::log_response(Exchange::Kraken, method, resp.status());
+            }
+            hyper::body::aggregate(resp.into_body()).map_err(Error::from)
+        }).await;
+        metrics::rest_request(Exchange::Kraken, method, start);
+        let buf = self.endpoints.track(buf)?;
         let reader = buf.reader();
         json::deserialize_json_r(reader)
     }
@@ -154,7 +204,7 @@ impl KrakenApi {
             concatenated.push(elem);
         }
 
-        let hmac_key = BASE64.decode(self.api_secret.as_bytes())?;
+        let hmac_key = BASE64.decode(self.api_secret.expose().as_bytes())?;
         let mut mac = Hmac::<Sha512>::new(&hmac_key[..]);
         mac.input(&concatenated);
         Ok(BASE64.encode(mac.result().code()))
@@ -167,7 +217,7 @@ impl KrakenApi {
     /// rfc1123 = as RFC 1123 time format
     /// ```
     /// Note: This is to aid in approximating the skew time between the server and client.
-    pub async fn get_server_time(&mut self) -> Result<Map<String, Value>> {
+    pub async fn get_server_time(&self) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         self.public_query("Time", &mut params).await
     }
@@ -191,7 +241,7 @@ impl KrakenApi {
     /// decimals = scaling decimal places for record keeping
     /// display_decimals = scaling decimal places for output display
     /// ```
-    pub async fn get_asset_info(&mut self,
+    pub async fn get_asset_info(&self,
                           info: &str,
                           aclass: &str,
                           asset: &str)
@@ -236,7 +286,7 @@ impl KrakenApi {
     ///     margin_call = margin call level
     ///     margin_stop = stop-out/liquidation margin level
     /// ```
-    pub async fn get_tradable_asset_pairs(&mut self,
+    pub async fn get_tradable_asset_pairs(&self,
                                     info: &str,
                                     pair: &str)
                                     -> Result<Map<String, Value>> {
@@ -266,7 +316,7 @@ impl KrakenApi {
     /// h = high array(<today>, <last 24 hours>),
     /// o = today's opening price
     /// ```
-    pub async fn get_ticker_information(&mut self, pair: &str) -> Result<Map<String, Value>> {
+    pub async fn get_ticker_information(&self, pair: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("pair", pair);
         self.public_query("Ticker", &mut params).await
@@ -292,7 +342,7 @@ impl KrakenApi {
     ///
     /// Note: the last entry in the OHLC array is for the current, not-yet-committed frame and will
     /// always be present, regardless of the value of "since".
-    pub async fn get_ohlc_data(&mut self,
+    pub async fn get_ohlc_data(&self,
                          pair: &str,
                          interval: &str,
                          since: &str)
@@ -317,7 +367,7 @@ impl KrakenApi {
     ///     asks = ask side array of array entries(<price>, <volume>, <timestamp>)
     ///     bids = bid side array of array entries(<price>, <volume>, <timestamp>)
     /// ```
-    pub async fn get_order_book(&mut self, pair: &str, count: &str) -> Result<Map<String, Value>> {
+    pub async fn get_order_book(&self, pair: &str, count: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("pair", pair);
         params.insert("count", count);
@@ -339,7 +389,7 @@ impl KrakenApi {
     /// <miscellaneous>)
     /// last = id to be used as since when polling for new trade data
     /// ```
-    pub async fn get_recent_trades(&mut self, pair: &str, since: &str) -> Result<Map<String, Value>> {
+    pub async fn get_recent_trades(&self, pair: &str, since: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("pair", pair);
         params.insert("since", since);
@@ -362,7 +412,7 @@ impl KrakenApi {
     /// ```
     /// Note: "since" is inclusive so any returned data with the same time as the previous set
     /// should overwrite all of the previous set's entries at that time
-    pub async fn get_recent_spread_data(&mut self,
+    pub async fn get_recent_spread_data(&self,
                                   pair: &str,
                                   since: &str)
                                   -> Result<Map<String, Value>> {
@@ -372,8 +422,49 @@ impl KrakenApi {
         self.public_query("Spread", &mut params).await
     }
 
+    /// Result: current system status
+    ///
+    /// ```json
+    /// status = online, maintenance, cancel_only or post_only
+    /// timestamp = current timestamp (RFC3339)
+    /// ```
+    pub async fn get_system_status(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.public_query("SystemStatus", &mut params).await
+    }
+
+    /// The candles of `pair`, `interval_mins` being one of 1, 5, 15, 30, 60, 240, 1440, 10080
+    /// and 21600. Only candles committed after `since` are returned, when given.
+    pub async fn ohlc(&self, pair: Pair, interval_mins: u32, since: Option<i64>) -> Result<OhlcData> {
+        let pair_name = utils::get_pair_string(&pair).ok_or_else(|| Error::PairUnsupported)?;
+        let since = since.map(|s| s.to_string()).unwrap_or_default();
+        let raw_response = self.get_ohlc_data(pair_name, &interval_mins.to_string(), &since).await?;
+        OhlcData::from_result(&utils::parse_result(&raw_response)?)
+    }
+
+    /// The recent best bids and asks of `pair`, since the `since` id when given
+    pub async fn spread(&self, pair: Pair, since: Option<i64>) -> Result<SpreadData> {
+        let pair_name = utils::get_pair_string(&pair).ok_or_else(|| Error::PairUnsupported)?;
+        let since = since.map(|s| s.to_string()).unwrap_or_default();
+        let raw_response = self.get_recent_spread_data(pair_name, &since).await?;
+        SpreadData::from_result(&utils::parse_result(&raw_response)?)
+    }
+
+    /// Every tradable pair, including those the crate has no `Pair` for
+    pub async fn asset_pairs(&self) -> Result<Vec<AssetPair>> {
+        let raw_response = self.get_tradable_asset_pairs("", "").await?;
+        utils::parse_result(&raw_response)?.iter()
+            .map(|(name, info)| AssetPair::from_result(name, info))
+            .collect()
+    }
+
+    pub async fn system_status(&self) -> Result<SystemStatus> {
+        let raw_response = self.get_system_status().await?;
+        Ok(serde_json::from_value(Value::Object(utils::parse_result(&raw_response)?))?)
+    }
+
     /// Result: array of asset names and balance amount
-    pub async fn get_account_balance(&mut self) -> Result<Map<String, Value>> {
+    pub async fn get_account_balance(&self) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         self.private_query("Balance", &mut params).await
     }
@@ -399,7 +490,7 @@ impl KrakenApi {
     /// ml = margin level = (equity / initial margin) * 100
     /// ```
     /// Note: Rates used for the floating valuation is the midpoint of the best bid and ask prices
-    pub async fn get_trade_balance(&mut self, aclass: &str, asset: &str) -> Result<Map<String, Value>> {
+    pub async fn get_trade_balance(&self, aclass: &str, asset: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("aclass", aclass);
         params.insert("asset", asset);
@@ -462,7 +553,7 @@ impl KrakenApi {
     /// a scale of 2.
     /// Similarly, if the asset pair's pricing scale is 5, the scale will remain as 5, even if the
     /// underlying currency has a scale of 8.
-    pub async fn get_open_orders(&mut self, trades: &str, userref: &str) -> Result<Map<String, Value>> {
+    pub async fn get_open_orders(&self, trades: &str, userref: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("trades", trades);
         params.insert("userref", userref);
@@ -493,7 +584,7 @@ impl KrakenApi {
     /// ```
     /// Note: Times given by order tx ids are more accurate than unix timestamps. If an order tx id
     /// is given for the time, the order's open time is used
-    pub async fn get_closed_orders(&mut self,
+    pub async fn get_closed_orders(&self,
                              trades: &str,
                              userref: &str,
                              start: &str,
@@ -508,7 +599,7 @@ impl KrakenApi {
         params.insert("end", end);
         params.insert("ofs", ofs);
         params.insert("closetime", closetime);
-        self.private_query("OpenOrders", &mut params).await
+        self.private_query("ClosedOrders", &mut params).await
     }
 
     /// Input:
@@ -523,7 +614,7 @@ impl KrakenApi {
     /// ```json
     /// <order_txid> = order info.  See Get open orders/Get closed orders
     /// ```
-    pub async fn query_orders_info(&mut self,
+    pub async fn query_orders_info(&self,
                              trades: &str,
                              userref: &str,
                              txid: &str)
@@ -585,7 +676,7 @@ impl KrakenApi {
     /// Unless otherwise stated, costs, fees, prices, and volumes are in the asset pair's scale,
     /// not the currency's scale.
     /// Times given by trade tx ids are more accurate than unix timestamps.
-    pub async fn get_trades_history(&mut self,
+    pub async fn get_trades_history(&self,
                               type_trade: &str,
                               trades: &str,
                               start: &str,
@@ -613,7 +704,7 @@ impl KrakenApi {
     /// ```json
     /// <trade_txid> = trade info.  See Get trades history
     /// ```
-    pub async fn query_trades_info(&mut self, txid: &str, trades: &str) -> Result<Map<String, Value>> {
+    pub async fn query_trades_info(&self, txid: &str, trades: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("txid", txid);
         params.insert("trades", trades);
@@ -649,7 +740,7 @@ impl KrakenApi {
     ///
     /// Note: Unless otherwise stated, costs, fees, prices, and volumes are in the asset pair's
     /// scale, not the currency's scale.
-    pub async fn get_open_positions(&mut self, txid: &str, docalcs: &str) -> Result<Map<String, Value>> {
+    pub async fn get_open_positions(&self, txid: &str, docalcs: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("txid", txid);
         params.insert("docalcs", docalcs);
@@ -686,7 +777,7 @@ impl KrakenApi {
     ///     balance = resulting balance
     /// ```
     /// Note: Times given by ledger ids are more accurate than unix timestamps.
-    pub async fn get_ledgers_info(&mut self,
+    pub async fn get_ledgers_info(&self,
                             aclass: &str,
                             asset: &str,
                             type_ledger: &str,
@@ -714,7 +805,7 @@ impl KrakenApi {
     /// ```json
     /// <ledger_id> = ledger info.  See Get ledgers info
     /// ```
-    pub async fn query_ledgers(&mut self, id: &str) -> Result<Map<String, Value>> {
+    pub async fn query_ledgers(&self, id: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("id", id);
         self.private_query("QueryLedgers", &mut params).await
@@ -750,13 +841,62 @@ impl KrakenApi {
     /// Note: If an asset pair is on a maker/taker fee schedule, the taker side is given in "fees"
     /// and maker side in "fees_maker". For pairs not on maker/taker, they will only be given in
     /// "fees".
-    pub async fn get_trade_volume(&mut self, pair: &str, fee_info: &str) -> Result<Map<String, Value>> {
+    pub async fn get_trade_volume(&self, pair: &str, fee_info: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("pair", pair);
         params.insert("fee-info", fee_info);
         self.private_query("TradeVolume", &mut params).await
     }
 
+    /// Result: token authenticating the private websocket feeds and the websocket trading
+    /// requests, valid for 15 minutes unless a connection using it stays open
+    ///
+    /// ```json
+    /// token = websocket token
+    /// expires = time to live of the token in seconds
+    /// ```
+    pub async fn get_websockets_token(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.private_query("GetWebSocketsToken", &mut params).await
+    }
+
+    /// A token for the private websocket feeds and the time to live it was given
+    pub async fn websocket_token(&self) -> Result<(SecretString, Duration)> {
+        let raw_response = self.get_websockets_token().await?;
+        let result = utils::parse_result(&raw_response)?;
+        let token = result.get("token").and_then(|t| t.as_str()).ok_or_else(|| Error::MissingField("token".to_string()))?;
+        let expires = result.get("expires").and_then(|e| e.as_u64()).unwrap_or(DEFAULT_TOKEN_TTL_SECS);
+        Ok((token.into(), Duration::from_secs(expires)))
+    }
+
+    /// A websocket token renewed `TOKEN_RENEWAL_MARGIN` before it expires, so that reconnections
+    /// can always authenticate. Renewals stop once every clone of the token is dropped.
+    pub async fn keep_websocket_token(self: Arc<Self>) -> Result<Arc<RwLock<SecretString>>> {
+        let (token, mut ttl) = self.websocket_token().await?;
+        let shared = Arc::new(RwLock::new(token));
+        let kept = Arc::downgrade(&shared);
+        actix_rt::spawn(async move {
+            loop {
+                tokio::time::delay_for(ttl.checked_sub(TOKEN_RENEWAL_MARGIN).unwrap_or(TOKEN_RETRY_DELAY)).await;
+                let kept = match kept.upgrade() {
+                    Some(kept) => kept,
+                    None => break,
+                };
+                match self.websocket_token().await {
+                    Ok((token, expires)) => {
+                        *kept.write().unwrap() = token;
+                        ttl = expires;
+                    }
+                    Err(e) => {
+                        warn!("Kraken : cannot renew the websocket token : {}", e);
+                        ttl = TOKEN_RENEWAL_MARGIN + TOKEN_RETRY_DELAY;
+                    }
+                }
+            }
+        });
+        Ok(shared)
+    }
+
     // TODO: add optional closing order
     /// Input:
     ///
@@ -794,6 +934,10 @@ impl KrakenApi {
     ///     0 = no expiration (default)
     ///     +<n> = expire <n> seconds from now
     ///     <n> = unix timestamp of expiration time
+    /// timeinforce = time in force (optional):
+    ///     GTC = good till canceled (default)
+    ///     IOC = immediate or cancel
+    ///     GTD = good till date, expiretm must be set
     /// userref = user reference id.  32-bit signed number.  (optional)
     /// validate = validate inputs only.  do not submit order (optional)
     ///
@@ -840,7 +984,7 @@ impl KrakenApi {
     /// close out your position.
     /// If you receive the error "EOrder:Trading agreement required", refer to your API key
     /// management page for further details.
-    pub async fn add_standard_order(&mut self,
+    pub async fn add_standard_order(&self,
                               pair: &str,
                               type_order: &str,
                               ordertype: &str,
@@ -851,6 +995,7 @@ impl KrakenApi {
                               oflags: &str,
                               starttm: &str,
                               expiretm: &str,
+                              timeinforce: &str,
                               userref: &str,
                               validate: &str)
                               -> Result<Map<String, Value>> {
@@ -865,6 +1010,7 @@ impl KrakenApi {
         params.insert("oflags", oflags);
         params.insert("starttm", starttm);
         params.insert("expiretm", expiretm);
+        params.insert("timeinforce", timeinforce);
         params.insert("userref", userref);
         params.insert("validate", validate);
         self.private_query("AddOrder", &mut params).await
@@ -882,12 +1028,66 @@ impl KrakenApi {
     /// pending = if set, order(s) is/are pending cancellation
     /// ```
     /// Note: txid may be a user reference id.
-    pub async fn cancel_open_order(&mut self, txid: &str) -> Result<Map<String, Value>> {
+    pub async fn cancel_open_order(&self, txid: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("txid", txid);
         self.private_query("CancelOrder", &mut params).await
     }
 
+    /// Input:
+    ///
+    /// ```json
+    /// txid = transaction id of the open order
+    /// pair = asset pair
+    /// volume = new order volume, empty to keep it
+    /// price = new price, the trigger price of conditional orders, empty to keep it
+    /// price2 = new limit price of stop limit orders, empty to keep it
+    /// ```
+    /// Result:
+    ///
+    /// ```json
+    /// status = "ok" when the order was edited
+    /// txid = transaction id of the new order
+    /// originaltxid = transaction id of the replaced order
+    /// ```
+    pub async fn edit_order(&self, txid: &str, pair: &str, volume: &str, price: &str, price2: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("txid", txid);
+        params.insert("pair", pair);
+        params.insert("volume", volume);
+        params.insert("price", price);
+        params.insert("price2", price2);
+        self.private_query("EditOrder", &mut params).await
+    }
+
+    /// Result:
+    ///
+    /// ```json
+    /// count = number of orders canceled
+    /// ```
+    pub async fn cancel_all(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.private_query("CancelAll", &mut params).await
+    }
+
+    /// Input:
+    ///
+    /// ```json
+    /// timeout = duration in seconds after which all the open orders are canceled, 0 disables the
+    /// timer
+    /// ```
+    /// Result:
+    ///
+    /// ```json
+    /// currentTime = time of the request
+    /// triggerTime = time at which the orders will be canceled, "0" if the timer is disabled
+    /// ```
+    pub async fn cancel_all_orders_after(&self, timeout: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("timeout", timeout);
+        self.private_query("CancelAllOrdersAfter", &mut params).await
+    }
+
     /// Input:
     ///
     /// ```json
@@ -903,7 +1103,7 @@ impl KrakenApi {
     /// fee = amount of fees that will be paid
     /// address-setup-fee = whether or not method has an address setup fee (optional)
     /// ```
-    pub async fn get_deposit_methods(&mut self, aclass: &str, asset: &str) -> Result<Map<String, Value>> {
+    pub async fn get_deposit_methods(&self, aclass: &str, asset: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("aclass", aclass);
         params.insert("asset", asset);
@@ -926,7 +1126,7 @@ impl KrakenApi {
     /// expiretm = expiration time in unix timestamp, or 0 if not expiring
     /// new = whether or not address has ever been used
     /// ```
-    pub async fn get_deposit_addresses(&mut self,
+    pub async fn get_deposit_addresses(&self,
                                  aclass: &str,
                                  asset: &str,
                                  method: &str,
@@ -966,7 +1166,7 @@ impl KrakenApi {
     ///     onhold = deposit is on hold pending review
     /// ```
     /// For information about the status, please refer to the IFEX financial transaction states.
-    pub async fn get_status_of_recent_deposits(&mut self,
+    pub async fn get_status_of_recent_deposits(&self,
                                          aclass: &str,
                                          asset: &str,
                                          method: &str)
@@ -994,7 +1194,7 @@ impl KrakenApi {
     /// limit = maximum net amount that can be withdrawn right now
     /// fee = amount of fees that will be paid
     /// ```
-    pub async fn get_withdrawal_information(&mut self,
+    pub async fn get_withdrawal_information(&self,
                                       aclass: &str,
                                       asset: &str,
                                       key: &str,
@@ -1022,7 +1222,7 @@ impl KrakenApi {
     /// ```json
     /// refid = reference id
     /// ```
-    pub async fn withdraw_funds(&mut self,
+    pub async fn withdraw_funds(&self,
                           aclass: &str,
                           asset: &str,
                           key: &str,
@@ -1065,7 +1265,7 @@ impl KrakenApi {
     ///     onhold = withdrawal is on hold pending review
     /// ```
     /// For information about the status, please refer to the IFEX financial transaction states.
-    pub async fn get_status_of_recent_withdrawals(&mut self,
+    pub async fn get_status_of_recent_withdrawals(&self,
                                             aclass: &str,
                                             asset: &str,
                                             method: &str)
@@ -1093,7 +1293,7 @@ impl KrakenApi {
     /// Note: Cancelation cannot be guaranteed. This will put in a cancelation request. Depending
     /// upon how far along the withdrawal process is, it may not be possible to cancel the
     /// withdrawal.
-    pub async fn request_withdrawal_cancelation(&mut self,
+    pub async fn request_withdrawal_cancelation(&self,
                                           aclass: &str,
                                           asset: &str,
                                           refid: &str)
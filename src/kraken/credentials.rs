@@ -1,27 +1,40 @@
 //! Contains the Kraken credentials.
 
 use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 
 use serde_json;
 use serde_json::Value;
 
 use crate::coinnect::Credentials;
+use crate::env_credentials::EnvCredentials;
 use crate::exchange::Exchange;
 use crate::helpers;
+use crate::helpers::secret::Redacted;
 use crate::error::*;
 
 use std::fs::File;
 use std::io::Read;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct KrakenCreds {
     exchange: Exchange,
     name: String,
     data: HashMap<String, String>,
 }
 
+impl fmt::Debug for KrakenCreds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KrakenCreds")
+            .field("exchange", &self.exchange)
+            .field("name", &self.name)
+            .field("data", &Redacted(&self.data))
+            .finish()
+    }
+}
+
 impl KrakenCreds {
     /// Create a new `KrakenCreds` from arguments.
     pub fn new(name: &str, api_key: &str, api_secret: &str) -> Self {
@@ -81,22 +94,62 @@ impl KrakenCreds {
 
         let data: Value = serde_json::from_str(&buffer)?;
         let json_obj = data.as_object()
-            .ok_or_else(|| ErrorKind::BadParse)?
+            .ok_or_else(|| Error::BadParse)?
             .get(name)
-            .ok_or_else(|| ErrorKind::MissingField(name.to_string()))?;
+            .ok_or_else(|| Error::MissingField(name.to_string()))?;
         let api_key = helpers::get_json_string(json_obj, "api_key")?;
         let api_secret = helpers::get_json_string(json_obj, "api_secret")?;
         let exchange = {
             let exchange_str = helpers::get_json_string(json_obj, "exchange")?;
             Exchange::from_str(exchange_str)
-                .chain_err(|| ErrorKind::InvalidFieldValue("exchange".to_string()))?
+                .with_context(|| Error::InvalidFieldValue("exchange".to_string()))?
         };
 
         if exchange != Exchange::Kraken {
-            return Err(ErrorKind::InvalidConfigType(Exchange::Kraken, exchange).into());
+            return Err(Error::InvalidConfigType(Exchange::Kraken, exchange));
         }
 
-        Ok(KrakenCreds::new(name, api_key, api_secret))
+        let mut creds = KrakenCreds::new(name, api_key, api_secret);
+        helpers::copy_client_settings(json_obj, &mut creds.data);
+        if let (Ok(key), Ok(secret)) = (helpers::get_json_string(json_obj, "futures_api_key"), helpers::get_json_string(json_obj, "futures_api_secret")) {
+            creds = creds.with_futures_keys(key, secret);
+        }
+        if let Ok(url) = helpers::get_json_string(json_obj, "futures_rest_url") {
+            creds = creds.with_futures_rest_url(url);
+        }
+        Ok(creds)
+    }
+
+    /// Create a new `KrakenCreds` from the environment variables `<prefix>_KRAKEN_API_KEY`
+    /// and `<prefix>_KRAKEN_API_SECRET`, see `EnvCredentials`.
+    /// The optional client settings are read the same way, e.g. `<prefix>_KRAKEN_PROXY`.
+    pub fn new_from_env(prefix: &str) -> Result<Self> {
+        let env = EnvCredentials::new(prefix, Exchange::Kraken);
+        let api_key = env.require("api_key")?;
+        let api_secret = env.require("api_secret")?;
+        let mut creds = KrakenCreds::new(&env.name(), &api_key, &api_secret);
+        env.copy_client_settings(&mut creds.data);
+        if let (Some(key), Some(secret)) = (env.get("futures_api_key"), env.get("futures_api_secret")) {
+            creds = creds.with_futures_keys(&key, &secret);
+        }
+        if let Some(url) = env.get("futures_rest_url") {
+            creds = creds.with_futures_rest_url(&url);
+        }
+        Ok(creds)
+    }
+
+    /// Add the api keys of Kraken Futures, which are distinct from the spot ones, see
+    /// `kraken_futures`
+    pub fn with_futures_keys(mut self, api_key: &str, api_secret: &str) -> Self {
+        self.data.insert("futures_api_key".to_string(), api_key.to_string());
+        self.data.insert("futures_api_secret".to_string(), api_secret.to_string());
+        self
+    }
+
+    /// Override the REST root of Kraken Futures, `rest_url` only applies to the spot api
+    pub fn with_futures_rest_url(mut self, url: &str) -> Self {
+        self.data.insert("futures_rest_url".to_string(), url.to_string());
+        self
     }
 }
 
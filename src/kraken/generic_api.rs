@@ -2,130 +2,379 @@
 //! This a more convenient and safe way to deal with the exchange since methods return a Result<>
 //! but this generic API does not provide all the functionnality that Kraken offers.
 
-use crate::exchange::{ExchangeApi};
+use crate::exchange::{ExchangeApi, Exchange, ExchangeCapabilities, SETTLEMENT_DELAY};
 use crate::kraken::api::KrakenApi;
+use crate::kraken::models::SystemState;
 
 use crate::error::*;
 use crate::types::*;
 use crate::kraken::utils;
 use crate::helpers;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::time::Duration;
 
 #[async_trait]
 impl ExchangeApi for KrakenApi {
-    async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
 
         let raw_response = self.get_ticker_information(pair_name).await?;
 
         let result = utils::parse_result(&raw_response)?;
 
-        let price = helpers::from_json_bigdecimal(&result[*pair_name]["c"][0], "c")?;
-        let ask = helpers::from_json_bigdecimal(&result[*pair_name]["a"][0], "a")?;
-        let bid = helpers::from_json_bigdecimal(&result[*pair_name]["b"][0], "b")?;
-        let vol = helpers::from_json_bigdecimal(&result[*pair_name]["v"][0], "v")?;
-
-        Ok(Ticker {
-               timestamp: helpers::get_unix_timestamp_ms(),
-               pair: pair,
-               last_trade_price: price,
-               lowest_ask: ask,
-               highest_bid: bid,
-               volume: Some(vol),
-           })
-
+        utils::parse_ticker(pair, &result[*pair_name], self.keep_raw)
     }
 
-    async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
 
         let raw_response = self.get_order_book(pair_name, "1000").await?; // 1000 entries max
 
         let result = utils::parse_result(&raw_response)?;
 
-        let mut ask_offers = Vec::new();
-        let mut bid_offers = Vec::new();
+        utils::parse_orderbook(pair, &result[*pair_name])
+    }
 
-        let ask_array =
-            result[*pair_name]["asks"]
-                .as_array()
-                .ok_or_else(|| {
-                                ErrorKind::InvalidFieldFormat(format!("{}.asks",
-                                                                      result[*pair_name]))
-                            })?;
-        let bid_array =
-            result[*pair_name]["bids"]
-                .as_array()
-                .ok_or_else(|| {
-                                ErrorKind::InvalidFieldFormat(format!("{}.bids",
-                                                                      result[*pair_name]))
-                            })?;
+    async fn add_order(&self,
+                 order_type: OrderType,
+                 pair: Pair,
+                 quantity: Volume,
+                 price: Option<Price>)
+                 -> Result<OrderInfo> {
+        self.simple_order(OrderRequest::new(order_type, pair, &quantity, &price)).await
+    }
 
-        for ask in ask_array {
-            let price = helpers::from_json_bigdecimal(&ask[0], "ask price")?;
-            let volume = helpers::from_json_bigdecimal(&ask[1], "ask volume")?;
+    /// Client ids are sent as the `userref` of the order, a 32 bits integer
+    async fn add_order_with_id(&self,
+                               order_type: OrderType,
+                               pair: Pair,
+                               quantity: Volume,
+                               price: Option<Price>,
+                               client_id: &str)
+                               -> Result<OrderInfo> {
+        client_id.parse::<i32>().map_err(|_| Error::InvalidFieldValue("userref".to_string()))?;
+        self.simple_order(OrderRequest::new(order_type, pair, &quantity, &price).with_client_id(client_id)).await
+    }
 
-            ask_offers.push((price, volume));
+    /// Open orders are looked up first, then the closed ones. Userrefs are not unique, only the
+    /// limit and market orders of `pair` are considered and their request is returned for the
+    /// caller to check the volume.
+    async fn order_by_client_id(&self, pair: Pair, client_id: &str) -> Result<Option<OrderInfo>> {
+        let raw_response = self.get_open_orders("false", client_id).await?;
+        let mut result = utils::parse_result(&raw_response)?;
+        let mut found = utils::find_order(&result["open"], pair);
+        if found.is_none() {
+            let raw_response = self.get_closed_orders("false", client_id, "", "", "", "").await?;
+            result = utils::parse_result(&raw_response)?;
+            found = utils::find_order(&result["closed"], pair);
         }
 
-        for bid in bid_array {
-            let price = helpers::from_json_bigdecimal(&bid[0], "bid price")?;
-            let volume = helpers::from_json_bigdecimal(&bid[1], "bid volume")?;
+        Ok(found.map(|(txid, request)| OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: vec![txid],
+            raw: helpers::raw_payload(self.keep_raw, &result),
+            request: Some(request.with_client_id(client_id)),
+        }))
+    }
 
-            bid_offers.push((price, volume));
+    /// Limit orders are sent with `timeinforce=IOC`, the filled quantity is the executed volume of
+    /// the order once Kraken has closed it
+    async fn add_ioc_order(&self,
+                           order_type: OrderType,
+                           pair: Pair,
+                           quantity: Volume,
+                           price: Price)
+                           -> Result<Execution> {
+        match order_type {
+            OrderType::BuyLimit | OrderType::SellLimit => (),
+            _ => return Err(Error::OrderTypeUnsupported),
         }
+        let request = OrderRequest::new(order_type, pair, &quantity, &Some(price.clone()));
+        let order = self.standard_order(pair, order_type, "limit", &price.to_string(), "", "IOC", quantity, request).await?;
+        tokio::time::delay_for(SETTLEMENT_DELAY).await;
+        let executed = self.query_orders_info("false", "", &order.identifier.join(",")).await
+            .and_then(|raw_response| utils::parse_result(&raw_response))
+            .and_then(|result| utils::executed_volume(&Value::Object(result), &order.identifier));
+        let filled = match executed {
+            Ok(filled) => filled,
+            Err(e) => {
+                warn!("{:?} : fills of order {:?} unknown : {}", pair, order.identifier, e);
+                None
+            }
+        };
+        Ok(Execution { order, filled })
+    }
+
+    /// Stop loss, take profit and trailing stop orders are native, trailing stops take the
+    /// distance to the market as `trigger_price`
+    async fn add_conditional_order(&self,
+                             order_type: OrderType,
+                             pair: Pair,
+                             quantity: Volume,
+                             trigger_price: Price,
+                             price: Option<Price>)
+                             -> Result<OrderInfo> {
+        let trigger_str = trigger_price.to_string();
+        let request = OrderRequest::conditional(order_type, pair, &quantity, &trigger_price, &price);
+        match order_type {
+            OrderType::StopLoss(_) => self.standard_order(pair, order_type, "stop-loss", &trigger_str, "", "", quantity, request).await,
+            OrderType::StopLimit(_) => {
+                let limit_str = price.ok_or(Error::MissingPrice)?.to_string();
+                self.standard_order(pair, order_type, "stop-loss-limit", &trigger_str, &limit_str, "", quantity, request).await
+            }
+            OrderType::TakeProfit(_) => self.standard_order(pair, order_type, "take-profit", &trigger_str, "", "", quantity, request).await,
+            OrderType::TrailingStop(_) => self.standard_order(pair, order_type, "trailing-stop", &format!("+{}", trigger_str), "", "", quantity, request).await,
+            _ => Err(Error::OrderTypeUnsupported),
+        }
+    }
+
+    async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+        for txid in &order.identifier {
+            let raw_response = self.cancel_open_order(txid).await?;
+            utils::parse_result(&raw_response)?;
+        }
+        Ok(())
+    }
+
+    async fn cancel_all_orders(&self) -> Result<()> {
+        let raw_response = self.cancel_all().await?;
+        utils::parse_result(&raw_response)?;
+        Ok(())
+    }
+
+    /// The timer is kept in whole seconds, a timeout is rounded up to the next second so that it
+    /// does not disarm the timer. Kraken advises to set it again every 15 to 30 seconds with a
+    /// timeout of 60 seconds.
+    async fn set_cancel_after(&self, timeout: Duration) -> Result<()> {
+        let secs = utils::cancel_after_secs(timeout);
+        let raw_response = self.cancel_all_orders_after(&secs.to_string()).await?;
+        utils::parse_result(&raw_response)?;
+        Ok(())
+    }
 
-        Ok(Orderbook {
+    /// Native, the order is edited in one request. The new price of a conditional order is its
+    /// limit price, its trigger price is kept.
+    async fn amend_order(&self, order: &OrderInfo, new_price: Option<Price>, new_amount: Option<Volume>) -> Result<OrderInfo> {
+        let request = order.request.as_ref().ok_or_else(|| Error::MissingField("request".to_string()))?;
+        let txid = order.identifier.first().ok_or_else(|| Error::MissingField("txid".to_string()))?;
+        let pair_name = utils::get_pair_string(&request.pair).ok_or(Error::PairUnsupported)?;
+        let volume = new_amount.as_ref().map(|v| v.to_string()).unwrap_or_default();
+        let price = new_price.as_ref().map(|p| p.to_string()).unwrap_or_default();
+        let (price, price2) = if request.trigger_price.is_some() { ("", price.as_str()) } else { (price.as_str(), "") };
+
+        let raw_response = self.edit_order(txid, pair_name, &volume, price, price2).await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let txid = result["txid"].as_str().ok_or_else(|| Error::MissingField("txid".to_string()))?;
+        Ok(OrderInfo {
                timestamp: helpers::get_unix_timestamp_ms(),
-               pair: pair,
-               asks: ask_offers,
-               bids: bid_offers,
+               identifier: vec![txid.to_string()],
+               raw: helpers::raw_payload(self.keep_raw, &result),
+               request: Some(request.amended(new_price, new_amount)),
            })
     }
 
-    async fn add_order(&mut self,
-                 order_type: OrderType,
-                 pair: Pair,
-                 quantity: Volume,
-                 price: Option<Price>)
-                 -> Result<OrderInfo> {
-        let pair_name = match utils::get_pair_string(&pair) {
-            Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+    /// Pairs on a maker/taker schedule have their maker fee in `fees_maker`, the others pay the
+    /// same fee in both cases. Fees are given in percent.
+    async fn fees(&self, pair: Pair) -> Result<FeeRates> {
+        let pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let raw_response = self.get_trade_volume(pair_name, "true").await?;
+        let result = utils::parse_result(&raw_response)?;
+        let taker = helpers::from_json_bigdecimal(&result.get("fees").map(|f| f[*pair_name]["fee"].clone()).unwrap_or_default(), "fee")?;
+        let maker = helpers::from_json_bigdecimal_opt(&result.get("fees_maker").map(|f| f[*pair_name]["fee"].clone()).unwrap_or_default())
+            .unwrap_or_else(|| taker.clone());
+        Ok(FeeRates::from_percent(maker, taker))
+    }
+
+    async fn balances(&self) -> Result<Balances> {
+        let raw_response = self.get_account_balance().await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let mut balances = Balances::new();
+
+        for (key, val) in result.iter() {
+            let currency = utils::get_currency_enum(key);
+
+            match currency {
+                Some(c) => {
+                    let amount = helpers::from_json_bigdecimal(&val, "amount")?;
+
+                    balances.insert(c, amount);
+                },
+                _ => ()
+            }
+        }
+
+        Ok(balances)
+    }
+
+    async fn withdraw(&self, currency: Currency, amount: Amount, destination: &str) -> Result<WithdrawalInfo> {
+        let asset = utils::get_currency_string(currency).ok_or_else(|| Error::InvalidArguments)?;
+        let raw_response = self.withdraw_funds("currency", &asset, destination, &amount.to_string()).await?;
+        let result = utils::parse_result(&raw_response)?;
+        Ok(WithdrawalInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: result.get("refid").and_then(|id| id.as_str()).map(|id| id.to_string()),
+        })
+    }
+
+    /// Pages hold the 50 trades of every pair following the offset given as cursor, those of
+    /// other pairs are skipped
+    async fn fills_page(&self, pair: Pair, cursor: Option<String>) -> Result<FillsPage> {
+        let pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let ofs = cursor.unwrap_or_default();
+        let raw_response = self.get_trades_history("all", "false", "", "", &ofs).await?;
+        let result = utils::parse_result(&raw_response)?;
+        let trades = result.get("trades").and_then(|t| t.as_object()).ok_or_else(|| Error::MissingField("trades".to_string()))?;
+        let count = result.get("count").and_then(|c| c.as_u64()).unwrap_or_default();
+        let fetched = ofs.parse::<u64>().unwrap_or_default() + trades.len() as u64;
+        let fills = trades.iter()
+            .filter(|(_, trade)| trade["pair"].as_str() == Some(*pair_name))
+            .map(|(trade_id, trade)| utils::parse_fill(trade_id, pair, trade))
+            .collect::<Result<Vec<FillEvent>>>()?;
+        let next = if !trades.is_empty() && fetched < count { Some(fetched.to_string()) } else { None };
+        Ok(FillsPage { fills, next })
+    }
+
+    /// Kraken gives its time to the second. Nonces only have to increase, they are not corrected.
+    async fn time(&self) -> Result<i64> {
+        let sent_ms = helpers::get_unix_timestamp_ms();
+        let raw_response = self.get_server_time().await?;
+        let result = utils::parse_result(&raw_response)?;
+        let server_ms = result.get("unixtime").and_then(|t| t.as_i64()).ok_or_else(|| Error::MissingField("unixtime".to_string()))? * 1000;
+        self.clock.observe(server_ms, sent_ms, helpers::get_unix_timestamp_ms());
+        Ok(server_ms)
+    }
+
+    async fn status(&self) -> Result<ExchangeStatus> {
+        let sent_ms = helpers::get_unix_timestamp_ms();
+        let status = self.system_status().await?;
+        let latency_ms = (helpers::get_unix_timestamp_ms() - sent_ms).max(0) as u64;
+        let (state, message) = match status.status {
+            SystemState::Online => (ExchangeState::Online, None),
+            SystemState::Maintenance => (ExchangeState::Maintenance, None),
+            SystemState::CancelOnly => (ExchangeState::CancelOnly, None),
+            SystemState::PostOnly => (ExchangeState::PostOnly, None),
+            SystemState::LimitOnly => (ExchangeState::LimitOnly, None),
+            SystemState::Unknown => (ExchangeState::Degraded, Some("unknown system status".to_string())),
         };
+        Ok(ExchangeStatus { state, latency_ms, message })
+    }
 
-        let direction = match order_type {
-            OrderType::BuyLimit | OrderType::BuyMarket => "buy",
-            OrderType::SellLimit | OrderType::SellMarket => "sell",
+    fn clock_skew(&self) -> Option<i64> {
+        self.clock.skew()
+    }
+
+    /// At most the 1000 trades following `since`, the most recent ones without it
+    async fn public_trades(&self, pair: Pair, since: Option<DateTime<Utc>>) -> Result<Vec<PublicTrade>> {
+        let pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        // Trade ids are timestamps in ns
+        let since = since.map(|s| s.timestamp_nanos().to_string()).unwrap_or_default();
+        let raw_response = self.get_recent_trades(pair_name, &since).await?;
+        let result = utils::parse_result(&raw_response)?;
+        let trades = result.get(*pair_name).and_then(|t| t.as_array()).ok_or_else(|| Error::MissingField(pair_name.to_string()))?;
+        trades.iter().map(|t| {
+            // [<price>, <volume>, <time>, <buy/sell>, <market/limit>, <miscellaneous>]
+            let time = t[2].as_f64().ok_or_else(|| Error::InvalidFieldFormat(t[2].to_string()))?;
+            Ok(PublicTrade {
+                id: None,
+                event_ms: (time * 1000.0) as i64,
+                pair,
+                price: helpers::from_json_bigdecimal(&t[0], "price")?,
+                amount: helpers::from_json_bigdecimal(&t[1], "volume")?,
+                side: match t[3].as_str() {
+                    Some("b") => TradeType::Buy,
+                    Some("s") => TradeType::Sell,
+                    other => TradeType::unknown(other.unwrap_or_default()),
+                },
+            })
+        }).collect()
+    }
+
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        let asset_pairs = self.asset_pairs().await?;
+        Ok(asset_pairs.iter().filter_map(|p| utils::get_pair_enum(&p.name).copied()).collect())
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Kraken.capabilities()
+    }
+}
+
+impl KrakenApi {
+    /// Place a spot order on the public book or the dark pool of the currencies of `pair`.
+    /// Perpetual futures are traded with `kraken_futures::KrakenFuturesApi`.
+    pub async fn add_instrument_order(&self,
+                                      kind: InstrumentKind,
+                                      order_type: OrderType,
+                                      pair: Pair,
+                                      quantity: Volume,
+                                      price: Option<Price>)
+                                      -> Result<OrderInfo> {
+        let pair = match kind {
+            InstrumentKind::Spot => pair.lit(),
+            InstrumentKind::DarkPool => pair.dark_pool().ok_or(Error::PairUnsupported)?,
+            InstrumentKind::Perpetual => return Err(Error::PairUnsupported),
         };
+        self.add_order(order_type, pair, quantity, price).await
+    }
 
-        let order_type_str = match order_type {
+    /// Place a limit or market order
+    async fn simple_order(&self, request: OrderRequest) -> Result<OrderInfo> {
+        let order_type_str = match request.order_type {
             OrderType::BuyLimit | OrderType::SellLimit => "limit",
             OrderType::BuyMarket | OrderType::SellMarket => "market",
+            _ => return Err(Error::OrderTypeUnsupported),
         };
+        let price_str = request.price.as_ref().map(|p| p.to_string()).unwrap_or_default();
+        self.standard_order(request.pair, request.order_type, order_type_str, &price_str, "", "", request.quantity.clone(), request).await
+    }
 
-        let mut price_str = "".to_string();
-        if price.is_some() {
-            price_str = price.unwrap().to_string()
+    async fn standard_order(&self,
+                            pair: Pair,
+                            order_type: OrderType,
+                            order_type_str: &str,
+                            price: &str,
+                            price2: &str,
+                            timeinforce: &str,
+                            quantity: Volume,
+                            request: OrderRequest)
+                            -> Result<OrderInfo> {
+        let pair_name = match utils::get_pair_string(&pair) {
+            Some(name) => name,
+            None => return Err(Error::PairUnsupported),
+        };
+
+        // The dark pool only matches limit orders
+        if pair.instrument_kind() == InstrumentKind::DarkPool && order_type_str != "limit" {
+            return Err(Error::OrderTypeUnsupported);
+        }
+
+        let direction = match order_type.side() {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
         };
 
         let raw_response = self.add_standard_order(pair_name,
                                                    direction,
                                                    order_type_str,
-                                                   &price_str,
-                                                   "",
+                                                   price,
+                                                   price2,
                                                    &quantity.to_string(),
                                                    "",
                                                    "",
                                                    "",
                                                    "",
-                                                   "",
+                                                   timeinforce,
+                                                   request.client_id.as_deref().unwrap_or_default(),
                                                    "").await?;
 
         let result = utils::parse_result(&raw_response)?;
@@ -135,39 +384,19 @@ impl ExchangeApi for KrakenApi {
         let list_id =
             result["txid"]
                 .as_array()
-                .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["txid"])))?;
+                .ok_or_else(|| Error::InvalidFieldFormat(format!("{}", result["txid"])))?;
 
         for id in list_id {
             txids.push(id.as_str()
-                           .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", id)))?
+                           .ok_or_else(|| Error::InvalidFieldFormat(format!("{}", id)))?
                            .to_string());
         }
 
         Ok(OrderInfo {
                timestamp: helpers::get_unix_timestamp_ms(),
                identifier: txids,
+               raw: helpers::raw_payload(self.keep_raw, &result),
+               request: Some(request),
            })
     }
-
-    async fn balances(&mut self) -> Result<Balances> {
-        let raw_response = self.get_account_balance().await?;
-        let result = utils::parse_result(&raw_response)?;
-
-        let mut balances = Balances::new();
-
-        for (key, val) in result.iter() {
-            let currency = utils::get_currency_enum(key);
-
-            match currency {
-                Some(c) => {
-                    let amount = helpers::from_json_bigdecimal(&val, "amount")?;
-
-                    balances.insert(c, amount);
-                },
-                _ => ()
-            }
-        }
-
-        Ok(balances)
-    }
 }
@@ -1,10 +1,20 @@
 //! Use this module to interact with Kraken exchange.
 //! See examples for more informations.
 
+#[cfg(feature = "native")]
 pub mod api;
+#[cfg(feature = "native")]
 pub mod generic_api;
+pub mod models;
+#[cfg(feature = "native")]
 pub mod credentials;
 pub mod utils;
+#[cfg(feature = "streaming")]
+pub mod streaming_api;
+#[cfg(feature = "streaming")]
+pub mod ws_trading;
 
+#[cfg(feature = "native")]
 pub use self::credentials::KrakenCreds;
+#[cfg(feature = "native")]
 pub use self::api::KrakenApi;
@@ -0,0 +1,180 @@
+//! Typed results of the Kraken public endpoints, see `KrakenApi::ohlc`, `KrakenApi::spread`,
+//! `KrakenApi::asset_pairs` and `KrakenApi::system_status`.
+
+use bigdecimal::BigDecimal;
+use serde_json::Value;
+use serde_json::value::Map;
+
+use crate::error::*;
+use crate::helpers;
+use crate::kraken::utils;
+use crate::types::Currency;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ohlc {
+    /// UNIX timestamp in seconds of the start of the candle
+    pub time: i64,
+    pub open: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub close: BigDecimal,
+    pub vwap: BigDecimal,
+    pub volume: BigDecimal,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct OhlcData {
+    /// Kraken name of the pair
+    pub pair: String,
+    /// The last candle is the current one, not committed yet
+    pub candles: Vec<Ohlc>,
+    /// Id to poll new committed candles with
+    pub last: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpreadEntry {
+    /// UNIX timestamp in seconds
+    pub time: i64,
+    pub bid: BigDecimal,
+    pub ask: BigDecimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpreadData {
+    /// Kraken name of the pair
+    pub pair: String,
+    pub entries: Vec<SpreadEntry>,
+    /// Id to poll new spreads with, inclusive
+    pub last: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetPair {
+    /// Kraken name of the pair, e.g. `XXBTZEUR`
+    pub name: String,
+    pub altname: String,
+    /// Name on the websocket api, e.g. `XBT/EUR`
+    pub wsname: Option<String>,
+    /// Kraken asset ids of the components, e.g. `XXBT`
+    pub base: String,
+    pub quote: String,
+    pub base_currency: Option<Currency>,
+    pub quote_currency: Option<Currency>,
+    pub pair_decimals: u32,
+    pub lot_decimals: u32,
+    /// Minimum order volume, in base currency
+    pub ordermin: Option<BigDecimal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemState {
+    Online,
+    Maintenance,
+    CancelOnly,
+    PostOnly,
+    LimitOnly,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SystemStatus {
+    pub status: SystemState,
+    /// RFC 3339 time of the status
+    pub timestamp: String,
+}
+
+fn decimal(v: &Value, field: &str) -> Result<BigDecimal> {
+    helpers::from_json_bigdecimal_opt(v).ok_or_else(|| Error::InvalidFieldFormat(field.to_string()))
+}
+
+fn integer(v: &Value, field: &str) -> Result<i64> {
+    v.as_i64()
+        .or_else(|| v.as_f64().map(|f| f as i64))
+        .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+        .ok_or_else(|| Error::InvalidFieldFormat(field.to_string()))
+}
+
+/// The entries of the only pair of a result, along with the `last` id
+fn pair_entries(result: &Map<String, Value>) -> Result<(String, &Vec<Value>, i64)> {
+    let (pair, entries) = result.iter().find(|(k, _)| k.as_str() != "last")
+        .ok_or_else(|| Error::MissingField("pair".to_string()))?;
+    let entries = entries.as_array().ok_or_else(|| Error::InvalidFieldFormat(pair.to_string()))?;
+    let last = result.get("last").map_or(Ok(0), |l| integer(l, "last"))?;
+    Ok((pair.to_string(), entries, last))
+}
+
+impl OhlcData {
+    pub fn from_result(result: &Map<String, Value>) -> Result<Self> {
+        let (pair, entries, last) = pair_entries(result)?;
+        let candles = entries.iter().map(|e| {
+            Ok(Ohlc {
+                time: integer(&e[0], "time")?,
+                open: decimal(&e[1], "open")?,
+                high: decimal(&e[2], "high")?,
+                low: decimal(&e[3], "low")?,
+                close: decimal(&e[4], "close")?,
+                vwap: decimal(&e[5], "vwap")?,
+                volume: decimal(&e[6], "volume")?,
+                count: integer(&e[7], "count")? as u64,
+            })
+        }).collect::<Result<Vec<Ohlc>>>()?;
+        Ok(OhlcData { pair, candles, last })
+    }
+}
+
+impl SpreadData {
+    pub fn from_result(result: &Map<String, Value>) -> Result<Self> {
+        let (pair, entries, last) = pair_entries(result)?;
+        let entries = entries.iter().map(|e| {
+            Ok(SpreadEntry { time: integer(&e[0], "time")?, bid: decimal(&e[1], "bid")?, ask: decimal(&e[2], "ask")? })
+        }).collect::<Result<Vec<SpreadEntry>>>()?;
+        Ok(SpreadData { pair, entries, last })
+    }
+}
+
+impl AssetPair {
+    pub fn from_result(name: &str, info: &Value) -> Result<Self> {
+        let string = |field: &str| info[field].as_str().map(|s| s.to_string());
+        let base = string("base").ok_or_else(|| Error::MissingField("base".to_string()))?;
+        let quote = string("quote").ok_or_else(|| Error::MissingField("quote".to_string()))?;
+        Ok(AssetPair {
+            name: name.to_string(),
+            altname: string("altname").unwrap_or_default(),
+            wsname: string("wsname"),
+            base_currency: utils::parse_asset(&base),
+            quote_currency: utils::parse_asset(&quote),
+            base,
+            quote,
+            pair_decimals: integer(&info["pair_decimals"], "pair_decimals")? as u32,
+            lot_decimals: integer(&info["lot_decimals"], "lot_decimals")? as u32,
+            ordermin: helpers::from_json_bigdecimal_opt(&info["ordermin"]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod kraken_models_tests {
+    use super::*;
+
+    #[test]
+    fn parse_ohlc_and_asset_pairs() {
+        let result: Value = serde_json::from_str(r#"{
+            "XXBTZEUR": [[1577836800, "6400.1", "6420.0", "6390.5", "6410.2", "6405.3", "12.5", 120]],
+            "last": 1577836740
+        }"#).unwrap();
+        let ohlc = OhlcData::from_result(result.as_object().unwrap()).unwrap();
+        assert_eq!(ohlc.pair, "XXBTZEUR");
+        assert_eq!(ohlc.candles[0].count, 120);
+        assert_eq!(ohlc.last, 1577836740);
+
+        let info: Value = serde_json::from_str(r#"{"altname": "XBTEUR", "wsname": "XBT/EUR", "base": "XXBT",
+            "quote": "ZEUR", "pair_decimals": 1, "lot_decimals": 8, "ordermin": "0.002"}"#).unwrap();
+        let pair = AssetPair::from_result("XXBTZEUR", &info).unwrap();
+        assert_eq!(pair.base_currency, Some(Currency::BTC));
+        assert_eq!(pair.quote_currency, Some(Currency::EUR));
+    }
+}
@@ -0,0 +1,269 @@
+//! Stream the public channels of Kraken : trades, and candles from the native `ohlc` channel.
+//! Kraken sends the candle being built on every trade, without saying when it closes. A candle is
+//! sent as closed once the first update of the next one arrives.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::{io::SinkWrite, Addr, Recipient};
+use actix_codec::Framed;
+use async_trait::async_trait;
+use awc::{ws::{Codec, Message}, BoxedSocket};
+use bytes::{Buf, Bytes};
+use futures::future::LocalBoxFuture;
+use futures::stream::SplitSink;
+use serde_json::Value;
+
+use crate::coinnect::Credentials;
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeCapabilities};
+use crate::exchange_bot::{shutdown_ws, DefaultWsActor, ObserveRawFrames, RawFrameEvent, WsHandler, ExchangeBot};
+use crate::helpers;
+use crate::helpers::delivery::{Delivery, DeliveryPolicy};
+use crate::helpers::failover::Endpoints;
+use crate::kraken::ws_trading::{ws_pair, ws_pair_name};
+use crate::metrics;
+use crate::types::{Channel, LiveCandle, LiveEvent, LiveEventEnveloppe, LiveTrade, Pair, SubscriptionResult, TradeMeta, TradeType};
+
+static WEBSOCKET_URL: &'static str = "wss://ws.kraken.com";
+
+/// Lengths in minutes of the candles of the `ohlc` channel
+const OHLC_INTERVALS: [u64; 9] = [1, 5, 15, 30, 60, 240, 1440, 10080, 21600];
+
+/// Length in minutes of the native candles of this length in seconds, if Kraken has one
+pub fn ohlc_interval(interval_secs: u64) -> Option<u64> {
+    OHLC_INTERVALS.iter().copied().find(|minutes| minutes * 60 == interval_secs)
+}
+
+pub struct KrakenBot {
+    addr: Addr<DefaultWsActor>,
+    endpoints: Arc<Endpoints>,
+}
+
+impl ExchangeBot for KrakenBot {
+    fn is_connected(&self) -> bool {
+        self.addr.connected()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Kraken.capabilities()
+    }
+
+    fn observe_raw_frames(&self, recipient: Recipient<RawFrameEvent>) -> Result<()> {
+        self.addr.do_send(ObserveRawFrames(Exchange::Kraken, recipient));
+        Ok(())
+    }
+
+    fn endpoints(&self) -> Option<Arc<Endpoints>> {
+        Some(self.endpoints.clone())
+    }
+
+    fn shutdown(&self) -> LocalBoxFuture<'static, ()> {
+        shutdown_ws(&self.addr)
+    }
+}
+
+#[derive(Debug)]
+pub struct KrakenStreamingApi {
+    delivery: Delivery<LiveEventEnveloppe>,
+    channels: HashMap<Channel, HashSet<Pair>>,
+    /// Length of the candles in minutes, see `ohlc_interval`
+    candle_interval: u64,
+    /// Latest update of the candle being built for each pair
+    candles: HashMap<Pair, LiveCandle>,
+}
+
+impl KrakenStreamingApi {
+    /// Create a new Kraken exchange bot, only trades and candles are streamed.
+    /// `candle_interval_secs` must be one of the intervals of the `ohlc` channel.
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, candle_interval_secs: u64, delivery: DeliveryPolicy, recipients: Vec<Recipient<LiveEventEnveloppe>>, raw_frames: Option<Recipient<RawFrameEvent>>) -> Result<KrakenBot> {
+        if let Some(channel) = channels.keys().find(|c| **c != Channel::LiveTrades && **c != Channel::LiveCandles) {
+            return Err(Error::ChannelUnsupported(Exchange::Kraken, channel.clone()));
+        }
+        let candle_interval = match ohlc_interval(candle_interval_secs) {
+            Some(minutes) => minutes,
+            None if channels.contains_key(&Channel::LiveCandles) => return Err(Error::InvalidFieldValue("candle_interval_secs".to_string())),
+            None => 1,
+        };
+        let connection = creds.connection();
+        let api = KrakenStreamingApi { delivery: Delivery::new(recipients, delivery), channels, candle_interval, candles: HashMap::new() };
+        let endpoints = Arc::new(connection.ws_endpoints(Exchange::Kraken, WEBSOCKET_URL));
+        let addr = DefaultWsActor::new("KrakenStream", endpoints.clone(), connection.proxy()?, Some(Duration::from_secs(20)), raw_frames.map(|r| (Exchange::Kraken, r)).into_iter().collect(), Box::new(api)).await?;
+        Ok(KrakenBot { addr, endpoints })
+    }
+
+    fn send(&mut self, le: LiveEvent) {
+        metrics::ws_event(Exchange::Kraken, &le);
+        self.delivery.send(LiveEventEnveloppe(Exchange::Kraken, le));
+    }
+
+    /// The candles to send for an update of the candle of `pair` : the previous one once closed,
+    /// then the update
+    fn candle_update(&mut self, pair: Pair, candle: LiveCandle) -> Vec<LiveCandle> {
+        let mut updates = vec![];
+        if let Some(previous) = self.candles.get(&pair) {
+            if previous.event_ms < candle.event_ms {
+                updates.push(LiveCandle { closed: true, ..previous.clone() });
+            }
+        }
+        self.candles.insert(pair, candle.clone());
+        updates.push(candle);
+        updates
+    }
+
+    /// `{"event": "subscriptionStatus", "status": "subscribed", "pair": "XBT/USD", "subscription": {"name": "ohlc"}}`
+    fn subscription_status(&mut self, v: &Value) {
+        let channel = match v["subscription"]["name"].as_str() {
+            Some("trade") => Channel::LiveTrades,
+            Some("ohlc") => Channel::LiveCandles,
+            _ => return,
+        };
+        let pair = match v["pair"].as_str().and_then(ws_pair) {
+            Some(pair) => pair,
+            None => return,
+        };
+        let result = match v["status"].as_str() {
+            Some("subscribed") => SubscriptionResult::accepted(channel, pair),
+            Some("error") => {
+                let reason = v["errorMessage"].as_str().unwrap_or("unknown error");
+                warn!("Kraken : {}", reason);
+                SubscriptionResult::rejected(channel, pair, reason)
+            }
+            _ => return,
+        };
+        self.send(LiveEvent::SubscriptionResult(result));
+    }
+}
+
+/// Trades of a `trade` message : `[channelID, [[price, volume, time, side, orderType, misc], ...], "trade", "XBT/USD"]`
+fn parse_trades(pair: Pair, v: &Value) -> Result<Vec<LiveTrade>> {
+    let trades = v[1].as_array().ok_or_else(|| Error::MissingField("trades".to_string()))?;
+    trades.iter().map(|t| {
+        let time: f64 = t[2].as_str().and_then(|t| t.parse().ok()).ok_or_else(|| Error::InvalidFieldFormat("time".to_string()))?;
+        Ok(LiveTrade {
+            event_ms: (time * 1000.0) as i64,
+            pair,
+            amount: helpers::from_json_bigdecimal(&t[1], "volume")?,
+            price: helpers::from_json_bigdecimal(&t[0], "price")?,
+            tt: match t[3].as_str() {
+                Some("b") => TradeType::Buy,
+                Some("s") => TradeType::Sell,
+                other => TradeType::Unknown(other.unwrap_or_default().to_string()),
+            },
+            meta: TradeMeta::default(),
+        })
+    }).collect()
+}
+
+/// Candle of an `ohlc` message :
+/// `[channelID, [time, etime, open, high, low, close, vwap, volume, count], "ohlc-5", "XBT/USD"]`
+fn parse_candle(v: &Value) -> Result<LiveCandle> {
+    let interval_secs = v[2].as_str()
+        .and_then(|name| name.trim_start_matches("ohlc-").parse::<u64>().ok())
+        .ok_or_else(|| Error::InvalidFieldFormat("channelName".to_string()))? * 60;
+    let ohlc = &v[1];
+    let end: f64 = ohlc[1].as_str().and_then(|t| t.parse().ok()).ok_or_else(|| Error::InvalidFieldFormat("etime".to_string()))?;
+    Ok(LiveCandle {
+        event_ms: end as i64 * 1000 - interval_secs as i64 * 1000,
+        pair: v[3].as_str().unwrap_or_default().to_string(),
+        interval_secs,
+        open: helpers::from_json_bigdecimal(&ohlc[2], "open")?,
+        high: helpers::from_json_bigdecimal(&ohlc[3], "high")?,
+        low: helpers::from_json_bigdecimal(&ohlc[4], "low")?,
+        close: helpers::from_json_bigdecimal(&ohlc[5], "close")?,
+        volume: helpers::from_json_bigdecimal(&ohlc[7], "volume")?,
+        closed: false,
+    })
+}
+
+#[async_trait]
+impl WsHandler for KrakenStreamingApi {
+    fn handle_in(&mut self, _w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, msg: Bytes) {
+        let v: Value = match serde_json::from_slice(msg.bytes()) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Kraken : cannot parse {:?} : {}", msg, e);
+                return;
+            }
+        };
+        if v["event"] == "subscriptionStatus" {
+            self.subscription_status(&v);
+            return;
+        }
+        // Heartbeats and system status are objects, channel messages are arrays
+        let pair = match v[3].as_str().and_then(ws_pair) {
+            Some(pair) => pair,
+            None => return,
+        };
+        match v[2].as_str() {
+            Some("trade") => match parse_trades(pair, &v) {
+                Ok(trades) => for trade in trades {
+                    self.send(LiveEvent::LiveTrade(trade));
+                },
+                Err(e) => warn!("Kraken : cannot parse the trades {} : {}", v, e),
+            },
+            Some(name) if name.starts_with("ohlc") => match parse_candle(&v) {
+                Ok(candle) => for candle in self.candle_update(pair, candle) {
+                    self.send(LiveEvent::LiveCandle(candle));
+                },
+                Err(e) => warn!("Kraken : cannot parse the candle {} : {}", v, e),
+            },
+            _ => (),
+        }
+    }
+
+    /// Candles being built are sent again after subscribing
+    fn handle_reconnect(&mut self) {
+        self.candles.clear();
+    }
+
+    fn flush_deliveries(&mut self) {
+        self.delivery.flush();
+    }
+
+    fn handle_started(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>) {
+        for (channel, pairs) in self.channels.clone() {
+            let subscription = match channel {
+                Channel::LiveTrades => serde_json::json!({"name": "trade"}),
+                Channel::LiveCandles => serde_json::json!({"name": "ohlc", "interval": self.candle_interval}),
+                _ => continue,
+            };
+            let names: Vec<String> = pairs.iter().filter_map(|p| ws_pair_name(*p)).collect();
+            let request = serde_json::json!({"event": "subscribe", "pair": names, "subscription": subscription});
+            if let Err(e) = w.write(Message::Text(request.to_string())) {
+                warn!("Kraken : cannot subscribe to {:?} : {:?}", channel, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod kraken_streaming_tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+
+    #[test]
+    fn candles_are_closed_by_the_next_one() {
+        let v: Value = serde_json::from_str(r#"[42, ["1542057314.748456", "1542057360.435743", "3586.70000", "3586.70000", "3586.60000", "3586.60000", "3586.68894", "0.03373000", 2], "ohlc-1", "XBT/USD"]"#).unwrap();
+        let candle = parse_candle(&v).unwrap();
+        assert_eq!((candle.event_ms, candle.interval_secs), (1542057300000, 60));
+        assert_eq!(candle.volume, "0.03373000".parse::<BigDecimal>().unwrap());
+
+        let mut api = KrakenStreamingApi { delivery: Delivery::new(vec![], DeliveryPolicy::default()), channels: HashMap::new(), candle_interval: 1, candles: HashMap::new() };
+        assert_eq!(api.candle_update(Pair::BTC_USD, candle.clone()).len(), 1);
+        assert_eq!(api.candle_update(Pair::BTC_USD, candle.clone()).len(), 1);
+        let next = LiveCandle { event_ms: candle.event_ms + 60_000, ..candle };
+        let updates = api.candle_update(Pair::BTC_USD, next);
+        assert_eq!(updates.iter().map(|c| c.closed).collect::<Vec<_>>(), vec![true, false]);
+    }
+
+    #[test]
+    fn trades_are_parsed() {
+        let v: Value = serde_json::from_str(r#"[0, [["5541.20000", "0.15850568", "1534614057.321597", "s", "l", ""]], "trade", "XBT/USD"]"#).unwrap();
+        let trades = parse_trades(Pair::BTC_USD, &v).unwrap();
+        assert_eq!(trades[0].event_ms, 1534614057321);
+        assert_eq!(trades[0].tt, TradeType::Sell);
+        assert_eq!(trades[0].price, "5541.2".parse::<BigDecimal>().unwrap());
+    }
+}
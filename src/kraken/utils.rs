@@ -1,13 +1,19 @@
+use std::time::Duration;
+
 use bidir_map::BidirMap;
 use serde_json;
 use serde_json::Value;
 use serde_json::value::Map;
 
 use crate::error::*;
-use crate::types::Currency;
+use crate::exchange::Exchange;
+use crate::helpers;
+use crate::types::{Currency, FillEvent, Liquidity, OrderRequest, OrderType, Orderbook, Ticker, TradeType, Volume};
 use crate::types::Pair;
 use crate::types::Pair::*;
 
+pub const API_URL: &str = "https://api.kraken.com";
+
 lazy_static! {
     static ref PAIRS_STRING: BidirMap<Pair, &'static str> = {
         let mut m = BidirMap::new();
@@ -58,7 +64,7 @@ lazy_static! {
         m.insert(BTC_JPY_d, "XXBTZJPY.d");
         m.insert(BTC_USD, "XXBTZUSD");
         m.insert(BTC_USD_d, "XXBTZUSD.d");
-        m.insert(XDG_BTC, "XXDGXXBT");
+        m.insert(DOGE_BTC, "XXDGXXBT");
         m.insert(XLM_BTC, "XXLMXXBT");
         m.insert(XMR_BTC, "XXMRXXBT");
         m.insert(XMR_EUR, "XXMRZEUR");
@@ -85,6 +91,11 @@ pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
     PAIRS_STRING.get_by_second(&pair)
 }
 
+/// Every pair with a Kraken name
+pub fn supported_pairs() -> Vec<Pair> {
+    PAIRS_STRING.iter().map(|(pair, _)| *pair).collect()
+}
+
 /// If error array is null, return the result (encoded in a json object)
 /// else return the error string found in array
 pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>> {
@@ -92,38 +103,100 @@ pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>>
         Some(array) => {
             array
                 .as_array()
-                .ok_or_else(|| ErrorKind::InvalidFieldFormat("error".to_string()))?
+                .ok_or_else(|| Error::InvalidFieldFormat("error".to_string()))?
         }
-        None => return Err(ErrorKind::BadParse.into()),
+        None => return Err(Error::BadParse),
     };
     if error_array.is_empty() {
         return Ok(response
                       .get("result")
-                      .ok_or_else(|| ErrorKind::MissingField("result".to_string()))?
+                      .ok_or_else(|| Error::MissingField("result".to_string()))?
                       .as_object()
-                      .ok_or_else(|| ErrorKind::InvalidFieldFormat("result".to_string()))?
+                      .ok_or_else(|| Error::InvalidFieldFormat("result".to_string()))?
                       .clone());
     }
     let error_msg = error_array[0]
         .as_str()
-        .ok_or_else(|| ErrorKind::InvalidFieldFormat(error_array[0].to_string()))?
+        .ok_or_else(|| Error::InvalidFieldFormat(error_array[0].to_string()))?
         .to_string();
 
     //TODO: Parse correctly the reason for "EService:Unavailable".
     match error_msg.as_ref() {
         "EService:Unavailable" => {
-            Err(ErrorKind::ServiceUnavailable("Unknown...".to_string()).into())
+            Err(Error::ServiceUnavailable("Unknown...".to_string()))
         }
-        "EAPI:Invalid key" => Err(ErrorKind::BadCredentials.into()),
-        "EAPI:Invalid nonce" => Err(ErrorKind::InvalidNonce.into()),
-        "EOrder:Rate limit exceeded" => Err(ErrorKind::RateLimitExceeded.into()),
-        "EQuery:Unknown asset pair" => Err(ErrorKind::PairUnsupported.into()),
-        "EGeneral:Invalid arguments" => Err(ErrorKind::InvalidArguments.into()),
-        "EGeneral:Permission denied" => Err(ErrorKind::PermissionDenied.into()),
-        "EOrder:Insufficient funds" => Err(ErrorKind::InsufficientFunds.into()),
-        "EOrder:Order minimum not met" => Err(ErrorKind::InsufficientOrderSize.into()),
-        other => Err(ErrorKind::ExchangeSpecificError(other.to_string()).into()),
+        "EService:Busy" => Err(Error::ServiceUnavailable("Busy".to_string())),
+        "EAPI:Invalid key" | "EAPI:Invalid signature" => Err(Error::BadCredentials),
+        "EAPI:Invalid nonce" => Err(Error::InvalidNonce),
+        "EAPI:Rate limit exceeded" | "EOrder:Rate limit exceeded" => Err(Error::RateLimited(None)),
+        "EOrder:Unknown order" => Err(Error::OrderNotFound),
+        "EQuery:Unknown asset pair" => Err(Error::PairUnsupported),
+        "EGeneral:Invalid arguments" => Err(Error::InvalidArguments),
+        "EGeneral:Permission denied" => Err(Error::PermissionDenied),
+        "EOrder:Insufficient funds" => Err(Error::InsufficientFunds),
+        "EOrder:Order minimum not met" => Err(Error::InsufficientOrderSize),
+        other => Err(Error::ExchangeSpecificError(other.to_string())),
+    }
+}
+
+/// Parse the ticker of `pair` from the result of the Ticker endpoint
+pub fn parse_ticker(pair: Pair, ticker: &Value, keep_raw: bool) -> Result<Ticker> {
+    let price = helpers::from_json_bigdecimal(&ticker["c"][0], "c")?;
+    let ask = helpers::from_json_bigdecimal(&ticker["a"][0], "a")?;
+    let bid = helpers::from_json_bigdecimal(&ticker["b"][0], "b")?;
+    let vol = helpers::from_json_bigdecimal(&ticker["v"][0], "v")?;
+    let open = helpers::from_json_bigdecimal_opt(&ticker["o"]);
+    let percent_change = open.as_ref().and_then(|o| helpers::percent_change(o, &price));
+
+    Ok(Ticker {
+        timestamp: helpers::get_unix_timestamp_ms(),
+        pair,
+        last_trade_price: price,
+        lowest_ask: ask,
+        highest_bid: bid,
+        volume: Some(vol),
+        bid_size: helpers::from_json_bigdecimal_opt(&ticker["b"][2]),
+        ask_size: helpers::from_json_bigdecimal_opt(&ticker["a"][2]),
+        open_24h: open,
+        high_24h: helpers::from_json_bigdecimal_opt(&ticker["h"][1]),
+        low_24h: helpers::from_json_bigdecimal_opt(&ticker["l"][1]),
+        percent_change,
+        raw: helpers::raw_payload(keep_raw, ticker),
+    })
+}
+
+/// Parse the order book of `pair` from the result of the Depth endpoint
+pub fn parse_orderbook(pair: Pair, book: &Value) -> Result<Orderbook> {
+    let ask_array = book["asks"]
+        .as_array()
+        .ok_or_else(|| Error::InvalidFieldFormat(format!("{}.asks", book)))?;
+    let bid_array = book["bids"]
+        .as_array()
+        .ok_or_else(|| Error::InvalidFieldFormat(format!("{}.bids", book)))?;
+
+    let mut ask_offers = Vec::with_capacity(ask_array.len());
+    let mut bid_offers = Vec::with_capacity(bid_array.len());
+
+    for ask in ask_array {
+        let price = helpers::from_json_bigdecimal(&ask[0], "ask price")?;
+        let volume = helpers::from_json_bigdecimal(&ask[1], "ask volume")?;
+
+        ask_offers.push((price, volume));
+    }
+
+    for bid in bid_array {
+        let price = helpers::from_json_bigdecimal(&bid[0], "bid price")?;
+        let volume = helpers::from_json_bigdecimal(&bid[1], "bid volume")?;
+
+        bid_offers.push((price, volume));
     }
+
+    Ok(Orderbook {
+        timestamp: helpers::get_unix_timestamp_ms(),
+        pair,
+        asks: ask_offers,
+        bids: bid_offers,
+    })
 }
 
 /// Return the currency enum associated with the
@@ -139,29 +212,16 @@ pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>>
 /// assert_eq!(Some(Currency::USD), currency);
 /// ```
 pub fn get_currency_enum(currency: &str) -> Option<Currency> {
-    match currency {
-        "ZEUR" => Some(Currency::EUR),
-        "ZCAD" => Some(Currency::CAD),
-        "ZGBP" => Some(Currency::GBP),
-        "ZJPY" => Some(Currency::JPY),
-        "ZUSD" => Some(Currency::USD),
-        "XDASH" => Some(Currency::DASH),
-        "XETC" => Some(Currency::ETC),
-        "XETH" => Some(Currency::ETH),
-        "XGNO" => Some(Currency::GNO),
-        "XICN" => Some(Currency::ICN),
-        "XLTC" => Some(Currency::LTC),
-        "XMLN" => Some(Currency::MLN),
-        "XREP" => Some(Currency::REP),
-        "XUSDT" => Some(Currency::USDT),
-        "XXBT" => Some(Currency::BTC),
-        "XXDG" => Some(Currency::XDG),
-        "XXLM" => Some(Currency::XLM),
-        "XXMR" => Some(Currency::XMR),
-        "XXRP" => Some(Currency::XRP),
-        "XZEC" => Some(Currency::ZEC),
+    Currency::from_alias(Exchange::Kraken, currency)
+}
+
+/// Parse a Kraken asset id or alternate name, e.g. `XXBT`, `XBT` or `ZEUR`, into a `Currency`.
+/// Kraken names bitcoin `XBT` and prefixes the ids of older assets with `X` (crypto) or `Z` (fiat).
+pub fn parse_asset(asset: &str) -> Option<Currency> {
+    get_currency_enum(asset).or_else(|| match asset.len() {
+        4 if asset.starts_with('X') || asset.starts_with('Z') => get_currency_enum(&asset[1..]),
         _ => None,
-    }
+    })
 }
 
 /// Return the currency String associated with the
@@ -177,27 +237,142 @@ pub fn get_currency_enum(currency: &str) -> Option<Currency> {
 /// assert_eq!(currency, Some("XXBT".to_string()));
 /// ```
 pub fn get_currency_string(currency: Currency) -> Option<String> {
-    match currency {
-        Currency::EUR => Some("ZEUR".to_string()),
-        Currency::CAD => Some("ZCAD".to_string()),
-        Currency::GBP => Some("ZGBP".to_string()),
-        Currency::JPY => Some("ZJPY".to_string()),
-        Currency::USD => Some("ZUSD".to_string()),
-        Currency::DASH => Some("XDASH".to_string()),
-        Currency::ETC => Some("XETC".to_string()),
-        Currency::ETH => Some("XETH".to_string()),
-        Currency::GNO => Some("XGNO".to_string()),
-        Currency::ICN => Some("XICN".to_string()),
-        Currency::LTC => Some("XLTC".to_string()),
-        Currency::MLN => Some("XMLN".to_string()),
-        Currency::REP => Some("XREP".to_string()),
-        Currency::USDT => Some("XUSDT".to_string()),
-        Currency::BTC => Some("XXBT".to_string()),
-        Currency::XDG => Some("XXDG".to_string()),
-        Currency::XLM => Some("XXLM".to_string()),
-        Currency::XMR => Some("XXMR".to_string()),
-        Currency::XRP => Some("XXRP".to_string()),
-        Currency::ZEC => Some("XZEC".to_string()),
-        _ => None,
+    let listed = PAIRS_STRING.iter()
+        .filter_map(|(pair, _)| crate::utils::pair_currencies(pair))
+        .any(|(base, quote)| base == currency || quote == currency);
+    if listed { Some(currency.alias_for(Exchange::Kraken)) } else { None }
+}
+
+/// Seconds of a `CancelAllOrdersAfter` timeout, rounded up so that a timeout under a second arms
+/// the timer instead of disarming it
+pub fn cancel_after_secs(timeout: Duration) -> u64 {
+    timeout.as_secs() + if timeout.subsec_nanos() > 0 { 1 } else { 0 }
+}
+
+/// Name of the pair `name` in the description of the orders, XXBTZUSD is described as XBTUSD
+pub fn pair_altname(name: &str) -> String {
+    let prefixed = |c: u8| c == b'X' || c == b'Z';
+    let bytes = name.as_bytes();
+    if bytes.len() == 8 && prefixed(bytes[0]) && prefixed(bytes[4]) {
+        format!("{}{}", &name[1..4], &name[5..])
+    } else {
+        name.to_string()
+    }
+}
+
+/// The txid and the request of the first limit or market order of `orders` placed on `pair`.
+/// Orders are filtered by userref, which is not unique : orders of other pairs or of other
+/// clients may share it, hence the check of the pair here and of the volume by the caller.
+pub fn find_order(orders: &Value, pair: Pair) -> Option<(String, OrderRequest)> {
+    let altname = pair_altname(get_pair_string(&pair)?);
+    orders.as_object()?.iter().find_map(|(txid, order)| {
+        let descr = &order["descr"];
+        if descr["pair"].as_str() != Some(altname.as_str()) {
+            return None;
+        }
+        let order_type = match (descr["type"].as_str()?, descr["ordertype"].as_str()?) {
+            ("buy", "limit") => OrderType::BuyLimit,
+            ("sell", "limit") => OrderType::SellLimit,
+            ("buy", "market") => OrderType::BuyMarket,
+            ("sell", "market") => OrderType::SellMarket,
+            _ => return None,
+        };
+        let quantity = helpers::from_json_bigdecimal_opt(&order["vol"])?;
+        let price = match order_type {
+            OrderType::BuyLimit | OrderType::SellLimit => Some(helpers::from_json_bigdecimal_opt(&descr["price"])?),
+            _ => None,
+        };
+        Some((txid.clone(), OrderRequest::new(order_type, pair, &quantity, &price)))
+    })
+}
+
+/// Volume executed by the orders `txids` of the `QueryOrders` result `orders`, `None` while one
+/// of them is not closed or canceled, its executed volume may still grow
+pub fn executed_volume(orders: &Value, txids: &[String]) -> Result<Option<Volume>> {
+    let mut executed = Volume::from(0);
+    for txid in txids {
+        let order = &orders[txid.as_str()];
+        match order["status"].as_str() {
+            Some("closed") | Some("canceled") => (),
+            Some(_) => return Ok(None),
+            None => return Err(Error::MissingField("status".to_string())),
+        }
+        executed = executed + helpers::from_json_bigdecimal(&order["vol_exec"], "vol_exec")?;
+    }
+    Ok(Some(executed))
+}
+
+/// A fill from the trade info `trade` named `trade_id`, as returned by the trades history and by
+/// the `ownTrades` websocket channel. Kraken charges the fee in the quote currency.
+pub fn parse_fill(trade_id: &str, pair: Pair, trade: &Value) -> Result<FillEvent> {
+    let time = trade["time"].as_f64()
+        .or_else(|| trade["time"].as_str().and_then(|t| t.parse().ok()))
+        .ok_or_else(|| Error::MissingField("time".to_string()))?;
+    let order_id = trade["ordertxid"].as_str().ok_or_else(|| Error::MissingField("ordertxid".to_string()))?;
+    Ok(FillEvent {
+        event_ms: (time * 1000.0) as i64,
+        order_id: order_id.to_string(),
+        trade_id: Some(trade_id.to_string()),
+        pair,
+        side: TradeType::parse(trade["type"].as_str().unwrap_or_default()),
+        price: helpers::from_json_bigdecimal(&trade["price"], "price")?,
+        amount: helpers::from_json_bigdecimal(&trade["vol"], "vol")?,
+        fee: helpers::from_json_bigdecimal(&trade["fee"], "fee")?,
+        fee_currency: crate::utils::pair_currencies(&pair).map(|(_, quote)| quote),
+        liquidity: match trade["maker"].as_bool() {
+            Some(true) => Liquidity::Maker,
+            Some(false) => Liquidity::Taker,
+            None => Liquidity::Unknown,
+        },
+    })
+}
+
+#[cfg(test)]
+mod kraken_utils_tests {
+    use super::*;
+
+    #[test]
+    fn executed_volumes_are_known_once_orders_are_done() {
+        let txids = vec!["OQCLML-BW3P3-BUCMWZ".to_string()];
+        let orders: Value = serde_json::from_str(r#"{"OQCLML-BW3P3-BUCMWZ": {"status": "canceled", "vol": "1.25", "vol_exec": "0.5"}}"#).unwrap();
+        assert_eq!(executed_volume(&orders, &txids).unwrap(), Some("0.5".parse().unwrap()));
+        let orders: Value = serde_json::from_str(r#"{"OQCLML-BW3P3-BUCMWZ": {"status": "open", "vol": "1.25", "vol_exec": "0.5"}}"#).unwrap();
+        assert_eq!(executed_volume(&orders, &txids).unwrap(), None);
+        assert!(executed_volume(&Value::Null, &txids).is_err());
+    }
+
+    #[test]
+    fn cancel_after_timeouts_are_rounded_up() {
+        assert_eq!(cancel_after_secs(Duration::from_millis(0)), 0);
+        assert_eq!(cancel_after_secs(Duration::from_millis(300)), 1);
+        assert_eq!(cancel_after_secs(Duration::from_millis(60_000)), 60);
+        assert_eq!(cancel_after_secs(Duration::from_millis(60_001)), 61);
+    }
+
+    #[test]
+    fn pair_and_balance_currencies_match() {
+        let pair = *get_pair_enum("XXDGXXBT").unwrap();
+        assert_eq!(crate::utils::pair_currencies(&pair), Some((Currency::DOGE, Currency::BTC)));
+        assert_eq!(parse_asset("XXDG"), Some(Currency::DOGE));
+        assert_eq!(get_currency_string(Currency::DOGE), Some("XXDG".to_string()));
+        assert_eq!(get_currency_string(Currency::_1ST), None);
+    }
+
+    #[test]
+    fn orders_of_other_pairs_sharing_the_userref_are_skipped() {
+        assert_eq!(pair_altname("XXBTZUSD"), "XBTUSD");
+        assert_eq!(pair_altname("DOTUSD"), "DOTUSD");
+        let orders = serde_json::json!({
+            "OQCLML-BW3P3-BUCMWZ": {"userref": 42, "vol": "2.00000000",
+                "descr": {"pair": "ETHXBT", "type": "buy", "ordertype": "limit", "price": "0.05"}},
+            "OB5VMB-B4U2U-DK2WRW": {"userref": 42, "vol": "1.25000000",
+                "descr": {"pair": "XBTUSD", "type": "sell", "ordertype": "limit", "price": "30010.0"}}
+        });
+        let (txid, request) = find_order(&orders, Pair::BTC_USD).unwrap();
+        assert_eq!(txid, "OB5VMB-B4U2U-DK2WRW");
+        assert_eq!(request.order_type, OrderType::SellLimit);
+        assert_eq!(request.quantity, "1.25".parse().unwrap());
+        assert_eq!(request.price, Some("30010".parse().unwrap()));
+        assert!(find_order(&orders, Pair::LTC_USD).is_none());
     }
 }
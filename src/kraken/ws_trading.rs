@@ -0,0 +1,245 @@
+//! Place and cancel Kraken orders over the authenticated websocket, which answers faster than the
+//! REST API. Requests are tagged with a `reqid` and matched with the `addOrderStatus` and
+//! `cancelOrderStatus` events sent back by the exchange. The fills of the account are streamed
+//! from the `ownTrades` channel to the recipients of the gateway.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use actix::{io::SinkWrite, Actor, Addr, Recipient};
+use actix_codec::Framed;
+use async_trait::async_trait;
+use awc::{ws::{Codec, Message}, BoxedSocket};
+use bytes::{Buf, Bytes};
+use futures::channel::oneshot;
+use futures::stream::SplitSink;
+use serde_json::Value;
+
+use crate::coinnect::Credentials;
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::exchange_bot::{AddOrder, CancelOrder, DefaultWsActor, OrderGateway, WsHandler};
+use crate::helpers;
+use crate::helpers::delivery::{Delivery, DeliveryPolicy};
+use crate::helpers::secret::SecretString;
+use crate::kraken::api::KrakenApi;
+use crate::kraken::utils;
+use crate::types::*;
+
+static WEBSOCKET_AUTH_URL: &'static str = "wss://ws-auth.kraken.com";
+
+enum Pending {
+    Add(OrderRequest, oneshot::Sender<Result<OrderInfo>>),
+    Cancel(oneshot::Sender<Result<()>>),
+}
+
+pub struct KrakenWsTrading {
+    /// Renewed in the background by `KrakenApi::keep_websocket_token`
+    token: Arc<RwLock<SecretString>>,
+    keep_raw: bool,
+    next_reqid: u64,
+    pending: HashMap<u64, Pending>,
+    fills: Delivery<LiveEventEnveloppe>,
+}
+
+impl KrakenWsTrading {
+    /// Start an `OrderGateway` placing orders over the authenticated websocket of Kraken, and
+    /// falling back to the REST API. Fills of the account are sent to `recipients`.
+    pub async fn new_gateway<C: Credentials>(creds: C, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<Addr<OrderGateway>> {
+        let connection = creds.connection();
+        let api = Arc::new(KrakenApi::new(creds)?);
+        let token = api.clone().keep_websocket_token().await?;
+        let handler = KrakenWsTrading { token, keep_raw: api.keep_raw, next_reqid: 1, pending: HashMap::new(), fills: Delivery::new(recipients, DeliveryPolicy::default()) };
+        let addr = DefaultWsActor::new("KrakenWsTrading", Arc::new(connection.ws_endpoints(Exchange::Kraken, WEBSOCKET_AUTH_URL)), connection.proxy()?, Some(Duration::from_secs(5)), vec![], Box::new(handler)).await?;
+        Ok(OrderGateway::new(Some(addr), api).start())
+    }
+
+    fn token(&self) -> String {
+        self.token.read().unwrap().expose().to_string()
+    }
+
+    fn send(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, mut request: Value, pending: Pending) {
+        let reqid = self.next_reqid;
+        self.next_reqid += 1;
+        request["token"] = Value::String(self.token());
+        request["reqid"] = Value::from(reqid);
+        self.pending.insert(reqid, pending);
+        if let Err(e) = w.write(Message::Text(request.to_string())) {
+            self.fail(reqid, Error::ServiceUnavailable(format!("{:?}", e)));
+        }
+    }
+
+    fn fail(&mut self, reqid: u64, e: Error) {
+        match self.pending.remove(&reqid) {
+            Some(Pending::Add(_, reply)) => { let _ = reply.send(Err(e)); }
+            Some(Pending::Cancel(reply)) => { let _ = reply.send(Err(e)); }
+            None => (),
+        }
+    }
+}
+
+/// Name of a pair on the websocket API, e.g. "XBT/USD"
+pub fn ws_pair_name(pair: Pair) -> Option<String> {
+    let (base, quote) = crate::utils::pair_currencies(&pair)?;
+    let name = |c: Currency| match c {
+        Currency::BTC => "XBT".to_string(),
+        Currency::DOGE => "XDG".to_string(),
+        c => format!("{:?}", c),
+    };
+    Some(format!("{}/{}", name(base), name(quote)))
+}
+
+/// The pair named `name` on the websocket API, e.g. "XBT/USD"
+pub fn ws_pair(name: &str) -> Option<Pair> {
+    let mut currencies = name.splitn(2, '/');
+    let base = utils::parse_asset(currencies.next()?)?;
+    let quote = utils::parse_asset(currencies.next()?)?;
+    crate::utils::currencies_pair(base, quote)
+}
+
+/// Fills of an `ownTrades` message : `[[{"<trade id>": {<trade info>}}, ...], "ownTrades", {"sequence": 1}]`
+fn own_trades(v: &Value) -> Vec<FillEvent> {
+    let trades = v[0].as_array().map(|t| t.as_slice()).unwrap_or_default();
+    trades.iter()
+        .filter_map(|t| t.as_object())
+        .flat_map(|t| t.iter())
+        .filter_map(|(trade_id, trade)| {
+            let pair = trade["pair"].as_str().and_then(ws_pair)?;
+            utils::parse_fill(trade_id, pair, trade)
+                .map_err(|e| warn!("KrakenWsTrading : cannot parse trade {} : {}", trade_id, e))
+                .ok()
+        })
+        .collect()
+}
+
+/// The addOrder request of an order
+fn add_order_request(order: &AddOrder) -> Result<Value> {
+    let pair = ws_pair_name(order.pair).ok_or(Error::PairUnsupported)?;
+    let (ordertype, side) = match order.order_type {
+        OrderType::BuyLimit => ("limit", "buy"),
+        OrderType::SellLimit => ("limit", "sell"),
+        OrderType::BuyMarket => ("market", "buy"),
+        OrderType::SellMarket => ("market", "sell"),
+        _ => return Err(Error::OrderTypeUnsupported),
+    };
+    let mut request = serde_json::json!({
+        "event": "addOrder",
+        "ordertype": ordertype,
+        "type": side,
+        "pair": pair,
+        "volume": order.quantity.to_string(),
+    });
+    if ordertype == "limit" {
+        request["price"] = Value::String(order.price.as_ref().ok_or(Error::MissingPrice)?.to_string());
+    }
+    if let Some(client_id) = &order.client_id {
+        client_id.parse::<i32>().map_err(|_| Error::InvalidFieldValue("userref".to_string()))?;
+        request["userref"] = Value::String(client_id.clone());
+    }
+    Ok(request)
+}
+
+#[async_trait]
+impl WsHandler for KrakenWsTrading {
+    fn handle_in(&mut self, _w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, msg: Bytes) {
+        let v: Value = match serde_json::from_slice(msg.bytes()) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("KrakenWsTrading : cannot parse {:?} : {}", msg, e);
+                return;
+            }
+        };
+        if v[1].as_str() == Some("ownTrades") {
+            for fill in own_trades(&v) {
+                self.fills.send(LiveEventEnveloppe(Exchange::Kraken, LiveEvent::Fill(fill)));
+            }
+            return;
+        }
+        let reqid = match (v["event"].as_str(), v["reqid"].as_u64()) {
+            (Some("addOrderStatus"), Some(reqid)) | (Some("cancelOrderStatus"), Some(reqid)) => reqid,
+            _ => return,
+        };
+        if v["status"].as_str() != Some("ok") {
+            let message = v["errorMessage"].as_str().unwrap_or("unknown error").to_string();
+            self.fail(reqid, Error::ExchangeSpecificError(message));
+            return;
+        }
+        match self.pending.remove(&reqid) {
+            Some(Pending::Add(request, reply)) => {
+                let identifier = v["txid"].as_str().map(|t| vec![t.to_string()]).unwrap_or_default();
+                let _ = reply.send(Ok(OrderInfo { timestamp: helpers::get_unix_timestamp_ms(), identifier, raw: helpers::raw_payload(self.keep_raw, &v), request: Some(request) }));
+            }
+            Some(Pending::Cancel(reply)) => { let _ = reply.send(Ok(())); }
+            None => (),
+        }
+    }
+
+    fn handle_started(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>) {
+        // Requests sent before a reconnection will never be answered
+        let pending: Vec<u64> = self.pending.keys().cloned().collect();
+        for reqid in pending {
+            self.fail(reqid, Error::ServiceUnavailable("websocket reconnected".to_string()));
+        }
+        if !self.fills.is_empty() {
+            // Without the snapshot of the last trades, fills are not sent twice after a reconnection
+            let request = serde_json::json!({
+                "event": "subscribe",
+                "subscription": { "name": "ownTrades", "token": self.token(), "snapshot": false },
+            });
+            if let Err(e) = w.write(Message::Text(request.to_string())) {
+                warn!("KrakenWsTrading : cannot subscribe to ownTrades : {:?}", e);
+            }
+        }
+    }
+
+    fn add_order(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, order: AddOrder, reply: oneshot::Sender<Result<OrderInfo>>) {
+        match add_order_request(&order) {
+            Ok(request) => {
+                let mut placed = OrderRequest::new(order.order_type, order.pair, &order.quantity, &order.price);
+                placed.client_id = order.client_id.clone();
+                self.send(w, request, Pending::Add(placed, reply))
+            }
+            Err(e) => { let _ = reply.send(Err(e)); }
+        }
+    }
+
+    fn cancel_order(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, order: CancelOrder, reply: oneshot::Sender<Result<()>>) {
+        let request = serde_json::json!({ "event": "cancelOrder", "txid": [order.0] });
+        self.send(w, request, Pending::Cancel(reply));
+    }
+}
+
+#[cfg(test)]
+mod ws_trading_tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+
+    #[test]
+    fn build_add_order_requests() {
+        let order = AddOrder { order_type: OrderType::BuyLimit, pair: Pair::BTC_USD, quantity: BigDecimal::from(1), price: Some(BigDecimal::from(9000)), client_id: Some("42".to_string()) };
+        let request = add_order_request(&order).unwrap();
+        assert_eq!(request["pair"], "XBT/USD");
+        assert_eq!(request["ordertype"], "limit");
+        assert_eq!(request["price"], "9000");
+        assert_eq!(request["userref"], "42");
+        assert!(add_order_request(&AddOrder { client_id: Some("abc".to_string()), ..order.clone() }).is_err());
+        let order = AddOrder { price: None, ..order };
+        assert!(add_order_request(&order).is_err());
+    }
+
+    #[test]
+    fn parse_own_trades() {
+        let v: Value = serde_json::from_str(r#"[[{"TDLH43-DVQXD-2KHVYY": {"cost": "1000000.00000", "fee": "1600.00000",
+            "margin": "0.00000", "ordertxid": "TDLH43-DVQXD-2KHVYY", "ordertype": "limit", "pair": "XBT/EUR",
+            "postxid": "OGTT3Y-C6I3P-XRI6HX", "price": "100000.00000", "time": "1560516023.070651", "type": "sell",
+            "vol": "1000000000.00000000"}}], "ownTrades", {"sequence": 2}]"#).unwrap();
+        let fills = own_trades(&v);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].pair, Pair::BTC_EUR);
+        assert_eq!(fills[0].side, TradeType::Sell);
+        assert_eq!(fills[0].event_ms, 1560516023070);
+        assert_eq!(fills[0].fee_currency, Some(Currency::EUR));
+        assert_eq!(fills[0].liquidity, Liquidity::Unknown);
+    }
+}
@@ -0,0 +1,295 @@
+//! Use this module to interact with the raw-original API provided by Kraken Futures.
+//! Sizes are in contracts, one contract of an inverse perpetual is worth one unit of the quote
+//! currency, e.g. 1 USD for `PI_XBTUSD`.
+
+use hyper::{Body, Method, Request, Uri};
+
+use serde_json::Value;
+use serde_json::value::Map;
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::time::Instant;
+
+use crate::coinnect::Credentials;
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::helpers;
+use crate::helpers::failover::Endpoints;
+use crate::helpers::json;
+use crate::helpers::proxy::HttpClient;
+use crate::helpers::secret::SecretString;
+use crate::helpers::signing;
+use crate::kraken_futures::utils;
+use crate::metrics;
+use crate::types::{InstrumentKind, OrderInfo, OrderRequest, OrderType, Orderbook, Pair, Price, Side, Volume};
+use futures::TryFutureExt;
+use bytes::buf::BufExt as _;
+
+const KEY_HEADER: &str = "APIKey";
+const NONCE_HEADER: &str = "Nonce";
+const SIGN_HEADER: &str = "Authent";
+/// Prefix of the REST root left out of the signed paths
+const PATH_PREFIX: &str = "/derivatives";
+
+#[derive(Debug)]
+pub struct KrakenFuturesApi {
+    api_key: SecretString,
+    api_secret: SecretString,
+    http_client: HttpClient,
+    keep_raw: bool,
+    debug_signing: bool,
+    endpoints: Endpoints,
+    last_nonce: AtomicU64, // private requests are rejected unless their nonce increases
+}
+
+impl KrakenFuturesApi {
+    /// Create a new KrakenFuturesApi from the credentials of a Kraken account
+    pub fn new<C: Credentials>(creds: C) -> Result<KrakenFuturesApi> {
+        if creds.exchange() != Exchange::Kraken {
+            return Err(Error::InvalidConfigType(Exchange::Kraken, creds.exchange()));
+        }
+        let mut connection = creds.connection();
+        // The `rest_url` of the account overrides the spot api, the futures one has its own
+        connection.rest_url = creds.get("futures_rest_url");
+        let endpoints = connection.rest_endpoints(Exchange::Kraken, utils::base_url(creds.environment()?));
+
+        Ok(KrakenFuturesApi {
+            api_key: creds.get("futures_api_key").unwrap_or_default().into(),
+            api_secret: creds.get("futures_api_secret").unwrap_or_default().into(),
+            http_client: connection.http_client()?,
+            keep_raw: connection.keep_raw,
+            debug_signing: connection.debug_signing,
+            endpoints,
+            last_nonce: AtomicU64::new(0),
+        })
+    }
+
+    /// The REST endpoints, to subscribe to failover events or recover the primary endpoint
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
+    /// Log the strings signed for the private requests and the status of their responses
+    pub fn set_debug_signing(&mut self, debug_signing: bool) {
+        self.debug_signing = debug_signing
+    }
+
+    async fn send(&self, endpoint: &str, req: Request<Body>, signed: bool) -> Result<Map<String, Value>> {
+        let start = Instant::now();
+        let debug_signing = signed && self.debug_signing;
+        let buf = self.http_client.request(req).and_then(|resp| {
+            if debug_signing {
+                signing::log_response(Exchange::Kraken, endpoint, resp.status());
+            }
+            hyper::body::aggregate(resp.into_body()).map_err(Error::from)
+        }).await;
+        metrics::rest_request(Exchange::Kraken, endpoint, start);
+        let buf = self.endpoints.track(buf)?;
+        json::deserialize_json_r(buf.reader())
+    }
+
+    async fn public_query(&self, endpoint: &str, params: &mut HashMap<&str, &str>) -> Result<Map<String, Value>> {
+        helpers::strip_empties(params);
+        let url = format!("{}/api/v3/{}?{}", self.endpoints.current(), endpoint, helpers::url_encode_hashmap(params));
+        let uri: Uri = url.parse().map_err(|_e| Error::BadParse)?;
+        let req = Request::get(uri).body(Body::empty()).map_err(|e| Error::ServiceUnavailable(e.to_string()))?;
+        self.send(endpoint, req, false).await
+    }
+
+    /// Private GET requests pass their parameters in the query string, POST requests in the body,
+    /// both are signed
+    async fn private_query(&self, method: Method, endpoint: &str, params: &mut HashMap<&str, &str>) -> Result<Map<String, Value>> {
+        helpers::strip_empties(params);
+        let post_data = helpers::url_encode_hashmap(params);
+        let nonce = helpers::next_nonce(&self.last_nonce).to_string();
+        let path = format!("/api/v3/{}", endpoint);
+        let signature = utils::sign(self.api_secret.expose(), &post_data, &nonce, &path)?;
+        if self.debug_signing {
+            let canonical = format!("{}{}{}", post_data, nonce, path);
+            signing::log_signed(Exchange::Kraken, &path, &nonce, &canonical, &[self.api_key.expose()]);
+        }
+
+        let mut url = format!("{}{}", self.endpoints.current(), path);
+        let body = if method == Method::GET {
+            if !post_data.is_empty() {
+                url = format!("{}?{}", url, post_data);
+            }
+            Body::empty()
+        } else {
+            Body::from(post_data)
+        };
+        let req = Request::builder()
+            .method(method)
+            .uri(&url)
+            .header(KEY_HEADER, self.api_key.expose())
+            .header(NONCE_HEADER, nonce.as_str())
+            .header(SIGN_HEADER, signature)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .map_err(|e| Error::ServiceUnavailable(e.to_string()))?;
+        self.send(endpoint, req, true).await
+    }
+
+    /// Every contract with its tick size, contract size and margin levels
+    pub async fn get_instruments(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.public_query("instruments", &mut params).await
+    }
+
+    /// Last price, best bid and ask, volume and funding rate of every contract
+    pub async fn get_tickers(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.public_query("tickers", &mut params).await
+    }
+
+    /// ```json
+    /// {"result": "success", "orderBook": {"bids": [[9000.5, 1500], ...], "asks": [[9001, 250], ...]}}
+    /// ```
+    pub async fn get_orderbook(&self, symbol: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("symbol", symbol);
+        self.public_query("orderbook", &mut params).await
+    }
+
+    /// Balances, margin requirements and unrealized pnl of every margin account
+    pub async fn get_accounts(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.private_query(Method::GET, "accounts", &mut params).await
+    }
+
+    /// ```json
+    /// {"result": "success", "openPositions": [{"side": "long", "symbol": "pi_xbtusd",
+    /// "price": 9000.5, "size": 1000, ...}]}
+    /// ```
+    pub async fn get_open_positions(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.private_query(Method::GET, "openpositions", &mut params).await
+    }
+
+    pub async fn get_open_orders(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.private_query(Method::GET, "openorders", &mut params).await
+    }
+
+    /// Fills of the account, the 100 most recent before `last_fill_time` (ISO 8601) if given
+    pub async fn get_fills(&self, last_fill_time: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("lastFillTime", last_fill_time);
+        self.private_query(Method::GET, "fills", &mut params).await
+    }
+
+    /// Input:
+    ///
+    /// ```json
+    /// orderType = lmt, post (post only), mkt, stp (stop), take_profit or ioc
+    /// symbol = contract symbol, e.g. PI_XBTUSD
+    /// side = buy or sell
+    /// size = number of contracts
+    /// limitPrice = limit price of lmt, post, ioc and stop limit orders
+    /// stopPrice = trigger price of stp and take_profit orders (optional)
+    /// cliOrdId = client order id (optional)
+    /// reduceOnly = true to only reduce a position (optional)
+    /// ```
+    /// Result: `sendStatus` with the `order_id` and a `status`, `placed` unless the order was refused
+    pub async fn send_order(&self,
+                            order_type: &str,
+                            symbol: &str,
+                            side: &str,
+                            size: &str,
+                            limit_price: &str,
+                            stop_price: &str,
+                            cli_ord_id: &str,
+                            reduce_only: &str)
+                            -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("orderType", order_type);
+        params.insert("symbol", symbol);
+        params.insert("side", side);
+        params.insert("size", size);
+        params.insert("limitPrice", limit_price);
+        params.insert("stopPrice", stop_price);
+        params.insert("cliOrdId", cli_ord_id);
+        params.insert("reduceOnly", reduce_only);
+        self.private_query(Method::POST, "sendorder", &mut params).await
+    }
+
+    pub async fn cancel_order(&self, order_id: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("order_id", order_id);
+        self.private_query(Method::POST, "cancelorder", &mut params).await
+    }
+
+    /// Cancel the open orders of `symbol`, of every contract if empty
+    pub async fn cancel_all_orders(&self, symbol: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("symbol", symbol);
+        self.private_query(Method::POST, "cancelallorders", &mut params).await
+    }
+
+    /// Order book of the perpetual contract of `pair`, sizes are in contracts
+    pub async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
+        let symbol = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let raw_response = self.get_orderbook(symbol).await?;
+        utils::parse_orderbook(pair, &utils::parse_result(&raw_response)?)
+    }
+
+    /// Place a limit or market order of `quantity` contracts on the perpetual contract of `pair`
+    pub async fn add_order(&self, order_type: OrderType, pair: Pair, quantity: Volume, price: Option<Price>) -> Result<OrderInfo> {
+        self.add_instrument_order(InstrumentKind::Perpetual, order_type, pair, quantity, price).await
+    }
+
+    /// Only perpetual contracts are traded here, spot and dark pool orders go through `KrakenApi`
+    pub async fn add_instrument_order(&self, kind: InstrumentKind, order_type: OrderType, pair: Pair, quantity: Volume, price: Option<Price>) -> Result<OrderInfo> {
+        let request = OrderRequest::new(order_type, pair, &quantity, &price);
+        if kind != InstrumentKind::Perpetual {
+            return Err(Error::PairUnsupported);
+        }
+        let symbol = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let (order_type_str, limit_price) = match order_type {
+            OrderType::BuyLimit | OrderType::SellLimit => ("lmt", price.ok_or(Error::MissingPrice)?.to_string()),
+            OrderType::BuyMarket | OrderType::SellMarket => ("mkt", String::new()),
+            _ => return Err(Error::OrderTypeUnsupported),
+        };
+        let side = match order_type.side() {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        };
+        let raw_response = self.send_order(order_type_str, symbol, side, &quantity.to_string(), &limit_price, "", "", "").await?;
+        let result = utils::parse_result(&raw_response)?;
+        let order_id = utils::parse_send_status(&result)?;
+        Ok(OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: vec![order_id],
+            raw: helpers::raw_payload(self.keep_raw, &result),
+            request: Some(request),
+        })
+    }
+}
+
+#[cfg(test)]
+mod kraken_futures_api_tests {
+    use super::*;
+    use crate::exchange::Environment;
+
+    /// Credentials of a Kraken account with the given settings
+    struct Settings(HashMap<String, String>);
+
+    impl Credentials for Settings {
+        fn get(&self, cred: &str) -> Option<String> { self.0.get(cred).cloned() }
+        fn exchange(&self) -> Exchange { Exchange::Kraken }
+        fn name(&self) -> String { "kraken".to_string() }
+    }
+
+    fn settings(pairs: &[(&str, &str)]) -> Settings {
+        Settings(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    #[test]
+    fn the_spot_rest_url_does_not_apply_to_futures() {
+        let spot_only = settings(&[("rest_url", "http://localhost:8080")]);
+        assert_eq!(KrakenFuturesApi::new(spot_only).unwrap().endpoints().current(), utils::base_url(Environment::Production));
+        let futures = settings(&[("rest_url", "http://localhost:8080"), ("futures_rest_url", "http://localhost:8081")]);
+        assert_eq!(KrakenFuturesApi::new(futures).unwrap().endpoints().current(), "http://localhost:8081");
+    }
+}
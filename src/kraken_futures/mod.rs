@@ -0,0 +1,10 @@
+//! Use this module to trade the perpetual futures of Kraken Futures.
+//! Kraken Futures has its own api keys, read from the `futures_api_key` and `futures_api_secret`
+//! credentials of a Kraken account, and a demo environment selected with
+//! `environment = "sandbox"`. Its REST root is overridden with `futures_rest_url`, the `rest_url`
+//! of the account only applies to the spot api.
+
+pub mod api;
+pub mod utils;
+
+pub use self::api::KrakenFuturesApi;
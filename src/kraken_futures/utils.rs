@@ -0,0 +1,136 @@
+use std::str::FromStr;
+
+use bidir_map::BidirMap;
+use bigdecimal::BigDecimal;
+use data_encoding::BASE64;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use serde_json::value::Map;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::error::*;
+use crate::exchange::Environment;
+use crate::helpers;
+use crate::types::{Orderbook, Pair, Price, Volume};
+use crate::types::Pair::*;
+
+pub const PRODUCTION_URL: &str = "https://futures.kraken.com/derivatives";
+pub const SANDBOX_URL: &str = "https://demo-futures.kraken.com/derivatives";
+
+lazy_static! {
+    /// Inverse perpetual contracts, margined in the base currency
+    static ref PAIRS_STRING: BidirMap<Pair, &'static str> = {
+        let mut m = BidirMap::new();
+        m.insert(BTC_USD, "PI_XBTUSD");
+        m.insert(ETH_USD, "PI_ETHUSD");
+        m.insert(LTC_USD, "PI_LTCUSD");
+        m.insert(BCH_USD, "PI_BCHUSD");
+        m.insert(XRP_USD, "PI_XRPUSD");
+        m
+    };
+}
+
+/// REST root of an environment
+pub fn base_url(environment: Environment) -> &'static str {
+    match environment {
+        Environment::Production => PRODUCTION_URL,
+        Environment::Sandbox => SANDBOX_URL,
+    }
+}
+
+/// Return the symbol of the perpetual contract of the pair
+/// If the Pair has no perpetual, None is returned.
+pub fn get_pair_string(pair: &Pair) -> Option<&&str> {
+    PAIRS_STRING.get_by_first(pair)
+}
+
+/// Return the Pair enum of a perpetual contract symbol
+/// If the symbol is not a supported perpetual, None is returned.
+pub fn get_pair_enum(symbol: &str) -> Option<&Pair> {
+    PAIRS_STRING.get_by_second(&symbol.to_uppercase().as_str())
+}
+
+/// Every pair with a perpetual contract
+pub fn supported_pairs() -> Vec<Pair> {
+    PAIRS_STRING.iter().map(|(pair, _)| *pair).collect()
+}
+
+/// `Authent` header of a private request : the sha512 hmac of the sha256 digest of the post data,
+/// the nonce and the endpoint path without the `/derivatives` prefix
+pub fn sign(api_secret: &str, post_data: &str, nonce: &str, endpoint_path: &str) -> Result<String> {
+    let mut sha256 = Sha256::default();
+    sha256.input(format!("{}{}{}", post_data, nonce, endpoint_path).as_bytes());
+    let hmac_key = BASE64.decode(api_secret.as_bytes())?;
+    let mut mac = Hmac::<Sha512>::new(&hmac_key[..]);
+    mac.input(&sha256.result());
+    Ok(BASE64.encode(mac.result().code()))
+}
+
+/// Map the errors of Kraken Futures to typed errors
+pub fn parse_error(error: &str) -> Error {
+    match error {
+        "authenticationError" | "requiredArgumentMissing: apiKey" => Error::BadCredentials,
+        "apiLimitExceeded" => Error::RateLimited(None),
+        "nonceBelowThreshold" | "nonceDuplicate" => Error::InvalidNonce,
+        "insufficientAvailableFunds" => Error::InsufficientFunds,
+        "invalidSize" => Error::InsufficientOrderSize,
+        "notFound" | "orderForEditNotFound" => Error::OrderNotFound,
+        "invalidArgument" => Error::InvalidArguments,
+        _ => Error::ExchangeSpecificError(error.to_string()),
+    }
+}
+
+/// The response of a successful request, `{"result": "success", ...}`
+pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>> {
+    match response.get("result").and_then(|r| r.as_str()) {
+        Some("success") => Ok(response.clone()),
+        _ => Err(parse_error(response.get("error").and_then(|e| e.as_str()).unwrap_or("unknown error"))),
+    }
+}
+
+/// The id of a placed order, orders refused by the matching engine succeed with another status
+pub fn parse_send_status(result: &Map<String, Value>) -> Result<String> {
+    let status = result.get("sendStatus").ok_or_else(|| Error::MissingField("sendStatus".to_string()))?;
+    match status["status"].as_str() {
+        Some("placed") => Ok(helpers::get_json_string(status, "order_id")?.to_string()),
+        Some(other) => Err(parse_error(other)),
+        None => Err(Error::MissingField("status".to_string())),
+    }
+}
+
+fn parse_levels(levels: &Value) -> Result<Vec<(Price, Volume)>> {
+    let levels = levels.as_array().ok_or_else(|| Error::InvalidFieldFormat(levels.to_string()))?;
+    // Prices and sizes are json numbers, parsed from their text to keep every decimal
+    levels.iter().map(|level| {
+        if !level[0].is_number() || !level[1].is_number() {
+            return Err(Error::InvalidFieldFormat(level.to_string()));
+        }
+        Ok((BigDecimal::from_str(&level[0].to_string())?, BigDecimal::from_str(&level[1].to_string())?))
+    }).collect()
+}
+
+/// The `orderBook` of a response of the orderbook endpoint, sizes are in contracts
+pub fn parse_orderbook(pair: Pair, result: &Map<String, Value>) -> Result<Orderbook> {
+    let book = result.get("orderBook").ok_or_else(|| Error::MissingField("orderBook".to_string()))?;
+    Ok(Orderbook {
+        timestamp: helpers::get_unix_timestamp_ms(),
+        pair,
+        asks: parse_levels(&book["asks"])?,
+        bids: parse_levels(&book["bids"])?,
+    })
+}
+
+#[cfg(test)]
+mod kraken_futures_utils_tests {
+    use super::*;
+
+    #[test]
+    fn refused_orders_are_errors() {
+        let placed = serde_json::json!({"result": "success", "sendStatus": {"order_id": "abc", "status": "placed"}});
+        assert_eq!(parse_send_status(placed.as_object().unwrap()).unwrap(), "abc");
+        let refused = serde_json::json!({"result": "success", "sendStatus": {"status": "insufficientAvailableFunds"}});
+        assert!(parse_send_status(refused.as_object().unwrap()).is_err());
+        let failed = serde_json::json!({"result": "error", "error": "apiLimitExceeded"});
+        assert!(parse_result(failed.as_object().unwrap()).is_err());
+    }
+}
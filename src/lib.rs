@@ -10,17 +10,38 @@
 //!
 //! ### Exchanges support:
 //! - [x] Poloniex
-//! - [x] Kraken
+//! - [x] Kraken (spot, dark pool and, with the `kraken_futures` module, perpetual futures)
 //! - [x] Bitstamp (partial)
 //! - [x] Bittrex
-//! - [x] Gdax
+//! - [x] Coinbase (Advanced Trade)
+//! - [x] Gdax (deprecated, mapped onto Coinbase when the `coinbase` feature is enabled)
+//! - [x] Okx (with order book checksums)
+//! - [x] Simulated (paper trading)
+//!
+//! Every exchange is behind a cargo feature of the same name, the live feeds and their websocket
+//! client behind the `streaming` feature, and the mock exchange of `testing` with its web server
+//! behind the `testing` feature. All of them are enabled by default, disable the default features
+//! to only compile what you use :
+//!
+//! ```toml
+//! coinnect_rt = { version = "0.5", default-features = false, features = ["native", "kraken"] }
+//! ```
+//!
+//! The clients, bots and everything built on actix, hyper and native TLS are behind the `native`
+//! feature. Without it only `public`, the types and the parsing of Bitstamp and Kraken are built,
+//! and the crate compiles for `wasm32-unknown-unknown` where `public::FetchTransport` queries the
+//! exchanges with the fetch api of the browser :
+//!
+//! ```toml
+//! coinnect_rt = { version = "0.5", default-features = false, features = ["public"] }
+//! ```
 //!
 //! # WARNING
 //! This library is highly experimental at the moment. Please do not invest what you
 //! can't afford to loose. This is a personal project, I can not be held responsible for
 //! the library malfunction, which can lead to a loss of money.
 
-// error_chain can make a lot of recursions.
+// Nested macros and async code can make a lot of recursions.
 #![recursion_limit="256"]
 
 // Allow lint customization.
@@ -32,9 +53,11 @@
 // Avoid warning for the Crypto-currency about quotes.
 #![allow(doc_markdown)]
 
+#[cfg(feature = "native")]
 extern crate hyper;
 extern crate sha2;
 extern crate hmac;
+#[cfg(feature = "native")]
 extern crate hyper_tls;
 #[macro_use]
 extern crate serde;
@@ -44,33 +67,126 @@ extern crate chrono;
 extern crate lazy_static;
 extern crate bidir_map;
 extern crate data_encoding;
-#[macro_use]
-extern crate error_chain;
 extern crate bigdecimal;
+#[cfg(feature = "testing")]
 extern crate actix_web;
+#[cfg(feature = "native")]
 extern crate actix;
+#[cfg(feature = "native")]
 #[macro_use] extern crate actix_derive;
 extern crate url;
 #[macro_use] extern crate log;
 extern crate futures;
 extern crate futures_util;
+#[cfg(feature = "streaming")]
 extern crate awc;
+#[cfg(feature = "bittrex")]
 extern crate signalr_rs;
 extern crate base64;
 extern crate libflate;
+#[cfg(feature = "metrics")]
+extern crate prometheus;
+
+#[cfg(all(feature = "binance", not(feature = "native")))]
+compile_error!("the `binance` feature requires the `native` feature");
 
+#[cfg(feature = "native")]
+pub mod cache;
+#[cfg(feature = "native")]
 pub mod coinnect;
+#[cfg(feature = "native")]
+pub mod config;
+#[cfg(feature = "native")]
+pub mod env_credentials;
 pub mod exchange;
+#[cfg(feature = "native")]
 pub mod exchange_bot;
+pub mod registry;
+#[cfg(feature = "native")]
+pub mod event_stream;
 #[allow(deprecated)]
 pub mod error;
 pub mod types;
 pub mod helpers;
 
+#[cfg(feature = "bitstamp")]
 pub mod bitstamp;
+#[cfg(feature = "poloniex")]
 pub mod poloniex;
+#[cfg(feature = "kraken")]
 pub mod kraken;
+#[cfg(all(feature = "kraken", feature = "native"))]
+pub mod kraken_futures;
+#[cfg(feature = "bittrex")]
 pub mod bittrex;
+#[cfg(feature = "gdax")]
 pub mod gdax;
+#[cfg(feature = "coinbase")]
+pub mod coinbase;
+#[cfg(feature = "binance")]
 pub mod binance;
+#[cfg(feature = "okx")]
+pub mod okx;
+pub mod simulated;
 pub mod utils;
+#[cfg(feature = "native")]
+pub mod arbitrage;
+#[cfg(feature = "native")]
+pub mod spread;
+#[cfg(feature = "native")]
+pub mod candles;
+#[cfg(feature = "native")]
+pub mod book_deltas;
+#[cfg(feature = "native")]
+pub mod submission;
+#[cfg(feature = "native")]
+pub mod watchdog;
+#[cfg(feature = "native")]
+pub mod snapshots;
+#[cfg(feature = "native")]
+pub mod analytics;
+#[cfg(feature = "native")]
+pub mod codec;
+#[cfg(feature = "native")]
+pub mod replay;
+#[cfg(feature = "native")]
+pub mod recorder;
+#[cfg(feature = "native")]
+pub mod queues;
+#[cfg(feature = "native")]
+pub mod persistence;
+#[cfg(feature = "native")]
+pub mod switch;
+#[cfg(feature = "native")]
+pub mod strategy;
+#[cfg(feature = "native")]
+pub mod rebalancer;
+#[cfg(feature = "native")]
+pub mod portfolio;
+#[cfg(feature = "native")]
+pub mod positions;
+#[cfg(feature = "native")]
+pub mod precision;
+#[cfg(feature = "native")]
+pub mod conversion;
+#[cfg(feature = "native")]
+pub mod fx;
+#[cfg(feature = "native")]
+pub mod history;
+#[cfg(feature = "native")]
+pub mod supervisor;
+#[cfg(feature = "native")]
+pub mod conditional;
+#[cfg(feature = "native")]
+pub mod order_builder;
+#[cfg(feature = "native")]
+pub mod sweep;
+#[cfg(feature = "native")]
+pub mod market_data;
+pub mod metrics;
+#[cfg(feature = "native")]
+pub mod import;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "public")]
+pub mod public;
@@ -0,0 +1,156 @@
+//! Synchronous access to the latest market data.
+//! `MarketDataCache` keeps the last order book and trade of every pair on every venue, fed by a
+//! `MarketDataUpdater` actor subscribed to the streaming bots. Its getters only take a read lock,
+//! so plain synchronous strategy code can call them from any thread.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use actix::{Actor, Addr, Context, Handler};
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+
+use crate::exchange::Exchange;
+use crate::spread::bbo;
+use crate::types::{LiveEvent, LiveEventEnveloppe, LiveTrade, Orderbook, Pair, Price};
+
+/// A cached value along with when it was received
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stamped<T> {
+    pub value: T,
+    /// UNIX timestamp in ms of the reception of the value
+    pub received_ms: i64,
+}
+
+impl<T> Stamped<T> {
+    /// Age of the value in ms
+    pub fn age_ms(&self) -> i64 {
+        Utc::now().timestamp_millis() - self.received_ms
+    }
+
+    /// Whether the value is older than `max_age_ms`
+    pub fn is_stale(&self, max_age_ms: i64) -> bool {
+        self.age_ms() > max_age_ms
+    }
+
+    fn map<U, F: FnOnce(&T) -> Option<U>>(&self, f: F) -> Option<Stamped<U>> {
+        f(&self.value).map(|value| Stamped { value, received_ms: self.received_ms })
+    }
+}
+
+#[derive(Debug, Default)]
+struct MarketData {
+    book: Option<Stamped<Orderbook>>,
+    last_trade: Option<Stamped<LiveTrade>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MarketDataCache {
+    markets: Arc<RwLock<HashMap<(Exchange, Pair), MarketData>>>,
+}
+
+impl MarketDataCache {
+    pub fn new() -> Self {
+        MarketDataCache::default()
+    }
+
+    /// Create a cache and start the actor feeding it, its address is the recipient to give to bots
+    pub fn start_new() -> (MarketDataCache, Addr<MarketDataUpdater>) {
+        let cache = MarketDataCache::new();
+        let addr = MarketDataUpdater { cache: cache.clone() }.start();
+        (cache, addr)
+    }
+
+    /// Record a live event, events other than order books and trades are ignored
+    pub fn update(&self, event: &LiveEventEnveloppe) {
+        let received_ms = Utc::now().timestamp_millis();
+        let LiveEventEnveloppe(exchange, le) = event;
+        let mut markets = self.markets.write().unwrap();
+        match le {
+            LiveEvent::LiveOrderbook(ob) => {
+                let market = markets.entry((*exchange, ob.pair)).or_default();
+                market.book = Some(Stamped { value: ob.clone(), received_ms });
+            }
+            LiveEvent::LiveTrade(t) => {
+                let market = markets.entry((*exchange, t.pair)).or_default();
+                market.last_trade = Some(Stamped { value: t.clone(), received_ms });
+            }
+            _ => (),
+        }
+    }
+
+    /// The latest order book of `pair` on `exchange`
+    pub fn book(&self, exchange: Exchange, pair: Pair) -> Option<Stamped<Orderbook>> {
+        let markets = self.markets.read().unwrap();
+        markets.get(&(exchange, pair)).and_then(|m| m.book.clone())
+    }
+
+    /// The best bid and offer of `pair` on `exchange`
+    pub fn bbo(&self, exchange: Exchange, pair: Pair) -> Option<Stamped<(Price, Price)>> {
+        let markets = self.markets.read().unwrap();
+        markets.get(&(exchange, pair))?.book.as_ref()?.map(bbo)
+    }
+
+    pub fn best_bid(&self, exchange: Exchange, pair: Pair) -> Option<Stamped<Price>> {
+        self.bbo(exchange, pair)?.map(|(bid, _)| Some(bid.clone()))
+    }
+
+    pub fn best_ask(&self, exchange: Exchange, pair: Pair) -> Option<Stamped<Price>> {
+        self.bbo(exchange, pair)?.map(|(_, ask)| Some(ask.clone()))
+    }
+
+    /// Mid price between the best bid and offer
+    pub fn mid(&self, exchange: Exchange, pair: Pair) -> Option<Stamped<Price>> {
+        self.bbo(exchange, pair)?.map(|(bid, ask)| Some((bid.clone() + ask.clone()) / BigDecimal::from(2)))
+    }
+
+    /// The latest trade of `pair` on `exchange`
+    pub fn last_trade(&self, exchange: Exchange, pair: Pair) -> Option<Stamped<LiveTrade>> {
+        let markets = self.markets.read().unwrap();
+        markets.get(&(exchange, pair)).and_then(|m| m.last_trade.clone())
+    }
+}
+
+/// Actor updating a `MarketDataCache` with every `LiveEventEnveloppe` it receives
+pub struct MarketDataUpdater {
+    cache: MarketDataCache,
+}
+
+impl Actor for MarketDataUpdater {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for MarketDataUpdater {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        self.cache.update(&msg);
+    }
+}
+
+#[cfg(test)]
+mod market_data_tests {
+    use super::*;
+    use crate::types::{TradeMeta, TradeType};
+
+    #[test]
+    fn cache_book_and_trade() {
+        let cache = MarketDataCache::new();
+        let ob = Orderbook {
+            timestamp: 0,
+            pair: Pair::BTC_USD,
+            asks: vec![(BigDecimal::from(101), BigDecimal::from(1))],
+            bids: vec![(BigDecimal::from(99), BigDecimal::from(1))],
+        };
+        cache.update(&LiveEventEnveloppe(Exchange::Bitstamp, LiveEvent::LiveOrderbook(ob)));
+        let trade = LiveTrade { event_ms: 0, pair: Pair::BTC_USD, amount: BigDecimal::from(1), price: BigDecimal::from(100), tt: TradeType::Buy, meta: TradeMeta::default() };
+        cache.update(&LiveEventEnveloppe(Exchange::Bitstamp, LiveEvent::LiveTrade(trade)));
+
+        assert_eq!(cache.best_bid(Exchange::Bitstamp, Pair::BTC_USD).unwrap().value, BigDecimal::from(99));
+        let mid = cache.mid(Exchange::Bitstamp, Pair::BTC_USD).unwrap();
+        assert_eq!(mid.value, BigDecimal::from(100));
+        assert!(!mid.is_stale(60_000));
+        assert!(cache.last_trade(Exchange::Bitstamp, Pair::BTC_USD).is_some());
+        assert!(cache.book(Exchange::Kraken, Pair::BTC_USD).is_none());
+    }
+}
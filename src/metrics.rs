@@ -0,0 +1,188 @@
+//! Prometheus metrics of the REST and websocket activity, behind the `metrics` feature.
+//! Without the feature the recording functions do nothing, so call sites need no `cfg`.
+//!
+//! Metrics are kept in a dedicated registry, prefixed with `coinnect_` :
+//! * `coinnect_rest_request_seconds` : latency of REST requests, by exchange and endpoint
+//! * `coinnect_ws_messages_total` : websocket events, by exchange and channel
+//! * `coinnect_ws_reconnects_total` : websocket reconnections, by connection name
+//! * `coinnect_orderbook_lag_seconds` : delay between an order book timestamp and its handling
+//! * `coinnect_unknown_values_total` : values received from the exchanges that were not recognized, by field
+//! * `coinnect_dropped_events_total` : events dropped because a recipient could not keep up, by delivery policy
+
+use std::time::Instant;
+
+use crate::exchange::Exchange;
+use crate::types::LiveEvent;
+
+#[cfg(feature = "metrics")]
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder};
+#[cfg(feature = "metrics")]
+pub use prometheus::proto::MetricFamily;
+
+#[cfg(feature = "metrics")]
+lazy_static! {
+    static ref REGISTRY: Registry = Registry::new_custom(Some("coinnect".to_string()), None).unwrap();
+
+    static ref REST_REQUEST_SECONDS: HistogramVec = {
+        let opts = HistogramOpts::new("rest_request_seconds", "Latency of REST requests");
+        let histogram = HistogramVec::new(opts, &["exchange", "endpoint"]).unwrap();
+        REGISTRY.register(Box::new(histogram.clone())).unwrap();
+        histogram
+    };
+
+    static ref WS_MESSAGES_TOTAL: IntCounterVec = {
+        let opts = Opts::new("ws_messages_total", "Websocket events received");
+        let counter = IntCounterVec::new(opts, &["exchange", "channel"]).unwrap();
+        REGISTRY.register(Box::new(counter.clone())).unwrap();
+        counter
+    };
+
+    static ref WS_RECONNECTS_TOTAL: IntCounterVec = {
+        let opts = Opts::new("ws_reconnects_total", "Websocket reconnections");
+        let counter = IntCounterVec::new(opts, &["name"]).unwrap();
+        REGISTRY.register(Box::new(counter.clone())).unwrap();
+        counter
+    };
+
+    static ref ORDERBOOK_LAG_SECONDS: HistogramVec = {
+        let opts = HistogramOpts::new("orderbook_lag_seconds", "Delay between an order book update and its handling")
+            .buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]);
+        let histogram = HistogramVec::new(opts, &["exchange"]).unwrap();
+        REGISTRY.register(Box::new(histogram.clone())).unwrap();
+        histogram
+    };
+
+    static ref UNKNOWN_VALUES_TOTAL: IntCounterVec = {
+        let opts = Opts::new("unknown_values_total", "Values received from the exchanges that were not recognized");
+        let counter = IntCounterVec::new(opts, &["field"]).unwrap();
+        REGISTRY.register(Box::new(counter.clone())).unwrap();
+        counter
+    };
+
+    static ref DROPPED_EVENTS_TOTAL: IntCounterVec = {
+        let opts = Opts::new("dropped_events_total", "Events dropped because a recipient could not keep up");
+        let counter = IntCounterVec::new(opts, &["policy"]).unwrap();
+        REGISTRY.register(Box::new(counter.clone())).unwrap();
+        counter
+    };
+}
+
+/// The registry holding every coinnect metric, to be merged into an application registry
+#[cfg(feature = "metrics")]
+pub fn registry() -> &'static Registry {
+    init();
+    &REGISTRY
+}
+
+/// Snapshot of every coinnect metric
+#[cfg(feature = "metrics")]
+pub fn gather() -> Vec<MetricFamily> {
+    registry().gather()
+}
+
+/// Every coinnect metric in the Prometheus text exposition format
+#[cfg(feature = "metrics")]
+pub fn encode_text() -> String {
+    let mut buffer = vec![];
+    TextEncoder::new().encode(&gather(), &mut buffer).unwrap();
+    String::from_utf8(buffer).unwrap()
+}
+
+/// Register the metrics, so they are exposed before their first observation
+#[cfg(feature = "metrics")]
+fn init() {
+    lazy_static::initialize(&REST_REQUEST_SECONDS);
+    lazy_static::initialize(&WS_MESSAGES_TOTAL);
+    lazy_static::initialize(&WS_RECONNECTS_TOTAL);
+    lazy_static::initialize(&ORDERBOOK_LAG_SECONDS);
+    lazy_static::initialize(&UNKNOWN_VALUES_TOTAL);
+    lazy_static::initialize(&DROPPED_EVENTS_TOTAL);
+}
+
+/// Label of a REST endpoint : its first path segment, leaving out identifiers and query strings
+pub(crate) fn endpoint_label(endpoint: &str) -> &str {
+    endpoint.trim_start_matches('/')
+        .split(|c| c == '/' || c == '?')
+        .next()
+        .unwrap_or_default()
+}
+
+/// Channel of a websocket event
+pub(crate) fn channel_label(event: &LiveEvent) -> &'static str {
+    match event {
+        LiveEvent::LiveOrder(_) => "order",
+        LiveEvent::LiveTrade(_) => "trade",
+        LiveEvent::LiveOrderbook(_) => "orderbook",
+        LiveEvent::LiveOrderbookDelta(_) => "orderbook_delta",
+        LiveEvent::LiveDetailOrderbook(_) => "detail_orderbook",
+        LiveEvent::LiveCandle(_) => "candle",
+        LiveEvent::LiveSummary(_) => "summary",
+        LiveEvent::Fill(_) => "fill",
+        LiveEvent::SubscriptionResult(_) => "subscription",
+        LiveEvent::Noop => "noop",
+    }
+}
+
+/// Record a REST request to `endpoint` started at `start`
+#[allow(unused_variables)]
+pub(crate) fn rest_request(exchange: Exchange, endpoint: &str, start: Instant) {
+    #[cfg(feature = "metrics")]
+    {
+        REST_REQUEST_SECONDS
+            .with_label_values(&[&format!("{:?}", exchange), endpoint_label(endpoint)])
+            .observe(start.elapsed().as_secs_f64());
+    }
+}
+
+/// Record a websocket event, and the lag of order book updates
+#[allow(unused_variables)]
+pub(crate) fn ws_event(exchange: Exchange, event: &LiveEvent) {
+    #[cfg(feature = "metrics")]
+    {
+        let exchange = format!("{:?}", exchange);
+        WS_MESSAGES_TOTAL.with_label_values(&[&exchange, channel_label(event)]).inc();
+        if let LiveEvent::LiveOrderbook(ob) = event {
+            let lag_ms = crate::helpers::get_unix_timestamp_ms() - ob.timestamp;
+            ORDERBOOK_LAG_SECONDS.with_label_values(&[&exchange]).observe(lag_ms.max(0) as f64 / 1000.0);
+        }
+    }
+}
+
+/// Record a reconnection of the websocket connection `name`
+#[allow(unused_variables)]
+pub(crate) fn ws_reconnect(name: &str) {
+    #[cfg(feature = "metrics")]
+    {
+        WS_RECONNECTS_TOTAL.with_label_values(&[name]).inc();
+    }
+}
+
+/// Record a value of `field` that was not recognized
+#[allow(unused_variables)]
+pub(crate) fn unknown_value(field: &str) {
+    #[cfg(feature = "metrics")]
+    {
+        UNKNOWN_VALUES_TOTAL.with_label_values(&[field]).inc();
+    }
+}
+
+/// Record `count` events dropped under the delivery `policy`
+#[allow(unused_variables)]
+pub(crate) fn dropped_events(policy: &str, count: usize) {
+    #[cfg(feature = "metrics")]
+    {
+        DROPPED_EVENTS_TOTAL.with_label_values(&[policy]).inc_by(count as i64);
+    }
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_label_drops_identifiers() {
+        assert_eq!(endpoint_label("Ticker"), "Ticker");
+        assert_eq!(endpoint_label("/orders/1234?status=open"), "orders");
+        assert_eq!(endpoint_label("markets/BTC-ETH/ticker"), "markets");
+    }
+}
@@ -0,0 +1,284 @@
+//! Use this module to interact with the raw-original API v5 provided by Okx.
+//! WARNING: Special attention should be paid to error management: parsing number, etc.
+
+use hyper::{Body, Method, Request, Uri};
+use hyper::header::CONTENT_TYPE;
+
+use chrono::{TimeZone, Utc};
+use serde_json::Value;
+use serde_json::value::Map;
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicI64;
+use std::time::Instant;
+
+use crate::coinnect::Credentials;
+use crate::error::*;
+use crate::exchange::{Environment, Exchange};
+use crate::helpers;
+use crate::helpers::clock::ServerClock;
+use crate::helpers::rate_limit::RateLimits;
+use crate::helpers::failover::Endpoints;
+use crate::helpers::json;
+use crate::helpers::proxy::HttpClient;
+use crate::helpers::secret::SecretString;
+use crate::helpers::signing;
+use crate::metrics;
+use crate::okx::utils;
+use bytes::buf::BufExt as _;
+
+const OK_ACCESS_KEY: &str = "OK-ACCESS-KEY";
+const OK_ACCESS_SIGN: &str = "OK-ACCESS-SIGN";
+const OK_ACCESS_TIMESTAMP: &str = "OK-ACCESS-TIMESTAMP";
+const OK_ACCESS_PASSPHRASE: &str = "OK-ACCESS-PASSPHRASE";
+/// Sent with every request of the demo trading environment
+const SIMULATED_TRADING: &str = "x-simulated-trading";
+
+#[derive(Debug)]
+pub struct OkxApi {
+    last_request: AtomicI64, // unix timestamp in ms, to avoid ban
+    api_key: SecretString,
+    api_secret: SecretString,
+    passphrase: SecretString,
+    http_client: HttpClient,
+    burst: bool,
+    demo: bool,
+    pub(crate) keep_raw: bool,
+    debug_signing: bool,
+    endpoints: Endpoints,
+    pub(crate) clock: ServerClock,
+    pub(crate) rate_limits: RateLimits,
+}
+
+impl OkxApi {
+    /// Create a new OkxApi by providing an API key, API secret and passphrase, demo trading is
+    /// targeted when the credentials ask for the sandbox
+    pub fn new<C: Credentials>(creds: C) -> Result<OkxApi> {
+        if creds.exchange() != Exchange::Okx {
+            return Err(Error::InvalidConfigType(Exchange::Okx, creds.exchange()));
+        }
+
+        let connection = creds.connection();
+        let endpoints = connection.rest_endpoints(Exchange::Okx, utils::API_URL);
+
+        Ok(OkxApi {
+            last_request: AtomicI64::new(0),
+            api_key: creds.get("api_key").unwrap_or_default().into(),
+            api_secret: creds.get("api_secret").unwrap_or_default().into(),
+            passphrase: creds.get("passphrase").unwrap_or_default().into(),
+            http_client: connection.http_client()?,
+            burst: false,
+            demo: creds.environment()? == Environment::Sandbox,
+            keep_raw: connection.keep_raw,
+            debug_signing: connection.debug_signing,
+            endpoints,
+            clock: ServerClock::default(),
+            rate_limits: RateLimits::default(),
+        })
+    }
+
+    /// The REST endpoints, to subscribe to failover events or recover the primary endpoint
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
+    /// The number of calls in a given period is limited. In order to avoid a ban we limit
+    /// by default the number of api requests.
+    /// This function sets or removes the limitation.
+    /// Burst false implies no block.
+    /// Burst true implies there is a control over the number of calls allowed to the exchange
+    pub fn set_burst(&mut self, burst: bool) {
+        self.burst = burst
+    }
+
+    /// Keep the payload of the exchange in the `raw` field of typed responses
+    pub fn set_keep_raw(&mut self, keep_raw: bool) {
+        self.keep_raw = keep_raw
+    }
+
+    /// Log the strings signed for the private requests and the status of their responses
+    pub fn set_debug_signing(&mut self, debug_signing: bool) {
+        self.debug_signing = debug_signing
+    }
+
+    async fn block_or_continue(&self) {
+        if !self.burst {
+            helpers::rate_limit::wait_for_slot(&self.last_request, 100).await; // 10 requests/sec
+        }
+    }
+
+    /// Send a request to `path`, which includes the query string, signed with the OK-ACCESS-*
+    /// headers when `signed`. `body` is sent as JSON.
+    async fn query(&self, method: Method, path: &str, body: Option<Value>, signed: bool) -> Result<Map<String, Value>> {
+        let url: Uri = format!("{}{}", self.endpoints.current(), path).parse().map_err(|_e| Error::BadParse)?;
+        let content = body.map(|b| b.to_string()).unwrap_or_default();
+
+        let mut builder = Request::builder()
+            .method(method.clone())
+            .uri(url)
+            .header(CONTENT_TYPE, "application/json");
+        if signed {
+            let timestamp = Utc.timestamp_millis(self.clock.now_ms()).format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+            let signature = utils::build_signature(&timestamp, method.as_str(), path, &content, self.api_secret.expose())?;
+            if self.debug_signing {
+                let canonical = format!("{}{}{}{}", timestamp, method.as_str(), path, content);
+                signing::log_signed(Exchange::Okx, path, &timestamp, &canonical, &[self.api_key.expose(), self.passphrase.expose()]);
+            }
+            builder = builder
+                .header(OK_ACCESS_KEY, self.api_key.expose())
+                .header(OK_ACCESS_SIGN, signature)
+                .header(OK_ACCESS_TIMESTAMP, timestamp)
+                .header(OK_ACCESS_PASSPHRASE, self.passphrase.expose());
+        }
+        if self.demo {
+            builder = builder.header(SIMULATED_TRADING, "1");
+        }
+        let req = builder.body(Body::from(content)).map_err(|e| Error::ServiceUnavailable(e.to_string()))?;
+
+        self.block_or_continue().await;
+        let start = Instant::now();
+        let resp = self.http_client.request(req).await;
+        metrics::rest_request(Exchange::Okx, path, start);
+        let resp = self.endpoints.track(resp)?;
+        self.rate_limits.observe(resp.headers(), helpers::get_unix_timestamp_ms());
+        if signed && self.debug_signing {
+            signing::log_response(Exchange::Okx, path, resp.status());
+        }
+        let buf = hyper::body::aggregate(resp.into_body()).await?;
+        json::deserialize_json_r(buf.reader())
+    }
+
+    async fn public_query(&self, path: &str, params: &mut HashMap<&str, &str>) -> Result<Map<String, Value>> {
+        helpers::strip_empties(params);
+        let path = format!("{}?{}", path, helpers::url_encode_hashmap(params));
+        self.query(Method::GET, &path, None, false).await
+    }
+
+    async fn private_get(&self, path: &str, params: &mut HashMap<&str, &str>) -> Result<Map<String, Value>> {
+        helpers::strip_empties(params);
+        let query = helpers::url_encode_hashmap(params);
+        let path = if query.is_empty() { path.to_string() } else { format!("{}?{}", path, query) };
+        self.query(Method::GET, &path, None, true).await
+    }
+
+    /// Server time
+    ///
+    /// ```json
+    /// {"code": "0", "msg": "", "data": [{"ts": "1597026383085"}]}
+    /// ```
+    pub async fn get_time(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.public_query("/api/v5/public/time", &mut params).await
+    }
+
+    /// Instruments of a type : SPOT, MARGIN, SWAP, FUTURES or OPTION
+    pub async fn get_instruments(&self, inst_type: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("instType", inst_type);
+        self.public_query("/api/v5/public/instruments", &mut params).await
+    }
+
+    /// ```json
+    /// {"code": "0", "msg": "", "data": [{"instId": "BTC-USDT", "last": "9999.99", "askPx": "9999.99",
+    /// "askSz": "11", "bidPx": "8888.88", "bidSz": "5", "open24h": "9000", "high24h": "10000",
+    /// "low24h": "8888.88", "vol24h": "2222", "ts": "1597026383085", ...}]}
+    /// ```
+    pub async fn get_ticker(&self, inst_id: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("instId", inst_id);
+        self.public_query("/api/v5/market/ticker", &mut params).await
+    }
+
+    /// Tickers of every instrument of a type
+    pub async fn get_tickers(&self, inst_type: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("instType", inst_type);
+        self.public_query("/api/v5/market/tickers", &mut params).await
+    }
+
+    /// Order book of at most 400 levels per side
+    ///
+    /// ```json
+    /// {"code": "0", "msg": "", "data": [{"asks": [["41006.8", "0.60038921", "0", "1"]],
+    /// "bids": [["41006.3", "0.30178218", "0", "2"]], "ts": "1629966436396"}]}
+    /// ```
+    pub async fn get_books(&self, inst_id: &str, size: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("instId", inst_id);
+        params.insert("sz", size);
+        self.public_query("/api/v5/market/books", &mut params).await
+    }
+
+    /// Most recent trades of an instrument
+    pub async fn get_trades(&self, inst_id: &str, limit: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("instId", inst_id);
+        params.insert("limit", limit);
+        self.public_query("/api/v5/market/trades", &mut params).await
+    }
+
+    /// Balance of each currency of the trading account, `availBal` and `frozenBal` in `details`
+    pub async fn get_balance(&self) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        self.private_get("/api/v5/account/balance", &mut params).await
+    }
+
+    /// Maker and taker fee rates of an instrument, negative for fees paid by the account
+    pub async fn get_trade_fee(&self, inst_type: &str, inst_id: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("instType", inst_type);
+        params.insert("instId", inst_id);
+        self.private_get("/api/v5/account/trade-fee", &mut params).await
+    }
+
+    /// Fills of the last 3 days, older than the fill `after` (a `billId`) if given
+    pub async fn get_fills(&self, inst_type: &str, inst_id: &str, after: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("instType", inst_type);
+        params.insert("instId", inst_id);
+        params.insert("after", after);
+        self.private_get("/api/v5/trade/fills", &mut params).await
+    }
+
+    /// Input:
+    ///
+    /// ```json
+    /// instId = instrument id, e.g. BTC-USDT
+    /// tdMode = cash for spot, cross or isolated for margin
+    /// side = buy or sell
+    /// ordType = market, limit, post_only, fok or ioc
+    /// sz = quantity, in quote currency for spot market buys
+    /// px = price of the limit orders
+    /// tgtCcy = base_ccy or quote_ccy, currency of sz for spot market orders
+    /// ```
+    /// Result: `ordId`, and `sCode`/`sMsg` which are not `0` when the order was refused
+    pub async fn place_order(&self, inst_id: &str, td_mode: &str, side: &str, ord_type: &str, size: &str, price: Option<&str>, tgt_ccy: Option<&str>) -> Result<Map<String, Value>> {
+        let mut body = serde_json::json!({"instId": inst_id, "tdMode": td_mode, "side": side, "ordType": ord_type, "sz": size});
+        if let Some(px) = price {
+            body["px"] = Value::String(px.to_string());
+        }
+        if let Some(tgt_ccy) = tgt_ccy {
+            body["tgtCcy"] = Value::String(tgt_ccy.to_string());
+        }
+        self.query(Method::POST, "/api/v5/trade/order", Some(body), true).await
+    }
+
+    pub async fn cancel_order(&self, inst_id: &str, ord_id: &str) -> Result<Map<String, Value>> {
+        let body = serde_json::json!({"instId": inst_id, "ordId": ord_id});
+        self.query(Method::POST, "/api/v5/trade/cancel-order", Some(body), true).await
+    }
+
+    /// Open orders of an instrument, of every instrument if empty
+    pub async fn get_orders_pending(&self, inst_id: &str) -> Result<Map<String, Value>> {
+        let mut params = HashMap::new();
+        params.insert("instId", inst_id);
+        self.private_get("/api/v5/trade/orders-pending", &mut params).await
+    }
+
+    /// Server time in ms
+    pub async fn server_time(&self) -> Result<i64> {
+        let raw_response = self.get_time().await?;
+        let data = utils::parse_first(&raw_response)?;
+        data["ts"].as_str().and_then(|ts| ts.parse().ok()).ok_or_else(|| Error::MissingField("ts".to_string()))
+    }
+}
@@ -0,0 +1,149 @@
+//! Contains the Okx credentials.
+
+use serde_json;
+use serde_json::Value;
+
+use crate::coinnect::Credentials;
+use crate::env_credentials::EnvCredentials;
+use crate::exchange::{Environment, Exchange};
+use crate::helpers;
+use crate::helpers::secret::Redacted;
+use crate::error::*;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub struct OkxCreds {
+    exchange: Exchange,
+    name: String,
+    data: HashMap<String, String>,
+}
+
+impl fmt::Debug for OkxCreds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OkxCreds")
+            .field("exchange", &self.exchange)
+            .field("name", &self.name)
+            .field("data", &Redacted(&self.data))
+            .finish()
+    }
+}
+
+impl OkxCreds {
+    /// Create a new `OkxCreds` from a json configuration file. This file must follow this
+    /// structure:
+    ///
+    /// ```json
+    /// {
+    ///     "account_okx": {
+    ///         "exchange"  : "okx",
+    ///         "api_key"   : "123456789ABCDEF",
+    ///         "api_secret": "ABC&EF?abcdef",
+    ///         "passphrase": "123456",
+    ///         "environment": "sandbox"
+    ///     },
+    ///     "account_bitstamp": {
+    ///         "exchange"   : "bitstamp",
+    ///         "api_key"    : "1234567890ABCDEF1234567890ABCDEF",
+    ///         "api_secret" : "1234567890ABCDEF1234567890ABCDEF",
+    ///         "customer_id": "123456"
+    ///     }
+    /// }
+    /// ```
+    /// `environment` (defaults to `production`), `rest_url`, `ws_url` and `proxy` are optional.
+    /// For this example, you could use load your Okx account with
+    /// `OkxApi::new(OkxCreds::new_from_file("account_okx", Path::new("/keys.json")))`
+    pub fn new_from_file(name: &str, path: PathBuf) -> Result<Self> {
+        let mut f = File::open(&path)?;
+        let mut buffer = String::new();
+        f.read_to_string(&mut buffer)?;
+
+        let data: Value = serde_json::from_str(&buffer)?;
+        let json_obj = data.as_object()
+            .ok_or_else(|| Error::BadParse)?
+            .get(name)
+            .ok_or_else(|| Error::MissingField(name.to_string()))?;
+
+        let api_key = helpers::get_json_string(json_obj, "api_key")?;
+        let api_secret = helpers::get_json_string(json_obj, "api_secret")?;
+        let passphrase = helpers::get_json_string(json_obj, "passphrase")?;
+        let exchange = {
+            let exchange_str = helpers::get_json_string(json_obj, "exchange")?;
+            Exchange::from_str(exchange_str)
+                .with_context(|| Error::InvalidFieldValue("exchange".to_string()))?
+        };
+
+        if exchange != Exchange::Okx {
+            return Err(Error::InvalidConfigType(Exchange::Okx, exchange));
+        }
+
+        let mut creds = OkxCreds::new(name, api_key, api_secret, passphrase);
+        helpers::copy_client_settings(json_obj, &mut creds.data);
+        Ok(creds)
+    }
+
+    /// Target the given environment instead of production.
+    pub fn with_environment(mut self, environment: Environment) -> Self {
+        let value = match environment {
+            Environment::Production => "production",
+            Environment::Sandbox => "sandbox",
+        };
+        self.data.insert("environment".to_string(), value.to_string());
+        self
+    }
+
+
+    /// Create a new `OkxCreds` from arguments.
+    pub fn new(name: &str, api_key: &str, api_secret: &str, passphrase: &str) -> Self {
+        let mut creds = OkxCreds {
+            data: HashMap::new(),
+            exchange: Exchange::Okx,
+            name: if name.is_empty() {
+                "OkxClient".to_string()
+            } else {
+                name.to_string()
+            },
+        };
+        creds.data.insert("api_key".to_string(), api_key.to_string());
+        creds.data.insert("api_secret".to_string(), api_secret.to_string());
+        creds.data.insert("passphrase".to_string(), passphrase.to_string());
+        creds
+    }
+
+    /// Create a new `OkxCreds` from the environment variables `<prefix>_OKX_API_KEY`, `<prefix>_OKX_API_SECRET`
+    /// and `<prefix>_OKX_PASSPHRASE`, see `EnvCredentials`.
+    /// The optional client settings are read the same way, e.g. `<prefix>_OKX_PROXY`.
+    pub fn new_from_env(prefix: &str) -> Result<Self> {
+        let env = EnvCredentials::new(prefix, Exchange::Okx);
+        let api_key = env.require("api_key")?;
+        let api_secret = env.require("api_secret")?;
+        let passphrase = env.require("passphrase")?;
+        let mut creds = OkxCreds::new(&env.name(), &api_key, &api_secret, &passphrase);
+        env.copy_client_settings(&mut creds.data);
+        Ok(creds)
+    }
+}
+
+impl Credentials for OkxCreds {
+    /// Return a value from the credentials.
+    fn get(&self, key: &str) -> Option<String> {
+        if let Some(res) = self.data.get(key) {
+            Some(res.clone())
+        } else {
+            None
+        }
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+}
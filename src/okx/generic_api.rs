@@ -0,0 +1,165 @@
+//! Use this module to interact with Okx through a Generic API.
+//! This a more convenient and safe way to deal with the exchange since methods return a Result<>
+//! but this generic API does not provide all the functionnality that Okx offers.
+
+use crate::exchange::{ExchangeApi, Exchange, ExchangeCapabilities};
+use crate::okx::api::OkxApi;
+use crate::okx::utils;
+
+use crate::error::*;
+use crate::types::*;
+use crate::helpers;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use serde_json::Value;
+
+/// Currencies of the balances are native symbols, which match the `Currency` names
+fn parse_currency(ccy: &Value) -> Option<Currency> {
+    serde_json::from_value(ccy.clone()).ok()
+}
+
+#[async_trait]
+impl ExchangeApi for OkxApi {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
+        let inst_id = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let raw_response = self.get_ticker(inst_id).await?;
+        utils::parse_ticker(pair, &utils::parse_first(&raw_response)?, self.keep_raw)
+    }
+
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
+        let inst_id = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let raw_response = self.get_books(inst_id, "400").await?;
+        utils::parse_orderbook(pair, &utils::parse_first(&raw_response)?)
+    }
+
+    /// Spot orders are placed in `cash` mode, market buys are sized in the base currency
+    async fn add_order(&self,
+                 order_type: OrderType,
+                 pair: Pair,
+                 quantity: Volume,
+                 price: Option<Price>)
+                 -> Result<OrderInfo> {
+        let request = OrderRequest::new(order_type, pair, &quantity, &price);
+        let inst_id = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        // Spot market buys are sized in the quote currency unless told otherwise
+        let (ord_type, price, tgt_ccy) = match order_type {
+            OrderType::BuyLimit | OrderType::SellLimit => ("limit", Some(price.ok_or(Error::MissingPrice)?.to_string()), None),
+            OrderType::BuyMarket | OrderType::SellMarket => ("market", None, Some("base_ccy")),
+            _ => return Err(Error::OrderTypeUnsupported),
+        };
+        let side = match order_type.side() {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        };
+        let raw_response = self.place_order(inst_id, "cash", side, ord_type, &quantity.to_string(), price.as_deref(), tgt_ccy).await?;
+        utils::check_order_code(&raw_response)?;
+        let result = utils::parse_first(&raw_response)?;
+
+        Ok(OrderInfo {
+               timestamp: helpers::get_unix_timestamp_ms(),
+               identifier: vec![result["ordId"].as_str().ok_or_else(|| Error::MissingField("ordId".to_string()))?.to_string()],
+               raw: helpers::raw_payload(self.keep_raw, &result),
+               request: Some(request),
+           })
+    }
+
+    /// Orders are cancelled by instrument, the pair is taken from the `request` of the order
+    async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+        let request = order.request.as_ref().ok_or_else(|| Error::MissingField("request".to_string()))?;
+        let inst_id = utils::get_pair_string(&request.pair).ok_or(Error::PairUnsupported)?;
+        for ord_id in &order.identifier {
+            let raw_response = self.cancel_order(inst_id, ord_id).await?;
+            utils::check_order_code(&raw_response)?;
+            utils::parse_first(&raw_response)?;
+        }
+        Ok(())
+    }
+
+    /// Fee rates are negative when paid by the account, rebates are positive
+    async fn fees(&self, pair: Pair) -> Result<FeeRates> {
+        let inst_id = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let raw_response = self.get_trade_fee("SPOT", inst_id).await?;
+        let result = utils::parse_first(&raw_response)?;
+        let maker: BigDecimal = helpers::from_json_bigdecimal(&result["maker"], "maker")?;
+        let taker: BigDecimal = helpers::from_json_bigdecimal(&result["taker"], "taker")?;
+        Ok(FeeRates::new(-maker, -taker))
+    }
+
+    /// Return the available balance of each currency of the trading account
+    async fn balances(&self) -> Result<Balances> {
+        Ok(self.detailed_balances().await?.into_iter().map(|(c, b)| (c, b.available)).collect())
+    }
+
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        let raw_response = self.get_balance().await?;
+        let account = utils::parse_first(&raw_response)?;
+        let details = account["details"].as_array().ok_or_else(|| Error::InvalidFieldFormat("details".to_string()))?;
+
+        let mut balances = DetailedBalances::new();
+
+        for detail in details {
+            if let Some(c) = parse_currency(&detail["ccy"]) {
+                let available = helpers::from_json_bigdecimal(&detail["availBal"], "availBal")?;
+                let frozen = helpers::from_json_bigdecimal(&detail["frozenBal"], "frozenBal")?;
+                balances.insert(c, Balance::new(available, frozen));
+            }
+        }
+
+        Ok(balances)
+    }
+
+    /// Okx charges the fee in the currency received, pages are walked with the `billId` of their
+    /// oldest fill as cursor
+    async fn fills_page(&self, pair: Pair, cursor: Option<String>) -> Result<FillsPage> {
+        let inst_id = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let raw_response = self.get_fills("SPOT", inst_id, cursor.as_deref().unwrap_or_default()).await?;
+        let fills = utils::parse_result(&raw_response)?.iter().map(|f| {
+            let fee: BigDecimal = helpers::from_json_bigdecimal(&f["fee"], "fee")?;
+            Ok(FillEvent {
+                event_ms: f["ts"].as_str().and_then(|ts| ts.parse().ok()).ok_or_else(|| Error::MissingField("ts".to_string()))?,
+                order_id: f["ordId"].as_str().ok_or_else(|| Error::MissingField("ordId".to_string()))?.to_string(),
+                trade_id: f["billId"].as_str().map(|id| id.to_string()),
+                pair,
+                side: TradeType::parse(f["side"].as_str().unwrap_or_default()),
+                price: helpers::from_json_bigdecimal(&f["fillPx"], "fillPx")?,
+                amount: helpers::from_json_bigdecimal(&f["fillSz"], "fillSz")?,
+                fee: -fee,
+                fee_currency: parse_currency(&f["feeCcy"]),
+                liquidity: match f["execType"].as_str() {
+                    Some("M") => Liquidity::Maker,
+                    Some("T") => Liquidity::Taker,
+                    _ => Liquidity::Unknown,
+                },
+            })
+        }).collect::<Result<Vec<FillEvent>>>()?;
+        let next = fills.iter().filter_map(|f| f.trade_id.as_ref()?.parse::<u64>().ok()).min().map(|id| id.to_string());
+        Ok(FillsPage { fills, next })
+    }
+
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        let raw_response = self.get_instruments("SPOT").await?;
+        Ok(utils::parse_result(&raw_response)?.iter()
+            .filter(|i| i["state"] == "live")
+            .filter_map(|i| i["instId"].as_str().and_then(utils::get_pair_enum).copied())
+            .collect())
+    }
+
+    async fn time(&self) -> Result<i64> {
+        let sent_ms = helpers::get_unix_timestamp_ms();
+        let server_ms = self.server_time().await?;
+        self.clock.observe(server_ms, sent_ms, helpers::get_unix_timestamp_ms());
+        Ok(server_ms)
+    }
+
+    fn clock_skew(&self) -> Option<i64> {
+        self.clock.skew()
+    }
+
+    fn limits(&self) -> RateLimitStatus {
+        self.rate_limits.status()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Okx.capabilities()
+    }
+}
@@ -0,0 +1,11 @@
+//! Use this module to interact with Okx exchange.
+
+pub mod api;
+pub mod generic_api;
+pub mod credentials;
+pub mod utils;
+#[cfg(feature = "streaming")]
+pub mod streaming_api;
+
+pub use self::credentials::OkxCreds;
+pub use self::api::OkxApi;
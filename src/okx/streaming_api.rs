@@ -0,0 +1,287 @@
+//! Stream the public channels of Okx.
+//! The `books` channel sends a snapshot of 400 levels then the levels that changed, each message
+//! carries the checksum of the 25 best levels of the resulting book. A book whose checksum does not
+//! match is resynced : it is cleared and its instrument subscribed again for a fresh snapshot.
+
+use crate::coinnect::Credentials;
+use crate::exchange_bot::{self, DefaultWsActor, ObserveRawFrames, RawFrameEvent, WsHandler, ExchangeBot, Snapshot};
+use crate::error::*;
+use crate::okx::utils;
+use bytes::Bytes;
+use bytes::Buf;
+use futures::future::LocalBoxFuture;
+use futures::stream::SplitSink;
+use actix::{io::SinkWrite, Addr, Recipient};
+use awc::{
+    ws::{Codec, Message},  BoxedSocket
+};
+use actix_codec::Framed;
+use crate::types::{LiveEvent, Channel, LiveAggregatedOrderBook, DEFAULT_BOOK_DEPTH, LiveEventEnveloppe, LiveTrade, Orderbook, Pair, SubscriptionResult, TradeMeta, TradeTape, TradeType, WarmUp};
+use crate::exchange::{Exchange, ExchangeCapabilities};
+use crate::helpers;
+use crate::helpers::delivery::{Delivery, DeliveryPolicy};
+use crate::helpers::failover::Endpoints;
+use crate::metrics;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+
+pub struct OkxBot {
+    addr: Addr<DefaultWsActor>,
+    endpoints: Arc<Endpoints>,
+}
+
+impl ExchangeBot for OkxBot {
+    fn is_connected(&self) -> bool {
+        self.addr.connected()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Okx.capabilities()
+    }
+
+    fn observe_raw_frames(&self, recipient: Recipient<RawFrameEvent>) -> Result<()> {
+        self.addr.do_send(ObserveRawFrames(Exchange::Okx, recipient));
+        Ok(())
+    }
+
+    fn endpoints(&self) -> Option<Arc<Endpoints>> {
+        Some(self.endpoints.clone())
+    }
+
+    fn shutdown(&self) -> LocalBoxFuture<'static, ()> {
+        exchange_bot::shutdown_ws(&self.addr)
+    }
+}
+
+impl OkxBot {
+    /// The current order book and latest trade of `pair`
+    pub async fn snapshot(&self, pair: Pair) -> Result<Option<Snapshot>> {
+        exchange_bot::query_snapshot(&self.addr, pair).await
+    }
+}
+
+#[derive(Debug)]
+pub struct OkxStreamingApi {
+    delivery: Delivery<LiveEventEnveloppe>,
+    channels: HashMap<Channel, HashSet<Pair>>,
+    warm_up: WarmUp,
+    depth: i8,
+    agg: HashMap<Pair, LiveAggregatedOrderBook>,
+    books: HashMap<Pair, Orderbook>,
+    last_trades: HashMap<Pair, LiveTrade>,
+    tape: TradeTape,
+}
+
+impl OkxStreamingApi {
+    pub async fn new_bot<C: Credentials>(creds: Box<C>, channels: HashMap<Channel, HashSet<Pair>>, warm_up: WarmUp, depth: Option<i8>, delivery: DeliveryPolicy, recipients: Vec<Recipient<LiveEventEnveloppe>>, raw_frames: Option<Recipient<RawFrameEvent>>) -> Result<OkxBot> {
+        let connection = creds.connection();
+        let url = utils::websocket_url(creds.environment()?);
+        let api = OkxStreamingApi {
+            delivery: Delivery::new(recipients, delivery),
+            channels,
+            warm_up,
+            depth: depth.unwrap_or(DEFAULT_BOOK_DEPTH),
+            agg: HashMap::new(),
+            books: HashMap::new(),
+            last_trades: HashMap::new(),
+            tape: TradeTape::default(),
+        };
+        let endpoints = Arc::new(connection.ws_endpoints(Exchange::Okx, url));
+        let addr = DefaultWsActor::new("OkxStream", endpoints.clone(), connection.proxy()?, Some(Duration::from_secs(20)), raw_frames.map(|r| (Exchange::Okx, r)).into_iter().collect(), Box::new(api)).await?;
+        Ok(OkxBot { addr, endpoints })
+    }
+
+    /// Every order book channel is served by the `books` channel
+    fn book_pairs(&self) -> HashSet<Pair> {
+        [Channel::LiveOrderBook, Channel::LiveDetailOrderBook, Channel::LiveFullOrderBook].iter()
+            .filter_map(|c| self.channels.get(c))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Apply a `books` message, the book is resynced if its checksum does not match.
+    /// Returns the book if its top changed.
+    fn apply_book(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, pair: Pair, snapshot: bool, data: &Value) -> Result<Option<Orderbook>> {
+        let asks = utils::parse_levels(&data["asks"])?;
+        let bids = utils::parse_levels(&data["bids"])?;
+        let warm_up = self.warm_up;
+        let depth = self.depth;
+        let book = self.agg.entry(pair).or_insert_with(|| LiveAggregatedOrderBook::with_warm_up(pair, warm_up).with_depth(depth));
+        if snapshot {
+            book.resync();
+            book.reset_asks(asks.into_iter());
+            book.reset_bids(bids.into_iter());
+        } else if book.is_resyncing() {
+            // Updates sent before the new snapshot
+            return Ok(None);
+        } else {
+            book.update_asks(asks.into_iter());
+            book.update_bids(bids.into_iter());
+        }
+        if let Some(checksum) = data["checksum"].as_i64() {
+            if utils::book_checksum(&book.asks_by_price, &book.bids_by_price) as i64 != checksum {
+                warn!("Okx : checksum mismatch for the order book of {:?}, resyncing", pair);
+                book.resync();
+                self.books.remove(&pair);
+                resubscribe(w, pair);
+                return Ok(None);
+            }
+        }
+        Ok(book.latest_order_book())
+    }
+
+    /// Requested channels served by the Okx channel `name` for `pair`
+    fn requested_channels(&self, name: &str, pair: Pair) -> Vec<Channel> {
+        let served: &[Channel] = match name {
+            "books" => &[Channel::LiveOrderBook, Channel::LiveDetailOrderBook, Channel::LiveFullOrderBook],
+            "trades" => &[Channel::LiveTrades],
+            _ => &[],
+        };
+        served.iter().filter(|c| self.channels.get(c).map_or(false, |pairs| pairs.contains(&pair))).cloned().collect()
+    }
+
+    /// Subscriptions are acknowledged one by one, errors only name the instrument in their message
+    fn subscription_event(&mut self, v: &Value) {
+        let mut results = vec![];
+        if v["event"] == "subscribe" {
+            if let (Some(name), Some(pair)) = (v["arg"]["channel"].as_str(), v["arg"]["instId"].as_str().and_then(utils::get_pair_enum)) {
+                results.extend(self.requested_channels(name, *pair).into_iter().map(|c| SubscriptionResult::accepted(c, *pair)));
+            }
+        } else if v["event"] == "error" {
+            let reason = format!("{} {}", v["code"].as_str().unwrap_or_default(), v["msg"].as_str().unwrap_or_default());
+            warn!("Okx : {}", reason);
+            for (channel, pairs) in &self.channels {
+                for pair in pairs.iter().filter(|p| utils::get_pair_string(p).map_or(false, |inst| reason.contains(*inst))) {
+                    results.push(SubscriptionResult::rejected(channel.clone(), *pair, &reason));
+                }
+            }
+        }
+        for result in results {
+            self.send(LiveEvent::SubscriptionResult(result));
+        }
+    }
+
+    fn send(&mut self, le: LiveEvent) {
+        metrics::ws_event(Exchange::Okx, &le);
+        if self.delivery.is_empty() {
+            debug!("{:?}", le);
+        } else {
+            self.delivery.send(LiveEventEnveloppe(Exchange::Okx, le));
+        }
+    }
+}
+
+fn subscription(op: &str, args: Vec<Value>) -> String {
+    serde_json::json!({"op": op, "args": args}).to_string()
+}
+
+fn book_arg(pair: Pair) -> Value {
+    serde_json::json!({"channel": "books", "instId": utils::get_pair_string(&pair).unwrap()})
+}
+
+/// Subscribing again to the `books` channel of an instrument sends a new snapshot
+fn resubscribe(w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, pair: Pair) {
+    w.write(Message::Text(subscription("unsubscribe", vec![book_arg(pair)])));
+    w.write(Message::Text(subscription("subscribe", vec![book_arg(pair)])));
+}
+
+fn parse_trade(pair: Pair, trade: &Value) -> Result<LiveTrade> {
+    Ok(LiveTrade {
+        event_ms: trade["ts"].as_str().and_then(|ts| ts.parse().ok()).unwrap_or_else(helpers::get_unix_timestamp_ms),
+        pair,
+        amount: helpers::from_json_bigdecimal(&trade["sz"], "sz")?,
+        price: helpers::from_json_bigdecimal(&trade["px"], "px")?,
+        tt: TradeType::parse(trade["side"].as_str().unwrap_or_default()),
+        meta: trade["tradeId"].as_str().map(TradeMeta::with_id).unwrap_or_default(),
+    })
+}
+
+#[async_trait]
+impl WsHandler for OkxStreamingApi {
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_in(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>, msg: Bytes) {
+        // Keep-alive replies are not json
+        let v: Value = match serde_json::from_slice(msg.bytes()) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        if v["event"].is_string() {
+            self.subscription_event(&v);
+            return;
+        }
+        let pair = match v["arg"]["instId"].as_str().and_then(utils::get_pair_enum) {
+            Some(pair) => *pair,
+            None => return,
+        };
+        let data = match v["data"].as_array() {
+            Some(data) => data,
+            None => return,
+        };
+        match v["arg"]["channel"].as_str() {
+            Some("books") => {
+                let snapshot = v["action"] == "snapshot";
+                for book in data {
+                    match self.apply_book(w, pair, snapshot, book) {
+                        Ok(Some(ob)) => {
+                            self.books.insert(pair, ob.clone());
+                            self.send(LiveEvent::LiveOrderbook(ob));
+                        }
+                        Ok(None) => (),
+                        Err(e) => warn!("Okx : cannot parse the order book of {:?} : {}", pair, e),
+                    }
+                }
+            }
+            Some("trades") => {
+                let mut trades = vec![];
+                for trade in data {
+                    match parse_trade(pair, trade) {
+                        Ok(t) => trades.push(t),
+                        Err(e) => warn!("Okx : cannot parse a trade of {:?} : {}", pair, e),
+                    }
+                }
+                for t in self.tape.accept_all(trades) {
+                    self.last_trades.insert(pair, t.clone());
+                    self.send(LiveEvent::LiveTrade(t));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn snapshot(&self, pair: Pair) -> Option<Snapshot> {
+        let orderbook = self.books.get(&pair).cloned();
+        let last_trade = self.last_trades.get(&pair).cloned();
+        if orderbook.is_none() && last_trade.is_none() {
+            return None;
+        }
+        Some(Snapshot { orderbook, last_trade })
+    }
+
+    /// Books are seeded again by the snapshot sent after subscribing
+    fn handle_reconnect(&mut self) {
+        for book in self.agg.values_mut() {
+            book.resync();
+        }
+        self.books.clear();
+        self.tape.mark_gap();
+    }
+
+    fn flush_deliveries(&mut self) {
+        self.delivery.flush();
+    }
+
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn handle_started(&mut self, w: &mut SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>) {
+        let mut args: Vec<Value> = self.book_pairs().into_iter().map(book_arg).collect();
+        for pair in self.channels.get(&Channel::LiveTrades).into_iter().flatten() {
+            args.push(serde_json::json!({"channel": "trades", "instId": utils::get_pair_string(pair).unwrap()}));
+        }
+        if !args.is_empty() {
+            w.write(Message::Text(subscription("subscribe", args)));
+        }
+    }
+}
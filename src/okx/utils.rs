@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use bidir_map::BidirMap;
+use bigdecimal::BigDecimal;
+use data_encoding::BASE64;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use serde_json::Value;
+use serde_json::value::Map;
+
+use crate::error::*;
+use crate::exchange::Environment;
+use crate::helpers;
+use crate::types::{Orderbook, Pair, Price, Ticker, Volume};
+use crate::types::Pair::*;
+
+pub const API_URL: &str = "https://www.okx.com";
+pub const WEBSOCKET_URL: &str = "wss://ws.okx.com:8443/ws/v5/public";
+pub const DEMO_WEBSOCKET_URL: &str = "wss://wspap.okx.com:8443/ws/v5/public?brokerId=9999";
+
+/// Levels of each side covered by the book checksums
+pub const CHECKSUM_LEVELS: usize = 25;
+
+lazy_static! {
+    static ref PAIRS_STRING: BidirMap<Pair, &'static str> = {
+        let mut m = BidirMap::new();
+        m.insert(BTC_USDT, "BTC-USDT");
+        m.insert(ETH_USDT, "ETH-USDT");
+        m.insert(LTC_USDT, "LTC-USDT");
+        m.insert(XRP_USDT, "XRP-USDT");
+        m.insert(BCH_USDT, "BCH-USDT");
+        m.insert(EOS_USDT, "EOS-USDT");
+        m.insert(ETC_USDT, "ETC-USDT");
+        m.insert(ETH_BTC, "ETH-BTC");
+        m.insert(LTC_BTC, "LTC-BTC");
+        m.insert(XRP_BTC, "XRP-BTC");
+        m.insert(BTC_EUR, "BTC-EUR");
+        m.insert(ETH_EUR, "ETH-EUR");
+        m
+    };
+}
+
+/// Return the instrument id of the pair, e.g. `BTC-USDT`
+/// If the Pair is not supported, None is returned.
+pub fn get_pair_string(pair: &Pair) -> Option<&&str> {
+    PAIRS_STRING.get_by_first(pair)
+}
+
+/// Return the Pair enum associated to an instrument id
+/// If the Pair is not supported, None is returned.
+pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
+    PAIRS_STRING.get_by_second(&pair)
+}
+
+/// Every pair with an Okx name
+pub fn supported_pairs() -> Vec<Pair> {
+    PAIRS_STRING.iter().map(|(pair, _)| *pair).collect()
+}
+
+/// Public websocket of an environment, demo trading has its own
+pub fn websocket_url(environment: Environment) -> &'static str {
+    match environment {
+        Environment::Production => WEBSOCKET_URL,
+        Environment::Sandbox => DEMO_WEBSOCKET_URL,
+    }
+}
+
+/// OK-ACCESS-SIGN header : the base64 encoded HMAC-SHA256 of the ISO 8601 timestamp, the HTTP
+/// method, the request path (with its query string) and the body
+pub fn build_signature(timestamp: &str, method: &str, request_path: &str, body: &str, api_secret: &str) -> Result<String> {
+    let message = format!("{}{}{}{}", timestamp, method, request_path, body);
+    let mut mac = Hmac::<Sha256>::new(api_secret.as_bytes());
+    mac.input(message.as_bytes());
+    Ok(BASE64.encode(mac.result().code()))
+}
+
+/// Map the error codes of Okx to typed errors, see https://www.okx.com/docs-v5/en/#error-code
+pub fn parse_error(code: &str, msg: &str) -> Error {
+    match code {
+        "50011" | "50061" => Error::RateLimited(None),
+        "50102" | "50112" => Error::InvalidNonce,
+        "50103" | "50104" | "50105" | "50111" | "50113" | "50114" => Error::BadCredentials,
+        "50120" | "50121" => Error::PermissionDenied,
+        "51001" => Error::PairUnsupported,
+        "51008" | "51131" => Error::InsufficientFunds,
+        "51020" => Error::InsufficientOrderSize,
+        "51400" | "51603" => Error::OrderNotFound,
+        _ => Error::ExchangeSpecificError(format!("{} : {}", code, msg)),
+    }
+}
+
+/// The `data` of a response, `{"code": "0", "msg": "", "data": [...]}`
+pub fn parse_result(response: &Map<String, Value>) -> Result<Vec<Value>> {
+    let code = response.get("code").and_then(|c| c.as_str()).unwrap_or("");
+    if code != "0" {
+        let msg = response.get("msg").and_then(|m| m.as_str()).unwrap_or("");
+        return Err(parse_error(code, msg));
+    }
+    match response.get("data") {
+        Some(Value::Array(data)) => Ok(data.clone()),
+        _ => Err(Error::MissingField("data".to_string())),
+    }
+}
+
+/// Refused orders and cancellations come with a generic code, the reason is in the `sCode` of
+/// the order
+pub fn check_order_code(response: &Map<String, Value>) -> Result<()> {
+    if let Some(order) = response.get("data").map(|data| &data[0]) {
+        match order["sCode"].as_str() {
+            Some("0") | None => {}
+            Some(code) => return Err(parse_error(code, order["sMsg"].as_str().unwrap_or_default())),
+        }
+    }
+    Ok(())
+}
+
+/// The first item of the `data` of a response
+pub fn parse_first(response: &Map<String, Value>) -> Result<Value> {
+    parse_result(response)?.into_iter().next().ok_or_else(|| Error::MissingField("data".to_string()))
+}
+
+/// Levels are `[price, size, "0", orders]`
+pub fn parse_levels(levels: &Value) -> Result<Vec<(Price, Volume)>> {
+    let levels = levels.as_array().ok_or_else(|| Error::InvalidFieldFormat(levels.to_string()))?;
+    levels.iter().map(|level| {
+        let price = level[0].as_str().ok_or_else(|| Error::InvalidFieldFormat(level.to_string()))?;
+        let size = level[1].as_str().ok_or_else(|| Error::InvalidFieldFormat(level.to_string()))?;
+        Ok((BigDecimal::from_str(price)?, BigDecimal::from_str(size)?))
+    }).collect()
+}
+
+pub fn parse_orderbook(pair: Pair, book: &Value) -> Result<Orderbook> {
+    Ok(Orderbook {
+        timestamp: book["ts"].as_str().and_then(|ts| ts.parse().ok()).unwrap_or_else(helpers::get_unix_timestamp_ms),
+        pair,
+        asks: parse_levels(&book["asks"])?,
+        bids: parse_levels(&book["bids"])?,
+    })
+}
+
+pub fn parse_ticker(pair: Pair, ticker: &Value, keep_raw: bool) -> Result<Ticker> {
+    let last_trade_price = helpers::from_json_bigdecimal(&ticker["last"], "last")?;
+    let open_24h = helpers::from_json_bigdecimal_opt(&ticker["open24h"]);
+    Ok(Ticker {
+        timestamp: ticker["ts"].as_str().and_then(|ts| ts.parse().ok()).unwrap_or_else(helpers::get_unix_timestamp_ms),
+        pair,
+        percent_change: open_24h.as_ref().and_then(|open| helpers::percent_change(open, &last_trade_price)),
+        last_trade_price,
+        lowest_ask: helpers::from_json_bigdecimal(&ticker["askPx"], "askPx")?,
+        highest_bid: helpers::from_json_bigdecimal(&ticker["bidPx"], "bidPx")?,
+        volume: helpers::from_json_bigdecimal_opt(&ticker["vol24h"]),
+        bid_size: helpers::from_json_bigdecimal_opt(&ticker["bidSz"]),
+        ask_size: helpers::from_json_bigdecimal_opt(&ticker["askSz"]),
+        open_24h,
+        high_24h: helpers::from_json_bigdecimal_opt(&ticker["high24h"]),
+        low_24h: helpers::from_json_bigdecimal_opt(&ticker["low24h"]),
+        raw: helpers::raw_payload(keep_raw, ticker),
+    })
+}
+
+/// CRC-32 (IEEE) of `bytes`, as used by the book checksums
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Checksum of the `CHECKSUM_LEVELS` best levels of a book : the CRC-32 of the bid and ask
+/// `price:size` pairs, alternating from the best ones and joined with `:`, as a signed integer
+pub fn book_checksum(asks: &BTreeMap<Price, Volume>, bids: &BTreeMap<Price, Volume>) -> i32 {
+    let mut asks = asks.iter().take(CHECKSUM_LEVELS);
+    let mut bids = bids.iter().rev().take(CHECKSUM_LEVELS);
+    let mut fields: Vec<String> = Vec::with_capacity(4 * CHECKSUM_LEVELS);
+    for _ in 0..CHECKSUM_LEVELS {
+        if let Some((price, size)) = bids.next() {
+            fields.push(price.to_string());
+            fields.push(size.to_string());
+        }
+        if let Some((price, size)) = asks.next() {
+            fields.push(price.to_string());
+            fields.push(size.to_string());
+        }
+    }
+    crc32(fields.join(":").as_bytes()) as i32
+}
+
+#[cfg(test)]
+mod okx_utils_tests {
+    use super::*;
+
+    #[test]
+    fn checksums_cover_both_sides() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let level = |p: &str, v: &str| (BigDecimal::from_str(p).unwrap(), BigDecimal::from_str(v).unwrap());
+        let asks: BTreeMap<Price, Volume> = vec![level("3366.8", "9")].into_iter().collect();
+        let bids: BTreeMap<Price, Volume> = vec![level("3366.1", "7")].into_iter().collect();
+        assert_eq!(book_checksum(&asks, &bids), crc32(b"3366.1:7:3366.8:9") as i32);
+        assert!(parse_result(serde_json::json!({"code": "51008", "msg": "", "data": []}).as_object().unwrap()).is_err());
+    }
+}
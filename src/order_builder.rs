@@ -0,0 +1,139 @@
+//! Build orders that respect the precision and minimums of a market.
+//! Exchanges reject prices that are not a multiple of the tick size, amounts that are not a
+//! multiple of the lot size, and orders that are too small. `OrderBuilder` rounds the price and
+//! the amount in the safe direction and checks the minimums before anything is sent.
+
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::error::*;
+use crate::exchange::ExchangeApi;
+use crate::types::*;
+
+/// Trading rules of a market, missing rules are not enforced
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MarketInfo {
+    /// Prices must be a multiple of the tick size
+    pub tick_size: Option<Price>,
+    /// Amounts must be a multiple of the lot size
+    pub lot_size: Option<Volume>,
+    /// Smallest amount accepted
+    pub min_amount: Option<Volume>,
+    /// Smallest value (amount * price) accepted, in quote currency
+    pub min_notional: Option<Price>,
+}
+
+/// An order checked against its `MarketInfo`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckedOrder {
+    pub order_type: OrderType,
+    pub pair: Pair,
+    pub quantity: Volume,
+    pub price: Option<Price>,
+}
+
+impl CheckedOrder {
+    pub async fn send(self, api: &dyn ExchangeApi) -> Result<OrderInfo> {
+        api.add_order(self.order_type, self.pair, self.quantity, self.price).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderBuilder<'a> {
+    market: &'a MarketInfo,
+    order_type: OrderType,
+    pair: Pair,
+    quantity: Volume,
+    price: Option<Price>,
+    reference_price: Option<Price>,
+}
+
+impl<'a> OrderBuilder<'a> {
+    pub fn new(market: &'a MarketInfo, order_type: OrderType, pair: Pair, quantity: Volume) -> Self {
+        OrderBuilder { market, order_type, pair, quantity, price: None, reference_price: None }
+    }
+
+    /// Limit price, required for limit orders
+    pub fn with_price(mut self, price: Price) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    /// Expected execution price of a market order, used to check the minimum notional
+    pub fn with_reference_price(mut self, price: Price) -> Self {
+        self.reference_price = Some(price);
+        self
+    }
+
+    /// Round the amount down to the lot size and the price to the tick size, down for buys and up
+    /// for sells, then check the minimums. Orders too small are rejected with
+    /// `Error::InsufficientOrderSize`.
+    pub fn build(self) -> Result<CheckedOrder> {
+        let is_limit = match self.order_type {
+            OrderType::BuyLimit | OrderType::SellLimit => true,
+            OrderType::BuyMarket | OrderType::SellMarket => false,
+            _ => return Err(Error::OrderTypeUnsupported),
+        };
+        let price = match (is_limit, self.price) {
+            (true, None) => return Err(Error::MissingPrice),
+            (true, Some(price)) => Some(match &self.market.tick_size {
+                Some(tick) => round_to_step(&price, tick, self.order_type.side() == Side::Sell),
+                None => price,
+            }),
+            (false, _) => None,
+        };
+        if price.as_ref().map_or(false, |p| p <= &BigDecimal::zero()) {
+            return Err(Error::InvalidArguments);
+        }
+        let quantity = match &self.market.lot_size {
+            Some(lot) => round_to_step(&self.quantity, lot, false),
+            None => self.quantity,
+        };
+        if quantity <= BigDecimal::zero() || self.market.min_amount.as_ref().map_or(false, |min| &quantity < min) {
+            return Err(Error::InsufficientOrderSize);
+        }
+        if let (Some(min), Some(p)) = (&self.market.min_notional, price.as_ref().or_else(|| self.reference_price.as_ref())) {
+            if &(quantity.clone() * p.clone()) < min {
+                return Err(Error::InsufficientOrderSize);
+            }
+        }
+        Ok(CheckedOrder { order_type: self.order_type, pair: self.pair, quantity, price })
+    }
+}
+
+/// Round `value` to a multiple of `step`, down or up
+pub fn round_to_step(value: &BigDecimal, step: &BigDecimal, up: bool) -> BigDecimal {
+    if step <= &BigDecimal::zero() {
+        return value.clone();
+    }
+    let down = (value.clone() / step.clone()).with_scale(0) * step.clone();
+    if up && &down < value {
+        down + step.clone()
+    } else {
+        down
+    }
+}
+
+#[cfg(test)]
+mod order_builder_tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn round_and_check_minimums() {
+        let market = MarketInfo { tick_size: Some(dec("0.01")), lot_size: Some(dec("0.001")), min_amount: None, min_notional: Some(dec("10")) };
+
+        let order = OrderBuilder::new(&market, OrderType::BuyLimit, Pair::BTC_USD, dec("0.12345")).with_price(dec("100.129")).build().unwrap();
+        assert_eq!(order.quantity, dec("0.123"));
+        assert_eq!(order.price, Some(dec("100.12")));
+        let order = OrderBuilder::new(&market, OrderType::SellLimit, Pair::BTC_USD, dec("0.5")).with_price(dec("100.121")).build().unwrap();
+        assert_eq!(order.price, Some(dec("100.13")));
+
+        assert!(OrderBuilder::new(&market, OrderType::BuyLimit, Pair::BTC_USD, dec("0.05")).with_price(dec("100")).build().is_err());
+        assert!(OrderBuilder::new(&market, OrderType::SellMarket, Pair::BTC_USD, dec("0.0009")).build().is_err());
+        assert!(OrderBuilder::new(&market, OrderType::SellMarket, Pair::BTC_USD, dec("0.05")).with_reference_price(dec("100")).build().is_err());
+    }
+}
@@ -4,9 +4,8 @@
 use hmac::{Hmac, Mac};
 use sha2::Sha512;
 
-use hyper::{Client, Uri, Request, Body, Method};
+use hyper::{Uri, Request, Body, Method};
 use hyper::header::{CONTENT_TYPE};
-use hyper_tls::HttpsConnector;
 
 use data_encoding::HEXLOWER;
 
@@ -14,18 +13,22 @@ use serde_json::Value;
 use serde_json::value::Map;
 
 use std::collections::HashMap;
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::AtomicI64;
+use std::time::Instant;
 
 use futures::{TryFutureExt};
 
 use crate::error::*;
+use crate::helpers::failover::Endpoints;
+use crate::helpers::secret::SecretString;
 use crate::helpers::{self, json};
+use crate::metrics;
+use crate::helpers::json::Projection;
 
 use crate::exchange::Exchange;
 use crate::coinnect::Credentials;
 
-use hyper::client::HttpConnector;
+use crate::helpers::proxy::HttpClient;
 use bytes::buf::BufExt as _;
 
 const KEY_HEADER: &str = "Key";
@@ -61,35 +64,48 @@ impl MoveOrderOption {
     }
 }
 
+const API_URL: &str = "https://poloniex.com";
+
 #[derive(Debug)]
 pub struct PoloniexApi {
-    last_request: i64, // unix timestamp in ms, to avoid ban
-    api_key: String,
-    api_secret: String,
-    http_client: Client<HttpsConnector<HttpConnector>>,
+    last_request: AtomicI64, // unix timestamp in ms, to avoid ban
+    api_key: SecretString,
+    api_secret: SecretString,
+    http_client: HttpClient,
     burst: bool,
+    pub(crate) keep_raw: bool,
+    endpoints: Endpoints,
 }
 
 impl PoloniexApi {
     /// Create a new PoloniexApi by providing an API key & API secret
     pub fn new<C: Credentials>(creds: C) -> Result<PoloniexApi> {
         if creds.exchange() != Exchange::Poloniex {
-            return Err(ErrorKind::InvalidConfigType(Exchange::Poloniex, creds.exchange()).into());
+            return Err(Error::InvalidConfigType(Exchange::Poloniex, creds.exchange()));
         }
+        creds.environment()?.production_only(Exchange::Poloniex)?;
 
-        let connector = HttpsConnector::new();
-        let ssl = Client::builder().build::<_, hyper::Body>(connector);
+        let connection = creds.connection();
+        let ssl = connection.http_client()?;
+        let endpoints = connection.rest_endpoints(Exchange::Poloniex, API_URL);
 
 
         Ok(PoloniexApi {
-            last_request: 0,
-            api_key: creds.get("api_key").unwrap_or_default(),
-            api_secret: creds.get("api_secret").unwrap_or_default(),
+            last_request: AtomicI64::new(0),
+            api_key: creds.get("api_key").unwrap_or_default().into(),
+            api_secret: creds.get("api_secret").unwrap_or_default().into(),
             http_client: ssl,
             burst: false,
+            keep_raw: connection.keep_raw,
+            endpoints,
         })
     }
 
+    /// The REST endpoints, to subscribe to failover events or recover the primary endpoint
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
     /// The number of calls in a given period is limited. In order to avoid a ban we limit
     /// by default the number of api requests.
     /// This function sets or removes the limitation.
@@ -99,35 +115,44 @@ impl PoloniexApi {
         self.burst = burst
     }
 
-    fn block_or_continue(&self) {
+    /// Keep the payload of the exchange in the `raw` field of typed responses
+    pub fn set_keep_raw(&mut self, keep_raw: bool) {
+        self.keep_raw = keep_raw
+    }
+
+    async fn block_or_continue(&self) {
         if !self.burst {
-            let threshold: u64 = 167; // 6 requests/sec = 1/6*1000
-            let offset: u64 = helpers::get_unix_timestamp_ms() as u64 - self.last_request as u64;
-            if offset < threshold {
-                let wait_ms = Duration::from_millis(threshold - offset);
-                thread::sleep(wait_ms);
-            }
+            helpers::rate_limit::wait_for_slot(&self.last_request, 167).await; // 6 requests/sec = 1/6*1000
         }
     }
 
-    async fn public_query(&mut self, method: &str, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
+    async fn public_query(&self, method: &str, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
+        self.public_query_projected(method, params, None).await
+    }
+
+    async fn public_query_projected(&self, method: &str, params: &HashMap<&str, &str>, projection: Option<&Projection>) -> Result<Map<String, Value>> {
         let mut params = params.clone();
         helpers::strip_empties(&mut params);
-        let string = "https://poloniex.com/public?command=".to_string() + method + "&" + &helpers::url_encode_hashmap(&params);
-        let url: Uri = string.as_str().parse().map_err(|_e| ErrorKind::BadParse)?;
-
-        self.block_or_continue();
-        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
-        self.last_request = helpers::get_unix_timestamp_ms();
+        let string = self.endpoints.current().to_string() + "/public?command=" + method + "&" + &helpers::url_encode_hashmap(&params);
+        let url: Uri = string.as_str().parse().map_err(|_e| Error::BadParse)?;
+
+        self.block_or_continue().await;
+        let start = Instant::now();
+        let buf = self.http_client.get(url).and_then(|resp| hyper::body::aggregate(resp.into_body()).map_err(Error::from)).await;
+        metrics::rest_request(Exchange::Poloniex, method, start);
+        let buf = self.endpoints.track(buf)?;
         let reader = buf.reader();
 
-        if method == "returnChartData" {
+        if method == "returnChartData" || method == "returnTradeHistory" {
             return json::deserialize_json_array_r(reader);
         }
-        json::deserialize_json_r(reader)
+        match projection {
+            Some(projection) => json::deserialize_json_projected_r(reader, projection),
+            None => json::deserialize_json_r(reader),
+        }
     }
 
-    async fn private_query(&mut self, method: &str, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
+    async fn private_query(&self, method: &str, params: &HashMap<&str, &str>) -> Result<Map<String, Value>> {
         let unix_timestamp = helpers::get_unix_timestamp_us().to_string();
         let mut post_params = params.clone();
         post_params.insert("command", method);
@@ -135,24 +160,26 @@ impl PoloniexApi {
         helpers::strip_empties(&mut post_params);
         let post_data = helpers::url_encode_hashmap(&post_params);
 
-        let mut mac = Hmac::<Sha512>::new(self.api_secret.as_bytes());
+        let mut mac = Hmac::<Sha512>::new(self.api_secret.expose().as_bytes());
         mac.input(post_data.as_bytes());
 
         let sign = HEXLOWER.encode(mac.result().code());
 
-        self.block_or_continue();
+        self.block_or_continue().await;
 
         let req: Result<Request<Body>> = Request::builder()
             .method(Method::POST)
-            .uri("https://poloniex.com/tradingApi")
-            .header(KEY_HEADER, self.api_key.as_str())
+            .uri(format!("{}/tradingApi", self.endpoints.current()))
+            .header(KEY_HEADER, self.api_key.expose())
             .header(SIGN_HEADER, sign)
             .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
             .body(post_data.into())
-            .map_err(|e| ErrorKind::ServiceUnavailable(e.to_string()).into());
+            .map_err(|e| Error::ServiceUnavailable(e.to_string()));
         let req2 = req.unwrap();
-        let buf = self.http_client.request(req2).and_then(|resp| hyper::body::aggregate(resp.into_body())).await?;
-        self.last_request = helpers::get_unix_timestamp_ms();
+        let start = Instant::now();
+        let buf = self.http_client.request(req2).and_then(|resp| hyper::body::aggregate(resp.into_body()).map_err(Error::from)).await;
+        metrics::rest_request(Exchange::Poloniex, method, start);
+        let buf = self.endpoints.track(buf)?;
         let reader = buf.reader();
         if method == "returnChartData" {
             return json::deserialize_json_array_r(reader);
@@ -172,11 +199,22 @@ impl PoloniexApi {
     /// "percentChange":"0.16701570","baseVolume":"0.45347489","quoteVolume":"9094"},
     /// ... }
     /// ```
-    pub async fn return_ticker(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_ticker(&self) -> Result<Map<String, Value>> {
         let params = HashMap::new();
         self.public_query("returnTicker", &params).await
     }
 
+    /// Same as `return_ticker`, keeping only the markets and fields of `projection`.
+    /// The `error` key is always kept.
+    pub async fn return_ticker_projected(&self, projection: &Projection) -> Result<Map<String, Value>> {
+        let mut projection = projection.clone();
+        if let Some(keys) = projection.keys.as_mut() {
+            keys.insert("error".to_string());
+        }
+        let params = HashMap::new();
+        self.public_query_projected("returnTicker", &params, Some(&projection)).await
+    }
+
     /// Sample output :
     ///
     /// ```json
@@ -184,7 +222,7 @@ impl PoloniexApi {
     /// "NXT":"14145"},
     /// ... "totalBTC":"81.89657704","totalLTC":"78.52083806"}
     /// ```
-    pub async fn return_24_volume(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_24_volume(&self) -> Result<Map<String, Value>> {
         let params = HashMap::new();
         self.public_query("return24Volume", &params).await
     }
@@ -195,7 +233,7 @@ impl PoloniexApi {
     /// {"asks":[[0.00007600,1164],[0.00007620,1300], ... ], "bids":[[0.00006901,200],
     /// [0.00006900,408], ... ], "isFrozen": 0, "seq": 18849}
     /// ```
-    pub async fn return_order_book(&mut self, currency_pair: &str, depth: &str) -> Result<Map<String, Value>> {
+    pub async fn return_order_book(&self, currency_pair: &str, depth: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currencyPair", currency_pair);
         params.insert("depth", depth);
@@ -210,7 +248,7 @@ impl PoloniexApi {
     /// {"date":"2014-02-10 01:19:37","type":"buy","rate":"0.00007600","amount":"655",
     /// "total":"0.04978"}, ... ]
     /// ```
-    pub async fn return_trade_history(&mut self, currency_pair: &str, start: &str, end: &str) -> Result<Map<String, Value>> {
+    pub async fn return_trade_history(&self, currency_pair: &str, start: &str, end: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currencyPair", currency_pair);
         params.insert("start", start);
@@ -224,7 +262,7 @@ impl PoloniexApi {
     /// {"data": {"date":1405699200,"high":0.0045388,"low":0.00403001,"open":0.00404545,"close":0.00427592,
     /// "volume":44.11655644,"quoteVolume":10259.29079097,"weightedAverage":0.00430015}, ...}
     /// ```
-    pub async fn return_chart_data(&mut self, currency_pair: &str, start: &str, end: &str, period: &str) -> Result<Map<String, Value>> {
+    pub async fn return_chart_data(&self, currency_pair: &str, start: &str, end: &str, period: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currencyPair", currency_pair);
         params.insert("start", start);
@@ -239,7 +277,7 @@ impl PoloniexApi {
     /// {"1CR":{"maxDailyWithdrawal":10000,"txFee":0.01,"minConf":3,"disabled":0},
     /// "ABY":{"maxDailyWithdrawal":10000000,"txFee":0.01,"minConf":8,"disabled":0}, ... }
     /// ```
-    pub async fn return_currencies(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_currencies(&self) -> Result<Map<String, Value>> {
         let params = HashMap::new();
         self.public_query("returnCurrencies", &params).await
     }
@@ -250,7 +288,7 @@ impl PoloniexApi {
     /// {"offers":[{"rate":"0.00200000","amount":"64.66305732","rangeMin":2,"rangeMax":8}, ... ],
     /// "demands":[{"rate":"0.00170000","amount":"26.54848841","rangeMin":2,"rangeMax":2}, ... ]}
     /// ```
-    pub async fn return_loan_orders(&mut self, currency: &str) -> Result<Map<String, Value>> {
+    pub async fn return_loan_orders(&self, currency: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currency", currency);
         self.public_query("returnLoanOrders", &params).await
@@ -263,7 +301,7 @@ impl PoloniexApi {
     /// ```json
     /// {"BTC":"0.59098578","LTC":"3.31117268", ... }
     /// ```
-    pub async fn return_balances(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_balances(&self) -> Result<Map<String, Value>> {
         let params = HashMap::new();
         self.private_query("returnBalances", &params).await
     }
@@ -278,7 +316,7 @@ impl PoloniexApi {
     /// ```json
     /// {"LTC":{"available":"5.015","onOrders":"1.0025","btcValue":"0.078"},"NXT":{...}, ... }
     /// ```
-    pub async fn return_complete_balances(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_complete_balances(&self) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("account", "all");
         self.private_query("returnCompleteBalances", &params).await
@@ -292,7 +330,7 @@ impl PoloniexApi {
     /// {"BTC":"19YqztHmspv2egyD6jQM3yn81x5t5krVdJ","LTC":"LPgf9kjv9H1Vuh4XSaKhzBe8JHdou1WgUB",
     /// ... "ITC":"Press Generate.." ... }
     /// ```
-    pub async fn return_deposit_addresses(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_deposit_addresses(&self) -> Result<Map<String, Value>> {
         let params = HashMap::new();
         self.private_query("returnDepositAddresses", &params).await
     }
@@ -304,7 +342,7 @@ impl PoloniexApi {
     /// ```json
     /// {"success":1,"response":"CKXbbs8FAVbtEa397gJHSutmrdrBrhUMxe"}
     /// ```
-    pub async fn generate_new_address(&mut self, currency: &str) -> Result<Map<String, Value>> {
+    pub async fn generate_new_address(&self, currency: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currency", currency);
         self.private_query("generateNewAddress", &params).await
@@ -330,7 +368,7 @@ impl PoloniexApi {
     /// "status":"COMPLETE: 36e483efa6aff9fd53a235177579d98451c4eb237c210e66cd2b9a2d4a988f8e",
     /// "ipAddress":"..."}]}
     /// ```
-    pub async fn return_deposits_withdrawals(&mut self, start: &str, end: &str) -> Result<Map<String, Value>> {
+    pub async fn return_deposits_withdrawals(&self, start: &str, end: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("start", start);
         params.insert("end", end);
@@ -353,7 +391,7 @@ impl PoloniexApi {
     /// "amount":"100","total":"2.5"},
     /// {"orderNumber":"120467","type":"sell","rate":"0.04","amount":"100","total":"4"}], ... }
     /// ```
-    pub async fn return_open_orders(&mut self, currency_pair: &str) -> Result<Map<String, Value>> {
+    pub async fn return_open_orders(&self, currency_pair: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currencyPair", currency_pair);
         self.private_query("returnOpenOrders", &params).await
@@ -390,7 +428,7 @@ impl PoloniexApi {
     /// "orderNumber": "12603319116", "type": "sell", "category": "marginTrade" }, ... ],
     /// "BTC_LTC":[ ... ] ... }
     /// ```
-    pub async fn return_private_trade_history(&mut self, currency_pair: &str, start: &str, end: &str) -> Result<Map<String, Value>> {
+    pub async fn return_private_trade_history(&self, currency_pair: &str, start: &str, end: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currencyPair", currency_pair);
         params.insert("start", start);
@@ -409,7 +447,7 @@ impl PoloniexApi {
     /// "rate": "0.00018500", "amount": "455.34206390", "total": "0.08423828", "fee": "0.00200000",
     /// "date": "2016-03-14 01:04:36"}, ...]
     /// ```
-    pub async fn return_order_trades(&mut self, order_number: &str) -> Result<Map<String, Value>> {
+    pub async fn return_order_trades(&self, order_number: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("orderNumber", order_number);
         self.private_query("returnOrderTrades", &params).await
@@ -426,7 +464,7 @@ impl PoloniexApi {
     /// "date":"2014-10-18 23:03:21", "rate":"0.00000173","total":"0.00058625","tradeID":"16164",
     /// "type":"buy"}]}
     /// ```
-    pub async fn buy<O>(&mut self, currency_pair: &str, rate: &str, amount: &str, option: O) -> Result<Map<String, Value>>
+    pub async fn buy<O>(&self, currency_pair: &str, rate: &str, amount: &str, option: O) -> Result<Map<String, Value>>
     where
         O: Into<Option<PlaceOrderOption>>,
     {
@@ -440,7 +478,7 @@ impl PoloniexApi {
 
     /// Places a sell order in a given market. Parameters and output are the same as for the buy
     /// method.
-    pub async fn sell<O>(&mut self, currency_pair: &str, rate: &str, amount: &str, option: O) -> Result<Map<String, Value>>
+    pub async fn sell<O>(&self, currency_pair: &str, rate: &str, amount: &str, option: O) -> Result<Map<String, Value>>
     where
         O: Into<Option<PlaceOrderOption>>,
     {
@@ -455,7 +493,7 @@ impl PoloniexApi {
     /// Cancels an order you have placed in a given market.
     /// Required POST parameter is "orderNumber". If successful, the method will return:
     /// {"success":1}
-    pub async fn cancel_order(&mut self, order_number: &str) -> Result<Map<String, Value>> {
+    pub async fn cancel_order(&self, order_number: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("orderNumber", order_number);
         self.private_query("cancelOrder", &params).await
@@ -473,7 +511,7 @@ impl PoloniexApi {
     /// ```json
     /// {"success":1,"orderNumber":"239574176","resultingTrades":{"BTC_BTS":[]}}
     /// ```
-    pub async fn move_order<O>(&mut self, order_number: &str, rate: &str, option: O) -> Result<Map<String, Value>>
+    pub async fn move_order<O>(&self, order_number: &str, rate: &str, option: O) -> Result<Map<String, Value>>
     where
         O: Into<Option<MoveOrderOption>>,
     {
@@ -495,7 +533,7 @@ impl PoloniexApi {
     /// ```json
     /// {"response":"Withdrew 2398 NXT."}
     /// ```
-    pub async fn withdraw(&mut self, currency: &str, amount: &str, address: &str) -> Result<Map<String, Value>> {
+    pub async fn withdraw(&self, currency: &str, amount: &str, address: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currency", currency);
         params.insert("amount", amount);
@@ -513,7 +551,7 @@ impl PoloniexApi {
     /// {"makerFee": "0.00140000", "takerFee": "0.00240000", "thirtyDayVolume": "612.00248891",
     /// "nextTier": "1200.00000000"}
     /// ```
-    pub async fn return_free_info(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_free_info(&self) -> Result<Map<String, Value>> {
         let params = HashMap::new();
         self.private_query("returnFeeInfo", &params).await
     }
@@ -531,7 +569,7 @@ impl PoloniexApi {
     /// "margin":{"BTC":"3.90015637", "DASH":"250.00238240","XMR":"497.12028113"},
     /// "lending":{"DASH":"0.01174765","LTC":"11.99936230"}}
     /// ```
-    pub async fn return_available_account_balances(&mut self, account: &str) -> Result<Map<String, Value>> {
+    pub async fn return_available_account_balances(&self, account: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("account", account);
         self.private_query("returnAvailableAccountBalances", &params).await
@@ -547,7 +585,7 @@ impl PoloniexApi {
     /// {"BTC_DASH":{"BTC":"8.50274777","DASH":"654.05752077"},"BTC_LTC":{"BTC":"8.50274777",
     /// "LTC":"1214.67825290"},"BTC_XMR":{"BTC":"8.50274777","XMR":"3696.84685650"}}
     /// ```
-    pub async fn return_tradable_balances(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_tradable_balances(&self) -> Result<Map<String, Value>> {
         let params = HashMap::new();
         self.private_query("returnTradableBalances", &params).await
     }
@@ -561,7 +599,7 @@ impl PoloniexApi {
     /// ```json
     /// {"success":1,"message":"Transferred 2 BTC from exchange to margin account."}
     /// ```
-    pub async fn transfer_balance(&mut self, currency: &str, amount: &str, from_account: &str, to_account: &str) -> Result<Map<String, Value>> {
+    pub async fn transfer_balance(&self, currency: &str, amount: &str, from_account: &str, to_account: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currency", currency);
         params.insert("amount", amount);
@@ -579,7 +617,7 @@ impl PoloniexApi {
     /// {"totalValue": "0.00346561","pl": "-0.00001220","lendingFees": "0.00000000",
     /// "netValue": "0.00345341","totalBorrowedValue": "0.00123220","currentMargin": "2.80263755"}
     /// ```
-    pub async fn return_margin_account_summary(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_margin_account_summary(&self) -> Result<Map<String, Value>> {
         let params = HashMap::new();
         self.private_query("returnMarginAccountSummary", &params).await
     }
@@ -596,7 +634,7 @@ impl PoloniexApi {
     /// "resultingTrades":{"BTC_DASH":[{"amount":"1.00000000","date":"2015-05-10 22:47:05",
     /// "rate":"0.01383692","total":"0.01383692","tradeID":"1213556","type":"buy"}]}}
     /// ```
-    pub async fn margin_buy(&mut self, currency_pair: &str, rate: &str, amount: &str, lending_rate: &str) -> Result<Map<String, Value>> {
+    pub async fn margin_buy(&self, currency_pair: &str, rate: &str, amount: &str, lending_rate: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currencyPair", currency_pair);
         params.insert("rate", rate);
@@ -617,7 +655,7 @@ impl PoloniexApi {
     /// "resultingTrades":{"BTC_DASH":[{"amount":"1.00000000","date":"2015-05-10 22:47:05",
     /// "rate":"0.01383692","total":"0.01383692","tradeID":"1213556","type":"sell"}]}}
     /// ```
-    pub async fn margin_sell(&mut self, currency_pair: &str, rate: &str, amount: &str, lending_rate: &str) -> Result<Map<String, Value>> {
+    pub async fn margin_sell(&self, currency_pair: &str, rate: &str, amount: &str, lending_rate: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currencyPair", currency_pair);
         params.insert("rate", rate);
@@ -639,7 +677,7 @@ impl PoloniexApi {
     /// {"amount":"40.94717831","total":"-0.09671314","basePrice":"0.00236190",
     /// "liquidationPrice":-1,"pl":"-0.00058655", "lendingFees":"-0.00000038","type":"long"}
     /// ```
-    pub async fn get_margin_position(&mut self, currency_pair: &str) -> Result<Map<String, Value>> {
+    pub async fn get_margin_position(&self, currency_pair: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currencyPair", currency_pair);
         self.private_query("getMarginPosition", &params).await
@@ -658,7 +696,7 @@ impl PoloniexApi {
     /// {"amount":"24.00289920","date":"2015-05-10 22:38:49","rate":"0.00235321",
     /// "total":"0.05648386","tradeID":"1213347","type":"sell"}]}}
     /// ```
-    pub async fn close_margin_position(&mut self, currency_pair: &str) -> Result<Map<String, Value>> {
+    pub async fn close_margin_position(&self, currency_pair: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currencyPair", currency_pair);
         self.private_query("closeMarginPosition", &params).await
@@ -672,7 +710,7 @@ impl PoloniexApi {
     /// ```json
     /// {"success":1,"message":"Loan order placed.","orderID":10590}
     /// ```
-    pub async fn create_loan_offer(&mut self, currency: &str, amount: &str, duration: &str, auto_renew: &str, lending_rate: &str) -> Result<Map<String, Value>> {
+    pub async fn create_loan_offer(&self, currency: &str, amount: &str, duration: &str, auto_renew: &str, lending_rate: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("currency", currency);
         params.insert("amount", amount);
@@ -689,7 +727,7 @@ impl PoloniexApi {
     /// ```json
     /// {"success":1,"message":"Loan offer canceled."}
     /// ```
-    pub async fn cancel_loan_offer(&mut self, order_number: &str) -> Result<Map<String, Value>> {
+    pub async fn cancel_loan_offer(&self, order_number: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("orderNumber", order_number);
         self.private_query("cancelLoanOffer", &params).await
@@ -704,7 +742,7 @@ impl PoloniexApi {
     /// "date":"2015-05-10 23:33:50"}],"LTC":[{"id":10598,"rate":"0.00002100",
     /// "amount":"10.00000000","duration":2,"autoRenew":1,"date":"2015-05-10 23:34:35"}]}
     /// ```
-    pub async fn return_open_loan_offers(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_open_loan_offers(&self) -> Result<Map<String, Value>> {
         let params = HashMap::new();
         self.private_query("returnOpenLoanOffers", &params).await
     }
@@ -721,7 +759,7 @@ impl PoloniexApi {
     /// "used":[{"id":75238,"currency":"BTC","rate":"0.00020000","amount":"0.04843834","range":2,
     /// "date":"2015-05-10 23:51:12","fees":"-0.00000001"}]}
     /// ```
-    pub async fn return_active_loans(&mut self) -> Result<Map<String, Value>> {
+    pub async fn return_active_loans(&self) -> Result<Map<String, Value>> {
         let params = HashMap::new();
         self.private_query("returnActiveLoans", &params).await
     }
@@ -737,7 +775,7 @@ impl PoloniexApi {
     /// "duration": "0.47610000", "interest": "0.00001196", "fee": "-0.00000179",
     /// "earned": "0.00001017", "open": "2016-09-28 06:47:26", "close": "2016-09-28 18:13:03" }]
     /// ```
-    pub async fn return_lending_history(&mut self, start: &str, end: &str, limit: &str) -> Result<Map<String, Value>> {
+    pub async fn return_lending_history(&self, start: &str, end: &str, limit: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("start", start);
         params.insert("end", end);
@@ -753,7 +791,7 @@ impl PoloniexApi {
     /// ```json
     /// {"success":1,"message":0}
     /// ```
-    pub async fn toggle_auto_renew(&mut self, order_number: &str) -> Result<Map<String, Value>> {
+    pub async fn toggle_auto_renew(&self, order_number: &str) -> Result<Map<String, Value>> {
         let mut params = HashMap::new();
         params.insert("orderNumber", order_number);
         self.private_query("toggleAutoRenew", &params).await
@@ -2,8 +2,8 @@
 //! This a more convenient and safe way to deal with the exchange since methods return a Result<>
 //! but this generic API does not provide all the functionnality that Poloniex offers.
 
-use crate::exchange::{ExchangeApi};
-use crate::poloniex::api::PoloniexApi;
+use crate::exchange::{ExchangeApi, Exchange, ExchangeCapabilities};
+use crate::poloniex::api::{PlaceOrderOption, PoloniexApi};
 
 use bigdecimal::BigDecimal;
 use std::str::FromStr;
@@ -12,38 +12,53 @@ use crate::error::*;
 use crate::types::*;
 use crate::poloniex::utils;
 use crate::helpers;
+use crate::helpers::json::Projection;
 use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
 
 #[async_trait]
 impl ExchangeApi for PoloniexApi {
-    async fn ticker(&mut self, pair: Pair) -> Result<Ticker> {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
-        let raw_response = self.return_ticker().await?;
+        let projection = Projection::keys(vec![*pair_name]).with_fields(TICKER_FIELDS.iter().cloned());
+        let raw_response = self.return_ticker_projected(&projection).await?;
 
         let result = utils::parse_result(&raw_response)?;
 
-        let price = helpers::from_json_bigdecimal(&result[*pair_name]["last"], "last")?;
-        let ask = helpers::from_json_bigdecimal(&result[*pair_name]["lowestAsk"], "lowestAsk")?;
-        let bid = helpers::from_json_bigdecimal(&result[*pair_name]["highestBid"], "highestBid")?;
-        let vol = helpers::from_json_bigdecimal(&result[*pair_name]["quoteVolume"], "quoteVolume")?;
+        parse_ticker(pair, &result[*pair_name], self.keep_raw)
+    }
 
-        Ok(Ticker {
-            timestamp: helpers::get_unix_timestamp_ms(),
-            pair: pair,
-            last_trade_price: price,
-            lowest_ask: ask,
-            highest_bid: bid,
-            volume: Some(vol),
-        })
+    async fn tickers(&self, pairs: &[Pair]) -> Result<HashMap<Pair, Ticker>> {
+        let mut pair_names = Vec::with_capacity(pairs.len());
+        for pair in pairs {
+            match utils::get_pair_string(pair) {
+                Some(name) => pair_names.push((*pair, *name)),
+                None => return Err(Error::PairUnsupported),
+            }
+        }
+        // returnTicker always returns every market, only the requested ones are parsed
+        let projection = Projection::keys(pair_names.iter().map(|(_, name)| *name))
+            .with_fields(TICKER_FIELDS.iter().cloned());
+        let raw_response = self.return_ticker_projected(&projection).await?;
+
+        let result = utils::parse_result(&raw_response)?;
+
+        let mut tickers = HashMap::new();
+        for (pair, pair_name) in pair_names {
+            tickers.insert(pair, parse_ticker(pair, &result[pair_name], self.keep_raw)?);
+        }
+        Ok(tickers)
     }
 
-    async fn orderbook(&mut self, pair: Pair) -> Result<Orderbook> {
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
         let raw_response = self.return_order_book(pair_name, "1000").await?; // 1000 entries max
 
@@ -54,10 +69,10 @@ impl ExchangeApi for PoloniexApi {
 
         let ask_array = result["asks"]
             .as_array()
-            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["asks"])))?;
+            .ok_or_else(|| Error::InvalidFieldFormat(format!("{}", result["asks"])))?;
         let bid_array = result["bids"]
             .as_array()
-            .ok_or_else(|| ErrorKind::InvalidFieldFormat(format!("{}", result["asks"])))?;
+            .ok_or_else(|| Error::InvalidFieldFormat(format!("{}", result["asks"])))?;
 
         for ask in ask_array {
             let price = helpers::from_json_bigdecimal(&ask[0], "ask price")?;
@@ -83,10 +98,11 @@ impl ExchangeApi for PoloniexApi {
         })
     }
 
-    async fn add_order(&mut self, order_type: OrderType, pair: Pair, quantity: Volume, price: Option<Price>) -> Result<OrderInfo> {
+    async fn add_order(&self, order_type: OrderType, pair: Pair, quantity: Volume, price: Option<Price>) -> Result<OrderInfo> {
+        let request = OrderRequest::new(order_type, pair, &quantity, &price);
         let pair_name = match utils::get_pair_string(&pair) {
             Some(name) => name,
-            None => return Err(ErrorKind::PairUnsupported.into()),
+            None => return Err(Error::PairUnsupported),
         };
 
         // The trick is to use minimal (0.0) and "maximum" (999..) price to simulate market order
@@ -94,7 +110,7 @@ impl ExchangeApi for PoloniexApi {
             // Unwrap safe here with the check above.
             OrderType::BuyLimit => {
                 if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
+                    return Err(Error::MissingPrice);
                 }
 
                 self.buy(
@@ -112,7 +128,7 @@ impl ExchangeApi for PoloniexApi {
             ).await,
             OrderType::SellLimit => {
                 if price.is_none() {
-                    return Err(ErrorKind::MissingPrice.into());
+                    return Err(Error::MissingPrice);
                 }
 
                 self.sell(
@@ -123,6 +139,7 @@ impl ExchangeApi for PoloniexApi {
                 ).await
             }
             OrderType::SellMarket => self.sell(pair_name, "0.0", &quantity.to_string(), None).await,
+            _ => return Err(Error::OrderTypeUnsupported),
         }?;
 
         let result = utils::parse_result(&raw_response)?;
@@ -132,13 +149,64 @@ impl ExchangeApi for PoloniexApi {
             identifier: vec![
                 result["orderNumber"]
                     .as_f64()
-                    .ok_or_else(|| ErrorKind::MissingField("orderNumber".to_string()))?
+                    .ok_or_else(|| Error::MissingField("orderNumber".to_string()))?
                     .to_string(),
             ],
+            raw: helpers::raw_payload(self.keep_raw, &result),
+            request: Some(request),
         })
     }
 
-    async fn balances(&mut self) -> Result<Balances> {
+    /// Limit orders are sent with the `immediateOrCancel` flag, the filled quantity is the amount
+    /// of their resulting trades
+    async fn add_ioc_order(&self,
+                           order_type: OrderType,
+                           pair: Pair,
+                           quantity: Volume,
+                           price: Price)
+                           -> Result<Execution> {
+        let request = OrderRequest::new(order_type, pair, &quantity, &Some(price.clone()));
+        let pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let raw_response = match order_type {
+            OrderType::BuyLimit => self.buy(pair_name, &price.to_string(), &quantity.to_string(), PlaceOrderOption::ImmediateOrCancel).await,
+            OrderType::SellLimit => self.sell(pair_name, &price.to_string(), &quantity.to_string(), PlaceOrderOption::ImmediateOrCancel).await,
+            _ => return Err(Error::OrderTypeUnsupported),
+        }?;
+        let result = utils::parse_result(&raw_response)?;
+        let order_number = result.get("orderNumber").and_then(|n| n.as_f64()).ok_or_else(|| Error::MissingField("orderNumber".to_string()))?;
+        let filled = match utils::resulting_amount(&result) {
+            Ok(filled) => Some(filled),
+            Err(e) => {
+                warn!("{:?} : fills of order {} unknown : {}", pair, order_number, e);
+                None
+            }
+        };
+        let order = OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: vec![order_number.to_string()],
+            raw: helpers::raw_payload(self.keep_raw, &result),
+            request: Some(request),
+        };
+        Ok(Execution { order, filled })
+    }
+
+    async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+        for order_number in &order.identifier {
+            let raw_response = self.cancel_order(order_number).await?;
+            utils::parse_result(&raw_response)?;
+        }
+        Ok(())
+    }
+
+    /// Fee rates depend on the 30 days volume of the account, not on the pair
+    async fn fees(&self, _pair: Pair) -> Result<FeeRates> {
+        let raw_response = self.return_free_info().await?;
+        let result = utils::parse_result(&raw_response)?;
+        Ok(FeeRates::new(helpers::from_json_bigdecimal(&result["makerFee"], "makerFee")?,
+                         helpers::from_json_bigdecimal(&result["takerFee"], "takerFee")?))
+    }
+
+    async fn balances(&self) -> Result<Balances> {
         let raw_response = self.return_balances().await?;
         let result = utils::parse_result(&raw_response)?;
 
@@ -154,4 +222,98 @@ impl ExchangeApi for PoloniexApi {
         }
         Ok(balances)
     }
+
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        let raw_response = self.return_complete_balances().await?;
+        let result = utils::parse_result(&raw_response)?;
+
+        let mut balances = DetailedBalances::new();
+
+        for (key, val) in result.iter() {
+            if let Some(c) = utils::get_currency_enum(key) {
+                let available = helpers::from_json_bigdecimal(&val["available"], "available")?;
+                let reserved = helpers::from_json_bigdecimal(&val["onOrders"], "onOrders")?;
+                balances.insert(c, Balance::new(available, reserved));
+            }
+        }
+        Ok(balances)
+    }
+
+    async fn withdraw(&self, currency: Currency, amount: Amount, destination: &str) -> Result<WithdrawalInfo> {
+        let currency_name = utils::get_currency_string(currency).ok_or_else(|| Error::InvalidArguments)?;
+        let raw_response = self.withdraw(&currency_name, &amount.to_string(), destination).await?;
+        utils::parse_result(&raw_response)?;
+        // Poloniex only answers with a message, there is no withdrawal reference
+        Ok(WithdrawalInfo { timestamp: helpers::get_unix_timestamp_ms(), identifier: None })
+    }
+
+    /// Poloniex returns at most 50000 trades, the 200 most recent ones without `since`
+    async fn public_trades(&self, pair: Pair, since: Option<DateTime<Utc>>) -> Result<Vec<PublicTrade>> {
+        let pair_name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+        let start = since.map(|s| s.timestamp().to_string()).unwrap_or_default();
+        let end = since.map(|_| (helpers::get_unix_timestamp_ms() / 1000).to_string()).unwrap_or_default();
+        let raw_response = self.return_trade_history(pair_name, &start, &end).await?;
+        let trades = raw_response.get("data").and_then(|d| d.as_array()).ok_or_else(|| Error::InvalidFieldFormat("data".to_string()))?;
+        let since_ms = since.map_or(i64::MIN, |s| s.timestamp_millis());
+        let mut trades = trades.iter().map(|t| {
+            let date = t["date"].as_str().ok_or_else(|| Error::MissingField("date".to_string()))?;
+            let event_ms = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+                .map_err(|_| Error::InvalidFieldFormat(date.to_string()))?
+                .timestamp_millis();
+            Ok(PublicTrade {
+                id: Some(t["globalTradeID"].to_string()),
+                event_ms,
+                pair,
+                price: helpers::from_json_bigdecimal(&t["rate"], "rate")?,
+                amount: helpers::from_json_bigdecimal(&t["amount"], "amount")?,
+                side: TradeType::parse(t["type"].as_str().unwrap_or_default()),
+            })
+        }).collect::<Result<Vec<PublicTrade>>>()?;
+        trades.retain(|t| t.event_ms > since_ms);
+        trades.sort_by_key(|t| t.event_ms);
+        Ok(trades)
+    }
+
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        let raw_response = self.return_ticker().await?;
+        let result = utils::parse_result(&raw_response)?;
+        // Frozen markets are listed but cannot be traded
+        Ok(result.iter()
+            .filter(|(_, ticker)| ticker["isFrozen"] != "1" && ticker["isFrozen"] != 1)
+            .filter_map(|(name, _)| utils::get_pair_enum(name).copied())
+            .collect())
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Poloniex.capabilities()
+    }
+}
+
+/// Fields of a market read by `parse_ticker`
+const TICKER_FIELDS: [&str; 7] = ["last", "lowestAsk", "highestBid", "quoteVolume", "percentChange", "high24hr", "low24hr"];
+
+fn parse_ticker(pair: Pair, market: &Value, keep_raw: bool) -> Result<Ticker> {
+    let price = helpers::from_json_bigdecimal(&market["last"], "last")?;
+    let ask = helpers::from_json_bigdecimal(&market["lowestAsk"], "lowestAsk")?;
+    let bid = helpers::from_json_bigdecimal(&market["highestBid"], "highestBid")?;
+    let vol = helpers::from_json_bigdecimal(&market["quoteVolume"], "quoteVolume")?;
+    // Poloniex gives the change as a ratio, 0.025 for +2.5%
+    let percent_change = helpers::from_json_bigdecimal_opt(&market["percentChange"])
+        .map(|c| c * BigDecimal::from(100));
+
+    Ok(Ticker {
+        timestamp: helpers::get_unix_timestamp_ms(),
+        pair: pair,
+        last_trade_price: price,
+        lowest_ask: ask,
+        highest_bid: bid,
+        volume: Some(vol),
+        bid_size: None,
+        ask_size: None,
+        open_24h: None,
+        high_24h: helpers::from_json_bigdecimal_opt(&market["high24hr"]),
+        low_24h: helpers::from_json_bigdecimal_opt(&market["low24hr"]),
+        percent_change,
+        raw: helpers::raw_payload(keep_raw, market),
+    })
 }
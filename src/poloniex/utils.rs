@@ -4,7 +4,9 @@ use serde_json::Value;
 use serde_json::value::Map;
 
 use crate::error::*;
-use crate::types::Currency;
+use crate::exchange::Exchange;
+use crate::helpers;
+use crate::types::{Currency, Volume};
 use crate::types::Pair;
 use crate::types::Pair::*;
 
@@ -42,7 +44,7 @@ lazy_static! {
         m.insert(PPC_BTC, "BTC_PPC");
         m.insert(RIC_BTC, "BTC_RIC");
         m.insert(SJCX_BTC, "BTC_SJCX");
-        m.insert(STR_BTC, "BTC_STR");
+        m.insert(XLM_BTC, "BTC_STR");
         m.insert(SYS_BTC, "BTC_SYS");
         m.insert(VIA_BTC, "BTC_VIA");
         m.insert(XVC_BTC, "BTC_XVC");
@@ -58,7 +60,7 @@ lazy_static! {
         m.insert(DASH_USDT, "USDT_DASH");
         m.insert(LTC_USDT, "USDT_LTC");
         m.insert(NXT_USDT, "USDT_NXT");
-        m.insert(STR_USDT, "USDT_STR");
+        m.insert(XLM_USDT, "USDT_STR");
         m.insert(XMR_USDT, "USDT_XMR");
         m.insert(XRP_USDT, "USDT_XRP");
         m.insert(BCN_XMR, "XMR_BCN");
@@ -129,6 +131,11 @@ pub fn get_pair_enum(pair: &str) -> Option<&Pair> {
     PAIRS_STRING.get_by_second(&pair)
 }
 
+/// Every pair with a Poloniex name
+pub fn supported_pairs() -> Vec<Pair> {
+    PAIRS_STRING.iter().map(|(pair, _)| *pair).collect()
+}
+
 /// If error array is null, return the result (encoded in a json object)
 /// else return the error string found in array
 pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>> {
@@ -136,16 +143,20 @@ pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>>
         Some(error) => {
             error
                 .as_str()
-                .ok_or_else(|| ErrorKind::InvalidFieldFormat("error".to_string()))?
+                .ok_or_else(|| Error::InvalidFieldFormat("error".to_string()))?
         }
         None => return Ok(response.clone()),
     };
 
-    match error_msg.as_ref() {
-        "Invalid command." => Err(ErrorKind::InvalidArguments.into()),
-        "Invalid API key/secret pair." => Err(ErrorKind::BadCredentials.into()),
-        "Total must be at least 0.0001." => Err(ErrorKind::InsufficientOrderSize.into()),
-        other => Err(ErrorKind::ExchangeSpecificError(other.to_string()).into()),
+    match error_msg {
+        "Invalid command." => Err(Error::InvalidArguments),
+        "Invalid API key/secret pair." => Err(Error::BadCredentials),
+        m if m.starts_with("Total must be at least") => Err(Error::InsufficientOrderSize),
+        m if m.starts_with("Not enough ") => Err(Error::InsufficientFunds),
+        m if m.starts_with("Invalid order number") => Err(Error::OrderNotFound),
+        m if m.starts_with("Nonce must be greater than") => Err(Error::InvalidNonce),
+        m if m.starts_with("Please do not make more than") => Err(Error::RateLimited(None)),
+        other => Err(Error::ExchangeSpecificError(other.to_string())),
     }
 }
 
@@ -162,20 +173,7 @@ pub fn parse_result(response: &Map<String, Value>) -> Result<Map<String, Value>>
 /// assert_eq!(currency, Currency::BTC);
 /// ```
 pub fn get_currency_enum(currency: &str) -> Option<Currency> {
-    match currency {
-        "AMP" => Some(Currency::AMP),
-        "BTC" => Some(Currency::BTC),
-        "ARDR" => Some(Currency::ARDR),
-        "ETH" => Some(Currency::ETH),
-        "ETC" => Some(Currency::ETC),
-        "LBC" => Some(Currency::LBC),
-        "XMR" => Some(Currency::XMR),
-        "XPM" => Some(Currency::XPM),
-        "XRP" => Some(Currency::XRP),
-        "XVC" => Some(Currency::XVC),
-        "ZEC" => Some(Currency::ZEC),
-        _ => None,
-    }
+    Currency::from_alias(Exchange::Poloniex, currency)
 }
 
 /// Return the currency string associated with the
@@ -191,18 +189,30 @@ pub fn get_currency_enum(currency: &str) -> Option<Currency> {
 /// assert_eq!(currency, Some("BTC".to_string()));
 /// ```
 pub fn get_currency_string(currency: Currency) -> Option<String> {
-    match currency {
-        Currency::AMP => Some("AMP".to_string()),
-        Currency::BTC => Some("BTC".to_string()),
-        Currency::ARDR => Some("ARDR".to_string()),
-        Currency::ETH => Some("ETH".to_string()),
-        Currency::ETC => Some("ETC".to_string()),
-        Currency::LBC => Some("LBC".to_string()),
-        Currency::XMR => Some("XMR".to_string()),
-        Currency::XPM => Some("XPM".to_string()),
-        Currency::XRP => Some("XRP".to_string()),
-        Currency::XVC => Some("XVC".to_string()),
-        Currency::ZEC => Some("ZEC".to_string()),
-        _ => None,
+    let listed = PAIRS_STRING.iter()
+        .filter_map(|(pair, _)| crate::utils::pair_currencies(pair))
+        .any(|(base, quote)| base == currency || quote == currency);
+    if listed { Some(currency.alias_for(Exchange::Poloniex)) } else { None }
+}
+
+/// Amount traded by an order when it was placed, from the `resultingTrades` of the `buy` and `sell`
+/// responses
+pub fn resulting_amount(result: &Map<String, Value>) -> Result<Volume> {
+    let trades = result.get("resultingTrades").and_then(|t| t.as_array())
+        .ok_or_else(|| Error::MissingField("resultingTrades".to_string()))?;
+    trades.iter().try_fold(Volume::from(0), |amount, trade| Ok(amount + helpers::from_json_bigdecimal(&trade["amount"], "amount")?))
+}
+
+#[cfg(test)]
+mod poloniex_utils_tests {
+    use super::*;
+
+    #[test]
+    fn resulting_trades_are_summed() {
+        let result: Value = serde_json::from_str(r#"{"orderNumber": 31226040, "resultingTrades": [
+            {"amount": "338.8732", "date": "2014-10-18 23:03:21", "rate": "0.00000173", "total": "0.00058625", "tradeID": "16164", "type": "buy"},
+            {"amount": "1.1268", "date": "2014-10-18 23:03:21", "rate": "0.00000174", "total": "0.00000196", "tradeID": "16165", "type": "buy"}]}"#).unwrap();
+        assert_eq!(resulting_amount(result.as_object().unwrap()).unwrap(), Volume::from(340));
+        assert!(resulting_amount(&Map::new()).is_err());
     }
 }
@@ -0,0 +1,190 @@
+//! Value the balances held on several exchanges in a single quote currency.
+//! Prices are taken from tickers, as the mid price between the best bid and the best ask. A
+//! currency without a pair against the quote currency is valued through the inverse pair, or
+//! through BTC. With fiat rates, see `fx`, a currency priced in another fiat is converted.
+
+use std::collections::HashMap;
+
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeApi};
+use crate::fx::FxRates;
+use crate::rebalancer;
+use crate::types::*;
+use crate::utils;
+
+/// Total value of a portfolio in `quote` currency
+#[derive(Debug, Clone, PartialEq)]
+pub struct Valuation {
+    pub quote: Currency,
+    pub total: Amount,
+    /// Value held on each exchange
+    pub by_exchange: HashMap<Exchange, Amount>,
+    /// Value of each currency, summed over the exchanges
+    pub by_currency: HashMap<Currency, Amount>,
+    /// Currencies held without any known price, left out of the totals
+    pub unpriced: Vec<Currency>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Portfolio {
+    pub balances: HashMap<Exchange, Balances>,
+    /// Mid price of every known pair
+    pub prices: HashMap<Pair, Price>,
+    /// Rates between fiat currencies, for the prices quoted in another fiat
+    pub fx: Option<FxRates>,
+}
+
+impl Portfolio {
+    pub fn new(balances: HashMap<Exchange, Balances>) -> Self {
+        Portfolio { balances, prices: HashMap::new(), fx: None }
+    }
+
+    /// Convert the prices quoted in another fiat currency with `rates`
+    pub fn with_fx(mut self, rates: FxRates) -> Self {
+        self.fx = Some(rates);
+        self
+    }
+
+    /// Fetch the balances of every exchange, and the tickers needed to value them in `quote`
+    pub async fn fetch(apis: &HashMap<Exchange, Box<dyn ExchangeApi>>, quote: Currency) -> Result<Self> {
+        let mut portfolio = Portfolio::new(rebalancer::portfolio(apis).await?);
+        let held: Vec<Currency> = rebalancer::total_balances(&portfolio.balances).into_iter()
+            .filter(|(c, amount)| *c != quote && !amount.is_zero())
+            .map(|(c, _)| c)
+            .collect();
+        for currency in held {
+            if portfolio.price(currency, quote).is_none() {
+                portfolio.fetch_price(apis, currency, quote).await;
+            }
+            if portfolio.price(currency, quote).is_none() && currency != Currency::BTC && quote != Currency::BTC {
+                portfolio.fetch_price(apis, currency, Currency::BTC).await;
+                portfolio.fetch_price(apis, Currency::BTC, quote).await;
+            }
+        }
+        Ok(portfolio)
+    }
+
+    /// Fetch the ticker of `base`/`quote` or of its inverse from the first exchange listing it
+    async fn fetch_price(&mut self, apis: &HashMap<Exchange, Box<dyn ExchangeApi>>, base: Currency, quote: Currency) {
+        let pairs = vec![utils::currencies_pair(base, quote), utils::currencies_pair(quote, base)];
+        for pair in pairs.into_iter().flatten() {
+            if self.prices.contains_key(&pair) {
+                return;
+            }
+            for (exchange, api) in apis.iter() {
+                if utils::pair_fn(*exchange)(&pair).is_none() {
+                    continue;
+                }
+                match api.ticker(pair).await {
+                    Ok(ticker) => {
+                        self.add_ticker(&ticker);
+                        return;
+                    }
+                    Err(e) => debug!("Portfolio : no {:?} ticker on {:?} : {}", pair, exchange, e),
+                }
+            }
+        }
+    }
+
+    pub fn add_ticker(&mut self, ticker: &Ticker) {
+        let mid = (ticker.highest_bid.clone() + ticker.lowest_ask.clone()) / BigDecimal::from(2);
+        self.prices.insert(ticker.pair, mid);
+    }
+
+    /// Price of one `base` in `quote`, from the direct or the inverse pair, or through BTC, or
+    /// in another fiat currency converted with the fiat rates
+    pub fn price(&self, base: Currency, quote: Currency) -> Option<Price> {
+        if base == quote {
+            return Some(BigDecimal::from(1));
+        }
+        self.crypto_price(base, quote).or_else(|| {
+            let fx = self.fx.as_ref()?;
+            if let Some(rate) = fx.rate(base, quote) {
+                return Some(rate);
+            }
+            fx.currencies().into_iter()
+                .filter(|fiat| *fiat != quote)
+                .find_map(|fiat| Some(self.crypto_price(base, fiat)? * fx.rate(fiat, quote)?))
+        })
+    }
+
+    fn crypto_price(&self, base: Currency, quote: Currency) -> Option<Price> {
+        self.direct_price(base, quote).or_else(|| {
+            if base == Currency::BTC || quote == Currency::BTC {
+                return None;
+            }
+            Some(self.direct_price(base, Currency::BTC)? * self.direct_price(Currency::BTC, quote)?)
+        })
+    }
+
+    fn direct_price(&self, base: Currency, quote: Currency) -> Option<Price> {
+        if let Some(price) = utils::currencies_pair(base, quote).and_then(|p| self.prices.get(&p)) {
+            return Some(price.clone());
+        }
+        utils::currencies_pair(quote, base)
+            .and_then(|p| self.prices.get(&p))
+            .filter(|price| !price.is_zero())
+            .map(|price| BigDecimal::from(1) / price.clone())
+    }
+
+    /// Value every balance in `quote`
+    pub fn value(&self, quote: Currency) -> Valuation {
+        let mut valuation = Valuation { quote, total: BigDecimal::zero(), by_exchange: HashMap::new(), by_currency: HashMap::new(), unpriced: vec![] };
+        for (exchange, balances) in &self.balances {
+            let mut exchange_total = BigDecimal::zero();
+            for (currency, amount) in balances {
+                if amount.is_zero() {
+                    continue;
+                }
+                let price = match self.price(*currency, quote) {
+                    Some(price) => price,
+                    None => {
+                        if !valuation.unpriced.contains(currency) {
+                            valuation.unpriced.push(*currency);
+                        }
+                        continue;
+                    }
+                };
+                let value = amount.clone() * price;
+                let current = valuation.by_currency.get(currency).cloned().unwrap_or_else(BigDecimal::zero);
+                valuation.by_currency.insert(*currency, current + value.clone());
+                exchange_total = exchange_total + value;
+            }
+            valuation.total = valuation.total.clone() + exchange_total.clone();
+            valuation.by_exchange.insert(*exchange, exchange_total);
+        }
+        valuation
+    }
+}
+
+#[cfg(test)]
+mod portfolio_tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn value_through_inverse_and_btc_pairs() {
+        let mut balances = HashMap::new();
+        balances.insert(Exchange::Kraken, vec![(Currency::BTC, dec("1")), (Currency::EUR, dec("100"))].into_iter().collect());
+        balances.insert(Exchange::Bittrex, vec![(Currency::ETH, dec("10")), (Currency::DOGE, dec("5"))].into_iter().collect());
+        let mut portfolio = Portfolio::new(balances);
+        portfolio.prices.insert(Pair::BTC_EUR, dec("1000"));
+        portfolio.prices.insert(Pair::ETH_BTC, dec("0.05"));
+
+        let valuation = portfolio.value(Currency::EUR);
+        assert_eq!(valuation.by_exchange[&Exchange::Kraken], dec("1100"));
+        assert_eq!(valuation.by_currency[&Currency::ETH], dec("500"));
+        assert_eq!(valuation.total, dec("1600"));
+        assert_eq!(valuation.unpriced, vec![Currency::DOGE]);
+        assert_eq!(portfolio.value(Currency::BTC).by_currency[&Currency::EUR], dec("0.1"));
+
+        let portfolio = portfolio.with_fx(FxRates::new(Currency::USD).with_rate(Currency::EUR, dec("0.8")));
+        assert_eq!(portfolio.value(Currency::USD).by_exchange[&Exchange::Kraken], dec("1375"));
+    }
+}
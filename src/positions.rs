@@ -0,0 +1,390 @@
+//! Positions and profit and loss of an account, from its fills.
+//! `PositionTracker` listens to `LiveEvent::Fill` events and keeps, for every pair of every venue,
+//! the open quantity, its average entry price and the profit realized by the fills reducing it.
+//! The unrealized profit is valued at the last price of the pair, taken from live trades, the mid
+//! price of live order books or `MarkPrice` messages. Positions held before the tracker started
+//! are given with `SetPosition`, or opened from the balances of the account with `SetBalances`.
+//! Fills are counted once per trade id, those replayed after a reconnection are ignored. Positions
+//! are queried with `GetPosition` and `GetPositions`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use actix::{Actor, Context, Handler, MessageResult};
+use bigdecimal::{BigDecimal, Signed, Zero};
+
+use crate::exchange::Exchange;
+use crate::types::*;
+use crate::utils;
+
+/// Position of a pair, amounts are in quote currency
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub exchange: Exchange,
+    pub pair: Pair,
+    /// In base currency, negative when short
+    pub quantity: Volume,
+    /// Average price of the open quantity, zero when flat
+    pub entry_price: Price,
+    /// Profit of the closed quantities, net of the fees
+    pub realized_pnl: Amount,
+    /// Fees paid, fees in base currency are valued at the price of their fill and fees in other
+    /// currencies at the last price of the currency in quote currency
+    pub fees: Amount,
+    /// Fees in other currencies paid while no price of the currency was known, not counted in
+    /// `fees` nor in the profit
+    pub unvalued_fees: HashMap<Currency, Amount>,
+    /// Last known price of the pair
+    pub last_price: Option<Price>,
+}
+
+impl Position {
+    fn new(exchange: Exchange, pair: Pair) -> Self {
+        Position {
+            exchange,
+            pair,
+            quantity: BigDecimal::zero(),
+            entry_price: BigDecimal::zero(),
+            realized_pnl: BigDecimal::zero(),
+            fees: BigDecimal::zero(),
+            unvalued_fees: HashMap::new(),
+            last_price: None,
+        }
+    }
+
+    /// Profit of the open quantity at the last price, `None` until a price is known
+    pub fn unrealized_pnl(&self) -> Option<Amount> {
+        self.last_price.as_ref().map(|price| (price - &self.entry_price) * &self.quantity)
+    }
+
+    /// Realized and unrealized profit, `None` until a price is known
+    pub fn total_pnl(&self) -> Option<Amount> {
+        self.unrealized_pnl().map(|pnl| pnl + &self.realized_pnl)
+    }
+
+    /// Apply a fill, at average cost : fills increasing the position move its entry price, fills
+    /// reducing it realize the difference with the entry price. `fee_price` is the price of the
+    /// fee currency in quote currency, for fees in neither currency of the pair.
+    fn apply(&mut self, fill: &FillEvent, fee_price: Option<&Price>) {
+        let signed = match fill.side {
+            TradeType::Buy => fill.amount.clone(),
+            TradeType::Sell => -fill.amount.clone(),
+            TradeType::Unknown(_) => {
+                warn!("Positions : fill {} of {:?} has no side, ignored", fill.order_id, fill.pair);
+                return;
+            }
+        };
+        if self.quantity.is_zero() || self.quantity.is_positive() == signed.is_positive() {
+            let open = self.quantity.abs();
+            self.entry_price = (&self.entry_price * &open + &fill.price * &fill.amount) / (open + &fill.amount);
+        } else {
+            let closed = fill.amount.clone().min(self.quantity.abs());
+            let direction = if self.quantity.is_positive() { BigDecimal::from(1) } else { BigDecimal::from(-1) };
+            self.realized_pnl += (&fill.price - &self.entry_price) * closed * direction;
+            if fill.amount > self.quantity.abs() {
+                self.entry_price = fill.price.clone();
+            }
+        }
+        self.quantity += signed;
+        if self.quantity.is_zero() {
+            self.entry_price = BigDecimal::zero();
+        }
+
+        let currencies = utils::pair_currencies(&fill.pair);
+        let fee = match (fill.fee_currency, currencies) {
+            (Some(currency), Some((base, _))) if currency == base => &fill.fee * &fill.price,
+            (Some(currency), Some((_, quote))) if currency != quote && !fill.fee.is_zero() => match fee_price {
+                Some(price) => &fill.fee * price,
+                None => {
+                    *self.unvalued_fees.entry(currency).or_insert_with(BigDecimal::zero) += &fill.fee;
+                    return;
+                }
+            },
+            _ => fill.fee.clone(),
+        };
+        self.realized_pnl -= fee.clone();
+        self.fees += fee;
+    }
+}
+
+/// Query the position of a pair
+#[derive(Message)]
+#[rtype(result = "Option<Position>")]
+pub struct GetPosition {
+    pub exchange: Exchange,
+    pub pair: Pair,
+}
+
+/// Query every position, flat ones included
+#[derive(Message)]
+#[rtype(result = "Vec<Position>")]
+pub struct GetPositions;
+
+/// Set a position opened before the tracker started, its realized profit is kept
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetPosition {
+    pub exchange: Exchange,
+    pub pair: Pair,
+    pub quantity: Volume,
+    pub entry_price: Price,
+}
+
+/// Open positions from the `balances` of an account on `exchange`, e.g. its balances at startup :
+/// every non zero balance but the `quote` one is a position on its pair against `quote`, currencies
+/// without such a pair are skipped. Their entry price is the first price known for the pair.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetBalances {
+    pub exchange: Exchange,
+    pub balances: Balances,
+    pub quote: Currency,
+}
+
+/// Value a pair at `price`, e.g. the last price of a ticker
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct MarkPrice {
+    pub exchange: Exchange,
+    pub pair: Pair,
+    pub price: Price,
+}
+
+/// Trade ids remembered to ignore fills seen twice
+const MAX_TRADE_IDS: usize = 100_000;
+
+/// Actor keeping the positions of an account from its fills
+#[derive(Default)]
+pub struct PositionTracker {
+    positions: HashMap<(Exchange, Pair), Position>,
+    /// Last price of every pair seen, tracked or not, to value the fees
+    prices: HashMap<(Exchange, Pair), Price>,
+    /// Positions opened from balances, waiting for a price to set their entry price
+    unpriced: HashSet<(Exchange, Pair)>,
+    trade_ids: HashSet<(Exchange, Pair, String)>,
+    trade_ids_order: VecDeque<(Exchange, Pair, String)>,
+}
+
+impl PositionTracker {
+    pub fn new() -> Self {
+        PositionTracker::default()
+    }
+
+    fn position(&mut self, exchange: Exchange, pair: Pair) -> &mut Position {
+        self.positions.entry((exchange, pair)).or_insert_with(|| Position::new(exchange, pair))
+    }
+
+    /// Update the last price of a pair, only the pairs with a position are tracked
+    fn mark(&mut self, exchange: Exchange, pair: Pair, price: Price) {
+        if let Some(position) = self.positions.get_mut(&(exchange, pair)) {
+            if self.unpriced.remove(&(exchange, pair)) {
+                position.entry_price = price.clone();
+            }
+            position.last_price = Some(price.clone());
+        }
+        self.prices.insert((exchange, pair), price);
+    }
+
+    /// Whether the fill `trade_id` of `pair` is seen for the first time, the oldest ids are
+    /// forgotten past `MAX_TRADE_IDS`
+    fn first_seen(&mut self, exchange: Exchange, pair: Pair, trade_id: &str) -> bool {
+        let key = (exchange, pair, trade_id.to_string());
+        if !self.trade_ids.insert(key.clone()) {
+            return false;
+        }
+        self.trade_ids_order.push_back(key);
+        if self.trade_ids_order.len() > MAX_TRADE_IDS {
+            if let Some(oldest) = self.trade_ids_order.pop_front() {
+                self.trade_ids.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// Price of `currency` in the quote currency of `pair`
+    fn fee_price(&self, exchange: Exchange, pair: Pair, currency: Option<Currency>) -> Option<Price> {
+        let (_, quote) = utils::pair_currencies(&pair)?;
+        let fee_pair = utils::currencies_pair(currency?, quote)?;
+        self.prices.get(&(exchange, fee_pair)).cloned()
+    }
+
+    /// See `SetBalances`
+    fn set_balances(&mut self, exchange: Exchange, balances: Balances, quote: Currency) {
+        for (currency, amount) in balances {
+            if currency == quote || amount.is_zero() {
+                continue;
+            }
+            let pair = match utils::currencies_pair(currency, quote) {
+                Some(pair) => pair,
+                None => {
+                    debug!("Positions : no pair for {:?} against {:?}, balance skipped", currency, quote);
+                    continue;
+                }
+            };
+            let price = self.prices.get(&(exchange, pair)).cloned();
+            if price.is_none() {
+                self.unpriced.insert((exchange, pair));
+            }
+            let position = self.position(exchange, pair);
+            position.quantity = amount;
+            position.entry_price = price.unwrap_or_else(BigDecimal::zero);
+        }
+    }
+
+    fn on_event(&mut self, exchange: Exchange, le: &LiveEvent) {
+        match le {
+            LiveEvent::Fill(fill) => {
+                if let Some(trade_id) = &fill.trade_id {
+                    if !self.first_seen(exchange, fill.pair, trade_id) {
+                        debug!("Positions : fill {} of {:?} already counted", trade_id, fill.pair);
+                        return;
+                    }
+                }
+                let fee_price = self.fee_price(exchange, fill.pair, fill.fee_currency);
+                if self.unpriced.remove(&(exchange, fill.pair)) {
+                    self.position(exchange, fill.pair).entry_price = fill.price.clone();
+                }
+                self.position(exchange, fill.pair).apply(fill, fee_price.as_ref());
+                self.mark(exchange, fill.pair, fill.price.clone());
+            }
+            LiveEvent::LiveTrade(t) => self.mark(exchange, t.pair, t.price.clone()),
+            LiveEvent::LiveOrderbook(ob) => {
+                if let (Some((ask, _)), Some((bid, _))) = (ob.best_ask(), ob.best_bid()) {
+                    self.mark(exchange, ob.pair, (ask + bid) / BigDecimal::from(2));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Actor for PositionTracker {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for PositionTracker {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        self.on_event(msg.0, &msg.1);
+    }
+}
+
+impl Handler<SetPosition> for PositionTracker {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetPosition, _ctx: &mut Context<Self>) {
+        self.unpriced.remove(&(msg.exchange, msg.pair));
+        let position = self.position(msg.exchange, msg.pair);
+        position.quantity = msg.quantity;
+        position.entry_price = msg.entry_price;
+    }
+}
+
+impl Handler<SetBalances> for PositionTracker {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetBalances, _ctx: &mut Context<Self>) {
+        self.set_balances(msg.exchange, msg.balances, msg.quote);
+    }
+}
+
+impl Handler<MarkPrice> for PositionTracker {
+    type Result = ();
+
+    fn handle(&mut self, msg: MarkPrice, _ctx: &mut Context<Self>) {
+        self.mark(msg.exchange, msg.pair, msg.price);
+    }
+}
+
+impl Handler<GetPosition> for PositionTracker {
+    type Result = MessageResult<GetPosition>;
+
+    fn handle(&mut self, msg: GetPosition, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.positions.get(&(msg.exchange, msg.pair)).cloned())
+    }
+}
+
+impl Handler<GetPositions> for PositionTracker {
+    type Result = MessageResult<GetPositions>;
+
+    fn handle(&mut self, _msg: GetPositions, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.positions.values().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod positions_tests {
+    use super::*;
+
+    fn fill(side: TradeType, price: i32, amount: i32, fee: i32) -> LiveEvent {
+        fill_of(None, Pair::BTC_USD, side, price, amount, fee, Currency::USD)
+    }
+
+    fn fill_of(trade_id: Option<&str>, pair: Pair, side: TradeType, price: i32, amount: i32, fee: i32, fee_currency: Currency) -> LiveEvent {
+        LiveEvent::Fill(FillEvent {
+            event_ms: 0,
+            order_id: "1".to_string(),
+            trade_id: trade_id.map(|id| id.to_string()),
+            pair,
+            side,
+            price: BigDecimal::from(price),
+            amount: BigDecimal::from(amount),
+            fee: BigDecimal::from(fee),
+            fee_currency: Some(fee_currency),
+            liquidity: Liquidity::Unknown,
+        })
+    }
+
+    #[test]
+    fn fills_move_the_entry_price_and_realize_profits() {
+        let mut tracker = PositionTracker::new();
+        tracker.on_event(Exchange::Kraken, &fill(TradeType::Buy, 100, 1, 0));
+        tracker.on_event(Exchange::Kraken, &fill(TradeType::Buy, 130, 2, 0));
+        let position = &tracker.positions[&(Exchange::Kraken, Pair::BTC_USD)];
+        assert_eq!(position.entry_price, BigDecimal::from(120));
+        assert_eq!(position.quantity, BigDecimal::from(3));
+
+        // Sells 3 at 150 then opens a short of 1
+        tracker.on_event(Exchange::Kraken, &fill(TradeType::Sell, 150, 4, 2));
+        let position = &tracker.positions[&(Exchange::Kraken, Pair::BTC_USD)];
+        assert_eq!(position.realized_pnl, BigDecimal::from(88));
+        assert_eq!(position.quantity, BigDecimal::from(-1));
+        assert_eq!(position.entry_price, BigDecimal::from(150));
+
+        tracker.mark(Exchange::Kraken, Pair::BTC_USD, BigDecimal::from(140));
+        let position = &tracker.positions[&(Exchange::Kraken, Pair::BTC_USD)];
+        assert_eq!(position.unrealized_pnl(), Some(BigDecimal::from(10)));
+        assert_eq!(position.total_pnl(), Some(BigDecimal::from(98)));
+    }
+
+    #[test]
+    fn fills_are_counted_once_and_fees_valued_in_quote() {
+        let mut tracker = PositionTracker::new();
+        tracker.mark(Exchange::Binance, Pair::BNB_USDT, BigDecimal::from(20));
+        tracker.on_event(Exchange::Binance, &fill_of(Some("t1"), Pair::BTC_USDT, TradeType::Buy, 100, 2, 1, Currency::BNB));
+        tracker.on_event(Exchange::Binance, &fill_of(Some("t1"), Pair::BTC_USDT, TradeType::Buy, 100, 2, 1, Currency::BNB));
+        tracker.on_event(Exchange::Binance, &fill_of(Some("t2"), Pair::BTC_USDT, TradeType::Buy, 100, 1, 1, Currency::ETH));
+        let position = &tracker.positions[&(Exchange::Binance, Pair::BTC_USDT)];
+        assert_eq!(position.quantity, BigDecimal::from(3));
+        assert_eq!(position.fees, BigDecimal::from(20));
+        assert_eq!(position.realized_pnl, BigDecimal::from(-20));
+        assert_eq!(position.unvalued_fees[&Currency::ETH], BigDecimal::from(1));
+    }
+
+    #[test]
+    fn balances_open_positions_priced_by_the_first_mark() {
+        let mut tracker = PositionTracker::new();
+        let mut balances = Balances::new();
+        balances.insert(Currency::BTC, BigDecimal::from(2));
+        balances.insert(Currency::USD, BigDecimal::from(1000));
+        tracker.set_balances(Exchange::Kraken, balances, Currency::USD);
+        assert_eq!(tracker.positions.len(), 1);
+
+        tracker.mark(Exchange::Kraken, Pair::BTC_USD, BigDecimal::from(100));
+        tracker.mark(Exchange::Kraken, Pair::BTC_USD, BigDecimal::from(110));
+        let position = &tracker.positions[&(Exchange::Kraken, Pair::BTC_USD)];
+        assert_eq!(position.quantity, BigDecimal::from(2));
+        assert_eq!(position.entry_price, BigDecimal::from(100));
+        assert_eq!(position.unrealized_pnl(), Some(BigDecimal::from(20)));
+    }
+}
@@ -0,0 +1,418 @@
+//! Prices and volumes rounded to the tick and lot sizes of their pair.
+//! Parsed and computed amounts often carry many meaningless digits, e.g. the mid price of a book
+//! or a volume summed from floats. With a `PairPrecision` configured in `ExchangeSettings`, the
+//! live events are rounded to the tick and lot by a `PrecisionNormalizer` before they reach the
+//! recipients, and `PreciseApi` does the same for the typed REST responses of an account with a
+//! `precision`. Asks are rounded up and bids down so that a book is never shown better than it
+//! is, the levels ending up at the same price are merged. Other prices and the volumes are rounded
+//! to the nearest tick and lot, a non-zero volume to one lot at least.
+//! Candles and summaries, whose pair is not typed, are left untouched.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix::{Actor, Context, Handler, Recipient};
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+
+use crate::error::*;
+use crate::exchange::{ExchangeApi, ExchangeCapabilities};
+use crate::order_builder::round_to_step;
+use crate::types::*;
+
+/// Tick and lot size of a pair, missing sizes leave the values untouched
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct PairPrecision {
+    pub tick_size: Option<Price>,
+    pub lot_size: Option<Volume>,
+}
+
+impl PairPrecision {
+    pub fn new(tick_size: Price, lot_size: Volume) -> Self {
+        PairPrecision { tick_size: Some(tick_size), lot_size: Some(lot_size) }
+    }
+
+    /// `price` rounded to the nearest tick
+    pub fn price(&self, price: &Price) -> Price {
+        round_opt(price, &self.tick_size)
+    }
+
+    /// `price` of an ask rounded up to a tick, or of a bid rounded down
+    pub fn side_price(&self, side: Side, price: &Price) -> Price {
+        match &self.tick_size {
+            Some(tick) => round_to_step(price, tick, side == Side::Sell),
+            None => price.clone(),
+        }
+    }
+
+    /// `volume` rounded to the nearest lot, a non-zero volume is one lot at least so that it is
+    /// not taken for a removed level
+    pub fn volume(&self, volume: &Volume) -> Volume {
+        let rounded = round_opt(volume, &self.lot_size);
+        match &self.lot_size {
+            Some(lot) if rounded.is_zero() && !volume.is_zero() => {
+                if volume < &BigDecimal::zero() { -lot.clone() } else { lot.clone() }
+            }
+            _ => rounded,
+        }
+    }
+}
+
+fn round_opt(value: &BigDecimal, step: &Option<BigDecimal>) -> BigDecimal {
+    match step {
+        Some(step) => round_to_nearest(value, step),
+        None => value.clone(),
+    }
+}
+
+/// Round `value` to the nearest multiple of `step`, halves away from zero
+pub fn round_to_nearest(value: &BigDecimal, step: &BigDecimal) -> BigDecimal {
+    if step <= &BigDecimal::zero() {
+        return value.clone();
+    }
+    if value < &BigDecimal::zero() {
+        return -round_to_nearest(&-value.clone(), step);
+    }
+    let down = round_to_step(value, step, false);
+    if (value - &down) * BigDecimal::from(2) >= *step {
+        down + step.clone()
+    } else {
+        down
+    }
+}
+
+/// Precision of each pair, pairs without one are left untouched
+pub type Precisions = HashMap<Pair, PairPrecision>;
+
+/// Round the levels of one side of a book, `Sell` for the asks. The volumes of the levels that
+/// end up at the same price are summed.
+fn round_levels(p: &PairPrecision, side: Side, levels: &mut Vec<(Price, Volume)>) {
+    let mut merged: Vec<(Price, Volume)> = Vec::with_capacity(levels.len());
+    for (price, volume) in levels.drain(..) {
+        let price = p.side_price(side, &price);
+        match merged.iter_mut().find(|(merged_price, _)| *merged_price == price) {
+            Some(level) => level.1 = level.1.clone() + volume,
+            None => merged.push((price, volume)),
+        }
+    }
+    for level in merged.iter_mut() {
+        level.1 = p.volume(&level.1);
+    }
+    *levels = merged;
+}
+
+pub fn normalize_orderbook(precisions: &Precisions, ob: &mut Orderbook) {
+    if let Some(p) = precisions.get(&ob.pair) {
+        round_levels(p, Side::Sell, &mut ob.asks);
+        round_levels(p, Side::Buy, &mut ob.bids);
+    }
+}
+
+pub fn normalize_ticker(precisions: &Precisions, ticker: &mut Ticker) {
+    if let Some(p) = precisions.get(&ticker.pair) {
+        ticker.last_trade_price = p.price(&ticker.last_trade_price);
+        ticker.lowest_ask = p.side_price(Side::Sell, &ticker.lowest_ask);
+        ticker.highest_bid = p.side_price(Side::Buy, &ticker.highest_bid);
+        for price in vec![&mut ticker.open_24h, &mut ticker.high_24h, &mut ticker.low_24h].into_iter().flatten() {
+            *price = p.price(price);
+        }
+        for volume in vec![&mut ticker.bid_size, &mut ticker.ask_size].into_iter().flatten() {
+            *volume = p.volume(volume);
+        }
+    }
+}
+
+pub fn normalize_fill(precisions: &Precisions, fill: &mut FillEvent) {
+    if let Some(p) = precisions.get(&fill.pair) {
+        fill.price = p.price(&fill.price);
+        fill.amount = p.volume(&fill.amount);
+    }
+}
+
+pub fn normalize_public_trade(precisions: &Precisions, trade: &mut PublicTrade) {
+    if let Some(p) = precisions.get(&trade.pair) {
+        trade.price = p.price(&trade.price);
+        trade.amount = p.volume(&trade.amount);
+    }
+}
+
+/// Round the prices and volumes of a live event, in place
+pub fn normalize_event(precisions: &Precisions, le: &mut LiveEvent) {
+    match le {
+        LiveEvent::LiveOrderbook(ob) => normalize_orderbook(precisions, ob),
+        LiveEvent::LiveOrderbookDelta(delta) => {
+            if let Some(p) = precisions.get(&delta.pair) {
+                for change in delta.changes.iter_mut() {
+                    change.price = p.side_price(change.side, &change.price);
+                    change.volume = p.volume(&change.volume);
+                }
+            }
+        }
+        LiveEvent::LiveTrade(t) => {
+            if let Some(p) = precisions.get(&t.pair) {
+                t.price = p.price(&t.price);
+                t.amount = p.volume(&t.amount);
+            }
+        }
+        LiveEvent::LiveOrder(o) => {
+            if let Some(p) = precisions.get(&o.pair) {
+                o.price = p.price(&o.price);
+                o.amount = p.volume(&o.amount);
+            }
+        }
+        LiveEvent::LiveDetailOrderbook(ob) => {
+            if let Some(p) = precisions.get(&ob.pair) {
+                for order in ob.asks.iter_mut() {
+                    order.price = p.side_price(Side::Sell, &order.price);
+                    order.amount = p.volume(&order.amount);
+                }
+                for order in ob.bids.iter_mut() {
+                    order.price = p.side_price(Side::Buy, &order.price);
+                    order.amount = p.volume(&order.amount);
+                }
+            }
+        }
+        LiveEvent::Fill(fill) => normalize_fill(precisions, fill),
+        _ => {}
+    }
+}
+
+/// Actor rounding the live events of a bot before forwarding them to its recipients
+pub struct PrecisionNormalizer {
+    precisions: Precisions,
+    recipients: Vec<Recipient<LiveEventEnveloppe>>,
+}
+
+impl PrecisionNormalizer {
+    pub fn new(precisions: Precisions, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Self {
+        PrecisionNormalizer { precisions, recipients }
+    }
+}
+
+impl Actor for PrecisionNormalizer {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for PrecisionNormalizer {
+    type Result = ();
+
+    fn handle(&mut self, mut msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        normalize_event(&self.precisions, &mut msg.1);
+        for r in &self.recipients {
+            if let Err(e) = r.do_send(msg.clone()) {
+                trace!("Precision : unable to send to recipient : {}", e);
+            }
+        }
+    }
+}
+
+/// An `ExchangeApi` rounding the prices and volumes of its market data and fills
+#[derive(Debug)]
+pub struct PreciseApi {
+    inner: Box<dyn ExchangeApi>,
+    precisions: Precisions,
+}
+
+impl PreciseApi {
+    pub fn new(inner: Box<dyn ExchangeApi>, precisions: Precisions) -> Self {
+        PreciseApi { inner, precisions }
+    }
+}
+
+#[async_trait]
+impl ExchangeApi for PreciseApi {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
+        let mut ticker = self.inner.ticker(pair).await?;
+        normalize_ticker(&self.precisions, &mut ticker);
+        Ok(ticker)
+    }
+
+    async fn tickers(&self, pairs: &[Pair]) -> Result<HashMap<Pair, Ticker>> {
+        let mut tickers = self.inner.tickers(pairs).await?;
+        for ticker in tickers.values_mut() {
+            normalize_ticker(&self.precisions, ticker);
+        }
+        Ok(tickers)
+    }
+
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
+        let mut book = self.inner.orderbook(pair).await?;
+        normalize_orderbook(&self.precisions, &mut book);
+        Ok(book)
+    }
+
+    async fn add_order(&self,
+                 order_type: OrderType,
+                 pair: Pair,
+                 quantity: Volume,
+                 price: Option<Price>)
+                 -> Result<OrderInfo> {
+        self.inner.add_order(order_type, pair, quantity, price).await
+    }
+
+    async fn add_conditional_order(&self,
+                             order_type: OrderType,
+                             pair: Pair,
+                             quantity: Volume,
+                             trigger_price: Price,
+                             price: Option<Price>)
+                             -> Result<OrderInfo> {
+        self.inner.add_conditional_order(order_type, pair, quantity, trigger_price, price).await
+    }
+
+    async fn add_ioc_order(&self,
+                           order_type: OrderType,
+                           pair: Pair,
+                           quantity: Volume,
+                           price: Price)
+                           -> Result<Execution> {
+        self.inner.add_ioc_order(order_type, pair, quantity, price).await
+    }
+
+    async fn add_order_with_id(&self,
+                               order_type: OrderType,
+                               pair: Pair,
+                               quantity: Volume,
+                               price: Option<Price>,
+                               client_id: &str)
+                               -> Result<OrderInfo> {
+        self.inner.add_order_with_id(order_type, pair, quantity, price, client_id).await
+    }
+
+    async fn order_by_client_id(&self, pair: Pair, client_id: &str) -> Result<Option<OrderInfo>> {
+        self.inner.order_by_client_id(pair, client_id).await
+    }
+
+    async fn cancel_order(&self, order: &OrderInfo) -> Result<()> {
+        self.inner.cancel_order(order).await
+    }
+
+    async fn cancel_all_orders(&self) -> Result<()> {
+        self.inner.cancel_all_orders().await
+    }
+
+    async fn set_cancel_after(&self, timeout: Duration) -> Result<()> {
+        self.inner.set_cancel_after(timeout).await
+    }
+
+    async fn amend_order(&self, order: &OrderInfo, new_price: Option<Price>, new_amount: Option<Volume>) -> Result<OrderInfo> {
+        self.inner.amend_order(order, new_price, new_amount).await
+    }
+
+    async fn balances(&self) -> Result<Balances> {
+        self.inner.balances().await
+    }
+
+    async fn verify_credentials(&self) -> Result<()> {
+        self.inner.verify_credentials().await
+    }
+
+    async fn detailed_balances(&self) -> Result<DetailedBalances> {
+        self.inner.detailed_balances().await
+    }
+
+    async fn fees(&self, pair: Pair) -> Result<FeeRates> {
+        self.inner.fees(pair).await
+    }
+
+    async fn withdraw(&self, currency: Currency, amount: Amount, destination: &str) -> Result<WithdrawalInfo> {
+        self.inner.withdraw(currency, amount, destination).await
+    }
+
+    async fn fills(&self, pair: Pair) -> Result<Vec<FillEvent>> {
+        let mut fills = self.inner.fills(pair).await?;
+        for fill in fills.iter_mut() {
+            normalize_fill(&self.precisions, fill);
+        }
+        Ok(fills)
+    }
+
+    async fn fills_page(&self, pair: Pair, cursor: Option<String>) -> Result<FillsPage> {
+        let mut page = self.inner.fills_page(pair, cursor).await?;
+        for fill in page.fills.iter_mut() {
+            normalize_fill(&self.precisions, fill);
+        }
+        Ok(page)
+    }
+
+    async fn public_trades(&self, pair: Pair, since: Option<DateTime<Utc>>) -> Result<Vec<PublicTrade>> {
+        let mut trades = self.inner.public_trades(pair, since).await?;
+        for trade in trades.iter_mut() {
+            normalize_public_trade(&self.precisions, trade);
+        }
+        Ok(trades)
+    }
+
+    async fn markets(&self) -> Result<Vec<Pair>> {
+        self.inner.markets().await
+    }
+
+    async fn time(&self) -> Result<i64> {
+        self.inner.time().await
+    }
+
+    async fn status(&self) -> Result<ExchangeStatus> {
+        self.inner.status().await
+    }
+
+    fn clock_skew(&self) -> Option<i64> {
+        self.inner.clock_skew()
+    }
+
+    fn limits(&self) -> RateLimitStatus {
+        self.inner.limits()
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        self.inner.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod precision_tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn live_events_are_rounded_to_the_tick_and_lot() {
+        assert_eq!(round_to_nearest(&dec("100.126"), &dec("0.01")), dec("100.13"));
+        assert_eq!(round_to_nearest(&dec("-100.124"), &dec("0.01")), dec("-100.12"));
+        let mut precisions = Precisions::new();
+        precisions.insert(Pair::BTC_USD, PairPrecision::new(dec("0.5"), dec("0.001")));
+        let mut le = LiveEvent::LiveOrderbook(Orderbook {
+            timestamp: 0,
+            pair: Pair::BTC_USD,
+            asks: vec![(dec("100.2600000000000000000000001"), dec("1.00049999"))],
+            bids: vec![(dec("99.74"), dec("0.0125"))],
+        });
+        normalize_event(&precisions, &mut le);
+        match le {
+            LiveEvent::LiveOrderbook(ob) => {
+                assert_eq!(ob.asks, vec![(dec("100.5"), dec("1.000"))]);
+                assert_eq!(ob.bids, vec![(dec("99.5"), dec("0.013"))]);
+                assert_eq!(ob.asks[0].0.to_string(), "100.5");
+            }
+            _ => panic!("not a book"),
+        }
+    }
+
+    #[test]
+    fn books_are_never_rounded_better() {
+        let mut precisions = Precisions::new();
+        precisions.insert(Pair::BTC_USD, PairPrecision::new(dec("0.5"), dec("0.01")));
+        let mut ob = Orderbook {
+            timestamp: 0,
+            pair: Pair::BTC_USD,
+            asks: vec![(dec("100.1"), dec("1")), (dec("100.4"), dec("0.5")), (dec("101"), dec("0.001"))],
+            bids: vec![(dec("99.9"), dec("2")), (dec("99.6"), dec("0.004")), (dec("99.4"), dec("1"))],
+        };
+        normalize_orderbook(&precisions, &mut ob);
+        assert_eq!(ob.asks, vec![(dec("100.5"), dec("1.50")), (dec("101"), dec("0.01"))]);
+        assert_eq!(ob.bids, vec![(dec("99.5"), dec("2.00")), (dec("99.0"), dec("1.00"))]);
+    }
+}
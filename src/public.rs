@@ -0,0 +1,178 @@
+//! Public market data over a pluggable HTTP transport.
+//! `PublicClient` queries the unauthenticated tickers and order books of the exchanges through an
+//! `HttpTransport` : hyper with the `native` feature, the fetch api of the browser on wasm32 or any
+//! other client of the application :
+//!
+//! ```ignore
+//! let client = PublicClient::new(FetchTransport::new());
+//! let ticker = client.ticker(Exchange::Kraken, Pair::BTC_USD).await?;
+//! ```
+//!
+//! Only Bitstamp and Kraken are supported, the other exchanges answer `Error::Unsupported`.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+#[cfg(feature = "native")]
+use hyper::Uri;
+use serde_json::{Map, Value};
+
+use crate::error::*;
+use crate::exchange::Exchange;
+#[cfg(feature = "native")]
+use crate::helpers::proxy::HttpClient;
+use crate::types::{Orderbook, Pair, Ticker};
+
+/// Levels of each side of the order books
+pub const BOOK_DEPTH: usize = 100;
+
+/// GET requests of a `PublicClient`. Futures are not `Send`, so that a browser client fits.
+#[async_trait(?Send)]
+pub trait HttpTransport {
+    /// The body of the response to a GET of `url`
+    async fn get(&self, url: &str) -> Result<Vec<u8>>;
+}
+
+#[cfg(feature = "native")]
+#[async_trait(?Send)]
+impl HttpTransport for HttpClient {
+    async fn get(&self, url: &str) -> Result<Vec<u8>> {
+        let uri: Uri = url.parse().map_err(|_| Error::BadParse)?;
+        let response = HttpClient::get(self, uri).await?;
+        Ok(hyper::body::to_bytes(response.into_body()).await?.to_vec())
+    }
+}
+
+/// GET requests through the fetch api of the browser. The exchanges must allow the origin of the
+/// page, or be queried through a CORS proxy with `PublicClient::with_root`.
+#[cfg(target_arch = "wasm32")]
+#[derive(Clone, Debug, Default)]
+pub struct FetchTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl FetchTransport {
+    pub fn new() -> Self {
+        FetchTransport { client: reqwest::Client::new() }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[async_trait(?Send)]
+impl HttpTransport for FetchTransport {
+    async fn get(&self, url: &str) -> Result<Vec<u8>> {
+        let response = self.client.get(url).send().await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+pub struct PublicClient<T> {
+    transport: T,
+    roots: HashMap<Exchange, String>,
+}
+
+impl<T: HttpTransport> PublicClient<T> {
+    pub fn new(transport: T) -> Self {
+        PublicClient { transport, roots: HashMap::new() }
+    }
+
+    /// Query `exchange` at `root` instead of its production api, e.g. through a CORS proxy
+    pub fn with_root(mut self, exchange: Exchange, root: &str) -> Self {
+        self.roots.insert(exchange, root.trim_end_matches('/').to_string());
+        self
+    }
+
+    #[allow(unused_variables)]
+    fn root(&self, exchange: Exchange) -> Result<String> {
+        if let Some(root) = self.roots.get(&exchange) {
+            return Ok(root.clone());
+        }
+        match exchange {
+            #[cfg(feature = "bitstamp")]
+            Exchange::Bitstamp => Ok(crate::bitstamp::utils::API_URL.to_string()),
+            #[cfg(feature = "kraken")]
+            Exchange::Kraken => Ok(crate::kraken::utils::API_URL.to_string()),
+            _ => Err(Error::Unsupported),
+        }
+    }
+
+    async fn get_json(&self, url: &str) -> Result<Map<String, Value>> {
+        match serde_json::from_slice(&self.transport.get(url).await?)? {
+            Value::Object(map) => Ok(map),
+            _ => Err(Error::BadParse),
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub async fn ticker(&self, exchange: Exchange, pair: Pair) -> Result<Ticker> {
+        let root = self.root(exchange)?;
+        match exchange {
+            #[cfg(feature = "bitstamp")]
+            Exchange::Bitstamp => {
+                use crate::bitstamp::utils;
+                let name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+                let response = self.get_json(&utils::build_url(&root, "ticker", name)).await?;
+                utils::parse_ticker(pair, &utils::parse_result(&response)?, false)
+            }
+            #[cfg(feature = "kraken")]
+            Exchange::Kraken => {
+                use crate::kraken::utils;
+                let name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+                let response = self.get_json(&format!("{}/0/public/Ticker?pair={}", root, name)).await?;
+                utils::parse_ticker(pair, &utils::parse_result(&response)?[*name], false)
+            }
+            _ => Err(Error::Unsupported),
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub async fn order_book(&self, exchange: Exchange, pair: Pair) -> Result<Orderbook> {
+        let root = self.root(exchange)?;
+        match exchange {
+            #[cfg(feature = "bitstamp")]
+            Exchange::Bitstamp => {
+                use crate::bitstamp::utils;
+                let name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+                let response = self.get_json(&utils::build_url(&root, "order_book", name)).await?;
+                let mut book = utils::parse_orderbook(pair, &utils::parse_result(&response)?)?;
+                book.asks.truncate(BOOK_DEPTH);
+                book.bids.truncate(BOOK_DEPTH);
+                Ok(book)
+            }
+            #[cfg(feature = "kraken")]
+            Exchange::Kraken => {
+                use crate::kraken::utils;
+                let name = utils::get_pair_string(&pair).ok_or(Error::PairUnsupported)?;
+                let response = self.get_json(&format!("{}/0/public/Depth?pair={}&count={}", root, name, BOOK_DEPTH)).await?;
+                utils::parse_orderbook(pair, &utils::parse_result(&response)?[*name])
+            }
+            _ => Err(Error::Unsupported),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "kraken", feature = "native"))]
+mod public_tests {
+    use super::*;
+
+    /// Answers the same body to every request
+    struct Canned(Value);
+
+    #[async_trait(?Send)]
+    impl HttpTransport for Canned {
+        async fn get(&self, _url: &str) -> Result<Vec<u8>> {
+            Ok(self.0.to_string().into_bytes())
+        }
+    }
+
+    #[test]
+    fn parses_over_any_transport() {
+        let depth = serde_json::json!({"error": [], "result": {"XXBTZUSD": {"asks": [["101.0", "1.0", 1]], "bids": [["99.0", "2.0", 1]]}}});
+        let client = PublicClient::new(Canned(depth));
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let book = rt.block_on(client.order_book(Exchange::Kraken, Pair::BTC_USD)).unwrap();
+        assert_eq!(book.asks.len(), 1);
+        assert!(rt.block_on(client.ticker(Exchange::Gdax, Pair::BTC_USD)).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+//! Forward live events to message queues, for consumers not written in Rust.
+//! `QueueSink` is a recipient of `LiveEventEnveloppe`s publishing each event through a
+//! `Publisher`, one per broker : Kafka (`kafka-sink` feature), NATS (`nats-sink`) and Redis pub/sub
+//! (`redis-sink`). Events are published to the subject `{prefix}.{exchange}.{event}`, e.g.
+//! `coinnect.kraken.trade`, keyed by pair, as JSON objects of a versioned schema :
+//!
+//! ```json
+//! {"version": 1, "exchange": "kraken", "event": "trade", "pair": "BTC_EUR", "timestamp": 1594596023162, "data": {..}}
+//! ```
+//!
+//! `data` holds the fields of the event type, fields may be added within a version but are never
+//! removed or renamed. Prices, volumes and fees are decimal strings, sides are `buy` or `sell`,
+//! levels are `[price, volume]` arrays :
+//!
+//! | event | `data` fields |
+//! |---|---|
+//! | `trade` | `price`, `amount`, `side`, `trade_id` (nullable), `gap_detected` |
+//! | `order` | `id` (nullable), `kind` (`created`, `changed`, `deleted` or `unknown`), `price`, `amount`, `side` |
+//! | `orderbook` | `asks` by ascending price, `bids` by descending price |
+//! | `orderbook_delta` | `sequence`, `changes` : objects with `side` (`bid` or `ask`), `price`, `volume`, 0 to delete the level |
+//! | `detail_orderbook` | `asks` and `bids` : objects with `id`, `price`, `amount` |
+//! | `candle` | `interval_secs`, `open`, `high`, `low`, `close`, `volume`, `closed` |
+//! | `summary` | `high`, `low`, `last`, `bid`, `ask`, `volume` |
+//! | `fill` | `order_id`, `trade_id` (nullable), `side`, `price`, `amount`, `fee`, `fee_currency` (nullable), `liquidity` (`maker`, `taker` or `unknown`) |
+//!
+//! ```ignore
+//! let sink = QueueSink::new(NatsPublisher::connect("nats://localhost:4222")?, "coinnect").start();
+//! Coinnect::new_stream(Exchange::Kraken, creds, settings, vec![sink.recipient()]).await?;
+//! ```
+
+use actix::{Actor, Context, Handler};
+use serde_json::{json, Value};
+
+use crate::error::*;
+use crate::types::*;
+
+/// Version of the JSON schema of the published events
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A connection to a broker
+pub trait Publisher {
+    /// Publish `payload` to `subject`, `key` is the pair of the event
+    fn publish(&mut self, subject: &str, key: &str, payload: &[u8]) -> Result<()>;
+}
+
+#[derive(Serialize)]
+struct QueueEvent<'a> {
+    version: u32,
+    exchange: &'a str,
+    event: &'static str,
+    pair: String,
+    timestamp: Option<i64>,
+    data: Value,
+}
+
+/// Name and pair of the events that are published
+fn describe(le: &LiveEvent) -> Option<(&'static str, String)> {
+    match le {
+        LiveEvent::LiveOrder(o) => Some(("order", format!("{:?}", o.pair))),
+        LiveEvent::LiveTrade(t) => Some(("trade", format!("{:?}", t.pair))),
+        LiveEvent::LiveOrderbook(ob) => Some(("orderbook", format!("{:?}", ob.pair))),
+        LiveEvent::LiveOrderbookDelta(d) => Some(("orderbook_delta", format!("{:?}", d.pair))),
+        LiveEvent::LiveDetailOrderbook(ob) => Some(("detail_orderbook", format!("{:?}", ob.pair))),
+        LiveEvent::LiveCandle(c) => Some(("candle", c.pair.clone())),
+        LiveEvent::LiveSummary(s) => Some(("summary", s.pair.clone())),
+        LiveEvent::Fill(f) => Some(("fill", format!("{:?}", f.pair))),
+        LiveEvent::SubscriptionResult(_) | LiveEvent::Noop => None,
+    }
+}
+
+fn side(tt: &TradeType) -> String {
+    match tt {
+        TradeType::Buy => "buy".to_string(),
+        TradeType::Sell => "sell".to_string(),
+        TradeType::Unknown(s) => s.to_lowercase(),
+    }
+}
+
+fn levels(levels: &[(Price, Volume)]) -> Value {
+    levels.iter().map(|(price, volume)| json!([price.to_string(), volume.to_string()])).collect()
+}
+
+fn book_orders(orders: &[BookOrder]) -> Value {
+    orders.iter().map(|o| json!({"id": o.id, "price": o.price.to_string(), "amount": o.amount.to_string()})).collect()
+}
+
+/// The `data` of a published event, built field by field so that the schema does not follow the
+/// Rust types
+fn data(le: &LiveEvent) -> Value {
+    match le {
+        LiveEvent::LiveTrade(t) => json!({
+            "price": t.price.to_string(),
+            "amount": t.amount.to_string(),
+            "side": side(&t.tt),
+            "trade_id": t.meta.trade_id,
+            "gap_detected": t.meta.gap_detected,
+        }),
+        LiveEvent::LiveOrder(o) => json!({
+            "id": o.id,
+            "kind": format!("{:?}", o.kind).to_lowercase(),
+            "price": o.price.to_string(),
+            "amount": o.amount.to_string(),
+            "side": side(&o.tt),
+        }),
+        LiveEvent::LiveOrderbook(ob) => json!({"asks": levels(&ob.asks), "bids": levels(&ob.bids)}),
+        LiveEvent::LiveOrderbookDelta(d) => json!({
+            "sequence": d.sequence,
+            "changes": d.changes.iter().map(|c| {
+                let side = match c.side { Side::Buy => "bid", Side::Sell => "ask" };
+                json!({"side": side, "price": c.price.to_string(), "volume": c.volume.to_string()})
+            }).collect::<Value>(),
+        }),
+        LiveEvent::LiveDetailOrderbook(ob) => json!({"asks": book_orders(&ob.asks), "bids": book_orders(&ob.bids)}),
+        LiveEvent::LiveCandle(c) => json!({
+            "interval_secs": c.interval_secs,
+            "open": c.open.to_string(),
+            "high": c.high.to_string(),
+            "low": c.low.to_string(),
+            "close": c.close.to_string(),
+            "volume": c.volume.to_string(),
+            "closed": c.closed,
+        }),
+        LiveEvent::LiveSummary(s) => json!({
+            "high": s.high.to_string(),
+            "low": s.low.to_string(),
+            "last": s.last.to_string(),
+            "bid": s.bid.to_string(),
+            "ask": s.ask.to_string(),
+            "volume": s.volume.to_string(),
+        }),
+        LiveEvent::Fill(f) => json!({
+            "order_id": f.order_id,
+            "trade_id": f.trade_id,
+            "side": side(&f.side),
+            "price": f.price.to_string(),
+            "amount": f.amount.to_string(),
+            "fee": f.fee.to_string(),
+            "fee_currency": f.fee_currency.map(|c| format!("{:?}", c)),
+            "liquidity": format!("{:?}", f.liquidity).to_lowercase(),
+        }),
+        LiveEvent::SubscriptionResult(_) | LiveEvent::Noop => Value::Null,
+    }
+}
+
+/// Subject, key and JSON payload of an event, `None` for the events that are not published
+pub fn encode(prefix: &str, e: &LiveEventEnveloppe) -> Result<Option<(String, String, Vec<u8>)>> {
+    let (event, pair) = match describe(&e.1) {
+        Some(d) => d,
+        None => return Ok(None),
+    };
+    let exchange: String = e.0.into();
+    let exchange = exchange.to_lowercase();
+    let payload = serde_json::to_vec(&QueueEvent { version: SCHEMA_VERSION, exchange: &exchange, event, pair: pair.clone(), timestamp: e.1.timestamp(), data: data(&e.1) })?;
+    Ok(Some((format!("{}.{}.{}", prefix, exchange, event), pair, payload)))
+}
+
+/// Actor publishing the live events it receives, publication errors are logged
+pub struct QueueSink<P: Publisher> {
+    publisher: P,
+    prefix: String,
+}
+
+impl<P: Publisher> QueueSink<P> {
+    pub fn new(publisher: P, prefix: &str) -> Self {
+        QueueSink { publisher, prefix: prefix.to_string() }
+    }
+}
+
+impl<P: Publisher + Unpin + 'static> Actor for QueueSink<P> {
+    type Context = Context<Self>;
+}
+
+impl<P: Publisher + Unpin + 'static> Handler<LiveEventEnveloppe> for QueueSink<P> {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        let published = encode(&self.prefix, &msg).and_then(|encoded| match encoded {
+            Some((subject, key, payload)) => self.publisher.publish(&subject, &key, &payload),
+            None => Ok(()),
+        });
+        if let Err(e) = published {
+            error!("Queue sink : unable to publish an event : {}", e);
+        }
+    }
+}
+
+#[cfg(feature = "kafka-sink")]
+pub use self::kafka::KafkaPublisher;
+#[cfg(feature = "nats-sink")]
+pub use self::nats_publisher::NatsPublisher;
+#[cfg(feature = "redis-sink")]
+pub use self::redis_publisher::RedisPublisher;
+
+#[cfg(feature = "kafka-sink")]
+mod kafka {
+    use rdkafka::config::ClientConfig;
+    use rdkafka::producer::{BaseRecord, DefaultProducerContext, ThreadedProducer};
+
+    use super::Publisher;
+    use crate::error::*;
+
+    /// Kafka producer, messages are queued and sent by a background thread
+    pub struct KafkaPublisher(ThreadedProducer<DefaultProducerContext>);
+
+    impl KafkaPublisher {
+        /// `brokers` is a comma separated list of `host:port`
+        pub fn connect(brokers: &str) -> Result<Self> {
+            ClientConfig::new()
+                .set("bootstrap.servers", brokers)
+                .create()
+                .map(KafkaPublisher)
+                .map_err(|e| Error::Queue(e.to_string()))
+        }
+    }
+
+    impl Publisher for KafkaPublisher {
+        fn publish(&mut self, subject: &str, key: &str, payload: &[u8]) -> Result<()> {
+            self.0.send(BaseRecord::to(subject).key(key).payload(payload))
+                .map_err(|(e, _)| Error::Queue(e.to_string()))
+        }
+    }
+}
+
+#[cfg(feature = "nats-sink")]
+mod nats_publisher {
+    use super::Publisher;
+    use crate::error::*;
+
+    /// NATS connection, publications are buffered by the client
+    pub struct NatsPublisher(nats::Connection);
+
+    impl NatsPublisher {
+        pub fn connect(url: &str) -> Result<Self> {
+            Ok(NatsPublisher(nats::connect(url)?))
+        }
+    }
+
+    impl Publisher for NatsPublisher {
+        fn publish(&mut self, subject: &str, _key: &str, payload: &[u8]) -> Result<()> {
+            Ok(self.0.publish(subject, payload)?)
+        }
+    }
+}
+
+#[cfg(feature = "redis-sink")]
+mod redis_publisher {
+    use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+    use std::thread;
+
+    use super::Publisher;
+    use crate::error::*;
+
+    /// Publications waiting for the background thread, newer ones are refused when it is full
+    pub const REDIS_QUEUE: usize = 10_000;
+
+    /// Redis connection publishing to pub/sub channels. Publications are queued and sent by a
+    /// background thread, so that the round trips to the server never block the actix arbiter.
+    pub struct RedisPublisher(SyncSender<(String, Vec<u8>)>);
+
+    impl RedisPublisher {
+        pub fn connect(url: &str) -> Result<Self> {
+            let mut connection = redis::Client::open(url)
+                .and_then(|client| client.get_connection())
+                .map_err(|e| Error::Queue(e.to_string()))?;
+            let (tx, rx) = sync_channel::<(String, Vec<u8>)>(REDIS_QUEUE);
+            thread::Builder::new()
+                .name("coinnect-redis".to_string())
+                .spawn(move || {
+                    for (subject, payload) in rx {
+                        let published = redis::cmd("PUBLISH").arg(&subject).arg(payload).query::<i64>(&mut connection);
+                        if let Err(e) = published {
+                            error!("Queue sink : unable to publish to {} : {}", subject, e);
+                        }
+                    }
+                })
+                .map_err(|e| Error::Queue(e.to_string()))?;
+            Ok(RedisPublisher(tx))
+        }
+    }
+
+    impl Publisher for RedisPublisher {
+        fn publish(&mut self, subject: &str, _key: &str, payload: &[u8]) -> Result<()> {
+            self.0.try_send((subject.to_string(), payload.to_vec())).map_err(|e| match e {
+                TrySendError::Full(_) => Error::Queue("redis publications are late, event dropped".to_string()),
+                TrySendError::Disconnected(_) => Error::Queue("redis publisher stopped".to_string()),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod queues_tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use crate::exchange::Exchange;
+
+    #[derive(Default)]
+    struct Published(Vec<(String, String, Value)>);
+
+    impl Publisher for Published {
+        fn publish(&mut self, subject: &str, key: &str, payload: &[u8]) -> Result<()> {
+            self.0.push((subject.to_string(), key.to_string(), serde_json::from_slice(payload)?));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn events_follow_the_versioned_schema() {
+        let trade = LiveEvent::LiveTrade(LiveTrade {
+            event_ms: 1_000,
+            pair: Pair::BTC_EUR,
+            amount: BigDecimal::from(2),
+            price: BigDecimal::from(100),
+            tt: TradeType::Buy,
+            meta: TradeMeta::default(),
+        });
+        let mut publisher = Published::default();
+        for le in vec![trade, LiveEvent::Noop] {
+            if let Some((subject, key, payload)) = encode("coinnect", &LiveEventEnveloppe(Exchange::Kraken, le)).unwrap() {
+                publisher.publish(&subject, &key, &payload).unwrap();
+            }
+        }
+        assert_eq!(publisher.0.len(), 1);
+        let (subject, key, json) = &publisher.0[0];
+        assert_eq!(subject, "coinnect.kraken.trade");
+        assert_eq!(key, "BTC_EUR");
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["timestamp"], 1_000);
+        assert_eq!(json["data"], json!({"price": "100", "amount": "2", "side": "buy", "trade_id": null, "gap_detected": false}));
+    }
+}
@@ -0,0 +1,147 @@
+//! Bring a portfolio back to target weights.
+//! The `Rebalancer` values every currency in a common quote currency, and generates one trade
+//! against the quote currency for each currency whose weight drifted outside the tolerance band.
+
+use std::collections::HashMap;
+
+use bigdecimal::{BigDecimal, Signed, Zero};
+
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeApi};
+use crate::types::*;
+use crate::utils;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RebalanceTrade {
+    pub currency: Currency,
+    /// Buy or Sell `currency` against the quote currency
+    pub tt: TradeType,
+    /// Amount of `currency` to trade
+    pub amount: Amount,
+    /// Value of the trade in quote currency
+    pub value: Amount,
+}
+
+#[derive(Debug)]
+pub struct Rebalancer {
+    /// Target weight of each currency, weights should add up to 1
+    pub targets: HashMap<Currency, BigDecimal>,
+    /// Currencies within `target ± tolerance` are left untouched
+    pub tolerance: BigDecimal,
+    /// Currency every position is valued and traded against
+    pub quote: Currency,
+}
+
+/// Sum the balances of every exchange
+pub fn total_balances(portfolio: &HashMap<Exchange, Balances>) -> Balances {
+    let mut total = Balances::new();
+    for balances in portfolio.values() {
+        for (currency, amount) in balances {
+            let current = total.get(currency).cloned().unwrap_or_else(BigDecimal::zero);
+            total.insert(*currency, current + amount.clone());
+        }
+    }
+    total
+}
+
+/// Fetch the balances of every exchange
+pub async fn portfolio(apis: &HashMap<Exchange, Box<dyn ExchangeApi>>) -> Result<HashMap<Exchange, Balances>> {
+    let mut portfolio = HashMap::new();
+    for (exchange, api) in apis.iter() {
+        portfolio.insert(*exchange, api.balances().await?);
+    }
+    Ok(portfolio)
+}
+
+impl Rebalancer {
+    pub fn new(targets: HashMap<Currency, BigDecimal>, tolerance: BigDecimal, quote: Currency) -> Self {
+        Rebalancer { targets, tolerance, quote }
+    }
+
+    /// Trades needed to bring `balances` within the tolerance bands.
+    /// `prices` gives the price of each currency in quote currency, the quote currency itself does
+    /// not need a price.
+    pub fn plan(&self, balances: &Balances, prices: &HashMap<Currency, Price>) -> Result<Vec<RebalanceTrade>> {
+        let price_of = |c: &Currency| -> Result<Price> {
+            if *c == self.quote {
+                Ok(BigDecimal::from(1))
+            } else {
+                prices.get(c).cloned().ok_or_else(|| Error::MissingPrice)
+            }
+        };
+
+        let mut total = BigDecimal::zero();
+        for (currency, amount) in balances {
+            if amount.is_zero() || (!self.targets.contains_key(currency) && *currency != self.quote) {
+                continue;
+            }
+            total = total + amount.clone() * price_of(currency)?;
+        }
+        if total.is_zero() {
+            return Ok(vec![]);
+        }
+
+        let mut trades = vec![];
+        for (currency, target) in &self.targets {
+            if *currency == self.quote {
+                continue;
+            }
+            let price = price_of(currency)?;
+            let held = balances.get(currency).cloned().unwrap_or_else(BigDecimal::zero);
+            let value = held * price.clone();
+            let weight = value.clone() / total.clone();
+            if (weight - target.clone()).abs() <= self.tolerance {
+                continue;
+            }
+            let diff = target.clone() * total.clone() - value;
+            let tt = if diff.is_positive() { TradeType::Buy } else { TradeType::Sell };
+            let value = diff.abs();
+            trades.push(RebalanceTrade { currency: *currency, tt, amount: value.clone() / price, value });
+        }
+        // Sell first so that buys can be funded with the proceeds
+        trades.sort_by_key(|t| if t.tt == TradeType::Sell { 0 } else { 1 });
+        Ok(trades)
+    }
+
+    /// Send the planned trades as market orders
+    pub async fn execute(&self, trades: &[RebalanceTrade], api: &dyn ExchangeApi) -> Result<Vec<OrderInfo>> {
+        let mut infos = Vec::with_capacity(trades.len());
+        for trade in trades {
+            let pair = utils::currencies_pair(trade.currency, self.quote).ok_or(Error::PairUnsupported)?;
+            let order_type = if trade.tt == TradeType::Buy { OrderType::BuyMarket } else { OrderType::SellMarket };
+            infos.push(api.add_order(order_type, pair, trade.amount.clone(), None).await?);
+        }
+        Ok(infos)
+    }
+}
+
+#[cfg(test)]
+mod rebalancer_tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn plan_trades_outside_tolerance() {
+        let targets = vec![(Currency::BTC, dec("0.5")), (Currency::ETH, dec("0.25")), (Currency::USD, dec("0.25"))].into_iter().collect();
+        let rebalancer = Rebalancer::new(targets, dec("0.05"), Currency::USD);
+        let balances = vec![(Currency::BTC, dec("1")), (Currency::ETH, dec("10")), (Currency::USD, dec("0"))].into_iter().collect();
+        let prices = vec![(Currency::BTC, dec("1000")), (Currency::ETH, dec("100"))].into_iter().collect();
+        let trades = rebalancer.plan(&balances, &prices).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].currency, Currency::ETH);
+        assert_eq!(trades[0].tt, TradeType::Sell);
+        assert_eq!(trades[0].amount, dec("5"));
+    }
+
+    #[test]
+    fn total_balances_sums_exchanges() {
+        let mut portfolio = HashMap::new();
+        portfolio.insert(Exchange::Kraken, vec![(Currency::BTC, dec("1"))].into_iter().collect());
+        portfolio.insert(Exchange::Bitstamp, vec![(Currency::BTC, dec("0.5"))].into_iter().collect());
+        assert_eq!(total_balances(&portfolio)[&Currency::BTC], dec("1.5"));
+    }
+}
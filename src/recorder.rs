@@ -0,0 +1,175 @@
+//! Persist live events to disk so that they can be replayed later with `ReplayBot`.
+//! Files are rotated when they grow past a size or get older than a maximum age.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use actix::{Actor, ActorContext, AsyncContext, Context, Handler};
+use chrono::Utc;
+
+use crate::codec::EventFormat;
+use crate::error::*;
+use crate::exchange_bot::Shutdown;
+use crate::types::LiveEventEnveloppe;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Rotation {
+    /// Start a new file once the current one is larger than this many bytes
+    pub max_bytes: Option<u64>,
+    /// Start a new file once the current one was opened this many seconds ago
+    pub max_age_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecorderSettings {
+    /// Directory the files are written to, created if missing
+    pub dir: PathBuf,
+    /// Files are named `{prefix}-{date}-{index}.{extension}`
+    pub prefix: String,
+    pub format: EventFormat,
+    #[serde(default)]
+    pub rotation: Rotation,
+}
+
+struct RecordFile {
+    writer: BufWriter<File>,
+    bytes: u64,
+    opened_at: Instant,
+}
+
+pub struct RecorderActor {
+    settings: RecorderSettings,
+    current: Option<RecordFile>,
+    index: u32,
+}
+
+impl RecorderActor {
+    pub fn new(settings: RecorderSettings) -> Result<Self> {
+        fs::create_dir_all(&settings.dir)?;
+        Ok(RecorderActor { settings, current: None, index: 0 })
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.settings.format {
+            EventFormat::JsonLines => "jsonl",
+            EventFormat::Csv => "csv",
+        }
+    }
+
+    fn should_rotate(&self, f: &RecordFile) -> bool {
+        let rotation = &self.settings.rotation;
+        rotation.max_bytes.map_or(false, |max| f.bytes >= max)
+            || rotation.max_age_secs.map_or(false, |max| f.opened_at.elapsed() >= Duration::from_secs(max))
+    }
+
+    fn open(&mut self) -> Result<RecordFile> {
+        self.index += 1;
+        let name = format!("{}-{}-{}.{}", self.settings.prefix, Utc::now().format("%Y%m%d%H%M%S"), self.index, self.extension());
+        let path = self.settings.dir.join(name);
+        info!("Recorder : writing to {:?}", path);
+        let mut writer = BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?);
+        let mut bytes = 0;
+        if let Some(header) = self.settings.format.header() {
+            writeln!(writer, "{}", header)?;
+            bytes += header.len() as u64 + 1;
+        }
+        Ok(RecordFile { writer, bytes, opened_at: Instant::now() })
+    }
+
+    fn write(&mut self, le: &LiveEventEnveloppe) -> Result<()> {
+        let line = match self.settings.format.encode(le)? {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        if self.current.as_ref().map_or(true, |f| self.should_rotate(f)) {
+            self.flush();
+            self.current = Some(self.open()?);
+        }
+        let f = self.current.as_mut().unwrap();
+        writeln!(f.writer, "{}", line)?;
+        f.bytes += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        if let Some(f) = self.current.as_mut() {
+            if let Err(e) = f.writer.flush() {
+                error!("Recorder : unable to flush : {}", e);
+            }
+        }
+    }
+}
+
+impl Actor for RecorderActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(FLUSH_INTERVAL, |act, _| act.flush());
+    }
+
+    fn stopped(&mut self, _: &mut Context<Self>) {
+        self.flush();
+    }
+}
+
+impl Handler<Shutdown> for RecorderActor {
+    type Result = ();
+
+    fn handle(&mut self, _msg: Shutdown, ctx: &mut Context<Self>) {
+        self.flush();
+        ctx.stop();
+    }
+}
+
+impl Handler<LiveEventEnveloppe> for RecorderActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        if let Err(e) = self.write(&msg) {
+            error!("Recorder : unable to record event : {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod recorder_tests {
+    use super::*;
+
+    #[test]
+    fn recorded_events_are_read_back() {
+        let dir = std::env::temp_dir().join(format!("coinnect-recorder-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let settings = RecorderSettings {
+            dir: dir.clone(),
+            prefix: "test".to_string(),
+            format: EventFormat::Csv,
+            rotation: Rotation { max_bytes: Some(1), max_age_secs: None },
+        };
+        let lines = vec![
+            "Bitstamp,trade,1577146143220,BTC_USD,7312.91,0.5,Buy,,",
+            "Bitstamp,trade,1577146143221,BTC_USD,7313,0.00000001,Sell,,",
+        ];
+        let mut recorder = RecorderActor::new(settings).unwrap();
+        for line in &lines {
+            recorder.write(&EventFormat::Csv.decode(line).unwrap()).unwrap();
+        }
+        recorder.flush();
+
+        // Every event is past the size limit, hence one file each
+        let mut files: Vec<PathBuf> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        files.sort();
+        assert_eq!(files.len(), 2);
+        let recorded: Vec<String> = files.iter()
+            .flat_map(|f| fs::read_to_string(f).unwrap().lines().skip(1).map(|l| l.to_string()).collect::<Vec<_>>())
+            .collect();
+        let decoded: Vec<String> = recorded.iter()
+            .map(|l| EventFormat::Csv.encode(&EventFormat::Csv.decode(l).unwrap()).unwrap().unwrap())
+            .collect();
+        assert_eq!(decoded, lines);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
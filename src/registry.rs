@@ -0,0 +1,229 @@
+//! Registration of exchanges implemented outside of this crate.
+//! A downstream crate implements `ExchangeApi` and `ExchangeBot` for a venue coinnect does not
+//! support, then registers their factories under a name. `Coinnect::new` and
+//! `Coinnect::new_stream` build them for `Exchange::Custom(name)` like any other exchange :
+//!
+//! ```ignore
+//! let exchange = CustomExchange::new("MyVenue")
+//!     .api(|creds| Ok(Box::new(MyVenueApi::new(creds)?)))
+//!     .stream(|creds, params| Box::pin(MyVenueBot::new(creds.name(), params)))
+//!     .register();
+//! let api = Coinnect::new(exchange, creds)?;
+//! ```
+//!
+//! Names are case-insensitive, `Exchange::from_str("myvenue")` finds the exchange once registered.
+
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::collections::HashSet;
+use std::sync::RwLock;
+#[cfg(feature = "native")]
+use std::sync::Arc;
+
+#[cfg(feature = "native")]
+use actix::Recipient;
+use bigdecimal::{BigDecimal, Zero};
+#[cfg(feature = "native")]
+use futures::future::LocalBoxFuture;
+
+#[cfg(feature = "native")]
+use crate::coinnect::Credentials;
+#[cfg(feature = "native")]
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeCapabilities};
+#[cfg(feature = "native")]
+use crate::exchange::ExchangeApi;
+#[cfg(feature = "native")]
+use crate::exchange_bot::{ExchangeBot, RawFrameEvent};
+#[cfg(feature = "native")]
+use crate::helpers::delivery::DeliveryPolicy;
+use crate::types::{FeeRates, Pair};
+#[cfg(feature = "native")]
+use crate::types::{Channel, LiveEventEnveloppe, WarmUp};
+
+/// Builds the REST client of a custom exchange
+#[cfg(feature = "native")]
+pub type ApiFactory = dyn Fn(&dyn Credentials) -> Result<Box<dyn ExchangeApi>> + Send + Sync;
+
+/// Builds the streaming bot of a custom exchange
+#[cfg(feature = "native")]
+pub type StreamFactory = dyn Fn(&dyn Credentials, StreamParams) -> LocalBoxFuture<'static, Result<Box<dyn ExchangeBot>>> + Send + Sync;
+
+/// What a streaming bot is built with, as resolved from the `ExchangeSettings` by `Coinnect::new_stream`
+#[cfg(feature = "native")]
+pub struct StreamParams {
+    /// Pairs to subscribe to for each channel, candles are aggregated from trades
+    pub channels: HashMap<Channel, HashSet<Pair>>,
+    pub warm_up: WarmUp,
+    pub depth: Option<i8>,
+    pub delivery: DeliveryPolicy,
+    pub recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    /// Recipient of the websocket frames as received, see `RawFrameObserver`
+    pub raw_frames: Option<Recipient<RawFrameEvent>>,
+}
+
+struct Registration {
+    name: &'static str,
+    #[cfg(feature = "native")]
+    api: Option<Arc<ApiFactory>>,
+    #[cfg(feature = "native")]
+    stream: Option<Arc<StreamFactory>>,
+    fees: Option<FeeRates>,
+    capabilities: ExchangeCapabilities,
+    pairs: fn(&Pair) -> Option<&&str>,
+}
+
+lazy_static! {
+    static ref REGISTRY: RwLock<HashMap<String, Registration>> = RwLock::new(HashMap::new());
+}
+
+/// Every pair is supported unless the exchange says otherwise, under no native name
+static ANY_PAIR: &str = "";
+
+fn any_pair(_pair: &Pair) -> Option<&&str> {
+    Some(&ANY_PAIR)
+}
+
+/// Builder of a custom exchange registration
+pub struct CustomExchange {
+    name: String,
+    #[cfg(feature = "native")]
+    api: Option<Arc<ApiFactory>>,
+    #[cfg(feature = "native")]
+    stream: Option<Arc<StreamFactory>>,
+    fees: Option<FeeRates>,
+    capabilities: ExchangeCapabilities,
+    pairs: fn(&Pair) -> Option<&&str>,
+}
+
+impl CustomExchange {
+    pub fn new(name: &str) -> Self {
+        CustomExchange {
+            name: name.to_string(),
+            #[cfg(feature = "native")]
+            api: None,
+            #[cfg(feature = "native")]
+            stream: None,
+            fees: None,
+            capabilities: ExchangeCapabilities::default(),
+            pairs: any_pair,
+        }
+    }
+
+    /// Factory of the REST client, used by `Coinnect::new`
+    #[cfg(feature = "native")]
+    pub fn api<F>(mut self, factory: F) -> Self
+        where F: Fn(&dyn Credentials) -> Result<Box<dyn ExchangeApi>> + Send + Sync + 'static {
+        self.api = Some(Arc::new(factory));
+        self
+    }
+
+    /// Factory of the streaming bot, used by `Coinnect::new_stream`
+    #[cfg(feature = "native")]
+    pub fn stream<F>(mut self, factory: F) -> Self
+        where F: Fn(&dyn Credentials, StreamParams) -> LocalBoxFuture<'static, Result<Box<dyn ExchangeBot>>> + Send + Sync + 'static {
+        self.stream = Some(Arc::new(factory));
+        self
+    }
+
+    /// Fee rates of the lowest volume tier, zero if not set
+    pub fn fees(mut self, fees: FeeRates) -> Self {
+        self.fees = Some(fees);
+        self
+    }
+
+    /// What the clients support, nothing if not set
+    pub fn capabilities(mut self, capabilities: ExchangeCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Native names of the supported pairs, pairs without a name are left out of the subscriptions
+    pub fn pairs(mut self, pairs: fn(&Pair) -> Option<&&str>) -> Self {
+        self.pairs = pairs;
+        self
+    }
+
+    /// Register the exchange, replacing the factories of a previous registration under the same name
+    pub fn register(self) -> Exchange {
+        let key = self.name.to_lowercase();
+        let mut registry = REGISTRY.write().unwrap();
+        // Names are leaked once, so that `Exchange` stays `Copy`
+        let name = match registry.get(&key) {
+            Some(previous) => previous.name,
+            None => Box::leak(self.name.into_boxed_str()),
+        };
+        registry.insert(key, Registration {
+            name,
+            #[cfg(feature = "native")]
+            api: self.api,
+            #[cfg(feature = "native")]
+            stream: self.stream,
+            fees: self.fees,
+            capabilities: self.capabilities,
+            pairs: self.pairs,
+        });
+        Exchange::Custom(name)
+    }
+}
+
+/// The custom exchange registered under `name`
+pub fn custom_exchange(name: &str) -> Option<Exchange> {
+    REGISTRY.read().unwrap().get(&name.to_lowercase()).map(|r| Exchange::Custom(r.name))
+}
+
+pub(crate) fn fees(name: &str) -> FeeRates {
+    REGISTRY.read().unwrap().get(&name.to_lowercase()).and_then(|r| r.fees.clone())
+        .unwrap_or_else(|| FeeRates::new(BigDecimal::zero(), BigDecimal::zero()))
+}
+
+pub(crate) fn capabilities(name: &str) -> ExchangeCapabilities {
+    REGISTRY.read().unwrap().get(&name.to_lowercase()).map(|r| r.capabilities).unwrap_or_default()
+}
+
+pub(crate) fn pair_fn(name: &str) -> fn(&Pair) -> Option<&&str> {
+    REGISTRY.read().unwrap().get(&name.to_lowercase()).map_or(any_pair, |r| r.pairs)
+}
+
+/// The factories are cloned out of the registry so that they can register exchanges themselves
+#[cfg(feature = "native")]
+fn factories(name: &str) -> Result<(Option<Arc<ApiFactory>>, Option<Arc<StreamFactory>>)> {
+    REGISTRY.read().unwrap().get(&name.to_lowercase())
+        .map(|r| (r.api.clone(), r.stream.clone()))
+        .ok_or_else(|| Error::InvalidExchange(name.to_string()))
+}
+
+#[cfg(feature = "native")]
+pub(crate) fn new_api(name: &'static str, creds: &dyn Credentials) -> Result<Box<dyn ExchangeApi>> {
+    match factories(name)?.0 {
+        Some(factory) => factory(creds),
+        None => Err(Error::ExchangeDisabled(Exchange::Custom(name))),
+    }
+}
+
+#[cfg(feature = "native")]
+pub(crate) async fn new_stream(name: &'static str, creds: &dyn Credentials, params: StreamParams) -> Result<Box<dyn ExchangeBot>> {
+    match factories(name)?.1 {
+        Some(factory) => factory(creds, params).await,
+        None => Err(Error::ExchangeDisabled(Exchange::Custom(name))),
+    }
+}
+
+#[cfg(test)]
+mod registry_tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn registered_exchanges_are_found_by_name() {
+        let exchange = CustomExchange::new("RegistryVenue")
+            .fees(FeeRates::new(BigDecimal::from_str("0.001").unwrap(), BigDecimal::from_str("0.002").unwrap()))
+            .register();
+        assert_eq!(Exchange::from_str("registryvenue").unwrap(), exchange);
+        assert_eq!(custom_exchange("REGISTRYVENUE"), Some(exchange));
+        assert_eq!(exchange.default_fees().taker, BigDecimal::from_str("0.002").unwrap());
+        let name: String = exchange.into();
+        assert_eq!(name, "RegistryVenue");
+        assert!(Exchange::from_str("unregistered").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Replay recorded live events to recipients, so that the same actor-based strategy code can be
+//! used for backtesting and live trading.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use actix::Recipient;
+
+use crate::codec::EventFormat;
+use crate::error::*;
+use crate::exchange_bot::ExchangeBot;
+use crate::types::LiveEventEnveloppe;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Wait between events as long as they were apart when recorded
+    Original,
+    /// Divide the recorded delay between events by this factor
+    Accelerated(f64),
+    /// Emit events without waiting
+    AsFastAsPossible,
+}
+
+impl ReplaySpeed {
+    fn delay(&self, elapsed_ms: i64) -> Option<Duration> {
+        if elapsed_ms <= 0 {
+            return None;
+        }
+        match *self {
+            ReplaySpeed::Original => Some(Duration::from_millis(elapsed_ms as u64)),
+            ReplaySpeed::Accelerated(factor) if factor > 0.0 => Some(Duration::from_millis((elapsed_ms as f64 / factor) as u64)),
+            _ => None,
+        }
+    }
+}
+
+pub struct ReplayBot {
+    running: Arc<AtomicBool>,
+}
+
+impl ExchangeBot for ReplayBot {
+    fn is_connected(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+}
+
+impl ReplayBot {
+    /// Replay the events recorded in `path` to the recipients, lines that cannot be decoded are
+    /// skipped. The bot is disconnected once every event has been sent.
+    pub fn new(path: PathBuf, format: EventFormat, speed: ReplaySpeed, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<ReplayBot> {
+        let reader = BufReader::new(File::open(&path)?);
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+        actix_rt::spawn(async move {
+            let mut last_ts: Option<i64> = None;
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(l) => l,
+                    Err(e) => {
+                        error!("Replay : unable to read {:?} : {}", path, e);
+                        break;
+                    }
+                };
+                if line.is_empty() || format.header() == Some(line.as_str()) {
+                    continue;
+                }
+                let le = match format.decode(&line) {
+                    Ok(le) => le,
+                    Err(e) => {
+                        trace!("Replay : skipping line {:?} : {}", line, e);
+                        continue;
+                    }
+                };
+                if let Some(ts) = le.1.timestamp() {
+                    if let Some(delay) = last_ts.and_then(|last| speed.delay(ts - last)) {
+                        tokio::time::delay_for(delay).await;
+                    }
+                    last_ts = Some(ts);
+                }
+                for r in &recipients {
+                    if let Err(e) = r.do_send(le.clone()) {
+                        trace!("Replay : unable to send to recipient : {}", e);
+                    }
+                }
+            }
+            flag.store(false, Ordering::SeqCst);
+        });
+        Ok(ReplayBot { running })
+    }
+}
@@ -0,0 +1,330 @@
+//! Use this module to paper trade against a simulated exchange.
+//! Orders are filled immediately against the known order book of the pair and whatever cannot be
+//! filled is cancelled (immediate-or-cancel). Every level taken is a taker fill whose fee, given by
+//! the `FeeModel`, is taken on the received currency.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use actix::{Actor, Addr, Context, Handler};
+use bigdecimal::{BigDecimal, Signed, Zero};
+use serde_json::Value;
+
+use crate::coinnect::Credentials;
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::helpers;
+use crate::simulated::fees::{FeeModel, PercentageFee};
+use crate::simulated::outage::OutageSimulator;
+use crate::types::*;
+use crate::utils;
+
+/// State changed by orders, behind a single lock so that concurrent orders see consistent balances
+#[derive(Debug, Default)]
+struct Account {
+    balances: Balances,
+    last_prices: HashMap<Pair, Price>,
+    next_order_id: u64,
+}
+
+#[derive(Debug)]
+pub struct SimulatedApi {
+    account: Mutex<Account>,
+    books: Arc<RwLock<HashMap<Pair, Orderbook>>>,
+    fee_model: Box<dyn FeeModel>,
+    pub(crate) latency: Duration,
+    outages: Option<Mutex<OutageSimulator>>,
+}
+
+impl SimulatedApi {
+    /// Create a new SimulatedApi from the initial balances and trading conditions in the credentials
+    pub fn new<C: Credentials>(creds: C) -> Result<SimulatedApi> {
+        if creds.exchange() != Exchange::Simulated {
+            return Err(Error::InvalidConfigType(Exchange::Simulated, creds.exchange()));
+        }
+
+        let mut balances = Balances::new();
+        if let Some(raw) = creds.get("balances").filter(|b| !b.is_empty()) {
+            let json: Value = serde_json::from_str(&raw)?;
+            let obj = json.as_object().ok_or_else(|| Error::InvalidFieldFormat("balances".to_string()))?;
+            for (currency, amount) in obj {
+                let c: Currency = serde_json::from_value(Value::String(currency.clone()))
+                    .with_context(|| Error::InvalidFieldValue(currency.clone()))?;
+                balances.insert(c, helpers::from_json_bigdecimal(amount, currency)?);
+            }
+        }
+        let fee_rate = match creds.get("fee_rate").filter(|f| !f.is_empty()) {
+            Some(f) => BigDecimal::from_str(&f).with_context(|| Error::InvalidFieldFormat("fee_rate".to_string()))?,
+            None => BigDecimal::zero(),
+        };
+        let latency = match creds.get("latency_ms").filter(|l| !l.is_empty()) {
+            Some(l) => Duration::from_millis(l.parse::<u64>().with_context(|| Error::InvalidFieldFormat("latency_ms".to_string()))?),
+            None => Duration::from_millis(0),
+        };
+
+        Ok(SimulatedApi {
+            account: Mutex::new(Account { balances, ..Account::default() }),
+            books: Arc::new(RwLock::new(HashMap::new())),
+            fee_model: Box::new(PercentageFee::flat(fee_rate)),
+            latency,
+            outages: None,
+        })
+    }
+
+    /// Fee rate applied on the value of every fill, e.g. 0.0025 for 0.25%
+    pub fn set_fee_rate(&mut self, fee_rate: BigDecimal) {
+        self.fee_model = Box::new(PercentageFee::flat(fee_rate));
+    }
+
+    /// Fees of the fills, a flat `fee_rate` by default
+    pub fn set_fee_model<F: FeeModel + 'static>(&mut self, fee_model: F) {
+        self.fee_model = Box::new(fee_model);
+    }
+
+    pub fn fee_model(&self) -> &dyn FeeModel {
+        self.fee_model.as_ref()
+    }
+
+    /// Delay applied before every order is filled
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.latency = latency;
+    }
+
+    /// Make the exchange unavailable or reject orders according to `outages`
+    pub fn set_outages(&mut self, outages: OutageSimulator) {
+        self.outages = Some(Mutex::new(outages));
+    }
+
+    /// Fail if the simulated exchange is down
+    pub(crate) fn check_available(&self) -> Result<()> {
+        match &self.outages {
+            Some(o) => o.lock().unwrap().check_request(helpers::get_unix_timestamp_ms()),
+            None => Ok(()),
+        }
+    }
+
+    /// Fail if an order sent now would be rejected
+    pub(crate) fn check_order_accepted(&self) -> Result<()> {
+        match &self.outages {
+            Some(o) => o.lock().unwrap().check_order(helpers::get_unix_timestamp_ms()),
+            None => Ok(()),
+        }
+    }
+
+    pub fn set_balance(&self, currency: Currency, amount: Amount) {
+        self.account.lock().unwrap().balances.insert(currency, amount);
+    }
+
+    /// Use a static order book for the pair, replaced by any later live update
+    pub fn set_orderbook(&self, ob: Orderbook) {
+        self.books.write().unwrap().insert(ob.pair, ob);
+    }
+
+    /// Start an actor that keeps the order books of the simulation up to date with a live feed,
+    /// pass its recipient to `Coinnect::new_stream`
+    pub fn book_feed(&self) -> Addr<BookFeed> {
+        BookFeed { books: self.books.clone() }.start()
+    }
+
+    pub(crate) fn current_orderbook(&self, pair: Pair) -> Result<Orderbook> {
+        self.books.read().unwrap().get(&pair).cloned()
+            .ok_or_else(|| Error::ExchangeSpecificError(format!("no order book for {:?}", pair)))
+    }
+
+    pub(crate) fn last_price(&self, pair: Pair) -> Option<Price> {
+        self.account.lock().unwrap().last_prices.get(&pair).cloned()
+    }
+
+    pub(crate) fn balances_snapshot(&self) -> Balances {
+        self.account.lock().unwrap().balances.clone()
+    }
+
+    /// Take `amount` out of the account, as for a withdrawal
+    pub(crate) fn debit(&self, currency: Currency, amount: Amount) -> Result<()> {
+        let mut account = self.account.lock().unwrap();
+        let available = account.balances.get(&currency).cloned().unwrap_or_else(BigDecimal::zero);
+        if available < amount {
+            return Err(Error::InsufficientFunds);
+        }
+        account.balances.insert(currency, available - amount);
+        Ok(())
+    }
+
+    /// Fill an order against the current order book of the pair and update the balances
+    pub(crate) fn fill(&self, order_type: OrderType, pair: Pair, quantity: Volume, price: Option<Price>) -> Result<OrderInfo> {
+        self.execute(order_type, pair, quantity, price).map(|execution| execution.order)
+    }
+
+    /// `fill` returning the quantity filled, what the book cannot fill at once is dropped
+    pub(crate) fn execute(&self, order_type: OrderType, pair: Pair, quantity: Volume, price: Option<Price>) -> Result<Execution> {
+        let request = OrderRequest::new(order_type, pair, &quantity, &price);
+        let (base, quote) = utils::pair_currencies(&pair).ok_or(Error::PairUnsupported)?;
+        let book = self.current_orderbook(pair)?;
+        let limit = match order_type {
+            OrderType::BuyLimit | OrderType::SellLimit => Some(price.ok_or(Error::MissingPrice)?),
+            OrderType::BuyMarket | OrderType::SellMarket => None,
+            _ => return Err(Error::OrderTypeUnsupported),
+        };
+        let side = order_type.side();
+        let is_buy = side == Side::Buy;
+        let mut levels = if is_buy { book.asks } else { book.bids };
+        if is_buy {
+            levels.sort_by(|a, b| a.0.cmp(&b.0));
+        } else {
+            levels.sort_by(|a, b| b.0.cmp(&a.0));
+        }
+
+        let mut remaining = quantity;
+        let mut filled = BigDecimal::zero();
+        let mut cost = BigDecimal::zero();
+        let mut fills = vec![];
+        let mut last_price = None;
+        for (level_price, level_volume) in levels {
+            if remaining.is_zero() {
+                break;
+            }
+            let crosses = match &limit {
+                Some(l) => if is_buy { &level_price <= l } else { &level_price >= l },
+                None => true,
+            };
+            if !crosses {
+                break;
+            }
+            let take = if level_volume < remaining { level_volume } else { remaining.clone() };
+            remaining = remaining - take.clone();
+            cost = cost + take.clone() * level_price.clone();
+            filled = filled + take.clone();
+            fills.push((level_price.clone(), take));
+            last_price = Some(level_price);
+        }
+        if filled.is_zero() {
+            return Err(Error::ExchangeSpecificError(format!("{:?} order on {:?} would not fill", order_type, pair)));
+        }
+
+        let executed = filled.clone();
+        // The fee is in quote currency, converted at the average price when taken on the base
+        let fee = self.fee_model.order_fee(pair, side, &fills, Liquidity::Taker);
+        let (spent_currency, spent, received_currency, received) = if is_buy {
+            let fee_in_base = fee * filled.clone() / cost.clone();
+            (quote, cost, base, filled - fee_in_base)
+        } else {
+            (base, filled, quote, cost - fee)
+        };
+        if received.is_negative() {
+            return Err(Error::InsufficientOrderSize);
+        }
+        let mut account = self.account.lock().unwrap();
+        let available = account.balances.get(&spent_currency).cloned().unwrap_or_else(BigDecimal::zero);
+        if available < spent {
+            return Err(Error::InsufficientFunds);
+        }
+        let credited = account.balances.get(&received_currency).cloned().unwrap_or_else(BigDecimal::zero);
+        account.balances.insert(spent_currency, available - spent);
+        account.balances.insert(received_currency, credited + received);
+        if let Some(p) = last_price {
+            account.last_prices.insert(pair, p);
+        }
+
+        account.next_order_id += 1;
+        let order = OrderInfo {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            identifier: vec![format!("SIM-{}", account.next_order_id)],
+            raw: None,
+            request: Some(request),
+        };
+        Ok(Execution { order, filled: Some(executed) })
+    }
+}
+
+/// Actor updating the order books of a `SimulatedApi` from live events
+pub struct BookFeed {
+    books: Arc<RwLock<HashMap<Pair, Orderbook>>>,
+}
+
+impl Actor for BookFeed {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for BookFeed {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        if let LiveEvent::LiveOrderbook(ob) = msg.1 {
+            self.books.write().unwrap().insert(ob.pair, ob);
+        }
+    }
+}
+
+#[cfg(test)]
+mod simulated_api_tests {
+    use super::*;
+    use crate::simulated::SimulatedCreds;
+    use crate::simulated::fees::FixedFee;
+
+    fn api() -> SimulatedApi {
+        let creds = SimulatedCreds::new("test", r#"{"BTC": "1", "USD": "1000"}"#, "0.01", "0");
+        let api = SimulatedApi::new(creds).unwrap();
+        api.set_orderbook(Orderbook {
+            timestamp: 0,
+            pair: Pair::BTC_USD,
+            asks: vec![(BigDecimal::from(101), BigDecimal::from(1)), (BigDecimal::from(102), BigDecimal::from(1))],
+            bids: vec![(BigDecimal::from(99), BigDecimal::from(1)), (BigDecimal::from(98), BigDecimal::from(1))],
+        });
+        api
+    }
+
+    #[test]
+    fn market_buy_walks_the_book() {
+        let api = api();
+        api.fill(OrderType::BuyMarket, Pair::BTC_USD, BigDecimal::from(2), None).unwrap();
+        let balances = api.balances_snapshot();
+        assert_eq!(balances[&Currency::USD], BigDecimal::from(797));
+        assert_eq!(balances[&Currency::BTC], BigDecimal::from_str("2.98").unwrap());
+    }
+
+    #[test]
+    fn limit_sell_stops_at_limit() {
+        let api = api();
+        api.fill(OrderType::SellLimit, Pair::BTC_USD, BigDecimal::from(1), Some(BigDecimal::from(99))).unwrap();
+        let balances = api.balances_snapshot();
+        assert_eq!(balances[&Currency::BTC], BigDecimal::zero());
+        assert_eq!(balances[&Currency::USD], BigDecimal::from_str("1098.01").unwrap());
+    }
+
+    #[test]
+    fn fixed_fees_are_paid_per_order() {
+        let mut api = api();
+        api.set_fee_model(FixedFee(BigDecimal::from(2)));
+        api.set_balance(Currency::BTC, BigDecimal::from(2));
+        api.fill(OrderType::SellMarket, Pair::BTC_USD, BigDecimal::from(2), None).unwrap();
+        assert_eq!(api.balances_snapshot()[&Currency::USD], BigDecimal::from(1195));
+        assert!(api.fee_model().rates(Pair::BTC_USD).is_none());
+    }
+
+    #[test]
+    fn ioc_orders_fill_what_the_book_offers() {
+        let api = api();
+        let execution = api.execute(OrderType::BuyLimit, Pair::BTC_USD, BigDecimal::from(3), Some(BigDecimal::from(101))).unwrap();
+        assert_eq!(execution.filled, Some(BigDecimal::from(1)));
+    }
+
+    #[test]
+    fn fills_not_covering_the_fee_are_rejected() {
+        let mut api = api();
+        api.set_fee_model(FixedFee(BigDecimal::from(200)));
+        match api.fill(OrderType::SellMarket, Pair::BTC_USD, BigDecimal::from(1), None) {
+            Err(Error::InsufficientOrderSize) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(api.balances_snapshot()[&Currency::BTC], BigDecimal::from(1));
+    }
+
+    #[test]
+    fn buy_without_funds_fails() {
+        let api = api();
+        api.set_balance(Currency::USD, BigDecimal::from(10));
+        assert!(api.fill(OrderType::BuyMarket, Pair::BTC_USD, BigDecimal::from(1), None).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Contains the simulated exchange credentials.
+//! There is nothing to authenticate, the credentials carry the initial balances and the trading
+//! conditions of the simulation instead.
+
+use serde_json;
+use serde_json::Value;
+
+use crate::coinnect::Credentials;
+use crate::env_credentials::EnvCredentials;
+use crate::exchange::Exchange;
+use crate::helpers;
+use crate::helpers::secret::Redacted;
+use crate::error::*;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub struct SimulatedCreds {
+    exchange: Exchange,
+    name: String,
+    data: HashMap<String, String>,
+}
+
+impl fmt::Debug for SimulatedCreds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SimulatedCreds")
+            .field("exchange", &self.exchange)
+            .field("name", &self.name)
+            .field("data", &Redacted(&self.data))
+            .finish()
+    }
+}
+
+impl SimulatedCreds {
+    /// Create a new `SimulatedCreds` from arguments.
+    /// `balances` is a json object of currency to amount, e.g. `{"BTC": "1.5", "EUR": "1000"}`,
+    /// `fee_rate` is applied to the received amount of every fill and `latency_ms` delays every
+    /// order.
+    pub fn new(name: &str, balances: &str, fee_rate: &str, latency_ms: &str) -> Self {
+        let mut creds = SimulatedCreds {
+            data: HashMap::new(),
+            exchange: Exchange::Simulated,
+            name: if name.is_empty() {
+                "SimulatedClient".to_string()
+            } else {
+                name.to_string()
+            },
+        };
+
+        creds
+            .data
+            .insert("balances".to_string(), balances.to_string());
+        creds
+            .data
+            .insert("fee_rate".to_string(), fee_rate.to_string());
+        creds
+            .data
+            .insert("latency_ms".to_string(), latency_ms.to_string());
+
+        creds
+    }
+
+    /// Create a new `SimulatedCreds` from a json configuration file. This file must follow this
+    /// structure:
+    ///
+    /// ```json
+    /// {
+    ///     "account_paper": {
+    ///         "exchange"  : "simulated",
+    ///         "balances"  : {"BTC": "1.5", "EUR": "1000"},
+    ///         "fee_rate"  : "0.0025",
+    ///         "latency_ms": "100"
+    ///     }
+    /// }
+    /// ```
+    /// For this example, you could use load your paper trading account with
+    /// `SimulatedApi::new(SimulatedCreds::new_from_file("account_paper", Path::new("/keys.json")))`
+    pub fn new_from_file(name: &str, path: PathBuf) -> Result<Self> {
+        let mut f = File::open(&path)?;
+        let mut buffer = String::new();
+        f.read_to_string(&mut buffer)?;
+
+        let data: Value = serde_json::from_str(&buffer)?;
+        let json_obj = data.as_object()
+            .ok_or_else(|| Error::BadParse)?
+            .get(name)
+            .ok_or_else(|| Error::MissingField(name.to_string()))?;
+
+        let balances = json_obj
+            .get("balances")
+            .ok_or_else(|| Error::MissingField("balances".to_string()))?
+            .to_string();
+        let fee_rate = helpers::get_json_string(json_obj, "fee_rate")?;
+        let latency_ms = helpers::get_json_string(json_obj, "latency_ms")?;
+        let exchange = {
+            let exchange_str = helpers::get_json_string(json_obj, "exchange")?;
+            Exchange::from_str(exchange_str)
+                .with_context(|| Error::InvalidFieldValue("exchange".to_string()))?
+        };
+
+        if exchange != Exchange::Simulated {
+            return Err(Error::InvalidConfigType(Exchange::Simulated, exchange));
+        }
+
+        Ok(SimulatedCreds::new(name, &balances, fee_rate, latency_ms))
+    }
+
+    /// Create a new `SimulatedCreds` from the environment variables `<prefix>_SIMULATED_BALANCES`, `<prefix>_SIMULATED_FEE_RATE`
+    /// and `<prefix>_SIMULATED_LATENCY_MS`, see `EnvCredentials`.
+    pub fn new_from_env(prefix: &str) -> Result<Self> {
+        let env = EnvCredentials::new(prefix, Exchange::Simulated);
+        let balances = env.require("balances")?;
+        let fee_rate = env.require("fee_rate")?;
+        let latency_ms = env.require("latency_ms")?;
+        Ok(SimulatedCreds::new(&env.name(), &balances, &fee_rate, &latency_ms))
+    }
+}
+
+impl Credentials for SimulatedCreds {
+    /// Return a value from the credentials.
+    fn get(&self, key: &str) -> Option<String> {
+        if let Some(res) = self.data.get(key) {
+            Some(res.clone())
+        } else {
+            None
+        }
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+}
@@ -0,0 +1,70 @@
+//! Trading fees of the simulated exchange.
+//! A `FeeModel` gives the fee of every simulated fill in quote currency, so that paper trading
+//! pays what the real exchange would. `PercentageFee` charges maker and taker rates on the value
+//! of the fill, `FixedFee` a flat amount per order whatever the number of levels it takes.
+
+use std::fmt::Debug;
+
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::types::*;
+
+/// Fee of a fill
+pub trait FeeModel: Debug + Send + Sync {
+    /// Fee in quote currency of `amount` filled at `price`
+    fn fee(&self, pair: Pair, side: Side, price: &Price, amount: &Volume, liquidity: Liquidity) -> Amount;
+
+    /// Fee in quote currency of an order filled in `fills`, as price and amount, the sum of the
+    /// fees of the fills by default
+    fn order_fee(&self, pair: Pair, side: Side, fills: &[(Price, Volume)], liquidity: Liquidity) -> Amount {
+        fills.iter().fold(BigDecimal::zero(), |fee, (price, amount)| fee + self.fee(pair, side, price, amount, liquidity))
+    }
+
+    /// Rates reported by `ExchangeApi::fees`, `None` when the fee is not proportional
+    fn rates(&self, _pair: Pair) -> Option<FeeRates> {
+        None
+    }
+}
+
+/// Maker and taker rates on the value of the fill, fills of unknown liquidity pay the taker rate
+#[derive(Debug, Clone, PartialEq)]
+pub struct PercentageFee(pub FeeRates);
+
+impl PercentageFee {
+    pub fn new(maker: BigDecimal, taker: BigDecimal) -> Self {
+        PercentageFee(FeeRates::new(maker, taker))
+    }
+
+    /// The same rate for makers and takers
+    pub fn flat(rate: BigDecimal) -> Self {
+        PercentageFee::new(rate.clone(), rate)
+    }
+}
+
+impl FeeModel for PercentageFee {
+    fn fee(&self, _pair: Pair, _side: Side, price: &Price, amount: &Volume, liquidity: Liquidity) -> Amount {
+        let rate = match liquidity {
+            Liquidity::Maker => &self.0.maker,
+            Liquidity::Taker | Liquidity::Unknown => &self.0.taker,
+        };
+        rate * price * amount
+    }
+
+    fn rates(&self, _pair: Pair) -> Option<FeeRates> {
+        Some(self.0.clone())
+    }
+}
+
+/// The same amount for every order, in quote currency
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedFee(pub Amount);
+
+impl FeeModel for FixedFee {
+    fn fee(&self, _pair: Pair, _side: Side, _price: &Price, _amount: &Volume, _liquidity: Liquidity) -> Amount {
+        self.0.clone()
+    }
+
+    fn order_fee(&self, _pair: Pair, _side: Side, fills: &[(Price, Volume)], _liquidity: Liquidity) -> Amount {
+        if fills.is_empty() { BigDecimal::zero() } else { self.0.clone() }
+    }
+}
@@ -0,0 +1,91 @@
+//! Use this module to interact with the simulated exchange through a Generic API.
+
+use crate::exchange::{ExchangeApi, Exchange, ExchangeCapabilities};
+use crate::simulated::api::SimulatedApi;
+
+use crate::error::*;
+use crate::types::*;
+use crate::helpers;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+
+#[async_trait]
+impl ExchangeApi for SimulatedApi {
+    async fn ticker(&self, pair: Pair) -> Result<Ticker> {
+        self.check_available()?;
+        let book = self.current_orderbook(pair)?;
+        let (ask, ask_size) = book.best_ask().cloned().ok_or_else(|| Error::MissingField("asks".to_string()))?;
+        let (bid, bid_size) = book.best_bid().cloned().ok_or_else(|| Error::MissingField("bids".to_string()))?;
+        let price = self.last_price(pair).unwrap_or_else(|| (ask.clone() + bid.clone()) / BigDecimal::from(2));
+        Ok(Ticker {
+            timestamp: helpers::get_unix_timestamp_ms(),
+            pair,
+            last_trade_price: price,
+            lowest_ask: ask,
+            highest_bid: bid,
+            volume: None,
+            bid_size: Some(bid_size),
+            ask_size: Some(ask_size),
+            open_24h: None,
+            high_24h: None,
+            low_24h: None,
+            percent_change: None,
+            raw: None,
+        })
+    }
+
+    async fn orderbook(&self, pair: Pair) -> Result<Orderbook> {
+        self.check_available()?;
+        self.current_orderbook(pair)
+    }
+
+    async fn add_order(&self,
+                 order_type: OrderType,
+                 pair: Pair,
+                 quantity: Volume,
+                 price: Option<Price>)
+                 -> Result<OrderInfo> {
+        self.check_order_accepted()?;
+        if self.latency.as_millis() > 0 {
+            tokio::time::delay_for(self.latency).await;
+        }
+        self.fill(order_type, pair, quantity, price)
+    }
+
+    /// Orders are filled at once for what the book offers, the rest is dropped
+    async fn add_ioc_order(&self,
+                           order_type: OrderType,
+                           pair: Pair,
+                           quantity: Volume,
+                           price: Price)
+                           -> Result<Execution> {
+        match order_type {
+            OrderType::BuyLimit | OrderType::SellLimit => (),
+            _ => return Err(Error::OrderTypeUnsupported),
+        }
+        self.check_order_accepted()?;
+        if self.latency.as_millis() > 0 {
+            tokio::time::delay_for(self.latency).await;
+        }
+        self.execute(order_type, pair, quantity, Some(price))
+    }
+
+    async fn fees(&self, pair: Pair) -> Result<FeeRates> {
+        self.fee_model().rates(pair).ok_or(Error::Unsupported)
+    }
+
+    async fn balances(&self) -> Result<Balances> {
+        self.check_available()?;
+        Ok(self.balances_snapshot())
+    }
+
+    async fn withdraw(&self, currency: Currency, amount: Amount, _destination: &str) -> Result<WithdrawalInfo> {
+        self.check_available()?;
+        self.debit(currency, amount)?;
+        Ok(WithdrawalInfo { timestamp: helpers::get_unix_timestamp_ms(), identifier: None })
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        Exchange::Simulated.capabilities()
+    }
+}
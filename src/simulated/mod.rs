@@ -0,0 +1,22 @@
+//! Use this module to paper trade against a simulated exchange.
+//! Orders are filled against a static order book or a live `LiveOrderbook` feed, and balances are
+//! kept in memory. Fees follow a `FeeModel`.
+
+#[cfg(feature = "native")]
+pub mod api;
+#[cfg(feature = "native")]
+pub mod generic_api;
+#[cfg(feature = "native")]
+pub mod credentials;
+pub mod utils;
+#[cfg(feature = "native")]
+pub mod outage;
+pub mod fees;
+
+#[cfg(feature = "native")]
+pub use self::credentials::SimulatedCreds;
+#[cfg(feature = "native")]
+pub use self::api::SimulatedApi;
+pub use self::fees::{FeeModel, FixedFee, PercentageFee};
+#[cfg(feature = "native")]
+pub use self::outage::{OutageKind, OutageSimulator, OutageWindow, ReliabilityProfile};
@@ -0,0 +1,170 @@
+//! Downtime and rejected-order periods for the simulated exchange, to check how a strategy
+//! behaves when the exchange it trades on is unreliable.
+//!
+//! Outages are either scheduled at fixed times or drawn at random from a `ReliabilityProfile`.
+//! Random outages use a seeded generator so that a run can be reproduced.
+
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::strategy::SeededRng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum OutageKind {
+    /// Every request fails as if the exchange could not be reached
+    Down,
+    /// Market data is available but every order is rejected
+    RejectOrders,
+}
+
+/// An outage between two UNIX timestamps in ms, end excluded
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutageWindow {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub kind: OutageKind,
+}
+
+impl OutageWindow {
+    fn contains(&self, now_ms: i64) -> bool {
+        self.start_ms <= now_ms && now_ms < self.end_ms
+    }
+}
+
+/// Likelihood of random outages, evaluated on every request
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReliabilityProfile {
+    /// Probability that a request starts a downtime
+    pub downtime_probability: f64,
+    /// Average length of a downtime, actual lengths are drawn between half and one and a half times it
+    pub mean_downtime_ms: u64,
+    /// Probability that an order is rejected while the exchange is up
+    pub reject_probability: f64,
+}
+
+impl ReliabilityProfile {
+    /// A rough profile of an exchange, the less reliable exchanges going down more often and for
+    /// longer. These are ballpark figures, use measured ones for anything more than a smoke test.
+    pub fn mirroring(exchange: Exchange) -> Self {
+        let (downtime_probability, mean_downtime_ms, reject_probability) = match exchange {
+            Exchange::Bitstamp => (0.0005, 30_000, 0.002),
+            Exchange::Kraken => (0.002, 120_000, 0.01),
+            Exchange::Poloniex => (0.001, 60_000, 0.005),
+            Exchange::Bittrex => (0.001, 60_000, 0.005),
+            Exchange::Gdax => (0.0005, 30_000, 0.002),
+            Exchange::Coinbase => (0.0005, 30_000, 0.002),
+            Exchange::Binance => (0.0005, 20_000, 0.003),
+            Exchange::Okx => (0.0005, 30_000, 0.003),
+            Exchange::Simulated => (0.0, 0, 0.0),
+            Exchange::Custom(_) => (0.0, 0, 0.0),
+        };
+        ReliabilityProfile { downtime_probability, mean_downtime_ms, reject_probability }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutageSimulator {
+    schedule: Vec<OutageWindow>,
+    profile: Option<ReliabilityProfile>,
+    rng: SeededRng,
+    down_until_ms: Option<i64>,
+}
+
+impl OutageSimulator {
+    /// Only the scheduled outages
+    pub fn scheduled(schedule: Vec<OutageWindow>) -> Self {
+        OutageSimulator { schedule, profile: None, rng: SeededRng::new(0), down_until_ms: None }
+    }
+
+    /// Random outages drawn from `profile`, the same seed yields the same outages
+    pub fn random(profile: ReliabilityProfile, seed: u64) -> Self {
+        OutageSimulator { schedule: vec![], profile: Some(profile), rng: SeededRng::new(seed), down_until_ms: None }
+    }
+
+    /// Add a scheduled outage, on top of the random ones if any
+    pub fn with_window(mut self, window: OutageWindow) -> Self {
+        self.schedule.push(window);
+        self
+    }
+
+    fn scheduled_kind(&self, now_ms: i64) -> Option<OutageKind> {
+        let active: Vec<OutageKind> = self.schedule.iter().filter(|w| w.contains(now_ms)).map(|w| w.kind).collect();
+        if active.contains(&OutageKind::Down) {
+            Some(OutageKind::Down)
+        } else {
+            active.first().cloned()
+        }
+    }
+
+    fn is_down(&mut self, now_ms: i64) -> bool {
+        if self.scheduled_kind(now_ms) == Some(OutageKind::Down) {
+            return true;
+        }
+        if self.down_until_ms.map_or(false, |until| now_ms < until) {
+            return true;
+        }
+        self.down_until_ms = None;
+        let starts = match &self.profile {
+            Some(p) if p.downtime_probability > 0.0 => self.rng.next_f64() < p.downtime_probability,
+            _ => false,
+        };
+        if starts {
+            let mean = self.profile.as_ref().map_or(0, |p| p.mean_downtime_ms) as f64;
+            let length = mean * (0.5 + self.rng.next_f64());
+            self.down_until_ms = Some(now_ms + length as i64);
+            info!("Simulated exchange : down for {} ms", length as i64);
+        }
+        starts
+    }
+
+    /// Fail if the exchange cannot be reached at `now_ms`
+    pub fn check_request(&mut self, now_ms: i64) -> Result<()> {
+        if self.is_down(now_ms) {
+            return Err(Error::ServiceUnavailable("simulated downtime".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Fail if an order sent at `now_ms` would be rejected
+    pub fn check_order(&mut self, now_ms: i64) -> Result<()> {
+        self.check_request(now_ms)?;
+        let rejected = self.scheduled_kind(now_ms) == Some(OutageKind::RejectOrders)
+            || match &self.profile {
+                Some(p) if p.reject_probability > 0.0 => self.rng.next_f64() < p.reject_probability,
+                _ => false,
+            };
+        if rejected {
+            return Err(Error::ExchangeSpecificError("order rejected by simulated outage".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod outage_tests {
+    use super::*;
+
+    #[test]
+    fn scheduled_windows() {
+        let mut sim = OutageSimulator::scheduled(vec![
+            OutageWindow { start_ms: 100, end_ms: 200, kind: OutageKind::Down },
+            OutageWindow { start_ms: 300, end_ms: 400, kind: OutageKind::RejectOrders },
+        ]);
+        assert!(sim.check_request(50).is_ok());
+        assert!(sim.check_request(150).is_err());
+        assert!(sim.check_request(200).is_ok());
+        assert!(sim.check_request(350).is_ok());
+        assert!(sim.check_order(350).is_err());
+    }
+
+    #[test]
+    fn random_outages_are_reproducible() {
+        let profile = ReliabilityProfile { downtime_probability: 0.1, mean_downtime_ms: 50, reject_probability: 0.1 };
+        let run = |seed| {
+            let mut sim = OutageSimulator::random(profile.clone(), seed);
+            (0..1000).map(|t| sim.check_order(t * 10).is_ok()).collect::<Vec<_>>()
+        };
+        let outcomes = run(7);
+        assert_eq!(outcomes, run(7));
+        assert!(outcomes.iter().any(|ok| !ok));
+    }
+}
@@ -0,0 +1,9 @@
+use crate::types::Pair;
+
+/// The simulated exchange has no native pair names
+static ANY_PAIR: &str = "";
+
+/// Every pair can be traded on the simulated exchange as long as an order book is provided for it
+pub fn get_pair_string(_pair: &Pair) -> Option<&&str> {
+    Some(&ANY_PAIR)
+}
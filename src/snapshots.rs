@@ -0,0 +1,129 @@
+//! Order books sampled at fixed intervals instead of on every change.
+//! `SnapshotScheduler` keeps the latest order book of each pair and sends it on ticks aligned on
+//! multiples of the interval since the UNIX epoch, so that the books of every pair and every
+//! exchange are taken at the same instants. The books received between two ticks are coalesced,
+//! only the last one is sent, and a pair whose book did not change since the previous tick sends
+//! nothing.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix::{Actor, AsyncContext, Context, Handler, Recipient};
+
+use crate::exchange::Exchange;
+use crate::helpers;
+use crate::types::*;
+
+/// Next multiple of `interval_ms` strictly after `now_ms`
+pub fn next_tick(now_ms: i64, interval_ms: u64) -> i64 {
+    let interval_ms = interval_ms.max(1) as i64;
+    now_ms - now_ms.rem_euclid(interval_ms) + interval_ms
+}
+
+/// Latest order book of each pair, until the next tick
+#[derive(Debug, Default)]
+pub struct BookCoalescer {
+    pending: HashMap<(Exchange, Pair), Orderbook>,
+}
+
+impl BookCoalescer {
+    /// Replace the pending book of the pair
+    pub fn push(&mut self, exchange: Exchange, ob: Orderbook) {
+        self.pending.insert((exchange, ob.pair), ob);
+    }
+
+    /// The books received since the last tick, stamped with the time of the tick `tick_ms`
+    pub fn tick(&mut self, tick_ms: i64) -> Vec<(Exchange, Orderbook)> {
+        self.pending.drain()
+            .map(|((exchange, _), mut ob)| {
+                ob.timestamp = tick_ms;
+                (exchange, ob)
+            })
+            .collect()
+    }
+}
+
+/// Actor sending the order books of a bot to its recipients on clock aligned ticks.
+/// Every other event is forwarded untouched.
+pub struct SnapshotScheduler {
+    interval_ms: u64,
+    books: BookCoalescer,
+    recipients: Vec<Recipient<LiveEventEnveloppe>>,
+}
+
+impl SnapshotScheduler {
+    pub fn new(interval_ms: u64, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Self {
+        SnapshotScheduler { interval_ms: interval_ms.max(1), books: BookCoalescer::default(), recipients }
+    }
+
+    /// Wait for the next tick, scheduled again from the clock each time so that ticks do not drift
+    fn schedule(&self, ctx: &mut Context<Self>) {
+        let tick_ms = next_tick(helpers::get_unix_timestamp_ms(), self.interval_ms);
+        let delay_ms = (tick_ms - helpers::get_unix_timestamp_ms()).max(0) as u64;
+        ctx.run_later(Duration::from_millis(delay_ms), move |act, ctx| {
+            for (exchange, ob) in act.books.tick(tick_ms) {
+                act.send(LiveEventEnveloppe(exchange, LiveEvent::LiveOrderbook(ob)));
+            }
+            act.schedule(ctx);
+        });
+    }
+
+    fn send(&self, le: LiveEventEnveloppe) {
+        for r in &self.recipients {
+            if let Err(e) = r.do_send(le.clone()) {
+                trace!("Snapshots : unable to send to recipient : {}", e);
+            }
+        }
+    }
+}
+
+impl Actor for SnapshotScheduler {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        self.schedule(ctx);
+    }
+}
+
+impl Handler<LiveEventEnveloppe> for SnapshotScheduler {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        match msg.1 {
+            LiveEvent::LiveOrderbook(ob) => self.books.push(msg.0, ob),
+            _ => self.send(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod snapshots_tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+
+    fn book(timestamp: i64, ask: i32) -> Orderbook {
+        Orderbook {
+            timestamp,
+            pair: Pair::BTC_USD,
+            asks: vec![(BigDecimal::from(ask), BigDecimal::from(1))],
+            bids: vec![(BigDecimal::from(99), BigDecimal::from(1))],
+        }
+    }
+
+    #[test]
+    fn books_are_coalesced_until_the_tick() {
+        assert_eq!(next_tick(1_050, 100), 1_100);
+        assert_eq!(next_tick(1_100, 100), 1_200);
+        let mut books = BookCoalescer::default();
+        books.push(Exchange::Bitstamp, book(1_010, 101));
+        books.push(Exchange::Bitstamp, book(1_020, 102));
+        books.push(Exchange::Kraken, book(1_030, 103));
+        let mut sent = books.tick(1_100);
+        sent.sort_by_key(|(_, ob)| ob.asks[0].0.clone());
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].0, Exchange::Bitstamp);
+        assert_eq!(sent[0].1.asks[0].0, BigDecimal::from(102));
+        assert_eq!(sent[0].1.timestamp, 1_100);
+        assert!(books.tick(1_200).is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+//! Derived stream of rolling spread statistics.
+//! `SpreadStatsActor` listens to live order books, tracks the best bid and offer of every pair on
+//! every venue and emits the mean and 95th percentile of the spread over the last `window` books.
+//! Quoting strategies can use it to widen or narrow their quotes with market volatility.
+
+use std::collections::{HashMap, VecDeque};
+
+use actix::{Actor, Context, Handler, Recipient};
+use bigdecimal::BigDecimal;
+
+use crate::exchange::Exchange;
+use crate::types::{LiveEvent, LiveEventEnveloppe, Orderbook, Pair, Price};
+
+#[derive(Message, Clone, Debug)]
+#[rtype(result = "()")]
+pub struct SpreadStats {
+    pub exchange: Exchange,
+    pub pair: Pair,
+    /// UNIX timestamp in ms of the last order book in the window
+    pub timestamp: i64,
+    /// Spread of the last order book in the window
+    pub last: Price,
+    /// Mean spread over the window
+    pub mean: Price,
+    /// 95th percentile of the spread over the window
+    pub p95: Price,
+    /// Number of samples in the window
+    pub samples: usize,
+}
+
+/// Best bid and offer of an order book, `None` if one side is empty
+pub fn bbo(ob: &Orderbook) -> Option<(Price, Price)> {
+    let (best_ask, _) = ob.best_ask()?;
+    let (best_bid, _) = ob.best_bid()?;
+    Some((best_bid.clone(), best_ask.clone()))
+}
+
+#[derive(Debug, Default)]
+struct SpreadWindow {
+    spreads: VecDeque<Price>,
+}
+
+impl SpreadWindow {
+    fn push(&mut self, spread: Price, window: usize) {
+        if self.spreads.len() == window {
+            self.spreads.pop_front();
+        }
+        self.spreads.push_back(spread);
+    }
+
+    fn mean(&self) -> Price {
+        let sum = self.spreads.iter().fold(BigDecimal::from(0), |acc, s| acc + s.clone());
+        sum / BigDecimal::from(self.spreads.len() as i64)
+    }
+
+    fn percentile(&self, pct: usize) -> Price {
+        let mut sorted: Vec<&Price> = self.spreads.iter().collect();
+        sorted.sort();
+        let idx = ((sorted.len() * pct + 99) / 100).max(1) - 1;
+        sorted[idx].clone()
+    }
+}
+
+pub struct SpreadStatsActor {
+    window: usize,
+    windows: HashMap<(Exchange, Pair), SpreadWindow>,
+    recipients: Vec<Recipient<SpreadStats>>,
+}
+
+impl SpreadStatsActor {
+    /// Create a new actor computing statistics over the last `window` order books of each pair
+    pub fn new(window: usize, recipients: Vec<Recipient<SpreadStats>>) -> Self {
+        SpreadStatsActor { window: window.max(1), windows: HashMap::new(), recipients }
+    }
+
+    fn update(&mut self, exchange: Exchange, ob: &Orderbook) -> Option<SpreadStats> {
+        let (bid, ask) = bbo(ob)?;
+        let spread = ask - bid;
+        let sw = self.windows.entry((exchange, ob.pair)).or_default();
+        sw.push(spread.clone(), self.window);
+        Some(SpreadStats {
+            exchange,
+            pair: ob.pair,
+            timestamp: ob.timestamp,
+            last: spread,
+            mean: sw.mean(),
+            p95: sw.percentile(95),
+            samples: sw.spreads.len(),
+        })
+    }
+}
+
+impl Actor for SpreadStatsActor {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for SpreadStatsActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        if let LiveEvent::LiveOrderbook(ob) = &msg.1 {
+            if let Some(stats) = self.update(msg.0, ob) {
+                for r in &self.recipients {
+                    if let Err(e) = r.do_send(stats.clone()) {
+                        trace!("Spread stats : unable to send to recipient : {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod spread_tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn book(bid: i64, ask: i64) -> Orderbook {
+        Orderbook {
+            timestamp: 0,
+            pair: Pair::BTC_USD,
+            asks: vec![(BigDecimal::from(ask + 1), BigDecimal::from(1)), (BigDecimal::from(ask), BigDecimal::from(1))],
+            bids: vec![(BigDecimal::from(bid - 1), BigDecimal::from(1)), (BigDecimal::from(bid), BigDecimal::from(1))],
+        }
+    }
+
+    #[test]
+    fn rolling_spread_stats() {
+        let mut actor = SpreadStatsActor::new(4, vec![]);
+        for (bid, ask) in &[(100, 101), (100, 102), (100, 103), (100, 104), (100, 110)] {
+            actor.update(Exchange::Bitstamp, &book(*bid, *ask));
+        }
+        let stats = actor.update(Exchange::Bitstamp, &book(100, 102)).unwrap();
+        assert_eq!(stats.samples, 4);
+        assert_eq!(stats.last, BigDecimal::from(2));
+        assert_eq!(stats.mean, BigDecimal::from_str("4.75").unwrap());
+        assert_eq!(stats.p95, BigDecimal::from(10));
+    }
+}
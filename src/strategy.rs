@@ -0,0 +1,164 @@
+//! Run strategies against live or replayed events with injected time and randomness.
+//! A strategy must only read the time and draw random numbers through its `StrategyContext`.
+//! With `TimeSource::Event` and a fixed seed, the same event stream then leads to the same
+//! decisions whether it comes from an exchange or from a `ReplayBot`, so that live and
+//! simulated runs can be compared decision by decision.
+
+use actix::{Actor, Context, Handler};
+
+use crate::helpers;
+use crate::types::LiveEventEnveloppe;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeSource {
+    /// The system clock, not reproducible
+    Wall,
+    /// The timestamp of the last event received
+    Event,
+}
+
+/// Clock handed to strategies
+#[derive(Debug, Clone)]
+pub struct Clock {
+    source: TimeSource,
+    last_event_ms: i64,
+}
+
+impl Clock {
+    pub fn new(source: TimeSource) -> Self {
+        Clock { source, last_event_ms: 0 }
+    }
+
+    /// UNIX timestamp in ms
+    pub fn now_ms(&self) -> i64 {
+        match self.source {
+            TimeSource::Wall => helpers::get_unix_timestamp_ms(),
+            TimeSource::Event => self.last_event_ms,
+        }
+    }
+
+    fn observe(&mut self, event_ms: i64) {
+        if event_ms > self.last_event_ms {
+            self.last_event_ms = event_ms;
+        }
+    }
+}
+
+/// Seeded xorshift64* generator, the same seed always yields the same sequence
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed } }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform number in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+pub struct StrategyContext<'a> {
+    pub clock: &'a Clock,
+    pub rng: &'a mut SeededRng,
+}
+
+pub trait Strategy {
+    fn on_event(&mut self, ctx: &mut StrategyContext, event: &LiveEventEnveloppe);
+}
+
+/// Actor feeding live events to a strategy with its injected clock and random generator
+pub struct StrategyRunner<S: Strategy + 'static> {
+    strategy: S,
+    clock: Clock,
+    rng: SeededRng,
+}
+
+impl<S: Strategy + 'static> StrategyRunner<S> {
+    pub fn new(strategy: S, time_source: TimeSource, seed: u64) -> Self {
+        StrategyRunner { strategy, clock: Clock::new(time_source), rng: SeededRng::new(seed) }
+    }
+
+    /// Runner whose decisions only depend on the events it receives
+    pub fn deterministic(strategy: S, seed: u64) -> Self {
+        StrategyRunner::new(strategy, TimeSource::Event, seed)
+    }
+
+    pub fn process(&mut self, event: &LiveEventEnveloppe) {
+        if let Some(ts) = event.1.timestamp() {
+            self.clock.observe(ts);
+        }
+        let mut ctx = StrategyContext { clock: &self.clock, rng: &mut self.rng };
+        self.strategy.on_event(&mut ctx, event);
+    }
+
+    pub fn strategy(&self) -> &S {
+        &self.strategy
+    }
+}
+
+impl<S: Strategy + 'static> Actor for StrategyRunner<S> {
+    type Context = Context<Self>;
+}
+
+impl<S: Strategy + 'static> Handler<LiveEventEnveloppe> for StrategyRunner<S> {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        self.process(&msg);
+    }
+}
+
+#[cfg(test)]
+mod strategy_tests {
+    use super::*;
+    use crate::exchange::Exchange;
+    use crate::types::*;
+    use bigdecimal::BigDecimal;
+
+    #[derive(Default)]
+    struct Recording {
+        decisions: Vec<(i64, u64)>,
+    }
+
+    impl Strategy for Recording {
+        fn on_event(&mut self, ctx: &mut StrategyContext, _event: &LiveEventEnveloppe) {
+            self.decisions.push((ctx.clock.now_ms(), ctx.rng.next_u64()));
+        }
+    }
+
+    fn trade(event_ms: i64) -> LiveEventEnveloppe {
+        LiveEventEnveloppe(Exchange::Bitstamp, LiveEvent::LiveTrade(LiveTrade {
+            event_ms,
+            pair: Pair::BTC_USD,
+            amount: BigDecimal::from(1),
+            price: BigDecimal::from(7000),
+            tt: TradeType::Buy,
+            meta: TradeMeta::default(),
+        }))
+    }
+
+    #[test]
+    fn same_events_same_decisions() {
+        let mut a = StrategyRunner::deterministic(Recording::default(), 42);
+        let mut b = StrategyRunner::deterministic(Recording::default(), 42);
+        for ts in &[1, 5, 3, 8] {
+            a.process(&trade(*ts));
+            b.process(&trade(*ts));
+        }
+        assert_eq!(a.strategy().decisions, b.strategy().decisions);
+        assert_eq!(a.strategy().decisions[2].0, 5);
+    }
+}
@@ -0,0 +1,130 @@
+//! Submission of orders that are placed at most once.
+//! When a submission times out or the connection drops, the order may or may not have reached the
+//! exchange, and sending it again could place it twice. `submit_once` tags the order with a client
+//! id and, before each new attempt, asks the exchange whether it knows an order with this id.
+//!
+//! ```ignore
+//! let request = OrderRequest::new(OrderType::BuyLimit, Pair::BTC_EUR, &quantity, &Some(price));
+//! let order = submit_once(api.as_ref(), &request, "42", 3, Duration::from_secs(5)).await?;
+//! ```
+
+use std::time::Duration;
+
+use crate::error::*;
+use crate::exchange::ExchangeApi;
+use crate::types::*;
+
+/// Whether the order may have been placed despite `e`
+fn is_uncertain(e: &Error) -> bool {
+    match e.kind() {
+        Error::ServiceUnavailable(_) | Error::Hyper(_) | Error::Io(_) => true,
+        _ => false,
+    }
+}
+
+/// Place the limit or market order `request` tagged with `client_id`, making up to `attempts`
+/// submissions each limited to `timeout`. Submissions whose outcome is unknown are followed by a
+/// lookup of `client_id`, the order found is returned instead of being sent again unless its known
+/// pair or quantity differ from `request`, as happens with the non unique userrefs of Kraken.
+/// Errors of the lookups are returned as is, since retrying blindly could place the order twice.
+pub async fn submit_once(api: &dyn ExchangeApi, request: &OrderRequest, client_id: &str, attempts: u32, timeout: Duration) -> Result<OrderInfo> {
+    let mut attempt = 1;
+    loop {
+        let submission = api.add_order_with_id(request.order_type, request.pair, request.quantity.clone(), request.price.clone(), client_id);
+        let e = match tokio::time::timeout(timeout, submission).await {
+            Ok(Err(e)) if is_uncertain(&e) => e,
+            Ok(result) => return result,
+            Err(_) => Error::ServiceUnavailable(format!("no answer after {:?}", timeout)),
+        };
+        warn!("Order {} : attempt {}/{} failed : {}", client_id, attempt, attempts, e);
+        if let Some(mut order) = api.order_by_client_id(request.pair, client_id).await? {
+            let matching = order.request.as_ref().map_or(true, |r| r.pair == request.pair && r.quantity == request.quantity);
+            if matching {
+                order.request.get_or_insert_with(|| request.clone().with_client_id(client_id));
+                return Ok(order);
+            }
+            warn!("Order {} : order {:?} found with another pair or quantity, ignored", client_id, order.identifier);
+        }
+        if attempt >= attempts {
+            return Err(e);
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod submission_tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bigdecimal::BigDecimal;
+    use std::sync::Mutex;
+
+    /// Places the orders but loses the answer of the first submission
+    #[derive(Debug, Default)]
+    struct Flaky(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl ExchangeApi for Flaky {
+        async fn ticker(&self, _pair: Pair) -> Result<Ticker> { Err(Error::Unsupported) }
+        async fn orderbook(&self, _pair: Pair) -> Result<Orderbook> { Err(Error::Unsupported) }
+        async fn add_order(&self, _order_type: OrderType, _pair: Pair, _quantity: Volume, _price: Option<Price>) -> Result<OrderInfo> { Err(Error::Unsupported) }
+        async fn balances(&self) -> Result<Balances> { Err(Error::Unsupported) }
+
+        async fn add_order_with_id(&self, _order_type: OrderType, _pair: Pair, _quantity: Volume, _price: Option<Price>, client_id: &str) -> Result<OrderInfo> {
+            let mut placed = self.0.lock().unwrap();
+            placed.push(client_id.to_string());
+            if placed.len() == 1 {
+                return Err(Error::ServiceUnavailable("connection reset".to_string()));
+            }
+            Ok(OrderInfo { timestamp: 0, identifier: vec![placed.len().to_string()], raw: None, request: None })
+        }
+
+        async fn order_by_client_id(&self, _pair: Pair, client_id: &str) -> Result<Option<OrderInfo>> {
+            let placed = self.0.lock().unwrap();
+            Ok(placed.iter().position(|id| id == client_id)
+                .map(|i| OrderInfo { timestamp: 0, identifier: vec![(i + 1).to_string()], raw: None, request: None }))
+        }
+    }
+
+    #[test]
+    fn lost_submissions_are_found_instead_of_sent_again() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let api = Flaky::default();
+        let request = OrderRequest::new(OrderType::BuyLimit, Pair::BTC_USD, &BigDecimal::from(1), &Some(BigDecimal::from(100)));
+        let order = rt.block_on(submit_once(&api, &request, "42", 3, Duration::from_secs(1))).unwrap();
+        assert_eq!(order.identifier, vec!["1".to_string()]);
+        assert_eq!(order.request.unwrap().client_id, Some("42".to_string()));
+        assert_eq!(api.0.lock().unwrap().len(), 1);
+    }
+
+    /// Loses the answer of every submission, the lookup finds an order of another quantity
+    #[derive(Debug, Default)]
+    struct Shared(Mutex<u32>);
+
+    #[async_trait]
+    impl ExchangeApi for Shared {
+        async fn ticker(&self, _pair: Pair) -> Result<Ticker> { Err(Error::Unsupported) }
+        async fn orderbook(&self, _pair: Pair) -> Result<Orderbook> { Err(Error::Unsupported) }
+        async fn add_order(&self, _order_type: OrderType, _pair: Pair, _quantity: Volume, _price: Option<Price>) -> Result<OrderInfo> { Err(Error::Unsupported) }
+        async fn balances(&self) -> Result<Balances> { Err(Error::Unsupported) }
+
+        async fn add_order_with_id(&self, _order_type: OrderType, _pair: Pair, _quantity: Volume, _price: Option<Price>, _client_id: &str) -> Result<OrderInfo> {
+            *self.0.lock().unwrap() += 1;
+            Err(Error::ServiceUnavailable("connection reset".to_string()))
+        }
+
+        async fn order_by_client_id(&self, pair: Pair, client_id: &str) -> Result<Option<OrderInfo>> {
+            let request = OrderRequest::new(OrderType::BuyLimit, pair, &BigDecimal::from(5), &None).with_client_id(client_id);
+            Ok(Some(OrderInfo { timestamp: 0, identifier: vec!["other".to_string()], raw: None, request: Some(request) }))
+        }
+    }
+
+    #[test]
+    fn orders_of_another_quantity_are_not_taken_for_the_submission() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let api = Shared::default();
+        let request = OrderRequest::new(OrderType::BuyLimit, Pair::BTC_USD, &BigDecimal::from(1), &Some(BigDecimal::from(100)));
+        assert!(rt.block_on(submit_once(&api, &request, "42", 2, Duration::from_secs(1))).is_err());
+        assert_eq!(*api.0.lock().unwrap(), 2);
+    }
+}
@@ -0,0 +1,226 @@
+//! Run the streams of a deployment configuration under a single actor.
+//!
+//! `CoinnectSupervisor` builds a streaming bot for every stream of a `DeploymentConfig`, forwards
+//! the events of all of them to one set of recipients, and rebuilds any bot that is no longer
+//! connected. The sinks of the streams are not started, subscribe them to the supervisor instead.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix::{Actor, ActorFuture, Addr, AsyncContext, Context, ContextFutureSpawner, Handler, MessageResult, Recipient, WrapFuture};
+
+use crate::coinnect::Coinnect;
+use crate::config::{AccountCreds, DeploymentConfig};
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeSettings};
+use crate::exchange_bot::ExchangeBot;
+use crate::helpers;
+use crate::types::LiveEventEnveloppe;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// State of a supervised stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamStatus {
+    pub exchange: Exchange,
+    pub connected: bool,
+    /// Number of times the bot was rebuilt after losing its connection
+    pub restarts: u32,
+    pub events: u64,
+    /// UNIX timestamp in ms of the last event received
+    pub last_event_ms: Option<i64>,
+}
+
+/// Query the status of every stream, by account name
+#[derive(Message)]
+#[rtype(result = "HashMap<String, StreamStatus>")]
+pub struct GetStatus;
+
+/// Add a recipient of the events of every stream
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Subscribe(pub Recipient<LiveEventEnveloppe>);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct StreamEvent(String, LiveEventEnveloppe);
+
+/// Tags the events of one bot with the name of its stream
+struct StreamTap {
+    name: String,
+    supervisor: Addr<CoinnectSupervisor>,
+}
+
+impl Actor for StreamTap {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for StreamTap {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        self.supervisor.do_send(StreamEvent(self.name.clone(), msg));
+    }
+}
+
+struct SupervisedStream {
+    exchange: Exchange,
+    creds: AccountCreds,
+    settings: ExchangeSettings,
+    bot: Option<Box<dyn ExchangeBot>>,
+    starting: bool,
+    restarts: u32,
+    events: u64,
+    last_event_ms: Option<i64>,
+}
+
+impl SupervisedStream {
+    fn is_connected(&self) -> bool {
+        self.bot.as_ref().map_or(false, |b| b.is_connected())
+    }
+}
+
+pub struct CoinnectSupervisor {
+    streams: HashMap<String, SupervisedStream>,
+    recipients: Vec<Recipient<LiveEventEnveloppe>>,
+}
+
+impl CoinnectSupervisor {
+    /// A supervisor of every stream of `config`, the bots are built once the actor is started
+    pub fn new(config: &DeploymentConfig, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Result<Self> {
+        let mut streams = HashMap::new();
+        for (name, stream) in config.stream_names()?.into_iter().zip(&config.streams) {
+            let account = config.accounts.get(&stream.account).ok_or_else(|| Error::MissingField(stream.account.clone()))?;
+            streams.insert(name, SupervisedStream {
+                exchange: account.exchange,
+                creds: AccountCreds::new(&stream.account, account.exchange, account.credentials.clone()),
+                settings: stream.settings.clone(),
+                bot: None,
+                starting: false,
+                restarts: 0,
+                events: 0,
+                last_event_ms: None,
+            });
+        }
+        Ok(CoinnectSupervisor { streams, recipients })
+    }
+
+    fn start_stream(&mut self, name: String, ctx: &mut Context<Self>) {
+        let stream = match self.streams.get_mut(&name) {
+            Some(stream) if !stream.starting => stream,
+            _ => return,
+        };
+        stream.starting = true;
+        let tap = StreamTap { name: name.clone(), supervisor: ctx.address() }.start();
+        Coinnect::new_stream(stream.exchange, Box::new(stream.creds.clone()), stream.settings.clone(), vec![tap.recipient()])
+            .into_actor(self)
+            .map(move |res, act, _ctx| {
+                if let Some(stream) = act.streams.get_mut(&name) {
+                    stream.starting = false;
+                    match res {
+                        Ok(bot) => {
+                            info!("Supervisor : stream {} started on {:?}", name, stream.exchange);
+                            stream.bot = Some(bot);
+                        }
+                        Err(e) => error!("Supervisor : unable to start stream {} : {}", name, e),
+                    }
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Rebuild the bots that are not connected
+    fn check(&mut self, ctx: &mut Context<Self>) {
+        let down: Vec<String> = self.streams.iter()
+            .filter(|(_, stream)| !stream.starting && !stream.is_connected())
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in down {
+            if let Some(stream) = self.streams.get_mut(&name) {
+                if stream.bot.take().is_some() {
+                    warn!("Supervisor : stream {} disconnected, restarting", name);
+                    stream.restarts += 1;
+                }
+            }
+            self.start_stream(name, ctx);
+        }
+    }
+}
+
+impl Actor for CoinnectSupervisor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        let names: Vec<String> = self.streams.keys().cloned().collect();
+        for name in names {
+            self.start_stream(name, ctx);
+        }
+        ctx.run_interval(CHECK_INTERVAL, |act, ctx| act.check(ctx));
+    }
+}
+
+impl Handler<StreamEvent> for CoinnectSupervisor {
+    type Result = ();
+
+    fn handle(&mut self, msg: StreamEvent, _ctx: &mut Context<Self>) {
+        if let Some(stream) = self.streams.get_mut(&msg.0) {
+            stream.events += 1;
+            stream.last_event_ms = Some(helpers::get_unix_timestamp_ms());
+        }
+        for r in &self.recipients {
+            if let Err(e) = r.do_send(msg.1.clone()) {
+                trace!("Supervisor : unable to send to recipient : {}", e);
+            }
+        }
+    }
+}
+
+impl Handler<Subscribe> for CoinnectSupervisor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Subscribe, _ctx: &mut Context<Self>) {
+        self.recipients.push(msg.0);
+    }
+}
+
+impl Handler<GetStatus> for CoinnectSupervisor {
+    type Result = MessageResult<GetStatus>;
+
+    fn handle(&mut self, _msg: GetStatus, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.streams.iter().map(|(name, stream)| (name.clone(), StreamStatus {
+            exchange: stream.exchange,
+            connected: stream.is_connected(),
+            restarts: stream.restarts,
+            events: stream.events,
+            last_event_ms: stream.last_event_ms,
+        })).collect())
+    }
+}
+
+#[cfg(test)]
+mod supervisor_tests {
+    use super::*;
+
+    #[test]
+    fn streams_need_a_known_account() {
+        let config: DeploymentConfig = serde_json::from_str(r#"{
+            "accounts": {"account_bitstamp": {"exchange": "bitstamp"}},
+            "streams": [{"account": "account_kraken", "settings": {"orderbook": {"symbols": ["BTC_EUR"]}}, "sinks": []}]
+        }"#).unwrap();
+        assert!(CoinnectSupervisor::new(&config, vec![]).is_err());
+    }
+
+    #[test]
+    fn streams_of_an_account_are_supervised_separately() {
+        let config: DeploymentConfig = serde_json::from_str(r#"{
+            "accounts": {"account_bitstamp": {"exchange": "bitstamp"}},
+            "streams": [
+                {"account": "account_bitstamp", "settings": {"orderbook": {"symbols": ["BTC_EUR"]}}},
+                {"account": "account_bitstamp", "name": "bitstamp_trades", "settings": {"trades": {"symbols": ["BTC_EUR"]}}}
+            ]
+        }"#).unwrap();
+        let supervisor = CoinnectSupervisor::new(&config, vec![]).unwrap();
+        assert_eq!(supervisor.streams.len(), 2);
+        assert!(supervisor.streams.contains_key("bitstamp_trades"));
+    }
+}
@@ -0,0 +1,109 @@
+//! Withdraw everything but a reserve of a currency from an exchange account.
+//! Withdrawal fees and minimums are not exposed by every exchange, they are given per currency
+//! as `WithdrawalTerms`.
+
+use std::collections::HashMap;
+
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeApi};
+use crate::types::*;
+
+/// Withdrawal conditions of a currency on an exchange
+#[derive(Debug, Clone, Deserialize)]
+pub struct WithdrawalTerms {
+    /// Fee charged for a withdrawal, in the withdrawn currency
+    pub fee: Amount,
+    /// Smallest amount the exchange accepts to withdraw
+    pub min_amount: Amount,
+}
+
+/// Amount to withdraw from `balance` once the reserve, and the fee when `exchange` charges it on
+/// top of the amount, are set aside
+pub fn withdrawable_amount(exchange: Exchange, balance: &Amount, terms: &WithdrawalTerms, reserve: &Amount) -> Result<Amount> {
+    let (amount, received) = if exchange.withdrawal_fee_included() {
+        let amount = balance.clone() - reserve.clone();
+        (amount.clone(), amount - terms.fee.clone())
+    } else {
+        let amount = balance.clone() - terms.fee.clone() - reserve.clone();
+        (amount.clone(), amount)
+    };
+    if received <= BigDecimal::zero() || amount < terms.min_amount {
+        return Err(Error::InsufficientFunds);
+    }
+    Ok(amount)
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Sweeper {
+    terms: HashMap<Currency, WithdrawalTerms>,
+    /// Amounts kept on the account, none by default
+    #[serde(default)]
+    reserves: HashMap<Currency, Amount>,
+}
+
+impl Sweeper {
+    pub fn new(terms: HashMap<Currency, WithdrawalTerms>) -> Self {
+        Sweeper { terms, reserves: HashMap::new() }
+    }
+
+    /// Keep `amount` of `currency` on the account when sweeping
+    pub fn with_reserve(mut self, currency: Currency, amount: Amount) -> Self {
+        self.reserves.insert(currency, amount);
+        self
+    }
+
+    /// Withdraw the whole available balance of `currency` but the reserve and the fee to `destination`,
+    /// `api` being a client of `exchange`.
+    /// Fails without withdrawing if the amount is below the minimum withdrawal.
+    pub async fn sweep(&self, exchange: Exchange, api: &dyn ExchangeApi, currency: Currency, destination: &str) -> Result<WithdrawalInfo> {
+        let terms = self.terms.get(&currency)
+            .ok_or_else(|| Error::MissingField(format!("{:?} withdrawal terms", currency)))?;
+        let reserve = self.reserves.get(&currency).cloned().unwrap_or_else(BigDecimal::zero);
+        let balance = api.balances().await?.get(&currency).cloned().unwrap_or_else(BigDecimal::zero);
+        let amount = withdrawable_amount(exchange, &balance, terms, &reserve)?;
+        info!("Sweeping {} {:?} to {}", amount, currency, destination);
+        api.withdraw(currency, amount, destination).await
+    }
+}
+
+#[cfg(test)]
+mod sweep_tests {
+    use super::*;
+    use std::str::FromStr;
+    use crate::simulated::{SimulatedApi, SimulatedCreds};
+
+    fn terms() -> HashMap<Currency, WithdrawalTerms> {
+        let mut terms = HashMap::new();
+        terms.insert(Currency::BTC, WithdrawalTerms { fee: BigDecimal::from_str("0.0005").unwrap(), min_amount: BigDecimal::from_str("0.001").unwrap() });
+        terms
+    }
+
+    #[test]
+    fn sweep_keeps_fee_and_reserve() {
+        let creds = SimulatedCreds::new("test", r#"{"BTC": "1.5"}"#, "0", "0");
+        let api = SimulatedApi::new(creds).unwrap();
+        let sweeper = Sweeper::new(terms()).with_reserve(Currency::BTC, BigDecimal::from_str("0.5").unwrap());
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            assert!(sweeper.sweep(Exchange::Simulated, &api, Currency::BTC, "address").await.is_ok());
+            let balances = api.balances().await.unwrap();
+            assert_eq!(balances[&Currency::BTC], BigDecimal::from_str("0.5005").unwrap());
+            // What is left is the reserve and the fee
+            assert!(sweeper.sweep(Exchange::Simulated, &api, Currency::BTC, "address").await.is_err());
+        });
+    }
+
+    #[test]
+    fn kraken_takes_the_fee_from_the_withdrawn_amount() {
+        let terms = &terms()[&Currency::BTC];
+        let reserve = BigDecimal::from_str("0.5").unwrap();
+        let balance = BigDecimal::from_str("1.5").unwrap();
+        assert_eq!(withdrawable_amount(Exchange::Kraken, &balance, terms, &reserve).unwrap(), BigDecimal::from(1));
+        assert_eq!(withdrawable_amount(Exchange::Bitstamp, &balance, terms, &reserve).unwrap(), BigDecimal::from_str("0.9995").unwrap());
+        // Nothing would be received once the fee is paid
+        let balance = BigDecimal::from_str("0.5005").unwrap();
+        assert!(withdrawable_amount(Exchange::Kraken, &balance, terms, &reserve).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! Indirection between consumers and the streaming bot feeding them, so that the underlying
+//! implementation can be replaced at runtime (e.g. to migrate to a newer protocol) without the
+//! consumers noticing.
+//!
+//! Swaps are make-before-break : the new bot is connected while the old one keeps feeding the
+//! consumers, then events of the old bot are dropped from the moment the new one is activated.
+//! The events the new bot sends while it is built, such as its first snapshot, are buffered and
+//! forwarded on activation. Events of both bots are never interleaved and are always enveloped
+//! with the same exchange.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::Arc;
+
+use actix::{Actor, Addr, Context, Handler, Recipient};
+use futures::future::{self, LocalBoxFuture};
+
+use crate::error::*;
+use crate::exchange::{Exchange, ExchangeCapabilities};
+use crate::exchange_bot::{ExchangeBot, RawFrameEvent};
+use crate::helpers::failover::Endpoints;
+use crate::types::LiveEventEnveloppe;
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct TaggedEvent(u64, LiveEventEnveloppe);
+
+/// Buffer the events of a generation until it is activated or discarded
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Prepare(u64);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Activate(u64);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Discard(u64);
+
+/// Most events buffered for a generation being built, the newest ones are dropped past it
+const MAX_BUFFERED: usize = 10_000;
+
+/// Forwards the events of the active generation to the consumers
+struct StreamRouter {
+    exchange: Exchange,
+    current: u64,
+    recipients: Vec<Recipient<LiveEventEnveloppe>>,
+    /// Generation being built and its events so far
+    next: Option<(u64, VecDeque<LiveEventEnveloppe>)>,
+}
+
+impl StreamRouter {
+    fn forward(&self, msg: LiveEventEnveloppe) {
+        for r in &self.recipients {
+            if let Err(e) = r.do_send(LiveEventEnveloppe(self.exchange, msg.1.clone())) {
+                trace!("Stream switch : unable to send to recipient : {}", e);
+            }
+        }
+    }
+}
+
+impl Actor for StreamRouter {
+    type Context = Context<Self>;
+}
+
+impl Handler<TaggedEvent> for StreamRouter {
+    type Result = ();
+
+    fn handle(&mut self, msg: TaggedEvent, _ctx: &mut Context<Self>) {
+        if msg.0 == self.current {
+            self.forward(msg.1);
+        } else if let Some((generation, buffered)) = self.next.as_mut() {
+            if *generation != msg.0 {
+                return;
+            }
+            if buffered.len() < MAX_BUFFERED {
+                buffered.push_back(msg.1);
+            } else {
+                warn!("Stream switch {:?} : generation {} buffered too many events, dropping", self.exchange, msg.0);
+            }
+        }
+    }
+}
+
+impl Handler<Prepare> for StreamRouter {
+    type Result = ();
+
+    fn handle(&mut self, msg: Prepare, _ctx: &mut Context<Self>) {
+        self.next = Some((msg.0, VecDeque::new()));
+    }
+}
+
+impl Handler<Activate> for StreamRouter {
+    type Result = ();
+
+    fn handle(&mut self, msg: Activate, _ctx: &mut Context<Self>) {
+        info!("Stream switch {:?} : activating generation {}", self.exchange, msg.0);
+        self.current = msg.0;
+        if let Some((generation, buffered)) = self.next.take() {
+            if generation == msg.0 {
+                for event in buffered {
+                    self.forward(event);
+                }
+            }
+        }
+    }
+}
+
+impl Handler<Discard> for StreamRouter {
+    type Result = ();
+
+    fn handle(&mut self, msg: Discard, _ctx: &mut Context<Self>) {
+        if self.next.as_ref().map_or(false, |(generation, _)| *generation == msg.0) {
+            self.next = None;
+        }
+    }
+}
+
+/// Tags the events of one bot with its generation
+struct GenerationTap {
+    generation: u64,
+    router: Addr<StreamRouter>,
+}
+
+impl Actor for GenerationTap {
+    type Context = Context<Self>;
+}
+
+impl Handler<LiveEventEnveloppe> for GenerationTap {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+        self.router.do_send(TaggedEvent(self.generation, msg));
+    }
+}
+
+pub struct SwitchableStream {
+    router: Addr<StreamRouter>,
+    generation: u64,
+    bot: Option<Box<dyn ExchangeBot>>,
+    /// Registered again on every new bot
+    raw_observers: RefCell<Vec<Recipient<RawFrameEvent>>>,
+}
+
+impl SwitchableStream {
+    /// Create a stream without any bot, events are enveloped with `exchange`
+    pub fn new(exchange: Exchange, recipients: Vec<Recipient<LiveEventEnveloppe>>) -> Self {
+        let router = StreamRouter { exchange, current: 0, recipients, next: None }.start();
+        SwitchableStream { router, generation: 0, bot: None, raw_observers: RefCell::new(vec![]) }
+    }
+
+    /// Replace the current bot with the one built by `factory`, which must send its events to
+    /// the given recipient. The current bot is kept if the new one cannot be built.
+    pub async fn swap<F, Fut>(&mut self, factory: F) -> Result<()>
+        where F: FnOnce(Recipient<LiveEventEnveloppe>) -> Fut,
+              Fut: Future<Output = Result<Box<dyn ExchangeBot>>> {
+        let generation = self.generation + 1;
+        self.router.do_send(Prepare(generation));
+        let tap = GenerationTap { generation, router: self.router.clone() }.start();
+        let bot = match factory(tap.recipient()).await {
+            Ok(bot) => bot,
+            Err(e) => {
+                self.router.do_send(Discard(generation));
+                return Err(e);
+            }
+        };
+        for r in self.raw_observers.borrow().iter() {
+            if let Err(e) = bot.observe_raw_frames(r.clone()) {
+                warn!("Stream switch : raw frames of generation {} are not observed : {}", generation, e);
+            }
+        }
+        self.router.do_send(Activate(generation));
+        self.generation = generation;
+        self.bot = Some(bot);
+        Ok(())
+    }
+
+    /// Generation of the active bot, incremented on every swap
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl ExchangeBot for SwitchableStream {
+    fn is_connected(&self) -> bool {
+        self.bot.as_ref().map_or(false, |b| b.is_connected())
+    }
+
+    fn capabilities(&self) -> ExchangeCapabilities {
+        self.bot.as_ref().map(|b| b.capabilities()).unwrap_or_default()
+    }
+
+    fn endpoints(&self) -> Option<Arc<Endpoints>> {
+        self.bot.as_ref().and_then(|b| b.endpoints())
+    }
+
+    fn shutdown(&self) -> LocalBoxFuture<'static, ()> {
+        match self.bot.as_ref() {
+            Some(bot) => bot.shutdown(),
+            None => Box::pin(future::ready(())),
+        }
+    }
+
+    /// Observe the current bot and the ones it is swapped for. The frames sent while a new bot
+    /// connects are missed, pass the recipient to its `ExchangeSettings` to get them.
+    fn observe_raw_frames(&self, recipient: Recipient<RawFrameEvent>) -> Result<()> {
+        self.raw_observers.borrow_mut().push(recipient.clone());
+        self.bot.as_ref().map_or(Ok(()), |bot| bot.observe_raw_frames(recipient))
+    }
+}
+
+#[cfg(test)]
+mod switch_tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use actix::System;
+
+    use super::*;
+    use crate::types::{Channel, LiveEvent, Pair, SubscriptionResult};
+
+    struct Collector(Arc<Mutex<Vec<LiveEvent>>>);
+
+    impl Actor for Collector {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<LiveEventEnveloppe> for Collector {
+        type Result = ();
+
+        fn handle(&mut self, msg: LiveEventEnveloppe, _ctx: &mut Context<Self>) {
+            self.0.lock().unwrap().push(msg.1);
+        }
+    }
+
+    struct Connected;
+
+    impl ExchangeBot for Connected {
+        fn is_connected(&self) -> bool { true }
+    }
+
+    /// Counts the observers registered on it
+    struct Observed(Arc<Mutex<usize>>);
+
+    impl ExchangeBot for Observed {
+        fn is_connected(&self) -> bool { true }
+
+        fn observe_raw_frames(&self, _recipient: Recipient<RawFrameEvent>) -> Result<()> {
+            *self.0.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    struct NoFrames;
+
+    impl Actor for NoFrames {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<RawFrameEvent> for NoFrames {
+        type Result = ();
+
+        fn handle(&mut self, _msg: RawFrameEvent, _ctx: &mut Context<Self>) {}
+    }
+
+    fn event(pair: Pair) -> LiveEventEnveloppe {
+        LiveEventEnveloppe(Exchange::Bitstamp, LiveEvent::SubscriptionResult(SubscriptionResult::accepted(Channel::LiveTrades, pair)))
+    }
+
+    #[test]
+    fn events_sent_while_building_are_kept() {
+        System::new("switch_tests").block_on(async {
+            let events = Arc::new(Mutex::new(vec![]));
+            let collector = Collector(events.clone()).start();
+            let mut stream = SwitchableStream::new(Exchange::Bitstamp, vec![collector.recipient()]);
+            let mut old = None;
+            stream.swap(|r| {
+                old = Some(r);
+                async { Ok(Box::new(Connected) as Box<dyn ExchangeBot>) }
+            }).await.unwrap();
+            let old = old.unwrap();
+            old.do_send(event(Pair::BTC_USD)).unwrap();
+            tokio::time::delay_for(Duration::from_millis(20)).await;
+
+            stream.swap(|r| {
+                // e.g. the first snapshot of the new bot
+                r.do_send(event(Pair::ETH_USD)).unwrap();
+                async { Ok(Box::new(Connected) as Box<dyn ExchangeBot>) }
+            }).await.unwrap();
+            tokio::time::delay_for(Duration::from_millis(20)).await;
+            old.do_send(event(Pair::LTC_USD)).unwrap();
+            tokio::time::delay_for(Duration::from_millis(20)).await;
+
+            let pairs: Vec<Pair> = events.lock().unwrap().iter().map(|e| match e {
+                LiveEvent::SubscriptionResult(r) => r.pair,
+                _ => panic!("unexpected event"),
+            }).collect();
+            assert_eq!(pairs, vec![Pair::BTC_USD, Pair::ETH_USD]);
+            assert_eq!(stream.generation(), 2);
+        });
+    }
+
+    #[test]
+    fn failed_swaps_keep_the_current_bot() {
+        System::new("switch_tests").block_on(async {
+            let events = Arc::new(Mutex::new(vec![]));
+            let collector = Collector(events.clone()).start();
+            let mut stream = SwitchableStream::new(Exchange::Bitstamp, vec![collector.recipient()]);
+            let mut old = None;
+            stream.swap(|r| {
+                old = Some(r);
+                async { Ok(Box::new(Connected) as Box<dyn ExchangeBot>) }
+            }).await.unwrap();
+            let failed = stream.swap(|r| {
+                r.do_send(event(Pair::ETH_USD)).unwrap();
+                async { Err(Error::Unsupported) }
+            }).await;
+            assert!(failed.is_err());
+            old.unwrap().do_send(event(Pair::BTC_USD)).unwrap();
+            tokio::time::delay_for(Duration::from_millis(20)).await;
+
+            assert_eq!(events.lock().unwrap().len(), 1);
+            assert_eq!(stream.generation(), 1);
+            assert!(stream.is_connected());
+        });
+    }
+
+    #[test]
+    fn raw_frame_observers_follow_the_swaps() {
+        System::new("switch_tests").block_on(async {
+            let registered = Arc::new(Mutex::new(0));
+            let mut stream = SwitchableStream::new(Exchange::Bitstamp, vec![]);
+            stream.observe_raw_frames(NoFrames.start().recipient()).unwrap();
+            for _ in 0..2 {
+                let bot = Observed(registered.clone());
+                stream.swap(|_| async { Ok(Box::new(bot) as Box<dyn ExchangeBot>) }).await.unwrap();
+            }
+            assert_eq!(*registered.lock().unwrap(), 2);
+        });
+    }
+}
@@ -0,0 +1,464 @@
+//! In-process mock of an exchange, to run integration tests without real credentials.
+//! `MockExchange` serves simplified Bitstamp or Kraken REST and websocket protocols on a local
+//! port : orders are accepted and recorded, websocket subscriptions are acknowledged, and the test
+//! pushes live frames or drops the connections to exercise the reconnect logic :
+//!
+//! ```ignore
+//! let mock = MockExchange::start(Exchange::Bitstamp)?;
+//! let api = BitstampApi::new(mock.credentials())?;
+//! api.add_order(OrderType::BuyLimit, Pair::BTC_USD, volume, Some(price)).await?;
+//! assert_eq!(mock.orders().len(), 1);
+//! mock.disconnect_all();
+//! ```
+//!
+//! The server runs on the actix system of the caller. Any other REST response is set with
+//! `set_response`, keyed by its path relative to the REST root, e.g. `ticker/btcusd` for Bitstamp
+//! or `0/public/Ticker` for Kraken.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use actix::{Actor, ActorContext, Addr, AsyncContext, Handler, StreamHandler};
+use actix_web::dev::Server;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_web_actors::ws;
+use serde_json::{json, Value};
+
+use crate::coinnect::Credentials;
+use crate::error::*;
+use crate::exchange::Exchange;
+use crate::helpers;
+
+/// Websocket token answered by the Kraken mock
+pub const MOCK_WS_TOKEN: &str = "mock-token";
+
+/// An order received by the mock, over REST or websocket
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockOrder {
+    /// Identifier answered to the client
+    pub id: String,
+    /// REST path or websocket event of the request
+    pub method: String,
+    /// Parameters of the request
+    pub params: HashMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    exchange: Option<Exchange>,
+    responses: HashMap<String, Value>,
+    orders: Vec<MockOrder>,
+    cancelled: Vec<String>,
+    subscriptions: Vec<String>,
+    sessions: Vec<Addr<MockSession>>,
+    connections: usize,
+}
+
+type Shared = Arc<Mutex<MockState>>;
+
+impl MockState {
+    fn exchange(&self) -> Exchange {
+        self.exchange.unwrap_or(Exchange::Bitstamp)
+    }
+
+    fn add_order(&mut self, method: &str, params: HashMap<String, String>) -> String {
+        let id = format!("{}", 1000 + self.orders.len());
+        self.orders.push(MockOrder { id: id.clone(), method: method.to_string(), params });
+        id
+    }
+
+    /// The response to a REST request on `path`, `None` if the mock does not know the path
+    fn rest_reply(&mut self, path: &str, params: HashMap<String, String>) -> Option<Value> {
+        if let Some(response) = self.responses.get(path) {
+            return Some(response.clone());
+        }
+        match (self.exchange(), path) {
+            (Exchange::Bitstamp, p) if p.starts_with("buy/") || p.starts_with("sell/") => {
+                let ty = if p.starts_with("buy/") { "0" } else { "1" };
+                let (price, amount) = (params.get("price").cloned(), params.get("amount").cloned());
+                let id = self.add_order(p, params);
+                Some(json!({"id": id, "type": ty, "price": price, "amount": amount, "datetime": helpers::get_unix_timestamp_ms().to_string()}))
+            }
+            (Exchange::Bitstamp, "cancel_order") => {
+                let id = params.get("id").cloned().unwrap_or_default();
+                self.cancelled.push(id.clone());
+                Some(json!({"id": id}))
+            }
+            (Exchange::Kraken, "0/private/AddOrder") => {
+                let descr = format!("{} {} {}", params.get("type").map_or("", |s| s), params.get("volume").map_or("", |s| s), params.get("pair").map_or("", |s| s));
+                let id = self.add_order(path, params);
+                Some(json!({"error": [], "result": {"descr": {"order": descr}, "txid": [id]}}))
+            }
+            (Exchange::Kraken, "0/private/CancelOrder") => {
+                self.cancelled.push(params.get("txid").cloned().unwrap_or_default());
+                Some(json!({"error": [], "result": {"count": 1}}))
+            }
+            (Exchange::Kraken, "0/private/GetWebSocketsToken") => Some(json!({"error": [], "result": {"token": MOCK_WS_TOKEN, "expires": 900}})),
+            (Exchange::Kraken, "0/public/Time") => {
+                Some(json!({"error": [], "result": {"unixtime": helpers::get_unix_timestamp_ms() / 1000}}))
+            }
+            _ => None,
+        }
+    }
+
+    fn unknown_method(&self) -> Value {
+        match self.exchange() {
+            Exchange::Kraken => json!({"error": ["EGeneral:Unknown method"]}),
+            _ => json!({"status": "error", "reason": "Invalid command."}),
+        }
+    }
+
+    /// The answer to a websocket request, `None` if it needs none
+    fn ws_reply(&mut self, request: &Value) -> Option<Value> {
+        match (self.exchange(), request["event"].as_str()?) {
+            (Exchange::Bitstamp, "bts:subscribe") => {
+                let channel = request["data"]["channel"].as_str()?.to_string();
+                self.subscriptions.push(channel.clone());
+                Some(json!({"event": "bts:subscription_succeeded", "channel": channel, "data": {}}))
+            }
+            (Exchange::Kraken, "subscribe") => {
+                let name = request["subscription"]["name"].as_str()?.to_string();
+                self.subscriptions.push(name.clone());
+                Some(json!({"event": "subscriptionStatus", "status": "subscribed", "subscription": {"name": name}}))
+            }
+            (Exchange::Kraken, "addOrder") => {
+                let params = request.as_object()?.iter()
+                    .filter(|(k, _)| *k != "event" && *k != "token")
+                    .map(|(k, v)| (k.clone(), v.as_str().map_or_else(|| v.to_string(), |s| s.to_string())))
+                    .collect();
+                let id = self.add_order("addOrder", params);
+                Some(json!({"event": "addOrderStatus", "reqid": request["reqid"], "status": "ok", "txid": id}))
+            }
+            (Exchange::Kraken, "cancelOrder") => {
+                for txid in request["txid"].as_array()?.iter().filter_map(|t| t.as_str()) {
+                    self.cancelled.push(txid.to_string());
+                }
+                Some(json!({"event": "cancelOrderStatus", "reqid": request["reqid"], "status": "ok"}))
+            }
+            (Exchange::Kraken, "ping") => Some(json!({"event": "pong", "reqid": request["reqid"]})),
+            _ => None,
+        }
+    }
+}
+
+/// Credentials pointing a client to a `MockExchange`
+#[derive(Debug, Clone)]
+pub struct MockCreds {
+    exchange: Exchange,
+    data: HashMap<String, String>,
+}
+
+impl Credentials for MockCreds {
+    fn get(&self, cred: &str) -> Option<String> {
+        self.data.get(cred).cloned()
+    }
+
+    fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+
+    fn name(&self) -> String {
+        format!("{:?}Mock", self.exchange)
+    }
+}
+
+/// A mock exchange listening on a local port
+pub struct MockExchange {
+    exchange: Exchange,
+    addr: SocketAddr,
+    server: Server,
+    state: Shared,
+}
+
+impl MockExchange {
+    /// Start a mock speaking the protocol of `exchange`, Bitstamp or Kraken, on a free local port.
+    /// Must be called from a running actix system.
+    pub fn start(exchange: Exchange) -> Result<MockExchange> {
+        if exchange != Exchange::Bitstamp && exchange != Exchange::Kraken {
+            return Err(Error::ExchangeSpecificError(format!("no mock for {:?}", exchange)));
+        }
+        let state: Shared = Arc::new(Mutex::new(MockState { exchange: Some(exchange), ..MockState::default() }));
+        let app_state = state.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .data(app_state.clone())
+                .route("/ws", web::get().to(ws_route))
+                .default_service(web::route().to(rest_route))
+        })
+            .workers(1)
+            .bind("127.0.0.1:0")?;
+        let addr = server.addrs()[0];
+        let server = server.run();
+        Ok(MockExchange { exchange, addr, server, state })
+    }
+
+    /// Root of the REST api, to use as the `rest_url` of a client
+    pub fn rest_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Websocket endpoint, to use as the `ws_url` of a client
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}/ws", self.addr)
+    }
+
+    /// Credentials accepted by the mock, with the endpoint overrides of the mock
+    pub fn credentials(&self) -> MockCreds {
+        let mut data = HashMap::new();
+        data.insert("api_key".to_string(), "mock-key".to_string());
+        data.insert("api_secret".to_string(), base64::encode("mock-secret"));
+        data.insert("customer_id".to_string(), "1".to_string());
+        data.insert("rest_url".to_string(), self.rest_url());
+        data.insert("ws_url".to_string(), self.ws_url());
+        MockCreds { exchange: self.exchange, data }
+    }
+
+    /// Answer `response` to every REST request on `path`, relative to the REST root
+    pub fn set_response(&self, path: &str, response: Value) {
+        self.state.lock().unwrap().responses.insert(path.trim_matches('/').to_string(), response);
+    }
+
+    /// Orders received so far
+    pub fn orders(&self) -> Vec<MockOrder> {
+        self.state.lock().unwrap().orders.clone()
+    }
+
+    /// Identifiers of the orders cancelled so far
+    pub fn cancelled(&self) -> Vec<String> {
+        self.state.lock().unwrap().cancelled.clone()
+    }
+
+    /// Channels subscribed to so far, over every connection
+    pub fn subscriptions(&self) -> Vec<String> {
+        self.state.lock().unwrap().subscriptions.clone()
+    }
+
+    /// Websocket connections accepted so far, reconnections included
+    pub fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+
+    /// Send a frame to every connected websocket client
+    pub fn push(&self, frame: Value) {
+        let mut state = self.state.lock().unwrap();
+        state.sessions.retain(|s| s.connected());
+        for session in &state.sessions {
+            session.do_send(Push(frame.to_string()));
+        }
+    }
+
+    /// Close every websocket connection, as an exchange going through maintenance
+    pub fn disconnect_all(&self) {
+        for session in self.state.lock().unwrap().sessions.drain(..) {
+            session.do_send(Disconnect);
+        }
+    }
+
+    /// Stop the server
+    pub async fn stop(self) {
+        self.server.stop(true).await;
+    }
+}
+
+async fn rest_route(req: HttpRequest, body: web::Bytes, state: web::Data<Shared>) -> HttpResponse {
+    let path = req.path().trim_matches('/').to_string();
+    let params: HashMap<String, String> = url::form_urlencoded::parse(req.query_string().as_bytes())
+        .chain(url::form_urlencoded::parse(&body))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    let mut state = state.lock().unwrap();
+    match state.rest_reply(&path, params) {
+        Some(response) => HttpResponse::Ok().json(response),
+        None => HttpResponse::NotFound().json(state.unknown_method()),
+    }
+}
+
+async fn ws_route(req: HttpRequest, stream: web::Payload, state: web::Data<Shared>) -> std::result::Result<HttpResponse, actix_web::Error> {
+    ws::start(MockSession { state: state.get_ref().clone() }, &req, stream)
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Push(String);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Disconnect;
+
+struct MockSession {
+    state: Shared,
+}
+
+impl Actor for MockSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let mut state = self.state.lock().unwrap();
+        state.sessions.push(ctx.address());
+        state.connections += 1;
+    }
+}
+
+impl Handler<Push> for MockSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: Push, ctx: &mut Self::Context) {
+        ctx.text(msg.0);
+    }
+}
+
+impl Handler<Disconnect> for MockSession {
+    type Result = ();
+
+    fn handle(&mut self, _msg: Disconnect, ctx: &mut Self::Context) {
+        ctx.close(None);
+        ctx.stop();
+    }
+}
+
+impl StreamHandler<std::result::Result<ws::Message, ws::ProtocolError>> for MockSession {
+    fn handle(&mut self, msg: std::result::Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let request: Option<Value> = match msg {
+            Ok(ws::Message::Text(text)) => serde_json::from_str(&text).ok(),
+            Ok(ws::Message::Binary(bin)) => serde_json::from_slice(&bin).ok(),
+            Ok(ws::Message::Ping(ping)) => {
+                ctx.pong(&ping);
+                None
+            }
+            Ok(ws::Message::Close(_)) | Err(_) => {
+                ctx.stop();
+                None
+            }
+            _ => None,
+        };
+        if let Some(reply) = request.and_then(|r| self.state.lock().unwrap().ws_reply(&r)) {
+            ctx.text(reply.to_string());
+        }
+    }
+}
+
+#[cfg(all(test, feature = "bitstamp", feature = "kraken"))]
+mod testing_tests {
+    use super::*;
+    use actix_rt::System;
+    use bigdecimal::BigDecimal;
+
+    use std::time::Duration;
+
+    use crate::bitstamp::BitstampApi;
+    use crate::coinnect::Coinnect;
+    use crate::exchange::{ExchangeApi, ExchangeSettings};
+    use crate::exchange_bot::{ExchangeBot, RawFrameEvent};
+    use crate::kraken::KrakenApi;
+    use crate::types::{Balance, Currency, OrderType, Pair};
+
+    struct Frames(Arc<Mutex<Vec<String>>>);
+
+    impl Actor for Frames {
+        type Context = actix::Context<Self>;
+    }
+
+    impl Handler<RawFrameEvent> for Frames {
+        type Result = ();
+
+        fn handle(&mut self, msg: RawFrameEvent, _ctx: &mut actix::Context<Self>) {
+            self.0.lock().unwrap().push(String::from_utf8_lossy(&msg.payload).to_string());
+        }
+    }
+
+    #[test]
+    fn orders_are_recorded() {
+        System::new("testing_tests").block_on(async {
+            let mock = MockExchange::start(Exchange::Bitstamp).unwrap();
+            let api = BitstampApi::new(mock.credentials()).unwrap();
+            let order = api.add_order(OrderType::BuyLimit, Pair::BTC_USD, BigDecimal::from(1), Some(BigDecimal::from(100))).await.unwrap();
+            assert_eq!(mock.orders()[0].id, order.identifier[0]);
+            assert!(api.ticker(Pair::BTC_USD).await.is_err());
+            mock.stop().await;
+
+            let mock = MockExchange::start(Exchange::Kraken).unwrap();
+            let api = KrakenApi::new(mock.credentials()).unwrap();
+            let order = api.add_order(OrderType::SellMarket, Pair::BTC_USD, BigDecimal::from(1), None).await.unwrap();
+            assert_eq!(order.identifier, vec![mock.orders()[0].id.clone()]);
+            assert_eq!(api.websocket_token().await.unwrap().0.expose(), MOCK_WS_TOKEN);
+            mock.stop().await;
+        });
+    }
+
+    #[test]
+    fn raw_payloads_are_kept_on_demand() {
+        System::new("testing_tests").block_on(async {
+            let mock = MockExchange::start(Exchange::Bitstamp).unwrap();
+            let mut api = BitstampApi::new(mock.credentials()).unwrap();
+            let order = api.add_order(OrderType::BuyLimit, Pair::BTC_USD, BigDecimal::from(1), Some(BigDecimal::from(100))).await.unwrap();
+            assert!(order.raw.is_none());
+            api.set_keep_raw(true);
+            let order = api.add_order(OrderType::BuyLimit, Pair::BTC_USD, BigDecimal::from(1), Some(BigDecimal::from(100))).await.unwrap();
+            let raw = order.raw.unwrap();
+            assert_eq!(raw["id"], json!(order.identifier[0]));
+            mock.stop().await;
+        });
+    }
+
+    #[test]
+    fn detailed_balances_split_available_and_reserved_funds() {
+        System::new("testing_tests").block_on(async {
+            let mock = MockExchange::start(Exchange::Bitstamp).unwrap();
+            mock.set_response("balance", json!({
+                "btc_balance": "1.5", "btc_available": "1", "btc_reserved": "0.5",
+                "usd_balance": "100", "usd_available": "100", "usd_reserved": "0"
+            }));
+            let api = BitstampApi::new(mock.credentials()).unwrap();
+            let balances = api.detailed_balances().await.unwrap();
+            assert_eq!(balances[&Currency::BTC], Balance::new(BigDecimal::from(1), "0.5".parse().unwrap()));
+            assert_eq!(balances[&Currency::BTC].total, "1.5".parse().unwrap());
+            assert_eq!(balances[&Currency::USD].reserved, BigDecimal::from(0));
+            mock.stop().await;
+        });
+    }
+
+    #[test]
+    fn raw_frames_are_observed_from_the_subscription_acks() {
+        System::new("testing_tests").block_on(async {
+            let mock = MockExchange::start(Exchange::Bitstamp).unwrap();
+            let frames = Arc::new(Mutex::new(vec![]));
+            let observer = Frames(frames.clone()).start();
+            let settings = ExchangeSettings::builder().trades(vec![Pair::BTC_USD]).raw_frames(observer.recipient()).build().unwrap();
+            let bot = Coinnect::new_stream(Exchange::Bitstamp, Box::new(mock.credentials()), settings, vec![]).await.unwrap();
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+            assert!(bot.is_connected());
+            assert!(frames.lock().unwrap().iter().any(|f| f.contains("bts:subscription_succeeded")));
+            mock.stop().await;
+        });
+    }
+
+    #[test]
+    fn streams_subscribe_again_after_a_disconnection() {
+        System::new("testing_tests").block_on(async {
+            let mock = MockExchange::start(Exchange::Bitstamp).unwrap();
+            let settings = ExchangeSettings::builder().trades(vec![Pair::BTC_USD]).build().unwrap();
+            let bot = Coinnect::new_stream(Exchange::Bitstamp, Box::new(mock.credentials()), settings, vec![]).await.unwrap();
+            tokio::time::delay_for(Duration::from_millis(100)).await;
+            assert_eq!(mock.connections(), 1);
+            let subscribed = mock.subscriptions().len();
+            assert!(subscribed > 0);
+
+            mock.disconnect_all();
+            tokio::time::delay_for(Duration::from_millis(500)).await;
+            assert_eq!(mock.connections(), 2);
+            assert_eq!(mock.subscriptions().len(), 2 * subscribed);
+            assert!(bot.is_connected());
+            mock.stop().await;
+        });
+    }
+
+    #[test]
+    fn websocket_requests_are_answered() {
+        let mut state = MockState { exchange: Some(Exchange::Kraken), ..MockState::default() };
+        let reply = state.ws_reply(&json!({"event": "addOrder", "reqid": 3, "ordertype": "limit", "token": MOCK_WS_TOKEN})).unwrap();
+        assert_eq!(reply["reqid"], json!(3));
+        assert_eq!(state.orders[0].params.get("ordertype").map(|s| s.as_str()), Some("limit"));
+        assert!(state.ws_reply(&json!({"event": "unknown"})).is_none());
+    }
+}
@@ -1,9 +1,11 @@
 //! Types definition used for handling returned data when generic API is used.
 
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, HashSet, BTreeMap, VecDeque};
+use std::fmt;
 use bigdecimal::{BigDecimal, ToPrimitive, Zero};
 use std::str::FromStr;
-use crate::error::{ErrorKind, Result};
+use crate::error::{Error, Result};
+use serde_json::Value;
 
 pub type Amount = BigDecimal;
 pub type Price = BigDecimal;
@@ -16,27 +18,143 @@ pub trait BigDecimalConv {
 
 impl BigDecimalConv for BigDecimal {
     fn as_f64(&self) -> Result<f64> {
-        self.to_f64().ok_or(ErrorKind::BigDecimalTooLarge(self.clone()).into())
+        self.to_f64().ok_or_else(|| Error::BigDecimalTooLarge(self.clone()))
     }
 
     fn as_f32(&self) -> Result<f32> {
-        self.to_f32().ok_or(ErrorKind::BigDecimalTooLarge(self.clone()).into())
+        self.to_f32().ok_or_else(|| Error::BigDecimalTooLarge(self.clone()))
     }
 }
 
 pub type Balances = HashMap<Currency, Amount>;
 
+/// Funds held in a currency
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Balance {
+    /// Available plus reserved
+    pub total: Amount,
+    /// Free to trade or withdraw
+    pub available: Amount,
+    /// Held by open orders or pending withdrawals
+    pub reserved: Amount,
+}
+
+impl Balance {
+    pub fn new(available: Amount, reserved: Amount) -> Self {
+        Balance { total: available.clone() + reserved.clone(), available, reserved }
+    }
+}
+
+pub type DetailedBalances = HashMap<Currency, Balance>;
+
 use chrono::prelude::*;
 use crate::exchange::Exchange;
 use derive_more::Display;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum Channel {
     LiveTrades,
+    /// Orders of the public book as they are created, changed and deleted, only streamed by
+    /// Bitstamp
     LiveOrders,
     LiveOrderBook,
+    /// Orders at the top of the book with their ids, `LiveEvent::LiveDetailOrderbook`. Only
+    /// streamed by Bitstamp, see `ExchangeCapabilities::detail_book`
     LiveDetailOrderBook,
     LiveFullOrderBook,
+    LiveCandles,
+    LiveSummary,
+}
+
+/// Trading state of an exchange, see `ExchangeStatus`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExchangeState {
+    Online,
+    /// Online with an ongoing incident
+    Degraded,
+    /// No order can be sent or cancelled
+    Maintenance,
+    /// Orders can only be cancelled
+    CancelOnly,
+    /// Only orders adding liquidity are accepted
+    PostOnly,
+    /// Only limit orders are accepted
+    LimitOnly,
+}
+
+/// Health of an exchange, as returned by `ExchangeApi::status`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeStatus {
+    pub state: ExchangeState,
+    /// Round trip of the status request
+    pub latency_ms: u64,
+    /// Explanation given by the exchange, if any
+    pub message: Option<String>,
+}
+
+impl ExchangeStatus {
+    /// Whether new orders of any type are accepted
+    pub fn can_trade(&self) -> bool {
+        match self.state {
+            ExchangeState::Online | ExchangeState::Degraded => true,
+            _ => false,
+        }
+    }
+}
+
+/// Request budget of a REST client, as last reported by the exchange in its response headers,
+/// see `ExchangeApi::limits`. Fields the exchange does not report are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitStatus {
+    /// Requests, or weight, allowed in the current window
+    pub limit: Option<u64>,
+    /// Requests, or weight, left in the current window
+    pub remaining: Option<u64>,
+    /// UNIX timestamp in ms at which the window resets, or after which a rate limited client may
+    /// retry
+    pub reset_ms: Option<i64>,
+    /// UNIX timestamp in ms of the response the status was read from
+    pub updated_ms: Option<i64>,
+}
+
+impl RateLimitStatus {
+    /// Whether no request should be sent before `reset_ms`
+    pub fn is_exhausted(&self, now_ms: i64) -> bool {
+        self.remaining == Some(0) && self.reset_ms.map_or(false, |reset| reset > now_ms)
+    }
+}
+
+/// Trading fee rates, 0.0026 for 0.26%
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FeeRates {
+    /// Fee of the orders adding liquidity to the book
+    pub maker: BigDecimal,
+    /// Fee of the orders taking liquidity from the book
+    pub taker: BigDecimal,
+}
+
+impl FeeRates {
+    pub fn new(maker: BigDecimal, taker: BigDecimal) -> Self {
+        FeeRates { maker, taker }
+    }
+
+    /// Rates given in percent, e.g. 0.26 for 0.26%
+    pub fn from_percent(maker: BigDecimal, taker: BigDecimal) -> Self {
+        FeeRates { maker: maker / BigDecimal::from(100), taker: taker / BigDecimal::from(100) }
+    }
+}
+
+/// Price including the fee, higher than `price` for a buy and lower for a sell
+pub fn fee_adjusted(side: Side, price: Price, fee_rate: &BigDecimal) -> Price {
+    let fee = &price * fee_rate;
+    match side {
+        Side::Buy => price + fee,
+        Side::Sell => price - fee,
+    }
+}
+
+lazy_static! {
+    static ref TWO: BigDecimal = BigDecimal::from(2);
 }
 
 #[derive(Debug)]
@@ -54,9 +172,60 @@ pub struct Ticker {
     // Bittrex does not support Volume for ticker so volume could be None
     /// Last 24 hours volume (quote-volume)
     pub volume: Option<Volume>,
+    /// Volume available at the highest bid
+    pub bid_size: Option<Volume>,
+    /// Volume available at the lowest ask
+    pub ask_size: Option<Volume>,
+    /// Opening price of the last 24 hours (or of the day, depending on the exchange)
+    pub open_24h: Option<Price>,
+    /// Highest price of the last 24 hours
+    pub high_24h: Option<Price>,
+    /// Lowest price of the last 24 hours
+    pub low_24h: Option<Price>,
+    /// Price change over the last 24 hours in percent, e.g. 2.5 for +2.5%
+    pub percent_change: Option<BigDecimal>,
+    /// Payload of the exchange, when the client keeps raw payloads
+    pub raw: Option<Value>,
 }
 
+/// Parameters of `add_order` and `add_conditional_order`
 #[derive(Debug, Clone)]
+pub struct OrderRequest {
+    pub order_type: OrderType,
+    pub pair: Pair,
+    pub quantity: Volume,
+    pub price: Option<Price>,
+    /// Stop or take profit price of a conditional order
+    pub trigger_price: Option<Price>,
+    /// Id chosen by the caller, see `ExchangeApi::add_order_with_id`
+    pub client_id: Option<String>,
+}
+
+impl OrderRequest {
+    pub fn new(order_type: OrderType, pair: Pair, quantity: &Volume, price: &Option<Price>) -> Self {
+        OrderRequest { order_type, pair, quantity: quantity.clone(), price: price.clone(), trigger_price: None, client_id: None }
+    }
+
+    pub fn conditional(order_type: OrderType, pair: Pair, quantity: &Volume, trigger_price: &Price, price: &Option<Price>) -> Self {
+        OrderRequest { trigger_price: Some(trigger_price.clone()), ..OrderRequest::new(order_type, pair, quantity, price) }
+    }
+
+    pub fn with_client_id(self, client_id: &str) -> Self {
+        OrderRequest { client_id: Some(client_id.to_string()), ..self }
+    }
+
+    /// The same order with a new price and/or quantity, the client id is not carried over
+    pub fn amended(&self, new_price: Option<Price>, new_amount: Option<Volume>) -> OrderRequest {
+        OrderRequest {
+            quantity: new_amount.unwrap_or_else(|| self.quantity.clone()),
+            price: new_price.or_else(|| self.price.clone()),
+            client_id: None,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Orderbook {
     /// UNIX timestamp in ms (when the response was received)
     pub timestamp: i64,
@@ -69,18 +238,192 @@ pub struct Orderbook {
 }
 
 impl Orderbook {
+    /// The ask level with the lowest price, whatever the order of `asks`
+    pub fn best_ask(&self) -> Option<&(Price, Volume)> {
+        self.asks.iter().min_by(|a, b| a.0.cmp(&b.0))
+    }
+
+    /// The bid level with the highest price, whatever the order of `bids`
+    pub fn best_bid(&self) -> Option<&(Price, Volume)> {
+        self.bids.iter().max_by(|a, b| a.0.cmp(&b.0))
+    }
+
     /// Convenient function that returns the average price from the orderbook
     /// Return None if Orderbook is empty
     /// `Average price = (lowest ask + highest bid)/2`
     pub fn avg_price(&self) -> Option<Price> {
-        if self.asks.is_empty() || self.bids.is_empty() {
+        let (ask, _) = self.best_ask()?;
+        let (bid, _) = self.best_bid()?;
+        Some((ask + bid) / &*TWO)
+    }
+
+    /// Average price paid (buy) or received (sell) per unit for `quantity`, taking the book
+    /// levels in order and including `fee_rate`.
+    /// Return None if the book is not deep enough
+    pub fn effective_price(&self, side: Side, quantity: &Volume, fee_rate: &BigDecimal) -> Option<Price> {
+        if quantity <= &BigDecimal::zero() {
             return None;
         }
-        Some(
-            (self.asks[0].0.clone() + self.bids[0].0.clone())
-                /
-                BigDecimal::from_str("2.0").unwrap()
-        )
+        let mut levels: Vec<&(Price, Volume)> = match side {
+            Side::Buy => self.asks.iter().collect(),
+            Side::Sell => self.bids.iter().collect(),
+        };
+        match side {
+            Side::Buy => levels.sort_by(|a, b| a.0.cmp(&b.0)),
+            Side::Sell => levels.sort_by(|a, b| b.0.cmp(&a.0)),
+        }
+        let mut remaining = quantity.clone();
+        let mut cost = BigDecimal::zero();
+        for (price, volume) in levels {
+            let taken = if volume < &remaining { volume.clone() } else { remaining.clone() };
+            cost = cost + price * &taken;
+            remaining = remaining - &taken;
+            if remaining.is_zero() {
+                return Some(fee_adjusted(side, cost / quantity, fee_rate));
+            }
+        }
+        None
+    }
+
+    /// The levels to change to turn this book into `next`, removed levels have a zero volume
+    pub fn changes_to(&self, next: &Orderbook) -> Vec<LevelChange> {
+        fn side_changes(side: Side, previous: &[(Price, Volume)], next: &[(Price, Volume)], changes: &mut Vec<LevelChange>) {
+            let before: BTreeMap<&Price, &Volume> = previous.iter().map(|(p, v)| (p, v)).collect();
+            let after: BTreeMap<&Price, &Volume> = next.iter().map(|(p, v)| (p, v)).collect();
+            for (price, volume) in &after {
+                if before.get(price) != Some(volume) {
+                    changes.push(LevelChange { side, price: (*price).clone(), volume: (*volume).clone() });
+                }
+            }
+            for price in before.keys().filter(|p| !after.contains_key(*p)) {
+                changes.push(LevelChange { side, price: (*price).clone(), volume: BigDecimal::zero() });
+            }
+        }
+        let mut changes = vec![];
+        side_changes(Side::Buy, &self.bids, &next.bids, &mut changes);
+        side_changes(Side::Sell, &self.asks, &next.asks, &mut changes);
+        changes
+    }
+
+    /// Apply the changes of `delta`, asks are then sorted by ascending price and bids by
+    /// descending price
+    pub fn apply(&mut self, delta: &OrderbookDelta) {
+        for change in &delta.changes {
+            let levels = match change.side {
+                Side::Buy => &mut self.bids,
+                Side::Sell => &mut self.asks,
+            };
+            levels.retain(|(price, _)| price != &change.price);
+            if !change.volume.is_zero() {
+                levels.push((change.price.clone(), change.volume.clone()));
+            }
+        }
+        self.asks.sort_by(|a, b| a.0.cmp(&b.0));
+        self.bids.sort_by(|a, b| b.0.cmp(&a.0));
+        self.timestamp = delta.timestamp;
+    }
+}
+
+/// An order resting in a `DetailOrderbook`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookOrder {
+    /// Id of the order on the exchange
+    pub id: String,
+    pub price: Price,
+    pub amount: Volume,
+}
+
+/// Order level book : the orders at the top of the book rather than their aggregated volume per
+/// price, streamed by `Channel::LiveDetailOrderBook`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetailOrderbook {
+    /// UNIX timestamp in ms
+    pub timestamp: i64,
+    pub pair: Pair,
+    /// By ascending price
+    pub asks: Vec<BookOrder>,
+    /// By descending price
+    pub bids: Vec<BookOrder>,
+}
+
+impl DetailOrderbook {
+    /// Orders of both sides by id
+    pub fn orders(&self) -> HashMap<&str, &BookOrder> {
+        self.asks.iter().chain(self.bids.iter()).map(|o| (o.id.as_str(), o)).collect()
+    }
+
+    /// The book with the volumes of the orders at the same price summed
+    pub fn aggregated(&self) -> Orderbook {
+        fn levels(orders: &[BookOrder]) -> Vec<(Price, Volume)> {
+            let mut levels: Vec<(Price, Volume)> = vec![];
+            for o in orders {
+                match levels.last_mut() {
+                    Some((price, volume)) if *price == o.price => *volume += &o.amount,
+                    _ => levels.push((o.price.clone(), o.amount.clone())),
+                }
+            }
+            levels
+        }
+        Orderbook { timestamp: self.timestamp, pair: self.pair, asks: levels(&self.asks), bids: levels(&self.bids) }
+    }
+}
+
+/// A level of an order book whose volume changed, a zero volume removes it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelChange {
+    /// `Buy` for a bid, `Sell` for an ask
+    pub side: Side,
+    pub price: Price,
+    pub volume: Volume,
+}
+
+/// The levels of an order book that changed since the previous delta of the pair
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderbookDelta {
+    /// UNIX timestamp in ms
+    pub timestamp: i64,
+    pub pair: Pair,
+    /// Incremented with every delta of the pair from 1, a gap means deltas were lost and the book
+    /// has to be queried again
+    pub sequence: u64,
+    pub changes: Vec<LevelChange>,
+}
+
+impl Ticker {
+    /// Top of book price including `fee_rate`, the ask for a buy and the bid for a sell
+    pub fn effective_price(&self, side: Side, fee_rate: &BigDecimal) -> Price {
+        match side {
+            Side::Buy => fee_adjusted(side, self.lowest_ask.clone(), fee_rate),
+            Side::Sell => fee_adjusted(side, self.highest_bid.clone(), fee_rate),
+        }
+    }
+}
+
+/// Condition a live order book must meet before it is emitted to recipients.
+/// Right after connecting, books are thin and would otherwise look like a liquidity collapse.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum WarmUp {
+    /// Emit the book as soon as it changes
+    Disabled,
+    /// Wait until both sides have at least this many price levels
+    MinLevels(usize),
+    /// Wait until a full snapshot of the book has been received
+    Snapshot,
+}
+
+impl WarmUp {
+    /// Whether a standalone order book snapshot meets the condition
+    pub fn accepts(&self, book: &Orderbook) -> bool {
+        match *self {
+            WarmUp::MinLevels(levels) => book.asks.len() >= levels && book.bids.len() >= levels,
+            _ => true,
+        }
+    }
+}
+
+impl Default for WarmUp {
+    fn default() -> Self {
+        WarmUp::Disabled
     }
 }
 
@@ -88,16 +431,29 @@ impl Orderbook {
 pub struct LiveAggregatedOrderBook {
     pub depth: i8,
     pub pair: Pair,
-    pub asks_by_price: BTreeMap<Price, (Price, Volume)>,
-    pub bids_by_price: BTreeMap<Price, (Price, Volume)>,
+    /// Volume of each ask level, by price
+    pub asks_by_price: BTreeMap<Price, Volume>,
+    /// Volume of each bid level, by price
+    pub bids_by_price: BTreeMap<Price, Volume>,
     pub last_asks: Vec<(Price, Volume)>,
     pub last_bids: Vec<(Price, Volume)>,
+    pub warm_up: WarmUp,
+    snapshot_received: bool,
+    awaiting_snapshot: bool,
+    /// The last emitted books, oldest first
+    history: VecDeque<Orderbook>,
+    history_len: usize,
 }
 
-const DEFAULT_BOOK_DEPTH: i8 = 5;
+/// Number of levels kept on each side of a live order book when no depth is requested
+pub const DEFAULT_BOOK_DEPTH: i8 = 5;
 
 impl LiveAggregatedOrderBook {
     pub fn default(pair: Pair) -> LiveAggregatedOrderBook {
+        LiveAggregatedOrderBook::with_warm_up(pair, WarmUp::Disabled)
+    }
+
+    pub fn with_warm_up(pair: Pair, warm_up: WarmUp) -> LiveAggregatedOrderBook {
         LiveAggregatedOrderBook {
             depth: DEFAULT_BOOK_DEPTH,
             pair,
@@ -105,12 +461,48 @@ impl LiveAggregatedOrderBook {
             bids_by_price: BTreeMap::new(),
             last_asks: vec![],
             last_bids: vec![],
+            warm_up,
+            snapshot_received: false,
+            awaiting_snapshot: false,
+            history: VecDeque::new(),
+            history_len: 0,
+        }
+    }
+
+    /// Keep `depth` levels on each side of the book
+    pub fn with_depth(mut self, depth: i8) -> LiveAggregatedOrderBook {
+        self.depth = depth;
+        self
+    }
+
+    /// Retain the last `len` books returned by `latest_order_book`, none by default
+    pub fn with_history(mut self, len: usize) -> LiveAggregatedOrderBook {
+        self.history_len = len;
+        self.history = VecDeque::with_capacity(len);
+        self
+    }
+
+    /// The last emitted books with their timestamps, oldest first
+    pub fn history(&self) -> &VecDeque<Orderbook> {
+        &self.history
+    }
+
+    /// Whether the book meets its warm-up condition and can be emitted
+    pub fn is_warm(&self) -> bool {
+        if self.awaiting_snapshot {
+            return false;
+        }
+        match self.warm_up {
+            WarmUp::Disabled => true,
+            WarmUp::MinLevels(levels) => self.asks_by_price.len() >= levels && self.bids_by_price.len() >= levels,
+            WarmUp::Snapshot => self.snapshot_received,
         }
     }
 
     pub fn order_book(&self) -> Orderbook {
-        let asks: Vec<(Price, Volume)> = self.asks_by_price.iter().map(|(_, v)| v.clone()).take(self.depth as usize).rev().collect();
-        let bids: Vec<(Price, Volume)> = self.bids_by_price.iter().rev().map(|(_, v)| v.clone()).take(self.depth as usize).rev().collect();
+        let level = |(p, v): (&Price, &Volume)| (p.clone(), v.clone());
+        let asks: Vec<(Price, Volume)> = self.top_asks().map(level).collect();
+        let bids: Vec<(Price, Volume)> = self.top_bids().map(level).collect();
         Orderbook {
             timestamp: Utc::now().timestamp_millis(),
             pair: self.pair,
@@ -119,65 +511,119 @@ impl LiveAggregatedOrderBook {
         }
     }
 
+    /// The `depth` lowest asks, by descending price
+    fn top_asks(&self) -> impl Iterator<Item=(&Price, &Volume)> {
+        self.asks_by_price.iter().take(self.depth as usize).rev()
+    }
+
+    /// The `depth` highest bids, by ascending price
+    fn top_bids(&self) -> impl Iterator<Item=(&Price, &Volume)> {
+        self.bids_by_price.iter().rev().take(self.depth as usize).rev()
+    }
+
+    /// Whether the top of the book is the one last flushed, compared without building the book
+    fn top_unchanged(&self) -> bool {
+        self.top_asks().eq(self.last_asks.iter().map(|(p, v)| (p, v)))
+            && self.top_bids().eq(self.last_bids.iter().map(|(p, v)| (p, v)))
+    }
+
+    /// Drop every level and ignore deltas until the next snapshot, after the stream lost its connection
+    pub fn resync(&mut self) {
+        self.asks_by_price.clear();
+        self.bids_by_price.clear();
+        self.last_asks.clear();
+        self.last_bids.clear();
+        self.snapshot_received = false;
+        self.awaiting_snapshot = true;
+    }
+
+    /// Whether the book was cleared by `resync` and no snapshot was received since
+    pub fn is_resyncing(&self) -> bool {
+        self.awaiting_snapshot
+    }
+
     pub fn latest_order_book(&mut self) -> Option<Orderbook> {
-        let latest_order_book: Orderbook = self.order_book();
-        if latest_order_book.asks == self.last_asks && latest_order_book.bids == self.last_bids {
+        if !self.is_warm() {
+            trace!("Order book for {:?} still warming up, not flushing", self.pair);
+            return None;
+        }
+        if self.top_unchanged() {
             trace!("Order book top unchanged, not flushing");
             return None;
-        } else {
-            self.last_asks = latest_order_book.asks.clone();
-            self.last_bids = latest_order_book.bids.clone();
-            Some(latest_order_book)
         }
+        let latest_order_book: Orderbook = self.order_book();
+        self.last_asks = latest_order_book.asks.clone();
+        self.last_bids = latest_order_book.bids.clone();
+        if self.history_len > 0 {
+            if self.history.len() == self.history_len {
+                self.history.pop_front();
+            }
+            self.history.push_back(latest_order_book.clone());
+        }
+        Some(latest_order_book)
     }
 
+    /// Replace the asks with the levels of a snapshot, levels without volume are skipped
     pub fn reset_asks<I>(&mut self, iter: I)
         where
             I: Iterator<Item=(BigDecimal, BigDecimal)> {
-        for kp in iter {
-            self.asks_by_price.entry(kp.0.clone()).or_insert(kp);
+        self.asks_by_price.clear();
+        for (price, volume) in iter.filter(|(_, volume)| !volume.is_zero()) {
+            self.asks_by_price.insert(price, volume);
         }
+        self.snapshot_received = true;
+        self.awaiting_snapshot = false;
     }
 
+    /// Replace the bids with the levels of a snapshot, levels without volume are skipped
     pub fn reset_bids<I>(&mut self, iter: I)
         where
             I: Iterator<Item=(BigDecimal, BigDecimal)> {
-        for kp in iter {
-            self.bids_by_price.entry(kp.0.clone()).or_insert(kp);
+        self.bids_by_price.clear();
+        for (price, volume) in iter.filter(|(_, volume)| !volume.is_zero()) {
+            self.bids_by_price.insert(price, volume);
         }
+        self.snapshot_received = true;
+        self.awaiting_snapshot = false;
     }
 
     pub fn update_asks<I>(&mut self, iter: I)
         where
             I: Iterator<Item=(BigDecimal, BigDecimal)> {
+        if self.awaiting_snapshot {
+            return;
+        }
         for kp in iter {
             self.update_ask(kp)
         }
     }
 
     pub fn update_ask(&mut self, kp: (BigDecimal, BigDecimal)) {
-        let bids = &mut self.asks_by_price;
-        if kp.1.is_zero() {
-            bids.remove(&kp.0.clone());
+        let (price, volume) = kp;
+        if volume.is_zero() {
+            self.asks_by_price.remove(&price);
         } else {
-            bids.entry(kp.0.clone()).or_insert(kp);
+            self.asks_by_price.insert(price, volume);
         }
     }
 
     pub fn update_bids<I>(&mut self, iter: I)
         where
             I: Iterator<Item=(BigDecimal, BigDecimal)> {
+        if self.awaiting_snapshot {
+            return;
+        }
         for kp in iter {
-            self.update_ask(kp)
+            self.update_bid(kp)
         }
     }
 
     pub fn update_bid(&mut self, kp: (BigDecimal, BigDecimal)) {
-        let bids = &mut self.bids_by_price;
-        if kp.1.is_zero() {
-            bids.remove(&kp.0.clone());
+        let (price, volume) = kp;
+        if volume.is_zero() {
+            self.bids_by_price.remove(&price);
         } else {
-            bids.entry(kp.0.clone()).or_insert(kp);
+            self.bids_by_price.insert(price, volume);
         }
     }
 }
@@ -189,39 +635,203 @@ pub struct OrderInfo {
     /// This identifiers list is specific to the platform you use. You must store it somewhere if
     /// you want to modify/cancel the order later
     pub identifier: Vec<String>,
+    /// Payload of the exchange, when the client keeps raw payloads
+    pub raw: Option<Value>,
+    /// The order as it was placed, to amend it by placing it again
+    pub request: Option<OrderRequest>,
+}
+
+/// An order executed at once, see `ExchangeApi::add_ioc_order`
+#[derive(Debug)]
+pub struct Execution {
+    pub order: OrderInfo,
+    /// Quantity filled, in base currency, the rest of the order was canceled. `None` when it could
+    /// not be known once the order was accepted, the order may be filled and even still be open.
+    pub filled: Option<Volume>,
+}
+
+#[derive(Debug)]
+pub struct WithdrawalInfo {
+    /// UNIX timestamp in ms (when the response was received)
+    pub timestamp: i64,
+    /// Reference of the withdrawal on the platform, when it returns one
+    pub identifier: Option<String>,
+}
+
+/// A fill of one of our orders, as reported by the exchange
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeFill {
+    pub exchange: Exchange,
+    /// UNIX timestamp in ms
+    pub timestamp: i64,
+    pub pair: Pair,
+    pub side: TradeType,
+    pub price: Price,
+    /// In base currency
+    pub volume: Volume,
+    pub fee: Amount,
+    pub fee_currency: Currency,
+    /// Reference of the trade on the platform, when known
+    pub identifier: Option<String>,
+    /// Payload of the exchange, when the client keeps raw payloads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<Value>,
+}
+
+/// Whether a fill added liquidity to the book or took it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+    /// The exchange does not report it
+    Unknown,
+}
+
+/// An execution of one of our orders, normalized across exchanges, from private streams or from
+/// `ExchangeApi::fills`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FillEvent {
+    /// UNIX timestamp in ms of the execution
+    pub event_ms: i64,
+    pub order_id: String,
+    /// Reference of the trade on the platform, when it reports one
+    pub trade_id: Option<String>,
+    pub pair: Pair,
+    pub side: TradeType,
+    pub price: Price,
+    /// In base currency
+    pub amount: Volume,
+    pub fee: Amount,
+    /// Currency of the fee, when the exchange reports it
+    pub fee_currency: Option<Currency>,
+    pub liquidity: Liquidity,
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+/// A trade of the market, from the public trade history of an exchange
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicTrade {
+    /// Reference of the trade on the platform, when it reports one
+    pub id: Option<String>,
+    /// UNIX timestamp in ms of the execution
+    pub event_ms: i64,
+    pub pair: Pair,
+    pub price: Price,
+    /// In base currency
+    pub amount: Volume,
+    /// Side of the taker
+    pub side: TradeType,
+}
+
+/// A page of the trade history of an account
+#[derive(Debug, Clone, PartialEq)]
+pub struct FillsPage {
+    pub fills: Vec<FillEvent>,
+    /// Cursor of the next, older, page, `None` on the last page
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TransferKind {
+    Deposit,
+    Withdrawal,
+}
+
+/// Funds moved in or out of an exchange account
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transfer {
+    pub exchange: Exchange,
+    /// UNIX timestamp in ms
+    pub timestamp: i64,
+    pub kind: TransferKind,
+    pub currency: Currency,
+    /// Amount credited or debited, fee excluded
+    pub amount: Amount,
+    pub fee: Amount,
+    /// Reference of the transfer on the platform, when known
+    pub identifier: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
 pub enum OrderType {
     BuyLimit,
     SellLimit,
     BuyMarket,
     SellMarket,
+    /// Market order sent once the price reaches the stop price
+    StopLoss(Side),
+    /// Limit order sent once the price reaches the stop price
+    StopLimit(Side),
+    /// Market order sent once the price reaches the take profit price
+    TakeProfit(Side),
+    /// Stop loss following the market at a fixed distance
+    TrailingStop(Side),
+}
+
+impl OrderType {
+    pub fn side(&self) -> Side {
+        match *self {
+            OrderType::BuyLimit | OrderType::BuyMarket => Side::Buy,
+            OrderType::SellLimit | OrderType::SellMarket => Side::Sell,
+            OrderType::StopLoss(side) | OrderType::StopLimit(side) | OrderType::TakeProfit(side) | OrderType::TrailingStop(side) => side,
+        }
+    }
+
+    /// Whether the order waits for a trigger price, see `ExchangeApi::add_conditional_order`
+    pub fn is_conditional(&self) -> bool {
+        match self {
+            OrderType::BuyLimit | OrderType::SellLimit | OrderType::BuyMarket | OrderType::SellMarket => false,
+            _ => true,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Side of a trade or order, from the point of view of the taker for trades
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum TradeType {
     Sell,
     Buy,
-    None,
+    /// Value not recognized, as received from the exchange
+    Unknown(String),
 }
 
-impl From<String> for TradeType {
-    fn from(s: String) -> Self {
+impl TradeType {
+    /// Parse "buy" or "sell" in any case, other values are kept as `Unknown` and logged
+    pub fn parse(s: &str) -> TradeType {
         match s.to_lowercase().as_str() {
             "sell" => TradeType::Sell,
             "buy" => TradeType::Buy,
-            _ => TradeType::None,
+            _ => TradeType::unknown(s),
         }
     }
+
+    /// An `Unknown` trade type, logged and counted in the metrics
+    pub(crate) fn unknown<T: ToString>(value: T) -> TradeType {
+        let value = value.to_string();
+        warn!("Unknown trade type : {}", value);
+        crate::metrics::unknown_value("trade_type");
+        TradeType::Unknown(value)
+    }
 }
 
-impl From<i64> for TradeType {
-    fn from(v: i64) -> Self {
-        if v == 0 {
-            return TradeType::Buy;
+impl From<String> for TradeType {
+    fn from(s: String) -> Self {
+        TradeType::parse(&s)
+    }
+}
+
+impl fmt::Display for TradeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TradeType::Sell => f.write_str("Sell"),
+            TradeType::Buy => f.write_str("Buy"),
+            TradeType::Unknown(value) => f.write_str(value),
         }
-        return TradeType::Sell;
     }
 }
 
@@ -234,47 +844,419 @@ impl Into<i32> for TradeType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LiveTrade {
     /// UNIX timestamp in ms (when the event occured)
     pub event_ms: i64,
     /// The Pair corresponding to the Ticker returned (maybe useful later for asynchronous APIs)
-    pub pair: String,
+    pub pair: Pair,
     /// Amount of the trade
-    pub amount: f32,
+    pub amount: Volume,
     /// Price of the trade
     pub price: Price,
     /// Buy or Sell
     pub tt: TradeType,
+    /// Identifier and ordering of the trade, see `TradeTape`
+    #[serde(default)]
+    pub meta: TradeMeta,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TradeMeta {
+    /// Identifier of the trade on the exchange, when it has one
+    pub trade_id: Option<String>,
+    /// Trades may be missing before this one, or it was received out of order and its
+    /// `event_ms` raised to the one of the previous trade of its pair
+    #[serde(default)]
+    pub gap_detected: bool,
+}
+
+impl TradeMeta {
+    pub fn with_id<T: ToString>(trade_id: T) -> Self {
+        TradeMeta { trade_id: Some(trade_id.to_string()), gap_detected: false }
+    }
+}
+
+/// Trade identifiers remembered for each pair to drop the trades sent again
+pub const TRADE_IDS_KEPT: usize = 1000;
+
+#[derive(Debug, Default)]
+struct PairTape {
+    ids: HashSet<String>,
+    ids_order: VecDeque<String>,
+    last_ms: Option<i64>,
+    /// The connection was lost since the last trade
+    interrupted: bool,
+}
+
+/// Live trades of a streaming bot as a tape : trades sent again, e.g. after a reconnection, are
+/// dropped by their identifier and trades are emitted with non decreasing timestamps per pair.
+/// A trade received out of order is stamped with the timestamp of the previous trade and flagged
+/// with `gap_detected`, as is the first trade of each pair after a reconnection.
+#[derive(Debug, Default)]
+pub struct TradeTape {
+    pairs: HashMap<Pair, PairTape>,
+}
+
+impl TradeTape {
+    /// The trades of a message in timestamp order, without the ones already emitted
+    pub fn accept_all(&mut self, mut trades: Vec<LiveTrade>) -> Vec<LiveTrade> {
+        trades.sort_by_key(|t| t.event_ms);
+        trades.into_iter().filter_map(|t| self.accept(t)).collect()
+    }
+
+    /// The trade to emit, `None` if it was already emitted
+    pub fn accept(&mut self, mut trade: LiveTrade) -> Option<LiveTrade> {
+        let tape = self.pairs.entry(trade.pair).or_default();
+        if let Some(id) = &trade.meta.trade_id {
+            if !tape.ids.insert(id.clone()) {
+                return None;
+            }
+            tape.ids_order.push_back(id.clone());
+            if tape.ids_order.len() > TRADE_IDS_KEPT {
+                if let Some(oldest) = tape.ids_order.pop_front() {
+                    tape.ids.remove(&oldest);
+                }
+            }
+        }
+        match tape.last_ms {
+            Some(last_ms) if trade.event_ms < last_ms => {
+                trade.event_ms = last_ms;
+                trade.meta.gap_detected = true;
+            }
+            _ => tape.last_ms = Some(trade.event_ms),
+        }
+        if tape.interrupted {
+            trade.meta.gap_detected = true;
+            tape.interrupted = false;
+        }
+        Some(trade)
+    }
+
+    /// Trades may have been missed, e.g. while disconnected : the next trade of each pair is
+    /// flagged. Identifiers are kept to drop the trades sent again by a new connection.
+    pub fn mark_gap(&mut self) {
+        for tape in self.pairs.values_mut() {
+            tape.interrupted = true;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LiveOrder {
     /// UNIX timestamp in ms (when the event occured)
     pub event_ms: i64,
+    /// Id of the order on the exchange, to follow its changes
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub kind: OrderEventKind,
     /// The Pair corresponding to the Ticker returned (maybe useful later for asynchronous APIs)
-    pub pair: String,
+    pub pair: Pair,
     /// Amount of the trade
-    pub amount: f32,
+    pub amount: Volume,
     /// Price of the trade
     pub price: Price,
     /// Buy or Sell
     pub tt: TradeType,
 }
 
-#[derive(Message, Clone, Debug)]
-#[rtype(result = "()")]
+/// What happened to the order of a `LiveOrder` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderEventKind {
+    Created,
+    /// Partially filled, or its amount or price was changed
+    Changed,
+    /// Filled or cancelled
+    Deleted,
+    /// Recorded before the kind was known
+    Unknown,
+}
+
+impl Default for OrderEventKind {
+    fn default() -> Self {
+        OrderEventKind::Unknown
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveCandle {
+    /// UNIX timestamp in ms of the start of the candle
+    pub event_ms: i64,
+    /// The Pair corresponding to the candle, as named by the exchange
+    pub pair: String,
+    /// Length of the candle in seconds
+    pub interval_secs: u64,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    /// Traded volume during the candle
+    pub volume: Volume,
+    /// Whether the candle is complete, an open candle can be sent again with updated values
+    pub closed: bool,
+}
+
+/// Outcome of the subscription of a bot to a channel for a pair, a rejected pair (e.g. delisted)
+/// sends nothing on the channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionResult {
+    pub channel: Channel,
+    pub pair: Pair,
+    pub success: bool,
+    /// Why the exchange rejected the subscription
+    pub reason: Option<String>,
+}
+
+impl SubscriptionResult {
+    pub fn accepted(channel: Channel, pair: Pair) -> Self {
+        SubscriptionResult { channel, pair, success: true, reason: None }
+    }
+
+    pub fn rejected(channel: Channel, pair: Pair, reason: &str) -> Self {
+        SubscriptionResult { channel, pair, success: false, reason: Some(reason.to_string()) }
+    }
+}
+
+/// Rolling 24 hours summary of a market
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveSummary {
+    /// UNIX timestamp in ms of the summary
+    pub event_ms: i64,
+    /// The Pair corresponding to the summary, as named by the exchange
+    pub pair: String,
+    pub high: Price,
+    pub low: Price,
+    /// Volume traded over the last 24 hours (base-volume)
+    pub volume: Volume,
+    pub last: Price,
+    pub bid: Price,
+    pub ask: Price,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "native", derive(Message), rtype(result = "()"))]
 pub enum LiveEvent {
     LiveOrder(LiveOrder),
     LiveTrade(LiveTrade),
     LiveOrderbook(Orderbook),
+    LiveOrderbookDelta(OrderbookDelta),
+    LiveDetailOrderbook(DetailOrderbook),
+    LiveCandle(LiveCandle),
+    LiveSummary(LiveSummary),
+    Fill(FillEvent),
+    SubscriptionResult(SubscriptionResult),
     Noop,
 }
 
-#[derive(Message, Clone, Debug)]
-#[rtype(result = "()")]
+impl LiveEvent {
+    /// UNIX timestamp in ms of the event, if any
+    pub fn timestamp(&self) -> Option<i64> {
+        match self {
+            LiveEvent::LiveOrder(o) => Some(o.event_ms),
+            LiveEvent::LiveTrade(t) => Some(t.event_ms),
+            LiveEvent::LiveOrderbook(ob) => Some(ob.timestamp),
+            LiveEvent::LiveOrderbookDelta(d) => Some(d.timestamp),
+            LiveEvent::LiveDetailOrderbook(ob) => Some(ob.timestamp),
+            LiveEvent::LiveCandle(c) => Some(c.event_ms),
+            LiveEvent::LiveSummary(s) => Some(s.event_ms),
+            LiveEvent::Fill(f) => Some(f.event_ms),
+            LiveEvent::SubscriptionResult(_) | LiveEvent::Noop => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "native", derive(Message), rtype(result = "()"))]
 pub struct LiveEventEnveloppe(pub Exchange, pub LiveEvent);
 
+#[cfg(test)]
+mod types_tests {
+    use super::*;
+
+    #[test]
+    fn currency_aliases() {
+        assert_eq!(Currency::from_alias(Exchange::Kraken, "XXBT"), Some(Currency::BTC));
+        assert_eq!(Currency::from_alias(Exchange::Binance, "xbt"), Some(Currency::BTC));
+        assert_eq!(Currency::from_alias(Exchange::Kraken, "XDG"), Some(Currency::DOGE));
+        assert_eq!(Currency::from_alias(Exchange::Poloniex, "STR"), Some(Currency::XLM));
+        assert_eq!(Currency::from_alias(Exchange::Kraken, "ZUSD"), Some(Currency::USD));
+        assert_eq!(Currency::from_alias(Exchange::Bittrex, "1ST"), Some(Currency::_1ST));
+        assert_eq!(Currency::from_alias(Exchange::Bittrex, "NOPE"), None);
+        // Aliases of one exchange are symbols of their own elsewhere
+        assert_eq!(Currency::from_alias(Exchange::Bittrex, "XDG"), Some(Currency::XDG));
+        assert_eq!(Currency::from_alias(Exchange::Bittrex, "STR"), Some(Currency::STR));
+        assert_eq!(Currency::BTC.alias_for(Exchange::Kraken), "XXBT");
+        assert_eq!(Currency::XLM.alias_for(Exchange::Poloniex), "STR");
+        assert_eq!(Currency::_1ST.alias_for(Exchange::Bittrex), "1ST");
+        assert_eq!(Currency::BTC.alias_for(Exchange::Bitstamp), "BTC");
+    }
+
+    #[test]
+    fn dark_pool_pairs() {
+        assert_eq!(Pair::BTC_EUR_d.instrument_kind(), InstrumentKind::DarkPool);
+        assert_eq!(Pair::BTC_EUR.dark_pool(), Some(Pair::BTC_EUR_d));
+        assert_eq!(Pair::BTC_EUR_d.lit(), Pair::BTC_EUR);
+        assert_eq!(Pair::ZRX_BTC.dark_pool(), None);
+    }
+
+    #[test]
+    fn book_warms_up_with_min_levels() {
+        let mut book = LiveAggregatedOrderBook::with_warm_up(Pair::BTC_USD, WarmUp::MinLevels(2));
+        book.update_ask((BigDecimal::from(101), BigDecimal::from(1)));
+        book.update_bid((BigDecimal::from(99), BigDecimal::from(1)));
+        assert!(book.latest_order_book().is_none());
+        book.update_ask((BigDecimal::from(102), BigDecimal::from(1)));
+        book.update_bid((BigDecimal::from(98), BigDecimal::from(1)));
+        assert!(book.latest_order_book().is_some());
+    }
+
+    #[test]
+    fn book_warms_up_with_snapshot() {
+        let mut book = LiveAggregatedOrderBook::with_warm_up(Pair::BTC_USD, WarmUp::Snapshot);
+        book.update_ask((BigDecimal::from(101), BigDecimal::from(1)));
+        assert!(!book.is_warm());
+        book.reset_bids(vec![(BigDecimal::from(99), BigDecimal::from(1))].into_iter());
+        assert!(book.is_warm());
+    }
+
+    #[test]
+    fn history_keeps_the_last_emitted_books() {
+        let mut book = LiveAggregatedOrderBook::default(Pair::BTC_USD).with_history(2);
+        for price in 101..104 {
+            book.update_asks(vec![(BigDecimal::from(price), BigDecimal::from(1))].into_iter());
+            book.update_asks(vec![(BigDecimal::from(price - 1), BigDecimal::from(0))].into_iter());
+            assert!(book.latest_order_book().is_some());
+        }
+        assert!(book.latest_order_book().is_none());
+        let best_asks: Vec<BigDecimal> = book.history().iter().map(|ob| ob.asks[0].0.clone()).collect();
+        assert_eq!(best_asks, vec![BigDecimal::from(102), BigDecimal::from(103)]);
+    }
+
+    #[test]
+    fn resync_waits_for_a_snapshot() {
+        let mut book = LiveAggregatedOrderBook::default(Pair::BTC_USD);
+        book.update_asks(vec![(BigDecimal::from(101), BigDecimal::from(1))].into_iter());
+        assert!(book.latest_order_book().is_some());
+        book.resync();
+        book.update_asks(vec![(BigDecimal::from(102), BigDecimal::from(1))].into_iter());
+        assert!(book.asks_by_price.is_empty());
+        assert!(book.latest_order_book().is_none());
+        book.reset_asks(vec![(BigDecimal::from(103), BigDecimal::from(1))].into_iter());
+        book.reset_bids(vec![(BigDecimal::from(99), BigDecimal::from(1))].into_iter());
+        assert!(!book.is_resyncing());
+        assert_eq!(book.latest_order_book().map(|ob| ob.asks.len()), Some(1));
+    }
+
+    #[test]
+    fn snapshots_replace_the_previous_levels() {
+        let mut book = LiveAggregatedOrderBook::default(Pair::BTC_USD);
+        book.reset_asks(vec![(BigDecimal::from(101), BigDecimal::from(1)), (BigDecimal::from(102), BigDecimal::from(1))].into_iter());
+        book.reset_asks(vec![(BigDecimal::from(102), BigDecimal::from(4)), (BigDecimal::from(103), BigDecimal::from(0))].into_iter());
+        assert_eq!(book.order_book().asks, vec![(BigDecimal::from(102), BigDecimal::from(4))]);
+    }
+
+    #[test]
+    fn deltas_replace_level_volumes() {
+        let mut book = LiveAggregatedOrderBook::default(Pair::BTC_USD);
+        book.reset_bids(vec![(BigDecimal::from(99), BigDecimal::from(1))].into_iter());
+        book.update_bids(vec![(BigDecimal::from(99), BigDecimal::from(3)), (BigDecimal::from(98), BigDecimal::from(2))].into_iter());
+        assert!(book.asks_by_price.is_empty());
+        assert_eq!(book.order_book().bids, vec![(BigDecimal::from(98), BigDecimal::from(2)), (BigDecimal::from(99), BigDecimal::from(3))]);
+        book.update_bids(vec![(BigDecimal::from(99), BigDecimal::from(0))].into_iter());
+        assert_eq!(book.order_book().bids.len(), 1);
+    }
+
+    #[test]
+    fn orderbook_deltas_rebuild_the_book() {
+        let level = |p: i32, v: i32| (BigDecimal::from(p), BigDecimal::from(v));
+        let before = Orderbook { timestamp: 1, pair: Pair::BTC_USD, asks: vec![level(101, 1), level(102, 1)], bids: vec![level(99, 1)] };
+        let after = Orderbook { timestamp: 2, pair: Pair::BTC_USD, asks: vec![level(101, 2)], bids: vec![level(99, 1), level(98, 4)] };
+        let changes = before.changes_to(&after);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.contains(&LevelChange { side: Side::Sell, price: BigDecimal::from(102), volume: BigDecimal::zero() }));
+        let mut book = before.clone();
+        book.apply(&OrderbookDelta { timestamp: 2, pair: Pair::BTC_USD, sequence: 2, changes });
+        assert_eq!(book.asks, after.asks);
+        assert_eq!(book.bids, after.bids);
+        assert_eq!(book.timestamp, 2);
+    }
+
+    #[test]
+    fn conditional_order_sides() {
+        assert_eq!(OrderType::SellMarket.side(), Side::Sell);
+        assert_eq!(OrderType::TrailingStop(Side::Buy).side(), Side::Buy);
+        assert!(OrderType::StopLimit(Side::Sell).is_conditional());
+        assert!(!OrderType::BuyLimit.is_conditional());
+    }
+
+    #[test]
+    fn effective_price_walks_the_book() {
+        let book = Orderbook {
+            timestamp: 0,
+            pair: Pair::BTC_USD,
+            asks: vec![(BigDecimal::from(100), BigDecimal::from(1)), (BigDecimal::from(110), BigDecimal::from(1))],
+            bids: vec![(BigDecimal::from(90), BigDecimal::from(2))],
+        };
+        let no_fee = BigDecimal::zero();
+        assert_eq!(book.effective_price(Side::Buy, &BigDecimal::from(2), &no_fee), Some(BigDecimal::from(105)));
+        assert_eq!(book.effective_price(Side::Buy, &BigDecimal::from(3), &no_fee), None);
+        let fee = BigDecimal::from_str("0.01").unwrap();
+        assert_eq!(book.effective_price(Side::Sell, &BigDecimal::from(1), &fee), Some(BigDecimal::from_str("89.1").unwrap()));
+    }
+
+    #[test]
+    fn best_levels_whatever_the_order() {
+        let book = Orderbook {
+            timestamp: 0,
+            pair: Pair::BTC_USD,
+            asks: vec![(BigDecimal::from(110), BigDecimal::from(1)), (BigDecimal::from(100), BigDecimal::from(2))],
+            bids: vec![(BigDecimal::from(80), BigDecimal::from(1)), (BigDecimal::from(90), BigDecimal::from(3))],
+        };
+        assert_eq!(book.best_ask().map(|l| &l.0), Some(&BigDecimal::from(100)));
+        assert_eq!(book.best_bid().map(|l| &l.1), Some(&BigDecimal::from(3)));
+        assert_eq!(book.avg_price(), Some(BigDecimal::from(95)));
+    }
+
+    #[test]
+    fn unknown_trade_types_are_kept() {
+        assert_eq!(TradeType::parse("BUY"), TradeType::Buy);
+        assert_eq!(TradeType::parse("PARTIAL_FILL"), TradeType::Unknown("PARTIAL_FILL".to_string()));
+        assert_eq!(TradeType::parse(&TradeType::Unknown("x".to_string()).to_string()), TradeType::Unknown("x".to_string()));
+    }
+
+    #[test]
+    fn tape_drops_resent_trades_and_keeps_order() {
+        let trade = |id: &str, event_ms: i64| LiveTrade {
+            event_ms,
+            pair: Pair::BTC_USD,
+            amount: BigDecimal::from(1),
+            price: BigDecimal::from(100),
+            tt: TradeType::Buy,
+            meta: TradeMeta::with_id(id),
+        };
+        let mut tape = TradeTape::default();
+        let first = tape.accept_all(vec![trade("2", 20), trade("1", 10)]);
+        assert_eq!(first.iter().map(|t| t.event_ms).collect::<Vec<i64>>(), vec![10, 20]);
+        assert!(first.iter().all(|t| !t.meta.gap_detected));
+        tape.mark_gap();
+        assert!(tape.accept(trade("2", 20)).is_none());
+        let late = tape.accept(trade("0", 5)).unwrap();
+        assert_eq!(late.event_ms, 20);
+        assert!(late.meta.gap_detected);
+        assert!(!tape.accept(trade("3", 30)).unwrap().meta.gap_detected);
+    }
+
+    #[test]
+    fn only_online_exchanges_accept_every_order() {
+        let status = |state| ExchangeStatus { state, latency_ms: 80, message: None };
+        assert!(status(ExchangeState::Online).can_trade());
+        assert!(status(ExchangeState::Degraded).can_trade());
+        assert!(!status(ExchangeState::Maintenance).can_trade());
+        assert!(!status(ExchangeState::CancelOnly).can_trade());
+    }
+}
+
 /// Currency lists all currencies that can be traded on supported exchanges.
 /// Update date : 27/10/2017.
 /// Note : 1ST, 2GIVE, 8BIT have been renammed "_1ST", "_2GIVE" and "_8BIT" since variables name
@@ -592,6 +1574,73 @@ pub enum Currency {
     ZEN,
 }
 
+/// Names used by every exchange for a currency known by another symbol, e.g. the ISO 4217 like
+/// `XBT` for bitcoin
+const COMMON_ALIASES: [(&str, Currency); 1] = [
+    ("XBT", Currency::BTC),
+];
+
+/// Names specific to an exchange, the first name of a currency is the one sent to the exchange
+fn exchange_aliases(exchange: Exchange) -> &'static [(&'static str, Currency)] {
+    match exchange {
+        Exchange::Kraken => &[
+            ("ZEUR", Currency::EUR),
+            ("ZCAD", Currency::CAD),
+            ("ZGBP", Currency::GBP),
+            ("ZJPY", Currency::JPY),
+            ("ZUSD", Currency::USD),
+            ("XDASH", Currency::DASH),
+            ("XETC", Currency::ETC),
+            ("XETH", Currency::ETH),
+            ("XGNO", Currency::GNO),
+            ("XICN", Currency::ICN),
+            ("XLTC", Currency::LTC),
+            ("XMLN", Currency::MLN),
+            ("XREP", Currency::REP),
+            ("XUSDT", Currency::USDT),
+            ("XXBT", Currency::BTC),
+            ("XXDG", Currency::DOGE),
+            ("XXLM", Currency::XLM),
+            ("XXMR", Currency::XMR),
+            ("XXRP", Currency::XRP),
+            ("XZEC", Currency::ZEC),
+            ("XDG", Currency::DOGE),
+        ],
+        Exchange::Poloniex => &[("STR", Currency::XLM)],
+        _ => &[],
+    }
+}
+
+impl Currency {
+    /// The currency named `s` by `exchange`, e.g. `XBT` or `XXBT` for `BTC` on Kraken, in any case
+    pub fn from_alias(exchange: Exchange, s: &str) -> Option<Currency> {
+        let s = s.to_uppercase();
+        exchange_aliases(exchange).iter()
+            .chain(COMMON_ALIASES.iter())
+            .find(|(alias, _)| *alias == s)
+            .map(|&(_, currency)| currency)
+            .or_else(|| Currency::from_symbol(&s))
+    }
+
+    /// The name of the currency for `exchange`, its symbol if the exchange has no name of its own
+    pub fn alias_for(self, exchange: Exchange) -> String {
+        exchange_aliases(exchange).iter()
+            .find(|(_, currency)| *currency == self)
+            .map(|(alias, _)| alias.to_string())
+            .unwrap_or_else(|| self.symbol())
+    }
+
+    /// The usual symbol of the currency, e.g. `1ST` for `_1ST`
+    pub fn symbol(self) -> String {
+        format!("{:?}", self).trim_start_matches('_').to_string()
+    }
+
+    fn from_symbol(symbol: &str) -> Option<Currency> {
+        let parse = |s: String| serde_json::from_value(Value::String(s)).ok();
+        parse(symbol.to_string()).or_else(|| parse(format!("_{}", symbol)))
+    }
+}
+
 /// Pair lists all pairs that can be traded on supported exchanges.
 /// Update date : 27/10/2017.
 ///
@@ -933,6 +1982,7 @@ pub enum Pair {
     XEM_ETH,
     XLM_BTC,
     XLM_ETH,
+    XLM_USDT,
     XMG_BTC,
     XMR_BTC,
     XMR_ETH,
@@ -963,3 +2013,38 @@ pub enum Pair {
     ZRX_BTC,
     ZRX_ETH,
 }
+
+/// Kind of market an order is routed to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum InstrumentKind {
+    /// The public order book
+    Spot,
+    /// Hidden order book of Kraken, only limit orders are accepted
+    DarkPool,
+    /// Perpetual future of Kraken Futures, see `kraken_futures`
+    Perpetual,
+}
+
+impl Pair {
+    /// Dark pool pairs end with `_d`, all the others are spot pairs
+    pub fn instrument_kind(&self) -> InstrumentKind {
+        if format!("{:?}", self).ends_with("_d") { InstrumentKind::DarkPool } else { InstrumentKind::Spot }
+    }
+
+    /// The dark pool pair of the same currencies, if there is one
+    pub fn dark_pool(&self) -> Option<Pair> {
+        match self.instrument_kind() {
+            InstrumentKind::DarkPool => Some(*self),
+            _ => serde_json::from_value(Value::String(format!("{:?}_d", self))).ok(),
+        }
+    }
+
+    /// The spot pair of the same currencies
+    pub fn lit(&self) -> Pair {
+        let name = format!("{:?}", self);
+        if !name.ends_with("_d") {
+            return *self;
+        }
+        serde_json::from_value(Value::String(name[..name.len() - 2].to_string())).unwrap_or(*self)
+    }
+}
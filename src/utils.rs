@@ -1,15 +1,59 @@
 use crate::error::*;
 use crate::exchange::Exchange;
-use crate::types::Pair;
+use crate::types::{Currency, Pair};
+use serde_json::Value;
+
+/// Pairs of the exchanges disabled by the cargo features
+#[allow(dead_code)]
+fn no_pair_string(_pair: &Pair) -> Option<&&str> {
+    None
+}
 
 pub fn pair_fn(xchg: Exchange) -> fn(&Pair) -> Option<&&str> {
     match xchg {
+        #[cfg(feature = "bittrex")]
         Exchange::Bittrex => crate::bittrex::utils::get_pair_string,
+        #[cfg(feature = "bitstamp")]
         Exchange::Bitstamp => crate::bitstamp::utils::get_pair_string,
+        #[cfg(feature = "coinbase")]
+        Exchange::Gdax | Exchange::Coinbase => crate::coinbase::utils::get_pair_string,
+        #[cfg(all(feature = "gdax", not(feature = "coinbase")))]
         Exchange::Gdax => crate::gdax::utils::get_pair_string,
+        #[cfg(feature = "kraken")]
         Exchange::Kraken => crate::kraken::utils::get_pair_string,
+        #[cfg(feature = "poloniex")]
         Exchange::Poloniex => crate::poloniex::utils::get_pair_string,
+        #[cfg(feature = "binance")]
         Exchange::Binance => crate::binance::utils::get_pair_string,
+        #[cfg(feature = "okx")]
+        Exchange::Okx => crate::okx::utils::get_pair_string,
+        Exchange::Simulated => crate::simulated::utils::get_pair_string,
+        Exchange::Custom(name) => crate::registry::pair_fn(name),
+        #[allow(unreachable_patterns)]
+        _ => no_pair_string,
+    }
+}
+
+/// Every pair with a name on `xchg`, none for the exchanges accepting any pair
+pub fn supported_pairs(xchg: Exchange) -> Vec<Pair> {
+    match xchg {
+        #[cfg(feature = "bittrex")]
+        Exchange::Bittrex => crate::bittrex::utils::supported_pairs(),
+        #[cfg(feature = "bitstamp")]
+        Exchange::Bitstamp => crate::bitstamp::utils::supported_pairs(),
+        #[cfg(feature = "coinbase")]
+        Exchange::Gdax | Exchange::Coinbase => crate::coinbase::utils::supported_pairs(),
+        #[cfg(all(feature = "gdax", not(feature = "coinbase")))]
+        Exchange::Gdax => crate::gdax::utils::supported_pairs(),
+        #[cfg(feature = "kraken")]
+        Exchange::Kraken => crate::kraken::utils::supported_pairs(),
+        #[cfg(feature = "poloniex")]
+        Exchange::Poloniex => crate::poloniex::utils::supported_pairs(),
+        #[cfg(feature = "binance")]
+        Exchange::Binance => crate::binance::utils::supported_pairs(),
+        #[cfg(feature = "okx")]
+        Exchange::Okx => crate::okx::utils::supported_pairs(),
+        _ => vec![],
     }
 }
 
@@ -17,6 +61,51 @@ pub fn pair_or(xchg: Exchange, pair: &Pair) -> Result<&&str>{
     let pairs_fn : fn(&Pair) -> Option<&&str> = pair_fn(xchg);
     match pairs_fn(pair) {
         Some(name) => Ok(name),
-        None => Err(ErrorKind::PairUnsupported.into()),
+        None => Err(Error::PairUnsupported),
     }
 }
+
+/// Split a pair into its (base, quote) currencies, e.g. ETH_BTC gives (ETH, BTC).
+/// Dark pool pairs map to their regular currencies.
+pub fn pair_currencies(pair: &Pair) -> Option<(Currency, Currency)> {
+    let name = format!("{:?}", pair);
+    let (base, quote) = if name.starts_with('_') {
+        let mut parts = name[1..].splitn(3, '_');
+        (format!("_{}", parts.next()?), parts.next()?.to_string())
+    } else {
+        let mut parts = name.splitn(3, '_');
+        (parts.next()?.to_string(), parts.next()?.to_string())
+    };
+    let base: Currency = serde_json::from_value(Value::String(base)).ok()?;
+    let quote: Currency = serde_json::from_value(Value::String(quote)).ok()?;
+    Some((base, quote))
+}
+
+/// The pair trading `base` against `quote`, if it exists
+pub fn currencies_pair(base: Currency, quote: Currency) -> Option<Pair> {
+    serde_json::from_value(Value::String(format!("{:?}_{:?}", base, quote))).ok()
+}
+
+/// The pair of a live event named `name` by `xchg`, falling back to the name of the pair itself
+pub fn live_pair(xchg: Exchange, name: &str) -> Option<Pair> {
+    let pair = match xchg {
+        #[cfg(feature = "bittrex")]
+        Exchange::Bittrex => crate::bittrex::utils::get_pair_enum(name),
+        #[cfg(feature = "bitstamp")]
+        Exchange::Bitstamp => crate::bitstamp::utils::get_pair_enum(name),
+        #[cfg(feature = "coinbase")]
+        Exchange::Gdax | Exchange::Coinbase => crate::coinbase::utils::get_pair_enum(name),
+        #[cfg(all(feature = "gdax", not(feature = "coinbase")))]
+        Exchange::Gdax => crate::gdax::utils::get_pair_enum(name),
+        #[cfg(feature = "kraken")]
+        Exchange::Kraken => crate::kraken::utils::get_pair_enum(name),
+        #[cfg(feature = "poloniex")]
+        Exchange::Poloniex => crate::poloniex::utils::get_pair_enum(name),
+        #[cfg(feature = "binance")]
+        Exchange::Binance => crate::binance::utils::get_pair_enum(name),
+        #[cfg(feature = "okx")]
+        Exchange::Okx => crate::okx::utils::get_pair_enum(name),
+        _ => None,
+    };
+    pair.copied().or_else(|| serde_json::from_value(Value::String(name.to_string())).ok())
+}
@@ -0,0 +1,204 @@
+//! Dead man's switch of the open orders of a bot.
+//! `CancelWatchdog` expects a `Heartbeat` from the bot at least once per timeout. On exchanges
+//! with a native timer, see `ExchangeApi::set_cancel_after`, each heartbeat sets the timer again
+//! and the exchange cancels the orders even if the whole process dies. On the others the watchdog
+//! cancels every open order itself once the heartbeats stop, which covers a stuck bot but not a
+//! crashed process.
+//!
+//! ```ignore
+//! let watchdog = CancelWatchdog::new(api.clone(), Duration::from_secs(60))?.start();
+//! // in the loop of the bot
+//! watchdog.do_send(Heartbeat);
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix::{Actor, ActorFuture, AsyncContext, Context, ContextFutureSpawner, Handler, WrapFuture};
+
+use crate::error::*;
+use crate::exchange::ExchangeApi;
+
+/// Sent by the bot to show that it is still alive
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Heartbeat;
+
+/// How the open orders are protected
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Timer {
+    /// Not known until the exchange answers the first `set_cancel_after`, or unsure after it
+    /// failed : the client cancels the orders too
+    Unknown,
+    Native,
+    Client,
+}
+
+pub struct CancelWatchdog {
+    api: Arc<dyn ExchangeApi>,
+    timeout: Duration,
+    timer: Timer,
+    last_heartbeat: Instant,
+    last_armed: Option<Instant>,
+    /// Whether the orders were cancelled since the last heartbeat
+    fired: bool,
+}
+
+/// Shortest timeout accepted by `CancelWatchdog::new`
+pub const MIN_TIMEOUT: Duration = Duration::from_millis(10);
+
+impl CancelWatchdog {
+    /// Fails with `Error::InvalidArguments` for a timeout under `MIN_TIMEOUT`, a zero timeout
+    /// would disarm the native timer instead of arming it
+    pub fn new(api: Arc<dyn ExchangeApi>, timeout: Duration) -> Result<Self> {
+        if timeout < MIN_TIMEOUT {
+            return Err(Error::InvalidArguments);
+        }
+        Ok(CancelWatchdog {
+            api,
+            timeout,
+            timer: Timer::Unknown,
+            last_heartbeat: Instant::now(),
+            last_armed: None,
+            fired: false,
+        })
+    }
+
+    /// Set the native timer again, at most every quarter of the timeout
+    fn arm(&mut self, ctx: &mut Context<Self>) {
+        if self.timer == Timer::Client || self.last_armed.map_or(false, |at| at.elapsed() < self.timeout / 4) {
+            return;
+        }
+        self.last_armed = Some(Instant::now());
+        let api = self.api.clone();
+        let timeout = self.timeout;
+        async move { api.set_cancel_after(timeout).await }
+            .into_actor(self)
+            .map(|res, act, _ctx| match res {
+                Ok(()) => act.timer = Timer::Native,
+                Err(e) => match e.kind() {
+                    Error::Unsupported => {
+                        info!("Watchdog : no native timer, open orders are cancelled by the client");
+                        act.timer = Timer::Client;
+                    }
+                    _ => {
+                        warn!("Watchdog : unable to set the timer, open orders are also cancelled by the client : {}", e);
+                        act.timer = Timer::Unknown;
+                    }
+                },
+            })
+            .spawn(ctx);
+    }
+
+    /// Cancel the open orders once the heartbeats stopped for longer than the timeout
+    fn check(&mut self, ctx: &mut Context<Self>) {
+        if self.timer == Timer::Native || self.fired || self.last_heartbeat.elapsed() < self.timeout {
+            return;
+        }
+        warn!("Watchdog : no heartbeat for {:?}, cancelling the open orders", self.timeout);
+        self.fired = true;
+        let api = self.api.clone();
+        async move { api.cancel_all_orders().await }
+            .into_actor(self)
+            .map(|res, _act, _ctx| {
+                if let Err(e) = res {
+                    error!("Watchdog : unable to cancel the open orders : {}", e);
+                }
+            })
+            .spawn(ctx);
+    }
+}
+
+impl Actor for CancelWatchdog {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        self.arm(ctx);
+        ctx.run_interval(self.timeout / 4, |act, ctx| act.check(ctx));
+    }
+}
+
+impl Handler<Heartbeat> for CancelWatchdog {
+    type Result = ();
+
+    fn handle(&mut self, _msg: Heartbeat, ctx: &mut Context<Self>) {
+        self.last_heartbeat = Instant::now();
+        self.fired = false;
+        self.arm(ctx);
+    }
+}
+
+#[cfg(test)]
+mod watchdog_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use actix::System;
+    use async_trait::async_trait;
+    use crate::types::*;
+
+    #[derive(Debug, Default)]
+    struct Orders(AtomicUsize);
+
+    #[async_trait]
+    impl ExchangeApi for Orders {
+        async fn ticker(&self, _pair: Pair) -> Result<Ticker> { Err(Error::Unsupported) }
+        async fn orderbook(&self, _pair: Pair) -> Result<Orderbook> { Err(Error::Unsupported) }
+        async fn add_order(&self, _order_type: OrderType, _pair: Pair, _quantity: Volume, _price: Option<Price>) -> Result<OrderInfo> { Err(Error::Unsupported) }
+        async fn balances(&self) -> Result<Balances> { Err(Error::Unsupported) }
+
+        async fn cancel_all_orders(&self) -> Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn orders_are_cancelled_once_the_heartbeats_stop() {
+        System::new("watchdog_tests").block_on(async {
+            let api = Arc::new(Orders::default());
+            let watchdog = CancelWatchdog::new(api.clone(), Duration::from_millis(100)).unwrap().start();
+            for _ in 0..4 {
+                tokio::time::delay_for(Duration::from_millis(50)).await;
+                watchdog.do_send(Heartbeat);
+            }
+            assert_eq!(api.0.load(Ordering::SeqCst), 0);
+            tokio::time::delay_for(Duration::from_millis(300)).await;
+            assert_eq!(api.0.load(Ordering::SeqCst), 1);
+        });
+    }
+
+    #[derive(Debug, Default)]
+    struct FailingTimer(AtomicUsize);
+
+    #[async_trait]
+    impl ExchangeApi for FailingTimer {
+        async fn ticker(&self, _pair: Pair) -> Result<Ticker> { Err(Error::Unsupported) }
+        async fn orderbook(&self, _pair: Pair) -> Result<Orderbook> { Err(Error::Unsupported) }
+        async fn add_order(&self, _order_type: OrderType, _pair: Pair, _quantity: Volume, _price: Option<Price>) -> Result<OrderInfo> { Err(Error::Unsupported) }
+        async fn balances(&self) -> Result<Balances> { Err(Error::Unsupported) }
+
+        async fn cancel_all_orders(&self) -> Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn set_cancel_after(&self, _timeout: Duration) -> Result<()> {
+            Err(Error::ServiceUnavailable("timer".to_string()))
+        }
+    }
+
+    #[test]
+    fn client_cancels_when_the_native_timer_fails() {
+        System::new("watchdog_tests").block_on(async {
+            let api = Arc::new(FailingTimer::default());
+            let _watchdog = CancelWatchdog::new(api.clone(), Duration::from_millis(100)).unwrap().start();
+            tokio::time::delay_for(Duration::from_millis(300)).await;
+            assert_eq!(api.0.load(Ordering::SeqCst), 1);
+        });
+    }
+
+    #[test]
+    fn short_timeouts_are_rejected() {
+        assert!(CancelWatchdog::new(Arc::new(Orders::default()), Duration::from_millis(0)).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+#![cfg(all(feature = "bitstamp", feature = "kraken"))]
+
 #[cfg(test)]
 mod bitstamp_tests {
     extern crate coinnect;
@@ -15,14 +17,19 @@ mod bitstamp_tests {
 
     #[test]
     fn build_url_should_return_the_a_url() {
-        assert_eq!(utils::build_url("ticker", "btcusd"),
+        assert_eq!(utils::build_url(utils::API_URL, "ticker", "btcusd"),
                    "https://www.bitstamp.net/api/v2/ticker/btcusd/");
     }
     #[test]
     fn build_url_should_return_the_url_for_transactions_for_btc_usd() {
-        assert_eq!(utils::build_url("transactions", "btcusd"),
+        assert_eq!(utils::build_url(utils::API_URL, "transactions", "btcusd"),
                    "https://www.bitstamp.net/api/v2/transactions/btcusd/");
     }
+    #[test]
+    fn build_url_should_return_the_url_of_account_endpoints() {
+        assert_eq!(utils::build_url(utils::API_URL, "balance", ""),
+                   "https://www.bitstamp.net/api/v2/balance/");
+    }
 
     #[test]
     fn fail_with_invalid_creds() {
@@ -36,14 +43,14 @@ mod bitstamp_tests {
     #[test]
     fn can_get_real_bitstamp_tick() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         api.ticker(Pair::BTC_USD).unwrap();
     }
 
     #[test]
     fn ticker_should_have_the_correct_last() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         let result = api.ticker(Pair::BTC_USD);
         assert_ne!(result.unwrap().last_trade_price,
                    BigDecimal::from_str("0.0").unwrap());
@@ -51,7 +58,7 @@ mod bitstamp_tests {
     #[test]
     fn ticker_should_have_the_correct_high() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         let result = api.ticker(Pair::BTC_USD);
         assert_ne!(result.unwrap().highest_bid,
                    BigDecimal::from_str("0.0").unwrap());
@@ -59,7 +66,7 @@ mod bitstamp_tests {
     #[test]
     fn ticker_should_have_the_correct_low() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         let result = api.ticker(Pair::BTC_USD);
         assert_ne!(result.unwrap().lowest_ask,
                    BigDecimal::from_str("0.0").unwrap());
@@ -67,7 +74,7 @@ mod bitstamp_tests {
     #[test]
     fn ticker_should_have_the_correct_volume() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         let result = api.ticker(Pair::BTC_USD);
         assert_ne!(result.unwrap().volume.unwrap(),
                    BigDecimal::from_str("0.0").unwrap());
@@ -76,7 +83,7 @@ mod bitstamp_tests {
     #[test]
     fn should_return_an_order_book() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         let result = api.return_order_book(Pair::BTC_USD);
         assert_eq!(result.is_ok(), true);
     }
@@ -84,21 +91,21 @@ mod bitstamp_tests {
     #[test]
     fn order_book_should_have_a_timestamp() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         let result = api.return_order_book(Pair::BTC_USD);
         assert!(result.unwrap().contains_key("timestamp"));
     }
     #[test]
     fn order_book_should_have_bids() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         let result = api.return_order_book(Pair::BTC_USD);
         assert!(result.unwrap().contains_key("bids"));
     }
     #[test]
     fn order_book_should_have_asks() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         let result = api.return_order_book(Pair::BTC_USD);
         assert!(result.unwrap().contains_key("bids"));
     }
@@ -106,7 +113,7 @@ mod bitstamp_tests {
     #[test]
     fn order_book_should_have_asks_for_btcusd() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         assert!(api.return_order_book(Pair::BTC_USD)
                     .unwrap()
                     .contains_key("asks"));
@@ -114,7 +121,7 @@ mod bitstamp_tests {
     #[test]
     fn order_book_should_have_asks_for_btceur() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         assert!(api.return_order_book(Pair::BTC_USD)
                     .unwrap()
                     .contains_key("asks"));
@@ -143,7 +150,7 @@ mod bitstamp_tests {
     #[test]
     fn should_return_the_trade_history_for_btc_usd() {
         let creds = BitstampCreds::new("", "", "", "");
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         let result = api.return_trade_history(Pair::BTC_USD);
 
         assert_eq!(result.is_ok(), false);
@@ -156,7 +163,7 @@ mod bitstamp_tests {
         use std::path::PathBuf;
         let path = PathBuf::from("./let buf = futures.json");
         let creds = BitstampCreds::new_from_file("account_bitstamp", path).unwrap();
-        let mut api = BitstampApi::new(creds).unwrap();
+        let api = BitstampApi::new(creds).unwrap();
         let result = api.return_balances().unwrap();
         let result_looking_for_usd = result.clone();
         let result_looking_for_btc = result.clone();
@@ -1,3 +1,5 @@
+#![cfg(feature = "bittrex")]
+
 #[cfg(test)]
 mod bittrex_tests {
     extern crate coinnect;
@@ -7,22 +9,21 @@ mod bittrex_tests {
     #[test]
     fn get_markets_should_return_a_result() {
         let creds = BittrexCreds::new("bittrex", "", "");
-        let mut api = BittrexApi::new(creds).unwrap();
+        let api = BittrexApi::new(creds).unwrap();
 
         let result = api.get_markets().unwrap();
 
-        assert!(result.contains_key("success"))
+        assert!(result.is_array())
     }
 
     #[test]
     fn get_ticker_should_return_a_ticker() {
         let creds = BittrexCreds::new("bittrex", "", "");
-        let mut api = BittrexApi::new(creds).unwrap();
+        let api = BittrexApi::new(creds).unwrap();
 
-        let result = api.get_ticker("BTC-LTC").unwrap();
+        let result = api.get_ticker("LTC-BTC").unwrap();
 
-        let last_price = result.get("result").unwrap().as_object().unwrap().get("Last");
-        assert!(last_price.is_some())
+        assert!(result["lastTradeRate"].is_string())
     }
 
     /// IMPORTANT: Real keys are needed in order to retrieve the balances
@@ -32,10 +33,10 @@ mod bittrex_tests {
         use std::path::PathBuf;
         let path = PathBuf::from("./keys_real.json");
         let creds = BittrexCreds::new_from_file("account_bittrex", path).unwrap();
-        let mut api = BittrexApi::new(creds).unwrap();
+        let api = BittrexApi::new(creds).unwrap();
 
         let result = api.get_balances().unwrap();
 
-        assert!(result.get("result").is_some())
+        assert!(result.is_array())
     }
 }
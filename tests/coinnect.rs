@@ -1,3 +1,5 @@
+#![cfg(all(feature = "bitstamp", feature = "bittrex", feature = "kraken", feature = "poloniex"))]
+
 #[cfg(test)]
 mod coinnect_tests {
     extern crate coinnect;
@@ -41,7 +43,7 @@ mod coinnect_tests {
     #[test]
     fn coinnect_can_get_a_ticker_from_bitstamp() {
         let creds = BitstampCreds::new("test", "bs_api_key", "bs_api_secret", "bs_cust_id");
-        let mut api = coinnect_rt::new(Exchange::Bitstamp, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Bitstamp, creds).unwrap();
         let ticker = api.ticker(Pair::BTC_USD);
 
         assert_ne!(ticker.unwrap().last_trade_price, BigDecimal::from_str("0.0").unwrap());
@@ -50,7 +52,7 @@ mod coinnect_tests {
     #[test]
     fn coinnect_can_get_a_ticker_from_kraken() {
         let creds = KrakenCreds::new("test", "api_key", "api_secret");
-        let mut api = coinnect_rt::new(Exchange::Kraken, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Kraken, creds).unwrap();
         let ticker = api.ticker(Pair::BTC_EUR);
 
         assert_ne!(ticker.unwrap().last_trade_price, BigDecimal::from_str("0.0").unwrap());
@@ -59,7 +61,7 @@ mod coinnect_tests {
     #[test]
     fn coinnect_can_get_a_ticker_from_poloniex() {
         let creds = PoloniexCreds::new("test", "api_key", "api_secret");
-        let mut api = coinnect_rt::new(Exchange::Poloniex, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Poloniex, creds).unwrap();
         let ticker = api.ticker(Pair::ETH_BTC);
 
         assert_ne!(ticker.unwrap().last_trade_price, BigDecimal::from_str("0.0").unwrap());
@@ -68,7 +70,7 @@ mod coinnect_tests {
     #[test]
     fn coinnect_can_get_a_ticker_from_bittrex() {
         let creds = BittrexCreds::new("test", "api_key", "api_secret");
-        let mut api = coinnect_rt::new(Exchange::Bittrex, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Bittrex, creds).unwrap();
         let ticker = api.ticker(Pair::ETH_BTC);
 
         assert_ne!(ticker.unwrap().last_trade_price, BigDecimal::from_str("0.0").unwrap());
@@ -77,7 +79,7 @@ mod coinnect_tests {
     #[test]
     fn coinnect_can_get_an_orderbook_from_bitstamp() {
         let creds = BitstampCreds::new("test", "api_key", "api_secret", "customer_id");
-        let mut api = coinnect_rt::new(Exchange::Bitstamp, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Bitstamp, creds).unwrap();
         let orderbook = api.orderbook(Pair::BTC_EUR);
 
         assert_ne!(orderbook.unwrap().avg_price().unwrap(), BigDecimal::from_str("0.0").unwrap())
@@ -86,7 +88,7 @@ mod coinnect_tests {
     #[test]
     fn coinnect_can_get_an_orderbook_from_kraken() {
         let creds = KrakenCreds::new("test", "api_key", "api_secret");
-        let mut api = coinnect_rt::new(Exchange::Kraken, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Kraken, creds).unwrap();
         let orderbook = api.orderbook(Pair::BTC_EUR);
 
         assert_ne!(orderbook.unwrap().avg_price().unwrap(), BigDecimal::from_str("0.0").unwrap())
@@ -95,7 +97,7 @@ mod coinnect_tests {
     #[test]
     fn coinnect_can_get_an_orderbook_from_poloniex() {
         let creds = PoloniexCreds::new("test", "api_key", "api_secret");
-        let mut api = coinnect_rt::new(Exchange::Poloniex, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Poloniex, creds).unwrap();
         let orderbook = api.orderbook(Pair::ETH_BTC);
 
         assert_ne!(orderbook.unwrap().avg_price().unwrap(), BigDecimal::from_str("0.0").unwrap())
@@ -104,7 +106,7 @@ mod coinnect_tests {
     #[test]
     fn coinnect_can_get_an_orderbook_from_bittrex() {
         let creds = BittrexCreds::new("test", "api_key", "api_secret");
-        let mut api = coinnect_rt::new(Exchange::Bittrex, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Bittrex, creds).unwrap();
         let orderbook = api.orderbook(Pair::ETH_BTC);
 
         assert_ne!(orderbook.unwrap().avg_price().unwrap(), BigDecimal::from_str("0.0").unwrap())
@@ -114,7 +116,7 @@ mod coinnect_tests {
     #[cfg_attr(not(feature = "bitstamp_private_tests"), ignore)]
     fn coinnect_can_get_the_balances_from_bitstamp() {
         let path = PathBuf::from("./keys_real.json");
-        let mut api = coinnect_rt::new_from_file(Exchange::Bitstamp, "account_bitstamp", path)
+        let api = coinnect_rt::new_from_file(Exchange::Bitstamp, "account_bitstamp", path)
             .unwrap();
         let balances: Balances = api.balances().unwrap();
 
@@ -125,7 +127,7 @@ mod coinnect_tests {
     #[cfg_attr(not(feature = "poloniex_private_tests"), ignore)]
     fn coinnect_can_get_the_balances_from_poloniex() {
         let path = PathBuf::from("./keys_real.json");
-        let mut api = coinnect_rt::new_from_file(Exchange::Poloniex, "account_poloniex", path)
+        let api = coinnect_rt::new_from_file(Exchange::Poloniex, "account_poloniex", path)
             .unwrap();
         let balances: Balances = api.balances().unwrap();
 
@@ -136,7 +138,7 @@ mod coinnect_tests {
     #[cfg_attr(not(feature = "bitstamp_private_tests"), ignore)]
     fn coinnect_can_get_at_least_a_positive_balance_from_bitstamp() {
         let path = PathBuf::from("./keys_real.json");
-        let mut api = coinnect_rt::new_from_file(Exchange::Bitstamp, "account_bitstamp", path)
+        let api = coinnect_rt::new_from_file(Exchange::Bitstamp, "account_bitstamp", path)
             .unwrap();
         let balances: Balances = api.balances().unwrap();
 
@@ -150,7 +152,7 @@ mod coinnect_tests {
     #[cfg_attr(not(feature = "kraken_private_tests"), ignore)]
     fn coinnect_can_get_the_balances_from_kraken() {
         let path = PathBuf::from("./keys_real.json");
-        let mut api = coinnect_rt::new_from_file(Exchange::Kraken, "account_kraken", path)
+        let api = coinnect_rt::new_from_file(Exchange::Kraken, "account_kraken", path)
             .unwrap();
         let balances: Balances = api.balances().unwrap();
 
@@ -162,7 +164,7 @@ mod coinnect_tests {
     #[cfg_attr(not(feature = "poloniex_private_tests"), ignore)]
     fn coinnect_can_get_at_least_a_positive_balance_from_poloniex() {
         let path = PathBuf::from("./keys_real.json");
-        let mut api = coinnect_rt::new_from_file(Exchange::Poloniex, "account_poloniex", path)
+        let api = coinnect_rt::new_from_file(Exchange::Poloniex, "account_poloniex", path)
             .unwrap();
         let balances: Balances = api.balances().unwrap();
         let mut is_positive = false;
@@ -179,7 +181,7 @@ mod coinnect_tests {
     #[cfg_attr(not(feature = "bittrex_private_tests"), ignore)]
     fn coinnect_can_get_at_least_a_positive_balance_from_bittrex() {
         let path = PathBuf::from("./keys_real.json");
-        let mut api = coinnect_rt::new_from_file(Exchange::Bittrex, "account_bittrex", path)
+        let api = coinnect_rt::new_from_file(Exchange::Bittrex, "account_bittrex", path)
             .unwrap();
         let balances: Balances = api.balances().unwrap();
         let mut is_positive = false;
@@ -197,7 +199,7 @@ mod coinnect_tests {
     fn coinnect_can_add_order_from_kraken() {
         let path = PathBuf::from("./keys_real.json");
         let creds = KrakenCreds::new_from_file("account_kraken", path).unwrap();
-        let mut api = coinnect_rt::new(Exchange::Kraken, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Kraken, creds).unwrap();
         // following request should return an error since Kraken minimum order size is BigDecimal::from_str("0.01")?
         let orderinfo = api.add_order(OrderType::BuyLimit,
                                       Pair::BTC_EUR,
@@ -205,7 +207,7 @@ mod coinnect_tests {
                                       Some(BigDecimal::from_str("1000.58").unwrap()));
 
         assert_eq!(orderinfo.unwrap_err().to_string(),
-                   ErrorKind::InsufficientOrderSize.to_string())
+                   Error::InsufficientOrderSize.to_string())
     }
 
     #[test]
@@ -213,7 +215,7 @@ mod coinnect_tests {
     fn coinnect_can_add_order_from_poloniex() {
         let path = PathBuf::from("./keys_real.json");
         let creds = PoloniexCreds::new_from_file("account_poloniex", path).unwrap();
-        let mut api = coinnect_rt::new(Exchange::Poloniex, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Poloniex, creds).unwrap();
         // following request should return an error
         let orderinfo = api.add_order(OrderType::BuyLimit,
                                       Pair::ETH_BTC,
@@ -221,7 +223,7 @@ mod coinnect_tests {
                                       Some(BigDecimal::from_str("1000.58").unwrap()));
 
         assert_eq!(orderinfo.unwrap_err().to_string(),
-                   ErrorKind::InsufficientOrderSize.to_string())
+                   Error::InsufficientOrderSize.to_string())
     }
 
     #[test]
@@ -229,7 +231,7 @@ mod coinnect_tests {
     fn coinnect_can_add_order_from_bitstamp() {
         let path = PathBuf::from("./keys_real.json");
         let creds = BitstampCreds::new_from_file("account_bitstamp", path).unwrap();
-        let mut api = coinnect_rt::new(Exchange::Bitstamp, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Bitstamp, creds).unwrap();
         // following request should return an error
         let orderinfo = api.add_order(OrderType::BuyLimit,
                                       Pair::EUR_USD,
@@ -237,7 +239,7 @@ mod coinnect_tests {
                                       Some(BigDecimal::from_str("1000.58").unwrap()));
 
         assert_eq!(orderinfo.unwrap_err().to_string(),
-                   ErrorKind::InsufficientOrderSize.to_string())
+                   Error::InsufficientOrderSize.to_string())
     }
 
     #[test]
@@ -245,7 +247,7 @@ mod coinnect_tests {
     fn coinnect_can_add_order_from_bittrex() {
         let path = PathBuf::from("./keys_real.json");
         let creds = BittrexCreds::new_from_file("account_bittrex", path).unwrap();
-        let mut api = coinnect_rt::new(Exchange::Bittrex, creds).unwrap();
+        let api = coinnect_rt::new(Exchange::Bittrex, creds).unwrap();
         // following request should return an error
         let orderinfo = api.add_order(OrderType::BuyLimit,
                                       Pair::ETH_BTC,
@@ -253,6 +255,6 @@ mod coinnect_tests {
                                       Some(BigDecimal::from_str("1000.58").unwrap()));
 
         assert_eq!(orderinfo.unwrap_err().to_string(),
-                   ErrorKind::InsufficientOrderSize.to_string())
+                   Error::InsufficientOrderSize.to_string())
     }
 }
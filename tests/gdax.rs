@@ -1,3 +1,5 @@
+#![cfg(all(feature = "bitstamp", feature = "gdax"))]
+
 #[cfg(test)]
 mod gdax_tests {
     extern crate coinnect;
@@ -15,12 +17,12 @@ mod gdax_tests {
 
     #[test]
     fn build_url_should_return_the_a_url() {
-        assert_eq!(utils::build_url("ticker", "btc-usd"),
+        assert_eq!(utils::build_url(utils::PRODUCTION_URL, "ticker", "btc-usd"),
                    "https://api.gdax.com/products/btc-usd/ticker");
     }
     #[test]
     fn build_url_should_return_the_url_for_transactions_for_btc_usd() {
-        assert_eq!(utils::build_url("transactions", "btc-usd"),
+        assert_eq!(utils::build_url(utils::PRODUCTION_URL, "transactions", "btc-usd"),
                    "https://api.gdax.com/accounts/btc-usd/ledger");
     }
 
@@ -36,14 +38,14 @@ mod gdax_tests {
     #[test]
     fn can_get_real_gdax_tick() {
         let creds = GdaxCreds::new("", "", "", "");
-        let mut api = GdaxApi::new(creds).unwrap();
+        let api = GdaxApi::new(creds).unwrap();
         api.ticker(Pair::BTC_USD).unwrap();
     }
 
     #[test]
     fn ticker_should_have_the_correct_last() {
         let creds = GdaxCreds::new("", "", "", "");
-        let mut api = GdaxApi::new(creds).unwrap();
+        let api = GdaxApi::new(creds).unwrap();
         let result = api.ticker(Pair::BTC_USD);
         assert_ne!(result.unwrap().last_trade_price,
                    BigDecimal::from_str("0.0").unwrap());
@@ -51,7 +53,7 @@ mod gdax_tests {
     #[test]
     fn ticker_should_have_the_correct_high() {
         let creds = GdaxCreds::new("", "", "", "");
-        let mut api = GdaxApi::new(creds).unwrap();
+        let api = GdaxApi::new(creds).unwrap();
         let result = api.ticker(Pair::BTC_USD);
         assert_ne!(result.unwrap().highest_bid,
                    BigDecimal::from_str("0.0").unwrap());
@@ -59,7 +61,7 @@ mod gdax_tests {
     #[test]
     fn ticker_should_have_the_correct_low() {
         let creds = GdaxCreds::new("", "", "", "");
-        let mut api = GdaxApi::new(creds).unwrap();
+        let api = GdaxApi::new(creds).unwrap();
         let result = api.ticker(Pair::BTC_USD);
         assert_ne!(result.unwrap().lowest_ask,
                    BigDecimal::from_str("0.0").unwrap());
@@ -67,7 +69,7 @@ mod gdax_tests {
     #[test]
     fn ticker_should_have_the_correct_volume() {
         let creds = GdaxCreds::new("", "", "", "");
-        let mut api = GdaxApi::new(creds).unwrap();
+        let api = GdaxApi::new(creds).unwrap();
         let result = api.ticker(Pair::BTC_USD);
         assert_ne!(result.unwrap().volume.unwrap(),
                    BigDecimal::from_str("0.0").unwrap());
@@ -76,7 +78,7 @@ mod gdax_tests {
     #[test]
     fn should_return_an_order_book() {
         let creds = GdaxCreds::new("", "", "", "");
-        let mut api = GdaxApi::new(creds).unwrap();
+        let api = GdaxApi::new(creds).unwrap();
         let result = api.return_order_book(Pair::BTC_USD);
         assert_eq!(result.is_ok(), true);
     }
@@ -84,14 +86,14 @@ mod gdax_tests {
     #[test]
     fn order_book_should_have_a_timestamp() {
         let creds = GdaxCreds::new("", "", "", "");
-        let mut api = GdaxApi::new(creds).unwrap();
+        let api = GdaxApi::new(creds).unwrap();
         let result = api.return_order_book(Pair::BTC_USD);
         assert!(result.unwrap().contains_key("sequence"));
     }
     #[test]
     fn order_book_should_have_asks_for_btcusd() {
         let creds = GdaxCreds::new("", "", "", "");
-        let mut api = GdaxApi::new(creds).unwrap();
+        let api = GdaxApi::new(creds).unwrap();
         assert!(api.return_order_book(Pair::BTC_USD)
                     .unwrap()
                     .contains_key("asks"));
@@ -99,7 +101,7 @@ mod gdax_tests {
     #[test]
     fn order_book_should_have_asks_for_btceur() {
         let creds = GdaxCreds::new("", "", "", "");
-        let mut api = GdaxApi::new(creds).unwrap();
+        let api = GdaxApi::new(creds).unwrap();
         assert!(api.return_order_book(Pair::BTC_USD)
                     .unwrap()
                     .contains_key("asks"));
@@ -113,18 +115,15 @@ mod gdax_tests {
     fn should_create_a_nonce_bigger_than_2017() {
         assert!(utils::generate_nonce(None).parse::<i64>().unwrap() > 1483228800);
     }
-//    #[test]
-//    fn should_create_a_correct_signature() {
-//        let nonce = "1483228800";
-//        let passphrase = "123456";
-//        let api_key = "1234567890ABCDEF1234567890ABCDEF";
-//        let api_secret = "1234567890ABCDEF1234567890ABCDEF";
-//        let expected_signature = "7D7C4168D49CBC2620A45EF00EAA228C1287561F1C1F94172272E1231A8ADF6B"
-//            .to_string();
-//        assert_eq!(utils::build_signature(nonce, passphrase, api_key, api_secret).unwrap(),
-//                   expected_signature);
-//    }
-//
+    #[test]
+    fn should_create_a_correct_signature() {
+        let timestamp = "1483228800";
+        let api_secret = "MTIzNDU2Nzg5MEFCQ0RFRjEyMzQ1Njc4OTBBQkNERUY=";
+        let expected_signature = "WzWgH5vgizsx67H0TDQd8nADYnUPdu0kqUUoCBmEPKA=".to_string();
+        assert_eq!(utils::build_signature(timestamp, "POST", "/orders", r#"{"size":"0.01"}"#, api_secret).unwrap(),
+                   expected_signature);
+    }
+
 //    #[test]
 //    fn should_return_the_trade_history_for_btc_usd() {
 //        let creds = GdaxCreds::new("", "", "", "");
@@ -1,3 +1,5 @@
+#![cfg(all(feature = "bitstamp", feature = "kraken"))]
+
 #[cfg(test)]
 mod kraken_tests {
     extern crate coinnect;
@@ -20,7 +22,7 @@ mod kraken_tests {
         use std::path::PathBuf;
         let path = PathBuf::from("./keys_real.json");
         let creds = KrakenCreds::new_from_file("account_kraken", path).unwrap();
-        let mut api = KrakenApi::new(creds).unwrap();
+        let api = KrakenApi::new(creds).unwrap();
 
         let result = api.get_account_balance().unwrap();
 
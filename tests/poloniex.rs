@@ -1,3 +1,5 @@
+#![cfg(all(feature = "bitstamp", feature = "poloniex"))]
+
 #[cfg(test)]
 mod poloniex_tests {
     extern crate coinnect;
@@ -20,7 +22,7 @@ mod poloniex_tests {
         use std::path::PathBuf;
         let path = PathBuf::from("./keys_real.json");
         let creds = PoloniexCreds::new_from_file("account_poloniex", path).unwrap();
-        let mut api = PoloniexApi::new(creds).unwrap();
+        let api = PoloniexApi::new(creds).unwrap();
         let result = api.return_balances();
 
         assert!(result.unwrap().contains_key("BTC"));